@@ -0,0 +1,210 @@
+use chat_server::{
+    protocol::{ClientMessage, ServerMessage},
+    websocket::{self, close_code, Message},
+};
+use clap::Parser;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::{
+    io::{Error, ErrorKind},
+    process::ExitCode,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    sync::mpsc,
+    time,
+};
+
+/// How long to wait for the server to answer the close frame when quitting.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+const HELP: &str = "\
+* /nick <nick>   change your nick
+* /join <room>   leave the room for another one
+* /who           list the people in the room
+* /rooms         list the rooms
+* /quit          leave the chat, like Ctrl+D
+* Other lines are said to the room, with // for lines that start with /.";
+
+/// Chats in a room of the chat server from the terminal. Lines are said to the room, and
+/// commands like `/join rust` start with a slash.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The WebSocket URL of the server
+    #[arg(long, env = "CHAT_URL", default_value = "ws://127.0.0.1:8080/ws")]
+    url: String,
+    /// The nick to chat with, which is one like `guest3` if not given
+    #[arg(short, long, env = "CHAT_NICK")]
+    nick: Option<String>,
+    /// The room to join first, `lobby` if not given
+    #[arg(short, long)]
+    room: Option<String>,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    match run(Args::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+enum Input {
+    Send(ClientMessage),
+    Help,
+    Quit,
+}
+
+async fn run(args: Args) -> Result<(), Error> {
+    let mut url = args.url.clone();
+    let params = [("nick", &args.nick), ("room", &args.room)];
+    for (name, value) in params {
+        if let Some(value) = value {
+            let separator = if url.contains('?') { '&' } else { '?' };
+            let value = utf8_percent_encode(value.trim_start_matches('#'), NON_ALPHANUMERIC);
+            url.push_str(&format!("{}{}={}", separator, name, value));
+        }
+    }
+    let (mut reader, mut writer) = websocket::connect(&url).await?;
+    // Reading isn't cancel safe, so it goes on in a task of its own while lines are typed.
+    let (sender, mut incoming) = mpsc::channel(16);
+    tokio::spawn(async move {
+        loop {
+            let message = reader.read_message().await;
+            let is_last = !matches!(message, Ok(Some(_)));
+            if sender.send(message).await.is_err() || is_last {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                // The end of stdin, like Ctrl+D, quits too.
+                let input = match line? {
+                    Some(line) => parse_line(&line),
+                    None => Ok(Some(Input::Quit)),
+                };
+                match input {
+                    Ok(Some(Input::Send(message))) => {
+                        let json = serde_json::to_string(&message).map_err(Error::other)?;
+                        writer.write_message(&Message::Text(json)).await?;
+                    }
+                    Ok(Some(Input::Help)) => println!("{}", HELP),
+                    Ok(Some(Input::Quit)) => {
+                        let close = Message::Close(Some((close_code::NORMAL, String::new())));
+                        writer.write_message(&close).await?;
+                        // What was said before the server got the close frame is still shown.
+                        let _ = time::timeout(CLOSE_TIMEOUT, async {
+                            while let Some(Ok(Some(message))) = incoming.recv().await {
+                                if !show(message)? {
+                                    break;
+                                }
+                            }
+                            Ok::<_, Error>(())
+                        })
+                        .await;
+                        return Ok(());
+                    }
+                    Ok(None) => {}
+                    Err(message) => println!("! {}", message),
+                }
+            }
+            message = incoming.recv() => match message {
+                Some(Ok(Some(Message::Ping(data)))) => {
+                    writer.write_message(&Message::Pong(data)).await?;
+                }
+                Some(Ok(Some(message))) => {
+                    if !show(message)? {
+                        return Err(Error::new(ErrorKind::ConnectionAborted, "the server closed the connection"));
+                    }
+                }
+                Some(Err(error)) => return Err(error),
+                Some(Ok(None)) | None => {
+                    return Err(Error::new(ErrorKind::ConnectionAborted, "the server closed the connection"));
+                }
+            },
+        }
+    }
+}
+
+/// A line typed by the user, `None` for blank ones.
+fn parse_line(line: &str) -> Result<Option<Input>, String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let Some(command) = line.strip_prefix('/').filter(|rest| !rest.starts_with('/')) else {
+        // `//` says a line that starts with a slash.
+        let text = line.strip_prefix('/').unwrap_or(line).to_string();
+        return Ok(Some(Input::Send(ClientMessage::Say { text })));
+    };
+    let (name, argument) = command.split_once(' ').unwrap_or((command, ""));
+    let argument = argument.trim();
+    let input = match (name, argument) {
+        ("nick", nick) if !nick.is_empty() => Input::Send(ClientMessage::Nick {
+            nick: nick.to_string(),
+        }),
+        ("join", room) if !room.is_empty() => Input::Send(ClientMessage::Join {
+            room: room.trim_start_matches('#').to_string(),
+        }),
+        ("who", "") => Input::Send(ClientMessage::Members),
+        ("rooms", "") => Input::Send(ClientMessage::Rooms),
+        ("help", "") => Input::Help,
+        ("quit", "") => Input::Quit,
+        ("nick" | "join", _) => return Err(format!("/{} needs an argument", name)),
+        ("who" | "rooms" | "help" | "quit", _) => {
+            return Err(format!("/{} takes no argument", name))
+        }
+        _ => return Err(format!("unknown command '/{}', /help lists them", name)),
+    };
+    Ok(Some(input))
+}
+
+/// Prints a message of the server, returning whether the connection goes on.
+fn show(message: Message) -> Result<bool, Error> {
+    let text = match message {
+        Message::Text(text) => text,
+        Message::Close(Some((code, reason))) if code != close_code::NORMAL => {
+            return Err(Error::new(ErrorKind::ConnectionAborted, reason));
+        }
+        Message::Close(_) => return Ok(false),
+        Message::Binary(_) | Message::Ping(_) | Message::Pong(_) => return Ok(true),
+    };
+    let message: ServerMessage =
+        serde_json::from_str(&text).map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+    let line = match message {
+        ServerMessage::Welcome { nick, room } => {
+            format!(
+                "* Connected as {} in #{}, /help lists the commands",
+                nick, room
+            )
+        }
+        ServerMessage::Message { nick, text, .. } => format!("<{}> {}", nick, text),
+        ServerMessage::Joined { room, nick } => format!("* {} joined #{}", nick, room),
+        ServerMessage::Left { room, nick } => format!("* {} left #{}", nick, room),
+        ServerMessage::Renamed { from, to, .. } => format!("* {} is now known as {}", from, to),
+        ServerMessage::Members { room, nicks } => format!("* In #{}: {}", room, nicks.join(", ")),
+        ServerMessage::Rooms { rooms } => {
+            let rooms: Vec<String> = rooms
+                .iter()
+                .map(|room| format!("#{} ({})", room.name, room.members))
+                .collect();
+            format!("* Rooms: {}", rooms.join(", "))
+        }
+        ServerMessage::Error { message } => format!("! {}", message),
+    };
+    // Others could move the cursor or change colors with escape sequences otherwise.
+    let line: String = line
+        .chars()
+        .map(|c| if c.is_control() { '\u{fffd}' } else { c })
+        .collect();
+    println!("{}", line);
+    Ok(true)
+}
@@ -0,0 +1,231 @@
+//! The nicks and rooms of the chat. Every room has a broadcast channel that everything said in
+//! it goes through, which the connections of its members are subscribed to.
+
+use crate::protocol::{validate_nick, validate_room, RoomSummary, ServerMessage, MAX_TEXT_LEN};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+use tokio::sync::broadcast;
+
+/// How many messages a room keeps for members that are slow to read them, after which they
+/// miss the oldest ones.
+const ROOM_CAPACITY: usize = 256;
+
+pub const DEFAULT_ROOM: &str = "lobby";
+
+#[derive(Default)]
+pub struct Chat {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// In lowercase, since nicks that only differ in case would be easy to mix up.
+    nicks: HashSet<String>,
+    rooms: BTreeMap<String, Room>,
+    guest_count: u64,
+}
+
+struct Room {
+    members: BTreeSet<String>,
+    sender: broadcast::Sender<ServerMessage>,
+}
+
+impl Room {
+    /// Sends a message to the members, which can't fail while the room has any, since their
+    /// connections are subscribed.
+    fn broadcast(&self, message: ServerMessage) {
+        let _ = self.sender.send(message);
+    }
+}
+
+impl Chat {
+    pub fn new() -> Self {
+        Chat::default()
+    }
+
+    /// Takes a nick, or a nick like `guest3` if none is given, for a new connection.
+    pub fn claim_nick(&self, nick: Option<&str>) -> Result<String, String> {
+        let mut state = self.lock();
+        let Some(nick) = nick else {
+            loop {
+                state.guest_count += 1;
+                let guest = format!("guest{}", state.guest_count);
+                if state.nicks.insert(guest.clone()) {
+                    return Ok(guest);
+                }
+            }
+        };
+        validate_nick(nick)?;
+        if !state.nicks.insert(nick.to_lowercase()) {
+            return Err(format!("the nick '{}' is taken", nick));
+        }
+        Ok(nick.to_string())
+    }
+
+    pub fn release_nick(&self, nick: &str) {
+        self.lock().nicks.remove(&nick.to_lowercase());
+    }
+
+    /// Changes the nick of a member of `room`, telling the others.
+    pub fn rename(&self, room: &str, from: &str, to: &str) -> Result<(), String> {
+        validate_nick(to)?;
+        let mut state = self.lock();
+        // Changing just the case of a nick keeps it.
+        if from.to_lowercase() != to.to_lowercase() {
+            if !state.nicks.insert(to.to_lowercase()) {
+                return Err(format!("the nick '{}' is taken", to));
+            }
+            state.nicks.remove(&from.to_lowercase());
+        }
+        if let Some(entry) = state.rooms.get_mut(room) {
+            entry.members.remove(from);
+            entry.members.insert(to.to_string());
+            entry.broadcast(ServerMessage::Renamed {
+                room: room.to_string(),
+                from: from.to_string(),
+                to: to.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds a nick to a room, which is made if nobody is in it, returning the name of the
+    /// room, in lowercase, and what's sent to its members from now on.
+    pub fn join(
+        &self,
+        room: &str,
+        nick: &str,
+    ) -> Result<(String, broadcast::Receiver<ServerMessage>), String> {
+        validate_room(room)?;
+        let name = room.to_lowercase();
+        let mut state = self.lock();
+        let room = state.rooms.entry(name.clone()).or_insert_with(|| Room {
+            members: BTreeSet::new(),
+            sender: broadcast::channel(ROOM_CAPACITY).0,
+        });
+        room.members.insert(nick.to_string());
+        let receiver = room.sender.subscribe();
+        room.broadcast(ServerMessage::Joined {
+            room: name.clone(),
+            nick: nick.to_string(),
+        });
+        Ok((name, receiver))
+    }
+
+    /// Removes a nick from a room, which is dropped once it's empty.
+    pub fn leave(&self, name: &str, nick: &str) {
+        let mut state = self.lock();
+        let Some(room) = state.rooms.get_mut(name) else {
+            return;
+        };
+        room.members.remove(nick);
+        if room.members.is_empty() {
+            state.rooms.remove(name);
+        } else {
+            room.broadcast(ServerMessage::Left {
+                room: name.to_string(),
+                nick: nick.to_string(),
+            });
+        }
+    }
+
+    pub fn say(&self, name: &str, nick: &str, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("messages can't be empty".to_string());
+        }
+        if text.chars().count() > MAX_TEXT_LEN {
+            return Err(format!(
+                "messages can't be longer than {} characters",
+                MAX_TEXT_LEN
+            ));
+        }
+        let state = self.lock();
+        if let Some(room) = state.rooms.get(name) {
+            room.broadcast(ServerMessage::Message {
+                room: name.to_string(),
+                nick: nick.to_string(),
+                text: text.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn members(&self, name: &str) -> Vec<String> {
+        let state = self.lock();
+        let members = state.rooms.get(name).map(|room| &room.members);
+        members.into_iter().flatten().cloned().collect()
+    }
+
+    pub fn rooms(&self) -> Vec<RoomSummary> {
+        let state = self.lock();
+        let rooms = state.rooms.iter();
+        rooms
+            .map(|(name, room)| RoomSummary {
+                name: name.clone(),
+                members: room.members.len(),
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // Nothing panics while changing the state halfway.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broadcasts_to_rooms() {
+        let chat = Chat::new();
+        assert_eq!(chat.claim_nick(None).unwrap(), "guest1");
+        assert_eq!(chat.claim_nick(Some("alice")).unwrap(), "alice");
+        assert_eq!(
+            chat.claim_nick(Some("Alice")).unwrap_err(),
+            "the nick 'Alice' is taken"
+        );
+
+        let (room, mut alice) = chat.join("Lobby", "alice").unwrap();
+        assert_eq!(room, "lobby");
+        let (_, mut guest) = chat.join("lobby", "guest1").unwrap();
+        chat.say("lobby", "guest1", " hi ").unwrap();
+        chat.rename("lobby", "guest1", "bob").unwrap();
+        assert!(chat.say("lobby", "bob", "  ").is_err());
+        chat.leave("lobby", "bob");
+
+        let joined = |nick: &str| ServerMessage::Joined {
+            room: "lobby".to_string(),
+            nick: nick.to_string(),
+        };
+        assert_eq!(alice.try_recv().unwrap(), joined("alice"));
+        assert_eq!(alice.try_recv().unwrap(), joined("guest1"));
+        assert_eq!(
+            alice.try_recv().unwrap(),
+            ServerMessage::Message {
+                room: "lobby".to_string(),
+                nick: "guest1".to_string(),
+                text: "hi".to_string(),
+            }
+        );
+        assert!(matches!(
+            alice.try_recv().unwrap(),
+            ServerMessage::Renamed { .. }
+        ));
+        assert!(matches!(
+            alice.try_recv().unwrap(),
+            ServerMessage::Left { .. }
+        ));
+        assert_eq!(guest.try_recv().unwrap(), joined("guest1"));
+
+        // The old nick can be taken again, and rooms are dropped once they're empty.
+        assert_eq!(chat.claim_nick(Some("guest1")).unwrap(), "guest1");
+        assert_eq!(chat.members("lobby"), ["alice"]);
+        chat.leave("lobby", "alice");
+        assert!(chat.rooms().is_empty());
+    }
+}
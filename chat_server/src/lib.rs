@@ -0,0 +1,8 @@
+//! A chat server with rooms, which clients connect to with WebSocket, and the parts of the
+//! protocol its terminal client shares with it.
+
+pub mod chat;
+pub mod protocol;
+pub mod server;
+pub mod session;
+pub mod websocket;
@@ -0,0 +1,39 @@
+use chat_server::{chat::Chat, server};
+use clap::Parser;
+use std::{net::IpAddr, sync::Arc};
+use tokio::net::TcpListener;
+use tracing::{error, info};
+use tracing_subscriber::EnvFilter;
+
+/// Serves a chat with rooms over WebSocket, at `/ws`.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The address to listen on
+    #[arg(long, env = "CHAT_BIND", default_value = "127.0.0.1")]
+    bind: IpAddr,
+    #[arg(long, env = "CHAT_PORT", default_value_t = 8080)]
+    port: u16,
+}
+
+#[tokio::main]
+async fn main() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let args = Args::parse();
+
+    let listener = TcpListener::bind((args.bind, args.port))
+        .await
+        .unwrap_or_else(|error| {
+            error!("Cannot listen on {}:{}: {}", args.bind, args.port, error);
+            std::process::exit(1);
+        });
+    info!("Listening on ws://{}/ws", listener.local_addr().unwrap());
+    let app = server::app(Arc::new(Chat::new()));
+    // Upgraded connections aren't waited for, so members are disconnected when it stops.
+    axum::serve(listener, app)
+        .with_graceful_shutdown(common::shutdown::signal())
+        .await
+        .unwrap();
+    info!("Chat server has shut down");
+}
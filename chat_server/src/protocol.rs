@@ -0,0 +1,138 @@
+//! The messages of the chat, sent as JSON in WebSocket text messages, like
+//! `{"type":"say","text":"hello"}`, with the kind of message in `type`.
+
+use serde::{Deserialize, Serialize};
+
+/// The longest message people may say, in characters.
+pub const MAX_TEXT_LEN: usize = 2000;
+
+const MAX_NAME_LEN: usize = 24;
+
+/// What clients send.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Says something to the room.
+    Say {
+        text: String,
+    },
+    /// Changes the nick.
+    Nick {
+        nick: String,
+    },
+    /// Leaves the room for another one, which is made if nobody is in it yet.
+    Join {
+        room: String,
+    },
+    Rooms,
+    Members,
+}
+
+/// What the server sends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// The first message of a connection, with the nick and the room it has.
+    Welcome {
+        nick: String,
+        room: String,
+    },
+    Message {
+        room: String,
+        nick: String,
+        text: String,
+    },
+    Joined {
+        room: String,
+        nick: String,
+    },
+    Left {
+        room: String,
+        nick: String,
+    },
+    Renamed {
+        room: String,
+        from: String,
+        to: String,
+    },
+    /// The nicks of the people in a room, sorted.
+    Members {
+        room: String,
+        nicks: Vec<String>,
+    },
+    /// The rooms there are, sorted by name.
+    Rooms {
+        rooms: Vec<RoomSummary>,
+    },
+    /// A message the client sent was refused, for the reason in `message`.
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomSummary {
+    pub name: String,
+    pub members: usize,
+}
+
+/// Nicks are made of letters, digits, `-` and `_`, so they can't pass for other people in
+/// the messages of clients, like with spaces or the `*` of notices.
+pub fn validate_nick(nick: &str) -> Result<(), String> {
+    validate_name(nick).map_err(|problem| format!("nicks {}", problem))
+}
+
+/// The same as nicks, since rooms are named in URLs and commands.
+pub fn validate_room(room: &str) -> Result<(), String> {
+    validate_name(room).map_err(|problem| format!("room names {}", problem))
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(format!("must have 1 to {} characters", MAX_NAME_LEN));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("may only have letters, digits, '-' and '_'".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_and_writes_json() {
+        let message: ClientMessage = serde_json::from_str(r#"{"type":"say","text":"hi"}"#).unwrap();
+        assert_eq!(
+            message,
+            ClientMessage::Say {
+                text: "hi".to_string()
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<ClientMessage>(r#"{"type":"rooms"}"#).unwrap(),
+            ClientMessage::Rooms
+        );
+        let renamed = ServerMessage::Renamed {
+            room: "lobby".to_string(),
+            from: "guest1".to_string(),
+            to: "alice".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_string(&renamed).unwrap(),
+            r#"{"type":"renamed","room":"lobby","from":"guest1","to":"alice"}"#
+        );
+
+        assert!(validate_nick("alice_2-b").is_ok());
+        assert_eq!(
+            validate_nick("a b").unwrap_err(),
+            "nicks may only have letters, digits, '-' and '_'"
+        );
+        assert!(validate_room("").is_err());
+        assert!(validate_room(&"r".repeat(25)).is_err());
+    }
+}
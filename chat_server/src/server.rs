@@ -0,0 +1,127 @@
+//! The HTTP side of the server: the handshake that turns requests to `/ws` into WebSocket
+//! connections, and a list of the rooms at `/rooms`.
+
+use crate::{
+    chat::{Chat, DEFAULT_ROOM},
+    protocol::{validate_nick, validate_room, RoomSummary},
+    session,
+    websocket::accept_key,
+};
+use axum::{
+    extract::{Query, Request, State},
+    http::{
+        header::{
+            CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE,
+        },
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::debug;
+
+pub fn app(chat: Arc<Chat>) -> Router {
+    Router::new()
+        .route("/ws", get(connect))
+        .route("/rooms", get(rooms))
+        .with_state(chat)
+}
+
+async fn rooms(State(chat): State<Arc<Chat>>) -> Json<Vec<RoomSummary>> {
+    Json(chat.rooms())
+}
+
+#[derive(Deserialize)]
+struct ConnectParams {
+    nick: Option<String>,
+    room: Option<String>,
+}
+
+/// Accepts a WebSocket handshake, like `/ws?nick=alice&room=rust`, and runs the session of
+/// the connection once hyper hands it over. Nicks that are taken are refused before the
+/// upgrade, so clients can tell why.
+async fn connect(
+    State(chat): State<Arc<Chat>>,
+    Query(params): Query<ConnectParams>,
+    mut request: Request,
+) -> Response {
+    let key = match handshake_key(request.headers()) {
+        Ok(key) => key.to_string(),
+        // Telling clients of other versions which one is spoken, as RFC 6455 asks.
+        Err((StatusCode::UPGRADE_REQUIRED, message)) => {
+            let version = [(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"))];
+            return (StatusCode::UPGRADE_REQUIRED, version, message).into_response();
+        }
+        Err(refused) => return refused.into_response(),
+    };
+    let room = params.room.unwrap_or_else(|| DEFAULT_ROOM.to_string());
+    let checked = validate_room(&room).and_then(|()| match &params.nick {
+        Some(nick) => validate_nick(nick),
+        None => Ok(()),
+    });
+    if let Err(message) = checked {
+        return (StatusCode::BAD_REQUEST, message).into_response();
+    }
+    let nick = match chat.claim_nick(params.nick.as_deref()) {
+        Ok(nick) => nick,
+        Err(message) => return (StatusCode::CONFLICT, message).into_response(),
+    };
+
+    let on_upgrade = hyper::upgrade::on(&mut request);
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => session::run(chat, TokioIo::new(upgraded), nick, room).await,
+            Err(error) => {
+                debug!("Cannot upgrade the connection: {}", error);
+                chat.release_nick(&nick);
+            }
+        }
+    });
+    let headers = [
+        (UPGRADE, HeaderValue::from_static("websocket")),
+        (CONNECTION, HeaderValue::from_static("Upgrade")),
+        (SEC_WEBSOCKET_ACCEPT, header_value(&accept_key(&key))),
+    ];
+    (StatusCode::SWITCHING_PROTOCOLS, headers).into_response()
+}
+
+/// The `Sec-WebSocket-Key` of a handshake, or why the request isn't one.
+fn handshake_key(headers: &HeaderMap) -> Result<&str, (StatusCode, &'static str)> {
+    let has_token = |name: HeaderName, token: &str| {
+        let values = headers.get_all(name).into_iter();
+        values
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|value| value.trim().eq_ignore_ascii_case(token))
+    };
+    if !has_token(CONNECTION, "upgrade") || !has_token(UPGRADE, "websocket") {
+        return Err((StatusCode::BAD_REQUEST, "connect with a WebSocket"));
+    }
+    if headers
+        .get(SEC_WEBSOCKET_VERSION)
+        .map(HeaderValue::as_bytes)
+        != Some(b"13")
+    {
+        let message = "only version 13 of WebSocket is supported";
+        return Err((StatusCode::UPGRADE_REQUIRED, message));
+    }
+    let message = "the Sec-WebSocket-Key must be 16 bytes in base64";
+    headers
+        .get(SEC_WEBSOCKET_KEY)
+        .and_then(|key| key.to_str().ok())
+        .filter(|key| {
+            STANDARD
+                .decode(key)
+                .is_ok_and(|decoded| decoded.len() == 16)
+        })
+        .ok_or((StatusCode::BAD_REQUEST, message))
+}
+
+fn header_value(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).expect("accept keys are base64")
+}
@@ -0,0 +1,217 @@
+//! The WebSocket connection of a member of the chat, from joining the first room to leaving.
+//! What the member sends is read in a task of its own, so it can be waited for along with
+//! what's said in the room.
+
+use crate::{
+    chat::Chat,
+    protocol::{ClientMessage, ServerMessage},
+    websocket::{self, close_code, Message, Reader, Role, Writer},
+};
+use std::{
+    io::{Error, ErrorKind},
+    mem,
+    sync::Arc,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{
+        broadcast::{
+            self,
+            error::{RecvError, TryRecvError},
+        },
+        mpsc,
+    },
+};
+use tracing::{debug, info};
+
+type Incoming = mpsc::Receiver<Result<Option<Message>, Error>>;
+
+/// Runs the session of a member with a nick that was claimed already, in a room whose name
+/// is valid, until either side closes the connection.
+pub async fn run<S>(chat: Arc<Chat>, stream: S, nick: String, room: String)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (read_half, write_half) = tokio::io::split(stream);
+    let (sender, mut incoming) = mpsc::channel(16);
+    // Reading isn't cancel safe, as a frame read halfway would be lost, so it's never stopped
+    // to do something else.
+    let reader = tokio::spawn(async move {
+        let mut reader = Reader::new(read_half, Role::Server);
+        loop {
+            let message = reader.read_message().await;
+            let is_last = !matches!(message, Ok(Some(_)));
+            if sender.send(message).await.is_err() || is_last {
+                break;
+            }
+        }
+    });
+    let (room, events) = match chat.join(&room, &nick) {
+        Ok(joined) => joined,
+        Err(message) => {
+            debug!("Cannot join {}: {}", room, message);
+            chat.release_nick(&nick);
+            return;
+        }
+    };
+    info!(%nick, %room, "Connected");
+    let mut session = Session {
+        chat,
+        nick,
+        room,
+        events,
+        writer: Writer::new(write_half, Role::Server),
+    };
+    if let Err(error) = session.run(&mut incoming).await {
+        debug!(nick = %session.nick, "Connection failed: {}", error);
+    }
+    reader.abort();
+    let _ = session.writer.shutdown().await;
+    session.chat.leave(&session.room, &session.nick);
+    session.chat.release_nick(&session.nick);
+    info!(nick = %session.nick, "Disconnected");
+}
+
+struct Session<W> {
+    chat: Arc<Chat>,
+    nick: String,
+    room: String,
+    /// What's said in the room.
+    events: broadcast::Receiver<ServerMessage>,
+    writer: Writer<W>,
+}
+
+impl<W: AsyncWrite + Unpin> Session<W> {
+    async fn run(&mut self, incoming: &mut Incoming) -> Result<(), Error> {
+        let welcome = ServerMessage::Welcome {
+            nick: self.nick.clone(),
+            room: self.room.clone(),
+        };
+        self.send(&welcome).await?;
+        self.send_members().await?;
+        loop {
+            tokio::select! {
+                message = incoming.recv() => {
+                    if !self.receive(message).await? {
+                        return Ok(());
+                    }
+                }
+                event = self.events.recv() => match event {
+                    Ok(event) => self.send(&event).await?,
+                    Err(RecvError::Lagged(count)) => self.send_lagged(count).await?,
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+            }
+        }
+    }
+
+    /// Handles what the reader task read, returning whether the connection goes on.
+    async fn receive(
+        &mut self,
+        message: Option<Result<Option<Message>, Error>>,
+    ) -> Result<bool, Error> {
+        // What was said in the room before the message came is sent first, so answers come
+        // after it, and nothing is lost when the connection is closed.
+        self.flush_events().await?;
+        match message {
+            Some(Ok(Some(Message::Text(text)))) => self.handle(&text).await?,
+            Some(Ok(Some(Message::Binary(_)))) => {
+                self.send_error("binary messages aren't supported").await?;
+            }
+            Some(Ok(Some(Message::Ping(data)))) => {
+                self.writer.write_message(&Message::Pong(data)).await?;
+            }
+            Some(Ok(Some(Message::Pong(_)))) => {}
+            // The close frame of the client is answered, which ends the connection.
+            Some(Ok(Some(Message::Close(_)))) => {
+                let close = Message::Close(Some((close_code::NORMAL, String::new())));
+                self.writer.write_message(&close).await?;
+                return Ok(false);
+            }
+            Some(Ok(None)) | None => return Ok(false),
+            Some(Err(error)) => {
+                if error.kind() == ErrorKind::InvalidData {
+                    let code = websocket::error_close_code(&error);
+                    let close = Message::Close(Some((code, error.to_string())));
+                    let _ = self.writer.write_message(&close).await;
+                }
+                return Err(error);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Sends what was said in the room and wasn't sent yet.
+    async fn flush_events(&mut self) -> Result<(), Error> {
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => self.send(&event).await?,
+                Err(TryRecvError::Lagged(count)) => self.send_lagged(count).await?,
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+
+    async fn send_lagged(&mut self, count: u64) -> Result<(), Error> {
+        let message = format!("missed {} messages, which came too fast", count);
+        self.send_error(&message).await
+    }
+
+    async fn handle(&mut self, text: &str) -> Result<(), Error> {
+        let message = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(error) => {
+                return self
+                    .send_error(&format!("invalid message: {}", error))
+                    .await
+            }
+        };
+        let result = match message {
+            ClientMessage::Say { text } => self.chat.say(&self.room, &self.nick, &text),
+            ClientMessage::Nick { nick } => self
+                .chat
+                .rename(&self.room, &self.nick, &nick)
+                .map(|()| self.nick = nick),
+            ClientMessage::Join { room } if room.to_lowercase() == self.room => {
+                Err(format!("you're in #{} already", self.room))
+            }
+            ClientMessage::Join { room } => match self.chat.join(&room, &self.nick) {
+                Ok((room, events)) => {
+                    // Joining first, so members stay where they are if they can't.
+                    let old_room = mem::replace(&mut self.room, room);
+                    self.events = events;
+                    self.chat.leave(&old_room, &self.nick);
+                    return self.send_members().await;
+                }
+                Err(message) => Err(message),
+            },
+            ClientMessage::Rooms => {
+                let rooms = self.chat.rooms();
+                return self.send(&ServerMessage::Rooms { rooms }).await;
+            }
+            ClientMessage::Members => return self.send_members().await,
+        };
+        match result {
+            Ok(()) => Ok(()),
+            Err(message) => self.send_error(&message).await,
+        }
+    }
+
+    async fn send(&mut self, message: &ServerMessage) -> Result<(), Error> {
+        let json = serde_json::to_string(message).map_err(Error::other)?;
+        self.writer.write_message(&Message::Text(json)).await
+    }
+
+    async fn send_error(&mut self, message: &str) -> Result<(), Error> {
+        let message = message.to_string();
+        self.send(&ServerMessage::Error { message }).await
+    }
+
+    async fn send_members(&mut self) -> Result<(), Error> {
+        let members = ServerMessage::Members {
+            room: self.room.clone(),
+            nicks: self.chat.members(&self.room),
+        };
+        self.send(&members).await
+    }
+}
@@ -0,0 +1,465 @@
+//! The WebSocket protocol of RFC 6455: the handshake that upgrades an HTTP connection, and the
+//! frames messages are sent in afterwards. Both sides are here, the server's and the client's,
+//! which only differ in who masks the frames it sends.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::{
+    digest::{digest, SHA1_FOR_LEGACY_USE_ONLY},
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{
+    fmt,
+    io::{Error, ErrorKind},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+};
+
+/// Appended to the key of the client to make the key the server accepts it with, which shows
+/// the server speaks WebSocket rather than just echoing headers.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The longest message a side reads, so the other one can't make it buffer forever.
+pub const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+/// The headers of the handshake the client and the server send.
+const MAX_HEADERS_LEN: usize = 16 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    /// With the code and the reason of the side that closes the connection, if it gave them.
+    Close(Option<(u16, String)>),
+}
+
+/// Close codes, of the ones in RFC 6455 that are used here.
+pub mod close_code {
+    pub const NORMAL: u16 = 1000;
+    pub const GOING_AWAY: u16 = 1001;
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    pub const TOO_BIG: u16 = 1009;
+}
+
+/// Which side of the connection this is. Clients mask the frames they send, so that proxies
+/// that don't know WebSocket can't be tricked into caching what looks like HTTP.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Server,
+    Client,
+}
+
+/// The key the server answers the `Sec-WebSocket-Key` of the client with, in
+/// `Sec-WebSocket-Accept`.
+pub fn accept_key(key: &str) -> String {
+    let hash = digest(
+        &SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{}{}", key, GUID).as_bytes(),
+    );
+    STANDARD.encode(hash.as_ref())
+}
+
+/// Reads messages, put together from their frames, from one side of a connection.
+pub struct Reader<R> {
+    inner: R,
+    role: Role,
+    /// The opcode and the payload so far of a message sent in several frames, which control
+    /// frames may come between.
+    fragmented: Option<(u8, Vec<u8>)>,
+}
+
+impl<R: AsyncRead + Unpin> Reader<R> {
+    pub fn new(inner: R, role: Role) -> Self {
+        Reader {
+            inner,
+            role,
+            fragmented: None,
+        }
+    }
+
+    /// The next message, or `None` when the connection was closed, without a close frame.
+    /// Errors of the kind `InvalidData` are the other side breaking the protocol.
+    pub async fn read_message(&mut self) -> Result<Option<Message>, Error> {
+        loop {
+            let Some(frame) = self.read_frame().await? else {
+                return match self.fragmented {
+                    Some(_) => Err(ErrorKind::UnexpectedEof.into()),
+                    None => Ok(None),
+                };
+            };
+            let message = match frame.opcode {
+                opcode::CLOSE => Message::Close(close_payload(&frame.payload)?),
+                opcode::PING => Message::Ping(frame.payload),
+                opcode::PONG => Message::Pong(frame.payload),
+                opcode::TEXT | opcode::BINARY if self.fragmented.is_some() => {
+                    return Err(protocol_error("a message started inside another"));
+                }
+                opcode::TEXT | opcode::BINARY if !frame.fin => {
+                    self.fragmented = Some((frame.opcode, frame.payload));
+                    continue;
+                }
+                opcode::TEXT | opcode::BINARY => data_message(frame.opcode, frame.payload)?,
+                opcode::CONTINUATION => {
+                    let Some((_, payload)) = &mut self.fragmented else {
+                        return Err(protocol_error("a continuation without a message"));
+                    };
+                    if payload.len() + frame.payload.len() > MAX_MESSAGE_LEN {
+                        return Err(too_big());
+                    }
+                    payload.extend_from_slice(&frame.payload);
+                    if !frame.fin {
+                        continue;
+                    }
+                    let (opcode, payload) = self.fragmented.take().unwrap();
+                    data_message(opcode, payload)?
+                }
+                opcode => return Err(protocol_error(&format!("unknown opcode {}", opcode))),
+            };
+            return Ok(Some(message));
+        }
+    }
+
+    async fn read_frame(&mut self) -> Result<Option<Frame>, Error> {
+        let mut header = [0; 2];
+        match self.inner.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        }
+        let fin = header[0] & 0x80 != 0;
+        let opcode = header[0] & 0x0f;
+        // The reserved bits are for extensions, and none were agreed on in the handshake.
+        if header[0] & 0x70 != 0 {
+            return Err(protocol_error("reserved bits are set"));
+        }
+        let is_masked = header[1] & 0x80 != 0;
+        if is_masked != (self.role == Role::Server) {
+            let message = match self.role {
+                Role::Server => "frames from clients must be masked",
+                Role::Client => "frames from servers must not be masked",
+            };
+            return Err(protocol_error(message));
+        }
+        let len = match header[1] & 0x7f {
+            126 => self.inner.read_u16().await? as u64,
+            127 => self.inner.read_u64().await?,
+            len => len as u64,
+        };
+        if opcode & 0x08 != 0 && (!fin || len > 125) {
+            return Err(protocol_error("control frames must be whole and short"));
+        }
+        if len > MAX_MESSAGE_LEN as u64 {
+            return Err(too_big());
+        }
+        let mut mask = [0; 4];
+        if is_masked {
+            self.inner.read_exact(&mut mask).await?;
+        }
+        let mut payload = vec![0; len as usize];
+        self.inner.read_exact(&mut payload).await?;
+        apply_mask(&mut payload, mask);
+        Ok(Some(Frame {
+            fin,
+            opcode,
+            payload,
+        }))
+    }
+}
+
+/// Writes messages, each in a single frame, to one side of a connection.
+pub struct Writer<W> {
+    inner: W,
+    role: Role,
+    random: SystemRandom,
+}
+
+impl<W: AsyncWrite + Unpin> Writer<W> {
+    pub fn new(inner: W, role: Role) -> Self {
+        Writer {
+            inner,
+            role,
+            random: SystemRandom::new(),
+        }
+    }
+
+    pub async fn write_message(&mut self, message: &Message) -> Result<(), Error> {
+        let (opcode, payload) = match message {
+            Message::Text(text) => (opcode::TEXT, text.as_bytes().to_vec()),
+            Message::Binary(data) => (opcode::BINARY, data.clone()),
+            Message::Ping(data) => (opcode::PING, data.clone()),
+            Message::Pong(data) => (opcode::PONG, data.clone()),
+            Message::Close(None) => (opcode::CLOSE, Vec::new()),
+            Message::Close(Some((code, reason))) => {
+                let mut payload = code.to_be_bytes().to_vec();
+                payload.extend_from_slice(reason.as_bytes());
+                (opcode::CLOSE, payload)
+            }
+        };
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(0x80 | opcode);
+        let mask_bit = match self.role {
+            Role::Server => 0,
+            Role::Client => 0x80,
+        };
+        match payload.len() {
+            len if len < 126 => frame.push(mask_bit | len as u8),
+            len if len <= u16::MAX as usize => {
+                frame.push(mask_bit | 126);
+                frame.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                frame.push(mask_bit | 127);
+                frame.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        let mut payload = payload;
+        if self.role == Role::Client {
+            let mut mask = [0; 4];
+            self.random
+                .fill(&mut mask)
+                .map_err(|_| Error::other("no random numbers"))?;
+            frame.extend_from_slice(&mask);
+            apply_mask(&mut payload, mask);
+        }
+        frame.extend_from_slice(&payload);
+        self.inner.write_all(&frame).await?;
+        self.inner.flush().await
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Ends the connection for writing, after a close frame.
+    pub async fn shutdown(&mut self) -> Result<(), Error> {
+        self.inner.shutdown().await
+    }
+}
+
+/// Connects to a server at a URL like `ws://127.0.0.1:8080/ws?room=lobby`, with the handshake
+/// of a client. Errors for answers other than `101 Switching Protocols` have the body of the
+/// answer in them, which tells why the server refused.
+pub async fn connect(
+    url: &str,
+) -> Result<(Reader<BufReader<OwnedReadHalf>>, Writer<OwnedWriteHalf>), Error> {
+    let invalid_url = || Error::new(ErrorKind::InvalidInput, format!("invalid URL '{}'", url));
+    let rest = url.strip_prefix("ws://").ok_or_else(invalid_url)?;
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(start) if rest[start..].starts_with('?') => {
+            (&rest[..start], format!("/{}", &rest[start..]))
+        }
+        Some(start) => (&rest[..start], rest[start..].to_string()),
+        None => (rest, "/".to_string()),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| invalid_url())?),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(invalid_url());
+    }
+    let (read_half, mut write_half) = TcpStream::connect((host, port)).await?.into_split();
+
+    let mut key = [0; 16];
+    SystemRandom::new()
+        .fill(&mut key)
+        .map_err(|_| Error::other("no random numbers"))?;
+    let key = STANDARD.encode(key);
+    let request = format!(
+        "GET {path} HTTP/1.1\r\nHost: {authority}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {key}\r\nSec-WebSocket-Version: 13\r\n\r\n"
+    );
+    write_half.write_all(request.as_bytes()).await?;
+
+    // Frames may follow the headers right away, so the reader keeps what it buffered.
+    let mut reader = BufReader::new(read_half);
+    let mut status = String::new();
+    reader.read_line(&mut status).await?;
+    let mut headers = Vec::new();
+    let mut headers_len = status.len();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        headers_len += line.len();
+        if headers_len > MAX_HEADERS_LEN {
+            return Err(protocol_error("the headers of the answer are too long"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let status = status.trim_end();
+    if status.split_whitespace().nth(1) != Some("101") {
+        let body_len = header("content-length").and_then(|len| len.parse().ok());
+        let mut body = vec![0; body_len.unwrap_or(0).min(MAX_HEADERS_LEN)];
+        reader.read_exact(&mut body).await?;
+        let body = String::from_utf8_lossy(&body);
+        let message = match body.trim() {
+            "" => format!("the server answered '{}'", status),
+            body => format!("the server answered '{}': {}", status, body),
+        };
+        return Err(Error::new(ErrorKind::ConnectionRefused, message));
+    }
+    if header("sec-websocket-accept") != Some(accept_key(&key).as_str()) {
+        return Err(protocol_error(
+            "the server didn't accept the key of the handshake",
+        ));
+    }
+    Ok((
+        Reader::new(reader, Role::Client),
+        Writer::new(write_half, Role::Client),
+    ))
+}
+
+mod opcode {
+    pub const CONTINUATION: u8 = 0x0;
+    pub const TEXT: u8 = 0x1;
+    pub const BINARY: u8 = 0x2;
+    pub const CLOSE: u8 = 0x8;
+    pub const PING: u8 = 0x9;
+    pub const PONG: u8 = 0xa;
+}
+
+struct Frame {
+    /// Whether this is the last frame of its message.
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+fn data_message(opcode: u8, payload: Vec<u8>) -> Result<Message, Error> {
+    match opcode {
+        opcode::TEXT => String::from_utf8(payload)
+            .map(Message::Text)
+            .map_err(|_| protocol_error("text messages must be UTF-8")),
+        _ => Ok(Message::Binary(payload)),
+    }
+}
+
+fn close_payload(payload: &[u8]) -> Result<Option<(u16, String)>, Error> {
+    match payload {
+        [] => Ok(None),
+        [high, low, reason @ ..] => {
+            let reason = String::from_utf8(reason.to_vec())
+                .map_err(|_| protocol_error("close reasons must be UTF-8"))?;
+            Ok(Some((u16::from_be_bytes([*high, *low]), reason)))
+        }
+        [_] => Err(protocol_error("a close code must have two bytes")),
+    }
+}
+
+/// Masks the payload, or unmasks it, which is the same, by a XOR with the key.
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+fn protocol_error(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+/// The error of messages longer than [`MAX_MESSAGE_LEN`], which are closed with a code of
+/// their own.
+#[derive(Debug)]
+struct TooBig;
+
+impl fmt::Display for TooBig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "messages can't be longer than {} bytes", MAX_MESSAGE_LEN)
+    }
+}
+
+impl std::error::Error for TooBig {}
+
+fn too_big() -> Error {
+    Error::new(ErrorKind::InvalidData, TooBig)
+}
+
+/// The close code to send the other side for an error of [`Reader::read_message`].
+pub fn error_close_code(error: &Error) -> u16 {
+    match error.get_ref().is_some_and(|inner| inner.is::<TooBig>()) {
+        true => close_code::TOO_BIG,
+        false => close_code::PROTOCOL_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_keys() {
+        // The example of RFC 6455.
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_and_reads_frames() {
+        let messages = [
+            Message::Text("hello".to_string()),
+            Message::Binary(vec![7; 300]),
+            Message::Ping(b"ping".to_vec()),
+            Message::Close(Some((close_code::NORMAL, "bye".to_string()))),
+        ];
+        let mut sent = Vec::new();
+        let mut writer = Writer::new(&mut sent, Role::Client);
+        for message in &messages {
+            writer.write_message(message).await.unwrap();
+        }
+        // Masked, and with a 16-bit length for the binary message.
+        assert_eq!(sent[1], 0x80 | 5);
+        assert_eq!(sent[11..14], [0x80 | opcode::BINARY, 0x80 | 126, 1]);
+
+        let mut reader = Reader::new(sent.as_slice(), Role::Server);
+        for message in messages {
+            assert_eq!(reader.read_message().await.unwrap(), Some(message));
+        }
+        assert_eq!(reader.read_message().await.unwrap(), None);
+        // Servers don't take frames that aren't masked.
+        let mut reader = Reader::new(&[0x81, 0x01, b'a'][..], Role::Server);
+        let error = reader.read_message().await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn reads_fragmented_messages() {
+        // "Hel" and "lo" from RFC 6455, with a ping between them.
+        let frames: &[u8] = &[
+            0x01, 0x03, b'H', b'e', b'l', 0x89, 0x00, 0x80, 0x02, b'l', b'o',
+        ];
+        let mut reader = Reader::new(frames, Role::Client);
+        assert_eq!(
+            reader.read_message().await.unwrap(),
+            Some(Message::Ping(Vec::new()))
+        );
+        assert_eq!(
+            reader.read_message().await.unwrap(),
+            Some(Message::Text("Hello".to_string()))
+        );
+        let mut reader = Reader::new(&[0x80, 0x01, b'a'][..], Role::Client);
+        assert!(reader.read_message().await.is_err());
+    }
+}
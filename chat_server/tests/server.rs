@@ -0,0 +1,253 @@
+//! Runs the server on a free port and chats with it over WebSocket, with the client side of
+//! the protocol, and with the terminal client.
+
+use chat_server::{
+    chat::Chat,
+    protocol::{ClientMessage, ServerMessage},
+    server,
+    websocket::{self, close_code, Message, Reader, Role, Writer},
+};
+use std::{net::SocketAddr, process::Stdio, sync::Arc};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+};
+
+async fn spawn_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let app = server::app(Arc::new(Chat::new()));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    address
+}
+
+struct Client {
+    reader: Reader<BufReader<OwnedReadHalf>>,
+    writer: Writer<OwnedWriteHalf>,
+}
+
+impl Client {
+    async fn connect(address: SocketAddr, query: &str) -> Client {
+        let url = format!("ws://{}/ws{}", address, query);
+        let (reader, writer) = websocket::connect(&url).await.unwrap();
+        Client { reader, writer }
+    }
+
+    async fn send(&mut self, message: ClientMessage) {
+        let json = serde_json::to_string(&message).unwrap();
+        self.writer
+            .write_message(&Message::Text(json))
+            .await
+            .unwrap();
+    }
+
+    async fn receive(&mut self) -> ServerMessage {
+        match self.reader.read_message().await.unwrap() {
+            Some(Message::Text(json)) => serde_json::from_str(&json).unwrap(),
+            message => panic!("expected a text message, got {:?}", message),
+        }
+    }
+}
+
+fn joined(room: &str, nick: &str) -> ServerMessage {
+    ServerMessage::Joined {
+        room: room.to_string(),
+        nick: nick.to_string(),
+    }
+}
+
+fn members(room: &str, nicks: &[&str]) -> ServerMessage {
+    ServerMessage::Members {
+        room: room.to_string(),
+        nicks: nicks.iter().map(|nick| nick.to_string()).collect(),
+    }
+}
+
+/// Sends a raw HTTP request, returning the answer.
+async fn http(address: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut answer = String::new();
+    stream.read_to_string(&mut answer).await.unwrap();
+    answer
+}
+
+#[tokio::test]
+async fn chats_in_rooms() {
+    let address = spawn_server().await;
+    let mut alice = Client::connect(address, "?nick=alice").await;
+    let welcome = ServerMessage::Welcome {
+        nick: "alice".to_string(),
+        room: "lobby".to_string(),
+    };
+    assert_eq!(alice.receive().await, welcome);
+    assert_eq!(alice.receive().await, members("lobby", &["alice"]));
+    assert_eq!(alice.receive().await, joined("lobby", "alice"));
+
+    let mut guest = Client::connect(address, "").await;
+    assert!(
+        matches!(guest.receive().await, ServerMessage::Welcome { nick, .. } if nick == "guest1")
+    );
+    assert_eq!(
+        guest.receive().await,
+        members("lobby", &["alice", "guest1"])
+    );
+    assert_eq!(guest.receive().await, joined("lobby", "guest1"));
+    assert_eq!(alice.receive().await, joined("lobby", "guest1"));
+
+    let text = "hello".to_string();
+    guest.send(ClientMessage::Say { text }).await;
+    let said = ServerMessage::Message {
+        room: "lobby".to_string(),
+        nick: "guest1".to_string(),
+        text: "hello".to_string(),
+    };
+    assert_eq!(alice.receive().await, said);
+    assert_eq!(guest.receive().await, said);
+
+    guest
+        .send(ClientMessage::Nick {
+            nick: "Alice".to_string(),
+        })
+        .await;
+    let error = ServerMessage::Error {
+        message: "the nick 'Alice' is taken".to_string(),
+    };
+    assert_eq!(guest.receive().await, error);
+    guest
+        .send(ClientMessage::Nick {
+            nick: "bob".to_string(),
+        })
+        .await;
+    let renamed = ServerMessage::Renamed {
+        room: "lobby".to_string(),
+        from: "guest1".to_string(),
+        to: "bob".to_string(),
+    };
+    assert_eq!(guest.receive().await, renamed);
+    assert_eq!(alice.receive().await, renamed);
+
+    guest
+        .send(ClientMessage::Join {
+            room: "Rust".to_string(),
+        })
+        .await;
+    assert_eq!(guest.receive().await, members("rust", &["bob"]));
+    assert_eq!(guest.receive().await, joined("rust", "bob"));
+    let left = ServerMessage::Left {
+        room: "lobby".to_string(),
+        nick: "bob".to_string(),
+    };
+    assert_eq!(alice.receive().await, left);
+
+    let rooms = http(
+        address,
+        "GET /rooms HTTP/1.1\r\nHost: chat\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        rooms.ends_with(r#"[{"name":"lobby","members":1},{"name":"rust","members":1}]"#),
+        "{}",
+        rooms
+    );
+
+    // Closing is answered with a close frame, and frees the nick.
+    let close = Message::Close(Some((close_code::NORMAL, String::new())));
+    alice.writer.write_message(&close).await.unwrap();
+    assert_eq!(alice.reader.read_message().await.unwrap(), Some(close));
+    let mut alice = Client::connect(address, "?nick=alice&room=rust").await;
+    assert!(matches!(alice.receive().await, ServerMessage::Welcome { room, .. } if room == "rust"));
+}
+
+#[tokio::test]
+async fn refuses_bad_requests() {
+    let address = spawn_server().await;
+    let answer = http(
+        address,
+        "GET /ws HTTP/1.1\r\nHost: chat\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(answer.starts_with("HTTP/1.1 400"), "{}", answer);
+    assert!(answer.ends_with("connect with a WebSocket"), "{}", answer);
+
+    let url = format!("ws://{}/ws?nick=a%20b", address);
+    let error = websocket::connect(&url).await.err().unwrap();
+    assert!(
+        error
+            .to_string()
+            .contains("400 Bad Request': nicks may only have"),
+        "{}",
+        error
+    );
+
+    let mut alice = Client::connect(address, "?nick=alice").await;
+    let url = format!("ws://{}/ws?nick=ALICE", address);
+    let error = websocket::connect(&url).await.err().unwrap();
+    assert!(
+        error.to_string().ends_with("the nick 'ALICE' is taken"),
+        "{}",
+        error
+    );
+
+    alice
+        .writer
+        .write_message(&Message::Text("{}".to_string()))
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        alice.receive().await;
+    }
+    assert!(
+        matches!(alice.receive().await, ServerMessage::Error { message } if message.starts_with("invalid message"))
+    );
+
+    // Frames of clients must be masked, which the writer of a server doesn't do.
+    let Client { mut reader, writer } = alice;
+    let mut unmasked = Writer::new(writer.into_inner(), Role::Server);
+    unmasked
+        .write_message(&Message::Text("{}".to_string()))
+        .await
+        .unwrap();
+    let close = reader.read_message().await.unwrap();
+    let reason = "frames from clients must be masked".to_string();
+    assert_eq!(
+        close,
+        Some(Message::Close(Some((close_code::PROTOCOL_ERROR, reason))))
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn runs_the_terminal_client() {
+    let address = spawn_server().await;
+    let mut client = tokio::process::Command::new(env!("CARGO_BIN_EXE_chat_client"))
+        .args(["--url", &format!("ws://{}/ws", address), "--room", "#Rust"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let input = "/nick carol\nhello \u{1b}[31mthere\n//slash\n/who\n/dance\n/quit\n";
+    let mut stdin = client.stdin.take().unwrap();
+    stdin.write_all(input.as_bytes()).await.unwrap();
+    drop(stdin);
+    let output = client.wait_with_output().await.unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // The error of the unknown command isn't from the server, so it may come before or after
+    // what the server sent.
+    let error = "! unknown command '/dance', /help lists them\n";
+    assert!(stdout.contains(error), "{}", stdout);
+    assert_eq!(
+        stdout.replacen(error, "", 1),
+        "* Connected as guest1 in #rust, /help lists the commands\n\
+         * In #rust: guest1\n\
+         * guest1 joined #rust\n\
+         * guest1 is now known as carol\n\
+         <carol> hello \u{fffd}[31mthere\n\
+         <carol> /slash\n\
+         * In #rust: carol\n"
+    );
+}
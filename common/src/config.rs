@@ -0,0 +1,174 @@
+use serde::de::DeserializeOwned;
+use std::{
+    env, fmt, fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// A config file that couldn't be loaded.
+#[derive(Debug)]
+pub enum FileError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Read(path, error) => write!(f, "can't read {}: {}", path.display(), error),
+            FileError::Parse(path, error) => {
+                write!(f, "invalid config file {}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+impl From<FileError> for io::Error {
+    fn from(error: FileError) -> Self {
+        let kind = match &error {
+            FileError::Read(_, error) => error.kind(),
+            FileError::Parse(..) => ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, error.to_string())
+    }
+}
+
+/// Reads a TOML config file, or `None` if there's no such file, so it can be optional.
+pub fn load_toml<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, FileError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents)
+            .map(Some)
+            .map_err(|error| FileError::Parse(path.to_path_buf(), error)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(FileError::Read(path.to_path_buf(), error)),
+    }
+}
+
+/// An environment variable that doesn't hold a valid value for the setting it overrides.
+#[derive(Debug)]
+pub struct InvalidEnv {
+    pub name: &'static str,
+    pub value: String,
+}
+
+impl fmt::Display for InvalidEnv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid value '{}' for environment variable {}",
+            self.value, self.name
+        )
+    }
+}
+
+impl std::error::Error for InvalidEnv {}
+
+/// Replaces a setting by the environment variable `name`, if it's set.
+pub fn override_from_env<T: FromStr>(value: &mut T, name: &'static str) -> Result<(), InvalidEnv> {
+    if let Ok(env_value) = env::var(name) {
+        *value = env_value.parse().map_err(|_| InvalidEnv {
+            name,
+            value: env_value,
+        })?;
+    }
+    Ok(())
+}
+
+/// Sets an optional setting from the environment variable `name`, if it's set.
+pub fn override_optional_from_env<T: FromStr>(
+    value: &mut Option<T>,
+    name: &'static str,
+) -> Result<(), InvalidEnv> {
+    if let Ok(env_value) = env::var(name) {
+        *value = Some(env_value.parse().map_err(|_| InvalidEnv {
+            name,
+            value: env_value,
+        })?);
+    }
+    Ok(())
+}
+
+/// Replaces a list setting by the comma-separated values of the environment variable `name`, if
+/// it's set.
+pub fn override_list_from_env(value: &mut Vec<String>, name: &str) {
+    if let Ok(env_value) = env::var(name) {
+        *value = env_value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect();
+    }
+}
+
+/// An XDG base directory like `$XDG_CONFIG_HOME`, or its default under the home directory, like
+/// `.config`. `None` without either. Relative paths in the variable are ignored, as the
+/// specification asks.
+pub fn xdg_dir(variable: &str, default: &str) -> Option<PathBuf> {
+    let from_variable = env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute());
+    from_variable.or_else(|| {
+        env::var_os("HOME")
+            .filter(|home| !home.is_empty())
+            .map(|home| PathBuf::from(home).join(default))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Settings {
+        port: u16,
+    }
+
+    #[test]
+    fn loads_optional_toml_files() {
+        let dir = env::temp_dir().join(format!("common-config-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+
+        assert!(load_toml::<Settings>(&path).unwrap().is_none());
+        fs::write(&path, "port = 8080\n").unwrap();
+        assert_eq!(
+            load_toml::<Settings>(&path).unwrap(),
+            Some(Settings { port: 8080 })
+        );
+        fs::write(&path, "port = \"eighty\"\n").unwrap();
+        let error = load_toml::<Settings>(&path).unwrap_err();
+        assert!(error.to_string().starts_with("invalid config file "));
+        assert_eq!(io::Error::from(error).kind(), ErrorKind::InvalidData);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn overrides_settings_from_the_environment() {
+        // Names of their own, since tests run in parallel and share the environment.
+        env::set_var("COMMON_TEST_PORT", "8080");
+        env::set_var("COMMON_TEST_HOSTS", "a.example, ,b.example");
+        env::set_var("COMMON_TEST_INVALID", "eighty");
+
+        let mut port = 3000u16;
+        override_from_env(&mut port, "COMMON_TEST_PORT").unwrap();
+        assert_eq!(port, 8080);
+        override_from_env(&mut port, "COMMON_TEST_UNSET").unwrap();
+        assert_eq!(port, 8080);
+        let error = override_from_env(&mut port, "COMMON_TEST_INVALID").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value 'eighty' for environment variable COMMON_TEST_INVALID"
+        );
+
+        let mut optional: Option<u16> = None;
+        override_optional_from_env(&mut optional, "COMMON_TEST_PORT").unwrap();
+        assert_eq!(optional, Some(8080));
+        let mut hosts = Vec::new();
+        override_list_from_env(&mut hosts, "COMMON_TEST_HOSTS");
+        assert_eq!(hosts, ["a.example", "b.example"]);
+    }
+}
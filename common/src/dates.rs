@@ -0,0 +1,63 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+/// How long before `now` a moment was, like `just now`, `5m ago`, `3h ago`, `2d ago`, `3w ago`,
+/// `4mo ago` or `2y ago`. Moments after `now`, from clocks that disagree, count as just now.
+pub fn format_relative(date_time: NaiveDateTime, now: NaiveDateTime) -> String {
+    let elapsed = now - date_time;
+    let (minutes, days) = (elapsed.num_minutes(), elapsed.num_days());
+    let (count, unit) = match minutes {
+        ..1 => return "just now".to_string(),
+        1..60 => (minutes, "m"),
+        60..1440 => (elapsed.num_hours(), "h"),
+        _ if days < 14 => (days, "d"),
+        _ if days < 60 => (days / 7, "w"),
+        _ if days < 365 => (days / 30, "mo"),
+        _ => (days / 365, "y"),
+    };
+    format!("{}{} ago", count, unit)
+}
+
+/// A time as HTTP headers like `Expires` and `Last-Modified` give it, like
+/// `Fri, 17 May 2024 09:30:00 GMT`.
+pub fn format_http_date(date_time: DateTime<Utc>) -> String {
+    date_time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Reads a time given like [`format_http_date`] writes it, as in `If-Modified-Since`, or `None`
+/// if it isn't one.
+pub fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date_time| date_time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+
+    #[test]
+    fn formats_relative_times_and_http_dates() {
+        let now = NaiveDate::from_ymd_opt(2024, 5, 17)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap();
+        let ago = |duration| format_relative(now - duration, now);
+        assert_eq!(ago(Duration::seconds(30)), "just now");
+        assert_eq!(ago(Duration::minutes(-5)), "just now");
+        assert_eq!(ago(Duration::minutes(5)), "5m ago");
+        assert_eq!(ago(Duration::hours(3)), "3h ago");
+        assert_eq!(ago(Duration::days(20)), "2w ago");
+        assert_eq!(ago(Duration::days(800)), "2y ago");
+
+        assert_eq!(
+            format_http_date(now.and_utc()),
+            "Fri, 17 May 2024 09:30:00 GMT"
+        );
+        assert_eq!(
+            parse_http_date("Fri, 17 May 2024 09:30:00 GMT"),
+            Some(now.and_utc())
+        );
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+}
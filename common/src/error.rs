@@ -0,0 +1,7 @@
+use std::{io::Error, path::Path};
+
+/// Prefixes an I/O error with the path it's about, keeping its kind, since the errors of the
+/// standard library don't say which file they're about.
+pub fn with_path(error: Error, path: &Path) -> Error {
+    Error::new(error.kind(), format!("{}: {}", path.display(), error))
+}
@@ -0,0 +1,440 @@
+//! Highlights the keywords, strings, comments and numbers of code in a few common languages,
+//! with `<span>`s that stylesheets give colors, for the code blocks of md2html and the pastes
+//! of pastebin. It goes by the characters more than by the grammar of each language, which is
+//! enough for examples and snippets.
+
+use crate::html::escape;
+
+struct Language {
+    /// The names of the language, like `rust` in the info string ```` ```rust ````.
+    names: &'static [&'static str],
+    keywords: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// The quotes of strings, longer ones first, since `"""` starts with `"`. Strings in
+    /// quotes of one character end with the line.
+    quotes: &'static [&'static str],
+    /// Whether keywords are matched ignoring case, like in SQL.
+    ignores_case: bool,
+}
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        names: &["rust", "rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        // Not `'`, which starts lifetimes as well as characters.
+        quotes: &["\""],
+        ignores_case: false,
+    },
+    Language {
+        names: &["python", "py"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+            "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return",
+            "True", "try", "while", "with", "yield",
+        ],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &["\"\"\"", "'''", "\"", "'"],
+        ignores_case: false,
+    },
+    Language {
+        names: &["javascript", "js", "jsx", "typescript", "ts", "tsx"],
+        keywords: &[
+            "async",
+            "await",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "else",
+            "export",
+            "extends",
+            "false",
+            "finally",
+            "for",
+            "function",
+            "if",
+            "import",
+            "in",
+            "instanceof",
+            "interface",
+            "let",
+            "new",
+            "null",
+            "of",
+            "return",
+            "static",
+            "super",
+            "switch",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "type",
+            "typeof",
+            "undefined",
+            "var",
+            "void",
+            "while",
+            "yield",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &["`", "\"", "'"],
+        ignores_case: false,
+    },
+    Language {
+        names: &["go", "golang"],
+        keywords: &[
+            "break",
+            "case",
+            "chan",
+            "const",
+            "continue",
+            "default",
+            "defer",
+            "else",
+            "fallthrough",
+            "false",
+            "for",
+            "func",
+            "go",
+            "goto",
+            "if",
+            "import",
+            "interface",
+            "map",
+            "nil",
+            "package",
+            "range",
+            "return",
+            "select",
+            "struct",
+            "switch",
+            "true",
+            "type",
+            "var",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &["`", "\"", "'"],
+        ignores_case: false,
+    },
+    Language {
+        names: &["c", "h", "cpp", "c++", "cc", "hpp", "java", "csharp", "cs"],
+        keywords: &[
+            "auto",
+            "bool",
+            "break",
+            "case",
+            "catch",
+            "char",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "double",
+            "else",
+            "enum",
+            "extends",
+            "extern",
+            "false",
+            "final",
+            "float",
+            "for",
+            "goto",
+            "if",
+            "implements",
+            "import",
+            "int",
+            "long",
+            "namespace",
+            "new",
+            "null",
+            "nullptr",
+            "private",
+            "protected",
+            "public",
+            "return",
+            "short",
+            "signed",
+            "sizeof",
+            "static",
+            "struct",
+            "switch",
+            "template",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "typedef",
+            "union",
+            "unsigned",
+            "using",
+            "virtual",
+            "void",
+            "volatile",
+            "while",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &["\"", "'"],
+        ignores_case: false,
+    },
+    Language {
+        names: &["sh", "bash", "shell", "zsh"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+            "in", "local", "return", "then", "until", "while",
+        ],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &["\"", "'"],
+        ignores_case: false,
+    },
+    Language {
+        names: &["json"],
+        keywords: &["true", "false", "null"],
+        line_comments: &[],
+        block_comment: None,
+        quotes: &["\""],
+        ignores_case: false,
+    },
+    Language {
+        names: &["toml"],
+        keywords: &["true", "false"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &["\"\"\"", "'''", "\"", "'"],
+        ignores_case: false,
+    },
+    Language {
+        names: &["yaml", "yml"],
+        keywords: &["true", "false", "null", "yes", "no"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &["\"", "'"],
+        ignores_case: false,
+    },
+    Language {
+        names: &["sql"],
+        keywords: &[
+            "add",
+            "all",
+            "alter",
+            "and",
+            "as",
+            "asc",
+            "begin",
+            "by",
+            "commit",
+            "create",
+            "default",
+            "delete",
+            "desc",
+            "distinct",
+            "drop",
+            "exists",
+            "foreign",
+            "from",
+            "group",
+            "having",
+            "if",
+            "in",
+            "index",
+            "inner",
+            "insert",
+            "integer",
+            "into",
+            "is",
+            "join",
+            "key",
+            "left",
+            "limit",
+            "not",
+            "null",
+            "offset",
+            "on",
+            "or",
+            "order",
+            "outer",
+            "primary",
+            "real",
+            "references",
+            "right",
+            "select",
+            "set",
+            "table",
+            "text",
+            "transaction",
+            "union",
+            "unique",
+            "update",
+            "values",
+            "where",
+        ],
+        line_comments: &["--"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &["'", "\""],
+        ignores_case: true,
+    },
+];
+
+/// Highlights code as HTML, escaped, or `None` for the languages that aren't known.
+pub fn highlight(language: &str, code: &str) -> Option<String> {
+    Some(find(language)?.highlight(code))
+}
+
+/// Whether code in `language`, ignoring case, can be highlighted.
+pub fn is_known(language: &str) -> bool {
+    find(language).is_some()
+}
+
+fn find(language: &str) -> Option<&'static Language> {
+    let language = language.to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|known| known.names.contains(&language.as_str()))
+}
+
+impl Language {
+    fn highlight(&self, code: &str) -> String {
+        let mut html = String::with_capacity(code.len() * 2);
+        let mut rest = code;
+        while let Some(c) = rest.chars().next() {
+            let (class, len) = if let Some(len) = self.comment_len(rest) {
+                (Some("comment"), len)
+            } else if let Some(len) = self.string_len(rest) {
+                (Some("string"), len)
+            } else if c.is_ascii_digit() {
+                (Some("number"), number_len(rest))
+            } else if c.is_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                let class = self.is_keyword(&rest[..len]).then_some("keyword");
+                (class, len)
+            } else {
+                (None, c.len_utf8())
+            };
+            let (token, after) = rest.split_at(len);
+            match class {
+                Some(class) => html.push_str(&format!(
+                    "<span class=\"hl-{}\">{}</span>",
+                    class,
+                    escape(token)
+                )),
+                None => html.push_str(&escape(token)),
+            }
+            rest = after;
+        }
+        html
+    }
+
+    /// The length of the comment that `code` starts with, if it does.
+    fn comment_len(&self, code: &str) -> Option<usize> {
+        if self
+            .line_comments
+            .iter()
+            .any(|start| code.starts_with(start))
+        {
+            return Some(code.find('\n').unwrap_or(code.len()));
+        }
+        let (start, end) = self
+            .block_comment
+            .filter(|(start, _)| code.starts_with(start))?;
+        let len = code[start.len()..]
+            .find(end)
+            .map_or(code.len(), |len| start.len() + len + end.len());
+        Some(len)
+    }
+
+    /// The length of the string that `code` starts with, if it does and the string is closed.
+    fn string_len(&self, code: &str) -> Option<usize> {
+        let quote = self.quotes.iter().find(|quote| code.starts_with(*quote))?;
+        let mut chars = code.char_indices().skip(quote.len());
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    chars.next();
+                }
+                '\n' if quote.len() == 1 && *quote != "`" => return None,
+                _ if code[i..].starts_with(quote) => return Some(i + quote.len()),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn is_keyword(&self, word: &str) -> bool {
+        match self.ignores_case {
+            true => self.keywords.contains(&word.to_lowercase().as_str()),
+            false => self.keywords.contains(&word),
+        }
+    }
+}
+
+/// Numbers like `42`, `0xff`, `1_000`, `2.5` and `1e-3`, but not the `.` of `1..2`.
+fn number_len(code: &str) -> usize {
+    let bytes = code.as_bytes();
+    let mut len = 0;
+    while len < bytes.len() {
+        let is_part = match bytes[len] {
+            b if b.is_ascii_alphanumeric() || b == b'_' => true,
+            b'.' => bytes.get(len + 1).is_some_and(u8::is_ascii_digit),
+            b'-' | b'+' => matches!(bytes[len - 1], b'e' | b'E') && !code.starts_with("0x"),
+            _ => false,
+        };
+        if !is_part {
+            break;
+        }
+        len += 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_tokens() {
+        assert_eq!(
+            highlight("Rust", "fn main() { let s = \"a \\\" < b\"; // 1\n}").unwrap(),
+            "<span class=\"hl-keyword\">fn</span> main() { <span class=\"hl-keyword\">let</span> \
+             s = <span class=\"hl-string\">&quot;a \\&quot; &lt; b&quot;</span>; \
+             <span class=\"hl-comment\">// 1</span>\n}"
+        );
+        assert_eq!(
+            highlight("python", "x = '''it's\n''' if 1.5e-3 else None").unwrap(),
+            "x = <span class=\"hl-string\">'''it's\n'''</span> \
+             <span class=\"hl-keyword\">if</span> <span class=\"hl-number\">1.5e-3</span> \
+             <span class=\"hl-keyword\">else</span> <span class=\"hl-keyword\">None</span>"
+        );
+        assert_eq!(
+            highlight("sql", "Select 1 -- it's").unwrap(),
+            "<span class=\"hl-keyword\">Select</span> <span class=\"hl-number\">1</span> \
+             <span class=\"hl-comment\">-- it's</span>"
+        );
+        // A quote without its end is left as it is, like the one in `don't`.
+        assert_eq!(highlight("sh", "echo don't").unwrap(), "echo don't");
+        assert_eq!(highlight("cobol", "DISPLAY 1"), None);
+        assert!(is_known("Go") && !is_known("cobol"));
+    }
+}
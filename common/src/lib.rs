@@ -0,0 +1,16 @@
+//! Helpers shared by the projects of the workspace: loading config files and overriding their
+//! settings from the environment, formatting dates for people and protocols, errors that say
+//! which file they're about, reading secrets from the terminal, escaping and highlighting code
+//! for HTML, and, for the servers, waiting for the signal to shut down and generating short
+//! codes for links.
+
+pub mod config;
+pub mod dates;
+pub mod error;
+pub mod highlight;
+pub mod html;
+#[cfg(feature = "short_code")]
+pub mod short_code;
+#[cfg(feature = "shutdown")]
+pub mod shutdown;
+pub mod terminal;
@@ -0,0 +1,20 @@
+//! Random codes for short links, like `aZ3kQ9`, made of the characters of an alphabet.
+
+use rand::Rng;
+
+/// Digits and upper and lower case letters.
+pub const ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// Digits and lower case letters, for codes that are matched ignoring case.
+pub const ALPHANUMERIC_LOWERCASE: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+/// Like [`ALPHANUMERIC`], but without characters that are easily mistaken for each other when
+/// read aloud or typed from print: `0`, `O`, `o`, `1`, `I` and `l`.
+pub const UNAMBIGUOUS: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnpqrstuvwxyz";
+pub const UNAMBIGUOUS_LOWERCASE: &[u8] = b"23456789abcdefghijkmnpqrstuvwxyz";
+
+/// A random code of `length` characters of the alphabet, which has to be ASCII.
+pub fn generate(characters: &[u8], length: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..length)
+        .map(|_| char::from(characters[rng.gen_range(0..characters.len())]))
+        .collect()
+}
@@ -0,0 +1,63 @@
+//! Reading secrets, like passphrases, from the terminal.
+
+use std::io::{self, BufRead, Error, Write};
+
+/// Asks for a line on the terminal, with `prompt` on stderr, without showing what's typed.
+/// Callers check that stdin is a terminal first, since they know what to use otherwise.
+pub fn read_hidden(prompt: &str) -> Result<String, Error> {
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    let mut line = String::new();
+    {
+        let _echo_off = EchoOff::enable()?;
+        io::stdin().lock().read_line(&mut line)?;
+    }
+    eprintln!();
+    // Truncated in place rather than copied, so there's only one copy of the secret to clear.
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(len);
+    Ok(line)
+}
+
+/// Keeps the terminal from echoing what's typed, until dropped.
+struct EchoOff {
+    #[cfg(unix)]
+    original: libc::termios,
+}
+
+impl EchoOff {
+    #[cfg(unix)]
+    fn enable() -> Result<Self, Error> {
+        let mut termios = std::mem::MaybeUninit::uninit();
+        // SAFETY: tcgetattr fills in the termios it's given when it succeeds.
+        let original = unsafe {
+            if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) != 0 {
+                return Err(Error::last_os_error());
+            }
+            termios.assume_init()
+        };
+        let mut silent = original;
+        silent.c_lflag &= !libc::ECHO;
+        // SAFETY: tcsetattr only reads the termios it's given.
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &silent) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(EchoOff { original })
+    }
+
+    /// What's typed is shown where echoing can't be turned off.
+    #[cfg(not(unix))]
+    fn enable() -> Result<Self, Error> {
+        Ok(EchoOff {})
+    }
+}
+
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        // SAFETY: tcsetattr only reads the termios it's given.
+        #[cfg(unix)]
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! The last rates fetched, kept on disk so most conversions don't need the network, and so
+//! `--offline` works at all.
+
+use crate::rates::Snapshot;
+use common::{config::xdg_dir, error::with_path};
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+const APP_DIR_NAME: &str = "currency_converter";
+const FILE_NAME: &str = "rates.json";
+
+/// `$XDG_CACHE_HOME/currency_converter/rates.json` or
+/// `~/.cache/currency_converter/rates.json`.
+pub fn default_path() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache").map(|dir| dir.join(APP_DIR_NAME).join(FILE_NAME))
+}
+
+/// The rates at `path`, or `None` if there are none yet. A file that can't be read as rates
+/// counts as none, and is replaced by the next ones fetched.
+pub fn load(path: &Path) -> Result<Option<Snapshot>, Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(serde_json::from_str(&contents).ok()),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(with_path(error, path)),
+    }
+}
+
+/// Writes the rates to a file next to `path` first, so conversions running at the same time
+/// never read half of them.
+pub fn save(path: &Path, snapshot: &Snapshot) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|error| with_path(error, dir))?;
+    }
+    let contents = serde_json::to_string_pretty(snapshot).map_err(Error::other)?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, contents).map_err(|error| with_path(error, &tmp_path))?;
+    fs::rename(&tmp_path, path).map_err(|error| with_path(error, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::env;
+
+    #[test]
+    fn keeps_the_last_snapshot() {
+        let dir = env::temp_dir().join(format!("currency-cache-test-{}", std::process::id()));
+        let path = dir.join("nested").join(FILE_NAME);
+        assert_eq!(load(&path).unwrap(), None);
+
+        let snapshot = Snapshot {
+            fetched_at: Utc::now(),
+            date: "2024-10-11".parse().unwrap(),
+            base: "EUR".to_string(),
+            rates: [("USD".to_string(), 1.0938)].into(),
+        };
+        save(&path, &snapshot).unwrap();
+        assert_eq!(load(&path).unwrap(), Some(snapshot));
+        fs::write(&path, "[]").unwrap();
+        assert_eq!(load(&path).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
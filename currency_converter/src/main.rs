@@ -0,0 +1,163 @@
+use chrono::Utc;
+use clap::Parser;
+use common::dates::format_relative;
+use currency_converter::{
+    cache, parse_duration,
+    rates::{self, parse_currency, Snapshot},
+};
+use std::{
+    io::{Error, ErrorKind},
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+/// Converts amounts between currencies with the daily rates of the European Central Bank.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The amount to convert, like 19.99
+    #[arg(
+        value_name = "amount",
+        required_unless_present = "list",
+        allow_negative_numbers = true
+    )]
+    amount: Option<f64>,
+    /// The currency of the amount, like USD
+    #[arg(value_name = "from", required_unless_present = "list", value_parser = parse_currency)]
+    from: Option<String>,
+    /// The currencies to convert to, like EUR or `to EUR GBP`
+    #[arg(value_name = "to", required_unless_present = "list")]
+    to: Vec<String>,
+    /// List the currencies there are rates for, with what a euro is worth in them
+    #[arg(long, conflicts_with_all = ["amount", "from", "to"])]
+    list: bool,
+    /// Use the last rates fetched, however old, without the network
+    #[arg(long)]
+    offline: bool,
+    /// How long fetched rates are used before they're fetched again, like 30m or 12h. The
+    /// bank publishes new ones once a working day
+    #[arg(
+        long,
+        value_name = "duration",
+        env = "CURRENCY_CACHE_TTL",
+        default_value = "12h",
+        value_parser = parse_duration
+    )]
+    cache_ttl: Duration,
+    /// The file the last rates are kept in, `~/.cache/currency_converter/rates.json` if not
+    /// given
+    #[arg(long, value_name = "path", env = "CURRENCY_CACHE_FILE")]
+    cache_file: Option<PathBuf>,
+    /// The Frankfurter API to use, like a self-hosted one
+    #[arg(long, value_name = "url", env = "CURRENCY_API_URL", default_value = rates::DEFAULT_URL)]
+    api_url: String,
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let invalid_input = |message| Error::new(ErrorKind::InvalidInput, message);
+    // `100 USD to EUR` reads like `100 USD EUR`.
+    let targets = args
+        .to
+        .iter()
+        .filter(|word| !word.eq_ignore_ascii_case("to") && !word.eq_ignore_ascii_case("in"))
+        .map(|code| parse_currency(code))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(invalid_input)?;
+    if !args.list && targets.is_empty() {
+        return Err(invalid_input("no currency to convert to".to_string()));
+    }
+    if args.amount.is_some_and(|amount| !amount.is_finite()) {
+        return Err(invalid_input("the amount isn't a number".to_string()));
+    }
+
+    let snapshot = load_rates(&args)?;
+    if args.list {
+        println!("Rates per 1 {}, of {}", snapshot.base, snapshot.date);
+        for currency in snapshot.currencies() {
+            // Every currency has a rate, it's taken from them.
+            let rate = snapshot.rate(currency).unwrap_or_default();
+            println!("{}  {:>12.4}", currency, rate);
+        }
+        return Ok(());
+    }
+
+    let (Some(amount), Some(from)) = (args.amount, &args.from) else {
+        unreachable!("clap requires the amount and the currency without --list");
+    };
+    for to in &targets {
+        let converted = snapshot.convert(amount, from, to).map_err(invalid_input)?;
+        let rate = snapshot.convert(1.0, from, to).map_err(invalid_input)?;
+        println!(
+            "{:.2} {} = {:.2} {}  (1 {} = {:.4} {})",
+            amount, from, converted, to, from, rate, to
+        );
+    }
+    Ok(())
+}
+
+/// The cached rates if they're recent enough, or with `--offline`, and otherwise new ones, which
+/// replace them. Cached rates are still used, with a warning, when new ones can't be fetched.
+fn load_rates(args: &Args) -> Result<Snapshot, Error> {
+    let cache_path = args.cache_file.clone().or_else(cache::default_path);
+    let cached = match &cache_path {
+        Some(path) => cache::load(path)?,
+        None => None,
+    };
+    let now = Utc::now();
+    let age =
+        |snapshot: &Snapshot| format_relative(snapshot.fetched_at.naive_utc(), now.naive_utc());
+    if args.offline {
+        let Some(snapshot) = cached else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "no rates were fetched yet, run once without --offline",
+            ));
+        };
+        if !snapshot.is_fresh(args.cache_ttl, now) {
+            eprintln!(
+                "Using the rates of {}, fetched {}",
+                snapshot.date,
+                age(&snapshot)
+            );
+        }
+        return Ok(snapshot);
+    }
+    if let Some(snapshot) = cached.as_ref().filter(|s| s.is_fresh(args.cache_ttl, now)) {
+        return Ok(snapshot.clone());
+    }
+
+    match rates::fetch(&args.api_url) {
+        Ok(snapshot) => {
+            if let Some(path) = &cache_path {
+                // The conversion is still worth printing without the cache.
+                if let Err(error) = cache::save(path, &snapshot) {
+                    eprintln!("warning: couldn't save the rates: {}", error);
+                }
+            }
+            Ok(snapshot)
+        }
+        Err(error) => {
+            let Some(snapshot) = cached else {
+                return Err(error);
+            };
+            eprintln!(
+                "warning: {}, using the rates of {}, fetched {}",
+                error,
+                snapshot.date,
+                age(&snapshot)
+            );
+            Ok(snapshot)
+        }
+    }
+}
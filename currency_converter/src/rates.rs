@@ -0,0 +1,154 @@
+//! Exchange rates, fetched for a single base currency, which is enough to convert between any
+//! two of the others.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::{Error, ErrorKind},
+    time::Duration,
+};
+
+pub const DEFAULT_URL: &str = "https://api.frankfurter.dev/v1";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The rates of a day, as they're cached.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// When the rates were fetched, which the time they're kept in the cache counts from.
+    pub fetched_at: DateTime<Utc>,
+    /// The day the bank published the rates, which is the last working day on weekends and
+    /// holidays.
+    pub date: NaiveDate,
+    pub base: String,
+    /// How much one unit of the base currency is worth in each of the others.
+    pub rates: BTreeMap<String, f64>,
+}
+
+impl Snapshot {
+    /// How much one unit of the base currency is worth in `currency`.
+    pub fn rate(&self, currency: &str) -> Option<f64> {
+        if currency == self.base {
+            return Some(1.0);
+        }
+        self.rates.get(currency).copied()
+    }
+
+    /// Converts through the base currency, so `from` and `to` don't have to be it.
+    pub fn convert(&self, amount: f64, from: &str, to: &str) -> Result<f64, String> {
+        let rate = |currency| {
+            self.rate(currency)
+                .ok_or_else(|| format!("unknown currency {}, see --list", currency))
+        };
+        Ok(amount / rate(from)? * rate(to)?)
+    }
+
+    /// The codes of the currencies there are rates for, the base one included, sorted.
+    pub fn currencies(&self) -> Vec<&str> {
+        let mut currencies: Vec<&str> = self.rates.keys().map(String::as_str).collect();
+        currencies.push(&self.base);
+        currencies.sort_unstable();
+        currencies.dedup();
+        currencies
+    }
+
+    /// Whether the rates were fetched less than `ttl` before `now`.
+    pub fn is_fresh(&self, ttl: Duration, now: DateTime<Utc>) -> bool {
+        let age = (now - self.fetched_at).to_std().unwrap_or_default();
+        age < ttl
+    }
+}
+
+/// Reads a currency code like `usd` as `USD`.
+pub fn parse_currency(code: &str) -> Result<String, String> {
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Ok(code.to_ascii_uppercase())
+    } else {
+        Err(format!(
+            "invalid currency '{}', expected a code like USD",
+            code
+        ))
+    }
+}
+
+#[derive(Deserialize)]
+struct LatestResponse {
+    base: String,
+    date: NaiveDate,
+    rates: BTreeMap<String, f64>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+/// Fetches the latest rates, in euros, which are the ones the bank publishes.
+pub fn fetch(base_url: &str) -> Result<Snapshot, Error> {
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(concat!("currency_converter/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(Error::other)?;
+    let url = format!("{}/latest", base_url.trim_end_matches('/'));
+    let response = client
+        .get(&url)
+        .send()
+        .map_err(|error| Error::other(format!("couldn't fetch the exchange rates: {}", error)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let message = match response.json::<ErrorResponse>() {
+            Ok(response) => format!("{} answered {}: {}", url, status, response.message),
+            Err(_) => format!("{} answered {}", url, status),
+        };
+        return Err(Error::other(message));
+    }
+    let response: LatestResponse = response.json().map_err(|error| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid answer from {}: {}", url, error),
+        )
+    })?;
+    Ok(Snapshot {
+        fetched_at: Utc::now(),
+        date: response.date,
+        base: response.base,
+        rates: response.rates,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_through_the_base_currency() {
+        let response: LatestResponse = serde_json::from_str(
+            r#"{"amount":1.0,"base":"EUR","date":"2024-10-11",
+                "rates":{"GBP":0.8,"JPY":160.0,"USD":1.25}}"#,
+        )
+        .unwrap();
+        let snapshot = Snapshot {
+            fetched_at: "2024-10-11T17:00:00Z".parse().unwrap(),
+            date: response.date,
+            base: response.base,
+            rates: response.rates,
+        };
+        assert_eq!(snapshot.convert(10.0, "EUR", "USD"), Ok(12.5));
+        assert_eq!(snapshot.convert(12.5, "USD", "EUR"), Ok(10.0));
+        assert_eq!(snapshot.convert(100.0, "USD", "GBP"), Ok(64.0));
+        assert_eq!(
+            snapshot.convert(1.0, "USD", "XYZ").unwrap_err(),
+            "unknown currency XYZ, see --list"
+        );
+        assert_eq!(snapshot.currencies(), ["EUR", "GBP", "JPY", "USD"]);
+
+        let ttl = Duration::from_secs(60 * 60);
+        assert!(snapshot.is_fresh(ttl, "2024-10-11T17:59:59Z".parse().unwrap()));
+        assert!(!snapshot.is_fresh(ttl, "2024-10-11T18:00:00Z".parse().unwrap()));
+        assert_eq!(parse_currency("usd").unwrap(), "USD");
+        assert!(parse_currency("dollar").is_err());
+    }
+}
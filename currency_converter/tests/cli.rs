@@ -0,0 +1,123 @@
+//! Runs the `currency` binary against a fake of the Frankfurter API, which counts the requests
+//! it gets, to check when the cached rates are used.
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::Path,
+    process::{Command, Output},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+const LATEST_RESPONSE: &str = r#"{"amount":1.0,"base":"EUR","date":"2024-10-11",
+    "rates":{"GBP":0.8,"JPY":160.0,"USD":1.25}}"#;
+
+/// Serves the fake API on a free port, returning its URL and how many requests it got.
+fn start_api() -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let count = Arc::new(AtomicUsize::new(0));
+    let requests = Arc::clone(&count);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            // The request line and headers, up to the blank line.
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            requests.fetch_add(1, Ordering::SeqCst);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{}",
+                LATEST_RESPONSE.len(),
+                LATEST_RESPONSE
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    (url, count)
+}
+
+/// A URL nothing listens on.
+fn closed_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+fn currency(api_url: &str, cache_file: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_currency"))
+        .args(args)
+        .env("CURRENCY_API_URL", api_url)
+        .env("CURRENCY_CACHE_FILE", cache_file)
+        .env_remove("CURRENCY_CACHE_TTL")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn caches_rates_until_they_expire() {
+    let (url, requests) = start_api();
+    let dir = env::temp_dir().join(format!("currency-cli-test-{}", std::process::id()));
+    let cache_file = dir.join("rates.json");
+
+    let output = currency(&url, &cache_file, &["--offline", "1", "EUR", "USD"]);
+    assert!(!output.status.success());
+
+    let text = stdout(currency(
+        &url,
+        &cache_file,
+        &["100", "usd", "to", "eur", "GBP"],
+    ));
+    assert_eq!(
+        text,
+        "100.00 USD = 80.00 EUR  (1 USD = 0.8000 EUR)\n\
+         100.00 USD = 64.00 GBP  (1 USD = 0.6400 GBP)\n"
+    );
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+    // Within the TTL, the cached rates are used.
+    stdout(currency(&url, &cache_file, &["1", "JPY", "EUR"]));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    stdout(currency(
+        &url,
+        &cache_file,
+        &["--cache-ttl", "0", "1", "JPY", "EUR"],
+    ));
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    // Without the API, the last rates are still there.
+    let text = stdout(currency(
+        &closed_url(),
+        &cache_file,
+        &["--offline", "--list"],
+    ));
+    assert!(text.starts_with("Rates per 1 EUR, of 2024-10-11\nEUR        1.0000\nGBP"));
+    let output = currency(
+        &closed_url(),
+        &cache_file,
+        &["--cache-ttl", "0", "8", "GBP", "EUR"],
+    );
+    let warning = String::from_utf8(output.stderr.clone()).unwrap();
+    assert!(warning.starts_with("warning: couldn't fetch the exchange rates: "));
+    assert_eq!(
+        stdout(output),
+        "8.00 GBP = 10.00 EUR  (1 GBP = 1.2500 EUR)\n"
+    );
+
+    let output = currency(&url, &cache_file, &["1", "EUR", "XYZ"]);
+    let error = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(error, "error: unknown currency XYZ, see --list\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
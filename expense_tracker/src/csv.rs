@@ -0,0 +1,118 @@
+//! Expenses as CSV, with a `date,amount,category,description` header, the way spreadsheets and
+//! banking apps export them.
+
+use crate::{parse_amount, parse_category, parse_date, Expense};
+use std::io::{self, Error, ErrorKind, Read, Write};
+
+const COLUMNS: [&str; 4] = ["date", "amount", "category", "description"];
+
+/// Reads expenses from CSV with a header row. Columns are found by their names in the header,
+/// ignoring case, among any others: `date`, `amount` and `category` are required, and
+/// `description` is taken when it's there. Every row is checked before any is returned, so a
+/// file with a mistake can be fixed and imported again as a whole.
+pub fn read_expenses(input: impl Read) -> Result<Vec<Expense>, Error> {
+    let invalid = |message: String| Error::new(ErrorKind::InvalidInput, message);
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(input);
+    let header = reader
+        .headers()
+        .map_err(|error| invalid(error.to_string()))?
+        .clone();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|field| field.eq_ignore_ascii_case(name))
+    };
+    let required = |name: &str| {
+        column(name).ok_or_else(|| invalid(format!("no {} column in the header", name)))
+    };
+    let (date_column, amount_column, category_column) = (
+        required("date")?,
+        required("amount")?,
+        required("category")?,
+    );
+    let description_column = column("description");
+
+    let mut expenses = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        // The header is the first row.
+        let row = index + 2;
+        let record = record.map_err(|error| invalid(format!("row {}: {}", row, error)))?;
+        let field = |column: usize| record.get(column).unwrap_or_default();
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        let in_row = |message: String| invalid(format!("row {}: {}", row, message));
+        expenses.push(Expense {
+            id: 0,
+            date: parse_date(field(date_column)).map_err(in_row)?,
+            amount: parse_amount(field(amount_column)).map_err(in_row)?,
+            category: parse_category(field(category_column)).map_err(in_row)?,
+            description: description_column
+                .map(field)
+                .unwrap_or_default()
+                .to_string(),
+        });
+    }
+    Ok(expenses)
+}
+
+/// Writes the expenses as CSV, which [`read_expenses`] reads back as the same expenses
+/// without their ids.
+pub fn write_expenses(output: impl Write, expenses: &[Expense]) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_writer(output);
+    writer.write_record(COLUMNS).map_err(io::Error::from)?;
+    for expense in expenses {
+        writer
+            .write_record([
+                &expense.date.to_string(),
+                &expense.amount.to_string(),
+                &expense.category,
+                &expense.description,
+            ])
+            .map_err(io::Error::from)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Money;
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let input = "Date,Category,Amount,Account,Description\n\
+                     2024-10-01,Groceries,$12.50,checking,\"Milk, eggs\"\n\
+                     \n\
+                     2024-10-02, rent ,\"1,200\",checking\n";
+        let expenses = read_expenses(input.as_bytes()).unwrap();
+        assert_eq!(expenses.len(), 2);
+        assert_eq!(expenses[0].amount, Money(1250));
+        assert_eq!(expenses[0].category, "groceries");
+        assert_eq!(expenses[0].description, "Milk, eggs");
+        assert_eq!(expenses[1].amount, Money(120000));
+        assert_eq!(expenses[1].description, "");
+
+        let mut output = Vec::new();
+        write_expenses(&mut output, &expenses).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "date,amount,category,description\n\
+             2024-10-01,12.50,groceries,\"Milk, eggs\"\n\
+             2024-10-02,1200.00,rent,\n"
+        );
+        assert_eq!(read_expenses(output.as_bytes()).unwrap(), expenses);
+
+        let error = read_expenses("date,amount\n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "no category column in the header");
+        let error = read_expenses("date,amount,category\n2024-10-01,12,a\ntoday,-1,b\n".as_bytes());
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "row 3: the amount must be more than 0, not -1.00"
+        );
+    }
+}
@@ -0,0 +1,406 @@
+//! The expenses and budgets in a SQLite database, where sums by month and category are left
+//! to SQL, which does them without loading every expense.
+
+use crate::{error::ExpenseError, Expense, Money, Month};
+use chrono::NaiveDate;
+use common::{config::xdg_dir, error::with_path};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions},
+    Executor, Sqlite,
+};
+use std::{
+    fs,
+    future::Future,
+    io::Error,
+    path::{Path, PathBuf},
+};
+use tokio::runtime::Runtime;
+
+const APP_DIR_NAME: &str = "expense_tracker";
+const FILE_NAME: &str = "expenses.db";
+
+const SCHEMA: [&str; 3] = [
+    // Amounts are in cents, and dates like `2024-10-14`, which sort like the days they are and
+    // start with the month they're in.
+    "CREATE TABLE IF NOT EXISTS expenses (
+        id INTEGER PRIMARY KEY,
+        date TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        category TEXT NOT NULL,
+        description TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS expenses_by_date ON expenses (date)",
+    // How much can be spent on a category every month.
+    "CREATE TABLE IF NOT EXISTS budgets (
+        category TEXT PRIMARY KEY,
+        amount INTEGER NOT NULL
+    )",
+];
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The database, `$XDG_DATA_HOME/expense_tracker/expenses.db` or
+/// `~/.local/share/expense_tracker/expenses.db`.
+pub fn default_path() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join(APP_DIR_NAME).join(FILE_NAME))
+}
+
+/// Which expenses to list or export: the days from `from` to `to`, both included, and one
+/// category, each if given.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub category: Option<String>,
+}
+
+impl Filter {
+    pub fn month(month: Month) -> Self {
+        Filter {
+            from: Some(month.first_day()),
+            to: month.end().pred_opt(),
+            category: None,
+        }
+    }
+}
+
+/// What was spent on a category in a month, with how many expenses it was.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Total {
+    pub month: Month,
+    pub category: String,
+    pub amount: Money,
+    pub count: i64,
+}
+
+pub struct Database {
+    runtime: Runtime,
+    pool: SqlitePool,
+}
+
+impl Database {
+    /// Opens the database, creating it and its directory if they don't exist yet.
+    pub fn open(path: &Path) -> Result<Self, ExpenseError> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|error| with_path(error, dir))?;
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(ExpenseError::Storage)?;
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = runtime.block_on(async {
+            let pool = SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect_with(options)
+                .await?;
+            for statement in SCHEMA {
+                sqlx::query(statement).execute(&pool).await?;
+            }
+            Ok::<_, sqlx::Error>(pool)
+        });
+        let pool = pool.map_err(database_error)?;
+        Ok(Database { runtime, pool })
+    }
+
+    /// Runs a query to completion, since the rest of the CLI is synchronous.
+    fn block_on<T>(
+        &self,
+        query: impl Future<Output = Result<T, sqlx::Error>>,
+    ) -> Result<T, ExpenseError> {
+        self.runtime.block_on(query).map_err(database_error)
+    }
+
+    /// Adds an expense, ignoring its `id`, and returns the one it was given.
+    pub fn add(&self, expense: &Expense) -> Result<i64, ExpenseError> {
+        self.block_on(insert(&self.pool, expense))
+    }
+
+    /// Adds the expenses in a transaction, so an import that fails adds none of them.
+    pub fn add_all(&self, expenses: &[Expense]) -> Result<(), ExpenseError> {
+        self.block_on(async {
+            let mut transaction = self.pool.begin().await?;
+            for expense in expenses {
+                insert(&mut *transaction, expense).await?;
+            }
+            transaction.commit().await
+        })
+    }
+
+    /// Removes the expenses, or none of them if any doesn't exist, since the ids were probably
+    /// mistyped then.
+    pub fn remove(&self, ids: &[i64]) -> Result<(), ExpenseError> {
+        self.block_on(async {
+            let mut transaction = self.pool.begin().await?;
+            let mut missing = Vec::new();
+            for &id in ids {
+                let result = sqlx::query("DELETE FROM expenses WHERE id = $1")
+                    .bind(id)
+                    .execute(&mut *transaction)
+                    .await?;
+                if result.rows_affected() == 0 {
+                    missing.push(id.to_string());
+                }
+            }
+            if missing.is_empty() {
+                transaction.commit().await?;
+            }
+            Ok(missing)
+        })
+        .and_then(|missing| match missing.len() {
+            0 => Ok(()),
+            1 => Err(ExpenseError::NotFound(format!(
+                "expense {} not found",
+                missing[0]
+            ))),
+            _ => Err(ExpenseError::NotFound(format!(
+                "expenses {} not found",
+                missing.join(", ")
+            ))),
+        })
+    }
+
+    /// The expenses of the filter, oldest first.
+    pub fn list(&self, filter: &Filter) -> Result<Vec<Expense>, ExpenseError> {
+        let rows: Vec<(i64, String, i64, String, String)> = self.block_on(
+            sqlx::query_as(
+                "SELECT id, date, amount, category, description FROM expenses
+                WHERE ($1 IS NULL OR date >= $1) AND ($2 IS NULL OR date <= $2)
+                    AND ($3 IS NULL OR category = $3)
+                ORDER BY date, id",
+            )
+            .bind(filter.from.map(format_date))
+            .bind(filter.to.map(format_date))
+            .bind(&filter.category)
+            .fetch_all(&self.pool),
+        )?;
+        rows.into_iter()
+            .map(|(id, date, amount, category, description)| {
+                Ok(Expense {
+                    id,
+                    date: parse_stored_date(&date)?,
+                    amount: Money(amount),
+                    category,
+                    description,
+                })
+            })
+            .collect()
+    }
+
+    /// What was spent on each category in each month from `from` to `to`, both included,
+    /// sorted by month, then by the amount, most first.
+    pub fn totals(&self, from: Month, to: Month) -> Result<Vec<Total>, ExpenseError> {
+        let rows: Vec<(String, String, i64, i64)> = self.block_on(
+            sqlx::query_as(
+                "SELECT substr(date, 1, 7) AS month, category, SUM(amount) AS total, COUNT(*)
+                FROM expenses WHERE date >= $1 AND date < $2
+                GROUP BY month, category
+                ORDER BY month, total DESC, category",
+            )
+            .bind(format_date(from.first_day()))
+            .bind(format_date(to.end()))
+            .fetch_all(&self.pool),
+        )?;
+        rows.into_iter()
+            .map(|(month, category, amount, count)| {
+                let month = month.parse().map_err(|_| {
+                    ExpenseError::Storage(Error::other(format!(
+                        "invalid date in the database: '{}'",
+                        month
+                    )))
+                })?;
+                Ok(Total {
+                    month,
+                    category,
+                    amount: Money(amount),
+                    count,
+                })
+            })
+            .collect()
+    }
+
+    /// What was spent on a category in a month.
+    pub fn spent(&self, category: &str, month: Month) -> Result<Money, ExpenseError> {
+        let (total,): (i64,) = self.block_on(
+            sqlx::query_as(
+                "SELECT COALESCE(SUM(amount), 0) FROM expenses
+                WHERE category = $1 AND date >= $2 AND date < $3",
+            )
+            .bind(category)
+            .bind(format_date(month.first_day()))
+            .bind(format_date(month.end()))
+            .fetch_one(&self.pool),
+        )?;
+        Ok(Money(total))
+    }
+
+    /// Every category there are expenses or a budget for, with how many expenses it has and
+    /// what they add up to.
+    pub fn categories(&self) -> Result<Vec<(String, i64, Money)>, ExpenseError> {
+        let rows: Vec<(String, i64, i64)> = self.block_on(
+            sqlx::query_as(
+                "SELECT category, COUNT(id), COALESCE(SUM(amount), 0) FROM (
+                    SELECT id, category, amount FROM expenses
+                    UNION ALL SELECT NULL, category, NULL FROM budgets
+                )
+                GROUP BY category ORDER BY category",
+            )
+            .fetch_all(&self.pool),
+        )?;
+        let categories = rows.into_iter();
+        Ok(categories
+            .map(|(category, count, total)| (category, count, Money(total)))
+            .collect())
+    }
+
+    pub fn budget(&self, category: &str) -> Result<Option<Money>, ExpenseError> {
+        let row: Option<(i64,)> = self.block_on(
+            sqlx::query_as("SELECT amount FROM budgets WHERE category = $1")
+                .bind(category)
+                .fetch_optional(&self.pool),
+        )?;
+        Ok(row.map(|(amount,)| Money(amount)))
+    }
+
+    /// The monthly budgets, by category.
+    pub fn budgets(&self) -> Result<Vec<(String, Money)>, ExpenseError> {
+        let rows: Vec<(String, i64)> = self.block_on(
+            sqlx::query_as("SELECT category, amount FROM budgets ORDER BY category")
+                .fetch_all(&self.pool),
+        )?;
+        let budgets = rows.into_iter();
+        Ok(budgets
+            .map(|(category, amount)| (category, Money(amount)))
+            .collect())
+    }
+
+    /// Sets the monthly budget of a category, replacing the one it had.
+    pub fn set_budget(&self, category: &str, amount: Money) -> Result<(), ExpenseError> {
+        self.block_on(
+            sqlx::query(
+                "INSERT INTO budgets (category, amount) VALUES ($1, $2)
+                ON CONFLICT (category) DO UPDATE SET amount = excluded.amount",
+            )
+            .bind(category)
+            .bind(amount.cents())
+            .execute(&self.pool),
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_budget(&self, category: &str) -> Result<(), ExpenseError> {
+        let result = self.block_on(
+            sqlx::query("DELETE FROM budgets WHERE category = $1")
+                .bind(category)
+                .execute(&self.pool),
+        )?;
+        if result.rows_affected() == 0 {
+            return Err(ExpenseError::NotFound(format!(
+                "no budget for '{}'",
+                category
+            )));
+        }
+        Ok(())
+    }
+}
+
+async fn insert(
+    executor: impl Executor<'_, Database = Sqlite>,
+    expense: &Expense,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO expenses (date, amount, category, description) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(format_date(expense.date))
+    .bind(expense.amount.cents())
+    .bind(&expense.category)
+    .bind(&expense.description)
+    .execute(executor)
+    .await?;
+    Ok(result.last_insert_rowid())
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format(DATE_FORMAT).to_string()
+}
+
+fn parse_stored_date(date: &str) -> Result<NaiveDate, ExpenseError> {
+    NaiveDate::parse_from_str(date, DATE_FORMAT).map_err(|_| {
+        ExpenseError::Storage(Error::other(format!(
+            "invalid date in the database: '{}'",
+            date
+        )))
+    })
+}
+
+fn database_error(error: sqlx::Error) -> ExpenseError {
+    ExpenseError::Storage(Error::other(format!("database error: {}", error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn expense(date: &str, amount: i64, category: &str) -> Expense {
+        Expense {
+            id: 0,
+            date: date.parse().unwrap(),
+            amount: Money(amount),
+            category: category.to_string(),
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn sums_expenses_by_month_and_category() {
+        let dir = env::temp_dir().join(format!("expense-tracker-test-{}", std::process::id()));
+        let database = Database::open(&dir.join(FILE_NAME)).unwrap();
+        database
+            .add_all(&[
+                expense("2024-09-30", 1000, "rent"),
+                expense("2024-10-01", 1250, "groceries"),
+                expense("2024-10-15", 250, "groceries"),
+                expense("2024-10-31", 900, "rent"),
+                expense("2024-11-01", 700, "groceries"),
+            ])
+            .unwrap();
+        let october: Month = "2024-10".parse().unwrap();
+        let totals = database.totals(october, october).unwrap();
+        let totals: Vec<(&str, i64, i64)> = totals
+            .iter()
+            .map(|total| (total.category.as_str(), total.amount.cents(), total.count))
+            .collect();
+        assert_eq!(totals, [("groceries", 1500, 2), ("rent", 900, 1)]);
+        assert_eq!(
+            database.totals(october.previous(), october).unwrap().len(),
+            3
+        );
+        assert_eq!(database.spent("groceries", october).unwrap(), Money(1500));
+
+        let filter = Filter {
+            category: Some("groceries".to_string()),
+            ..Filter::month(october)
+        };
+        assert_eq!(database.list(&filter).unwrap().len(), 2);
+        assert_eq!(database.list(&Filter::default()).unwrap().len(), 5);
+
+        database.set_budget("eating-out", Money(5000)).unwrap();
+        database.set_budget("groceries", Money(1000)).unwrap();
+        database.set_budget("groceries", Money(2000)).unwrap();
+        assert_eq!(database.budget("groceries").unwrap(), Some(Money(2000)));
+        let categories = database.categories().unwrap();
+        assert_eq!(categories[0], ("eating-out".to_string(), 0, Money(0)));
+        assert_eq!(categories[1], ("groceries".to_string(), 3, Money(2200)));
+        assert!(database.remove_budget("travel").is_err());
+
+        // Nothing is removed when one of the expenses doesn't exist.
+        let error = database.remove(&[1, 42, 43]).unwrap_err();
+        assert_eq!(error.to_string(), "expenses 42, 43 not found");
+        database.remove(&[1]).unwrap();
+        assert_eq!(database.list(&Filter::default()).unwrap().len(), 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,52 @@
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+    process::ExitCode,
+};
+
+/// Why a command failed, which decides the exit status, so scripts can tell a missing expense
+/// from a mistyped amount or a broken database.
+#[derive(Debug)]
+pub enum ExpenseError {
+    /// Expenses or budgets that don't exist. Exits with 1.
+    NotFound(String),
+    /// Amounts, dates or categories that can't be read, and files that can't be imported.
+    /// Exits with 2, like the errors of clap.
+    Usage(String),
+    /// Reading or writing the database or other files failed. Exits with 3.
+    Storage(io::Error),
+}
+
+impl ExpenseError {
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            ExpenseError::NotFound(_) => ExitCode::from(1),
+            ExpenseError::Usage(_) => ExitCode::from(2),
+            ExpenseError::Storage(_) => ExitCode::from(3),
+        }
+    }
+}
+
+impl fmt::Display for ExpenseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpenseError::NotFound(message) | ExpenseError::Usage(message) => {
+                write!(f, "{}", message)
+            }
+            ExpenseError::Storage(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ExpenseError {}
+
+/// The modules report what they refuse, like rows of a CSV file that can't be read, as
+/// `InvalidInput`, and everything else as it happened.
+impl From<io::Error> for ExpenseError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            ErrorKind::InvalidInput => ExpenseError::Usage(error.to_string()),
+            _ => ExpenseError::Storage(error),
+        }
+    }
+}
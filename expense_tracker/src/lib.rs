@@ -0,0 +1,231 @@
+//! The expenses of the expense tracker, with their amounts, categories and the months they're
+//! summed up by, and everything that keeps, imports and reports them, for the `expenses`
+//! command and for tests.
+
+pub mod csv;
+pub mod database;
+pub mod error;
+pub mod report;
+
+use chrono::{Datelike, Local, Months, NaiveDate};
+use std::{fmt, str::FromStr};
+
+/// An amount of money in cents, since floats can't add up `0.10` and `0.20` exactly, and
+/// sums of expenses have to be exact.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(pub i64);
+
+impl Money {
+    pub fn cents(self) -> i64 {
+        self.0
+    }
+
+    /// How much of `total` this is, in percent, rounded down.
+    pub fn percent_of(self, total: Money) -> i64 {
+        match total.0 {
+            0 => 0,
+            total => self.0 * 100 / total,
+        }
+    }
+}
+
+/// Reads amounts like `12`, `12.5`, `12.50` or `1,234.56`. A currency symbol in front, like
+/// in `$12.50`, is ignored, so amounts can be pasted the way they're printed on receipts.
+impl FromStr for Money {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid amount '{}', expected one like 12.50", text);
+        let digits = text
+            .trim()
+            .trim_start_matches(|c: char| !c.is_ascii_digit() && c != '-' && c != '.')
+            .replace(',', "");
+        let (negative, digits) = match digits.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, digits.as_str()),
+        };
+        let (units, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if units.is_empty() && fraction.is_empty()
+            || fraction.len() > 2
+            || !all_digits(units)
+            || !all_digits(fraction)
+        {
+            return Err(invalid());
+        }
+        let units: i64 = match units {
+            "" => 0,
+            units => units.parse().map_err(|_| invalid())?,
+        };
+        // `.5` is 50 cents, not 5.
+        let fraction: i64 = format!("{:0<2}", fraction).parse().map_err(|_| invalid())?;
+        let cents = units
+            .checked_mul(100)
+            .and_then(|cents| cents.checked_add(fraction))
+            .ok_or_else(invalid)?;
+        Ok(Money(if negative { -cents } else { cents }))
+    }
+}
+
+/// Writes amounts like `1234.56`, without separators, so [`Money::from_str`] and
+/// spreadsheets read them back the same.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let cents = self.0.unsigned_abs();
+        let amount = format!("{}{}.{:02}", sign, cents / 100, cents % 100);
+        // So amounts can be lined up in columns with `{:>10}`.
+        f.pad(&amount)
+    }
+}
+
+impl std::iter::Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Self {
+        Money(iter.map(|money| money.0).sum())
+    }
+}
+
+/// Amounts that can be spent, which refunds aren't. They're kept as expenses of their own
+/// categories instead, if at all.
+pub fn parse_amount(text: &str) -> Result<Money, String> {
+    let amount: Money = text.parse()?;
+    if amount.0 <= 0 {
+        return Err(format!("the amount must be more than 0, not {}", amount));
+    }
+    Ok(amount)
+}
+
+/// Reads a category like `Groceries` as `groceries`, so it's the same however it's typed.
+pub fn parse_category(text: &str) -> Result<String, String> {
+    let category = text.trim().to_lowercase();
+    let is_valid = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+    if category.is_empty() || category.chars().count() > 32 || !category.chars().all(is_valid) {
+        return Err(format!(
+            "invalid category '{}', expected a word like groceries or eating-out",
+            text
+        ));
+    }
+    Ok(category)
+}
+
+/// Reads a date like `2024-10-14`, or `today` and `yesterday`.
+pub fn parse_date(text: &str) -> Result<NaiveDate, String> {
+    let today = Local::now().date_naive();
+    match text.trim().to_lowercase().as_str() {
+        "today" => Ok(today),
+        "yesterday" => Ok(today.pred_opt().unwrap_or(today)),
+        date => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| format!("invalid date '{}', expected one like 2024-10-14", text)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expense {
+    pub id: i64,
+    pub date: NaiveDate,
+    pub amount: Money,
+    pub category: String,
+    pub description: String,
+}
+
+/// A month of a year, which budgets are for and reports sum expenses up by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Month {
+    /// The first day of the month.
+    first_day: NaiveDate,
+}
+
+impl Month {
+    pub fn of(date: NaiveDate) -> Self {
+        Month {
+            first_day: date.with_day(1).expect("every month has a first day"),
+        }
+    }
+
+    pub fn current() -> Self {
+        Month::of(Local::now().date_naive())
+    }
+
+    pub fn first_day(self) -> NaiveDate {
+        self.first_day
+    }
+
+    /// The first day of the next month, which is where the month ends, not included.
+    pub fn end(self) -> NaiveDate {
+        self.first_day + Months::new(1)
+    }
+
+    pub fn previous(self) -> Self {
+        Month {
+            first_day: self.first_day - Months::new(1),
+        }
+    }
+
+    /// How many days the month has.
+    pub fn day_count(self) -> i64 {
+        (self.end() - self.first_day).num_days()
+    }
+
+    /// Names the month like `October 2024`.
+    pub fn name(self) -> String {
+        self.first_day.format("%B %Y").to_string()
+    }
+}
+
+/// Reads months like `2024-10`, or `this` and `last` for the current month and the one before.
+impl FromStr for Month {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.trim().to_lowercase().as_str() {
+            "this" => Ok(Month::current()),
+            "last" => Ok(Month::current().previous()),
+            month => NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+                .map(Month::of)
+                .map_err(|_| format!("invalid month '{}', expected one like 2024-10", text)),
+        }
+    }
+}
+
+/// Writes months like `2024-10`, as [`Month::from_str`] reads them.
+impl fmt::Display for Month {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.first_day.format("%Y-%m"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_writes_money() {
+        let cents = |text: &str| text.parse::<Money>().map(Money::cents);
+        assert_eq!(cents("12"), Ok(1200));
+        assert_eq!(cents("12.5"), Ok(1250));
+        assert_eq!(cents("$1,234.56"), Ok(123456));
+        assert_eq!(cents(".05"), Ok(5));
+        assert_eq!(cents("-3.10"), Ok(-310));
+        assert!(cents("12.345").is_err());
+        assert!(cents("twelve").is_err());
+        assert!(cents(".").is_err());
+        assert!(cents("99999999999999999999").is_err());
+
+        assert_eq!(Money(123456).to_string(), "1234.56");
+        assert_eq!(Money(-5).to_string(), "-0.05");
+        assert_eq!(format!("{:>8}", Money(150)), "    1.50");
+        // Floats would give 0.30000000000000004.
+        let sum: Money = ["0.10", "0.20"].iter().map(|a| a.parse().unwrap()).sum();
+        assert_eq!(sum, Money(30));
+        assert_eq!(Money(250).percent_of(Money(1000)), 25);
+        assert!(parse_amount("0").is_err());
+
+        assert_eq!(parse_category(" Eating-Out ").unwrap(), "eating-out");
+        assert!(parse_category("eating out").is_err());
+        let month: Month = "2024-02".parse().unwrap();
+        assert_eq!(month.day_count(), 29);
+        assert_eq!(month.previous().to_string(), "2024-01");
+        assert_eq!(month.name(), "February 2024");
+        assert!("2024-13".parse::<Month>().is_err());
+    }
+}
@@ -0,0 +1,337 @@
+use chrono::{Local, NaiveDate};
+use clap::{Args as ClapArgs, Parser, Subcommand};
+use common::error::with_path;
+use expense_tracker::{
+    csv,
+    database::{self, Database, Filter},
+    error::ExpenseError,
+    parse_amount, parse_category, parse_date,
+    report::{self, MonthReport},
+    Expense, Money, Month,
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+/// Keeps track of what you spend: expenses by category, monthly reports and budgets.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The database of the expenses, `~/.local/share/expense_tracker/expenses.db` if not given
+    #[arg(long, global = true, value_name = "path", env = "EXPENSE_DATABASE")]
+    database: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Adds an expense, and warns when its category goes over its budget
+    Add {
+        /// How much was spent, like 12.50
+        #[arg(value_name = "amount", value_parser = parse_amount)]
+        amount: Money,
+        /// What it was spent on, like groceries or eating-out
+        #[arg(value_name = "category", value_parser = parse_category)]
+        category: String,
+        /// What it was, in a few words
+        #[arg(value_name = "description")]
+        description: Vec<String>,
+        /// The day it was spent, like 2024-10-14 or yesterday, today if not given
+        #[arg(long, short, value_name = "date", value_parser = parse_date)]
+        date: Option<NaiveDate>,
+    },
+    /// Lists expenses, oldest first, with what they add up to
+    #[command(visible_alias = "ls")]
+    List {
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Removes expenses
+    #[command(visible_alias = "rm")]
+    Remove {
+        /// The numbers of the expenses, which `list` shows
+        #[arg(value_name = "id", required = true)]
+        ids: Vec<i64>,
+    },
+    /// Shows what was spent on each category in a month, next to the budgets
+    Report {
+        /// The month, like 2024-10, this or last, the current one if not given
+        #[arg(value_name = "month", conflicts_with = "months")]
+        month: Option<Month>,
+        /// Compare the totals of this many months up to the current one instead
+        #[arg(long, value_name = "count", value_parser = clap::value_parser!(u32).range(1..))]
+        months: Option<u32>,
+    },
+    /// Sets, removes or lists the monthly budgets of categories
+    Budget {
+        #[command(subcommand)]
+        command: BudgetCommands,
+    },
+    /// Lists the categories, with how many expenses each has and their total
+    Categories,
+    /// Adds the expenses of a CSV file with date, amount and category columns
+    Import {
+        /// The file, or - for the standard input
+        #[arg(value_name = "file")]
+        file: PathBuf,
+    },
+    /// Writes expenses as CSV
+    Export {
+        /// The file, the standard output if not given
+        #[arg(value_name = "file")]
+        file: Option<PathBuf>,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum BudgetCommands {
+    /// Sets how much can be spent on a category every month
+    Set {
+        #[arg(value_name = "category", value_parser = parse_category)]
+        category: String,
+        #[arg(value_name = "amount", value_parser = parse_amount)]
+        amount: Money,
+    },
+    /// Removes the budget of a category
+    #[command(visible_alias = "rm")]
+    Remove {
+        #[arg(value_name = "category", value_parser = parse_category)]
+        category: String,
+    },
+    /// Lists the budgets, with what was spent of them this month
+    #[command(visible_alias = "ls")]
+    List,
+}
+
+#[derive(ClapArgs)]
+struct FilterArgs {
+    /// Only the expenses of a month, like 2024-10, this or last
+    #[arg(long, value_name = "month", conflicts_with_all = ["from", "to"])]
+    month: Option<Month>,
+    /// Only the expenses from this day on
+    #[arg(long, value_name = "date", value_parser = parse_date)]
+    from: Option<NaiveDate>,
+    /// Only the expenses up to this day, included
+    #[arg(long, value_name = "date", value_parser = parse_date)]
+    to: Option<NaiveDate>,
+    /// Only the expenses of a category
+    #[arg(long, short, value_name = "category", value_parser = parse_category)]
+    category: Option<String>,
+}
+
+impl FilterArgs {
+    fn into_filter(self) -> Filter {
+        let dates = match self.month {
+            Some(month) => Filter::month(month),
+            None => Filter {
+                from: self.from,
+                to: self.to,
+                category: None,
+            },
+        };
+        Filter {
+            category: self.category,
+            ..dates
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            error.exit_code()
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), ExpenseError> {
+    let path = args
+        .database
+        .or_else(database::default_path)
+        .ok_or_else(|| {
+            ExpenseError::Usage(
+                "no home directory for the database, pick a file with --database".to_string(),
+            )
+        })?;
+    let database = Database::open(&path)?;
+    match args.command {
+        Commands::Add {
+            amount,
+            category,
+            description,
+            date,
+        } => {
+            let expense = Expense {
+                id: 0,
+                date: date.unwrap_or_else(|| Local::now().date_naive()),
+                amount,
+                category,
+                description: description.join(" "),
+            };
+            let id = database.add(&expense)?;
+            println!(
+                "Added expense {}: {} on {}",
+                id, expense.amount, expense.category
+            );
+            // The warning is for the month of the expense, which is the one its budget is for.
+            if let Some(budget) = database.budget(&expense.category)? {
+                let month = Month::of(expense.date);
+                let spent = database.spent(&expense.category, month)?;
+                if let Some(warning) =
+                    report::budget_warning(&expense.category, spent, budget, month)
+                {
+                    eprintln!("warning: {}", warning);
+                }
+            }
+            Ok(())
+        }
+        Commands::List { filter } => {
+            print_expenses(&database.list(&filter.into_filter())?);
+            Ok(())
+        }
+        Commands::Remove { ids } => {
+            database.remove(&ids)?;
+            match ids.as_slice() {
+                [id] => println!("Removed expense {}", id),
+                ids => println!("Removed {} expenses", ids.len()),
+            }
+            Ok(())
+        }
+        Commands::Report {
+            month: None,
+            months: Some(count),
+        } => {
+            let to = Month::current();
+            let from = (1..count).fold(to, |month, _| month.previous());
+            print!(
+                "{}",
+                report::render_months(from, to, &database.totals(from, to)?)
+            );
+            Ok(())
+        }
+        Commands::Report { month, .. } => {
+            let month = month.unwrap_or_else(Month::current);
+            let report =
+                MonthReport::new(month, &database.totals(month, month)?, &database.budgets()?);
+            print!("{}", report.render(Local::now().date_naive()));
+            for warning in report.warnings() {
+                eprintln!("warning: {}", warning);
+            }
+            Ok(())
+        }
+        Commands::Budget { command } => run_budget(&database, command),
+        Commands::Categories => {
+            let categories = database.categories()?;
+            if categories.is_empty() {
+                println!("No categories yet");
+            }
+            let width = categories
+                .iter()
+                .map(|(category, _, _)| category.chars().count())
+                .max()
+                .unwrap_or_default();
+            for (category, count, total) in categories {
+                let expenses = if count == 1 { "expense" } else { "expenses" };
+                println!(
+                    "{:<width$}  {:>10}  {} {}",
+                    category, total, count, expenses
+                );
+            }
+            Ok(())
+        }
+        Commands::Import { file } => {
+            let expenses = if file.as_os_str() == "-" {
+                csv::read_expenses(io::stdin().lock())?
+            } else {
+                let input = File::open(&file).map_err(|error| with_path(error, &file))?;
+                csv::read_expenses(input).map_err(|error| with_path(error, &file))?
+            };
+            database.add_all(&expenses)?;
+            let total: Money = expenses.iter().map(|expense| expense.amount).sum();
+            println!("Imported {} expenses, {} in total", expenses.len(), total);
+            Ok(())
+        }
+        Commands::Export { file, filter } => {
+            let expenses = database.list(&filter.into_filter())?;
+            match file {
+                Some(file) => {
+                    let output = File::create(&file).map_err(|error| with_path(error, &file))?;
+                    csv::write_expenses(BufWriter::new(output), &expenses)
+                        .map_err(|error| with_path(error, &file))?;
+                    eprintln!("Exported {} expenses to {}", expenses.len(), file.display());
+                }
+                None => csv::write_expenses(io::stdout().lock(), &expenses)?,
+            }
+            Ok(())
+        }
+    }
+}
+
+fn run_budget(database: &Database, command: BudgetCommands) -> Result<(), ExpenseError> {
+    match command {
+        BudgetCommands::Set { category, amount } => {
+            database.set_budget(&category, amount)?;
+            println!("The budget of {} is {} a month", category, amount);
+            Ok(())
+        }
+        BudgetCommands::Remove { category } => {
+            database.remove_budget(&category)?;
+            println!("Removed the budget of {}", category);
+            Ok(())
+        }
+        BudgetCommands::List => {
+            let budgets = database.budgets()?;
+            if budgets.is_empty() {
+                println!("No budgets yet, set one with `expenses budget set <category> <amount>`");
+                return Ok(());
+            }
+            let month = Month::current();
+            let width = budgets
+                .iter()
+                .map(|(category, _)| category.chars().count())
+                .max()
+                .unwrap_or_default();
+            for (category, budget) in &budgets {
+                let spent = database.spent(category, month)?;
+                println!(
+                    "{:<width$}  {:>10} a month, {}% used this month",
+                    category,
+                    budget,
+                    spent.percent_of(*budget)
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn print_expenses(expenses: &[Expense]) {
+    if expenses.is_empty() {
+        println!("No expenses");
+        return;
+    }
+    let width = expenses
+        .iter()
+        .map(|expense| expense.category.chars().count())
+        .max()
+        .unwrap_or_default();
+    for expense in expenses {
+        let row = format!(
+            "{:>4}  {}  {:>10}  {:<width$}  {}",
+            expense.id, expense.date, expense.amount, expense.category, expense.description
+        );
+        println!("{}", row.trim_end());
+    }
+    let total: Money = expenses.iter().map(|expense| expense.amount).sum();
+    let count = expenses.len();
+    let noun = if count == 1 { "expense" } else { "expenses" };
+    println!("{} {}, {} in total", count, noun, total);
+}
@@ -0,0 +1,261 @@
+//! The reports of a month, with what was spent on each category next to its budget, and of
+//! several months next to each other, as text for the terminal.
+
+use crate::{database::Total, Money, Month};
+use chrono::{Datelike, NaiveDate};
+use std::{collections::BTreeMap, fmt::Write};
+
+/// Budgets that are used up to this percentage get a warning before they're overspent.
+pub const NEAR_BUDGET_PERCENT: i64 = 80;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryLine {
+    pub category: String,
+    pub spent: Money,
+    pub count: i64,
+    pub budget: Option<Money>,
+}
+
+/// What was spent in a month, by category.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthReport {
+    pub month: Month,
+    /// The categories with expenses, most spent first, then the ones with a budget but no
+    /// expenses yet.
+    pub lines: Vec<CategoryLine>,
+}
+
+impl MonthReport {
+    /// Puts the totals of the month together with the budgets.
+    pub fn new(month: Month, totals: &[Total], budgets: &[(String, Money)]) -> Self {
+        let budget_of = |category: &str| {
+            let budget = budgets.iter().find(|(budgeted, _)| budgeted == category);
+            budget.map(|(_, amount)| *amount)
+        };
+        let mut lines: Vec<CategoryLine> = totals
+            .iter()
+            .filter(|total| total.month == month)
+            .map(|total| CategoryLine {
+                category: total.category.clone(),
+                spent: total.amount,
+                count: total.count,
+                budget: budget_of(&total.category),
+            })
+            .collect();
+        for (category, amount) in budgets {
+            if !lines.iter().any(|line| &line.category == category) {
+                lines.push(CategoryLine {
+                    category: category.clone(),
+                    spent: Money(0),
+                    count: 0,
+                    budget: Some(*amount),
+                });
+            }
+        }
+        MonthReport { month, lines }
+    }
+
+    pub fn total(&self) -> Money {
+        self.lines.iter().map(|line| line.spent).sum()
+    }
+
+    /// The budgets that are overspent or nearly used up, as warnings.
+    pub fn warnings(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .filter_map(|line| budget_warning(&line.category, line.spent, line.budget?, self.month))
+            .collect()
+    }
+
+    /// The table of the categories, with the total and the average of a day, which counts the
+    /// days up to `today` for the current month.
+    pub fn render(&self, today: NaiveDate) -> String {
+        let mut text = String::new();
+        // Writing to a `String` can't fail.
+        let _ = writeln!(text, "{}", self.month.name());
+        if self.lines.is_empty() {
+            text.push_str("No expenses\n");
+            return text;
+        }
+        let width = self
+            .lines
+            .iter()
+            .map(|line| line.category.chars().count())
+            .max()
+            .unwrap_or_default()
+            .max("Category".len());
+        let _ = writeln!(
+            text,
+            "{:<width$}  {:>10}  {:>10}  {:>10}",
+            "Category", "Spent", "Budget", "Left"
+        );
+        for line in &self.lines {
+            let (budget, left) = match line.budget {
+                Some(budget) => (
+                    budget.to_string(),
+                    Money(budget.0 - line.spent.0).to_string(),
+                ),
+                None => ("-".to_string(), String::new()),
+            };
+            let status = match line.budget {
+                Some(budget) if line.spent > budget => "  over budget",
+                Some(budget) if line.spent.percent_of(budget) >= NEAR_BUDGET_PERCENT => {
+                    "  nearly used"
+                }
+                _ => "",
+            };
+            let row = format!(
+                "{:<width$}  {:>10}  {:>10}  {:>10}{}",
+                line.category, line.spent, budget, left, status
+            );
+            let _ = writeln!(text, "{}", row.trim_end());
+        }
+        let budgets: Vec<Money> = self.lines.iter().filter_map(|line| line.budget).collect();
+        let total_budget = match budgets.is_empty() {
+            true => "-".to_string(),
+            false => budgets.into_iter().sum::<Money>().to_string(),
+        };
+        let _ = writeln!(
+            text,
+            "{:<width$}  {:>10}  {:>10}",
+            "Total",
+            self.total(),
+            total_budget
+        );
+        let count: i64 = self.lines.iter().map(|line| line.count).sum();
+        let days = match Month::of(today) == self.month {
+            true => i64::from(today.day()),
+            false => self.month.day_count(),
+        };
+        let _ = writeln!(
+            text,
+            "{} {}, {} a day on average",
+            count,
+            if count == 1 { "expense" } else { "expenses" },
+            Money(self.total().0 / days)
+        );
+        text
+    }
+}
+
+/// The warning for what was spent on a category in a month, if it's over its budget or near.
+pub fn budget_warning(category: &str, spent: Money, budget: Money, month: Month) -> Option<String> {
+    if spent > budget {
+        return Some(format!(
+            "{} is {} over its budget of {} for {}",
+            category,
+            Money(spent.0 - budget.0),
+            budget,
+            month.name()
+        ));
+    }
+    let percent = spent.percent_of(budget);
+    (percent >= NEAR_BUDGET_PERCENT).then(|| {
+        format!(
+            "{} has used {}% of its budget of {} for {}",
+            category,
+            percent,
+            budget,
+            month.name()
+        )
+    })
+}
+
+/// The months from `from` to `to`, one per line, with their total, how many expenses they had
+/// and the category most was spent on, then the average of a month.
+pub fn render_months(from: Month, to: Month, totals: &[Total]) -> String {
+    let mut months: BTreeMap<Month, Vec<&Total>> = BTreeMap::new();
+    let mut month = to;
+    while month >= from {
+        months.insert(month, Vec::new());
+        month = month.previous();
+    }
+    for total in totals {
+        if let Some(month) = months.get_mut(&total.month) {
+            month.push(total);
+        }
+    }
+
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "{:<7}  {:>10}  {:>8}  Top category",
+        "Month", "Total", "Expenses"
+    );
+    let mut sum = Money(0);
+    for (month, totals) in months.iter().rev() {
+        let total: Money = totals.iter().map(|total| total.amount).sum();
+        let count: i64 = totals.iter().map(|total| total.count).sum();
+        // The totals of a month come with the most spent first.
+        let top = match totals.first() {
+            Some(top) => format!("{} ({})", top.category, top.amount),
+            None => "-".to_string(),
+        };
+        let _ = writeln!(text, "{:<7}  {:>10}  {:>8}  {}", month, total, count, top);
+        sum = Money(sum.0 + total.0);
+    }
+    let average = Money(sum.0 / months.len().max(1) as i64);
+    let _ = writeln!(text, "{:<7}  {:>10}", "Average", average);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total(month: &str, category: &str, amount: i64, count: i64) -> Total {
+        Total {
+            month: month.parse().unwrap(),
+            category: category.to_string(),
+            amount: Money(amount),
+            count,
+        }
+    }
+
+    #[test]
+    fn reports_months_with_budgets() {
+        let october: Month = "2024-10".parse().unwrap();
+        let totals = [
+            total("2024-09", "rent", 120000, 1),
+            total("2024-10", "rent", 120000, 1),
+            total("2024-10", "groceries", 31250, 3),
+            total("2024-10", "books", 2000, 1),
+        ];
+        let budgets = [
+            ("eating-out".to_string(), Money(10000)),
+            ("groceries".to_string(), Money(30000)),
+            ("rent".to_string(), Money(140000)),
+        ];
+        let report = MonthReport::new(october, &totals, &budgets);
+        assert_eq!(report.total(), Money(153250));
+        assert_eq!(
+            report.warnings(),
+            [
+                "rent has used 85% of its budget of 1400.00 for October 2024",
+                "groceries is 12.50 over its budget of 300.00 for October 2024",
+            ]
+        );
+        let today = NaiveDate::from_ymd_opt(2024, 10, 10).unwrap();
+        assert_eq!(
+            report.render(today),
+            "October 2024\n\
+             Category         Spent      Budget        Left\n\
+             rent           1200.00     1400.00      200.00  nearly used\n\
+             groceries       312.50      300.00      -12.50  over budget\n\
+             books            20.00           -\n\
+             eating-out        0.00      100.00      100.00\n\
+             Total          1532.50     1800.00\n\
+             5 expenses, 153.25 a day on average\n"
+        );
+
+        let months = render_months(october.previous().previous(), october, &totals);
+        assert_eq!(
+            months,
+            "Month         Total  Expenses  Top category\n\
+             2024-10     1532.50         5  rent (1200.00)\n\
+             2024-09     1200.00         1  rent (1200.00)\n\
+             2024-08        0.00         0  -\n\
+             Average      910.83\n"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Runs the `expenses` binary on a database of its own: adds expenses with a budget, imports a
+//! CSV file, then reports and exports a month of it.
+
+use std::{
+    env, fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+const IMPORT: &str = "Date,Description,Amount,Category\n\
+                      2024-10-03,Weekly shop,$62.10,Groceries\n\
+                      2024-10-20,Cinema,14,eating-out\n\
+                      2024-09-28,Pizza,22.50,eating-out\n";
+
+fn expenses(database: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_expenses"))
+        .arg("--database")
+        .arg(database)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn tracks_expenses_and_budgets() {
+    let dir = env::temp_dir().join(format!("expense-tracker-cli-{}", std::process::id()));
+    let database = dir.join("expenses.db");
+    let run = |args: &[&str]| expenses(&database, args);
+
+    stdout(&run(&["budget", "set", "groceries", "100"]));
+    let output = run(&[
+        "add",
+        "45",
+        "groceries",
+        "milk",
+        "and",
+        "eggs",
+        "-d",
+        "2024-10-01",
+    ]);
+    assert_eq!(stdout(&output), "Added expense 1: 45.00 on groceries\n");
+    assert_eq!(stderr(&output), "");
+    stdout(&run(&["add", "1200", "rent", "-d", "2024-10-01"]));
+
+    // Importing checks every row first: a bad one adds none of them.
+    let csv = dir.join("bank.csv");
+    fs::write(
+        &csv,
+        format!("{}2024-10-21,Lunch,lots,eating-out\n", IMPORT),
+    )
+    .unwrap();
+    let output = run(&["import", csv.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).ends_with("row 5: invalid amount 'lots', expected one like 12.50\n"));
+    fs::write(&csv, IMPORT).unwrap();
+    let output = run(&["import", csv.to_str().unwrap()]);
+    assert_eq!(stdout(&output), "Imported 3 expenses, 98.60 in total\n");
+
+    // The groceries of October are 107.10 now, over the budget of 100.
+    let output = run(&["report", "2024-10"]);
+    assert_eq!(
+        stdout(&output),
+        "October 2024\n\
+         Category         Spent      Budget        Left\n\
+         rent           1200.00           -\n\
+         groceries       107.10      100.00       -7.10  over budget\n\
+         eating-out       14.00           -\n\
+         Total          1321.10      100.00\n\
+         4 expenses, 42.61 a day on average\n"
+    );
+    assert_eq!(
+        stderr(&output),
+        "warning: groceries is 7.10 over its budget of 100.00 for October 2024\n"
+    );
+    let output = run(&["add", "5", "groceries", "-d", "2024-10-30"]);
+    assert_eq!(
+        stderr(&output),
+        "warning: groceries is 12.10 over its budget of 100.00 for October 2024\n"
+    );
+
+    let output = run(&["list", "--month", "2024-10", "--category", "Groceries"]);
+    assert_eq!(
+        stdout(&output),
+        "   1  2024-10-01       45.00  groceries  milk and eggs\n   \
+         3  2024-10-03       62.10  groceries  Weekly shop\n   \
+         6  2024-10-30        5.00  groceries\n\
+         3 expenses, 112.10 in total\n"
+    );
+
+    let output = run(&["export", "--from", "2024-09-01", "--to", "2024-09-30"]);
+    assert_eq!(
+        stdout(&output),
+        "date,amount,category,description\n2024-09-28,22.50,eating-out,Pizza\n"
+    );
+
+    // Nothing is removed when one of the expenses doesn't exist.
+    let output = run(&["remove", "2", "99"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "error: expense 99 not found\n");
+    assert_eq!(stdout(&run(&["rm", "2"])), "Removed expense 2\n");
+    assert_eq!(
+        stdout(&run(&["categories"])),
+        "eating-out       36.50  2 expenses\n\
+         groceries       112.10  3 expenses\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
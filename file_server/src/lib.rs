@@ -0,0 +1,293 @@
+//! Serves the files of a directory over HTTP, with listings of directories, partial downloads
+//! and answers that let browsers keep using the copies they have.
+
+pub mod listing;
+pub mod range;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{
+        header::{
+            ACCEPT_RANGES, ALLOW, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+            IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, LOCATION, RANGE,
+        },
+        HeaderMap, HeaderValue, Method, StatusCode, Uri,
+    },
+    response::{IntoResponse, Response},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use common::dates::{format_http_date, parse_http_date};
+use percent_encoding::percent_decode_str;
+use range::RangeRequest;
+use std::{
+    fs::Metadata,
+    io::{self, ErrorKind, SeekFrom},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
+use tracing::{warn, Level};
+
+const INDEX_FILE: &str = "index.html";
+
+/// Serves the files under `root`, which has to be a directory.
+pub fn app(root: &Path) -> io::Result<Router> {
+    // Links on the way to a file are followed, but only to files under the root.
+    let root = std::fs::canonicalize(root)?;
+    if !root.is_dir() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} isn't a directory", root.display()),
+        ));
+    }
+    Ok(Router::new()
+        .fallback(serve)
+        .with_state(Arc::new(root))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        ))
+}
+
+async fn serve(
+    State(root): State<Arc<PathBuf>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Response {
+    if method != Method::GET && method != Method::HEAD {
+        let mut response = error_response(StatusCode::METHOD_NOT_ALLOWED);
+        let allowed = HeaderValue::from_static("GET, HEAD");
+        response.headers_mut().insert(ALLOW, allowed);
+        return response;
+    }
+    let response = match find(&root, uri.path()).await {
+        Ok((path, metadata)) if metadata.is_dir() => {
+            serve_dir(&root, &path, &uri, &headers, &method).await
+        }
+        Ok((path, metadata)) => serve_file(&path, &metadata, &headers, &method).await,
+        Err(error) => Err(error),
+    };
+    let response = response.unwrap_or_else(|error| match error.kind() {
+        ErrorKind::NotFound | ErrorKind::InvalidInput => error_response(StatusCode::NOT_FOUND),
+        ErrorKind::PermissionDenied => error_response(StatusCode::FORBIDDEN),
+        _ => {
+            warn!("Cannot serve {}: {}", uri.path(), error);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    });
+    if method == Method::HEAD {
+        // The headers stay, including the length of the body that would have been sent.
+        let (parts, _) = response.into_parts();
+        return Response::from_parts(parts, Body::empty());
+    }
+    response
+}
+
+/// Finds the file or directory of a request path under the root. Paths that lead out of the
+/// root, with `..` or a link, are reported as not found, like paths that don't exist.
+async fn find(root: &Path, request_path: &str) -> io::Result<(PathBuf, Metadata)> {
+    let not_found = || io::Error::from(ErrorKind::NotFound);
+    let decoded = percent_decode_str(request_path)
+        .decode_utf8()
+        .map_err(|_| not_found())?;
+    let mut path = root.to_path_buf();
+    for segment in decoded.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        // Segments like `..`, or `a\b` and `C:` on Windows, which aren't plain names.
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) if !segment.contains('\\') => path.push(name),
+            _ => return Err(not_found()),
+        }
+    }
+    let path = fs::canonicalize(&path).await?;
+    if !path.starts_with(root) {
+        return Err(not_found());
+    }
+    let metadata = fs::metadata(&path).await?;
+    Ok((path, metadata))
+}
+
+async fn serve_dir(
+    root: &Path,
+    path: &Path,
+    uri: &Uri,
+    headers: &HeaderMap,
+    method: &Method,
+) -> io::Result<Response> {
+    // Links in the listing and the index are relative, so they need the slash to work.
+    if !uri.path().ends_with('/') {
+        let location = match uri.query() {
+            Some(query) => format!("{}/?{}", uri.path(), query),
+            None => format!("{}/", uri.path()),
+        };
+        let mut response = StatusCode::MOVED_PERMANENTLY.into_response();
+        let location = HeaderValue::from_str(&location)
+            .map_err(|error| io::Error::new(ErrorKind::InvalidInput, error))?;
+        response.headers_mut().insert(LOCATION, location);
+        return Ok(response);
+    }
+    let index = path.join(INDEX_FILE);
+    if let Ok(metadata) = fs::metadata(&index).await {
+        if metadata.is_file() {
+            return serve_file(&index, &metadata, headers, method).await;
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut dir = fs::read_dir(path).await?;
+    while let Some(entry) = dir.next_entry().await? {
+        // Entries that vanished, and links to nowhere or out of the root, are left out.
+        match fs::canonicalize(entry.path()).await {
+            Ok(target) if target.starts_with(root) => {}
+            _ => continue,
+        }
+        let Ok(metadata) = fs::metadata(entry.path()).await else {
+            continue;
+        };
+        entries.push(listing::Entry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            is_dir: metadata.is_dir(),
+            size: metadata.len(),
+            modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+        });
+    }
+    let html = listing::render(uri.path(), entries);
+    let content_type = HeaderValue::from_static("text/html; charset=utf-8");
+    Ok(([(CONTENT_TYPE, content_type)], html).into_response())
+}
+
+async fn serve_file(
+    path: &Path,
+    metadata: &Metadata,
+    headers: &HeaderMap,
+    method: &Method,
+) -> io::Result<Response> {
+    let file_len = metadata.len();
+    // HTTP dates have no fractions of seconds, so neither has the time they're compared with.
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .and_then(|since_epoch| DateTime::from_timestamp(since_epoch.as_secs() as i64, 0));
+    let etag = entity_tag(metadata);
+
+    let mut validators = HeaderMap::new();
+    validators.insert(ETAG, header_value(&etag));
+    if let Some(modified) = modified {
+        validators.insert(LAST_MODIFIED, header_value(&format_http_date(modified)));
+    }
+    if is_fresh(headers, &etag, modified) {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        response.headers_mut().extend(validators);
+        return Ok(response);
+    }
+
+    let range = match headers.get(RANGE).and_then(|value| value.to_str().ok()) {
+        // Ranges of a version of the file the client doesn't have anymore would be mixed
+        // up with it, so it gets the whole file instead.
+        Some(range) if method == Method::GET && if_range_matches(headers, &etag, modified) => {
+            range::parse(range, file_len)
+        }
+        _ => RangeRequest::Full,
+    };
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    let content_type = match mime.type_() {
+        mime_guess::mime::TEXT => format!("{}; charset=utf-8", mime.essence_str()),
+        _ => mime.essence_str().to_string(),
+    };
+
+    let mut response_headers = validators;
+    response_headers.insert(CONTENT_TYPE, header_value(&content_type));
+    response_headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    let (status, start, length) = match range {
+        RangeRequest::Full => (StatusCode::OK, 0, file_len),
+        RangeRequest::Partial(range) => {
+            let content_range = format!("bytes {}-{}/{}", range.start, range.end, file_len);
+            response_headers.insert(CONTENT_RANGE, header_value(&content_range));
+            (StatusCode::PARTIAL_CONTENT, range.start, range.byte_count())
+        }
+        RangeRequest::Unsatisfiable => {
+            let mut response = error_response(StatusCode::RANGE_NOT_SATISFIABLE);
+            let content_range = format!("bytes */{}", file_len);
+            let response_headers = response.headers_mut();
+            response_headers.insert(CONTENT_RANGE, header_value(&content_range));
+            return Ok(response);
+        }
+    };
+    response_headers.insert(CONTENT_LENGTH, HeaderValue::from(length));
+
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        let mut file = File::open(path).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        // Sent as it's read, so large files don't have to fit in memory.
+        Body::from_stream(ReaderStream::new(file.take(length)))
+    };
+    Ok((status, response_headers, body).into_response())
+}
+
+/// A tag that changes whenever the file is changed, from its size and the time it was
+/// modified, like many servers make them, so files don't have to be read to tell.
+fn entity_tag(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", metadata.len(), modified.as_nanos())
+}
+
+/// Whether the copy the client has is still the file, by `If-None-Match`, or else by
+/// `If-Modified-Since`.
+fn is_fresh(headers: &HeaderMap, etag: &str, modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        // Tags that only differ in being weak, like `W/"a"`, count as the same here.
+        return if_none_match.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        });
+    }
+    let since = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_http_date);
+    matches!((since, modified), (Some(since), Some(modified)) if modified <= since)
+}
+
+/// Whether the range may be sent, by `If-Range`, which has the tag or the modification time
+/// of the version of the file the client has the rest of.
+fn if_range_matches(headers: &HeaderMap, etag: &str, modified: Option<DateTime<Utc>>) -> bool {
+    let Some(if_range) = headers.get(IF_RANGE).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    let if_range = if_range.trim();
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        // Weak tags don't promise the same bytes, so they never match.
+        return if_range == etag;
+    }
+    parse_http_date(if_range).is_some_and(|date| Some(date) == modified)
+}
+
+fn header_value(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).expect("header values are made of visible ASCII")
+}
+
+fn error_response(status: StatusCode) -> Response {
+    let message = format!(
+        "{} {}\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    );
+    (status, message).into_response()
+}
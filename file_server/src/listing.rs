@@ -0,0 +1,135 @@
+//! The HTML pages that list the contents of directories without an `index.html`.
+
+use chrono::{DateTime, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::fmt::Write;
+
+/// The characters that can't be left as they are in a path segment of a link.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'\\')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+pub struct Entry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// Renders the entries of the directory at `path`, as requested, like `/docs/`: directories
+/// first, then files, each sorted by name ignoring case.
+pub fn render(path: &str, mut entries: Vec<Entry>) -> String {
+    entries.sort_by(|a, b| {
+        (!a.is_dir, a.name.to_lowercase()).cmp(&(!b.is_dir, b.name.to_lowercase()))
+    });
+    let title = format!("Index of {}", escape(path));
+    let mut html = String::new();
+    // Writing to a `String` can't fail.
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>body {{ font-family: sans-serif; }} td {{ padding: 0 1em; }} \
+         .size {{ text-align: right; }}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<table>\n\
+         <tr><th>Name</th><th>Size</th><th>Modified</th></tr>\n"
+    );
+    if path != "/" {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    for entry in &entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        let href = escape(&utf8_percent_encode(&entry.name, PATH_SEGMENT).to_string());
+        let size = if entry.is_dir {
+            "-".to_string()
+        } else {
+            format_size(entry.size)
+        };
+        let modified = entry
+            .modified
+            .map(|modified| modified.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let _ = writeln!(
+            html,
+            "<tr><td><a href=\"{href}{suffix}\">{}{suffix}</a></td>\
+             <td class=\"size\">{size}</td><td>{modified}</td></tr>",
+            escape(&entry.name)
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// Sizes like `512 B`, `1.5 KiB` or `20.0 MiB`.
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Escapes text for HTML, so names like `<b>.txt` are shown as they are.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_directories_first_with_escaped_names() {
+        let entry = |name: &str, is_dir, size| Entry {
+            name: name.to_string(),
+            is_dir,
+            size,
+            modified: None,
+        };
+        let html = render(
+            "/docs/",
+            vec![
+                entry("b.txt", false, 1536),
+                entry("<a> & b", false, 10),
+                entry("Zebra", true, 0),
+            ],
+        );
+        let zebra = html.find("Zebra/").unwrap();
+        let special = html.find("&lt;a&gt; &amp; b").unwrap();
+        let b = html.find(">b.txt<").unwrap();
+        assert!(zebra < special && special < b);
+        assert!(html.contains("href=\"%3Ca%3E%20&amp;%20b\""));
+        assert!(html.contains("1.5 KiB"));
+        assert!(html.contains("href=\"../\""));
+        assert!(!render("/", Vec::new()).contains("href=\"../\""));
+    }
+}
@@ -0,0 +1,52 @@
+use clap::Parser;
+use std::{net::IpAddr, path::PathBuf};
+use tokio::net::TcpListener;
+use tracing::{error, info};
+use tracing_subscriber::EnvFilter;
+
+/// Serves the files of a directory over HTTP.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The address to listen on
+    #[arg(long, env = "FILE_SERVER_BIND", default_value = "127.0.0.1")]
+    bind: IpAddr,
+    #[arg(long, env = "FILE_SERVER_PORT", default_value_t = 8000)]
+    port: u16,
+    /// The directory to serve
+    #[arg(
+        long,
+        env = "FILE_SERVER_ROOT",
+        value_name = "dir",
+        default_value = "."
+    )]
+    root: PathBuf,
+}
+
+#[tokio::main]
+async fn main() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let args = Args::parse();
+
+    let app = file_server::app(&args.root).unwrap_or_else(|error| {
+        error!("Cannot serve {}: {}", args.root.display(), error);
+        std::process::exit(1);
+    });
+    let listener = TcpListener::bind((args.bind, args.port))
+        .await
+        .unwrap_or_else(|error| {
+            error!("Cannot listen on {}:{}: {}", args.bind, args.port, error);
+            std::process::exit(1);
+        });
+    info!(
+        "Serving {} on http://{}",
+        args.root.display(),
+        listener.local_addr().unwrap()
+    );
+    axum::serve(listener, app)
+        .with_graceful_shutdown(common::shutdown::signal())
+        .await
+        .unwrap();
+    info!("File server has shut down");
+}
@@ -0,0 +1,98 @@
+//! `Range` headers, which ask for a part of a file, like the rest of a download that broke off.
+
+/// A part of a file, from `start` to `end`, both included, as HTTP counts them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn byte_count(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RangeRequest {
+    /// The whole file, for headers that can't be read or ask for several ranges, which servers
+    /// may answer with the whole file instead.
+    Full,
+    Partial(ByteRange),
+    /// Answered with `416 Range Not Satisfiable`, since the range starts past the end.
+    Unsatisfiable,
+}
+
+/// Reads a header like `bytes=0-499`, `bytes=500-` or `bytes=-500`, for the last 500 bytes,
+/// for a file of `file_len` bytes. Ends past the end of the file are moved to its end.
+pub fn parse(header: &str, file_len: u64) -> RangeRequest {
+    let Some((unit, spec)) = header.trim().split_once('=') else {
+        return RangeRequest::Full;
+    };
+    if !unit.trim().eq_ignore_ascii_case("bytes") || spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+    let parse_number = |number: &str| number.trim().parse::<u64>().ok();
+    let last = file_len.checked_sub(1);
+    match (start.trim(), end.trim()) {
+        ("", suffix) => match (parse_number(suffix), last) {
+            (None, _) => RangeRequest::Full,
+            (Some(0), _) | (_, None) => RangeRequest::Unsatisfiable,
+            (Some(suffix), Some(last)) => RangeRequest::Partial(ByteRange {
+                start: file_len.saturating_sub(suffix),
+                end: last,
+            }),
+        },
+        (start, end) => {
+            let Some(start) = parse_number(start) else {
+                return RangeRequest::Full;
+            };
+            let end = match end {
+                "" => None,
+                end => match parse_number(end) {
+                    Some(end) if end >= start => Some(end),
+                    _ => return RangeRequest::Full,
+                },
+            };
+            match last {
+                Some(last) if start <= last => RangeRequest::Partial(ByteRange {
+                    start,
+                    end: end.map_or(last, |end| end.min(last)),
+                }),
+                _ => RangeRequest::Unsatisfiable,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partial(start: u64, end: u64) -> RangeRequest {
+        RangeRequest::Partial(ByteRange { start, end })
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse("bytes=0-499", 1000), partial(0, 499));
+        assert_eq!(parse("bytes=500-", 1000), partial(500, 999));
+        assert_eq!(parse("bytes=-300", 1000), partial(700, 999));
+        assert_eq!(parse("bytes=-3000", 1000), partial(0, 999));
+        assert_eq!(parse("bytes=900-2000", 1000), partial(900, 999));
+        assert_eq!(parse("Bytes = 1-1", 1000), partial(1, 1));
+
+        assert_eq!(parse("bytes=1000-", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=0-", 0), RangeRequest::Unsatisfiable);
+        assert_eq!(parse("bytes=-5", 0), RangeRequest::Unsatisfiable);
+
+        assert_eq!(parse("bytes=500-100", 1000), RangeRequest::Full);
+        assert_eq!(parse("bytes=0-1,5-6", 1000), RangeRequest::Full);
+        assert_eq!(parse("items=0-1", 1000), RangeRequest::Full);
+        assert_eq!(parse("bytes=a-b", 1000), RangeRequest::Full);
+    }
+}
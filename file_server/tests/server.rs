@@ -0,0 +1,162 @@
+//! Serves a temporary directory on a free port and requests its files over HTTP.
+
+use reqwest::{
+    header::{
+        ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE,
+        IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, LOCATION, RANGE,
+    },
+    redirect::Policy,
+    StatusCode,
+};
+use std::{fs, path::PathBuf};
+
+struct TestServer {
+    base_url: String,
+    client: reqwest::Client,
+    root: PathBuf,
+}
+
+impl TestServer {
+    /// Serves a directory with `hello.txt`, `docs/guide.md` and a `site/` with an index.
+    async fn spawn(name: &str) -> TestServer {
+        let root =
+            std::env::temp_dir().join(format!("file-server-{}-{}", name, std::process::id()));
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::create_dir_all(root.join("site")).unwrap();
+        fs::write(root.join("hello.txt"), "Hello, world!\n").unwrap();
+        fs::write(root.join("docs/guide.md"), "# Guide\n").unwrap();
+        fs::write(root.join("site/index.html"), "<h1>Site</h1>\n").unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = file_server::app(&root).unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        TestServer {
+            base_url: format!("http://{}", address),
+            client: reqwest::Client::builder()
+                .redirect(Policy::none())
+                .build()
+                .unwrap(),
+            root,
+        }
+    }
+
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client.get(format!("{}{}", self.base_url, path))
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[tokio::test]
+async fn serves_files_and_directories() {
+    let server = TestServer::spawn("files").await;
+
+    let response = server.get("/hello.txt").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[CONTENT_TYPE],
+        "text/plain; charset=utf-8"
+    );
+    assert_eq!(response.headers()[ACCEPT_RANGES], "bytes");
+    assert_eq!(response.text().await.unwrap(), "Hello, world!\n");
+
+    let response = server.get("/docs").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(response.headers()[LOCATION], "/docs/");
+    let listing = server
+        .get("/docs/")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(listing.contains("<a href=\"guide.md\">guide.md</a>"));
+    let index = server
+        .get("/site/")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(index, "<h1>Site</h1>\n");
+
+    let head = server.client.head(format!("{}/hello.txt", server.base_url));
+    let response = head.send().await.unwrap();
+    assert_eq!(response.headers()[CONTENT_LENGTH], "14");
+    assert_eq!(response.text().await.unwrap(), "");
+
+    for path in [
+        "/missing.txt",
+        "/../etc/passwd",
+        "/docs/%2e%2e/%2e%2e/etc/passwd",
+    ] {
+        let response = server.get(path).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+    }
+    let post = server.client.post(format!("{}/hello.txt", server.base_url));
+    assert_eq!(
+        post.send().await.unwrap().status(),
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+}
+
+#[tokio::test]
+async fn answers_ranges_and_conditional_requests() {
+    let server = TestServer::spawn("conditional").await;
+
+    let response = server
+        .get("/hello.txt")
+        .header(RANGE, "bytes=7-11")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers()[CONTENT_RANGE], "bytes 7-11/14");
+    assert_eq!(response.text().await.unwrap(), "world");
+    let response = server
+        .get("/hello.txt")
+        .header(RANGE, "bytes=20-")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(response.headers()[CONTENT_RANGE], "bytes */14");
+
+    let response = server.get("/hello.txt").send().await.unwrap();
+    let etag = response.headers()[ETAG].clone();
+    let last_modified = response.headers()[LAST_MODIFIED].clone();
+    let response = server
+        .get("/hello.txt")
+        .header(IF_NONE_MATCH, etag.clone())
+        .send();
+    assert_eq!(response.await.unwrap().status(), StatusCode::NOT_MODIFIED);
+    let response = server
+        .get("/hello.txt")
+        .header(IF_MODIFIED_SINCE, last_modified)
+        .send();
+    assert_eq!(response.await.unwrap().status(), StatusCode::NOT_MODIFIED);
+    let response = server
+        .get("/hello.txt")
+        .header(IF_NONE_MATCH, "\"other\"")
+        .send();
+    assert_eq!(response.await.unwrap().status(), StatusCode::OK);
+
+    // The range is only sent for the version of the file the client has.
+    let ranged = |if_range| {
+        let request = server.get("/hello.txt").header(RANGE, "bytes=0-4");
+        request.header(IF_RANGE, if_range).send()
+    };
+    assert_eq!(
+        ranged(etag).await.unwrap().status(),
+        StatusCode::PARTIAL_CONTENT
+    );
+    let response = ranged("\"other\"".parse().unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
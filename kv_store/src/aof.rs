@@ -0,0 +1,73 @@
+//! Persistence in an append-only file: every command that changes the store is appended to it
+//! as a line of the protocol, and the file is replayed when the server starts.
+
+use crate::protocol::Command;
+use common::error::with_path;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, ErrorKind, Write},
+    path::Path,
+};
+
+pub struct AppendOnlyFile {
+    writer: BufWriter<File>,
+}
+
+impl AppendOnlyFile {
+    /// Reads the commands of the file, or none if there's no file yet. A last line without a
+    /// line break is left out, since it's a write the server didn't finish before it stopped.
+    pub fn read(path: &Path) -> io::Result<Vec<Command>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(with_path(error, path)),
+        };
+        let complete = match contents.rfind('\n') {
+            Some(end) => &contents[..end],
+            None => "",
+        };
+        let mut commands = Vec::new();
+        for (index, line) in complete.split('\n').enumerate() {
+            match Command::parse(line) {
+                Ok(Some(command)) => commands.push(command),
+                Ok(None) => {}
+                Err(message) => {
+                    let message = format!("line {}: {}", index + 1, message);
+                    return Err(with_path(
+                        io::Error::new(ErrorKind::InvalidData, message),
+                        path,
+                    ));
+                }
+            }
+        }
+        Ok(commands)
+    }
+
+    /// Replaces the file by one with just the given commands, then opens it to append more.
+    /// The new file is written next to it first, so a crash leaves either of them complete.
+    pub fn rewrite(path: &Path, commands: impl IntoIterator<Item = Command>) -> io::Result<Self> {
+        let temporary_path = path.with_extension("rewrite");
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+        for command in commands {
+            writeln!(writer, "{}", command)?;
+        }
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&temporary_path, path).map_err(|error| with_path(error, path))?;
+
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(AppendOnlyFile {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Appends a command and hands it to the operating system, which writes it to the disk
+    /// soon, or right away with [`AppendOnlyFile::sync`].
+    pub fn append(&mut self, command: &Command) -> io::Result<()> {
+        writeln!(self.writer, "{}", command)?;
+        self.writer.flush()
+    }
+
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.writer.get_ref().sync_data()
+    }
+}
@@ -0,0 +1,103 @@
+use clap::Parser;
+use kv_store::protocol::{quote, Reply};
+use std::{
+    io::{self, BufRead, BufReader, IsTerminal, Write},
+    net::TcpStream,
+    process::ExitCode,
+};
+
+/// Sends commands to the key-value store, like `kv_client SET greeting "hello world"`, or reads
+/// them from stdin, one per line, without a command.
+#[derive(Parser)]
+struct Args {
+    #[arg(long, env = "KV_HOST", default_value = "127.0.0.1")]
+    host: String,
+    #[arg(long, env = "KV_PORT", default_value_t = 6380)]
+    port: u16,
+    /// A command and its arguments, each of which is sent as one word
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> io::Result<ExitCode> {
+    let stream = TcpStream::connect((args.host.as_str(), args.port))?;
+    let mut connection = Connection {
+        reader: BufReader::new(stream.try_clone()?),
+        writer: stream,
+    };
+
+    if !args.command.is_empty() {
+        let words: Vec<String> = args.command.iter().map(|word| quote(word)).collect();
+        let reply = connection.send(&words.join(" "))?;
+        println!("{}", format_reply(&reply));
+        let failed = matches!(reply, Reply::Error(_));
+        return Ok(if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    let interactive = io::stdin().is_terminal();
+    let prompt = format!("{}:{}> ", args.host, args.port);
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        if interactive {
+            print!("{}", prompt);
+            io::stdout().flush()?;
+        }
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(ExitCode::SUCCESS);
+        };
+        // The server doesn't answer blank lines.
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = connection.send(&line)?;
+        println!("{}", format_reply(&reply));
+        if line.trim().eq_ignore_ascii_case("quit") {
+            return Ok(ExitCode::SUCCESS);
+        }
+    }
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    fn send(&mut self, line: &str) -> io::Result<Reply> {
+        writeln!(self.writer, "{}", line)?;
+        let mut reply = String::new();
+        if self.reader.read_line(&mut reply)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the server closed the connection",
+            ));
+        }
+        Reply::parse(&reply).map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+    }
+}
+
+/// Shows replies the way `redis-cli` does.
+fn format_reply(reply: &Reply) -> String {
+    match reply {
+        Reply::Status(status) => status.clone(),
+        Reply::Integer(integer) => format!("(integer) {}", integer),
+        Reply::Value(value) => quote(value),
+        Reply::Nil => "(nil)".to_string(),
+        Reply::Error(message) => format!("(error) {}", message),
+    }
+}
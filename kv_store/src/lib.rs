@@ -0,0 +1,6 @@
+//! An in-memory key-value store, served over TCP with a line protocol like the one of Redis.
+
+pub mod aof;
+pub mod protocol;
+pub mod server;
+pub mod store;
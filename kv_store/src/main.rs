@@ -0,0 +1,70 @@
+use clap::Parser;
+use kv_store::{server, store::Store};
+use std::{net::IpAddr, path::PathBuf, sync::Arc, time::Duration};
+use tokio::net::TcpListener;
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
+
+/// How often expired keys are dropped and the append-only file is written to the disk.
+const HOUSEKEEPING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Serves an in-memory key-value store with GET, SET, DEL, EXPIRE and TTL commands.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The address to listen on
+    #[arg(long, env = "KV_BIND", default_value = "127.0.0.1")]
+    bind: IpAddr,
+    #[arg(long, env = "KV_PORT", default_value_t = 6380)]
+    port: u16,
+    /// Keep the keys in this append-only file, so they are still there after a restart
+    #[arg(long, env = "KV_AOF", value_name = "path")]
+    aof: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let args = Args::parse();
+
+    let store = match &args.aof {
+        Some(path) => Store::open(path).unwrap_or_else(|error| {
+            error!("Cannot load the append-only file: {}", error);
+            std::process::exit(1);
+        }),
+        None => Store::new(),
+    };
+    if args.aof.is_some() {
+        let key_count = store.len();
+        let suffix = if key_count == 1 { "" } else { "s" };
+        info!("Loaded {} key{}", key_count, suffix);
+    }
+    let store = Arc::new(store);
+
+    let housekeeping_store = store.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HOUSEKEEPING_INTERVAL);
+        loop {
+            interval.tick().await;
+            housekeeping_store.remove_expired();
+            if let Err(error) = housekeeping_store.sync() {
+                warn!("Cannot write the append-only file to the disk: {}", error);
+            }
+        }
+    });
+
+    let listener = TcpListener::bind((args.bind, args.port))
+        .await
+        .unwrap_or_else(|error| {
+            error!("Cannot listen on {}:{}: {}", args.bind, args.port, error);
+            std::process::exit(1);
+        });
+    info!("Listening on {}", listener.local_addr().unwrap());
+    server::serve(listener, store.clone(), common::shutdown::signal()).await;
+
+    if let Err(error) = store.sync() {
+        error!("Cannot write the append-only file to the disk: {}", error);
+    }
+    info!("Key-value store has shut down");
+}
@@ -0,0 +1,275 @@
+//! The line protocol: every command is a line of words, like `SET greeting "hello world" EX 60`,
+//! and is answered by a line with one reply. Words with spaces, quotes or line breaks are
+//! written in double quotes, with `\"`, `\\`, `\n`, `\r` and `\t` escapes.
+
+use std::{fmt, time::Duration};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Ping,
+    Get(String),
+    Set {
+        key: String,
+        value: String,
+        expire_after: Option<Duration>,
+    },
+    Del(Vec<String>),
+    Expire {
+        key: String,
+        after: Duration,
+    },
+    /// Expires a key at a time in milliseconds since the Unix epoch, which is how expirations
+    /// are kept in the append-only file, so they mean the same after a restart.
+    PexpireAt {
+        key: String,
+        at_millis: u64,
+    },
+    Ttl(String),
+    Quit,
+}
+
+impl Command {
+    /// Parses a line of the protocol, or `None` for a blank line. Command names are matched
+    /// ignoring case, like in Redis.
+    pub fn parse(line: &str) -> Result<Option<Command>, String> {
+        let words = split_words(line)?;
+        let Some((name, args)) = words.split_first() else {
+            return Ok(None);
+        };
+        let name = name.to_uppercase();
+        let arity_error = || format!("wrong number of arguments for '{}'", name.to_lowercase());
+        let command = match (name.as_str(), args) {
+            ("PING", []) => Command::Ping,
+            ("GET", [key]) => Command::Get(key.clone()),
+            ("SET", [key, value]) => Command::Set {
+                key: key.clone(),
+                value: value.clone(),
+                expire_after: None,
+            },
+            ("SET", [key, value, option, seconds]) if option.eq_ignore_ascii_case("EX") => {
+                Command::Set {
+                    key: key.clone(),
+                    value: value.clone(),
+                    expire_after: Some(parse_seconds(seconds)?),
+                }
+            }
+            ("SET", [_, _, option, _]) => return Err(format!("unknown option '{}'", option)),
+            ("DEL", keys) if !keys.is_empty() => Command::Del(keys.to_vec()),
+            ("EXPIRE", [key, seconds]) => Command::Expire {
+                key: key.clone(),
+                after: parse_seconds(seconds)?,
+            },
+            ("PEXPIREAT", [key, at_millis]) => Command::PexpireAt {
+                key: key.clone(),
+                at_millis: at_millis
+                    .parse()
+                    .map_err(|_| format!("invalid time '{}'", at_millis))?,
+            },
+            ("TTL", [key]) => Command::Ttl(key.clone()),
+            ("QUIT", []) => Command::Quit,
+            ("PING" | "GET" | "SET" | "DEL" | "EXPIRE" | "PEXPIREAT" | "TTL" | "QUIT", _) => {
+                return Err(arity_error())
+            }
+            _ => return Err(format!("unknown command '{}'", words[0])),
+        };
+        Ok(Some(command))
+    }
+}
+
+/// Writes the command as a line of the protocol, without the line break, which
+/// [`Command::parse`] reads back as the same command.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Ping => write!(f, "PING"),
+            Command::Get(key) => write!(f, "GET {}", quote(key)),
+            Command::Set {
+                key,
+                value,
+                expire_after,
+            } => {
+                write!(f, "SET {} {}", quote(key), quote(value))?;
+                match expire_after {
+                    Some(after) => write!(f, " EX {}", after.as_secs()),
+                    None => Ok(()),
+                }
+            }
+            Command::Del(keys) => {
+                write!(f, "DEL")?;
+                keys.iter().try_for_each(|key| write!(f, " {}", quote(key)))
+            }
+            Command::Expire { key, after } => {
+                write!(f, "EXPIRE {} {}", quote(key), after.as_secs())
+            }
+            Command::PexpireAt { key, at_millis } => {
+                write!(f, "PEXPIREAT {} {}", quote(key), at_millis)
+            }
+            Command::Ttl(key) => write!(f, "TTL {}", quote(key)),
+            Command::Quit => write!(f, "QUIT"),
+        }
+    }
+}
+
+fn parse_seconds(seconds: &str) -> Result<Duration, String> {
+    match seconds.parse() {
+        Ok(seconds) if seconds > 0 => Ok(Duration::from_secs(seconds)),
+        _ => Err(format!("invalid expire time '{}'", seconds)),
+    }
+}
+
+/// A reply line, which starts with a character that tells its kind: `+OK`, `:1`,
+/// `$"a value"`, `_` for no value and `-ERR message`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Status(String),
+    Integer(i64),
+    Value(String),
+    Nil,
+    Error(String),
+}
+
+impl Reply {
+    pub fn ok() -> Self {
+        Reply::Status("OK".to_string())
+    }
+
+    pub fn error(message: impl fmt::Display) -> Self {
+        Reply::Error(format!("ERR {}", message))
+    }
+
+    /// Parses a reply line, for clients.
+    pub fn parse(line: &str) -> Result<Reply, String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let invalid = || format!("invalid reply '{}'", line);
+        let mut chars = line.chars();
+        let reply = match chars.next() {
+            Some('+') => Reply::Status(chars.as_str().to_string()),
+            Some(':') => Reply::Integer(chars.as_str().parse().map_err(|_| invalid())?),
+            Some('$') => match split_words(chars.as_str())?.as_slice() {
+                [value] => Reply::Value(value.clone()),
+                _ => return Err(invalid()),
+            },
+            Some('_') if chars.as_str().is_empty() => Reply::Nil,
+            Some('-') => Reply::Error(chars.as_str().to_string()),
+            _ => return Err(invalid()),
+        };
+        Ok(reply)
+    }
+}
+
+impl fmt::Display for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reply::Status(status) => write!(f, "+{}", status),
+            Reply::Integer(integer) => write!(f, ":{}", integer),
+            Reply::Value(value) => write!(f, "${}", quote(value)),
+            Reply::Nil => write!(f, "_"),
+            // Line breaks would end the reply early.
+            Reply::Error(message) => write!(f, "-{}", message.replace(['\r', '\n'], " ")),
+        }
+    }
+}
+
+/// Splits a line into words at whitespace, taking words in double quotes as they're written.
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut chars = line.trim_end_matches(['\r', '\n']).chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            return Ok(words);
+        };
+        let mut word = String::new();
+        if first == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('"') => word.push('"'),
+                        Some('\\') => word.push('\\'),
+                        Some('n') => word.push('\n'),
+                        Some('r') => word.push('\r'),
+                        Some('t') => word.push('\t'),
+                        Some(c) => return Err(format!("unknown escape '\\{}'", c)),
+                        None => return Err("unterminated quotes".to_string()),
+                    },
+                    Some(c) => word.push(c),
+                    None => return Err("unterminated quotes".to_string()),
+                }
+            }
+            if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                return Err("quotes must be followed by a space".to_string());
+            }
+        } else {
+            word.push(first);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+        }
+        words.push(word);
+    }
+}
+
+/// Writes a word in double quotes, so [`split_words`] reads it back whatever it holds.
+pub fn quote(word: &str) -> String {
+    let mut quoted = String::with_capacity(word.len() + 2);
+    quoted.push('"');
+    for c in word.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands_with_quoted_words() {
+        let command = Command::parse(r#"set greeting "hello \"world\"\n" EX 60"#).unwrap();
+        let expected = Command::Set {
+            key: "greeting".to_string(),
+            value: "hello \"world\"\n".to_string(),
+            expire_after: Some(Duration::from_secs(60)),
+        };
+        assert_eq!(command, Some(expected.clone()));
+        // Commands are written the way they're parsed, for the append-only file.
+        assert_eq!(
+            Command::parse(&expected.to_string()).unwrap(),
+            Some(expected)
+        );
+
+        assert_eq!(Command::parse("  \r\n").unwrap(), None);
+        assert_eq!(
+            Command::parse("GET").unwrap_err(),
+            "wrong number of arguments for 'get'"
+        );
+        assert!(Command::parse("SET a b EX 0").is_err());
+        assert!(Command::parse(r#"GET "open"#).is_err());
+        assert!(Command::parse(r#"GET "a"b"#).is_err());
+        assert!(Command::parse("FLUSHALL").is_err());
+    }
+
+    #[test]
+    fn writes_and_parses_replies() {
+        let replies = [
+            Reply::ok(),
+            Reply::Integer(-2),
+            Reply::Value("two\nlines".to_string()),
+            Reply::Nil,
+            Reply::error("unknown command 'FLUSHALL'"),
+        ];
+        for reply in replies {
+            assert_eq!(Reply::parse(&reply.to_string()).unwrap(), reply);
+        }
+        assert!(Reply::parse("hello").is_err());
+    }
+}
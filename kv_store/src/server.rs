@@ -0,0 +1,75 @@
+//! Serving the store over TCP, with a task for every client.
+
+use crate::{
+    protocol::{Command, Reply},
+    store::Store,
+};
+use std::{future::Future, io, sync::Arc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{debug, info, warn};
+
+/// The longest line a client may send, so a client can't make the server read forever.
+pub const MAX_LINE_LENGTH: u64 = 1024 * 1024;
+
+/// Answers clients until `shutdown` resolves.
+pub async fn serve(listener: TcpListener, store: Arc<Store>, shutdown: impl Future<Output = ()>) {
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, address) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(error) => {
+                    // Like running out of file descriptors, which passes once clients leave.
+                    warn!("Cannot accept a connection: {}", error);
+                    continue;
+                }
+            },
+            () = &mut shutdown => break,
+        };
+        debug!(%address, "Client connected");
+        let store = store.clone();
+        tokio::spawn(async move {
+            if let Err(error) = handle_client(stream, &store).await {
+                debug!(%address, "Connection failed: {}", error);
+            }
+            debug!(%address, "Client disconnected");
+        });
+    }
+    info!("No longer accepting connections");
+}
+
+async fn handle_client(stream: TcpStream, store: &Store) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = (&mut reader)
+            .take(MAX_LINE_LENGTH + 1)
+            .read_line(&mut line)
+            .await;
+        let (reply, quit) = match read {
+            Ok(0) => return Ok(()),
+            Ok(_) if !line.ends_with('\n') && line.len() as u64 > MAX_LINE_LENGTH => {
+                (Reply::error("line too long"), true)
+            }
+            Ok(_) => match Command::parse(&line) {
+                Ok(Some(Command::Quit)) => (Reply::ok(), true),
+                Ok(Some(command)) => (store.execute(command), false),
+                Ok(None) => continue,
+                Err(message) => (Reply::error(message), false),
+            },
+            Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                (Reply::error("lines must be UTF-8"), true)
+            }
+            Err(error) => return Err(error),
+        };
+        writer.write_all(format!("{}\n", reply).as_bytes()).await?;
+        if quit {
+            return Ok(());
+        }
+    }
+}
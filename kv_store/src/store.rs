@@ -0,0 +1,321 @@
+//! The keys and values, in hash maps that are each behind a lock of their own, so clients using
+//! different keys rarely wait for each other.
+
+use crate::{
+    aof::AppendOnlyFile,
+    protocol::{Command, Reply},
+};
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    io,
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Enough shards for the threads of most machines to work on different ones most of the time.
+const SHARD_COUNT: usize = 16;
+
+struct Entry {
+    value: String,
+    /// A time rather than an `Instant`, so expirations in the append-only file still hold
+    /// after a restart.
+    expires_at: Option<SystemTime>,
+}
+
+impl Entry {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+type Shard = HashMap<String, Entry>;
+
+pub struct Store {
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+    log: Option<Mutex<AppendOnlyFile>>,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Store::new()
+    }
+}
+
+impl Store {
+    /// A store that's only kept in memory.
+    pub fn new() -> Self {
+        Store {
+            shards: (0..SHARD_COUNT).map(|_| Mutex::default()).collect(),
+            hasher: RandomState::new(),
+            log: None,
+        }
+    }
+
+    /// Loads the append-only file at `path`, if there is one, and appends every change to it.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut store = Store::new();
+        for command in AppendOnlyFile::read(path)? {
+            store.execute(command);
+        }
+        // The file starts over with just the keys that are left, so it doesn't keep growing
+        // with the changes of every run.
+        let log = AppendOnlyFile::rewrite(path, store.snapshot())?;
+        store.log = Some(Mutex::new(log));
+        Ok(store)
+    }
+
+    /// Runs a command, which fails if it can't be written to the append-only file.
+    pub fn execute(&self, command: Command) -> Reply {
+        let now = SystemTime::now();
+        let result = match command {
+            Command::Ping => return Reply::Status("PONG".to_string()),
+            Command::Quit => return Reply::ok(),
+            Command::Get(key) => return self.get(&key, now).map_or(Reply::Nil, Reply::Value),
+            Command::Ttl(key) => return Reply::Integer(self.ttl(&key, now)),
+            Command::Set {
+                key,
+                value,
+                expire_after,
+            } => {
+                let expires_at = expire_after.map(|after| now + after);
+                self.set(key, value, expires_at).map(|()| Reply::ok())
+            }
+            Command::Del(keys) => self
+                .delete(&keys, now)
+                .map(|count| Reply::Integer(count as i64)),
+            Command::Expire { key, after } => self.expire(&key, now + after, now),
+            Command::PexpireAt { key, at_millis } => {
+                let at = UNIX_EPOCH + Duration::from_millis(at_millis);
+                self.expire(&key, at, now)
+            }
+        };
+        result.unwrap_or_else(|error| {
+            Reply::error(format!("can't write the append-only file: {}", error))
+        })
+    }
+
+    fn get(&self, key: &str, now: SystemTime) -> Option<String> {
+        let mut shard = self.shard(key);
+        match shard.get(key) {
+            Some(entry) if entry.is_expired(now) => {
+                shard.remove(key);
+                None
+            }
+            entry => entry.map(|entry| entry.value.clone()),
+        }
+    }
+
+    fn set(&self, key: String, value: String, expires_at: Option<SystemTime>) -> io::Result<()> {
+        let mut shard = self.shard(&key);
+        // Written while the shard is locked, so the file has the changes of a key in the order
+        // they were made.
+        self.append(&Command::Set {
+            key: key.clone(),
+            value: value.clone(),
+            expire_after: None,
+        })?;
+        if let Some(expires_at) = expires_at {
+            self.append(&pexpire_at(&key, expires_at))?;
+        }
+        shard.insert(key, Entry { value, expires_at });
+        Ok(())
+    }
+
+    /// Returns how many of the keys there were.
+    fn delete(&self, keys: &[String], now: SystemTime) -> io::Result<usize> {
+        let mut count = 0;
+        for key in keys {
+            let mut shard = self.shard(key);
+            let Some(entry) = shard.get(key) else {
+                continue;
+            };
+            if !entry.is_expired(now) {
+                self.append(&Command::Del(vec![key.clone()]))?;
+                count += 1;
+            }
+            shard.remove(key);
+        }
+        Ok(count)
+    }
+
+    /// Answers 1 if the key exists, and 0 if it doesn't.
+    fn expire(&self, key: &str, at: SystemTime, now: SystemTime) -> io::Result<Reply> {
+        let mut shard = self.shard(key);
+        match shard.get_mut(key) {
+            Some(entry) if !entry.is_expired(now) => {
+                self.append(&pexpire_at(key, at))?;
+                entry.expires_at = Some(at);
+                Ok(Reply::Integer(1))
+            }
+            _ => Ok(Reply::Integer(0)),
+        }
+    }
+
+    /// The seconds until the key expires, rounded up, -1 if it doesn't expire and -2 if it
+    /// doesn't exist, like in Redis.
+    fn ttl(&self, key: &str, now: SystemTime) -> i64 {
+        let shard = self.shard(key);
+        match shard.get(key) {
+            Some(entry) if entry.is_expired(now) => -2,
+            Some(Entry {
+                expires_at: Some(expires_at),
+                ..
+            }) => {
+                let left = expires_at.duration_since(now).unwrap_or_default();
+                left.as_millis().div_ceil(1000) as i64
+            }
+            Some(_) => -1,
+            None => -2,
+        }
+    }
+
+    /// Drops the expired keys that nobody asked for since, returning how many there were.
+    pub fn remove_expired(&self) -> usize {
+        let now = SystemTime::now();
+        let mut count = 0;
+        for shard in &self.shards {
+            let mut shard = shard.lock().unwrap_or_else(PoisonError::into_inner);
+            let key_count = shard.len();
+            shard.retain(|_, entry| !entry.is_expired(now));
+            count += key_count - shard.len();
+        }
+        count
+    }
+
+    /// Writes what was appended to the disk, so not more than what changed since is lost if
+    /// the machine goes down.
+    pub fn sync(&self) -> io::Result<()> {
+        match &self.log {
+            Some(log) => log.lock().unwrap_or_else(PoisonError::into_inner).sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// How many keys there are, expired ones that weren't removed yet included.
+    pub fn len(&self) -> usize {
+        let shards = self.shards.iter();
+        shards
+            .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner).len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The commands that set the keys as they are now.
+    fn snapshot(&self) -> Vec<Command> {
+        let now = SystemTime::now();
+        let mut commands = Vec::new();
+        for shard in &self.shards {
+            let shard = shard.lock().unwrap_or_else(PoisonError::into_inner);
+            for (key, entry) in shard.iter().filter(|(_, entry)| !entry.is_expired(now)) {
+                commands.push(Command::Set {
+                    key: key.clone(),
+                    value: entry.value.clone(),
+                    expire_after: None,
+                });
+                if let Some(expires_at) = entry.expires_at {
+                    commands.push(pexpire_at(key, expires_at));
+                }
+            }
+        }
+        commands
+    }
+
+    fn shard(&self, key: &str) -> MutexGuard<'_, Shard> {
+        let index = self.hasher.hash_one(key) as usize % SHARD_COUNT;
+        // A client that panicked can't have left a map half changed.
+        self.shards[index]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn append(&self, command: &Command) -> io::Result<()> {
+        match &self.log {
+            Some(log) => log
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .append(command),
+            None => Ok(()),
+        }
+    }
+}
+
+fn pexpire_at(key: &str, at: SystemTime) -> Command {
+    let at_millis = at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    Command::PexpireAt {
+        key: key.to_string(),
+        at_millis: at_millis as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    fn run(store: &Store, line: &str) -> Reply {
+        store.execute(Command::parse(line).unwrap().unwrap())
+    }
+
+    #[test]
+    fn expires_keys() {
+        let store = Store::new();
+        assert_eq!(run(&store, "SET a 1"), Reply::ok());
+        assert_eq!(run(&store, "TTL a"), Reply::Integer(-1));
+        assert_eq!(run(&store, "EXPIRE a 60"), Reply::Integer(1));
+        assert_eq!(run(&store, "TTL a"), Reply::Integer(60));
+        assert_eq!(run(&store, "EXPIRE missing 60"), Reply::Integer(0));
+
+        assert_eq!(run(&store, "SET b 2"), Reply::ok());
+        // A time in the past expires the key right away.
+        assert_eq!(run(&store, "PEXPIREAT b 1"), Reply::Integer(1));
+        assert_eq!(run(&store, "GET b"), Reply::Nil);
+        assert_eq!(run(&store, "TTL b"), Reply::Integer(-2));
+        assert_eq!(run(&store, "DEL a b"), Reply::Integer(1));
+    }
+
+    #[test]
+    fn replays_the_append_only_file() {
+        let dir = env::temp_dir().join(format!("kv-store-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("store.aof");
+
+        let store = Store::open(&path).unwrap();
+        run(&store, r#"SET greeting "hello world""#);
+        run(&store, "SET counter 1");
+        run(&store, "SET counter 2 EX 3600");
+        run(&store, "SET gone soon");
+        run(&store, "DEL gone");
+        drop(store);
+        // A write that was cut off.
+        let mut contents = fs::read_to_string(&path).unwrap();
+        contents.push_str("SET half");
+        fs::write(&path, contents).unwrap();
+
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(
+            run(&store, "GET greeting"),
+            Reply::Value("hello world".to_string())
+        );
+        assert_eq!(run(&store, "GET counter"), Reply::Value("2".to_string()));
+        assert_eq!(run(&store, "TTL counter"), Reply::Integer(3600));
+        // Reopening leaves the file with just what's needed for the keys that are left.
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        fs::write(&path, "SET a\nSET b 1\n").unwrap();
+        let error = Store::open(&path).err().unwrap();
+        assert!(error
+            .to_string()
+            .ends_with("line 1: wrong number of arguments for 'set'"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+//! Runs the server on a free port and talks to it over TCP, the way clients do.
+
+use kv_store::{
+    protocol::Reply,
+    server::{self, MAX_LINE_LENGTH},
+    store::Store,
+};
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{tcp::OwnedReadHalf, tcp::OwnedWriteHalf, TcpListener, TcpStream},
+};
+
+struct Client {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl Client {
+    async fn connect(store: Arc<Store>) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(server::serve(listener, store, std::future::pending()));
+        let (reader, writer) = TcpStream::connect(address).await.unwrap().into_split();
+        Client {
+            reader: BufReader::new(reader),
+            writer,
+        }
+    }
+
+    async fn send(&mut self, line: &str) -> Reply {
+        self.writer.write_all(line.as_bytes()).await.unwrap();
+        self.writer.write_all(b"\n").await.unwrap();
+        let mut reply = String::new();
+        self.reader.read_line(&mut reply).await.unwrap();
+        Reply::parse(&reply).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn answers_commands() {
+    let mut client = Client::connect(Arc::new(Store::new())).await;
+    let value = |value: &str| Reply::Value(value.to_string());
+
+    assert_eq!(client.send("PING").await, Reply::Status("PONG".into()));
+    assert_eq!(
+        client.send(r#"SET greeting "hello world""#).await,
+        Reply::ok()
+    );
+    assert_eq!(client.send("get greeting").await, value("hello world"));
+    assert_eq!(client.send("GET missing").await, Reply::Nil);
+    assert_eq!(client.send("EXPIRE greeting 10").await, Reply::Integer(1));
+    assert_eq!(client.send("TTL greeting").await, Reply::Integer(10));
+    assert_eq!(client.send("DEL greeting missing").await, Reply::Integer(1));
+    // Blank lines aren't answered.
+    assert_eq!(client.send("\nGET greeting").await, Reply::Nil);
+    assert_eq!(
+        client.send("INCR counter").await,
+        Reply::error("unknown command 'INCR'")
+    );
+    assert_eq!(client.send("QUIT").await, Reply::ok());
+    let mut rest = String::new();
+    assert_eq!(client.reader.read_line(&mut rest).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn shares_keys_between_clients_and_refuses_long_lines() {
+    let store = Arc::new(Store::new());
+    let mut first = Client::connect(store.clone()).await;
+    let mut second = Client::connect(store).await;
+
+    first.send("SET shared 1").await;
+    assert_eq!(
+        second.send("GET shared").await,
+        Reply::Value("1".to_string())
+    );
+
+    let long_value = "x".repeat(MAX_LINE_LENGTH as usize);
+    let reply = first.send(&format!("SET long {}", long_value)).await;
+    assert_eq!(reply, Reply::error("line too long"));
+    assert_eq!(second.send("GET long").await, Reply::Nil);
+}
@@ -0,0 +1,759 @@
+//! The blocks of a document, like headings, paragraphs, lists and code, with the text inside
+//! them left for [`inline`](crate::inline). This follows CommonMark and the tables and task
+//! lists of GitHub, in a simpler way that gets the documents people write right, if not every
+//! corner case of the specification.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Heading {
+        level: u8,
+        text: String,
+    },
+    Paragraph(String),
+    Code {
+        language: Option<String>,
+        code: String,
+    },
+    Quote(Vec<Block>),
+    List(List),
+    Table(Table),
+    Rule,
+    /// Written to the output as it is.
+    Html(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct List {
+    /// The number of the first item of ordered lists, `None` for bullet lists.
+    pub start: Option<u64>,
+    /// Without blank lines between the items or their blocks, in which case their paragraphs
+    /// aren't wrapped in `<p>`.
+    pub tight: bool,
+    pub items: Vec<ListItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    /// Whether the box of a task list item, like `- [x] Done`, is checked.
+    pub checked: Option<bool>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub alignments: Vec<Alignment>,
+    pub header: Vec<String>,
+    /// With as many cells as the header.
+    pub rows: Vec<Vec<String>>,
+}
+
+/// The target of links like `[text][label]`, from a definition like `[label]: url "title"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+pub struct Parsed {
+    pub blocks: Vec<Block>,
+    /// By [`normalize_label`].
+    pub references: HashMap<String, Reference>,
+}
+
+pub fn parse(markdown: &str) -> Parsed {
+    let lines: Vec<String> = markdown.lines().map(expand_tabs).collect();
+    let mut parser = Parser {
+        references: HashMap::new(),
+    };
+    let (blocks, _) = parser.blocks(&lines);
+    Parsed {
+        blocks,
+        references: parser.references,
+    }
+}
+
+/// Labels are matched ignoring case and how much whitespace there is.
+pub fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+struct Parser {
+    references: HashMap<String, Reference>,
+}
+
+impl Parser {
+    /// Parses lines into blocks, returning also whether blank lines separate some of them,
+    /// which makes the list item they're in loose.
+    fn blocks(&mut self, lines: &[String]) -> (Vec<Block>, bool) {
+        let mut blocks = Vec::new();
+        let mut blank_between = false;
+        let mut after_blank = false;
+        let mut i = 0;
+        while i < lines.len() {
+            if is_blank(&lines[i]) {
+                after_blank = true;
+                i += 1;
+                continue;
+            }
+            if let Some(block) = self.block(lines, &mut i) {
+                blank_between |= after_blank && !blocks.is_empty();
+                blocks.push(block);
+            }
+            after_blank = false;
+        }
+        (blocks, blank_between)
+    }
+
+    /// Parses the block starting at the line `i`, which isn't blank, moving `i` past it. `None`
+    /// for paragraphs that only defined references.
+    fn block(&mut self, lines: &[String], i: &mut usize) -> Option<Block> {
+        let line = &lines[*i];
+        let indent = indent(line);
+        let rest = &line[indent..];
+
+        if indent >= 4 {
+            return Some(indented_code(lines, i));
+        }
+        if let Some(fence) = Fence::start(line) {
+            return Some(fence.code(lines, i));
+        }
+        if let Some((level, text)) = atx_heading(line) {
+            *i += 1;
+            return Some(Block::Heading { level, text });
+        }
+        if is_thematic_break(line) {
+            *i += 1;
+            return Some(Block::Rule);
+        }
+        if rest.starts_with('>') {
+            return Some(Block::Quote(self.quote(lines, i)));
+        }
+        if let Some(marker) = ListMarker::parse(line) {
+            return Some(Block::List(self.list(lines, i, marker)));
+        }
+        if is_html_block_start(rest) {
+            let start = *i;
+            while *i < lines.len() && !is_blank(&lines[*i]) {
+                *i += 1;
+            }
+            return Some(Block::Html(lines[start..*i].join("\n")));
+        }
+        if let Some(table) = table(lines, i) {
+            return Some(Block::Table(table));
+        }
+        self.paragraph(lines, i)
+    }
+
+    /// Lines starting with `>`, and the lines that continue their paragraphs without it.
+    fn quote(&mut self, lines: &[String], i: &mut usize) -> Vec<Block> {
+        let mut quoted: Vec<String> = Vec::new();
+        while let Some(line) = lines.get(*i) {
+            let indent = indent(line);
+            if indent <= 3 && line[indent..].starts_with('>') {
+                let content = &line[indent + 1..];
+                quoted.push(content.strip_prefix(' ').unwrap_or(content).to_string());
+            } else if !is_blank(line)
+                && quoted.last().is_some_and(|last| !is_blank(last))
+                && !starts_block(line)
+            {
+                quoted.push(line.to_string());
+            } else {
+                break;
+            }
+            *i += 1;
+        }
+        self.blocks(&quoted).0
+    }
+
+    fn list(&mut self, lines: &[String], i: &mut usize, mut marker: ListMarker) -> List {
+        let start = marker.ordered.then_some(marker.start);
+        let mut items = Vec::new();
+        let mut loose = false;
+        loop {
+            let first = match marker.is_empty {
+                true => String::new(),
+                false => lines[*i][marker.content_offset..].to_string(),
+            };
+            let mut item_lines = vec![first];
+            *i += 1;
+            // The item goes on with the lines indented as far as its content, and the lines
+            // that continue its last paragraph.
+            while let Some(line) = lines.get(*i) {
+                if is_blank(line) {
+                    item_lines.push(String::new());
+                } else if indent(line) >= marker.content_offset {
+                    item_lines.push(line[marker.content_offset..].to_string());
+                } else if item_lines.last().is_some_and(|last| is_blank(last))
+                    || starts_block(line)
+                    || ListMarker::parse(line).is_some()
+                {
+                    break;
+                } else {
+                    item_lines.push(line.trim_start().to_string());
+                }
+                *i += 1;
+            }
+            let mut trailing_blanks = 0;
+            while item_lines.len() > 1 && item_lines.last().is_some_and(|last| is_blank(last)) {
+                item_lines.pop();
+                trailing_blanks += 1;
+            }
+
+            let checked = task_marker(&mut item_lines[0]);
+            let (blocks, blank_between) = self.blocks(&item_lines);
+            loose |= blank_between;
+            items.push(ListItem { checked, blocks });
+
+            let next = lines
+                .get(*i)
+                .filter(|line| !is_thematic_break(line))
+                .and_then(|line| ListMarker::parse(line));
+            match next {
+                Some(next) if next.ordered == marker.ordered && next.kind == marker.kind => {
+                    loose |= trailing_blanks > 0;
+                    marker = next;
+                }
+                _ => {
+                    // The blank lines after the list are left to the blocks it's in, which
+                    // they may make loose.
+                    *i -= trailing_blanks;
+                    break;
+                }
+            }
+        }
+        List {
+            start,
+            tight: !loose,
+            items,
+        }
+    }
+
+    fn paragraph(&mut self, lines: &[String], i: &mut usize) -> Option<Block> {
+        let mut text_lines = vec![lines[*i].trim_start()];
+        *i += 1;
+        while let Some(line) = lines.get(*i) {
+            if is_blank(line) {
+                break;
+            }
+            // A line of `=` or `-` under a paragraph makes it a heading.
+            if let Some(level) = setext_level(line) {
+                *i += 1;
+                let text = text_lines.join("\n").trim().to_string();
+                return Some(Block::Heading { level, text });
+            }
+            if starts_block(line) {
+                break;
+            }
+            text_lines.push(line.trim_start());
+            *i += 1;
+        }
+        let text_lines = self.take_definitions(text_lines);
+        if text_lines.is_empty() {
+            return None;
+        }
+        let text = text_lines.join("\n");
+        // Trailing spaces would be a line break that ends the paragraph.
+        Some(Block::Paragraph(text.trim_end().to_string()))
+    }
+
+    /// Takes the reference definitions at the start of a paragraph, returning the rest of it.
+    /// Of several definitions of a label, the first one counts.
+    fn take_definitions<'a>(&mut self, lines: Vec<&'a str>) -> Vec<&'a str> {
+        let mut rest = lines.as_slice();
+        while let Some((label, reference)) = rest.first().and_then(|line| definition(line)) {
+            self.references.entry(label).or_insert(reference);
+            rest = &rest[1..];
+        }
+        rest.to_vec()
+    }
+}
+
+/// Lines indented by four spaces or more, with blank lines between them.
+fn indented_code(lines: &[String], i: &mut usize) -> Block {
+    let mut code_lines = Vec::new();
+    while let Some(line) = lines.get(*i) {
+        if !is_blank(line) && indent(line) < 4 {
+            break;
+        }
+        code_lines.push(strip_indent(line, 4));
+        *i += 1;
+    }
+    while code_lines.last().is_some_and(|line| is_blank(line)) {
+        code_lines.pop();
+    }
+    Block::Code {
+        language: None,
+        code: code_lines
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect(),
+    }
+}
+
+/// The opening line of a fenced code block, like ```` ```rust ````.
+struct Fence {
+    character: char,
+    length: usize,
+    indent: usize,
+    language: Option<String>,
+}
+
+impl Fence {
+    fn start(line: &str) -> Option<Fence> {
+        let indent = indent(line);
+        if indent > 3 {
+            return None;
+        }
+        let rest = &line[indent..];
+        let character = rest.chars().next().filter(|&c| c == '`' || c == '~')?;
+        let length = rest.chars().take_while(|&c| c == character).count();
+        let info = rest[length..].trim();
+        // Backticks in the info string would make it a code span.
+        if length < 3 || (character == '`' && info.contains('`')) {
+            return None;
+        }
+        let language = info.split_whitespace().next().map(str::to_string);
+        Some(Fence {
+            character,
+            length,
+            indent,
+            language,
+        })
+    }
+
+    /// The lines up to the closing fence, or to the end without one.
+    fn code(self, lines: &[String], i: &mut usize) -> Block {
+        let mut code = String::new();
+        *i += 1;
+        while let Some(line) = lines.get(*i) {
+            *i += 1;
+            let indent = indent(line);
+            let rest = line[indent..].trim_end();
+            let is_closing = indent <= 3
+                && rest.len() >= self.length
+                && rest.chars().all(|c| c == self.character);
+            if is_closing {
+                break;
+            }
+            code.push_str(strip_indent(line, self.indent));
+            code.push('\n');
+        }
+        Block::Code {
+            language: self.language,
+            code,
+        }
+    }
+}
+
+/// A heading like `## Title`, or `## Title ##`.
+fn atx_heading(line: &str) -> Option<(u8, String)> {
+    let indent = indent(line);
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let level = rest.chars().take_while(|&c| c == '#').count();
+    let text = &rest[level..];
+    if !(1..=6).contains(&level) || !(text.is_empty() || text.starts_with(' ')) {
+        return None;
+    }
+    let text = text.trim();
+    // The closing `#`s, which need a space before them.
+    let without_closing = text.trim_end_matches('#');
+    let text = match without_closing.is_empty() || without_closing.ends_with(' ') {
+        true => without_closing.trim_end(),
+        false => text,
+    };
+    Some((level as u8, text.to_string()))
+}
+
+/// Three or more `-`, `*` or `_`, with spaces between them or not.
+fn is_thematic_break(line: &str) -> bool {
+    if indent(line) > 3 {
+        return false;
+    }
+    let mut characters = line.chars().filter(|c| !c.is_whitespace());
+    let Some(first) = characters.next().filter(|c| matches!(c, '-' | '*' | '_')) else {
+        return false;
+    };
+    let mut count = 1;
+    for c in characters {
+        if c != first {
+            return false;
+        }
+        count += 1;
+    }
+    count >= 3
+}
+
+fn setext_level(line: &str) -> Option<u8> {
+    let text = line.trim();
+    if indent(line) > 3 || text.is_empty() {
+        return None;
+    }
+    if text.chars().all(|c| c == '=') {
+        Some(1)
+    } else if text.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Whether a line starts a block that ends a paragraph before it, which lines indented as
+/// code and lists that don't start with 1 don't.
+fn starts_block(line: &str) -> bool {
+    let rest = line.trim_start();
+    indent(line) <= 3
+        && (Fence::start(line).is_some()
+            || atx_heading(line).is_some()
+            || is_thematic_break(line)
+            || rest.starts_with('>')
+            || is_html_block_start(rest)
+            || ListMarker::parse(line).is_some_and(|m| !m.is_empty && (!m.ordered || m.start == 1)))
+}
+
+/// Like `<div>`, `</section>` or `<!--`, but not an autolink like `<https://example.com>`.
+fn is_html_block_start(text: &str) -> bool {
+    let Some(tag) = text.strip_prefix('<') else {
+        return false;
+    };
+    if tag.starts_with("!--") {
+        return true;
+    }
+    let tag = tag.strip_prefix('/').unwrap_or(tag);
+    let name_len = tag
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+        .unwrap_or(tag.len());
+    let after = &tag[name_len..];
+    name_len > 0
+        && tag.starts_with(|c: char| c.is_ascii_alphabetic())
+        && (after.is_empty()
+            || after.starts_with('>')
+            || after.starts_with("/>")
+            || after.starts_with(char::is_whitespace))
+}
+
+/// A marker of a list item, like `-`, `*`, `+`, `1.` or `1)`.
+struct ListMarker {
+    ordered: bool,
+    /// The bullet, or the `.` or `)` after the number, which have to be the same for items of
+    /// the same list.
+    kind: char,
+    start: u64,
+    /// Where the content of the item starts, which where its other lines have to be indented.
+    content_offset: usize,
+    is_empty: bool,
+}
+
+impl ListMarker {
+    fn parse(line: &str) -> Option<ListMarker> {
+        let indent = indent(line);
+        if indent > 3 {
+            return None;
+        }
+        let rest = &line[indent..];
+        let (marker_len, ordered, kind, start) = match rest.chars().next()? {
+            bullet @ ('-' | '*' | '+') => (1, false, bullet, 0),
+            _ => {
+                let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                let delimiter = rest[digits..].chars().next()?;
+                if !(1..=9).contains(&digits) || !matches!(delimiter, '.' | ')') {
+                    return None;
+                }
+                (digits + 1, true, delimiter, rest[..digits].parse().ok()?)
+            }
+        };
+        let after = &rest[marker_len..];
+        if after.trim().is_empty() {
+            return Some(ListMarker {
+                ordered,
+                kind,
+                start,
+                content_offset: indent + marker_len + 1,
+                is_empty: true,
+            });
+        }
+        if !after.starts_with(' ') {
+            return None;
+        }
+        // Content indented further is indented code, which starts a space after the marker.
+        let spaces = match self::indent(after) {
+            spaces if spaces > 4 => 1,
+            spaces => spaces,
+        };
+        Some(ListMarker {
+            ordered,
+            kind,
+            start,
+            content_offset: indent + marker_len + spaces,
+            is_empty: false,
+        })
+    }
+}
+
+/// Takes the box of a task list item off its first line, like `[x] ` for a checked one.
+fn task_marker(line: &mut String) -> Option<bool> {
+    let checked = match line.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    if line.len() > 3 && !line[3..].starts_with(' ') {
+        return None;
+    }
+    line.replace_range(..line.len().min(4), "");
+    Some(checked)
+}
+
+/// A table like GitHub's, with a header row, a row of delimiters like `| :--- | ---: |` for
+/// the alignments, and rows up to a blank line.
+fn table(lines: &[String], i: &mut usize) -> Option<Table> {
+    let header_line = &lines[*i];
+    let alignments = lines.get(*i + 1).and_then(|line| delimiter_row(line))?;
+    let header = split_row(header_line);
+    if !header_line.contains('|') || header.len() != alignments.len() {
+        return None;
+    }
+    *i += 2;
+    let mut rows = Vec::new();
+    while let Some(line) = lines.get(*i) {
+        if is_blank(line) || starts_block(line) {
+            break;
+        }
+        let mut cells = split_row(line);
+        cells.resize(header.len(), String::new());
+        rows.push(cells);
+        *i += 1;
+    }
+    Some(Table {
+        alignments,
+        header,
+        rows,
+    })
+}
+
+fn delimiter_row(line: &str) -> Option<Vec<Alignment>> {
+    if !line.contains('-') || indent(line) > 3 {
+        return None;
+    }
+    split_row(line)
+        .iter()
+        .map(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Alignment::Center,
+                (true, false) => Alignment::Left,
+                (false, true) => Alignment::Right,
+                (false, false) => Alignment::None,
+            })
+        })
+        .collect()
+}
+
+/// The cells of a row, split at the `|`s that aren't escaped as `\|`.
+fn split_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(stripped) if !stripped.ends_with('\\') => stripped,
+        _ => line,
+    };
+    let mut cells = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                chars.next();
+                cells.last_mut().unwrap().push('|');
+            }
+            '|' => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.iter().map(|cell| cell.trim().to_string()).collect()
+}
+
+/// A reference definition like `[label]: https://example.com "title"`, on one line.
+fn definition(line: &str) -> Option<(String, Reference)> {
+    let rest = line.strip_prefix('[')?;
+    let (label, rest) = rest.split_once("]:")?;
+    if label.trim().is_empty() || label.contains(['[', ']']) {
+        return None;
+    }
+    let rest = rest.trim();
+    let (url, rest) = match rest.strip_prefix('<') {
+        Some(bracketed) => bracketed.split_once('>')?,
+        None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+    };
+    if url.is_empty() {
+        return None;
+    }
+    let rest = rest.trim();
+    let title = match rest.chars().next() {
+        None => None,
+        Some(open @ ('"' | '\'' | '(')) => {
+            let close = if open == '(' { ')' } else { open };
+            let title = rest[1..].strip_suffix(close)?;
+            Some(title.to_string())
+        }
+        Some(_) => return None,
+    };
+    let reference = Reference {
+        url: url.to_string(),
+        title,
+    };
+    Some((normalize_label(label), reference))
+}
+
+/// Replaces the tabs in the indentation of a line by spaces, up to the next multiple of 4.
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(c) = rest.chars().next().filter(|&c| c == ' ' || c == '\t') {
+        match c {
+            '\t' => expanded.push_str(&" ".repeat(4 - expanded.len() % 4)),
+            _ => expanded.push(' '),
+        }
+        rest = &rest[1..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn strip_indent(line: &str, max: usize) -> &str {
+    &line[indent(line).min(max)..]
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paragraph(text: &str) -> Block {
+        Block::Paragraph(text.to_string())
+    }
+
+    #[test]
+    fn parses_nested_blocks() {
+        let parsed = parse(
+            "# Title #\n\nSome *text*\nover two lines.\n\n> quoted\nlazily\n>\n> - in a list\n\n\
+             1. first\n2. second\n   ```rust\n   let x = 1;\n\n   ```\n   - [x] done\n\nafter\n\n\
+             \x20   indented code\n",
+        );
+        let expected = vec![
+            Block::Heading {
+                level: 1,
+                text: "Title".to_string(),
+            },
+            paragraph("Some *text*\nover two lines."),
+            Block::Quote(vec![
+                paragraph("quoted\nlazily"),
+                Block::List(List {
+                    start: None,
+                    tight: true,
+                    items: vec![ListItem {
+                        checked: None,
+                        blocks: vec![paragraph("in a list")],
+                    }],
+                }),
+            ]),
+            Block::List(List {
+                start: Some(1),
+                tight: true,
+                items: vec![
+                    ListItem {
+                        checked: None,
+                        blocks: vec![paragraph("first")],
+                    },
+                    ListItem {
+                        checked: None,
+                        blocks: vec![
+                            paragraph("second"),
+                            Block::Code {
+                                language: Some("rust".to_string()),
+                                code: "let x = 1;\n\n".to_string(),
+                            },
+                            Block::List(List {
+                                start: None,
+                                tight: true,
+                                items: vec![ListItem {
+                                    checked: Some(true),
+                                    blocks: vec![paragraph("done")],
+                                }],
+                            }),
+                        ],
+                    },
+                ],
+            }),
+            paragraph("after"),
+            Block::Code {
+                language: None,
+                code: "indented code\n".to_string(),
+            },
+        ];
+        assert_eq!(parsed.blocks, expected);
+
+        let loose = parse("- a\n\n- b\n");
+        assert!(matches!(&loose.blocks[0], Block::List(list) if !list.tight));
+        assert_eq!(parse("* * *\n").blocks, vec![Block::Rule]);
+    }
+
+    #[test]
+    fn parses_tables_setext_headings_and_references() {
+        let parsed = parse(
+            "Title\n=====\n\n| Name | Size |\n| :--- | ---: |\n| a \\| b | 1 |\n| c |\n\n\
+             [Docs]: https://example.com/docs \"The docs\"\n[docs]: https://example.com/other\n\
+             See [the docs][docs].\n",
+        );
+        assert_eq!(
+            parsed.blocks,
+            vec![
+                Block::Heading {
+                    level: 1,
+                    text: "Title".to_string(),
+                },
+                Block::Table(Table {
+                    alignments: vec![Alignment::Left, Alignment::Right],
+                    header: vec!["Name".to_string(), "Size".to_string()],
+                    rows: vec![
+                        vec!["a | b".to_string(), "1".to_string()],
+                        vec!["c".to_string(), String::new()],
+                    ],
+                }),
+                paragraph("See [the docs][docs]."),
+            ]
+        );
+        assert_eq!(
+            parsed.references["docs"],
+            Reference {
+                url: "https://example.com/docs".to_string(),
+                title: Some("The docs".to_string()),
+            }
+        );
+    }
+}
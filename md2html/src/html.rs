@@ -0,0 +1,206 @@
+//! Renders parsed blocks to HTML, with ids on headings for links to them, like GitHub's.
+
+use crate::{
+    block::{Alignment, Block, List, Parsed, Reference, Table},
+    inline, Document,
+};
+use common::{highlight, html::escape};
+use std::{collections::HashMap, fmt::Write};
+
+pub fn render(parsed: &Parsed) -> Document {
+    let mut renderer = Renderer {
+        references: &parsed.references,
+        ids: HashMap::new(),
+        title: None,
+    };
+    let mut body = String::new();
+    renderer.blocks(&parsed.blocks, false, &mut body);
+    Document {
+        title: renderer.title,
+        body,
+    }
+}
+
+struct Renderer<'a> {
+    references: &'a HashMap<String, Reference>,
+    /// How many headings there were with each id, which are numbered after the first.
+    ids: HashMap<String, usize>,
+    title: Option<String>,
+}
+
+impl Renderer<'_> {
+    /// Renders blocks, which are in a tight list item if `tight`, where paragraphs are left
+    /// as text.
+    fn blocks(&mut self, blocks: &[Block], tight: bool, html: &mut String) {
+        for block in blocks {
+            self.block(block, tight, html);
+        }
+    }
+
+    fn block(&mut self, block: &Block, tight: bool, html: &mut String) {
+        // Writing to a `String` can't fail.
+        let _ = match block {
+            Block::Heading { level, text } => {
+                let content = self.inline(text);
+                let text = inline::plain_text(&content);
+                if *level == 1 && self.title.is_none() {
+                    self.title = Some(escape(&text));
+                }
+                let id = self.id(&text);
+                writeln!(html, "<h{level} id=\"{id}\">{content}</h{level}>")
+            }
+            Block::Paragraph(text) if tight => writeln!(html, "{}", self.inline(text)),
+            Block::Paragraph(text) => writeln!(html, "<p>{}</p>", self.inline(text)),
+            Block::Code { language, code } => {
+                let class = language.as_ref().map_or(String::new(), |language| {
+                    format!(" class=\"language-{}\"", escape(language))
+                });
+                let code = language
+                    .as_ref()
+                    .and_then(|language| highlight::highlight(language, code))
+                    .unwrap_or_else(|| escape(code));
+                writeln!(html, "<pre><code{class}>{code}</code></pre>")
+            }
+            Block::Quote(blocks) => {
+                html.push_str("<blockquote>\n");
+                self.blocks(blocks, false, html);
+                writeln!(html, "</blockquote>")
+            }
+            Block::List(list) => {
+                self.list(list, html);
+                Ok(())
+            }
+            Block::Table(table) => {
+                self.table(table, html);
+                Ok(())
+            }
+            Block::Rule => writeln!(html, "<hr>"),
+            Block::Html(raw) => writeln!(html, "{}", raw),
+        };
+    }
+
+    fn list(&mut self, list: &List, html: &mut String) {
+        let tag = match list.start {
+            Some(_) => "ol",
+            None => "ul",
+        };
+        let _ = match list.start {
+            Some(start) if start != 1 => writeln!(html, "<ol start=\"{}\">", start),
+            _ => writeln!(html, "<{}>", tag),
+        };
+        for item in &list.items {
+            let mut content = String::new();
+            self.blocks(&item.blocks, list.tight, &mut content);
+            let content = match list.tight {
+                true => content.trim_end_matches('\n'),
+                false => &content,
+            };
+            let separator = if list.tight || content.is_empty() {
+                ""
+            } else {
+                "\n"
+            };
+            let _ = match item.checked {
+                Some(checked) => writeln!(
+                    html,
+                    "<li class=\"task-list-item\"><input type=\"checkbox\" disabled{}> \
+                     {separator}{content}</li>",
+                    if checked { " checked" } else { "" }
+                ),
+                None => writeln!(html, "<li>{separator}{content}</li>"),
+            };
+        }
+        let _ = writeln!(html, "</{}>", tag);
+    }
+
+    fn table(&mut self, table: &Table, html: &mut String) {
+        html.push_str("<table>\n<thead>\n");
+        self.row("th", &table.header, &table.alignments, html);
+        html.push_str("</thead>\n");
+        if !table.rows.is_empty() {
+            html.push_str("<tbody>\n");
+            for row in &table.rows {
+                self.row("td", row, &table.alignments, html);
+            }
+            html.push_str("</tbody>\n");
+        }
+        html.push_str("</table>\n");
+    }
+
+    fn row(&mut self, tag: &str, cells: &[String], alignments: &[Alignment], html: &mut String) {
+        html.push_str("<tr>\n");
+        for (cell, alignment) in cells.iter().zip(alignments) {
+            let style = match alignment {
+                Alignment::None => "",
+                Alignment::Left => " style=\"text-align: left\"",
+                Alignment::Center => " style=\"text-align: center\"",
+                Alignment::Right => " style=\"text-align: right\"",
+            };
+            let _ = writeln!(html, "<{tag}{style}>{}</{tag}>", self.inline(cell));
+        }
+        html.push_str("</tr>\n");
+    }
+
+    fn inline(&self, text: &str) -> String {
+        inline::render(text, self.references)
+    }
+
+    /// An id for a heading with the text, like `getting-started`, with a number after it,
+    /// like `getting-started-1`, when another heading has the same text.
+    fn id(&mut self, text: &str) -> String {
+        let mut slug = slug(text);
+        if slug.is_empty() {
+            slug = "section".to_string();
+        }
+        let count = self.ids.entry(slug.clone()).or_insert(0);
+        *count += 1;
+        match *count {
+            1 => slug,
+            count => format!("{}-{}", slug, count - 1),
+        }
+    }
+}
+
+/// The text in lowercase, with spaces as `-`, and without punctuation other than `-` and `_`.
+fn slug(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_whitespace() => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert;
+
+    #[test]
+    fn renders_documents() {
+        let document = convert(
+            "# Hello *World* & Co\n\n## Usage\n\n## Usage\n\n- one\n- [ ] two\n  - nested\n\n\
+             3. loose\n\n4. list\n\n| a | b |\n|---|:-:|\n| 1 | 2 |\n\n> quote\n\n---\n\n\
+             ```text\n<raw>\n```\n",
+        );
+        assert_eq!(document.title.as_deref(), Some("Hello World &amp; Co"));
+        assert_eq!(
+            document.body,
+            "<h1 id=\"hello-world--co\">Hello <em>World</em> &amp; Co</h1>\n\
+             <h2 id=\"usage\">Usage</h2>\n\
+             <h2 id=\"usage-1\">Usage</h2>\n\
+             <ul>\n<li>one</li>\n\
+             <li class=\"task-list-item\"><input type=\"checkbox\" disabled> two\n\
+             <ul>\n<li>nested</li>\n</ul></li>\n</ul>\n\
+             <ol start=\"3\">\n<li>\n<p>loose</p>\n</li>\n<li>\n<p>list</p>\n</li>\n</ol>\n\
+             <table>\n<thead>\n<tr>\n<th>a</th>\n<th style=\"text-align: center\">b</th>\n\
+             </tr>\n</thead>\n<tbody>\n<tr>\n<td>1</td>\n\
+             <td style=\"text-align: center\">2</td>\n</tr>\n</tbody>\n</table>\n\
+             <blockquote>\n<p>quote</p>\n</blockquote>\n\
+             <hr>\n\
+             <pre><code class=\"language-text\">&lt;raw&gt;\n</code></pre>\n"
+        );
+    }
+}
@@ -0,0 +1,604 @@
+//! The text inside blocks: emphasis, code spans, links, images and line breaks, rendered
+//! straight to HTML. HTML tags and entities in the text are kept as they are, and other
+//! characters that mean something in HTML are escaped.
+
+use crate::block::{normalize_label, Reference};
+use common::html::{escape, push_escaped};
+use std::collections::HashMap;
+
+pub fn render(text: &str, references: &HashMap<String, Reference>) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    Renderer { references }.render(&chars, false)
+}
+
+/// The text of rendered HTML without its tags, like for the `alt` of images.
+pub fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+struct Renderer<'a> {
+    references: &'a HashMap<String, Reference>,
+}
+
+impl Renderer<'_> {
+    /// Renders the text, which is in the text of a link if `in_link`, where bare URLs aren't
+    /// made links of their own.
+    fn render(&self, chars: &[char], in_link: bool) -> String {
+        let mut html = String::with_capacity(chars.len());
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                '\\' if chars.get(i + 1) == Some(&'\n') => {
+                    html.push_str("<br>\n");
+                    i += 2;
+                }
+                '\\' if chars.get(i + 1).is_some_and(char::is_ascii_punctuation) => {
+                    push_escaped(&mut html, chars[i + 1]);
+                    i += 2;
+                }
+                '`' => {
+                    let run = run_len(chars, i);
+                    match code_span_end(chars, i) {
+                        Some(end) => {
+                            let code: String = chars[i + run..end - run].iter().collect();
+                            html.push_str(&format!("<code>{}</code>", escape(&code_text(&code))));
+                            i = end;
+                        }
+                        None => {
+                            html.extend(&chars[i..i + run]);
+                            i += run;
+                        }
+                    }
+                }
+                '*' | '_' => i = self.emphasis(chars, i, in_link, &mut html),
+                '~' if chars.get(i + 1) == Some(&'~') => {
+                    match find(chars, i + 2, |chars, j| chars[j..].starts_with(&['~', '~'])) {
+                        Some(end) if end > i + 2 => {
+                            let inner = self.render(&chars[i + 2..end], in_link);
+                            html.push_str(&format!("<del>{}</del>", inner));
+                            i = end + 2;
+                        }
+                        _ => {
+                            html.push_str("~~");
+                            i += 2;
+                        }
+                    }
+                }
+                '!' if chars.get(i + 1) == Some(&'[') => match self.link(chars, i + 1) {
+                    Some(link) => {
+                        let alt = plain_text(&self.render(link.text, true));
+                        html.push_str(&format!(
+                            "<img src=\"{}\" alt=\"{}\"{}>",
+                            escape_url(&link.url),
+                            escape(&alt),
+                            title_attribute(link.title.as_deref())
+                        ));
+                        i = link.end;
+                    }
+                    None => {
+                        html.push('!');
+                        i += 1;
+                    }
+                },
+                '[' => match self.link(chars, i).filter(|_| !in_link) {
+                    Some(link) => {
+                        html.push_str(&format!(
+                            "<a href=\"{}\"{}>{}</a>",
+                            escape_url(&link.url),
+                            title_attribute(link.title.as_deref()),
+                            self.render(link.text, true)
+                        ));
+                        i = link.end;
+                    }
+                    None => {
+                        html.push('[');
+                        i += 1;
+                    }
+                },
+                '<' => {
+                    if let Some((url, end)) = autolink(chars, i).filter(|_| !in_link) {
+                        let text: String = chars[i + 1..end - 1].iter().collect();
+                        html.push_str(&format!(
+                            "<a href=\"{}\">{}</a>",
+                            escape_url(&url),
+                            escape(&text)
+                        ));
+                        i = end;
+                    } else if let Some(end) = html_tag_end(chars, i) {
+                        html.extend(&chars[i..end]);
+                        i = end;
+                    } else {
+                        html.push_str("&lt;");
+                        i += 1;
+                    }
+                }
+                '&' => match entity_end(chars, i) {
+                    Some(end) => {
+                        html.extend(&chars[i..end]);
+                        i = end;
+                    }
+                    None => {
+                        html.push_str("&amp;");
+                        i += 1;
+                    }
+                },
+                'h' if !in_link && i.checked_sub(1).is_none_or(|j| !chars[j].is_alphanumeric()) => {
+                    match bare_url_end(chars, i) {
+                        Some(end) => {
+                            let url: String = chars[i..end].iter().collect();
+                            html.push_str(&format!(
+                                "<a href=\"{}\">{}</a>",
+                                escape_url(&url),
+                                escape(&url)
+                            ));
+                            i = end;
+                        }
+                        None => {
+                            html.push('h');
+                            i += 1;
+                        }
+                    }
+                }
+                '\n' => {
+                    // Two spaces or more at the end of a line break it.
+                    let spaces = html.len() - html.trim_end_matches(' ').len();
+                    html.truncate(html.len() - spaces);
+                    html.push_str(if spaces >= 2 { "<br>\n" } else { "\n" });
+                    i += 1;
+                }
+                c => {
+                    push_escaped(&mut html, c);
+                    i += 1;
+                }
+            }
+        }
+        html
+    }
+
+    /// Renders the emphasis that the run of `*` or `_` at `start` opens, returning where the
+    /// text after it starts. Runs that open nothing are written as they are.
+    fn emphasis(&self, chars: &[char], start: usize, in_link: bool, html: &mut String) -> usize {
+        let run = run_len(chars, start);
+        let closer = match Flanking::of(chars, start).can_open(chars[start]) {
+            true => closing_run(chars, start),
+            false => None,
+        };
+        let Some(closer) = closer else {
+            html.extend(&chars[start..start + run]);
+            return start + run;
+        };
+        let closer_run = run_len(chars, closer);
+        // The rest of longer runs, like the third `*` of `***both***`, is emphasis inside.
+        let (tag, used) = match run >= 2 && closer_run >= 2 {
+            true => ("strong", 2),
+            false => ("em", 1),
+        };
+        let inner = self.render(&chars[start + used..closer + closer_run - used], in_link);
+        html.push_str(&format!("<{tag}>{inner}</{tag}>"));
+        closer + closer_run
+    }
+
+    /// The link, or image without the `!`, starting with the `[` at `start`, with its target
+    /// in parentheses after it or defined as a reference.
+    fn link<'c>(&self, chars: &'c [char], start: usize) -> Option<Link<'c>> {
+        let close = closing_bracket(chars, start)?;
+        let text = &chars[start + 1..close];
+        let after = close + 1;
+        if chars.get(after) == Some(&'(') {
+            if let Some((url, title, end)) = inline_target(chars, after + 1) {
+                return Some(Link {
+                    text,
+                    url,
+                    title,
+                    end,
+                });
+            }
+        }
+        // Like `[text][label]`, `[text][]` for a label that's the text, or just `[text]`.
+        let (label, end) = match chars.get(after) {
+            Some('[') => {
+                let label_end = after + 1 + chars[after + 1..].iter().position(|&c| c == ']')?;
+                match label_end == after + 1 {
+                    true => (text, label_end + 1),
+                    false => (&chars[after + 1..label_end], label_end + 1),
+                }
+            }
+            _ => (text, after),
+        };
+        let label: String = label.iter().collect();
+        let reference = self.references.get(&normalize_label(&label))?;
+        Some(Link {
+            text,
+            url: reference.url.clone(),
+            title: reference.title.clone(),
+            end,
+        })
+    }
+}
+
+struct Link<'c> {
+    text: &'c [char],
+    url: String,
+    title: Option<String>,
+    /// Where the text after the link starts.
+    end: usize,
+}
+
+/// Whether a run of `*` or `_` is next to the text before it or after it, which tells whether
+/// it can open emphasis or close it, as in CommonMark.
+struct Flanking {
+    left: bool,
+    right: bool,
+    punctuation_before: bool,
+    punctuation_after: bool,
+}
+
+impl Flanking {
+    fn of(chars: &[char], start: usize) -> Flanking {
+        let before = start.checked_sub(1).map(|i| chars[i]);
+        let after = chars.get(start + run_len(chars, start)).copied();
+        let is_space = |c: Option<char>| c.is_none_or(char::is_whitespace);
+        let is_punctuation = |c: Option<char>| c.is_some_and(|c| c.is_ascii_punctuation());
+        let punctuation_before = is_punctuation(before);
+        let punctuation_after = is_punctuation(after);
+        Flanking {
+            left: !is_space(after)
+                && (!punctuation_after || is_space(before) || punctuation_before),
+            right: !is_space(before)
+                && (!punctuation_before || is_space(after) || punctuation_after),
+            punctuation_before,
+            punctuation_after,
+        }
+    }
+
+    /// Underscores inside words, like in `snake_case`, don't make emphasis.
+    fn can_open(&self, delimiter: char) -> bool {
+        self.left && (delimiter == '*' || !self.right || self.punctuation_before)
+    }
+
+    fn can_close(&self, delimiter: char) -> bool {
+        self.right && (delimiter == '*' || !self.left || self.punctuation_after)
+    }
+}
+
+/// The start of the run that closes the one at `start`, skipping the emphasis opened inside.
+fn closing_run(chars: &[char], start: usize) -> Option<usize> {
+    let delimiter = chars[start];
+    let mut i = start + run_len(chars, start);
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '`' => i = code_span_end(chars, i).unwrap_or(i + run_len(chars, i)),
+            c if c == delimiter => {
+                let flanking = Flanking::of(chars, i);
+                if flanking.can_close(delimiter) {
+                    return Some(i);
+                }
+                let nested = match flanking.can_open(delimiter) {
+                    true => closing_run(chars, i),
+                    false => None,
+                };
+                i = match nested {
+                    Some(closer) => closer + run_len(chars, closer),
+                    None => i + run_len(chars, i),
+                };
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// The first position from `start` where `is_match`, outside of code spans and escapes.
+fn find(
+    chars: &[char],
+    start: usize,
+    mut is_match: impl FnMut(&[char], usize) -> bool,
+) -> Option<usize> {
+    let mut i = start;
+    while i < chars.len() {
+        if is_match(chars, i) {
+            return Some(i);
+        }
+        i = match chars[i] {
+            '\\' => i + 2,
+            '`' => code_span_end(chars, i).unwrap_or(i + run_len(chars, i)),
+            _ => i + 1,
+        };
+    }
+    None
+}
+
+/// The `]` that matches the `[` at `start`.
+fn closing_bracket(chars: &[char], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    find(chars, start + 1, |chars, i| match chars[i] {
+        '[' => {
+            depth += 1;
+            false
+        }
+        ']' if depth == 0 => true,
+        ']' => {
+            depth -= 1;
+            false
+        }
+        _ => false,
+    })
+}
+
+/// The URL and title of `(url "title")` from after the `(`, and where the text after it starts.
+fn inline_target(chars: &[char], start: usize) -> Option<(String, Option<String>, usize)> {
+    let skip_spaces = |mut i: usize| {
+        while chars.get(i).is_some_and(|c| c.is_whitespace()) {
+            i += 1;
+        }
+        i
+    };
+    let mut i = skip_spaces(start);
+    let mut url = String::new();
+    if chars.get(i) == Some(&'<') {
+        i += 1;
+        while let Some(&c) = chars.get(i).filter(|&&c| c != '>') {
+            if c == '\n' {
+                return None;
+            }
+            url.push(c);
+            i += 1;
+        }
+        i += 1;
+    } else {
+        // Parentheses in URLs have to be balanced, like in `https://en.wikipedia.org/wiki/A_(b)`.
+        let mut depth = 0;
+        while let Some(&c) = chars.get(i) {
+            match c {
+                c if c.is_whitespace() => break,
+                '(' => depth += 1,
+                ')' if depth == 0 => break,
+                ')' => depth -= 1,
+                '\\' if chars.get(i + 1).is_some_and(char::is_ascii_punctuation) => i += 1,
+                _ => {}
+            }
+            url.push(chars[i]);
+            i += 1;
+        }
+    }
+    i = skip_spaces(i);
+    let title = match chars.get(i) {
+        Some(&open @ ('"' | '\'')) => {
+            let length = chars[i + 1..].iter().position(|&c| c == open)?;
+            let title = chars[i + 1..i + 1 + length].iter().collect();
+            i = skip_spaces(i + length + 2);
+            Some(title)
+        }
+        _ => None,
+    };
+    match chars.get(i) {
+        Some(')') => Some((url, title, i + 1)),
+        _ => None,
+    }
+}
+
+/// A URL or email address in angle brackets, like `<https://example.com>`, and where the text
+/// after it starts.
+fn autolink(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let length = chars[start + 1..].iter().position(|&c| c == '>')?;
+    let inner: String = chars[start + 1..start + 1 + length].iter().collect();
+    if inner.is_empty() || inner.contains(|c: char| c.is_whitespace() || c == '<') {
+        return None;
+    }
+    let end = start + length + 2;
+    if let Some((scheme, _)) = inner.split_once(':') {
+        let is_scheme = (2..=32).contains(&scheme.len())
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '.' | '-'));
+        if is_scheme {
+            return Some((inner, end));
+        }
+    }
+    match inner.split_once('@') {
+        Some((user, domain)) if !user.is_empty() && domain.contains('.') => {
+            Some((format!("mailto:{}", inner), end))
+        }
+        _ => None,
+    }
+}
+
+/// The end of an HTML tag, like `<b>`, `</b>`, `<img src="a.png" />` or `<!-- a comment -->`.
+fn html_tag_end(chars: &[char], start: usize) -> Option<usize> {
+    let rest = &chars[start + 1..];
+    if rest.starts_with(&['!', '-', '-']) {
+        let text: String = rest.iter().collect();
+        return text
+            .find("-->")
+            .map(|end| start + 1 + text[..end].chars().count() + 3);
+    }
+    let name_start = usize::from(rest.first() == Some(&'/'));
+    if !rest.get(name_start).is_some_and(char::is_ascii_alphabetic) {
+        return None;
+    }
+    let name_len = rest[name_start..]
+        .iter()
+        .take_while(|c| c.is_ascii_alphanumeric() || **c == '-')
+        .count();
+    let after_name = rest.get(name_start + name_len)?;
+    if !(after_name.is_whitespace() || *after_name == '>' || *after_name == '/') {
+        return None;
+    }
+    // Attribute values in quotes may have a `>` in them.
+    let mut quote = None;
+    for (i, &c) in rest.iter().enumerate().skip(name_start + name_len) {
+        match (quote, c) {
+            (None, '>') => return Some(start + i + 2),
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '<') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The end of an entity, like `&amp;`, `&#169;` or `&#xA9;`.
+fn entity_end(chars: &[char], start: usize) -> Option<usize> {
+    let rest = &chars[start + 1..];
+    let (skip, is_valid): (usize, fn(&char) -> bool) = match rest {
+        ['#', 'x' | 'X', ..] => (2, char::is_ascii_hexdigit),
+        ['#', ..] => (1, char::is_ascii_digit),
+        _ => (0, char::is_ascii_alphanumeric),
+    };
+    let length = rest[skip..].iter().take_while(|c| is_valid(c)).count();
+    match rest.get(skip + length) {
+        Some(';') if (1..=31).contains(&length) => Some(start + skip + length + 2),
+        _ => None,
+    }
+}
+
+/// The end of a URL written as it is, like `https://example.com/a`, without the punctuation
+/// after it that ends the sentence.
+fn bare_url_end(chars: &[char], start: usize) -> Option<usize> {
+    let rest = &chars[start..];
+    let scheme = ["https://", "http://"]
+        .into_iter()
+        .find(|scheme| rest.iter().take(scheme.len()).copied().eq(scheme.chars()))?;
+    let mut end = start
+        + rest
+            .iter()
+            .position(|&c| c.is_whitespace() || c == '<')
+            .unwrap_or(rest.len());
+    loop {
+        match chars[end - 1] {
+            '.' | ',' | ':' | ';' | '!' | '?' | '"' | '\'' | '*' | '_' | '~' => end -= 1,
+            // Unless it's part of the URL, like in `https://en.wikipedia.org/wiki/A_(b)`.
+            ')' if chars[start..end].iter().filter(|&&c| c == '(').count()
+                < chars[start..end].iter().filter(|&&c| c == ')').count() =>
+            {
+                end -= 1
+            }
+            _ => break,
+        }
+    }
+    (end > start + scheme.len()).then_some(end)
+}
+
+/// The end of the code span that starts with the run of backticks at `start`, which ends
+/// with a run just as long.
+fn code_span_end(chars: &[char], start: usize) -> Option<usize> {
+    let run = run_len(chars, start);
+    let mut i = start + run;
+    while i < chars.len() {
+        if chars[i] == '`' {
+            let closing = run_len(chars, i);
+            if closing == run {
+                return Some(i + run);
+            }
+            i += closing;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// The text of a code span, with line breaks as spaces, and without one space on both sides,
+/// which lets spans like `` `` `a` `` `` start or end with backticks.
+fn code_text(code: &str) -> String {
+    let code = code.replace('\n', " ");
+    match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+        Some(stripped) if !code.trim().is_empty() => stripped.to_string(),
+        _ => code,
+    }
+}
+
+fn run_len(chars: &[char], start: usize) -> usize {
+    let Some(&c) = chars.get(start) else {
+        return 0;
+    };
+    chars[start..]
+        .iter()
+        .take_while(|&&other| other == c)
+        .count()
+}
+
+fn escape_url(url: &str) -> String {
+    escape(&url.replace(' ', "%20"))
+}
+
+fn title_attribute(title: Option<&str>) -> String {
+    title.map_or(String::new(), |title| {
+        format!(" title=\"{}\"", escape(title))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_text(text: &str) -> String {
+        render(text, &HashMap::new())
+    }
+
+    #[test]
+    fn renders_emphasis_and_code() {
+        assert_eq!(
+            render_text("*a **b** c* and __d__, snake_case_name"),
+            "<em>a <strong>b</strong> c</em> and <strong>d</strong>, snake_case_name"
+        );
+        assert_eq!(
+            render_text("***both*** ~~gone~~ 2 * 3"),
+            "<strong><em>both</em></strong> <del>gone</del> 2 * 3"
+        );
+        assert_eq!(
+            render_text("`a <b>` and `` `c` `` \\*d\\*"),
+            "<code>a &lt;b&gt;</code> and <code>`c`</code> *d*"
+        );
+        assert_eq!(render_text("`not *code"), "`not *code");
+        assert_eq!(
+            render_text("line  \nbreak\\\nnext"),
+            "line<br>\nbreak<br>\nnext"
+        );
+    }
+
+    #[test]
+    fn renders_links_and_html() {
+        let mut references = HashMap::new();
+        references.insert(
+            "docs".to_string(),
+            Reference {
+                url: "https://example.com/docs".to_string(),
+                title: Some("The \"docs\"".to_string()),
+            },
+        );
+        assert_eq!(
+            render(
+                "[a *b*](/a_(b) 'T') [Docs] [x][docs] ![alt *c*](c.png) [none]",
+                &references
+            ),
+            "<a href=\"/a_(b)\" title=\"T\">a <em>b</em></a> \
+             <a href=\"https://example.com/docs\" title=\"The &quot;docs&quot;\">Docs</a> \
+             <a href=\"https://example.com/docs\" title=\"The &quot;docs&quot;\">x</a> \
+             <img src=\"c.png\" alt=\"alt c\"> [none]"
+        );
+        assert_eq!(
+            render_text("<https://a.b/c> <me@a.b> see https://a.b/(c). <b>ok</b> & &amp; <3"),
+            "<a href=\"https://a.b/c\">https://a.b/c</a> <a href=\"mailto:me@a.b\">me@a.b</a> \
+             see <a href=\"https://a.b/(c)\">https://a.b/(c)</a>. <b>ok</b> &amp; &amp; &lt;3"
+        );
+    }
+}
@@ -0,0 +1,20 @@
+//! Converts Markdown to HTML: the blocks of a document are parsed first, then the text inside
+//! them, so links can use references defined anywhere.
+
+pub mod block;
+pub mod html;
+pub mod inline;
+pub mod template;
+pub mod watch;
+
+/// A converted document.
+pub struct Document {
+    /// The text of the first level 1 heading, escaped for HTML.
+    pub title: Option<String>,
+    pub body: String,
+}
+
+pub fn convert(markdown: &str) -> Document {
+    let parsed = block::parse(markdown);
+    html::render(&parsed)
+}
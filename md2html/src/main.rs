@@ -0,0 +1,224 @@
+use clap::Parser;
+use common::{error::with_path, html::escape};
+use md2html::{template::Template, watch::Poller};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Error, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+/// Converts Markdown documents to HTML pages, with highlighted code, and can convert them again
+/// whenever they change.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The Markdown files, or `-` for stdin, which is read when there are none
+    #[arg(value_name = "file")]
+    inputs: Vec<PathBuf>,
+    /// The file to write the page of a single input to, instead of stdout
+    #[arg(short, long, value_name = "path", conflicts_with = "out_dir")]
+    output: Option<PathBuf>,
+    /// The directory to write the pages to, named after the inputs, like `README.html` for
+    /// `README.md`
+    #[arg(long, value_name = "dir")]
+    out_dir: Option<PathBuf>,
+    /// The template of the pages: `default`, `plain`, without styles, or the path of an HTML
+    /// file with `{{title}}` and `{{content}}` in it
+    #[arg(short, long, value_name = "name", default_value = "default")]
+    template: String,
+    /// Write just the HTML of the documents, without a template
+    #[arg(long)]
+    fragment: bool,
+    /// The title of the pages, instead of their first level 1 heading
+    #[arg(long)]
+    title: Option<String>,
+    /// Keep running, and convert the inputs again whenever they or the template change
+    #[arg(short, long)]
+    watch: bool,
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let inputs = match args.inputs.is_empty() {
+        true => vec![Input::Stdin],
+        false => args.inputs.iter().map(|path| Input::new(path)).collect(),
+    };
+    let outputs = outputs(&args, &inputs)?;
+    let mut converter = Converter {
+        template: (!args.fragment)
+            .then(|| Template::load(&args.template))
+            .transpose()?,
+        title: args.title.as_deref().map(escape),
+    };
+    if !args.watch {
+        for (input, output) in inputs.iter().zip(&outputs) {
+            converter.convert(input, output.as_deref())?;
+        }
+        return Ok(());
+    }
+
+    let mut paths = Vec::new();
+    for input in &inputs {
+        match input {
+            Input::File(path) => paths.push(path.clone()),
+            Input::Stdin => {
+                let message = "--watch needs files to watch, not stdin";
+                return Err(Error::new(ErrorKind::InvalidInput, message));
+            }
+        }
+    }
+    if outputs.iter().any(Option::is_none) {
+        let message = "--watch needs --output or --out-dir, to write the pages again";
+        return Err(Error::new(ErrorKind::InvalidInput, message));
+    }
+    let template_path = converter
+        .template
+        .as_ref()
+        .and_then(|template| template.path())
+        .map(Path::to_path_buf);
+    let mut poller = Poller::new(paths.iter().cloned().chain(template_path.clone()));
+    let convert_all = |converter: &mut Converter| {
+        for (input, output) in inputs.iter().zip(&outputs) {
+            converter.convert_and_report(input, output.as_deref());
+        }
+    };
+    convert_all(&mut converter);
+    eprintln!("Watching for changes, press Ctrl+C to stop");
+    loop {
+        let changed = poller.wait();
+        if template_path
+            .as_ref()
+            .is_some_and(|path| changed.contains(path))
+        {
+            // A broken template is reported, and the pages are written with the last one that
+            // worked until it's fixed.
+            match Template::load(&args.template) {
+                Ok(template) => {
+                    converter.template = Some(template);
+                    convert_all(&mut converter);
+                }
+                Err(error) => eprintln!("error: {}", error),
+            }
+            continue;
+        }
+        for (input, output) in inputs.iter().zip(&outputs) {
+            if matches!(input, Input::File(path) if changed.contains(path)) {
+                converter.convert_and_report(input, output.as_deref());
+            }
+        }
+    }
+}
+
+enum Input {
+    File(PathBuf),
+    Stdin,
+}
+
+impl Input {
+    fn new(path: &Path) -> Input {
+        match path.as_os_str() == "-" {
+            true => Input::Stdin,
+            false => Input::File(path.to_path_buf()),
+        }
+    }
+
+    fn read(&self) -> Result<String, Error> {
+        match self {
+            Input::File(path) => fs::read_to_string(path).map_err(|error| with_path(error, path)),
+            Input::Stdin => {
+                let mut markdown = String::new();
+                io::stdin().read_to_string(&mut markdown)?;
+                Ok(markdown)
+            }
+        }
+    }
+}
+
+/// Where the page of each input is written, `None` for stdout.
+fn outputs(args: &Args, inputs: &[Input]) -> Result<Vec<Option<PathBuf>>, Error> {
+    let invalid = |message: String| Err(Error::new(ErrorKind::InvalidInput, message));
+    let Some(out_dir) = &args.out_dir else {
+        if inputs.len() > 1 {
+            return invalid("several inputs need --out-dir, to write a page for each".to_string());
+        }
+        return Ok(vec![args.output.clone()]);
+    };
+    let mut outputs = Vec::new();
+    let mut inputs_by_output = HashMap::new();
+    for input in inputs {
+        let Input::File(path) = input else {
+            return invalid("the page of stdin needs a name, with --output".to_string());
+        };
+        let stem = path.file_stem().unwrap_or(path.as_os_str());
+        let output = out_dir.join(stem).with_extension("html");
+        if let Some(other) = inputs_by_output.insert(output.clone(), path) {
+            return invalid(format!(
+                "{} and {} would both be written to {}",
+                other.display(),
+                path.display(),
+                output.display()
+            ));
+        }
+        outputs.push(Some(output));
+    }
+    fs::create_dir_all(out_dir).map_err(|error| with_path(error, out_dir))?;
+    Ok(outputs)
+}
+
+struct Converter {
+    /// `None` for just the HTML of the documents.
+    template: Option<Template>,
+    /// Escaped for HTML, like the titles of documents.
+    title: Option<String>,
+}
+
+impl Converter {
+    fn convert(&self, input: &Input, output: Option<&Path>) -> Result<(), Error> {
+        let document = md2html::convert(&input.read()?);
+        let page = match &self.template {
+            Some(template) => {
+                let title = match (&self.title, document.title) {
+                    (Some(title), _) => title.clone(),
+                    (None, Some(title)) => title,
+                    // Pages without a heading are named after their file.
+                    (None, None) => match input {
+                        Input::File(path) => {
+                            let stem = path.file_stem().unwrap_or(path.as_os_str());
+                            escape(&stem.to_string_lossy())
+                        }
+                        Input::Stdin => "Untitled".to_string(),
+                    },
+                };
+                template.render(&title, &document.body)
+            }
+            None => document.body,
+        };
+        match output {
+            Some(path) => fs::write(path, page).map_err(|error| with_path(error, path)),
+            None => io::stdout().write_all(page.as_bytes()),
+        }
+    }
+
+    /// Converts an input in watch mode, where errors are reported and the watching goes on.
+    fn convert_and_report(&self, input: &Input, output: Option<&Path>) {
+        match self.convert(input, output) {
+            Ok(()) => {
+                if let Some(output) = output {
+                    eprintln!("Wrote {}", output.display());
+                }
+            }
+            Err(error) => eprintln!("error: {}", error),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! The pages converted documents are put in: the templates that come with the converter, or an
+//! HTML file with a `{{title}}` and a `{{content}}` in it.
+
+use common::error::with_path;
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+/// A page with a stylesheet, with colors for the highlighted code.
+const DEFAULT: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{{title}}</title>
+<style>
+body { max-width: 48em; margin: 2em auto; padding: 0 1em; font-family: system-ui, sans-serif; line-height: 1.5; color: #1f2328; }
+a { color: #0969da; }
+code, pre { font-family: ui-monospace, monospace; font-size: 0.9em; background: #f6f8fa; border-radius: 4px; }
+code { padding: 0.1em 0.3em; }
+pre { padding: 1em; overflow-x: auto; }
+pre code { padding: 0; background: none; }
+blockquote { margin: 0; padding: 0 1em; color: #59636e; border-left: 0.25em solid #d1d9e0; }
+table { border-collapse: collapse; }
+th, td { padding: 0.3em 0.8em; border: 1px solid #d1d9e0; }
+img { max-width: 100%; }
+.task-list-item { list-style: none; }
+.hl-keyword { color: #cf222e; }
+.hl-string { color: #0a3069; }
+.hl-comment { color: #6e7781; font-style: italic; }
+.hl-number { color: #0550ae; }
+</style>
+</head>
+<body>
+{{content}}</body>
+</html>
+"#;
+
+/// A page without styles, for a stylesheet of its own, or none.
+const PLAIN: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{{title}}</title>
+</head>
+<body>
+{{content}}</body>
+</html>
+"#;
+
+pub struct Template {
+    source: String,
+    /// The file it was read from, if it isn't one of the built-in templates.
+    path: Option<PathBuf>,
+}
+
+impl Template {
+    /// The built-in template called `name`, `default` or `plain`, or else the one in the file
+    /// at that path.
+    pub fn load(name: &str) -> Result<Template, Error> {
+        let builtin = match name {
+            "default" => Some(DEFAULT),
+            "plain" => Some(PLAIN),
+            _ => None,
+        };
+        if let Some(source) = builtin {
+            return Ok(Template {
+                source: source.to_string(),
+                path: None,
+            });
+        }
+        let path = PathBuf::from(name);
+        let source = fs::read_to_string(&path).map_err(|error| with_path(error, &path))?;
+        // It would make pages without the documents in them.
+        if !source.contains("{{content}}") {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: the template has no {{{{content}}}}", path.display()),
+            ));
+        }
+        Ok(Template {
+            source,
+            path: Some(path),
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The page with the title and the content, which are both HTML already. Placeholders
+    /// are replaced in one pass, so a `{{content}}` in the title is left as it is.
+    pub fn render(&self, title: &str, content: &str) -> String {
+        let mut page = String::with_capacity(self.source.len() + content.len());
+        let mut rest = self.source.as_str();
+        while let Some(start) = rest.find("{{") {
+            page.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let value = after.find("}}").and_then(|end| {
+                let value = match after[..end].trim() {
+                    "title" => title,
+                    "content" => content,
+                    _ => return None,
+                };
+                Some((value, end + 2))
+            });
+            match value {
+                Some((value, len)) => {
+                    page.push_str(value);
+                    rest = &after[len..];
+                }
+                None => {
+                    page.push_str("{{");
+                    rest = after;
+                }
+            }
+        }
+        page.push_str(rest);
+        page
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn renders_templates() {
+        let plain = Template::load("plain").unwrap();
+        let page = plain.render("A {{content}}", "<p>Hi</p>\n");
+        assert!(page.contains("<title>A {{content}}</title>"));
+        assert!(page.contains("<body>\n<p>Hi</p>\n</body>"));
+
+        let dir = env::temp_dir().join(format!("md2html-template-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("page.html");
+        fs::write(&path, "<h1>{{ title }}</h1>{{other}}{{content}}").unwrap();
+        let custom = Template::load(path.to_str().unwrap()).unwrap();
+        assert_eq!(custom.path(), Some(path.as_path()));
+        assert_eq!(custom.render("T", "C"), "<h1>T</h1>{{other}}C");
+
+        fs::write(&path, "<h1>{{title}}</h1>").unwrap();
+        let error = Template::load(path.to_str().unwrap()).err().unwrap();
+        assert!(error
+            .to_string()
+            .ends_with("the template has no {{content}}"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
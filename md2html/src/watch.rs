@@ -0,0 +1,96 @@
+//! Watching files for changes, by checking the times they were modified every now and then,
+//! which works the same on every system and for files on network drives.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+const INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long to wait after a change for more, since editors often save a file in several steps.
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// The time a file was modified and its size, which also changes when it's saved twice within
+/// the precision of the times of the file system. `None` for files that don't exist.
+type Stamp = Option<(SystemTime, u64)>;
+
+pub struct Poller {
+    files: Vec<(PathBuf, Stamp)>,
+}
+
+impl Poller {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Poller {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let stamp = stamp(&path);
+                (path, stamp)
+            })
+            .collect();
+        Poller { files }
+    }
+
+    /// The files that changed, were created or were removed since the poller was made or
+    /// this was called the last time.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for (path, old) in &mut self.files {
+            let new = stamp(path);
+            if new != *old {
+                *old = new;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+
+    /// Waits for files to change, returning those that did.
+    pub fn wait(&mut self) -> Vec<PathBuf> {
+        loop {
+            thread::sleep(INTERVAL);
+            let mut changed = self.changed();
+            if changed.is_empty() {
+                continue;
+            }
+            thread::sleep(SETTLE_TIME);
+            for path in self.changed() {
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+            return changed;
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn finds_changed_files() {
+        let dir = env::temp_dir().join(format!("md2html-watch-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.md"), dir.join("b.md"));
+        fs::write(&a, "# A").unwrap();
+        let mut poller = Poller::new([a.clone(), b.clone()]);
+        assert!(poller.changed().is_empty());
+
+        fs::write(&a, "# A changed").unwrap();
+        fs::write(&b, "# B").unwrap();
+        assert_eq!(poller.changed(), vec![a.clone(), b.clone()]);
+        assert!(poller.changed().is_empty());
+        fs::remove_file(&b).unwrap();
+        assert_eq!(poller.changed(), vec![b]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
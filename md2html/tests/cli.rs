@@ -0,0 +1,149 @@
+//! Runs the `md2html` binary on stdin and on files in a temporary directory, including in watch
+//! mode, where it's stopped once it wrote a page again.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+fn md2html(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md2html"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn stderr(output: Output) -> String {
+    assert!(!output.status.success(), "{:?}", output);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("md2html-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn path_str(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[test]
+fn converts_stdin() {
+    let markdown = "# Notes & more\n\n```rust\nfn main() {}\n```\n";
+    let page = stdout(md2html(&[], markdown));
+    assert!(page.starts_with("<!DOCTYPE html>"));
+    assert!(page.contains("<title>Notes &amp; more</title>"));
+    assert!(page.contains(".hl-keyword"));
+    assert!(page.contains("<span class=\"hl-keyword\">fn</span> main() {}"));
+
+    let fragment = stdout(md2html(&["--fragment", "-"], "Some *text*\n"));
+    assert_eq!(fragment, "<p>Some <em>text</em></p>\n");
+    let titled = stdout(md2html(
+        &["--template", "plain", "--title", "<Mine>"],
+        "# Other\n",
+    ));
+    assert!(titled.contains("<title>&lt;Mine&gt;</title>"));
+    assert!(!titled.contains("<style>"));
+}
+
+#[test]
+fn writes_files() {
+    let dir = temp_dir("files");
+    let (a, b) = (dir.join("a.md"), dir.join("b.markdown"));
+    fs::write(&a, "# First\n").unwrap();
+    fs::write(&b, "No heading\n").unwrap();
+    let template = dir.join("template.html");
+    fs::write(&template, "<title>{{title}}</title>\n{{content}}").unwrap();
+
+    let out_dir = dir.join("out");
+    let args = ["--out-dir", path_str(&out_dir), "-t", path_str(&template)];
+    stdout(md2html(
+        &[&args[..], &[path_str(&a), path_str(&b)]].concat(),
+        "",
+    ));
+    assert_eq!(
+        fs::read_to_string(out_dir.join("a.html")).unwrap(),
+        "<title>First</title>\n<h1 id=\"first\">First</h1>\n"
+    );
+    // Pages without a heading are named after their file.
+    assert_eq!(
+        fs::read_to_string(out_dir.join("b.html")).unwrap(),
+        "<title>b</title>\n<p>No heading</p>\n"
+    );
+
+    let output = dir.join("single.html");
+    stdout(md2html(
+        &["--fragment", "-o", path_str(&output), path_str(&a)],
+        "",
+    ));
+    let single = fs::read_to_string(&output).unwrap();
+    assert_eq!(single, "<h1 id=\"first\">First</h1>\n");
+
+    let error = stderr(md2html(&[path_str(&a), path_str(&b)], ""));
+    assert!(error.contains("several inputs need --out-dir"), "{}", error);
+    fs::write(&template, "<title>{{title}}</title>").unwrap();
+    let error = stderr(md2html(&["-t", path_str(&template), path_str(&a)], ""));
+    assert!(
+        error.contains("the template has no {{content}}"),
+        "{}",
+        error
+    );
+    let error = stderr(md2html(&["--watch"], ""));
+    assert!(error.contains("--watch needs files to watch"), "{}", error);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn converts_changed_files_again() {
+    let dir = temp_dir("watch");
+    let input = dir.join("notes.md");
+    fs::write(&input, "# Before\n").unwrap();
+    let output = dir.join("notes.html");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md2html"))
+        .args([
+            "--watch",
+            "--fragment",
+            "-o",
+            path_str(&output),
+            path_str(&input),
+        ])
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let wait_for = |expected: &str| {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while fs::read_to_string(&output).ok().as_deref() != Some(expected) {
+            assert!(Instant::now() < deadline, "{} wasn't written", expected);
+            thread::sleep(Duration::from_millis(50));
+        }
+    };
+    wait_for("<h1 id=\"before\">Before</h1>\n");
+    fs::write(&input, "# After the change\n").unwrap();
+    wait_for("<h1 id=\"after-the-change\">After the change</h1>\n");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
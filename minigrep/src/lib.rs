@@ -0,0 +1,7 @@
+//! Searches files for lines that match a regular expression, like grep, on as many threads as
+//! there are cores.
+
+pub mod output;
+pub mod parallel;
+pub mod search;
+pub mod walk;
@@ -0,0 +1,219 @@
+use clap::{Parser, ValueEnum};
+use minigrep::{
+    output::Printer,
+    parallel,
+    search::{self, Context},
+    walk,
+};
+use regex::{Regex, RegexBuilder};
+use std::{
+    env, fs,
+    io::{self, Error, ErrorKind, IsTerminal, Read, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+};
+
+/// Files that have a NUL byte in their first 8 KiB are taken for binary files, like grep does,
+/// and only told about, since their lines would garble the terminal.
+const BINARY_CHECK_LEN: usize = 8192;
+
+/// Searches files for lines that match a regular expression.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The regular expression, in the syntax of the regex crate
+    #[arg(value_name = "pattern")]
+    pattern: String,
+    /// The files and directories to search, with the directories inside them. The current
+    /// directory if none are given and the input is a terminal, or else the input
+    #[arg(value_name = "path")]
+    paths: Vec<PathBuf>,
+    /// Match upper and lower case letters alike
+    #[arg(long, short)]
+    ignore_case: bool,
+    /// Show the number of each line
+    #[arg(long, short = 'n')]
+    line_number: bool,
+    /// Show this many lines after each match
+    #[arg(short = 'A', long, value_name = "count")]
+    after_context: Option<usize>,
+    /// Show this many lines before each match
+    #[arg(short = 'B', long, value_name = "count")]
+    before_context: Option<usize>,
+    /// Show this many lines before and after each match
+    #[arg(short = 'C', long, value_name = "count")]
+    context: Option<usize>,
+    /// When to highlight matches
+    #[arg(long, value_name = "when", default_value = "auto")]
+    color: ColorChoice,
+    /// Search hidden files and directories too, like .git
+    #[arg(long)]
+    hidden: bool,
+    /// How many files to search at once, as many as there are cores if not given
+    #[arg(long, short = 'j', value_name = "count")]
+    threads: Option<NonZeroUsize>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    /// Colors when writing to a terminal, unless `NO_COLOR` is set to anything but an empty
+    /// string
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Exits like grep: with 0 when lines matched, 1 when none did, and 2 when something failed,
+/// even if others matched.
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// What searching a file found: whether it matched, and what to print.
+struct Found {
+    matched: bool,
+    output: String,
+}
+
+fn run(args: Args) -> Result<bool, Error> {
+    let regex = RegexBuilder::new(&args.pattern)
+        .case_insensitive(args.ignore_case)
+        .build()
+        .map_err(|error| Error::new(ErrorKind::InvalidInput, error.to_string()))?;
+    let context = Context {
+        before: args.before_context.or(args.context).unwrap_or(0),
+        after: args.after_context.or(args.context).unwrap_or(0),
+    };
+    let printer = Printer {
+        color: args.color.enabled(),
+        line_numbers: args.line_number,
+        context: context.before > 0 || context.after > 0,
+    };
+
+    let paths = match args.paths.is_empty() {
+        true if !io::stdin().is_terminal() => {
+            let mut input = Vec::new();
+            io::stdin().read_to_end(&mut input)?;
+            let found = search_text(&regex, context, &printer, None, &input);
+            io::stdout().write_all(found.output.as_bytes())?;
+            return Ok(found.matched);
+        }
+        true => vec![PathBuf::from(".")],
+        false => args.paths,
+    };
+    // Paths are shown in front of lines when more than one file may be searched.
+    let show_paths = paths.len() > 1 || paths.iter().any(|path| path.is_dir());
+    let threads = args
+        .threads
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+
+    let mut stdout = io::stdout().lock();
+    let (mut matched, mut failed, mut printed_any) = (false, 0, false);
+    let mut write_error = None;
+    parallel::map_ordered(
+        walk::files(paths, args.hidden),
+        threads,
+        |path| {
+            let path = path?;
+            let input = fs::read(&path).map_err(|error| common::error::with_path(error, &path))?;
+            let shown = show_paths.then(|| display(&path));
+            Ok(search_text(
+                &regex,
+                context,
+                &printer,
+                shown.as_deref(),
+                &input,
+            ))
+        },
+        |found: Result<Found, Error>| {
+            let found = match found {
+                Ok(found) => found,
+                Err(error) => {
+                    eprintln!("error: {}", error);
+                    failed += 1;
+                    return;
+                }
+            };
+            matched |= found.matched;
+            if found.output.is_empty() || write_error.is_some() {
+                return;
+            }
+            let mut output = String::new();
+            if printer.context && printed_any {
+                output.push_str(&printer.group_separator());
+                output.push('\n');
+            }
+            output.push_str(&found.output);
+            printed_any = true;
+            if let Err(error) = stdout.write_all(output.as_bytes()) {
+                write_error = Some(error);
+            }
+        },
+    );
+    match write_error {
+        // Like when the output is piped to `head`, which stops reading once it has enough.
+        Some(error) if error.kind() == ErrorKind::BrokenPipe => Ok(matched),
+        Some(error) => Err(error),
+        None if failed == 1 => Err(Error::other("1 file couldn't be searched")),
+        None if failed > 1 => Err(Error::other(format!(
+            "{} files couldn't be searched",
+            failed
+        ))),
+        None => Ok(matched),
+    }
+}
+
+fn search_text(
+    regex: &Regex,
+    context: Context,
+    printer: &Printer,
+    path: Option<&str>,
+    input: &[u8],
+) -> Found {
+    let text = String::from_utf8_lossy(input);
+    let is_binary = input[..input.len().min(BINARY_CHECK_LEN)].contains(&0);
+    if is_binary {
+        let matched = regex.is_match(&text);
+        let output = match (matched, path) {
+            (true, Some(path)) => format!("Binary file {} matches\n", path),
+            (true, None) => "Binary file (standard input) matches\n".to_string(),
+            (false, _) => String::new(),
+        };
+        return Found { matched, output };
+    }
+    let lines = search::search(regex, &text, context);
+    let mut output = String::new();
+    printer.write(path, &lines, &mut output);
+    Found {
+        matched: !lines.is_empty(),
+        output,
+    }
+}
+
+/// Shows paths without the `./` that searching the current directory puts in front of them.
+fn display(path: &Path) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path);
+    path.display().to_string()
+}
@@ -0,0 +1,102 @@
+//! Writes the lines that were found like grep does: `path:12:text` for matches, and
+//! `path-13-text` for the lines around them, with `--` between groups of lines that aren't
+//! next to each other.
+
+use crate::search::Line;
+
+const PATH_STYLE: &str = "35";
+const LINE_NUMBER_STYLE: &str = "32";
+const SEPARATOR_STYLE: &str = "36";
+const MATCH_STYLE: &str = "1;31";
+
+/// Writes the line between groups of lines, which [`Printer::write`] leaves to the caller
+/// between files.
+pub const GROUP_SEPARATOR: &str = "--";
+
+pub struct Printer {
+    pub color: bool,
+    pub line_numbers: bool,
+    /// Whether lines around matches are shown, which groups lines.
+    pub context: bool,
+}
+
+impl Printer {
+    /// Writes the lines of a file, after its path if there is one to show.
+    pub fn write(&self, path: Option<&str>, lines: &[Line], output: &mut String) {
+        let mut previous = None;
+        for line in lines {
+            if self.context && previous.is_some_and(|number| number + 1 < line.number) {
+                output.push_str(&self.group_separator());
+                output.push('\n');
+            }
+            previous = Some(line.number);
+            let separator = if line.is_match { ":" } else { "-" };
+            if let Some(path) = path {
+                output.push_str(&self.paint(path, PATH_STYLE));
+                output.push_str(&self.paint(separator, SEPARATOR_STYLE));
+            }
+            if self.line_numbers {
+                output.push_str(&self.paint(&line.number.to_string(), LINE_NUMBER_STYLE));
+                output.push_str(&self.paint(separator, SEPARATOR_STYLE));
+            }
+            let mut end = 0;
+            for range in &line.matches {
+                output.push_str(&line.text[end..range.start]);
+                output.push_str(&self.paint(&line.text[range.clone()], MATCH_STYLE));
+                end = range.end;
+            }
+            output.push_str(&line.text[end..]);
+            output.push('\n');
+        }
+    }
+
+    pub fn group_separator(&self) -> String {
+        self.paint(GROUP_SEPARATOR, SEPARATOR_STYLE)
+    }
+
+    /// Shows a text in a style, if colors are on.
+    fn paint(&self, text: &str, style: &str) -> String {
+        match self.color {
+            true => format!("\x1b[{}m{}\x1b[0m", style, text),
+            false => text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{search, Context};
+    use regex::Regex;
+
+    #[test]
+    fn writes_lines_like_grep() {
+        let text = "one fish\ntwo\nthree\nfour\nred fish, blue fish\n";
+        let regex = Regex::new("fish").unwrap();
+        let context = Context {
+            before: 0,
+            after: 1,
+        };
+        let lines = search(&regex, text, context);
+        let mut printer = Printer {
+            color: false,
+            line_numbers: true,
+            context: true,
+        };
+        let mut output = String::new();
+        printer.write(Some("fish.txt"), &lines, &mut output);
+        assert_eq!(
+            output,
+            "fish.txt:1:one fish\nfish.txt-2-two\n--\nfish.txt:5:red fish, blue fish\n"
+        );
+
+        printer.color = true;
+        printer.line_numbers = false;
+        let mut output = String::new();
+        printer.write(None, &lines[2..], &mut output);
+        assert_eq!(
+            output,
+            "red \x1b[1;31mfish\x1b[0m, blue \x1b[1;31mfish\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Runs a function on many items on a few threads, and hands back the results in the order of
+//! the items, so that searching files in parallel prints the same as searching them one after
+//! the other. [rayon](https://docs.rs/rayon) does this, among much more, but isn't among the
+//! dependencies this repository can build with, and the standard library is enough for it.
+
+use std::{
+    collections::BTreeMap,
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+/// Calls `work` on each item on `threads` threads, and `consume` on each result, in the order
+/// of the items, on the calling thread, as soon as the results before it are there.
+pub fn map_ordered<T, R>(
+    items: impl Iterator<Item = T> + Send,
+    threads: usize,
+    work: impl Fn(T) -> R + Sync,
+    mut consume: impl FnMut(R),
+) where
+    T: Send,
+    R: Send,
+{
+    let threads = threads.max(1);
+    // Bounded, so items are produced, like files found, only a little ahead of the work.
+    let (item_sender, item_receiver) = mpsc::sync_channel(threads * 4);
+    let item_receiver = Mutex::new(item_receiver);
+    let (result_sender, result_receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            for item in items.enumerate() {
+                if item_sender.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+        for _ in 0..threads {
+            let (item_receiver, result_sender, work) =
+                (&item_receiver, result_sender.clone(), &work);
+            scope.spawn(move || loop {
+                // The lock is only held while waiting for the next item, not while working.
+                let next = item_receiver.lock().unwrap().recv();
+                let Ok((index, item)) = next else {
+                    break;
+                };
+                if result_sender.send((index, work(item))).is_err() {
+                    break;
+                }
+            });
+        }
+        // The results end when the last thread drops its sender.
+        drop(result_sender);
+
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (index, result) in result_receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&next) {
+                consume(result);
+                next += 1;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn keeps_the_order_of_the_items() {
+        let mut results = Vec::new();
+        // The first items take the longest, so they'd come back last without reordering.
+        map_ordered(
+            0..20u64,
+            4,
+            |item| {
+                thread::sleep(Duration::from_millis(20u64.saturating_sub(item)));
+                item * 2
+            },
+            |result| results.push(result),
+        );
+        assert_eq!(results, (0..20).map(|item| item * 2).collect::<Vec<_>>());
+
+        let mut count = 0;
+        map_ordered(std::iter::empty::<u8>(), 0, |item| item, |_| count += 1);
+        assert_eq!(count, 0);
+    }
+}
@@ -0,0 +1,123 @@
+//! Finds the lines of a text that match, with the lines around them.
+
+use regex::Regex;
+use std::{collections::VecDeque, ops::Range};
+
+/// How many lines to show before and after each match.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Context {
+    pub before: usize,
+    pub after: usize,
+}
+
+/// A line to show: one that matches, or one around a match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line<'a> {
+    /// The number of the line, counting from 1.
+    pub number: usize,
+    pub text: &'a str,
+    pub is_match: bool,
+    /// Where the matches are in the text, which are highlighted. Empty matches, like the ones
+    /// of `a*`, aren't, although their lines match.
+    pub matches: Vec<Range<usize>>,
+}
+
+/// The lines of `text` that match `regex`, with the context around them, in order. Lines
+/// are only shown once, when the contexts of matches overlap.
+pub fn search<'a>(regex: &Regex, text: &'a str, context: Context) -> Vec<Line<'a>> {
+    let mut lines = Vec::new();
+    // Most files don't match at all, which is found out faster than line by line.
+    if !regex.is_match(text) {
+        return lines;
+    }
+    let context_line = |(number, text)| Line {
+        number,
+        text,
+        is_match: false,
+        matches: Vec::new(),
+    };
+    let mut before = VecDeque::with_capacity(context.before);
+    let mut after_left = 0;
+    for (number, text) in (1..).zip(text.lines()) {
+        if regex.is_match(text) {
+            lines.extend(before.drain(..).map(context_line));
+            let matches = regex
+                .find_iter(text)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.range())
+                .collect();
+            lines.push(Line {
+                number,
+                text,
+                is_match: true,
+                matches,
+            });
+            after_left = context.after;
+        } else if after_left > 0 {
+            lines.push(context_line((number, text)));
+            after_left -= 1;
+        } else if context.before > 0 {
+            if before.len() == context.before {
+                before.pop_front();
+            }
+            before.push_back((number, text));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(lines: &[Line]) -> Vec<(usize, bool)> {
+        lines
+            .iter()
+            .map(|line| (line.number, line.is_match))
+            .collect()
+    }
+
+    #[test]
+    fn finds_matches_with_context() {
+        let text = "one\ntwo fish\nthree\nfour\nfive fish\nsix\nseven\neight\nnine fish\n";
+        let regex = Regex::new("fish").unwrap();
+        let lines = search(&regex, text, Context::default());
+        assert_eq!(numbers(&lines), [(2, true), (5, true), (9, true)]);
+        assert_eq!(lines[0].text, "two fish");
+        assert_eq!(lines[0].matches, [Range { start: 4, end: 8 }]);
+
+        // The contexts of the first two matches overlap, and are shown once.
+        let context = Context {
+            before: 2,
+            after: 1,
+        };
+        assert_eq!(
+            numbers(&search(&regex, text, context)),
+            [
+                (1, false),
+                (2, true),
+                (3, false),
+                (4, false),
+                (5, true),
+                (6, false),
+                (7, false),
+                (8, false),
+                (9, true)
+            ]
+        );
+        let context = Context {
+            before: 0,
+            after: 1,
+        };
+        assert_eq!(
+            numbers(&search(&regex, text, context)),
+            [(2, true), (3, false), (5, true), (6, false), (9, true)]
+        );
+
+        assert!(search(&regex, "no\nmatches\n", context).is_empty());
+        let lines = search(&Regex::new("x*").unwrap(), "ab\r\nxx", Context::default());
+        assert_eq!(lines[0].text, "ab");
+        assert!(lines[0].matches.is_empty());
+        assert_eq!(lines[1].matches, [Range { start: 0, end: 2 }]);
+    }
+}
@@ -0,0 +1,82 @@
+//! Finds the files to search: the ones given, and the ones in the directories given, with
+//! the directories inside them.
+
+use common::error::with_path;
+use std::{
+    io::Error,
+    path::{Path, PathBuf},
+};
+use walkdir::{DirEntry, WalkDir};
+
+/// The files under `paths`, in the order of the paths, and by name inside directories, so
+/// that results come out the same every time. Hidden files and directories, like `.git`, are
+/// skipped unless `hidden`, or unless they're given themselves.
+pub fn files(
+    paths: Vec<PathBuf>,
+    hidden: bool,
+) -> impl Iterator<Item = Result<PathBuf, Error>> + Send {
+    paths.into_iter().flat_map(move |path| {
+        WalkDir::new(path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(move |entry| hidden || entry.depth() == 0 || !is_hidden(entry))
+            .filter_map(|entry| match entry {
+                Ok(entry) if entry.file_type().is_file() => Some(Ok(entry.into_path())),
+                Ok(_) => None,
+                Err(error) => {
+                    let (message, path) = (error.to_string(), error.path().map(Path::to_path_buf));
+                    Some(Err(match (error.into_io_error(), path) {
+                        (Some(error), Some(path)) => with_path(error, &path),
+                        (Some(error), None) => error,
+                        // Loops of symbolic links, which walkdir tells about with their paths.
+                        (None, _) => Error::other(message),
+                    }))
+                }
+            })
+    })
+}
+
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.file_name().to_string_lossy().starts_with('.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    #[test]
+    fn finds_files_in_order() {
+        let dir = env::temp_dir().join(format!("minigrep-walk-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        for file in [
+            "b.txt",
+            "a.txt",
+            ".hidden",
+            "src/main.rs",
+            "src/nested/x",
+            ".git/HEAD",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let found = |hidden| -> Vec<String> {
+            files(vec![dir.clone(), dir.join(".hidden")], hidden)
+                .map(|path| {
+                    let path = path.unwrap();
+                    let relative = path.strip_prefix(&dir).unwrap();
+                    relative.to_string_lossy().into_owned()
+                })
+                .collect()
+        };
+        assert_eq!(
+            found(false),
+            ["a.txt", "b.txt", "src/main.rs", "src/nested/x", ".hidden"]
+        );
+        assert_eq!(found(true).len(), 7);
+
+        let missing = files(vec![dir.join("missing")], false).next().unwrap();
+        assert!(missing.unwrap_err().to_string().contains("missing"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+//! Runs the `minigrep` binary on files in a temporary directory and on stdin, and checks what
+//! it prints and the codes it exits with.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+const POEM: &str = "one\ntwo Foo\nthree\nfour\nfive\nsix foo\n";
+
+fn minigrep(dir: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("NO_COLOR")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+/// A directory with a few files to search, hidden and binary ones among them.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("minigrep-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    fs::write(dir.join("poem.txt"), POEM).unwrap();
+    fs::write(dir.join("src/main.rs"), "fn main() {\n    foo();\n}\n").unwrap();
+    fs::write(dir.join(".git/config"), "[core]\nfoo = true\n").unwrap();
+    fs::write(dir.join("data.bin"), b"foo\0bar").unwrap();
+    dir
+}
+
+#[test]
+fn searches_files_and_directories() {
+    let dir = temp_dir("search");
+
+    let output = minigrep(&dir, &["foo", "poem.txt"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "six foo\n");
+
+    let output = minigrep(&dir, &["-in", "foo", "poem.txt"], "");
+    assert_eq!(stdout(&output), "2:two Foo\n6:six foo\n");
+
+    // Directories are searched by file name, without hidden files, and binary files are only
+    // told about.
+    let output = minigrep(&dir, &["-j", "2", "foo", "."], "");
+    assert_eq!(
+        stdout(&output),
+        "Binary file data.bin matches\npoem.txt:six foo\nsrc/main.rs:    foo();\n"
+    );
+    let output = minigrep(&dir, &["--hidden", "foo = true", "."], "");
+    assert_eq!(stdout(&output), ".git/config:foo = true\n");
+
+    let output = minigrep(&dir, &["--color", "always", "F(o+)", "poem.txt"], "");
+    assert_eq!(stdout(&output), "two \x1b[1;31mFoo\x1b[0m\n");
+
+    let output = minigrep(&dir, &["x foo"], "a\nx foo\n");
+    assert_eq!(stdout(&output), "x foo\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn shows_context_lines() {
+    let dir = temp_dir("context");
+
+    let output = minigrep(&dir, &["-n", "-C", "1", "-i", "foo", "poem.txt"], "");
+    assert_eq!(
+        stdout(&output),
+        "1-one\n2:two Foo\n3-three\n--\n5-five\n6:six foo\n"
+    );
+    let output = minigrep(&dir, &["-A", "1", "two", "poem.txt"], "");
+    assert_eq!(stdout(&output), "two Foo\nthree\n");
+    let output = minigrep(&dir, &["-B", "2", "four", "poem.txt"], "");
+    assert_eq!(stdout(&output), "two Foo\nthree\nfour\n");
+
+    // Files are told apart by a separator too.
+    let output = minigrep(&dir, &["-A", "1", "one|main", "poem.txt", "src"], "");
+    assert_eq!(
+        stdout(&output),
+        "poem.txt:one\npoem.txt-two Foo\n--\nsrc/main.rs:fn main() {\nsrc/main.rs-    foo();\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn exits_like_grep() {
+    let dir = temp_dir("exit");
+
+    let output = minigrep(&dir, &["nothing", "poem.txt"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+
+    let output = minigrep(&dir, &["(", "poem.txt"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("error: regex parse error"));
+
+    // The files that can be searched still are.
+    let output = minigrep(&dir, &["six", "missing.txt", "poem.txt"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stdout(&output), "poem.txt:six foo\n");
+    assert!(stderr(&output).starts_with("error: missing.txt: "));
+    assert!(stderr(&output).ends_with("error: 1 file couldn't be searched\n"));
+    fs::remove_dir_all(&dir).unwrap();
+}
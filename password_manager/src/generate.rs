@@ -0,0 +1,121 @@
+//! Random passwords and passphrases, from the random number generator of the system.
+
+use ring::rand::{SecureRandom, SystemRandom};
+use std::io::Error;
+use zeroize::Zeroizing;
+
+pub const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+pub const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+pub const DIGITS: &str = "0123456789";
+/// Without quotes, backslashes and backticks, which shells and some sites choke on.
+pub const SYMBOLS: &str = "!#$%&()*+,-./:;<=>?@[]^_{|}~";
+
+/// Common English words of 3 to 8 letters, one per line, for passphrases.
+const WORDS: &str = include_str!("words.txt");
+
+/// A password of `length` characters, picked from the union of the sets, with at least one of
+/// every set. Each set has to have characters.
+pub fn password(
+    random: &SystemRandom,
+    length: usize,
+    sets: &[Vec<char>],
+) -> Result<Zeroizing<String>, Error> {
+    if sets.is_empty() || sets.iter().any(Vec::is_empty) {
+        return Err(Error::other("there are no characters to pick from"));
+    }
+    if length < sets.len() {
+        return Err(Error::other(format!(
+            "a password with a character of each of {} sets needs at least {} characters",
+            sets.len(),
+            sets.len()
+        )));
+    }
+    let all: Vec<char> = sets.iter().flatten().copied().collect();
+    // Passwords that miss a set are thrown away, rather than fixed by replacing a character,
+    // so every password that's left is as likely as the others.
+    loop {
+        let mut password = Zeroizing::new(String::with_capacity(length));
+        for _ in 0..length {
+            password.push(all[random_below(random, all.len())?]);
+        }
+        if sets
+            .iter()
+            .all(|set| password.chars().any(|c| set.contains(&c)))
+        {
+            return Ok(password);
+        }
+    }
+}
+
+/// A passphrase of `count` words, like `maple-otter-ribbon-cable-dune-spark`.
+pub fn passphrase(
+    random: &SystemRandom,
+    count: usize,
+    separator: &str,
+) -> Result<Zeroizing<String>, Error> {
+    let words = words();
+    let mut passphrase = Zeroizing::new(String::new());
+    for index in 0..count {
+        if index > 0 {
+            passphrase.push_str(separator);
+        }
+        passphrase.push_str(words[random_below(random, words.len())?]);
+    }
+    Ok(passphrase)
+}
+
+pub fn words() -> Vec<&'static str> {
+    WORDS.lines().collect()
+}
+
+/// How many bits an attacker who knows how a password was made has to guess, of `count`
+/// picks out of `choices`. Passwords that need a character of every set have a little less.
+pub fn entropy_bits(choices: usize, count: usize) -> f64 {
+    (choices as f64).log2() * count as f64
+}
+
+/// A uniformly random number below `n`. Numbers from the part of the range that `n` doesn't
+/// fit in evenly are thrown away, so small numbers aren't more likely, as with a plain `%`.
+fn random_below(random: &SystemRandom, n: usize) -> Result<usize, Error> {
+    let n = n as u64;
+    let limit = u64::MAX - u64::MAX % n;
+    loop {
+        let mut bytes = [0; 8];
+        random
+            .fill(&mut bytes)
+            .map_err(|_| Error::other("the system's random number generator failed"))?;
+        let value = u64::from_le_bytes(bytes);
+        if value < limit {
+            return Ok((value % n) as usize);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_passwords_with_every_set() {
+        let random = SystemRandom::new();
+        let sets: Vec<Vec<char>> = [LOWERCASE, DIGITS, SYMBOLS]
+            .iter()
+            .map(|set| set.chars().collect())
+            .collect();
+        for _ in 0..100 {
+            let password = password(&random, 3, &sets).unwrap();
+            assert_eq!(password.chars().count(), 3);
+            for set in &sets {
+                assert!(password.chars().any(|c| set.contains(&c)), "{}", *password);
+            }
+        }
+        assert!(password(&random, 2, &sets).is_err());
+
+        let passphrase = passphrase(&random, 5, " ").unwrap();
+        let words = words();
+        assert_eq!(passphrase.split(' ').count(), 5);
+        assert!(passphrase.split(' ').all(|word| words.contains(&word)));
+        assert!(words.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((entropy_bits(1024, 6) - 60.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,5 @@
+//! Generates passwords and passphrases, and keeps passwords in a vault encrypted with a master
+//! password.
+
+pub mod generate;
+pub mod vault;
@@ -0,0 +1,352 @@
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use common::{dates::format_relative, terminal};
+use password_manager::{
+    generate::{self, DIGITS, LOWERCASE, SYMBOLS, UPPERCASE},
+    vault::{self, Entry, KdfParams, Vault},
+};
+use ring::rand::SystemRandom;
+use std::{
+    env,
+    io::{self, BufRead, Error, ErrorKind, IsTerminal},
+    path::PathBuf,
+    process::ExitCode,
+};
+use zeroize::Zeroizing;
+
+/// Short master passwords are found in no time, however slow deriving the key is.
+const MIN_MASTER_PASSWORD_LEN: usize = 8;
+
+/// Generates passwords and keeps them in an encrypted vault.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The vault file, `~/.local/share/password_manager/vault.json` if not given
+    #[arg(long, global = true, value_name = "path", env = "PASSWORD_VAULT")]
+    vault: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Prints a random password, or a passphrase
+    Generate(GenerateArgs),
+    /// Creates an empty vault, protected by a new master password
+    Init,
+    /// Adds an entry to the vault, with a password that's typed, read from stdin or generated
+    Add {
+        /// The name of the entry, like the site the password is for
+        #[arg(value_name = "name")]
+        name: String,
+        #[arg(long, short, value_name = "name")]
+        username: Option<String>,
+        /// Generate a password of 20 characters, like `generate` does without options
+        #[arg(long, short)]
+        generate: bool,
+        /// Replace the entry if there's one with the name
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Prints the password of an entry
+    Get {
+        #[arg(value_name = "name")]
+        name: String,
+        /// Print the username instead
+        #[arg(long, short)]
+        username: bool,
+    },
+    /// Lists the entries, without their passwords
+    #[command(visible_alias = "ls")]
+    List,
+    /// Removes an entry
+    #[command(visible_alias = "rm")]
+    Remove {
+        #[arg(value_name = "name")]
+        name: String,
+    },
+}
+
+#[derive(clap::Args)]
+struct GenerateArgs {
+    /// How many characters the password has
+    #[arg(long, short, default_value_t = 20)]
+    length: usize,
+    /// Leave out lowercase letters
+    #[arg(long)]
+    no_lowercase: bool,
+    /// Leave out uppercase letters
+    #[arg(long)]
+    no_uppercase: bool,
+    /// Leave out digits
+    #[arg(long)]
+    no_digits: bool,
+    /// Leave out symbols
+    #[arg(long)]
+    no_symbols: bool,
+    /// Pick the characters from these only, like 0123456789 for a PIN
+    #[arg(
+        long,
+        value_name = "characters",
+        conflicts_with_all = ["no_lowercase", "no_uppercase", "no_digits", "no_symbols"]
+    )]
+    charset: Option<String>,
+    /// Make a passphrase of common words instead, which is easier to type and remember
+    #[arg(long, conflicts_with_all = ["length", "charset"])]
+    passphrase: bool,
+    /// How many words the passphrase has
+    #[arg(long, default_value_t = 6, requires = "passphrase")]
+    words: usize,
+    /// What's put between the words of the passphrase
+    #[arg(long, default_value = "-", requires = "passphrase")]
+    separator: String,
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let vault_path = || {
+        args.vault
+            .clone()
+            .or_else(vault::default_path)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    "there's no home directory for the vault, give its path with --vault",
+                )
+            })
+    };
+    let random = SystemRandom::new();
+    match args.command {
+        Commands::Generate(options) => {
+            let (password, bits) = generate(&random, &options)?;
+            println!("{}", *password);
+            // Only for people, scripts just want the password.
+            if io::stdout().is_terminal() {
+                eprintln!("About {:.0} bits of entropy", bits);
+            }
+        }
+        Commands::Init => {
+            let path = vault_path()?;
+            let master_password = read_master_password(true)?;
+            Vault::create(&path, &master_password, KdfParams::default())?;
+            println!("Created a vault at {}", path.display());
+        }
+        Commands::Add {
+            name,
+            username,
+            generate,
+            force,
+        } => {
+            // Opened first, so a wrong master password fails before the password is typed.
+            let mut vault = Vault::open(&vault_path()?, &read_master_password(false)?)?;
+            if vault.get(&name).is_some() && !force {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!(
+                        "there's an entry named '{}' already, use --force to replace it",
+                        name
+                    ),
+                ));
+            }
+            let password = if generate {
+                let options = GenerateArgs::parse_default();
+                self::generate(&random, &options)?.0
+            } else {
+                read_entry_password()?
+            };
+            let entry = Entry {
+                username,
+                password,
+                updated_at: Utc::now(),
+            };
+            let replaced = vault.insert(name.clone(), entry).is_some();
+            vault.save()?;
+            println!("{} {}", if replaced { "Replaced" } else { "Added" }, name);
+        }
+        Commands::Get { name, username } => {
+            let vault = Vault::open(&vault_path()?, &read_master_password(false)?)?;
+            let entry = vault.get(&name).ok_or_else(|| no_entry(&name))?;
+            if username {
+                let username = entry.username.as_ref().ok_or_else(|| {
+                    Error::new(ErrorKind::NotFound, format!("'{}' has no username", name))
+                })?;
+                println!("{}", username);
+            } else {
+                println!("{}", *entry.password);
+            }
+        }
+        Commands::List => {
+            let vault = Vault::open(&vault_path()?, &read_master_password(false)?)?;
+            if vault.entries().is_empty() {
+                println!("The vault is empty");
+            }
+            let entries = vault.entries();
+            let name_width = entries.keys().map(|name| name.chars().count()).max();
+            let username_width = entries
+                .values()
+                .map(|entry| username(entry).chars().count());
+            let (name_width, username_width) = (
+                name_width.unwrap_or_default(),
+                username_width.max().unwrap_or_default(),
+            );
+            let now = Utc::now().naive_utc();
+            for (name, entry) in vault.entries() {
+                let updated = format_relative(entry.updated_at.naive_utc(), now);
+                println!(
+                    "{:<name_width$}  {:<username_width$}  (updated {})",
+                    name,
+                    username(entry),
+                    updated
+                );
+            }
+        }
+        Commands::Remove { name } => {
+            let mut vault = Vault::open(&vault_path()?, &read_master_password(false)?)?;
+            vault.remove(&name).ok_or_else(|| no_entry(&name))?;
+            vault.save()?;
+            println!("Removed {}", name);
+        }
+    }
+    Ok(())
+}
+
+impl GenerateArgs {
+    /// The options of `generate` without any given.
+    fn parse_default() -> Self {
+        #[derive(Parser)]
+        struct Defaults {
+            #[command(flatten)]
+            options: GenerateArgs,
+        }
+        Defaults::parse_from(["generate"]).options
+    }
+}
+
+/// A password, or a passphrase, with how many bits of entropy it has.
+fn generate(
+    random: &SystemRandom,
+    options: &GenerateArgs,
+) -> Result<(Zeroizing<String>, f64), Error> {
+    let invalid_input = |message: &str| Error::new(ErrorKind::InvalidInput, message.to_string());
+    if options.passphrase {
+        if options.words == 0 {
+            return Err(invalid_input("a passphrase needs at least one word"));
+        }
+        let passphrase = generate::passphrase(random, options.words, &options.separator)?;
+        let bits = generate::entropy_bits(generate::words().len(), options.words);
+        return Ok((passphrase, bits));
+    }
+
+    let sets: Vec<Vec<char>> = match &options.charset {
+        Some(charset) => {
+            // Characters given twice would be picked twice as often.
+            let mut characters: Vec<char> = Vec::new();
+            for c in charset.chars().filter(|c| !c.is_control()) {
+                if !characters.contains(&c) {
+                    characters.push(c);
+                }
+            }
+            vec![characters]
+        }
+        None => [
+            (LOWERCASE, options.no_lowercase),
+            (UPPERCASE, options.no_uppercase),
+            (DIGITS, options.no_digits),
+            (SYMBOLS, options.no_symbols),
+        ]
+        .into_iter()
+        .filter(|(_, left_out)| !left_out)
+        .map(|(set, _)| set.chars().collect())
+        .collect(),
+    };
+    if sets.iter().all(Vec::is_empty) {
+        return Err(invalid_input("there are no characters to pick from"));
+    }
+    let password = generate::password(random, options.length, &sets)?;
+    let choices = sets.iter().map(Vec::len).sum();
+    Ok((password, generate::entropy_bits(choices, options.length)))
+}
+
+/// The master password from `PASSWORD_VAULT_MASTER`, or typed on the terminal. A new one is
+/// asked for twice, to catch typos, since the vault can't be opened without it.
+fn read_master_password(is_new: bool) -> Result<Zeroizing<String>, Error> {
+    let from_env = env::var("PASSWORD_VAULT_MASTER")
+        .ok()
+        .filter(|p| !p.is_empty());
+    let password = match from_env {
+        Some(password) => Zeroizing::new(password),
+        None => {
+            if !io::stdin().is_terminal() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "a master password is needed, set PASSWORD_VAULT_MASTER when not running \
+                     in a terminal",
+                ));
+            }
+            let password = Zeroizing::new(terminal::read_hidden("Master password: ")?);
+            let repeated = || terminal::read_hidden("Repeat the master password: ");
+            if is_new && *Zeroizing::new(repeated()?) != *password {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "the master passwords don't match",
+                ));
+            }
+            password
+        }
+    };
+    if is_new && password.chars().count() < MIN_MASTER_PASSWORD_LEN {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "the master password needs at least {} characters",
+                MIN_MASTER_PASSWORD_LEN
+            ),
+        ));
+    }
+    Ok(password)
+}
+
+/// The password of a new entry, typed twice on the terminal, or the first line of stdin, so
+/// `generate` can be piped into `add`.
+fn read_entry_password() -> Result<Zeroizing<String>, Error> {
+    let password = if io::stdin().is_terminal() {
+        let password = Zeroizing::new(terminal::read_hidden("Password: ")?);
+        if *Zeroizing::new(terminal::read_hidden("Repeat the password: ")?) != *password {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the passwords don't match",
+            ));
+        }
+        password
+    } else {
+        let mut line = Zeroizing::new(String::new());
+        io::stdin().lock().read_line(&mut line)?;
+        let len = line.trim_end_matches(['\r', '\n']).len();
+        line.truncate(len);
+        line
+    };
+    if password.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "the password is empty"));
+    }
+    Ok(password)
+}
+
+fn username(entry: &Entry) -> &str {
+    entry.username.as_deref().unwrap_or("-")
+}
+
+fn no_entry(name: &str) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        format!("there's no entry named '{}'", name),
+    )
+}
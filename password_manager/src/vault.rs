@@ -0,0 +1,323 @@
+//! The vault: a single JSON file with the entries encrypted by ChaCha20-Poly1305, under a key
+//! derived from the master password with Argon2id. What's needed to derive the key again is
+//! kept next to the ciphertext, and authenticated along with it, so the file can't be changed
+//! unnoticed, not even its settings.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+use common::{config::xdg_dir, error::with_path};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::{Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+use zeroize::Zeroizing;
+
+const APP_DIR_NAME: &str = "password_manager";
+const FILE_NAME: &str = "vault.json";
+const FORMAT_VERSION: u32 = 1;
+const CIPHER: &str = "chacha20-poly1305";
+const KEY_DERIVATION: &str = "argon2id";
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+/// `$XDG_DATA_HOME/password_manager/vault.json` or
+/// `~/.local/share/password_manager/vault.json`.
+pub fn default_path() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join(APP_DIR_NAME).join(FILE_NAME))
+}
+
+/// How much work deriving the key takes, which is what makes guessing master passwords slow.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// The second of the settings recommended by RFC 9106, for machines with less memory
+    /// to spare than the 2 GiB of the first.
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 4,
+        }
+    }
+}
+
+/// What the file says about how it's encrypted, which is the additional data of the cipher.
+#[derive(Clone, Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    cipher: String,
+    key_derivation: String,
+    #[serde(flatten)]
+    params: KdfParams,
+    salt: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    #[serde(flatten)]
+    header: Header,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    pub password: Zeroizing<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An open vault, with its entries decrypted, by name.
+pub struct Vault {
+    path: PathBuf,
+    header: Header,
+    key: LessSafeKey,
+    entries: BTreeMap<String, Entry>,
+    random: SystemRandom,
+}
+
+impl Vault {
+    /// Creates an empty vault at `path`, which mustn't exist, so a vault is never replaced by
+    /// accident.
+    pub fn create(path: &Path, master_password: &str, params: KdfParams) -> Result<Vault, Error> {
+        if path.exists() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("there's a vault at {} already", path.display()),
+            ));
+        }
+        let random = SystemRandom::new();
+        let mut salt = [0; SALT_LEN];
+        random.fill(&mut salt).map_err(|_| random_failed())?;
+        let header = Header {
+            version: FORMAT_VERSION,
+            cipher: CIPHER.to_string(),
+            key_derivation: KEY_DERIVATION.to_string(),
+            params,
+            salt: BASE64.encode(salt),
+        };
+        let vault = Vault {
+            path: path.to_path_buf(),
+            key: derive_key(master_password, &salt, params)?,
+            header,
+            entries: BTreeMap::new(),
+            random,
+        };
+        vault.save()?;
+        Ok(vault)
+    }
+
+    /// Opens the vault at `path`, failing if the master password is wrong or the file was
+    /// changed.
+    pub fn open(path: &Path, master_password: &str) -> Result<Vault, Error> {
+        let contents = fs::read_to_string(path).map_err(|error| match error.kind() {
+            ErrorKind::NotFound => Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "there's no vault at {}, create one with `init`",
+                    path.display()
+                ),
+            ),
+            _ => with_path(error, path),
+        })?;
+        let file: VaultFile = serde_json::from_str(&contents)
+            .map_err(|error| invalid(format!("{} isn't a vault: {}", path.display(), error)))?;
+        let header = file.header;
+        if header.version != FORMAT_VERSION
+            || header.cipher != CIPHER
+            || header.key_derivation != KEY_DERIVATION
+        {
+            return Err(invalid(format!(
+                "{} is a vault of version {}, encrypted with {} and {}, which isn't supported",
+                path.display(),
+                header.version,
+                header.cipher,
+                header.key_derivation
+            )));
+        }
+        let decode = |text: &str| {
+            BASE64
+                .decode(text)
+                .map_err(|error| invalid(error.to_string()))
+        };
+        let salt = decode(&header.salt)?;
+        let nonce = Nonce::try_assume_unique_for_key(&decode(&file.nonce)?)
+            .map_err(|_| invalid("the nonce has the wrong length".to_string()))?;
+        let mut ciphertext = Zeroizing::new(decode(&file.ciphertext)?);
+
+        let key = derive_key(master_password, &salt, header.params)?;
+        let aad = serde_json::to_vec(&header).map_err(Error::other)?;
+        let plaintext = key
+            .open_in_place(nonce, Aad::from(aad), &mut ciphertext)
+            .map_err(|_| {
+                Error::new(
+                    ErrorKind::PermissionDenied,
+                    "the master password is wrong, or the vault was changed",
+                )
+            })?;
+        let entries = serde_json::from_slice(plaintext)
+            .map_err(|error| invalid(format!("the entries can't be read: {}", error)))?;
+        Ok(Vault {
+            path: path.to_path_buf(),
+            header,
+            key,
+            entries,
+            random: SystemRandom::new(),
+        })
+    }
+
+    pub fn entries(&self) -> &BTreeMap<String, Entry> {
+        &self.entries
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.get(name)
+    }
+
+    /// Adds an entry, or replaces the one with the name, returning it.
+    pub fn insert(&mut self, name: String, entry: Entry) -> Option<Entry> {
+        self.entries.insert(name, entry)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Entry> {
+        self.entries.remove(name)
+    }
+
+    /// Encrypts the entries with a new nonce, since a nonce must never be used twice with the
+    /// same key, and replaces the file with them.
+    pub fn save(&self) -> Result<(), Error> {
+        let mut nonce = [0; NONCE_LEN];
+        self.random.fill(&mut nonce).map_err(|_| random_failed())?;
+        let mut ciphertext =
+            Zeroizing::new(serde_json::to_vec(&self.entries).map_err(Error::other)?);
+        let aad = serde_json::to_vec(&self.header).map_err(Error::other)?;
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad),
+                &mut *ciphertext,
+            )
+            .map_err(|_| Error::other("encrypting failed"))?;
+        let file = VaultFile {
+            header: self.header.clone(),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(&*ciphertext),
+        };
+        let contents = serde_json::to_string_pretty(&file).map_err(Error::other)?;
+        write_private(&self.path, contents.as_bytes())
+    }
+}
+
+fn derive_key(master_password: &str, salt: &[u8], params: KdfParams) -> Result<LessSafeKey, Error> {
+    let argon2_params = argon2::Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        Some(KEY_LEN),
+    )
+    .map_err(|error| invalid(format!("invalid key derivation settings: {}", error)))?;
+    let argon2 = argon2::Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        argon2_params,
+    );
+    let mut key = Zeroizing::new([0; KEY_LEN]);
+    argon2
+        .hash_password_into(master_password.as_bytes(), salt, &mut *key)
+        .map_err(|error| Error::other(format!("deriving the key failed: {}", error)))?;
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &*key)
+        .map_err(|_| Error::other("the key has the wrong length"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Writes a file only its owner can read, next to `path` first and then renamed, so a vault is
+/// never left half written.
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|error| with_path(error, dir))?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(&tmp_path)
+        .map_err(|error| with_path(error, &tmp_path))?;
+    file.write_all(contents)
+        .and_then(|()| file.sync_all())
+        .map_err(|error| with_path(error, &tmp_path))?;
+    fs::rename(&tmp_path, path).map_err(|error| with_path(error, path))
+}
+
+fn random_failed() -> Error {
+    Error::other("the system's random number generator failed")
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// Little work, so the tests stay fast.
+    const TEST_PARAMS: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn opens_only_with_the_master_password() {
+        let dir = env::temp_dir().join(format!("password-vault-test-{}", std::process::id()));
+        let path = dir.join("vault.json");
+        let mut vault = Vault::create(&path, "correct horse", TEST_PARAMS).unwrap();
+        let entry = Entry {
+            username: Some("ada".to_string()),
+            password: Zeroizing::new("s3cret!".to_string()),
+            updated_at: Utc::now(),
+        };
+        vault.insert("github".to_string(), entry.clone());
+        vault.save().unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("s3cret") && !contents.contains("github"));
+        assert_eq!(
+            Vault::create(&path, "correct horse", TEST_PARAMS)
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::AlreadyExists
+        );
+
+        let vault = Vault::open(&path, "correct horse").unwrap();
+        assert_eq!(vault.get("github"), Some(&entry));
+        let error = Vault::open(&path, "battery staple").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+
+        // The settings are authenticated too, so they can't be lowered unnoticed.
+        fs::write(
+            &path,
+            contents.replace("\"iterations\": 1", "\"iterations\": 2"),
+        )
+        .unwrap();
+        let error = Vault::open(&path, "correct horse").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+//! Runs the `password` binary with a vault in a temporary directory, and the master password
+//! from the environment, as scripts would.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+const MASTER_PASSWORD: &str = "correct horse battery staple";
+
+fn password(vault: &Path, master_password: &str, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_password"))
+        .args(args)
+        .env("PASSWORD_VAULT", vault)
+        .env("PASSWORD_VAULT_MASTER", master_password)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn stderr(output: Output) -> String {
+    assert!(!output.status.success(), "{:?}", output);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn generates_passwords() {
+    let vault = Path::new("unused.json");
+    let generated = stdout(password(vault, "", &["generate"], ""));
+    assert_eq!(generated.trim_end().chars().count(), 20);
+
+    let pin = stdout(password(
+        vault,
+        "",
+        &["generate", "-l", "6", "--charset", "0123456789"],
+        "",
+    ));
+    assert!(pin.trim_end().len() == 6 && pin.trim_end().chars().all(|c| c.is_ascii_digit()));
+
+    let passphrase = stdout(password(
+        vault,
+        "",
+        &["generate", "--passphrase", "--words", "4"],
+        "",
+    ));
+    assert_eq!(passphrase.trim_end().split('-').count(), 4);
+
+    let options = [
+        "generate",
+        "--no-lowercase",
+        "--no-uppercase",
+        "--no-digits",
+        "--no-symbols",
+    ];
+    assert_eq!(
+        stderr(password(vault, "", &options, "")),
+        "error: there are no characters to pick from\n"
+    );
+}
+
+#[test]
+fn keeps_passwords_in_the_vault() {
+    let dir = env::temp_dir().join(format!("password-cli-test-{}", std::process::id()));
+    let vault = dir.join("vault.json");
+    let run = |args: &[&str], stdin: &str| password(&vault, MASTER_PASSWORD, args, stdin);
+
+    assert!(stderr(run(&["list"], "")).contains("create one with `init`"));
+    assert_eq!(
+        stderr(password(&vault, "short", &["init"], "")),
+        "error: the master password needs at least 8 characters\n"
+    );
+    assert!(stdout(run(&["init"], "")).starts_with("Created a vault at "));
+    assert!(stderr(run(&["init"], "")).contains("there's a vault at"));
+
+    assert_eq!(
+        stdout(run(&["add", "github", "-u", "ada"], "hunter22\n")),
+        "Added github\n"
+    );
+    assert!(stderr(run(&["add", "github"], "other\n")).contains("use --force"));
+    assert_eq!(
+        stdout(run(&["add", "gitlab", "--generate"], "")),
+        "Added gitlab\n"
+    );
+    assert_eq!(stdout(run(&["get", "github"], "")), "hunter22\n");
+    assert_eq!(stdout(run(&["get", "github", "--username"], "")), "ada\n");
+    assert_eq!(
+        stdout(run(&["get", "gitlab"], ""))
+            .trim_end()
+            .chars()
+            .count(),
+        20
+    );
+
+    let list = stdout(run(&["list"], ""));
+    assert_eq!(
+        list,
+        "github  ada  (updated just now)\ngitlab  -    (updated just now)\n"
+    );
+    assert!(!fs::read_to_string(&vault).unwrap().contains("hunter22"));
+    assert_eq!(
+        stderr(password(&vault, "not the password", &["list"], "")),
+        "error: the master password is wrong, or the vault was changed\n"
+    );
+
+    assert_eq!(stdout(run(&["remove", "github"], "")), "Removed github\n");
+    assert_eq!(
+        stderr(run(&["get", "github"], "")),
+        "error: there's no entry named 'github'\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,59 @@
+//! How long pastes are kept, written like `10m`, `1h`, `1d`, `2w` or `never`.
+
+use chrono::Duration;
+
+/// The choices of the form on the front page, with their labels.
+pub const CHOICES: [(&str, &str); 5] = [
+    ("10m", "10 minutes"),
+    ("1h", "1 hour"),
+    ("1d", "1 day"),
+    ("1w", "1 week"),
+    ("never", "Never"),
+];
+
+/// Reads an expiry, as a duration, or `None` for pastes that are kept for good.
+pub fn parse(text: &str) -> Result<Option<Duration>, String> {
+    let invalid = || {
+        format!(
+            "invalid expiry '{}', expected one like 10m, 1h, 1d, 1w or never",
+            text
+        )
+    };
+    let text = text.trim();
+    if text == "never" {
+        return Ok(None);
+    }
+    let unit_start = text
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let count: i64 = text[..unit_start].parse().map_err(|_| invalid())?;
+    let minutes = match &text[unit_start..] {
+        "m" => 1,
+        "h" => 60,
+        "d" => 60 * 24,
+        "w" => 60 * 24 * 7,
+        _ => return Err(invalid()),
+    };
+    match count.checked_mul(minutes).and_then(Duration::try_minutes) {
+        Some(duration) if count > 0 => Ok(Some(duration)),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_expiries() {
+        assert_eq!(parse("10m"), Ok(Some(Duration::minutes(10))));
+        assert_eq!(parse("2w"), Ok(Some(Duration::days(14))));
+        assert_eq!(parse("never"), Ok(None));
+        for (choice, _) in CHOICES {
+            assert!(parse(choice).is_ok());
+        }
+        for invalid in ["", "0h", "h", "1y", "1.5h", "99999999999999999w"] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+//! A pastebin: snippets of text and code are posted, get a short link, and are shown
+//! highlighted, or as they are, until they expire.
+
+pub mod expiry;
+pub mod pages;
+pub mod pastes;
+pub mod server;
@@ -0,0 +1,102 @@
+use chrono::Utc;
+use clap::Parser;
+use pastebin::{
+    expiry,
+    pastes::{self, Pastes},
+    server::{self, Config},
+};
+use std::{net::IpAddr, path::PathBuf, time::Duration};
+use tokio::net::TcpListener;
+use tracing::{error, info};
+use tracing_subscriber::EnvFilter;
+
+/// How often expired pastes are deleted. They can't be seen once they expire anyway.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Serves a pastebin: snippets of text and code with short links, shown highlighted.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The address to listen on
+    #[arg(long, env = "PASTEBIN_BIND", default_value = "127.0.0.1")]
+    bind: IpAddr,
+    #[arg(long, env = "PASTEBIN_PORT", default_value_t = 8090)]
+    port: u16,
+    /// The database of the pastes, `~/.local/share/pastebin/pastes.db` if not given
+    #[arg(long, env = "PASTEBIN_DATABASE", value_name = "path")]
+    database: Option<PathBuf>,
+    /// Where the server is reached, like https://paste.example.com, for the links to pastes
+    #[arg(long, env = "PASTEBIN_BASE_URL", value_name = "url")]
+    base_url: Option<String>,
+    /// The size of the largest paste, in KiB
+    #[arg(
+        long,
+        env = "PASTEBIN_MAX_SIZE",
+        value_name = "KiB",
+        default_value_t = 512
+    )]
+    max_size: usize,
+    /// How long pastes are kept when no expiry is asked for, like 1h, 1d, 1w or never
+    #[arg(
+        long,
+        env = "PASTEBIN_DEFAULT_EXPIRY",
+        value_name = "expiry",
+        default_value = "1w",
+        value_parser = parse_expiry
+    )]
+    default_expiry: String,
+}
+
+fn parse_expiry(text: &str) -> Result<String, String> {
+    expiry::parse(text).map(|_| text.to_string())
+}
+
+#[tokio::main]
+async fn main() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+    let args = Args::parse();
+
+    let Some(path) = args.database.or_else(pastes::default_path) else {
+        error!("No home directory for the database, pick a file with --database");
+        std::process::exit(1);
+    };
+    let pastes = Pastes::open(&path).await.unwrap_or_else(|error| {
+        error!("Cannot open {}: {}", path.display(), error);
+        std::process::exit(1);
+    });
+    spawn_cleanup(pastes.clone());
+
+    let listener = TcpListener::bind((args.bind, args.port))
+        .await
+        .unwrap_or_else(|error| {
+            error!("Cannot listen on {}:{}: {}", args.bind, args.port, error);
+            std::process::exit(1);
+        });
+    info!("Listening on http://{}", listener.local_addr().unwrap());
+    let config = Config {
+        base_url: args.base_url,
+        max_size: args.max_size.saturating_mul(1024),
+        default_expiry: args.default_expiry,
+    };
+    axum::serve(listener, server::app(pastes, config))
+        .with_graceful_shutdown(common::shutdown::signal())
+        .await
+        .unwrap();
+    info!("Pastebin has shut down");
+}
+
+/// Deletes the expired pastes in the background, so they don't take up the database.
+fn spawn_cleanup(pastes: Pastes) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match pastes.delete_expired(Utc::now()).await {
+                Ok(0) => {}
+                Ok(deleted) => info!("Deleted {} expired pastes", deleted),
+                Err(error) => error!("Cleanup failed: {}", error),
+            }
+        }
+    });
+}
@@ -0,0 +1,181 @@
+//! The HTML pages: the form new pastes are written in, and pastes with their code highlighted
+//! and their lines numbered.
+
+use crate::{expiry, pastes::Paste};
+use chrono::{DateTime, Utc};
+use common::{dates::format_relative, highlight, html::escape};
+use std::fmt::Write;
+
+/// The languages of the form, by the names [`highlight`] knows them by, with their labels.
+/// The empty name is plain text.
+pub const LANGUAGES: [(&str, &str); 15] = [
+    ("", "Plain text"),
+    ("sh", "Shell"),
+    ("c", "C"),
+    ("cpp", "C++"),
+    ("csharp", "C#"),
+    ("go", "Go"),
+    ("java", "Java"),
+    ("javascript", "JavaScript"),
+    ("json", "JSON"),
+    ("python", "Python"),
+    ("rust", "Rust"),
+    ("sql", "SQL"),
+    ("toml", "TOML"),
+    ("typescript", "TypeScript"),
+    ("yaml", "YAML"),
+];
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 64rem; margin: 2rem auto; padding: 0 1rem; color: #1f2328; }
+a { color: #0969da; }
+textarea { width: 100%; height: 24rem; font-family: ui-monospace, monospace; font-size: 0.9rem; box-sizing: border-box; }
+.meta { color: #656d76; }
+.code { display: flex; border: 1px solid #d0d7de; border-radius: 6px; background: #f6f8fa; overflow-x: auto; }
+.code pre { margin: 0; padding: 1rem; font-family: ui-monospace, monospace; font-size: 0.9rem; line-height: 1.4; }
+.lines { color: #8c959f; text-align: right; user-select: none; border-right: 1px solid #d0d7de; }
+.hl-keyword { color: #cf222e; }
+.hl-string { color: #0a3069; }
+.hl-comment { color: #6e7781; font-style: italic; }
+.hl-number { color: #0550ae; }
+";
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        STYLE,
+        body
+    )
+}
+
+/// The front page, with the form for a new paste, and how to post one with curl to the
+/// server at `base_url`.
+pub fn index(base_url: &str, max_size: usize, default_expiry: &str) -> String {
+    let mut languages = String::new();
+    for (name, label) in LANGUAGES {
+        let _ = writeln!(languages, "<option value=\"{}\">{}</option>", name, label);
+    }
+    let mut expiries = String::new();
+    for (value, label) in expiry::CHOICES {
+        let selected = if value == default_expiry {
+            " selected"
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            expiries,
+            "<option value=\"{}\"{}>{}</option>",
+            value, selected, label
+        );
+    }
+    let body = format!(
+        "<h1>New paste</h1>\n\
+         <form method=\"post\" action=\"/\">\n\
+         <p><textarea name=\"content\" required autofocus spellcheck=\"false\"></textarea></p>\n\
+         <p><label>Language <select name=\"language\">\n{}</select></label>\n\
+         <label>Expires <select name=\"expires\">\n{}</select></label>\n\
+         <button type=\"submit\">Paste</button></p>\n\
+         </form>\n\
+         <p class=\"meta\">Pastes can be up to {} KiB. Post them from the terminal too, with \
+         <code>curl --data-binary @main.rs '{}/?language=rust&amp;expires=1d'</code>.</p>\n",
+        languages,
+        expiries,
+        max_size / 1024,
+        escape(base_url)
+    );
+    page("New paste", &body)
+}
+
+/// A paste, highlighted if its language is known, with links to it as it is.
+pub fn paste(paste: &Paste, now: DateTime<Utc>) -> String {
+    let code = match &paste.language {
+        Some(language) => highlight::highlight(language, &paste.content),
+        None => None,
+    }
+    .unwrap_or_else(|| escape(&paste.content));
+    let line_count = paste.content.lines().count().max(1);
+    let lines: Vec<String> = (1..=line_count).map(|line| line.to_string()).collect();
+    let language = match &paste.language {
+        Some(language) => LANGUAGES
+            .iter()
+            .find(|(name, _)| name == language)
+            .map_or(language.as_str(), |(_, label)| label),
+        None => "Plain text",
+    };
+    let expiry = match paste.expires_at {
+        Some(expires_at) => format!("expires {}", expires_at.format("%Y-%m-%d %H:%M UTC")),
+        None => "never expires".to_string(),
+    };
+    let body = format!(
+        "<h1>Paste {code}</h1>\n\
+         <p class=\"meta\">{language}, {lines}, created {created}, {expiry} \u{b7} \
+         <a href=\"/{code}/raw\">Raw</a> \u{b7} <a href=\"/{code}/download\">Download</a> \
+         \u{b7} <a href=\"/\">New paste</a></p>\n\
+         <div class=\"code\"><pre class=\"lines\">{numbers}</pre>\
+         <pre><code>{content}</code></pre></div>\n",
+        code = paste.code,
+        language = escape(language),
+        lines = if line_count == 1 {
+            "1 line".to_string()
+        } else {
+            format!("{} lines", line_count)
+        },
+        created = format_relative(paste.created_at.naive_utc(), now.naive_utc()),
+        expiry = expiry,
+        numbers = lines.join("\n"),
+        content = code,
+    );
+    page(&format!("Paste {}", paste.code), &body)
+}
+
+/// The page of codes that don't exist, or whose pastes have expired.
+pub fn not_found() -> String {
+    page(
+        "Paste not found",
+        "<h1>Paste not found</h1>\n\
+         <p>This paste doesn't exist, or it has expired. <a href=\"/\">Write a new one</a>.</p>\n",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn renders_pastes() {
+        let now = DateTime::from_timestamp(1_728_900_000, 0).unwrap();
+        let mut paste = Paste {
+            code: "aZ3kQ9xY".to_string(),
+            content: "let x = \"<b>\";\nx\n".to_string(),
+            language: Some("rust".to_string()),
+            created_at: now - Duration::minutes(5),
+            expires_at: None,
+        };
+        let html = super::paste(&paste, now);
+        assert!(html.contains("<title>Paste aZ3kQ9xY</title>"));
+        assert!(html.contains("Rust, 2 lines, created 5m ago, never expires"));
+        assert!(html.contains("<pre class=\"lines\">1\n2</pre>"));
+        assert!(html.contains(
+            "<span class=\"hl-keyword\">let</span> x = \
+             <span class=\"hl-string\">&quot;&lt;b&gt;&quot;</span>;"
+        ));
+
+        paste.language = None;
+        paste.expires_at = Some(now + Duration::days(1));
+        let html = super::paste(&paste, now);
+        assert!(html.contains("Plain text, 2 lines, created 5m ago, expires 2024-10-15 10:00 UTC"));
+        assert!(html.contains("<code>let x = &quot;&lt;b&gt;&quot;;\nx\n</code>"));
+
+        let html = index("http://localhost:8090", 512 * 1024, "1w");
+        assert!(html.contains("<option value=\"1w\" selected>1 week</option>"));
+        assert!(html.contains("up to 512 KiB"));
+        assert!(html.contains("'http://localhost:8090/?language=rust&amp;expires=1d'"));
+        for (name, _) in &LANGUAGES[1..] {
+            assert!(highlight::is_known(name), "{}", name);
+        }
+    }
+}
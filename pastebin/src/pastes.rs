@@ -0,0 +1,207 @@
+//! The pastes, in a SQLite database, by their short codes.
+
+use chrono::{DateTime, Duration, Utc};
+use common::{config::xdg_dir, error::with_path, short_code};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow},
+    Row,
+};
+use std::{
+    fs,
+    io::Error,
+    path::{Path, PathBuf},
+};
+
+const APP_DIR_NAME: &str = "pastebin";
+const FILE_NAME: &str = "pastes.db";
+
+/// Codes of 8 characters of [`short_code::ALPHANUMERIC`] are hard to guess, so pastes can't be
+/// found by trying codes.
+const CODE_LENGTH: usize = 8;
+/// How many codes are tried when the ones generated are taken, which is very unlikely.
+const CODE_ATTEMPTS: usize = 5;
+
+// Times are seconds since the Unix epoch, which sort like the times they are.
+const SCHEMA: [&str; 2] = [
+    "CREATE TABLE IF NOT EXISTS pastes (
+        code TEXT PRIMARY KEY,
+        content TEXT NOT NULL,
+        language TEXT,
+        created_at INTEGER NOT NULL,
+        expires_at INTEGER
+    )",
+    "CREATE INDEX IF NOT EXISTS pastes_by_expiry ON pastes (expires_at)",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paste {
+    pub code: String,
+    pub content: String,
+    /// The language the paste is highlighted as, `None` for plain text.
+    pub language: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// When the paste expires, `None` if it's kept for good.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A paste to create.
+pub struct NewPaste {
+    pub content: String,
+    pub language: Option<String>,
+    pub expires_in: Option<Duration>,
+}
+
+/// The database, `$XDG_DATA_HOME/pastebin/pastes.db` or `~/.local/share/pastebin/pastes.db`.
+pub fn default_path() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join(APP_DIR_NAME).join(FILE_NAME))
+}
+
+#[derive(Clone)]
+pub struct Pastes {
+    pool: SqlitePool,
+}
+
+impl Pastes {
+    /// Opens the database, creating it and its directory if they don't exist yet.
+    pub async fn open(path: &Path) -> Result<Self, Error> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|error| with_path(error, dir))?;
+        }
+        // Pastes are read while others are written, which the write-ahead log allows.
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await
+            .map_err(database_error)?;
+        for statement in SCHEMA {
+            sqlx::query(statement)
+                .execute(&pool)
+                .await
+                .map_err(database_error)?;
+        }
+        Ok(Pastes { pool })
+    }
+
+    /// Saves a paste under a new random code.
+    pub async fn create(&self, paste: NewPaste, now: DateTime<Utc>) -> Result<Paste, Error> {
+        let expires_at = match paste.expires_in {
+            Some(duration) => Some(
+                now.checked_add_signed(duration)
+                    .ok_or_else(|| Error::other("the expiry is too far in the future"))?,
+            ),
+            None => None,
+        };
+        for _ in 0..CODE_ATTEMPTS {
+            let code = short_code::generate(short_code::ALPHANUMERIC, CODE_LENGTH);
+            let inserted = sqlx::query(
+                "INSERT INTO pastes (code, content, language, created_at, expires_at)
+                VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(&code)
+            .bind(&paste.content)
+            .bind(&paste.language)
+            .bind(now.timestamp())
+            .bind(expires_at.map(|time| time.timestamp()))
+            .execute(&self.pool)
+            .await;
+            match inserted {
+                Ok(_) => {
+                    return Ok(Paste {
+                        code,
+                        content: paste.content,
+                        language: paste.language,
+                        created_at: now,
+                        expires_at,
+                    })
+                }
+                Err(sqlx::Error::Database(error)) if error.is_unique_violation() => continue,
+                Err(error) => return Err(database_error(error)),
+            }
+        }
+        Err(Error::other(format!(
+            "no free code found in {} attempts",
+            CODE_ATTEMPTS
+        )))
+    }
+
+    /// The paste with the code, unless it doesn't exist or has expired by `now`.
+    pub async fn get(&self, code: &str, now: DateTime<Utc>) -> Result<Option<Paste>, Error> {
+        let row = sqlx::query(
+            "SELECT code, content, language, created_at, expires_at FROM pastes
+            WHERE code = $1 AND (expires_at IS NULL OR expires_at > $2)",
+        )
+        .bind(code)
+        .bind(now.timestamp())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(database_error)?;
+        Ok(row.as_ref().map(paste_from_row))
+    }
+
+    /// Deletes the pastes that expired by `now`, returning how many there were.
+    pub async fn delete_expired(&self, now: DateTime<Utc>) -> Result<u64, Error> {
+        let result = sqlx::query("DELETE FROM pastes WHERE expires_at <= $1")
+            .bind(now.timestamp())
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+        Ok(result.rows_affected())
+    }
+}
+
+fn paste_from_row(row: &SqliteRow) -> Paste {
+    let time = |seconds: i64| DateTime::from_timestamp(seconds, 0).unwrap_or_default();
+    Paste {
+        code: row.get("code"),
+        content: row.get("content"),
+        language: row.get("language"),
+        created_at: time(row.get("created_at")),
+        expires_at: row.get::<Option<i64>, _>("expires_at").map(time),
+    }
+}
+
+fn database_error(error: sqlx::Error) -> Error {
+    Error::other(format!("database error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[tokio::test]
+    async fn keeps_pastes_until_they_expire() {
+        let dir = env::temp_dir().join(format!("pastebin-test-{}", std::process::id()));
+        let pastes = Pastes::open(&dir.join(FILE_NAME)).await.unwrap();
+        let now = DateTime::from_timestamp(1_728_900_000, 0).unwrap();
+        let new_paste = |expires_in| NewPaste {
+            content: "fn main() {}\n".to_string(),
+            language: Some("rust".to_string()),
+            expires_in,
+        };
+        let kept = pastes.create(new_paste(None), now).await.unwrap();
+        let expiring = pastes
+            .create(new_paste(Some(Duration::hours(1))), now)
+            .await
+            .unwrap();
+        assert_eq!(kept.code.len(), CODE_LENGTH);
+        assert_ne!(kept.code, expiring.code);
+        assert_eq!(
+            pastes.get(&kept.code, now).await.unwrap(),
+            Some(kept.clone())
+        );
+
+        let later = now + Duration::hours(1);
+        assert!(pastes.get(&expiring.code, now).await.unwrap().is_some());
+        assert_eq!(pastes.get(&expiring.code, later).await.unwrap(), None);
+        assert_eq!(pastes.delete_expired(later).await.unwrap(), 1);
+        assert_eq!(pastes.delete_expired(later).await.unwrap(), 0);
+        assert!(pastes.get(&kept.code, later).await.unwrap().is_some());
+        assert_eq!(pastes.get("missing", now).await.unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,270 @@
+//! The routes of the pastebin: the form at `/`, pastes posted to `/` as they are, like with
+//! `curl --data-binary`, or from the form to `/new`, and each paste at `/<code>`, as HTML, and
+//! at `/<code>/raw` and `/<code>/download` as text.
+
+use crate::{
+    expiry, pages,
+    pastes::{NewPaste, Paste, Pastes},
+};
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{
+        header::{
+            CONTENT_DISPOSITION, CONTENT_SECURITY_POLICY, CONTENT_TYPE, HOST, LOCATION,
+            X_CONTENT_TYPE_OPTIONS,
+        },
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use chrono::Utc;
+use common::highlight;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::error;
+
+/// Pages only need their own styles and form, so nothing else is allowed in them, in case a
+/// paste ever got through unescaped.
+const PAGE_POLICY: &str = "default-src 'none'; style-src 'unsafe-inline'; form-action 'self'";
+
+pub struct Config {
+    /// Where the server is reached, like `https://paste.example.com`, which the links to new
+    /// pastes start with. Without it, they're made from the `Host` of the request.
+    pub base_url: Option<String>,
+    /// The size of the largest paste, in bytes.
+    pub max_size: usize,
+    /// How long pastes are kept when no expiry is asked for, like `1w`.
+    pub default_expiry: String,
+}
+
+#[derive(Clone)]
+struct AppState {
+    pastes: Pastes,
+    config: Arc<Config>,
+}
+
+pub fn app(pastes: Pastes, config: Config) -> Router {
+    // Forms are URL-encoded, which may take three bytes for one, so their sizes are checked
+    // once they're decoded.
+    let body_limit = DefaultBodyLimit::max(config.max_size.saturating_mul(3));
+    let state = AppState {
+        pastes,
+        config: Arc::new(config),
+    };
+    Router::new()
+        .route("/", get(index).post(create))
+        .route("/new", post(create_from_form))
+        .route("/:code", get(show))
+        .route("/:code/raw", get(raw))
+        .route("/:code/download", get(download))
+        .layer(body_limit)
+        .with_state(state)
+}
+
+type Refused = (StatusCode, String);
+
+async fn index(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let config = &state.config;
+    let body = pages::index(
+        &base_url(config, &headers),
+        config.max_size,
+        &config.default_expiry,
+    );
+    page(StatusCode::OK, body)
+}
+
+#[derive(Deserialize)]
+struct PasteOptions {
+    language: Option<String>,
+    expires: Option<String>,
+}
+
+/// Creates a paste of the body of the request, with the options in the query, and answers
+/// with its link, for `curl`.
+async fn create(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(options): Query<PasteOptions>,
+    body: Bytes,
+) -> Result<impl IntoResponse, Refused> {
+    let content = String::from_utf8(body.to_vec()).map_err(|_| {
+        let message = "pastes have to be UTF-8 text".to_string();
+        (StatusCode::BAD_REQUEST, message)
+    })?;
+    let paste = save(&state, content, options).await?;
+    let url = format!("{}/{}", base_url(&state.config, &headers), paste.code);
+    Ok((
+        StatusCode::CREATED,
+        [(LOCATION, url.clone())],
+        format!("{}\n", url),
+    ))
+}
+
+#[derive(Deserialize)]
+struct PasteForm {
+    content: String,
+    #[serde(flatten)]
+    options: PasteOptions,
+}
+
+/// Creates a paste from the form of the front page, and shows it.
+async fn create_from_form(
+    State(state): State<AppState>,
+    Form(form): Form<PasteForm>,
+) -> Result<Redirect, Refused> {
+    let paste = save(&state, form.content, form.options).await?;
+    Ok(Redirect::to(&format!("/{}", paste.code)))
+}
+
+async fn save(state: &AppState, content: String, options: PasteOptions) -> Result<Paste, Refused> {
+    let max_size = state.config.max_size;
+    if content.len() > max_size {
+        let message = format!("pastes can be up to {} KiB", max_size / 1024);
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, message));
+    }
+    if content.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "the paste is empty".to_string()));
+    }
+    let language = parse_language(options.language.as_deref().unwrap_or_default())
+        .map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+    let expires = options
+        .expires
+        .as_deref()
+        .unwrap_or(&state.config.default_expiry);
+    let expires_in =
+        expiry::parse(expires).map_err(|message| (StatusCode::BAD_REQUEST, message))?;
+    let new_paste = NewPaste {
+        content,
+        language,
+        expires_in,
+    };
+    state
+        .pastes
+        .create(new_paste, Utc::now())
+        .await
+        .map_err(internal_error)
+}
+
+async fn show(State(state): State<AppState>, Path(code): Path<String>) -> Response {
+    match find(&state, &code).await {
+        Ok(Some(paste)) => page(StatusCode::OK, pages::paste(&paste, Utc::now())),
+        Ok(None) => page(StatusCode::NOT_FOUND, pages::not_found()),
+        Err(refused) => refused.into_response(),
+    }
+}
+
+async fn raw(State(state): State<AppState>, Path(code): Path<String>) -> Response {
+    match find(&state, &code).await {
+        Ok(Some(paste)) => text(paste.content, None),
+        Ok(None) => not_found(),
+        Err(refused) => refused.into_response(),
+    }
+}
+
+/// The paste as a file to save, named after its code and language, like `aZ3kQ9xY.rs`.
+async fn download(State(state): State<AppState>, Path(code): Path<String>) -> Response {
+    match find(&state, &code).await {
+        Ok(Some(paste)) => {
+            let file_name = format!("{}.{}", paste.code, extension(paste.language.as_deref()));
+            text(paste.content, Some(&file_name))
+        }
+        Ok(None) => not_found(),
+        Err(refused) => refused.into_response(),
+    }
+}
+
+async fn find(state: &AppState, code: &str) -> Result<Option<Paste>, Refused> {
+    state
+        .pastes
+        .get(code, Utc::now())
+        .await
+        .map_err(internal_error)
+}
+
+/// Reads the language of a paste, as one [`highlight`] knows, or `None` for plain text.
+fn parse_language(name: &str) -> Result<Option<String>, String> {
+    let name = name.trim().to_lowercase();
+    match name.as_str() {
+        "" | "text" | "plain" | "txt" => Ok(None),
+        _ if highlight::is_known(&name) => Ok(Some(name)),
+        _ => Err(format!("unknown language '{}'", name)),
+    }
+}
+
+/// The usual file extension of a language, or the name it was given as, which is often one.
+fn extension(language: Option<&str>) -> &str {
+    match language {
+        None => "txt",
+        Some("rust") => "rs",
+        Some("python") => "py",
+        Some("javascript") => "js",
+        Some("typescript") => "ts",
+        Some("golang") => "go",
+        Some("bash" | "shell" | "zsh") => "sh",
+        Some("c++") => "cpp",
+        Some("csharp") => "cs",
+        Some(language) => language,
+    }
+}
+
+fn base_url(config: &Config, headers: &HeaderMap) -> String {
+    match &config.base_url {
+        Some(base_url) => base_url.trim_end_matches('/').to_string(),
+        None => {
+            let host = headers.get(HOST).and_then(|host| host.to_str().ok());
+            format!("http://{}", host.unwrap_or("localhost"))
+        }
+    }
+}
+
+fn page(status: StatusCode, html: String) -> Response {
+    let headers = [
+        (CONTENT_TYPE, "text/html; charset=utf-8"),
+        (CONTENT_SECURITY_POLICY, PAGE_POLICY),
+    ];
+    (status, headers, html).into_response()
+}
+
+/// Text as it is, which browsers mustn't take for HTML however it looks.
+fn text(content: String, file_name: Option<&str>) -> Response {
+    let headers = [
+        (CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+        (X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+    ];
+    match file_name {
+        Some(file_name) => {
+            let disposition = format!("attachment; filename=\"{}\"", file_name);
+            (headers, [(CONTENT_DISPOSITION, disposition)], content).into_response()
+        }
+        None => (headers, content).into_response(),
+    }
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, "paste not found\n").into_response()
+}
+
+fn internal_error(error: std::io::Error) -> Refused {
+    error!("{}", error);
+    let message = "internal server error".to_string();
+    (StatusCode::INTERNAL_SERVER_ERROR, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_languages() {
+        assert_eq!(parse_language(" Rust "), Ok(Some("rust".to_string())));
+        assert_eq!(parse_language("text"), Ok(None));
+        assert_eq!(parse_language(""), Ok(None));
+        assert!(parse_language("cobol").is_err());
+        assert_eq!(extension(Some("rust")), "rs");
+        assert_eq!(extension(Some("toml")), "toml");
+        assert_eq!(extension(None), "txt");
+    }
+}
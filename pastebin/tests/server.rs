@@ -0,0 +1,178 @@
+//! Serves a pastebin on a free port, with a database in a temporary directory, and posts and
+//! reads pastes over HTTP.
+
+use chrono::{Duration, Utc};
+use pastebin::{
+    pastes::{NewPaste, Pastes},
+    server::{self, Config},
+};
+use reqwest::{
+    header::{
+        CONTENT_DISPOSITION, CONTENT_SECURITY_POLICY, CONTENT_TYPE, LOCATION,
+        X_CONTENT_TYPE_OPTIONS,
+    },
+    redirect::Policy,
+    StatusCode,
+};
+use std::{fs, path::PathBuf};
+
+const CODE: &str = "fn main() {\n    println!(\"<hello>\");\n}\n";
+
+struct TestServer {
+    base_url: String,
+    client: reqwest::Client,
+    pastes: Pastes,
+    dir: PathBuf,
+}
+
+impl TestServer {
+    async fn spawn(name: &str) -> TestServer {
+        let dir = std::env::temp_dir().join(format!("pastebin-{}-{}", name, std::process::id()));
+        let pastes = Pastes::open(&dir.join("pastes.db")).await.unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let config = Config {
+            base_url: None,
+            max_size: 1024,
+            default_expiry: "1w".to_string(),
+        };
+        let app = server::app(pastes.clone(), config);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        TestServer {
+            base_url: format!("http://{}", address),
+            client: reqwest::Client::builder()
+                .redirect(Policy::none())
+                .build()
+                .unwrap(),
+            pastes,
+            dir,
+        }
+    }
+
+    async fn get(&self, path: &str) -> reqwest::Response {
+        let url = format!("{}{}", self.base_url, path);
+        self.client.get(url).send().await.unwrap()
+    }
+
+    async fn post(&self, path: &str, body: impl Into<reqwest::Body>) -> reqwest::Response {
+        let url = format!("{}{}", self.base_url, path);
+        self.client.post(url).body(body).send().await.unwrap()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[tokio::test]
+async fn posts_and_shows_pastes() {
+    let server = TestServer::spawn("pastes").await;
+
+    let response = server.get("/").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key(CONTENT_SECURITY_POLICY));
+    assert!(response.text().await.unwrap().contains("<form"));
+
+    // Posted like `curl --data-binary @main.rs`, which says it's a form.
+    let response = server
+        .client
+        .post(format!("{}/?language=Rust&expires=1h", server.base_url))
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(CODE)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = response.headers()[LOCATION].to_str().unwrap().to_string();
+    assert_eq!(response.text().await.unwrap(), format!("{}\n", location));
+    let code = location
+        .strip_prefix(&format!("{}/", server.base_url))
+        .unwrap();
+    assert_eq!(code.len(), 8);
+
+    let html = server
+        .get(&format!("/{}", code))
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("Rust, 3 lines, created just now, expires "));
+    assert!(html.contains("<span class=\"hl-keyword\">fn</span> main()"));
+    assert!(html.contains("<span class=\"hl-string\">&quot;&lt;hello&gt;&quot;</span>"));
+
+    let response = server.get(&format!("/{}/raw", code)).await;
+    assert_eq!(
+        response.headers()[CONTENT_TYPE],
+        "text/plain; charset=utf-8"
+    );
+    assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(response.text().await.unwrap(), CODE);
+    let response = server.get(&format!("/{}/download", code)).await;
+    assert_eq!(
+        response.headers()[CONTENT_DISPOSITION].to_str().unwrap(),
+        format!("attachment; filename=\"{}.rs\"", code)
+    );
+    assert_eq!(response.text().await.unwrap(), CODE);
+
+    // The form of the front page, which goes on to the paste.
+    let response = server
+        .client
+        .post(format!("{}/new", server.base_url))
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body("content=a+%3C+b%0A&language=&expires=never")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let location = response.headers()[LOCATION].to_str().unwrap().to_string();
+    let html = server.get(&location).await.text().await.unwrap();
+    assert!(html.contains("Plain text, 1 line, created just now, never expires"));
+    assert!(html.contains("<code>a &lt; b\n</code>"));
+    let raw = server.get(&format!("{}/raw", location)).await;
+    assert_eq!(raw.text().await.unwrap(), "a < b\n");
+}
+
+#[tokio::test]
+async fn refuses_pastes_and_forgets_expired_ones() {
+    let server = TestServer::spawn("refused").await;
+    let refused = [
+        ("/", "  \n".as_bytes().to_vec(), "the paste is empty"),
+        (
+            "/?language=cobol",
+            b"DISPLAY 1".to_vec(),
+            "unknown language 'cobol'",
+        ),
+        ("/?expires=1y", b"x".to_vec(), "invalid expiry '1y'"),
+        ("/", vec![0xff, 0xfe], "pastes have to be UTF-8 text"),
+    ];
+    for (path, body, message) in refused {
+        let response = server.post(path, body).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", path);
+        assert!(response.text().await.unwrap().starts_with(message));
+    }
+    let response = server.post("/", "x".repeat(1025)).await;
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(response.text().await.unwrap(), "pastes can be up to 1 KiB");
+
+    let response = server.get("/missing").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(response.text().await.unwrap().contains("Paste not found"));
+
+    // A paste of two hours ago that expired an hour later.
+    let expired = NewPaste {
+        content: "old".to_string(),
+        language: None,
+        expires_in: Some(Duration::hours(1)),
+    };
+    let paste = server
+        .pastes
+        .create(expired, Utc::now() - Duration::hours(2))
+        .await
+        .unwrap();
+    for path in ["", "/raw", "/download"] {
+        let response = server.get(&format!("/{}{}", paste.code, path)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
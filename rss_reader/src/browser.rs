@@ -0,0 +1,57 @@
+//! Opening links in the browser people use, from `$BROWSER` or the desktop's default.
+
+use std::{env, io::Error, process::Command};
+
+/// The command that opens links in the default browser, with the arguments before the link.
+fn default_command() -> (&'static str, &'static [&'static str]) {
+    if cfg!(target_os = "macos") {
+        ("open", &[])
+    } else if cfg!(windows) {
+        // The empty title keeps `start` from taking links in quotes for one.
+        ("cmd", &["/C", "start", ""])
+    } else {
+        ("xdg-open", &[])
+    }
+}
+
+/// Opens `url` with `$BROWSER` if it's set, like `firefox` or `firefox --new-tab`, or with the
+/// default browser otherwise, waiting for the command to return.
+pub fn open(url: &str) -> Result<(), Error> {
+    let browser = env::var("BROWSER")
+        .ok()
+        .filter(|browser| !browser.trim().is_empty());
+    let (program, args): (String, Vec<String>) = match &browser {
+        Some(browser) => {
+            let mut words = browser.split_whitespace().map(str::to_string);
+            let program = words.next().unwrap_or_default();
+            (program, words.collect())
+        }
+        None => {
+            let (program, args) = default_command();
+            (
+                program.to_string(),
+                args.iter().map(|arg| arg.to_string()).collect(),
+            )
+        }
+    };
+    let status = Command::new(&program)
+        .args(&args)
+        .arg(url)
+        .status()
+        .map_err(|error| {
+            Error::new(
+                error.kind(),
+                format!(
+                    "couldn't run {}, set BROWSER to the browser to use: {}",
+                    program, error
+                ),
+            )
+        })?;
+    if !status.success() {
+        return Err(Error::other(format!(
+            "{} couldn't open {} ({})",
+            program, url, status
+        )));
+    }
+    Ok(())
+}
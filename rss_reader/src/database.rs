@@ -0,0 +1,400 @@
+//! The subscriptions and their items, with which ones were read, in a SQLite database.
+
+use crate::{feed::Feed, fetch::Validators};
+use chrono::{DateTime, Utc};
+use common::{config::xdg_dir, error::with_path};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow},
+    Row,
+};
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+const APP_DIR_NAME: &str = "rss_reader";
+const FILE_NAME: &str = "feeds.db";
+
+const SCHEMA: [&str; 2] = [
+    // The validators of the last answer, so feeds that didn't change aren't sent again.
+    "CREATE TABLE IF NOT EXISTS feeds (
+        id INTEGER PRIMARY KEY,
+        url TEXT NOT NULL UNIQUE,
+        title TEXT NOT NULL,
+        site_url TEXT,
+        etag TEXT,
+        last_modified TEXT,
+        fetched_at INTEGER
+    )",
+    // Times are seconds since the Unix epoch, which sort like the times they are.
+    "CREATE TABLE IF NOT EXISTS items (
+        id INTEGER PRIMARY KEY,
+        feed_id INTEGER NOT NULL REFERENCES feeds (id) ON DELETE CASCADE,
+        guid TEXT NOT NULL,
+        title TEXT NOT NULL,
+        link TEXT,
+        summary TEXT,
+        published_at INTEGER,
+        added_at INTEGER NOT NULL,
+        read INTEGER NOT NULL DEFAULT 0,
+        UNIQUE (feed_id, guid)
+    )",
+];
+
+/// A subscription, with how many of its items weren't read yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscription {
+    pub id: i64,
+    pub url: String,
+    pub title: String,
+    pub site_url: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub fetched_at: Option<DateTime<Utc>>,
+    pub unread: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredItem {
+    pub id: i64,
+    pub feed_title: String,
+    pub title: String,
+    pub link: Option<String>,
+    pub summary: Option<String>,
+    /// When the feed says the item was published, or else when it was first fetched.
+    pub date: DateTime<Utc>,
+    pub read: bool,
+}
+
+/// Which items [`Database::items`] returns.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ItemFilter {
+    pub feed_id: Option<i64>,
+    pub include_read: bool,
+    pub limit: Option<u32>,
+}
+
+/// The database, `$XDG_DATA_HOME/rss_reader/feeds.db` or
+/// `~/.local/share/rss_reader/feeds.db`.
+pub fn default_path() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join(APP_DIR_NAME).join(FILE_NAME))
+}
+
+pub struct Database {
+    pool: SqlitePool,
+}
+
+impl Database {
+    /// Opens the database, creating it and its directory if they don't exist yet.
+    pub async fn open(path: &Path) -> Result<Self, Error> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|error| with_path(error, dir))?;
+        }
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .map_err(database_error)?;
+        for statement in SCHEMA {
+            sqlx::query(statement)
+                .execute(&pool)
+                .await
+                .map_err(database_error)?;
+        }
+        Ok(Database { pool })
+    }
+
+    /// Subscribes to the feed at `url`, with the items it has now, returning the id of the
+    /// subscription.
+    pub async fn subscribe(
+        &self,
+        url: &str,
+        feed: &Feed,
+        validators: &Validators,
+    ) -> Result<i64, Error> {
+        let inserted = sqlx::query("INSERT INTO feeds (url, title) VALUES ($1, $2)")
+            .bind(url)
+            .bind(&feed.title)
+            .execute(&self.pool)
+            .await;
+        let id = match inserted {
+            Ok(result) => result.last_insert_rowid(),
+            Err(sqlx::Error::Database(error)) if error.is_unique_violation() => {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("already subscribed to {}", url),
+                ))
+            }
+            Err(error) => return Err(database_error(error)),
+        };
+        self.save_fetch(id, feed, validators, Utc::now()).await?;
+        Ok(id)
+    }
+
+    pub async fn unsubscribe(&self, feed_id: i64) -> Result<(), Error> {
+        sqlx::query("DELETE FROM feeds WHERE id = $1")
+            .bind(feed_id)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+        Ok(())
+    }
+
+    /// The subscriptions, sorted by title.
+    pub async fn subscriptions(&self) -> Result<Vec<Subscription>, Error> {
+        let rows = sqlx::query(
+            "SELECT feeds.*, (SELECT COUNT(*) FROM items WHERE feed_id = feeds.id AND NOT read)
+                AS unread
+            FROM feeds ORDER BY title COLLATE NOCASE, id",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(database_error)?;
+        rows.iter().map(subscription).collect()
+    }
+
+    /// Finds a subscription by its id, or by its title or URL, ignoring case.
+    pub async fn find_subscription(&self, query: &str) -> Result<Subscription, Error> {
+        let subscriptions = self.subscriptions().await?;
+        let found = match query.parse::<i64>() {
+            Ok(id) => subscriptions.into_iter().find(|feed| feed.id == id),
+            Err(_) => subscriptions.into_iter().find(|feed| {
+                feed.title.eq_ignore_ascii_case(query) || feed.url.eq_ignore_ascii_case(query)
+            }),
+        };
+        found.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("no feed '{}', see `rss feeds`", query),
+            )
+        })
+    }
+
+    /// Keeps what a fetch found, returning how many of the items are new. Items that were seen
+    /// before keep whether they were read, but get the title and summary they have now, since
+    /// feeds fix typos too.
+    pub async fn save_fetch(
+        &self,
+        feed_id: i64,
+        feed: &Feed,
+        validators: &Validators,
+        now: DateTime<Utc>,
+    ) -> Result<u64, Error> {
+        let mut transaction = self.pool.begin().await.map_err(database_error)?;
+        sqlx::query(
+            "UPDATE feeds SET title = $1, site_url = $2, etag = $3, last_modified = $4,
+                fetched_at = $5
+            WHERE id = $6",
+        )
+        .bind(&feed.title)
+        .bind(&feed.link)
+        .bind(&validators.etag)
+        .bind(&validators.last_modified)
+        .bind(now.timestamp())
+        .bind(feed_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(database_error)?;
+        let mut new_count = 0;
+        for item in &feed.items {
+            let exists = sqlx::query("SELECT 1 FROM items WHERE feed_id = $1 AND guid = $2")
+                .bind(feed_id)
+                .bind(&item.guid)
+                .fetch_optional(&mut *transaction)
+                .await
+                .map_err(database_error)?
+                .is_some();
+            new_count += u64::from(!exists);
+            sqlx::query(
+                "INSERT INTO items (feed_id, guid, title, link, summary, published_at, added_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (feed_id, guid) DO UPDATE SET
+                    title = excluded.title, link = excluded.link, summary = excluded.summary,
+                    published_at = excluded.published_at",
+            )
+            .bind(feed_id)
+            .bind(&item.guid)
+            .bind(&item.title)
+            .bind(&item.link)
+            .bind(&item.summary)
+            .bind(item.published.map(|published| published.timestamp()))
+            .bind(now.timestamp())
+            .execute(&mut *transaction)
+            .await
+            .map_err(database_error)?;
+        }
+        transaction.commit().await.map_err(database_error)?;
+        Ok(new_count)
+    }
+
+    /// Notes that a feed was fetched and hadn't changed.
+    pub async fn touch(&self, feed_id: i64, now: DateTime<Utc>) -> Result<(), Error> {
+        sqlx::query("UPDATE feeds SET fetched_at = $1 WHERE id = $2")
+            .bind(now.timestamp())
+            .bind(feed_id)
+            .execute(&self.pool)
+            .await
+            .map_err(database_error)?;
+        Ok(())
+    }
+
+    /// The items, newest first.
+    pub async fn items(&self, filter: ItemFilter) -> Result<Vec<StoredItem>, Error> {
+        let rows = sqlx::query(
+            "SELECT items.*, feeds.title AS feed_title FROM items
+            JOIN feeds ON feeds.id = items.feed_id
+            WHERE ($1 IS NULL OR feed_id = $1) AND ($2 OR NOT read)
+            ORDER BY COALESCE(published_at, added_at) DESC, items.id DESC
+            LIMIT $3",
+        )
+        .bind(filter.feed_id)
+        .bind(filter.include_read)
+        .bind(filter.limit.map_or(-1, i64::from))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(database_error)?;
+        rows.iter().map(stored_item).collect()
+    }
+
+    pub async fn item(&self, id: i64) -> Result<StoredItem, Error> {
+        let row = sqlx::query(
+            "SELECT items.*, feeds.title AS feed_title FROM items
+            JOIN feeds ON feeds.id = items.feed_id
+            WHERE items.id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(database_error)?;
+        match row {
+            Some(row) => stored_item(&row),
+            None => Err(Error::new(
+                ErrorKind::NotFound,
+                format!("no item {}, see `rss read --all`", id),
+            )),
+        }
+    }
+
+    pub async fn set_read(&self, ids: &[i64], read: bool) -> Result<(), Error> {
+        let mut transaction = self.pool.begin().await.map_err(database_error)?;
+        for id in ids {
+            sqlx::query("UPDATE items SET read = $1 WHERE id = $2")
+                .bind(read)
+                .bind(id)
+                .execute(&mut *transaction)
+                .await
+                .map_err(database_error)?;
+        }
+        transaction.commit().await.map_err(database_error)
+    }
+}
+
+fn subscription(row: &SqliteRow) -> Result<Subscription, Error> {
+    let fetched_at: Option<i64> = row.try_get("fetched_at").map_err(database_error)?;
+    Ok(Subscription {
+        id: row.try_get("id").map_err(database_error)?,
+        url: row.try_get("url").map_err(database_error)?,
+        title: row.try_get("title").map_err(database_error)?,
+        site_url: row.try_get("site_url").map_err(database_error)?,
+        etag: row.try_get("etag").map_err(database_error)?,
+        last_modified: row.try_get("last_modified").map_err(database_error)?,
+        fetched_at: fetched_at.and_then(|seconds| DateTime::from_timestamp(seconds, 0)),
+        unread: row.try_get("unread").map_err(database_error)?,
+    })
+}
+
+fn stored_item(row: &SqliteRow) -> Result<StoredItem, Error> {
+    let published_at: Option<i64> = row.try_get("published_at").map_err(database_error)?;
+    let added_at: i64 = row.try_get("added_at").map_err(database_error)?;
+    Ok(StoredItem {
+        id: row.try_get("id").map_err(database_error)?,
+        feed_title: row.try_get("feed_title").map_err(database_error)?,
+        title: row.try_get("title").map_err(database_error)?,
+        link: row.try_get("link").map_err(database_error)?,
+        summary: row.try_get("summary").map_err(database_error)?,
+        date: DateTime::from_timestamp(published_at.unwrap_or(added_at), 0).unwrap_or_default(),
+        read: row.try_get("read").map_err(database_error)?,
+    })
+}
+
+fn database_error(error: sqlx::Error) -> Error {
+    Error::other(format!("database error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::Item;
+    use std::env;
+
+    fn item(guid: &str, title: &str, published: &str) -> Item {
+        Item {
+            guid: guid.to_string(),
+            title: title.to_string(),
+            link: Some(format!("https://example.com/{}", guid)),
+            summary: None,
+            published: Some(published.parse().unwrap()),
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_items_and_what_was_read() {
+        let dir = env::temp_dir().join(format!("rss-reader-test-{}", std::process::id()));
+        let database = Database::open(&dir.join("feeds.db")).await.unwrap();
+        let mut feed = Feed {
+            title: "Example".to_string(),
+            link: None,
+            items: vec![
+                item("a", "First", "2024-10-12T10:00:00Z"),
+                item("b", "Second", "2024-10-13T10:00:00Z"),
+            ],
+        };
+        let url = "https://example.com/feed.xml";
+        let id = database
+            .subscribe(url, &feed, &Validators::default())
+            .await
+            .unwrap();
+        let error = database
+            .subscribe(url, &feed, &Validators::default())
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+
+        let unread = database.items(ItemFilter::default()).await.unwrap();
+        let titles: Vec<&str> = unread.iter().map(|item| item.title.as_str()).collect();
+        assert_eq!(titles, ["Second", "First"]);
+        database.set_read(&[unread[0].id], true).await.unwrap();
+
+        // The item that was read stays read, with its new title, and only the third is new.
+        feed.items[1].title = "Second, fixed".to_string();
+        feed.items.push(item("c", "Third", "2024-10-14T10:00:00Z"));
+        let now = Utc::now();
+        let validators = Validators {
+            etag: Some("\"v2\"".to_string()),
+            last_modified: None,
+        };
+        let new_count = database.save_fetch(id, &feed, &validators, now).await;
+        assert_eq!(new_count.unwrap(), 1);
+        let subscription = database.find_subscription("EXAMPLE").await.unwrap();
+        assert_eq!(subscription.unread, 2);
+        assert_eq!(subscription.etag.as_deref(), Some("\"v2\""));
+        let filter = ItemFilter {
+            include_read: true,
+            limit: Some(2),
+            ..ItemFilter::default()
+        };
+        let items = database.items(filter).await.unwrap();
+        assert_eq!(items[1].title, "Second, fixed");
+        assert!(items[1].read);
+
+        database.unsubscribe(id).await.unwrap();
+        assert!(database.find_subscription("1").await.is_err());
+        assert!(database.item(items[0].id).await.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
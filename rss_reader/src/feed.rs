@@ -0,0 +1,249 @@
+//! Reading the three kinds of feeds there are: RSS 2.0, RSS 1.0, which is RDF, and Atom.
+
+use crate::{
+    text,
+    xml::{self, Element},
+};
+use chrono::{DateTime, Utc};
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feed {
+    pub title: String,
+    /// The site the feed is of.
+    pub link: Option<String>,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// What tells the item apart from the others of the feed, and from itself when it's
+    /// fetched again: its id if it has one, or else its link or title.
+    pub guid: String,
+    pub title: String,
+    pub link: Option<String>,
+    /// The summary or the content, as plain text.
+    pub summary: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+}
+
+/// Parses the document of the feed at `url`, which links that are relative are resolved with.
+pub fn parse(document: &str, url: &Url) -> Result<Feed, String> {
+    let root = xml::parse(document).map_err(|error| format!("invalid XML at {}", error))?;
+    let mut feed = match root.local_name() {
+        "rss" => {
+            let channel = root
+                .child("channel")
+                .ok_or("the RSS feed has no <channel>")?;
+            parse_rss(channel, channel.children("item"))
+        }
+        // The items of RSS 1.0 are next to the channel rather than in it.
+        "RDF" => {
+            let channel = root
+                .child("channel")
+                .ok_or("the RSS feed has no <channel>")?;
+            parse_rss(channel, root.children("item"))
+        }
+        "feed" => parse_atom(&root),
+        name => {
+            return Err(format!(
+                "expected an RSS or Atom feed, found a document of <{}>",
+                name
+            ))
+        }
+    };
+    feed.link = feed.link.and_then(|link| resolve(url, &link));
+    for item in &mut feed.items {
+        item.link = item.link.take().and_then(|link| resolve(url, &link));
+    }
+    if feed.title.is_empty() {
+        feed.title = url.host_str().unwrap_or(url.as_str()).to_string();
+    }
+    Ok(feed)
+}
+
+fn parse_rss<'a>(channel: &Element, items: impl Iterator<Item = &'a Element>) -> Feed {
+    let items = items.filter_map(|item| {
+        let title = item.child_text("title").map(|title| plain(&title));
+        let link = item.child_text("link");
+        // Descriptions are HTML, as is the full content some feeds have next to them.
+        let summary = ["description", "content:encoded"]
+            .into_iter()
+            .find_map(|name| item.child_text(name))
+            .map(|html| text::from_html(&html))
+            .filter(|summary| !summary.is_empty());
+        let published = ["pubDate", "dc:date"]
+            .into_iter()
+            .find_map(|name| item.child_text(name))
+            .and_then(|date| parse_date(&date));
+        let guid = item.child_text("guid").or_else(|| link.clone());
+        make_item(guid, title, link, summary, published)
+    });
+    Feed {
+        title: channel
+            .child_text("title")
+            .map(|title| plain(&title))
+            .unwrap_or_default(),
+        link: channel.child_text("link"),
+        items: items.collect(),
+    }
+}
+
+fn parse_atom(feed: &Element) -> Feed {
+    let items = feed.children("entry").filter_map(|entry| {
+        let title = entry.child("title").map(atom_text);
+        let link = atom_link(entry);
+        let summary = ["summary", "content"]
+            .into_iter()
+            .find_map(|name| entry.child(name))
+            .map(atom_text)
+            .filter(|summary| !summary.is_empty());
+        let published = ["published", "updated"]
+            .into_iter()
+            .find_map(|name| entry.child_text(name))
+            .and_then(|date| parse_date(&date));
+        let guid = entry.child_text("id").or_else(|| link.clone());
+        make_item(guid, title, link, summary, published)
+    });
+    Feed {
+        title: feed.child("title").map(atom_text).unwrap_or_default(),
+        link: atom_link(feed),
+        items: items.collect(),
+    }
+}
+
+/// Items without a title are named after their summary, and items without anything to tell
+/// them apart by are left out, since they'd be new every time.
+fn make_item(
+    guid: Option<String>,
+    title: Option<String>,
+    link: Option<String>,
+    summary: Option<String>,
+    published: Option<DateTime<Utc>>,
+) -> Option<Item> {
+    let title = title
+        .filter(|title| !title.is_empty())
+        .or_else(|| summary.as_ref().map(|summary| text::truncate(summary, 60)))
+        .unwrap_or_else(|| "(untitled)".to_string());
+    let guid = guid.or_else(|| summary.is_some().then(|| title.clone()))?;
+    Some(Item {
+        guid,
+        title,
+        link,
+        summary,
+        published,
+    })
+}
+
+/// The text of an Atom text construct, which says whether it's text, HTML or XHTML.
+fn atom_text(element: &Element) -> String {
+    match element.attribute("type") {
+        Some("html" | "xhtml") => text::from_html(&element.text()),
+        _ => plain(&element.text()),
+    }
+}
+
+/// The link to the page of an Atom entry or feed, which is the one with `rel="alternate"`, or
+/// without a `rel`.
+fn atom_link(element: &Element) -> Option<String> {
+    element
+        .children("link")
+        .find(|link| matches!(link.attribute("rel"), None | Some("alternate")))
+        .and_then(|link| link.attribute("href"))
+        .map(|href| href.trim().to_string())
+}
+
+fn plain(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Dates like `Mon, 14 Oct 2024 09:30:00 GMT` in RSS, or `2024-10-14T09:30:00Z` in Atom and
+/// the Dublin Core dates of RSS 1.0.
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(date)
+        .or_else(|_| DateTime::parse_from_rfc3339(date))
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Links, which may be relative to the feed, as absolute URLs, leaving out the ones that
+/// aren't web pages, like `javascript:` links.
+fn resolve(base: &Url, link: &str) -> Option<String> {
+    let url = base.join(link).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rss_and_atom() {
+        let url = Url::parse("https://example.com/blog/feed.xml").unwrap();
+        let rss = r#"<rss version="2.0"><channel>
+            <title>Example &amp; Co</title><link>https://example.com/</link>
+            <item>
+              <title>Hello</title><link>/blog/hello</link><guid>hello-1</guid>
+              <description>&lt;p&gt;Hi &lt;b&gt;there&lt;/b&gt;&lt;/p&gt;</description>
+              <pubDate>Mon, 14 Oct 2024 09:30:00 GMT</pubDate>
+            </item>
+            <item><description>Just a thought</description></item>
+            <item><title>Nothing to tell it apart</title></item>
+            </channel></rss>"#;
+        let feed = parse(rss, &url).unwrap();
+        assert_eq!(feed.title, "Example & Co");
+        assert_eq!(feed.items.len(), 2);
+        let item = &feed.items[0];
+        assert_eq!(item.guid, "hello-1");
+        assert_eq!(item.link.as_deref(), Some("https://example.com/blog/hello"));
+        assert_eq!(item.summary.as_deref(), Some("Hi there"));
+        assert_eq!(
+            item.published,
+            Some("2024-10-14T09:30:00Z".parse().unwrap())
+        );
+        assert_eq!(feed.items[1].title, "Just a thought");
+
+        let atom = r#"<?xml version="1.0"?>
+            <feed xmlns="http://www.w3.org/2005/Atom">
+              <title type="html">Atom &lt;em&gt;news&lt;/em&gt;</title>
+              <link rel="self" href="https://example.com/atom.xml"/>
+              <link href="https://example.com/"/>
+              <entry>
+                <id>urn:uuid:1</id><title>A &lt;tag&gt;</title>
+                <link rel="alternate" href="posts/1"/>
+                <updated>2024-10-13T08:00:00+02:00</updated>
+                <content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml">Some <b>content</b></div></content>
+              </entry>
+            </feed>"#;
+        let feed = parse(atom, &url).unwrap();
+        assert_eq!(feed.title, "Atom news");
+        assert_eq!(feed.link.as_deref(), Some("https://example.com/"));
+        let entry = &feed.items[0];
+        assert_eq!(entry.title, "A <tag>");
+        assert_eq!(
+            entry.link.as_deref(),
+            Some("https://example.com/blog/posts/1")
+        );
+        assert_eq!(entry.summary.as_deref(), Some("Some content"));
+        assert_eq!(
+            entry.published,
+            Some("2024-10-13T06:00:00Z".parse().unwrap())
+        );
+
+        let rdf = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+            xmlns="http://purl.org/rss/1.0/" xmlns:dc="http://purl.org/dc/elements/1.1/">
+            <channel><title>RDF</title></channel>
+            <item><title>One</title><link>https://example.com/1</link>
+              <dc:date>2024-10-12T10:00:00Z</dc:date></item>
+            </rdf:RDF>"#;
+        let feed = parse(rdf, &url).unwrap();
+        assert_eq!(feed.items[0].guid, "https://example.com/1");
+        assert!(feed.items[0].published.is_some());
+
+        let error = parse("<html><body></body></html>", &url).unwrap_err();
+        assert_eq!(
+            error,
+            "expected an RSS or Atom feed, found a document of <html>"
+        );
+    }
+}
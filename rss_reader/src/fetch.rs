@@ -0,0 +1,104 @@
+//! Fetching feeds over HTTP, many at once, with conditional requests so feeds that didn't
+//! change since the last time aren't sent again.
+
+use crate::feed::{self, Feed};
+use reqwest::{
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Client, StatusCode,
+};
+use std::{io::Error, sync::Arc, time::Duration};
+use tokio::{sync::Semaphore, task::JoinSet};
+use url::Url;
+
+/// Long enough for slow servers, short enough that one that hangs doesn't hold up the rest.
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Enough requests at once to fetch a few dozen feeds quickly, without looking like an attack
+/// to the servers that host several of them.
+pub const MAX_CONCURRENT_FETCHES: usize = 8;
+
+/// What the server said about the version of the feed the last answer was, for the next
+/// request to ask whether it changed since.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Fetched {
+    Changed(Feed, Validators),
+    NotModified,
+}
+
+pub fn client() -> Result<Client, Error> {
+    Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(concat!("rss_reader/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(Error::other)
+}
+
+/// Fetches the feed at `url`, sending the validators of the last answer, if there was one.
+pub async fn fetch(client: &Client, url: &str, validators: &Validators) -> Result<Fetched, String> {
+    let parsed_url =
+        Url::parse(url).map_err(|error| format!("invalid URL '{}': {}", url, error))?;
+    let mut request = client.get(parsed_url.clone());
+    if let Some(etag) = &validators.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, last_modified);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|error| format!("couldn't fetch {}: {}", url, error))?;
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(Fetched::NotModified);
+    }
+    if !status.is_success() {
+        return Err(format!("{} answered {}", url, status));
+    }
+    let header = |name| {
+        let value = response.headers().get(name)?.to_str().ok()?;
+        Some(value.to_string())
+    };
+    let validators = Validators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    };
+    // Relative links are relative to where the feed was found in the end, after redirects.
+    let final_url = response.url().clone();
+    let document = response
+        .text()
+        .await
+        .map_err(|error| format!("couldn't fetch {}: {}", url, error))?;
+    let feed = feed::parse(&document, &final_url).map_err(|error| format!("{}: {}", url, error))?;
+    Ok(Fetched::Changed(feed, validators))
+}
+
+/// Fetches the feeds, at most [`MAX_CONCURRENT_FETCHES`] at a time, returning the result of
+/// each with its key, in the order they finish.
+pub async fn fetch_all<K: Send + 'static>(
+    client: &Client,
+    feeds: Vec<(K, String, Validators)>,
+) -> Vec<(K, Result<Fetched, String>)> {
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_FETCHES));
+    let mut tasks = JoinSet::new();
+    for (key, url, validators) in feeds {
+        let (client, permits) = (client.clone(), Arc::clone(&permits));
+        tasks.spawn(async move {
+            // The semaphore is never closed.
+            let _permit = permits.acquire_owned().await;
+            (key, fetch(&client, &url, &validators).await)
+        });
+    }
+    let mut results = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        // A task that panicked has a bug worth seeing, rather than a feed that failed.
+        results.push(result.expect("fetching a feed panicked"));
+    }
+    results
+}
@@ -0,0 +1,9 @@
+//! Follows RSS and Atom feeds from the terminal: the subscriptions and which of their items were
+//! read are kept in SQLite, and the feeds are fetched all at once.
+
+pub mod browser;
+pub mod database;
+pub mod feed;
+pub mod fetch;
+pub mod text;
+pub mod xml;
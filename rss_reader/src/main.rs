@@ -0,0 +1,291 @@
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use common::dates::format_relative;
+use rss_reader::{
+    browser,
+    database::{self, Database, ItemFilter, StoredItem, Subscription},
+    fetch::{self, Fetched, Validators},
+    text,
+};
+use std::{
+    io::{Error, ErrorKind},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+/// How much of the summary of an item `read` shows.
+const SUMMARY_LEN: usize = 240;
+
+/// Follows RSS and Atom feeds: fetches them, and shows the items that weren't read yet.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The database of the subscriptions, `~/.local/share/rss_reader/feeds.db` if not given
+    #[arg(long, global = true, value_name = "path", env = "RSS_DATABASE")]
+    database: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Subscribes to a feed, with the items it has now
+    Add {
+        /// The URL of the feed, like https://blog.rust-lang.org/feed.xml
+        #[arg(value_name = "url")]
+        url: String,
+    },
+    /// Unsubscribes from a feed, forgetting its items
+    #[command(visible_alias = "rm")]
+    Remove {
+        /// The feed, by the number `feeds` shows, its title or its URL
+        #[arg(value_name = "feed")]
+        feed: String,
+    },
+    /// Lists the subscriptions, with how many items of each weren't read
+    #[command(visible_alias = "ls")]
+    Feeds,
+    /// Fetches the feeds, all at once, and tells how many new items each has
+    Fetch {
+        /// Only this feed
+        #[arg(value_name = "feed")]
+        feed: Option<String>,
+    },
+    /// Shows the items that weren't read, newest first, and marks them read
+    Read {
+        /// Only the items of this feed
+        #[arg(value_name = "feed")]
+        feed: Option<String>,
+        /// Show the items that were read too
+        #[arg(long, short)]
+        all: bool,
+        /// Show this many items at most
+        #[arg(long, short = 'n', value_name = "count")]
+        limit: Option<u32>,
+        /// Leave the items unread
+        #[arg(long)]
+        peek: bool,
+        /// Fetch the feeds first
+        #[arg(long, short)]
+        fetch: bool,
+    },
+    /// Opens the link of an item in the browser, and marks it read
+    Open {
+        /// The number of the item, which `read` shows
+        #[arg(value_name = "item")]
+        item: i64,
+    },
+    /// Marks items unread again
+    Unread {
+        #[arg(value_name = "item", required = true)]
+        items: Vec<i64>,
+    },
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    match run(Args::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<(), Error> {
+    let path = args
+        .database
+        .or_else(database::default_path)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                "no home directory for the database, pick a file with --database",
+            )
+        })?;
+    let database = Database::open(&path).await?;
+    match args.command {
+        Commands::Add { url } => {
+            let client = fetch::client()?;
+            let (feed, validators) = match fetch::fetch(&client, &url, &Validators::default())
+                .await
+                .map_err(Error::other)?
+            {
+                Fetched::Changed(feed, validators) => (feed, validators),
+                Fetched::NotModified => {
+                    let message = format!("{} answered 304 Not Modified to a plain request", url);
+                    return Err(Error::other(message));
+                }
+            };
+            let id = database.subscribe(&url, &feed, &validators).await?;
+            println!(
+                "Subscribed to {} as feed {}, with {} items",
+                feed.title,
+                id,
+                feed.items.len()
+            );
+            Ok(())
+        }
+        Commands::Remove { feed } => {
+            let subscription = database.find_subscription(&feed).await?;
+            database.unsubscribe(subscription.id).await?;
+            println!("Unsubscribed from {}", subscription.title);
+            Ok(())
+        }
+        Commands::Feeds => {
+            print_subscriptions(&database.subscriptions().await?);
+            Ok(())
+        }
+        Commands::Fetch { feed } => fetch_feeds(&database, feed.as_deref()).await,
+        Commands::Read {
+            feed,
+            all,
+            limit,
+            peek,
+            fetch,
+        } => {
+            if fetch {
+                fetch_feeds(&database, feed.as_deref()).await?;
+            }
+            let feed_id = match &feed {
+                Some(feed) => Some(database.find_subscription(feed).await?.id),
+                None => None,
+            };
+            let filter = ItemFilter {
+                feed_id,
+                include_read: all,
+                limit,
+            };
+            let items = database.items(filter).await?;
+            match (items.is_empty(), all) {
+                (true, false) => println!("No unread items"),
+                (true, true) => println!("No items"),
+                (false, _) => print_items(&items),
+            }
+            if !peek {
+                let unread: Vec<i64> = items
+                    .iter()
+                    .filter(|item| !item.read)
+                    .map(|item| item.id)
+                    .collect();
+                database.set_read(&unread, true).await?;
+            }
+            Ok(())
+        }
+        Commands::Open { item } => {
+            let item = database.item(item).await?;
+            let link = item.link.ok_or_else(|| {
+                Error::new(ErrorKind::NotFound, format!("item {} has no link", item.id))
+            })?;
+            browser::open(&link)?;
+            database.set_read(&[item.id], true).await
+        }
+        Commands::Unread { items } => {
+            for &id in &items {
+                database.item(id).await?;
+            }
+            database.set_read(&items, false).await
+        }
+    }
+}
+
+/// Fetches every feed, or the one given, printing how many new items each has. Feeds that
+/// can't be fetched don't keep the others from being saved, but make it fail at the end.
+async fn fetch_feeds(database: &Database, feed: Option<&str>) -> Result<(), Error> {
+    let subscriptions = match feed {
+        Some(feed) => vec![database.find_subscription(feed).await?],
+        None => database.subscriptions().await?,
+    };
+    let feeds = subscriptions
+        .iter()
+        .enumerate()
+        .map(|(index, subscription)| {
+            let validators = Validators {
+                etag: subscription.etag.clone(),
+                last_modified: subscription.last_modified.clone(),
+            };
+            (index, subscription.url.clone(), validators)
+        })
+        .collect();
+    let mut results = fetch::fetch_all(&fetch::client()?, feeds).await;
+    // Printed in the order of the subscriptions rather than the order they came in.
+    results.sort_by_key(|(index, _)| *index);
+
+    let now = Utc::now();
+    let mut failures = 0;
+    for (index, result) in results {
+        let subscription = &subscriptions[index];
+        match result {
+            Ok(Fetched::Changed(feed, validators)) => {
+                let new_count = database
+                    .save_fetch(subscription.id, &feed, &validators, now)
+                    .await?;
+                match new_count {
+                    0 => println!("{}: no new items", feed.title),
+                    _ => println!("{}: {}", feed.title, count(new_count, "new item")),
+                }
+            }
+            Ok(Fetched::NotModified) => {
+                database.touch(subscription.id, now).await?;
+                println!("{}: no new items", subscription.title);
+            }
+            Err(message) => {
+                eprintln!("{}: {}", subscription.title, message);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(Error::other(format!(
+            "{} of {} couldn't be fetched",
+            failures,
+            count(subscriptions.len() as u64, "feed")
+        )));
+    }
+    Ok(())
+}
+
+fn print_subscriptions(subscriptions: &[Subscription]) {
+    if subscriptions.is_empty() {
+        println!("No feeds yet, add one with `rss add <url>`");
+        return;
+    }
+    let now = Utc::now();
+    for subscription in subscriptions {
+        let fetched = match subscription.fetched_at {
+            Some(fetched_at) => format!("fetched {}", relative(fetched_at, now)),
+            None => "never fetched".to_string(),
+        };
+        println!(
+            "{:>3}  {} ({} unread, {})\n     {}",
+            subscription.id, subscription.title, subscription.unread, fetched, subscription.url
+        );
+    }
+}
+
+fn print_items(items: &[StoredItem]) {
+    let now = Utc::now();
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        let marker = if item.read { ' ' } else { '*' };
+        println!("{}[{}] {}", marker, item.id, item.title);
+        let mut details = vec![item.feed_title.clone(), relative(item.date, now)];
+        details.extend(item.link.clone());
+        println!("     {}", details.join(" · "));
+        if let Some(summary) = &item.summary {
+            println!("     {}", text::truncate(summary, SUMMARY_LEN));
+        }
+    }
+}
+
+fn relative(date: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    format_relative(date.naive_utc(), now.naive_utc())
+}
+
+/// Counts like `1 new item` and `3 new items`.
+fn count(count: u64, noun: &str) -> String {
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {}{}", count, noun, plural)
+}
@@ -0,0 +1,140 @@
+//! Plain text from the HTML that feeds put in titles and summaries, for the terminal.
+
+/// The named entities of HTML that show up in feeds, besides the ones of XML.
+const ENTITIES: [(&str, &str); 17] = [
+    ("amp", "&"),
+    ("lt", "<"),
+    ("gt", ">"),
+    ("quot", "\""),
+    ("apos", "'"),
+    ("nbsp", " "),
+    ("hellip", "…"),
+    ("mdash", "—"),
+    ("ndash", "–"),
+    ("lsquo", "‘"),
+    ("rsquo", "’"),
+    ("ldquo", "“"),
+    ("rdquo", "”"),
+    ("laquo", "«"),
+    ("raquo", "»"),
+    ("copy", "©"),
+    ("middot", "·"),
+];
+
+/// Elements whose contents aren't text to read.
+const SKIPPED_ELEMENTS: [&str; 3] = ["script", "style", "template"];
+
+/// The text of an HTML fragment, without its tags, with its entities decoded and every run of
+/// whitespace as a single space, since the terminal has no use for the layout.
+pub fn from_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(['<', '&']) {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with('&') {
+            rest = &rest[decode_entity(rest, &mut text)..];
+            continue;
+        }
+        // A `<` that doesn't start a tag, like in `1 < 2`.
+        let starts_tag = rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/');
+        let Some(end) = rest.find('>').filter(|_| starts_tag) else {
+            text.push('<');
+            rest = &rest[1..];
+            continue;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if SKIPPED_ELEMENTS.contains(&name.as_str()) {
+            let close = format!("</{}", name);
+            let skipped = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+            rest = &rest[skipped..];
+        }
+        // Tags like `</p>` and `<br>` separate words, where `<b>` doesn't.
+        if !matches!(
+            name.as_str(),
+            "a" | "b" | "i" | "em" | "strong" | "code" | "span"
+        ) {
+            text.push(' ');
+        }
+    }
+    text.push_str(rest);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Pushes the character of the entity `html` starts with, or just the `&` if it isn't one,
+/// returning how much of `html` was read.
+fn decode_entity(html: &str, text: &mut String) -> usize {
+    let Some(end) = html.find(';').filter(|&end| end <= 12) else {
+        text.push('&');
+        return 1;
+    };
+    let entity = &html[1..end];
+    if let Some((_, decoded)) = ENTITIES.iter().find(|(name, _)| *name == entity) {
+        text.push_str(decoded);
+        return end + 1;
+    }
+    // The XML parser decodes numbers in text, but not in CDATA sections, or in HTML that was
+    // escaped into the text, as `&amp;#39;`.
+    let code = match entity
+        .strip_prefix("#x")
+        .or_else(|| entity.strip_prefix("#X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => entity
+            .strip_prefix('#')
+            .and_then(|number| number.parse().ok()),
+    };
+    match code.and_then(char::from_u32) {
+        Some(c) => {
+            text.push(c);
+            end + 1
+        }
+        None => {
+            text.push('&');
+            1
+        }
+    }
+}
+
+/// Shortens text to about `max_chars` characters, at a space if there's one near the end, with
+/// `…` after it.
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    let Some((cut, _)) = text.char_indices().nth(max_chars) else {
+        return text.to_string();
+    };
+    let shortened = &text[..cut];
+    let shortened = match shortened.rfind(' ') {
+        Some(space) if space > cut * 3 / 4 => &shortened[..space],
+        _ => shortened,
+    };
+    format!("{}…", shortened.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_html_into_text() {
+        assert_eq!(
+            from_html("<p>Fish &amp; <b>chips</b>&nbsp;&#39;n&#x27;</p><p>peas&hellip;</p>"),
+            "Fish & chips 'n' peas…"
+        );
+        assert_eq!(
+            from_html("a<br/>b<script>alert('<p>')</script> 1 < 2 &unknown; & <i>c</i>"),
+            "a b 1 < 2 &unknown; & c"
+        );
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(
+            truncate("the quick brown fox jumps", 18),
+            "the quick brown…"
+        );
+        assert_eq!(truncate("ééééé", 3), "ééé…");
+    }
+}
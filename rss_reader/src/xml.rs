@@ -0,0 +1,382 @@
+//! A small XML parser, enough for feeds: elements, attributes, text, CDATA sections and the
+//! predefined and numeric entities. Declarations, comments, processing instructions and
+//! doctypes are skipped, and namespaces are left as the prefixes they're written with.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    /// The name as it's written, with its prefix, like `dc:date`.
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    /// The name without its prefix, like `date` for `dc:date`.
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The child elements named `name`, which is compared with prefixes if it has one, like
+    /// `content:encoded`, and with local names if it doesn't.
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter_map(move |node| match node {
+            Node::Element(element) if matches_name(element, name) => Some(element),
+            _ => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|node| match node {
+            Node::Element(element) if matches_name(element, name) => Some(element),
+            _ => None,
+        })
+    }
+
+    /// The text of the element and of the elements in it, joined.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        collect_text(self, &mut text);
+        text
+    }
+
+    /// The text of the child named `name`, trimmed, or `None` if there's no such child or its
+    /// text is blank.
+    pub fn child_text(&self, name: &str) -> Option<String> {
+        let text = self.child(name)?.text();
+        let text = text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, local)| local)
+}
+
+fn matches_name(element: &Element, name: &str) -> bool {
+    if name.contains(':') {
+        element.name == name
+    } else {
+        element.local_name() == name
+    }
+}
+
+fn collect_text(element: &Element, text: &mut String) {
+    for child in &element.children {
+        match child {
+            Node::Element(element) => collect_text(element, text),
+            Node::Text(chunk) => text.push_str(chunk),
+        }
+    }
+}
+
+/// Where a document stops being XML, with the line it happens on.
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Parses a document, returning its root element.
+pub fn parse(document: &str) -> Result<Element, Error> {
+    let mut parser = Parser {
+        input: document.trim_start_matches('\u{feff}'),
+        position: 0,
+    };
+    parser.skip_misc()?;
+    if !parser.rest().starts_with('<') {
+        return Err(parser.error("expected an element"));
+    }
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if !parser.rest().is_empty() {
+        return Err(parser.error("expected the end of the document after the root element"));
+    }
+    Ok(root)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error {
+            line: self.input[..self.position].matches('\n').count() + 1,
+            message: message.into(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Moves past `end`, returning what was before it.
+    fn take_until(&mut self, end: &str, what: &str) -> Result<&'a str, Error> {
+        let rest = self.rest();
+        match rest.find(end) {
+            Some(index) => {
+                self.position += index + end.len();
+                Ok(&rest[..index])
+            }
+            None => Err(self.error(format!("unterminated {}", what))),
+        }
+    }
+
+    /// Skips what may come around the root element: whitespace, the declaration, comments,
+    /// processing instructions like stylesheets, and the doctype.
+    fn skip_misc(&mut self) -> Result<(), Error> {
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("<?") {
+                self.take_until("?>", "processing instruction")?;
+            } else if rest.starts_with("<!--") {
+                self.take_until("-->", "comment")?;
+            } else if rest.starts_with("<!DOCTYPE") {
+                self.skip_doctype()?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Skips a doctype, with the declarations in brackets it may have.
+    fn skip_doctype(&mut self) -> Result<(), Error> {
+        let mut depth = 0;
+        for (index, c) in self.rest().char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                '>' if depth == 0 => {
+                    self.position += index + 1;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Err(self.error("unterminated doctype"))
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '=' | '<'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.position += len;
+        Ok(rest[..len].to_string())
+    }
+
+    /// Parses an element, starting at its `<`.
+    fn element(&mut self) -> Result<Element, Error> {
+        self.position += 1;
+        let name = self.name()?;
+        let mut attributes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.position += 2;
+                return Ok(Element {
+                    name,
+                    attributes,
+                    children: Vec::new(),
+                });
+            }
+            if rest.starts_with('>') {
+                self.position += 1;
+                break;
+            }
+            if rest.is_empty() {
+                return Err(self.error(format!("unterminated tag <{}>", name)));
+            }
+            attributes.push(self.attribute()?);
+        }
+
+        let mut children = Vec::new();
+        let mut text = String::new();
+        loop {
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                self.position += 2;
+                let end = self.name()?;
+                if end != name {
+                    return Err(self.error(format!("expected </{}>, found </{}>", name, end)));
+                }
+                self.skip_whitespace();
+                if !self.rest().starts_with('>') {
+                    return Err(self.error(format!("unterminated tag </{}>", end)));
+                }
+                self.position += 1;
+                break;
+            } else if rest.starts_with("<![CDATA[") {
+                self.position += "<![CDATA[".len();
+                text.push_str(self.take_until("]]>", "CDATA section")?);
+            } else if rest.starts_with("<!--") {
+                self.take_until("-->", "comment")?;
+            } else if rest.starts_with("<?") {
+                self.take_until("?>", "processing instruction")?;
+            } else if rest.starts_with('<') {
+                push_text(&mut children, &mut text);
+                children.push(Node::Element(self.element()?));
+            } else if rest.is_empty() {
+                return Err(self.error(format!("<{}> isn't closed", name)));
+            } else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                let chunk = &rest[..len];
+                text.push_str(&decode_entities(chunk).map_err(|message| self.error(message))?);
+                self.position += len;
+            }
+        }
+        push_text(&mut children, &mut text);
+        Ok(Element {
+            name,
+            attributes,
+            children,
+        })
+    }
+
+    fn attribute(&mut self) -> Result<(String, String), Error> {
+        let name = self.name()?;
+        self.skip_whitespace();
+        if !self.rest().starts_with('=') {
+            return Err(self.error(format!("expected a value for the attribute '{}'", name)));
+        }
+        self.position += 1;
+        self.skip_whitespace();
+        let quote = match self.rest().chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return Err(self.error(format!("expected quotes around '{}'", name))),
+        };
+        self.position += 1;
+        let value = self.take_until(&quote.to_string(), "attribute value")?;
+        let value = decode_entities(value).map_err(|message| self.error(message))?;
+        Ok((name, value))
+    }
+}
+
+/// Ends the text before a child element, leaving out whitespace between elements.
+fn push_text(children: &mut Vec<Node>, text: &mut String) {
+    if !text.trim().is_empty() {
+        children.push(Node::Text(std::mem::take(text)));
+    }
+    text.clear();
+}
+
+/// Replaces `&lt;`, `&#233;`, `&#xE9;` and the like by the characters they stand for. The
+/// entities of HTML, like `&nbsp;`, aren't defined in XML, but feeds use them anyway, so they're
+/// left as they are, for [`crate::text::from_html`] to deal with if they turn out to be HTML.
+pub fn decode_entities(text: &str) -> Result<String, String> {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';').filter(|&end| end <= 12) else {
+            decoded.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix('#') {
+                Some(number) => {
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16),
+                        None => number.parse(),
+                    };
+                    let c = code.ok().and_then(char::from_u32);
+                    Some(c.ok_or_else(|| format!("invalid character reference '&{};'", entity))?)
+                }
+                None => None,
+            },
+        };
+        match c {
+            Some(c) => decoded.push(c),
+            None => decoded.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    decoded.push_str(rest);
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_elements_text_and_entities() {
+        let document = r#"<?xml version="1.0" encoding="utf-8"?>
+            <!DOCTYPE rss [<!ENTITY copy "&#169;">]>
+            <!-- a comment -->
+            <rss version='2.0' xmlns:dc="http://purl.org/dc/elements/1.1/">
+              <title>Fish &amp; Chips &#x2014; &nbsp;news</title>
+              <dc:date>2024-10-14</dc:date>
+              <description><![CDATA[<p>Hello <b>world</b></p>]]></description>
+              <empty/>
+              <p>before <b>bold</b> after</p>
+            </rss>"#;
+        let root = parse(document).unwrap();
+        assert_eq!(root.name, "rss");
+        assert_eq!(root.attribute("version"), Some("2.0"));
+        assert_eq!(
+            root.child_text("title").unwrap(),
+            "Fish & Chips \u{2014} &nbsp;news"
+        );
+        assert_eq!(root.child_text("date").unwrap(), "2024-10-14");
+        assert_eq!(root.child("dc:date").unwrap().local_name(), "date");
+        assert!(root.child("atom:date").is_none());
+        assert_eq!(
+            root.child_text("description").unwrap(),
+            "<p>Hello <b>world</b></p>"
+        );
+        assert_eq!(root.child("empty").unwrap().children, []);
+        assert_eq!(root.child("p").unwrap().text(), "before bold after");
+
+        let error = parse("<rss>\n<title>a</item>\n</rss>").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2: expected </title>, found </item>"
+        );
+        assert!(parse("<rss>").is_err());
+        assert!(parse("<a>&#xD800;</a>").is_err());
+        assert!(parse("<html><body>hi</body></html> trailing").is_err());
+    }
+}
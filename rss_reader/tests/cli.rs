@@ -0,0 +1,205 @@
+//! Runs the `rss` binary against a fake web server with an RSS and an Atom feed, which can be
+//! switched to a second version where the RSS feed has a new item and the Atom one is broken.
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::Path,
+    process::{Command, Output},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+const BLOG_V1: &str = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+  <title>Example Blog</title><link>https://example.com/</link>
+  <item><title>First post</title><link>/posts/first</link><guid>first</guid>
+    <pubDate>Sat, 12 Oct 2024 10:00:00 GMT</pubDate>
+    <description>&lt;p&gt;Hello &lt;b&gt;world&lt;/b&gt;&lt;/p&gt;</description></item>
+  <item><title>Second post</title><link>/posts/second</link><guid>second</guid>
+    <pubDate>Sun, 13 Oct 2024 10:00:00 GMT</pubDate></item>
+</channel></rss>"#;
+
+const BLOG_V2_ITEM: &str = r#"<item><title>Third post</title><link>/posts/third</link>
+    <guid>third</guid><pubDate>Mon, 14 Oct 2024 10:00:00 GMT</pubDate></item>"#;
+
+const ATOM: &str = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Atom News</title>
+  <entry><id>urn:news:1</id><title>Atom entry</title>
+    <link href="https://news.example.com/1"/><updated>2024-10-13T12:00:00Z</updated></entry>
+</feed>"#;
+
+struct Server {
+    url: String,
+    switched: Arc<AtomicBool>,
+    /// The paths asked for, with the `If-None-Match` of the request if it had one, like
+    /// `/blog.xml "v1"`.
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+fn start_server() -> Server {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let switched = Arc::new(AtomicBool::new(false));
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let (is_switched, seen) = (Arc::clone(&switched), Arc::clone(&requests));
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut if_none_match = None;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("if-none-match") {
+                        if_none_match = Some(value.trim().to_string());
+                    }
+                }
+                line.clear();
+            }
+            let path = request_line.split(' ').nth(1).unwrap().to_string();
+            let switched = is_switched.load(Ordering::SeqCst);
+            let etag = if switched { "\"v2\"" } else { "\"v1\"" };
+            let blog = match switched {
+                false => BLOG_V1.to_string(),
+                true => BLOG_V1.replace("</channel>", &format!("{}</channel>", BLOG_V2_ITEM)),
+            };
+            let (status, body) = match path.as_str() {
+                "/blog.xml" if if_none_match.as_deref() == Some(etag) => {
+                    ("304 Not Modified", String::new())
+                }
+                "/blog.xml" => ("200 OK", blog),
+                "/atom.xml" if switched => ("500 Internal Server Error", String::new()),
+                "/atom.xml" => ("200 OK", ATOM.to_string()),
+                "/page.html" => ("200 OK", "<html><body>Hi</body></html>".to_string()),
+                _ => ("404 Not Found", String::new()),
+            };
+            let request = match &if_none_match {
+                Some(tag) => format!("{} {}", path, tag),
+                None => path.clone(),
+            };
+            seen.lock().unwrap().push(request);
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/xml\r\nETag: {}\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                etag,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    Server {
+        url,
+        switched,
+        requests,
+    }
+}
+
+fn rss(database: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rss"))
+        .args(args)
+        .env("RSS_DATABASE", database)
+        // Links are "opened" by printing them.
+        .env("BROWSER", "echo")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: Output) -> String {
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn follows_feeds() {
+    let server = start_server();
+    let dir = env::temp_dir().join(format!("rss-reader-cli-test-{}", std::process::id()));
+    let database = dir.join("feeds.db");
+    let blog_url = format!("{}/blog.xml", server.url);
+
+    assert!(stdout(rss(&database, &["feeds"])).starts_with("No feeds yet"));
+    assert_eq!(
+        stdout(rss(&database, &["add", &blog_url])),
+        "Subscribed to Example Blog as feed 1, with 2 items\n"
+    );
+    let atom_url = format!("{}/atom.xml", server.url);
+    assert!(stdout(rss(&database, &["add", &atom_url])).starts_with("Subscribed to Atom News"));
+    let output = rss(&database, &["add", &blog_url]);
+    let error = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        error,
+        format!("error: already subscribed to {}\n", blog_url)
+    );
+    let output = rss(&database, &["add", &format!("{}/page.html", server.url)]);
+    let error = String::from_utf8(output.stderr).unwrap();
+    assert!(error.ends_with("expected an RSS or Atom feed, found a document of <html>\n"));
+
+    let feeds = stdout(rss(&database, &["feeds"]));
+    assert!(feeds.contains("  1  Example Blog (2 unread, fetched just now)\n"));
+    assert!(feeds.contains("  2  Atom News (1 unread"));
+
+    // Newest first, with links resolved against the feed and summaries as text.
+    let text = stdout(rss(&database, &["read", "--peek"]));
+    let titles: Vec<&str> = text.lines().filter(|line| line.starts_with('*')).collect();
+    assert_eq!(
+        titles,
+        ["*[3] Atom entry", "*[2] Second post", "*[1] First post"]
+    );
+    let details = text
+        .lines()
+        .find(|line| line.contains("/posts/first"))
+        .unwrap();
+    assert!(details.starts_with("     Example Blog · "));
+    assert!(details.ends_with(&format!(" · {}/posts/first", server.url)));
+    assert!(text.ends_with("     Hello world\n"));
+    let text = stdout(rss(&database, &["read", "example blog"]));
+    assert_eq!(text.lines().filter(|line| line.starts_with('*')).count(), 2);
+    assert_eq!(stdout(rss(&database, &["read", "1"])), "No unread items\n");
+    assert!(stdout(rss(&database, &["read"])).starts_with("*[3] Atom entry\n"));
+
+    // The blog hasn't changed, which its tag tells.
+    assert_eq!(
+        stdout(rss(&database, &["fetch"])),
+        "Atom News: no new items\nExample Blog: no new items\n"
+    );
+    let requests = server.requests.lock().unwrap().clone();
+    assert!(requests.contains(&"/blog.xml \"v1\"".to_string()));
+
+    // The blog is saved even though the other feed fails.
+    server.switched.store(true, Ordering::SeqCst);
+    let output = rss(&database, &["fetch"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Example Blog: 1 new item\n"
+    );
+    let error = String::from_utf8(output.stderr).unwrap();
+    assert!(error.contains("Atom News: "));
+    assert!(error.ends_with("error: 1 of 2 feeds couldn't be fetched\n"));
+
+    let opened = stdout(rss(&database, &["open", "4"]));
+    assert_eq!(opened, format!("{}/posts/third\n", server.url));
+    assert_eq!(stdout(rss(&database, &["read"])), "No unread items\n");
+    stdout(rss(&database, &["unread", "4"]));
+    let text = stdout(rss(&database, &["read", "-n", "1"]));
+    assert!(text.starts_with("*[4] Third post\n"));
+    assert!(!rss(&database, &["unread", "99"]).status.success());
+
+    assert_eq!(
+        stdout(rss(&database, &["remove", "1"])),
+        "Unsubscribed from Example Blog\n"
+    );
+    assert_eq!(
+        stdout(rss(&database, &["read", "--all"])).lines().count(),
+        2
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,148 @@
+use crate::{
+    due::Due,
+    render::{Renderer, DUE_TODAY_STYLE, HEADER_STYLE, OVERDUE_STYLE},
+    Task, TaskList,
+};
+use chrono::{Datelike, Months, NaiveDate};
+
+/// Width of a day in the calendar of a month, enough for `31 (99)`.
+const CELL_WIDTH: usize = 8;
+
+/// The day a task is due on, for the tasks that have a due date.
+fn due_day(task: &Task) -> Option<NaiveDate> {
+    task.due.map(|due| due.start().date())
+}
+
+/// Prints the tasks due on each of `days` under a heading for the day, after the open tasks that
+/// were due before them. Days without tasks are left out, except for today.
+pub fn print_days(renderer: &Renderer, task_list: &TaskList, days: &[NaiveDate], today: NaiveDate) {
+    let (Some(&first), Some(&last)) = (days.first(), days.last()) else {
+        return;
+    };
+    let overdue: Vec<&Task> = task_list
+        .tasks
+        .iter()
+        .filter(|task| !task.completed && due_day(task).is_some_and(|day| day < first))
+        .collect();
+    let mut sections = Vec::new();
+    if !overdue.is_empty() {
+        sections.push((renderer.paint("Overdue", OVERDUE_STYLE), overdue));
+    }
+    for &day in days {
+        let tasks: Vec<&Task> = task_list
+            .tasks
+            .iter()
+            .filter(|task| due_day(task) == Some(day))
+            .collect();
+        if tasks.is_empty() && day != today {
+            continue;
+        }
+        let heading = if day == today {
+            renderer.paint(&format!("{} (today)", Due::On(day)), DUE_TODAY_STYLE)
+        } else {
+            renderer.paint(&Due::On(day).to_string(), HEADER_STYLE)
+        };
+        sections.push((heading, tasks));
+    }
+    if sections.is_empty() {
+        println!("Nothing is due by {}", Due::On(last));
+        return;
+    }
+    for (index, (heading, mut tasks)) in sections.into_iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        println!("{}", heading);
+        if tasks.is_empty() {
+            println!("  Nothing due");
+        }
+        tasks.sort_by_key(|task| task.due.map(|due| due.start()));
+        for task in tasks {
+            println!("  {}", renderer.summary_line(task_list, task));
+        }
+    }
+}
+
+/// Formats a calendar of the month `first` is the first day of, with a week on every line from
+/// Monday and the number of open tasks due on each day. Today, and earlier days with open tasks
+/// that are overdue, stand out.
+pub fn format_month(
+    renderer: &Renderer,
+    task_list: &TaskList,
+    first: NaiveDate,
+    today: NaiveDate,
+) -> String {
+    let next_month = first + Months::new(1);
+    let mut calendar = renderer.paint(&first.format("%B %Y").to_string(), HEADER_STYLE);
+    calendar.push('\n');
+    let weekdays: Vec<String> = first
+        .week(chrono::Weekday::Mon)
+        .first_day()
+        .iter_days()
+        .take(7)
+        .map(|day| format!("{:<width$}", day.format("%a"), width = CELL_WIDTH))
+        .collect();
+    calendar.push_str(weekdays.concat().trim_end());
+    calendar.push('\n');
+
+    let mut line = " ".repeat(CELL_WIDTH * first.weekday().num_days_from_monday() as usize);
+    for day in first.iter_days().take_while(|&day| day < next_month) {
+        let open = task_list
+            .tasks
+            .iter()
+            .filter(|task| !task.completed && due_day(task) == Some(day))
+            .count();
+        let mut cell = format!("{:>2}", day.day());
+        if open > 0 {
+            cell.push_str(&format!(" ({})", open));
+        }
+        let style = if day == today {
+            DUE_TODAY_STYLE
+        } else if day < today && open > 0 {
+            OVERDUE_STYLE
+        } else {
+            ""
+        };
+        let padding = CELL_WIDTH.saturating_sub(cell.chars().count());
+        line.push_str(&renderer.paint(&cell, style));
+        line.push_str(&" ".repeat(padding));
+        if day.weekday() == chrono::Weekday::Sun {
+            calendar.push_str(line.trim_end());
+            calendar.push('\n');
+            line.clear();
+        }
+    }
+    if !line.trim_end().is_empty() {
+        calendar.push_str(line.trim_end());
+        calendar.push('\n');
+    }
+    calendar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_a_month_with_the_open_tasks_of_each_day() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+        let mut task_list = TaskList::new();
+        for (title, day) in [("a", 1), ("b", 17), ("c", 17), ("d", 31)] {
+            task_list.add_task(title.to_string()).due = Some(Due::On(date(day)));
+        }
+        // Completed tasks aren't counted.
+        task_list.tasks[3].completed = true;
+
+        let calendar = format_month(&Renderer::new(false), &task_list, date(1), date(14));
+        let expected = "\
+May 2024
+Mon     Tue     Wed     Thu     Fri     Sat     Sun
+                 1 (1)   2       3       4       5
+ 6       7       8       9      10      11      12
+13      14      15      16      17 (2)  18      19
+20      21      22      23      24      25      26
+27      28      29      30      31
+";
+        assert_eq!(calendar, expected);
+    }
+}
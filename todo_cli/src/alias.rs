@@ -0,0 +1,105 @@
+use clap::Command;
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    io::{Error, ErrorKind},
+};
+
+/// Replaces the command word of the arguments with the command it's an alias of in the config
+/// file, like `urgent` for `list --priority high --pending`. The arguments after it are
+/// kept, so they're added to those of the alias. Commands and their built-in aliases can't be
+/// replaced, so the arguments are unchanged for them.
+pub fn expand(
+    command: &Command,
+    args: Vec<OsString>,
+    aliases: &BTreeMap<String, String>,
+) -> Result<Vec<OsString>, Error> {
+    let Some(position) = command_position(command, &args) else {
+        return Ok(args);
+    };
+    let Some(word) = args[position].to_str() else {
+        return Ok(args);
+    };
+    let is_builtin = command.find_subcommand(word).is_some();
+    let Some(expansion) = aliases.get(word).filter(|_| !is_builtin) else {
+        return Ok(args);
+    };
+    let words = split_words(expansion).map_err(|error| {
+        let message = format!("alias '{}' in the config file: {}", word, error);
+        Error::new(ErrorKind::InvalidData, message)
+    })?;
+    let mut expanded = args[..position].to_vec();
+    expanded.extend(words.into_iter().map(OsString::from));
+    expanded.extend_from_slice(&args[position + 1..]);
+    Ok(expanded)
+}
+
+/// Adds the default command from the config file, with its options, to arguments that have
+/// no command, like those of a bare `todo`. Asking for help needs none.
+pub fn with_default(
+    command: &Command,
+    mut args: Vec<OsString>,
+    default: &str,
+) -> Result<Vec<OsString>, Error> {
+    let is_help = args.iter().skip(1).any(|arg| {
+        let arg = arg.to_str().unwrap_or_default();
+        matches!(arg, "-h" | "--help" | "--")
+    });
+    if is_help || command_position(command, &args).is_some() {
+        return Ok(args);
+    }
+    let words = split_words(default).map_err(|error| {
+        let message = format!("default_command in the config file: {}", error);
+        Error::new(ErrorKind::InvalidData, message)
+    })?;
+    args.extend(words.into_iter().map(OsString::from));
+    Ok(args)
+}
+
+/// Finds the command word, skipping the program name and the global options before it, along
+/// with the values of those that take one.
+fn command_position(command: &Command, args: &[OsString]) -> Option<usize> {
+    let mut position = 1;
+    while let Some(arg) = args.get(position) {
+        let arg = arg.to_str()?;
+        if arg == "--" || !arg.starts_with('-') {
+            return (arg != "--").then_some(position);
+        }
+        let takes_value = command.get_arguments().any(|option| {
+            let long = arg.strip_prefix("--").filter(|name| !name.contains('='));
+            let short = arg.strip_prefix('-').and_then(|name| name.chars().next());
+            let matches = (long.is_some() && option.get_long() == long)
+                || (long.is_none() && short.is_some() && option.get_short() == short);
+            matches && option.get_action().takes_values()
+        });
+        position += if takes_value { 2 } else { 1 };
+    }
+    None
+}
+
+/// Splits an alias into words at whitespace, except inside single or double quotes.
+fn split_words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("a quote isn't closed".to_string());
+    }
+    words.extend(word);
+    if words.is_empty() {
+        return Err("it's empty".to_string());
+    }
+    Ok(words)
+}
@@ -0,0 +1,451 @@
+use crate::Args;
+use clap::{Arg, Command, CommandFactory, ValueEnum};
+
+/// The hidden command that the completion scripts run to complete task IDs.
+pub const TASK_IDS_COMMAND: &str = "__complete-ids";
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// What can be completed for an argument.
+enum Values {
+    None,
+    TaskIds,
+    Choices(Vec<String>),
+    Files,
+}
+
+struct OptionInfo {
+    long: Option<String>,
+    short: Option<char>,
+    help: String,
+    values: Values,
+}
+
+/// A command or subcommand, with the names leading to it, like `todo_cli lists create`.
+struct CommandInfo {
+    path: String,
+    subcommands: Vec<(String, String)>,
+    options: Vec<OptionInfo>,
+    arguments: Values,
+}
+
+/// Generates a completion script for a shell from the definition of the commands, which also
+/// completes the IDs of open tasks, by running the program itself.
+pub fn generate(shell: Shell) -> String {
+    let mut command = Args::command();
+    // Building the command adds the global options and `--help` to every subcommand.
+    command.build();
+    let name = command.get_name().to_string();
+    let mut commands = Vec::new();
+    collect(&command, name.clone(), &mut commands);
+    match shell {
+        Shell::Bash => bash(&name, &commands),
+        Shell::Zsh => zsh(&name, &commands),
+        Shell::Fish => fish(&name, &commands),
+        Shell::Powershell => powershell(&name, &commands),
+    }
+}
+
+fn collect(command: &Command, path: String, commands: &mut Vec<CommandInfo>) {
+    let subcommands: Vec<&Command> = command
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set() && subcommand.get_name() != "help")
+        .collect();
+    let mut info = CommandInfo {
+        path: path.clone(),
+        subcommands: subcommands
+            .iter()
+            .map(|subcommand| (subcommand.get_name().to_string(), help_text(subcommand)))
+            .collect(),
+        options: Vec::new(),
+        arguments: Values::None,
+    };
+    for arg in command.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        if arg.is_positional() {
+            // The first argument is the one completed, it's the task ID for most commands.
+            if matches!(info.arguments, Values::None) {
+                info.arguments = values(arg);
+            }
+        } else {
+            info.options.push(OptionInfo {
+                long: arg.get_long().map(|long| format!("--{}", long)),
+                short: arg.get_short(),
+                help: arg.get_help().map(ToString::to_string).unwrap_or_default(),
+                values: values(arg),
+            });
+        }
+    }
+    commands.push(info);
+    for subcommand in subcommands {
+        let path = format!("{} {}", path, subcommand.get_name());
+        collect(subcommand, path, commands);
+    }
+}
+
+fn help_text(command: &Command) -> String {
+    let about = command.get_about().map(ToString::to_string);
+    // Only the first line, since long descriptions wrap.
+    about
+        .unwrap_or_default()
+        .lines()
+        .next()
+        .unwrap_or("")
+        .to_string()
+}
+
+fn values(arg: &Arg) -> Values {
+    if !arg.get_action().takes_values() {
+        return Values::None;
+    }
+    let is_task_id = arg
+        .get_value_names()
+        .is_some_and(|names| names.iter().any(|name| name == "task-id"));
+    let choices: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if is_task_id {
+        Values::TaskIds
+    } else if !choices.is_empty() {
+        Values::Choices(choices)
+    } else {
+        Values::Files
+    }
+}
+
+impl CommandInfo {
+    fn option_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for option in &self.options {
+            names.extend(option.short.map(|short| format!("-{}", short)));
+            names.extend(option.long.clone());
+        }
+        names
+    }
+
+    fn subcommand_names(&self) -> Vec<&str> {
+        let names = self.subcommands.iter().map(|(name, _)| name.as_str());
+        names.collect()
+    }
+}
+
+/// How a shell completes the values of an argument, in the words of its script.
+fn shell_values(values: &Values, task_ids: &str, files: &str) -> Option<String> {
+    match values {
+        Values::None => None,
+        Values::TaskIds => Some(task_ids.to_string()),
+        Values::Choices(choices) => Some(choices.join(" ")),
+        Values::Files => Some(files.to_string()),
+    }
+}
+
+fn bash(name: &str, commands: &[CommandInfo]) -> String {
+    let function = format!("_{}", name);
+    let task_ids = "@ids";
+    let files = "@files";
+    let mut subcommands = String::new();
+    let mut options = String::new();
+    let mut values = String::new();
+    for command in commands {
+        let path = &command.path;
+        subcommands.push_str(&format!(
+            "        [\"{}\"]=\"{}\"\n",
+            path,
+            command.subcommand_names().join(" ")
+        ));
+        options.push_str(&format!(
+            "        [\"{}\"]=\"{}\"\n",
+            path,
+            command.option_names().join(" ")
+        ));
+        if let Some(words) = shell_values(&command.arguments, task_ids, files) {
+            values.push_str(&format!("        [\"{}\"]=\"{}\"\n", path, words));
+        }
+        for option in &command.options {
+            let Some(words) = shell_values(&option.values, task_ids, files) else {
+                continue;
+            };
+            let names = option.short.map(|short| format!("-{}", short));
+            for flag in names.into_iter().chain(option.long.clone()) {
+                values.push_str(&format!("        [\"{} {}\"]=\"{}\"\n", path, flag, words));
+            }
+        }
+    }
+
+    format!(
+        r#"{function}() {{
+    local -A subcommands=(
+{subcommands}    )
+    local -A options=(
+{options}    )
+    # The values of an option, keyed by the command and the option, or of the arguments of a
+    # command, keyed by the command.
+    local -A values=(
+{values}    )
+    local cur=${{COMP_WORDS[COMP_CWORD]}} prev=${{COMP_WORDS[COMP_CWORD-1]}}
+    local command_path="{name}" word i candidates
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word=${{COMP_WORDS[i]}}
+        if [[ " ${{subcommands[$command_path]}} " == *" $word "* ]]; then
+            command_path="$command_path $word"
+        fi
+    done
+
+    if [[ -n ${{values["$command_path $prev"]}} ]]; then
+        candidates=${{values["$command_path $prev"]}}
+    elif [[ $cur == -* ]]; then
+        candidates=${{options[$command_path]}}
+    elif [[ -n ${{subcommands[$command_path]}} ]]; then
+        candidates=${{subcommands[$command_path]}}
+    else
+        candidates=${{values[$command_path]}}
+    fi
+    case $candidates in
+        {task_ids}) candidates=$({name} {ids_command} 2>/dev/null | cut -f1) ;;
+        {files})
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return
+            ;;
+    esac
+    COMPREPLY=($(compgen -W "$candidates" -- "$cur"))
+}}
+
+complete -F {function} {name}
+"#,
+        ids_command = TASK_IDS_COMMAND,
+    )
+}
+
+fn zsh(name: &str, commands: &[CommandInfo]) -> String {
+    let function = format!("_{}", name);
+    let task_ids = "@ids";
+    let files = "@files";
+    let mut subcommands = String::new();
+    let mut options = String::new();
+    let mut values = String::new();
+    for command in commands {
+        let path = &command.path;
+        subcommands.push_str(&format!(
+            "        '{}' '{}'\n",
+            path,
+            command.subcommand_names().join(" ")
+        ));
+        options.push_str(&format!(
+            "        '{}' '{}'\n",
+            path,
+            command.option_names().join(" ")
+        ));
+        if let Some(words) = shell_values(&command.arguments, task_ids, files) {
+            values.push_str(&format!("        '{}' '{}'\n", path, words));
+        }
+        for option in &command.options {
+            let Some(words) = shell_values(&option.values, task_ids, files) else {
+                continue;
+            };
+            let names = option.short.map(|short| format!("-{}", short));
+            for flag in names.into_iter().chain(option.long.clone()) {
+                values.push_str(&format!("        '{} {}' '{}'\n", path, flag, words));
+            }
+        }
+    }
+
+    format!(
+        r#"#compdef {name}
+
+{function}() {{
+    local -A subcommands options values
+    subcommands=(
+{subcommands}    )
+    options=(
+{options}    )
+    # The values of an option, keyed by the command and the option, or of the arguments of a
+    # command, keyed by the command.
+    values=(
+{values}    )
+    local prev=${{words[CURRENT-1]}} command_path="{name}" word candidates
+    for word in ${{words[2,CURRENT-1]}}; do
+        if (( ${{${{(s: :)subcommands[$command_path]}}[(Ie)$word]}} )); then
+            command_path="$command_path $word"
+        fi
+    done
+
+    if [[ -n ${{values[$command_path $prev]}} ]]; then
+        candidates=${{values[$command_path $prev]}}
+    elif [[ $PREFIX == -* ]]; then
+        candidates=${{options[$command_path]}}
+    elif [[ -n ${{subcommands[$command_path]}} ]]; then
+        candidates=${{subcommands[$command_path]}}
+    else
+        candidates=${{values[$command_path]}}
+    fi
+    case $candidates in
+        {task_ids})
+            local -a ids
+            ids=(${{(f)"$({name} {ids_command} 2>/dev/null)"}})
+            compadd -- ${{ids%%$'\t'*}}
+            ;;
+        {files}) _files ;;
+        *) compadd -- ${{(s: :)candidates}} ;;
+    esac
+}}
+
+if [[ $zsh_eval_context[-1] == loadautofunc ]]; then
+    {function} "$@"
+else
+    compdef {function} {name}
+fi
+"#,
+        ids_command = TASK_IDS_COMMAND,
+    )
+}
+
+fn fish(name: &str, commands: &[CommandInfo]) -> String {
+    let function = format!("__{}_command_path", name);
+    let paths: Vec<String> = commands
+        .iter()
+        .skip(1)
+        .map(|command| fish_quote(&command.path))
+        .collect();
+    let mut script = format!(
+        r#"# Prints the subcommands given so far, like `{name} lists create`.
+function {function}
+    set -l command_path {name}
+    for word in (commandline -opc)[2..-1]
+        switch "$command_path $word"
+            case {paths}
+                set command_path "$command_path $word"
+        end
+    end
+    echo $command_path
+end
+
+"#,
+        paths = paths.join(" "),
+    );
+
+    let task_ids = format!("({} {})", name, TASK_IDS_COMMAND);
+    for command in commands {
+        let condition = fish_quote(&format!("test ({}) = \"{}\"", function, command.path));
+        for (subcommand, help) in &command.subcommands {
+            script.push_str(&format!(
+                "complete -c {} -f -n {} -a {} -d {}\n",
+                name,
+                condition,
+                subcommand,
+                fish_quote(help)
+            ));
+        }
+        if command.subcommands.is_empty() {
+            match shell_values(&command.arguments, &task_ids, "") {
+                Some(words) if !words.is_empty() => script.push_str(&format!(
+                    "complete -c {} -f -n {} -a {}\n",
+                    name,
+                    condition,
+                    fish_quote(&words)
+                )),
+                Some(_) => {}
+                None => script.push_str(&format!("complete -c {} -f -n {}\n", name, condition)),
+            }
+        }
+        for option in &command.options {
+            let mut line = format!("complete -c {} -n {}", name, condition);
+            if let Some(long) = &option.long {
+                line.push_str(&format!(" -l {}", long.trim_start_matches("--")));
+            }
+            if let Some(short) = option.short {
+                line.push_str(&format!(" -s {}", short));
+            }
+            match shell_values(&option.values, &task_ids, "") {
+                Some(words) if !words.is_empty() => {
+                    line.push_str(&format!(" -x -a {}", fish_quote(&words)))
+                }
+                Some(_) => line.push_str(" -r -F"),
+                None => {}
+            }
+            if !option.help.is_empty() {
+                line.push_str(&format!(" -d {}", fish_quote(&option.help)));
+            }
+            script.push_str(&line);
+            script.push('\n');
+        }
+    }
+    script
+}
+
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn powershell(name: &str, commands: &[CommandInfo]) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+    let list = |words: Vec<String>| {
+        let words: Vec<String> = words.iter().map(|word| quote(word)).collect();
+        format!("@({})", words.join(", "))
+    };
+    let mut subcommands = String::new();
+    let mut options = String::new();
+    let mut task_id_commands = Vec::new();
+    for command in commands {
+        let names = command
+            .subcommand_names()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        subcommands.push_str(&format!(
+            "        {} = {}\n",
+            quote(&command.path),
+            list(names)
+        ));
+        options.push_str(&format!(
+            "        {} = {}\n",
+            quote(&command.path),
+            list(command.option_names())
+        ));
+        if matches!(command.arguments, Values::TaskIds) {
+            task_id_commands.push(command.path.clone());
+        }
+    }
+
+    format!(
+        r#"Register-ArgumentCompleter -Native -CommandName {name} -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $subcommands = @{{
+{subcommands}    }}
+    $options = @{{
+{options}    }}
+    $taskIdCommands = {task_id_commands}
+
+    $commandPath = '{name}'
+    foreach ($element in $commandAst.CommandElements | Select-Object -Skip 1) {{
+        $word = $element.ToString()
+        if ($element.Extent.StartOffset -ge $cursorPosition) {{ break }}
+        if ($word -eq $wordToComplete) {{ break }}
+        if ($subcommands[$commandPath] -contains $word) {{ $commandPath = "$commandPath $word" }}
+    }}
+
+    if ($wordToComplete.StartsWith('-')) {{
+        $candidates = $options[$commandPath]
+    }} elseif ($subcommands[$commandPath].Count -gt 0) {{
+        $candidates = $subcommands[$commandPath]
+    }} elseif ($taskIdCommands -contains $commandPath) {{
+        $candidates = & {name} {ids_command} 2>$null | ForEach-Object {{ ($_ -split "`t")[0] }}
+    }} else {{
+        $candidates = @()
+    }}
+    $candidates | Where-Object {{ $_ -like "$wordToComplete*" }} | ForEach-Object {{
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }}
+}}
+"#,
+        task_id_commands = list(task_id_commands),
+        ids_command = TASK_IDS_COMMAND,
+    )
+}
@@ -0,0 +1,121 @@
+use crate::{
+    github::GithubConfig, hooks::Hooks, shortener::ShortenerConfig, sync::SyncConfig,
+    template::Template, timesheet,
+};
+use chrono::Duration;
+use common::config::{load_toml, xdg_dir};
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{self, Error, IsTerminal},
+    path::PathBuf,
+};
+
+const APP_DIR_NAME: &str = "todo";
+const CONFIG_FILE_NAME: &str = "config.toml";
+const LOCAL_FILE_NAME: &str = ".todo.json";
+
+/// Defaults read from `~/.config/todo/config.toml`. Every setting is optional, and so is the file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The list used without `--list` or `TODO_LIST`.
+    pub default_list: Option<String>,
+    /// The command run by a bare `todo`, with its options, like `today` or
+    /// `list --pending`. `list` if not set.
+    pub default_command: Option<String>,
+    /// A chrono format for dates, like `%d.%m.%Y`. Times are added as `%H:%M`.
+    pub date_format: Option<String>,
+    pub color: ColorChoice,
+    pub storage: StorageBackend,
+    /// Tasks added with `add --template`, by name.
+    pub templates: BTreeMap<String, Template>,
+    /// Commands with options, by the name they're run with, like
+    /// `urgent = "list --priority high --pending"`.
+    pub aliases: BTreeMap<String, String>,
+    pub sync: SyncConfig,
+    pub github: GithubConfig,
+    pub shortener: ShortenerConfig,
+    /// Keeps the data directory under git, with a commit after every command that changed
+    /// it, for `history` and `revert`.
+    pub git: bool,
+    /// Encrypts the list files with a passphrase from `TODO_PASSPHRASE`, the keyring or a
+    /// prompt. Only lists in JSON files can be encrypted.
+    pub encrypt: bool,
+    pub hooks: Hooks,
+    /// How much work fits in a day, like `6h`, which `list` warns about when the estimates of
+    /// the tasks due today add up to more.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub daily_capacity: Option<Duration>,
+    /// Uses the nearest `.todo.json` of the working directory and its parents as the file of
+    /// the default list, like `--file`, so projects can keep a list of their own.
+    pub local_files: bool,
+}
+
+/// Where lists are kept.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// A JSON file for every list, which is easy to read and edit by hand.
+    #[default]
+    Json,
+    /// A SQLite database, which stays fast with large lists.
+    Sqlite,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Colors when writing to a terminal, unless `NO_COLOR` is set to anything but an empty
+    /// string.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, Error> {
+        let Some(path) = xdg_dir("XDG_CONFIG_HOME", ".config") else {
+            return Ok(Config::default());
+        };
+        let path = path.join(APP_DIR_NAME).join(CONFIG_FILE_NAME);
+        Ok(load_toml(&path)?.unwrap_or_default())
+    }
+}
+
+/// Reads a duration like `6h` or `7h30m`.
+fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    let duration = String::deserialize(deserializer)?;
+    timesheet::parse_duration(&duration)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// The directory lists are kept in, `$XDG_DATA_HOME/todo` or `~/.local/share/todo`.
+pub fn data_dir_path() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join(APP_DIR_NAME))
+}
+
+/// The nearest `.todo.json` in the working directory or one of its parents.
+pub fn local_file_path() -> Option<PathBuf> {
+    let dir = env::current_dir().ok()?;
+    dir.ancestors()
+        .map(|dir| dir.join(LOCAL_FILE_NAME))
+        .find(|path| path.is_file())
+}
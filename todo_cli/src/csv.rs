@@ -0,0 +1,117 @@
+use crate::{due, parse_tag, Priority, Task};
+use chrono::Local;
+use clap::ValueEnum;
+
+/// Parses CSV with a header row into tasks without IDs, the way spreadsheets and other apps
+/// export it. Columns are found by their names in the header, ignoring case: `title` is the
+/// only one that's required, while `due`, `priority`, `tags`, `notes` and `completed` are
+/// taken when they're there. Other columns are ignored, and so are rows without a title.
+pub fn parse_tasks(input: &str) -> Result<Vec<Task>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(input.as_bytes());
+    let header = reader.headers().map_err(|error| error.to_string())?.clone();
+    if header.is_empty() {
+        return Ok(Vec::new());
+    }
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|field| field.eq_ignore_ascii_case(name))
+    };
+    let Some(title_column) = column("title") else {
+        return Err("no title column in the header".to_string());
+    };
+    let due_column = column("due");
+    let priority_column = column("priority");
+    let tags_column = column("tags");
+    let notes_column = column("notes");
+    let completed_column = column("completed");
+
+    let mut tasks = Vec::new();
+    // The header is the first row.
+    for (row, record) in reader
+        .records()
+        .enumerate()
+        .map(|(index, record)| (index + 2, record))
+    {
+        let record = record.map_err(|error| format!("row {}: {}", row, error))?;
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|field| !field.is_empty())
+        };
+        let Some(title) = field(Some(title_column)) else {
+            continue;
+        };
+        let mut task = Task::new(0, title.to_string());
+        if let Some(value) = field(due_column) {
+            let due = due::parse(value).map_err(|error| format!("row {}: {}", row, error))?;
+            task.due = Some(due);
+        }
+        if let Some(value) = field(priority_column) {
+            let priority = Priority::from_str(value, true)
+                .map_err(|_| format!("row {}: unknown priority '{}'", row, value))?;
+            task.priority = Some(priority);
+        }
+        if let Some(value) = field(tags_column) {
+            let words = value.split(|c: char| c.is_whitespace() || c == ',' || c == ';');
+            for word in words.filter(|word| !word.is_empty()) {
+                let tag = parse_tag(word).map_err(|error| format!("row {}: {}", row, error))?;
+                task.add_tag(tag);
+            }
+        }
+        if let Some(value) = field(notes_column) {
+            task.append_note(value);
+        }
+        if let Some(value) = field(completed_column) {
+            task.completed = matches!(value.to_lowercase().as_str(), "true" | "yes" | "x" | "1");
+            if task.completed {
+                task.completed_at = Some(Local::now());
+            }
+        }
+        tasks.push(task);
+    }
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::due::Due;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn parses_quoted_fields() {
+        let input = "title,notes\r\n\"Buy milk, eggs\",\"say \"\"hi\"\"\"\r\n\n\"multi\nline\",\n";
+        let tasks = parse_tasks(input).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].title, "Buy milk, eggs");
+        assert_eq!(tasks[0].notes, "say \"hi\"");
+        assert_eq!(tasks[1].title, "multi\nline");
+        assert_eq!(parse_tasks("").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn parses_tasks_by_column_name() {
+        let input = "\
+ID,Title,Priority,Due,Tags,Completed
+1,Buy milk,high,2024-05-18,\"shop; home\",
+2,,low,,,
+3,Call mom,,,,yes
+";
+        let tasks = parse_tasks(input).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].title, "Buy milk");
+        assert_eq!(tasks[0].priority, Some(Priority::High));
+        let date = NaiveDate::from_ymd_opt(2024, 5, 18).unwrap();
+        assert_eq!(tasks[0].due, Some(Due::On(date)));
+        assert_eq!(tasks[0].tags, vec!["shop", "home"]);
+        assert!(!tasks[0].completed);
+        assert!(tasks[1].completed);
+
+        assert!(parse_tasks("name\nBuy milk\n").is_err());
+        assert!(parse_tasks("title,priority\nBuy milk,urgent\n").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Write as _},
+    io::{Error, ErrorKind},
+    sync::OnceLock,
+};
+
+const DEFAULT_DATE_FORMAT: &str = "%a %Y-%m-%d";
+
+static DATE_FORMAT: OnceLock<String> = OnceLock::new();
+
+/// When a task is due, either on a whole day or at a time of day. It's stored as plain local
+/// time, `2024-05-17` or `2024-05-17T17:00:00`, like the clock of whoever uses the list.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum Due {
+    At(NaiveDateTime),
+    On(NaiveDate),
+}
+
+impl Due {
+    /// The first moment of the day or time the task is due.
+    pub fn start(&self) -> NaiveDateTime {
+        match self {
+            Due::At(date_time) => *date_time,
+            Due::On(date) => date.and_time(NaiveTime::MIN),
+        }
+    }
+
+    /// The same due date later by a duration, which stays a whole day if the duration is one.
+    pub fn shifted(&self, duration: Duration) -> Due {
+        match self {
+            Due::On(date) if duration.num_seconds() % 86_400 == 0 => {
+                Due::On(*date + Duration::days(duration.num_days()))
+            }
+            _ => Due::At(self.start() + duration),
+        }
+    }
+
+    /// The last moment the task is due, which is the end of the day for whole days.
+    pub fn deadline(&self) -> NaiveDateTime {
+        match self {
+            Due::At(date_time) => *date_time,
+            Due::On(date) => date.and_time(NaiveTime::MIN) + Duration::days(1),
+        }
+    }
+}
+
+impl fmt::Display for Due {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Due::At(date_time) => f.write_str(&format_timestamp(*date_time)),
+            Due::On(date) => write!(f, "{}", date.format(date_format())),
+        }
+    }
+}
+
+/// Sets the chrono format dates are shown in, before any are shown.
+pub fn set_date_format(format: &str) -> Result<(), Error> {
+    // Formatting fails on unknown specifiers or ones that need a time zone.
+    if write!(String::new(), "{}", NaiveDateTime::MIN.format(format)).is_err() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("invalid date format '{}'", format),
+        ));
+    }
+    let _ = DATE_FORMAT.set(format.to_string());
+    Ok(())
+}
+
+fn date_format() -> &'static str {
+    DATE_FORMAT
+        .get()
+        .map_or(DEFAULT_DATE_FORMAT, String::as_str)
+}
+
+/// Shows a time in the date format, followed by the time of day.
+pub fn format_timestamp(date_time: NaiveDateTime) -> String {
+    format!(
+        "{} {}",
+        date_time.format(date_format()),
+        date_time.format("%H:%M")
+    )
+}
+
+/// How long `snooze` hides a task: for a while, or until a date.
+#[derive(Clone, Copy, Debug)]
+pub enum Snooze {
+    For(Duration),
+    Until(Due),
+}
+
+/// Parses durations like `3d`, `2w`, `4h` or `30m`, or else a date like [`parse`] does, for use
+/// as a clap value parser.
+pub fn parse_snooze(input: &str) -> Result<Snooze, String> {
+    let trimmed = input.trim();
+    let units = [("w", 7 * 24 * 60), ("d", 24 * 60), ("h", 60), ("m", 1)];
+    for (unit, minutes) in units {
+        if let Some(count) = trimmed.strip_suffix(unit) {
+            if let Ok(count) = count.trim().parse::<i64>() {
+                return match Duration::try_minutes(count.saturating_mul(minutes)) {
+                    Some(duration) if count > 0 => Ok(Snooze::For(duration)),
+                    _ => Err(format!("invalid duration '{}'", input)),
+                };
+            }
+        }
+    }
+    parse(input).map(Snooze::Until).map_err(|_| {
+        "expected a duration like 3d, 2w or 4h, or a date like tomorrow or \"fri 5pm\"".to_string()
+    })
+}
+
+/// Parses a due date relative to the current local time, for use as a clap value parser.
+pub fn parse(input: &str) -> Result<Due, String> {
+    parse_relative_to(input, Local::now().naive_local())
+}
+
+/// Parses dates like `2024-05-17`, `today`, `tomorrow`, `fri`, `next monday` or `in 3 days`,
+/// optionally followed by a time like `5pm`, `9:30am` or `17:00`. A time on its own means
+/// today, or tomorrow if it has already passed.
+pub fn parse_relative_to(input: &str, now: NaiveDateTime) -> Result<Due, String> {
+    let input = input.trim().to_lowercase();
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    let invalid = || {
+        "expected a date like 2024-05-17, today, tomorrow, fri or in 3 days, optionally \
+        followed by a time like 5pm or 17:00"
+            .to_string()
+    };
+
+    let time = match words.last().and_then(|word| parse_time(word)) {
+        Some(time) => {
+            words.pop();
+            Some(time)
+        }
+        None => None,
+    };
+    let today = now.date();
+    let date = match (words.as_slice(), time) {
+        ([], Some(time)) if time > now.time() => today,
+        ([], Some(_)) => today + Duration::days(1),
+        ([], None) => return Err(invalid()),
+        (words, _) => parse_date(words, today).ok_or_else(invalid)?,
+    };
+
+    Ok(match time {
+        Some(time) => Due::At(date.and_time(time)),
+        None => Due::On(date),
+    })
+}
+
+fn parse_date(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    match words {
+        ["today"] => Some(today),
+        ["tomorrow"] => Some(today + Duration::days(1)),
+        ["next", weekday] => next_weekday(weekday, today),
+        ["in", count, unit] => {
+            let count: i64 = count.parse().ok()?;
+            let days = match *unit {
+                "day" | "days" => count,
+                "week" | "weeks" => count.checked_mul(7)?,
+                _ => return None,
+            };
+            today.checked_add_signed(Duration::try_days(days)?)
+        }
+        [word] => {
+            next_weekday(word, today).or_else(|| NaiveDate::parse_from_str(word, "%Y-%m-%d").ok())
+        }
+        _ => None,
+    }
+}
+
+/// Naming today's weekday means the one a week from now.
+fn next_weekday(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let weekday: Weekday = word.parse().ok()?;
+    let days_ahead =
+        (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    let days_ahead = if days_ahead == 0 { 7 } else { days_ahead };
+    Some(today + Duration::days(days_ahead.into()))
+}
+
+/// Parses `17:00`, `5pm` or `5:30pm`.
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    let (clock, offset) = if let Some(clock) = word.strip_suffix("am") {
+        (clock, Some(0))
+    } else if let Some(clock) = word.strip_suffix("pm") {
+        (clock, Some(12))
+    } else {
+        (word, None)
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour.parse().ok()?, minute.parse().ok()?),
+        Some(_) => return None,
+        // A bare number is a day count or a year, not a time, unless it says am or pm.
+        None if offset.is_some() => (clock.parse().ok()?, 0),
+        None => return None,
+    };
+    let hour: u32 = match offset {
+        Some(offset) if (1..=12).contains(&hour) => hour % 12 + offset,
+        Some(_) => return None,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    fn at(month: u32, day: u32, hour: u32, minute: u32) -> Due {
+        Due::At(date(month, day).and_hms_opt(hour, minute, 0).unwrap())
+    }
+
+    #[test]
+    fn parses_dates_relative_to_now() {
+        // A Wednesday morning.
+        let now = date(5, 15).and_hms_opt(10, 0, 0).unwrap();
+        let cases = [
+            ("today", Due::On(date(5, 15))),
+            ("Tomorrow", Due::On(date(5, 16))),
+            ("fri", Due::On(date(5, 17))),
+            ("fri 5pm", at(5, 17, 17, 0)),
+            ("next monday 9:30am", at(5, 20, 9, 30)),
+            // Today's weekday is the one a week from now.
+            ("wed", Due::On(date(5, 22))),
+            ("in 3 days", Due::On(date(5, 18))),
+            ("in 2 weeks 17:00", at(5, 29, 17, 0)),
+            ("2024-06-01", Due::On(date(6, 1))),
+            ("12am", at(5, 16, 0, 0)),
+            ("12pm", at(5, 15, 12, 0)),
+            // A time that has passed today is tomorrow's.
+            ("9am", at(5, 16, 9, 0)),
+            ("10:30", at(5, 15, 10, 30)),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_relative_to(input, now), Ok(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_dates_and_times() {
+        let now = date(5, 15).and_hms_opt(10, 0, 0).unwrap();
+        for input in [
+            "",
+            "25pm",
+            "0am",
+            "tomorrow 5:3pm",
+            "feb 30",
+            "2024-02-30",
+            "in three days",
+            "in 3 months",
+            "someday",
+        ] {
+            assert!(parse_relative_to(input, now).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_snooze_durations_and_dates() {
+        let minutes = |input| match parse_snooze(input) {
+            Ok(Snooze::For(duration)) => duration.num_minutes(),
+            _ => panic!("{} isn't a duration", input),
+        };
+        assert_eq!(minutes("30m"), 30);
+        assert_eq!(minutes("4h"), 4 * 60);
+        assert_eq!(minutes("3d"), 3 * 24 * 60);
+        assert_eq!(minutes(" 2w "), 2 * 7 * 24 * 60);
+        assert!(matches!(parse_snooze("tomorrow"), Ok(Snooze::Until(_))));
+        assert!(parse_snooze("0d").is_err());
+        assert!(parse_snooze("-1h").is_err());
+        assert!(parse_snooze("soon").is_err());
+    }
+}
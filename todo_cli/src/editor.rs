@@ -0,0 +1,100 @@
+use crate::{due, due::Due, parse_tag, Task};
+use std::{
+    env, fs,
+    io::{Error, ErrorKind},
+    path::Path,
+    process::Command,
+};
+
+const DEFAULT_EDITOR: &str = "vi";
+const NOTES_HEADER: &str = "# Notes, everything below this line:";
+
+/// Opens `$VISUAL` or `$EDITOR` on the title, due date, tags and notes of a task, and applies
+/// what was saved. The task is left as it was if the editor fails or the result can't be parsed.
+pub fn edit_task(task: &mut Task) -> Result<(), Error> {
+    let path = env::temp_dir().join(format!("todo-task-{}-{}.txt", task.id, std::process::id()));
+    fs::write(&path, format_template(task))?;
+    let edited = run_editor(&path).and_then(|()| fs::read_to_string(&path));
+    let _ = fs::remove_file(&path);
+    apply_template(task, &edited?).map_err(|error| Error::new(ErrorKind::InvalidData, error))
+}
+
+fn run_editor(path: &Path) -> Result<(), Error> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+    // Editors are often given with arguments, like `code --wait`.
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_EDITOR);
+    let status = Command::new(program).args(words).arg(path).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!("{} exited with {}", editor, status)))
+    }
+}
+
+fn format_template(task: &Task) -> String {
+    let due = match task.due {
+        Some(Due::At(date_time)) => date_time.format("%Y-%m-%d %H:%M").to_string(),
+        Some(Due::On(date)) => date.format("%Y-%m-%d").to_string(),
+        None => String::new(),
+    };
+    // Contexts are edited in the title they're taken from.
+    let contexts = task.contexts.iter().map(|context| format!(" @{}", context));
+    format!(
+        "Title: {}{}\nDue: {}\nTags: {}\n\n{}\n{}",
+        task.title,
+        contexts.collect::<String>(),
+        due,
+        task.tags.join(", "),
+        NOTES_HEADER,
+        task.notes
+    )
+}
+
+/// Reads the fields back from an edited template. Fields that are left empty are cleared.
+fn apply_template(task: &mut Task, text: &str) -> Result<(), String> {
+    let (fields, notes) = text.split_once(NOTES_HEADER).unwrap_or((text, ""));
+    let (mut title, mut due, mut tags) = (None, None, Vec::new());
+    for line in fields.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            return Err(format!(
+                "expected a field like 'Title: …', found '{}'",
+                line
+            ));
+        };
+        let value = value.trim();
+        match key.trim().to_lowercase().as_str() {
+            "title" => title = Some(value.to_string()),
+            "due" if value.is_empty() => due = None,
+            "due" => due = Some(due::parse(value).map_err(|error| format!("due: {}", error))?),
+            "tags" => {
+                for tag in value.split(',').filter(|tag| !tag.trim().is_empty()) {
+                    tags.push(parse_tag(tag).map_err(|error| format!("tags: {}", error))?);
+                }
+            }
+            _ => return Err(format!("unknown field '{}'", key.trim())),
+        }
+    }
+    let title = title.filter(|title| !title.is_empty());
+    let Some(title) = title else {
+        return Err("the title can't be empty".to_string());
+    };
+
+    task.title = title;
+    task.contexts.clear();
+    task.take_contexts();
+    task.due = due;
+    task.tags.clear();
+    for tag in tags {
+        task.add_tag(tag);
+    }
+    task.notes = notes.trim().to_string();
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+    process::ExitCode,
+};
+
+/// Why a command failed, which decides the exit status, so scripts can tell a missing task
+/// from a mistyped command or a broken file.
+#[derive(Debug)]
+pub enum TodoError {
+    /// Tasks that aren't in the list, by ID or UUID, after the others were changed. Exits
+    /// with 1.
+    NotFound { ids: Vec<String>, list_name: String },
+    /// Options that can't be used together, or names that aren't defined. Exits with 2, like
+    /// the errors of clap.
+    Usage(String),
+    /// Reading or writing the lists, the config file or other files failed. Exits with 3.
+    Storage(io::Error),
+}
+
+impl TodoError {
+    pub fn not_found(id: impl ToString, list_name: &str) -> Self {
+        TodoError::NotFound {
+            ids: vec![id.to_string()],
+            list_name: list_name.to_string(),
+        }
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            TodoError::NotFound { .. } => ExitCode::from(1),
+            TodoError::Usage(_) => ExitCode::from(2),
+            TodoError::Storage(_) => ExitCode::from(3),
+        }
+    }
+}
+
+impl fmt::Display for TodoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TodoError::NotFound { ids, list_name } => {
+                let noun = if ids.len() == 1 { "task" } else { "tasks" };
+                write!(
+                    f,
+                    "{} {} not found in '{}'",
+                    noun,
+                    ids.join(", "),
+                    list_name
+                )
+            }
+            TodoError::Usage(message) => write!(f, "{}", message),
+            TodoError::Storage(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for TodoError {}
+
+/// The modules report names and options they refuse, like invalid list names, as
+/// `InvalidInput`, and everything else as it happened.
+impl From<io::Error> for TodoError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            ErrorKind::InvalidInput => TodoError::Usage(error.to_string()),
+            _ => TodoError::Storage(error),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{parse_tag, Task};
+use reqwest::{
+    blocking::{Client, RequestBuilder},
+    StatusCode,
+};
+use serde::Deserialize;
+use std::{
+    env,
+    io::{Error, ErrorKind},
+};
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+/// The most issues GitHub returns on one page.
+const PAGE_SIZE: usize = 100;
+
+/// Access to GitHub for `import --from github`, from `[github]` in the config file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct GithubConfig {
+    /// A personal access token that can read the issues. `GITHUB_TOKEN` is used instead when
+    /// it's set.
+    pub token: Option<String>,
+    /// The API of a GitHub Enterprise server, `https://api.github.com` if not set.
+    pub api_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct Issue {
+    title: String,
+    html_url: String,
+    #[serde(default)]
+    labels: Vec<Label>,
+    /// Only set for pull requests, which GitHub lists as issues too.
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Label {
+    name: String,
+}
+
+/// Fetches the open issues of a repository, like `owner/name`, that are assigned to the owner
+/// of the token, as tasks tagged with the labels that make valid tags. The URL of every issue
+/// is its task's source.
+pub fn fetch_assigned_issues(config: &GithubConfig, repo: &str) -> Result<Vec<Task>, Error> {
+    if repo.split('/').count() != 2 || repo.split('/').any(str::is_empty) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("expected a repository like owner/name, not '{}'", repo),
+        ));
+    }
+    let token = env::var("GITHUB_TOKEN").ok().or(config.token.clone());
+    let Some(token) = token.filter(|token| !token.is_empty()) else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "no token for GitHub, set `token` under [github] in the config file or GITHUB_TOKEN",
+        ));
+    };
+    let api_url = config.api_url.as_deref().unwrap_or(DEFAULT_API_URL);
+    let api_url = api_url.trim_end_matches('/');
+    let client = Client::new();
+    let request = |url: &str| {
+        client
+            .get(url)
+            .bearer_auth(&token)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "todo_cli")
+    };
+
+    let user: User = send(request(&format!("{}/user", api_url)))?;
+    let mut tasks = Vec::new();
+    for page in 1.. {
+        let url = format!(
+            "{}/repos/{}/issues?state=open&assignee={}&per_page={}&page={}",
+            api_url, repo, user.login, PAGE_SIZE, page
+        );
+        let issues: Vec<Issue> = send(request(&url))?;
+        let is_last_page = issues.len() < PAGE_SIZE;
+        for issue in issues.into_iter().filter(|i| i.pull_request.is_none()) {
+            let mut task = Task::new(0, issue.title);
+            for label in &issue.labels {
+                if let Ok(tag) = parse_tag(&label.name) {
+                    task.add_tag(tag);
+                }
+            }
+            task.source = Some(issue.html_url);
+            tasks.push(task);
+        }
+        if is_last_page {
+            break;
+        }
+    }
+    Ok(tasks)
+}
+
+fn send<T: serde::de::DeserializeOwned>(request: RequestBuilder) -> Result<T, Error> {
+    let response = request
+        .send()
+        .map_err(|error| Error::other(format!("couldn't reach GitHub: {}", error)))?;
+    let url = response.url().to_string();
+    match response.status() {
+        status if status.is_success() => response.json().map_err(|error| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid answer from {}: {}", url, error),
+            )
+        }),
+        status => {
+            let kind = match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorKind::PermissionDenied,
+                StatusCode::NOT_FOUND => ErrorKind::NotFound,
+                _ => ErrorKind::Other,
+            };
+            Err(Error::new(kind, format!("{} answered {}", url, status)))
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::{DateTime, Local};
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::Path,
+    process::{Command, Output},
+};
+
+/// Files of the data directory that aren't lists, like the lock file and the journal of
+/// `undo`, or that are only there for a moment.
+const GITIGNORE: &str = "\
+todo.lock
+task_journal.json
+sync.json
+.*.tmp
+*.bak
+tasks.db-journal
+";
+
+/// A commit of the data directory, from `history`.
+pub struct Commit {
+    pub hash: String,
+    pub at: DateTime<Local>,
+    /// The command that was run, like `add Buy milk`.
+    pub message: String,
+}
+
+/// Commits everything that changed in the data directory, with the command line as the
+/// message. Does nothing if nothing changed. The repository is created on first use.
+pub fn commit(data_dir: &Path, operation: &str) -> Result<(), Error> {
+    if !data_dir.join(".git").is_dir() {
+        run(git(data_dir).args(["init", "--quiet"]))?;
+        fs::write(data_dir.join(".gitignore"), GITIGNORE)?;
+    }
+    run(git(data_dir).args(["add", "--all"]))?;
+    let status = run(git(data_dir).args(["status", "--porcelain"]))?;
+    if status.stdout.is_empty() {
+        return Ok(());
+    }
+    let mut command = git(data_dir);
+    // Machines without an identity for git can still keep the history.
+    if run(git(data_dir).args(["config", "user.email"])).is_err() {
+        command.args([
+            "-c",
+            "user.name=todo_cli",
+            "-c",
+            "user.email=todo_cli@localhost",
+        ]);
+    }
+    let message = if operation.is_empty() {
+        "todo_cli"
+    } else {
+        operation
+    };
+    run(command.args(["commit", "--quiet", "--message", message]))?;
+    Ok(())
+}
+
+/// The last `count` commits, newest first.
+pub fn log(data_dir: &Path, count: usize) -> Result<Vec<Commit>, Error> {
+    ensure_repository(data_dir)?;
+    let output = run(git(data_dir).args([
+        "log",
+        &format!("--max-count={}", count),
+        "--format=%h%x09%at%x09%s",
+    ]))?;
+    let mut commits = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(hash), Some(at), Some(message)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let Some(at) = at
+            .parse()
+            .ok()
+            .and_then(|at| DateTime::from_timestamp(at, 0))
+        else {
+            continue;
+        };
+        commits.push(Commit {
+            hash: hash.to_string(),
+            at: at.with_timezone(&Local),
+            message: message.to_string(),
+        });
+    }
+    Ok(commits)
+}
+
+/// Puts the data directory back as it was after a commit, and commits that as the operation,
+/// so it can be reverted in turn.
+///
+/// Lists are usually files of a single line, where `git revert` would conflict with any later
+/// change, so the whole directory is restored instead of undoing just that commit.
+pub fn revert(data_dir: &Path, commit: &str, operation: &str) -> Result<(), Error> {
+    ensure_repository(data_dir)?;
+    let revision = format!("{}^{{commit}}", commit);
+    if run(git(data_dir).args(["rev-parse", "--quiet", "--verify", &revision])).is_err() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("there's no commit '{}' in the history", commit),
+        ));
+    }
+    run(git(data_dir).args(["read-tree", "-u", "--reset", &revision]))?;
+    self::commit(data_dir, operation)
+}
+
+fn ensure_repository(data_dir: &Path) -> Result<(), Error> {
+    if data_dir.join(".git").is_dir() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            "there's no history yet, set `git = true` in the config file to keep one",
+        ))
+    }
+}
+
+/// Runs git on the repository of the data directory, and never on a repository it's inside
+/// of.
+fn git(data_dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .arg("--git-dir")
+        .arg(data_dir.join(".git"))
+        .arg("--work-tree")
+        .arg(data_dir);
+    command
+}
+
+/// Runs a git command, turning a failure into an error with what git printed.
+fn run(command: &mut Command) -> Result<Output, Error> {
+    let output = command
+        .output()
+        .map_err(|error| Error::new(error.kind(), format!("failed to run git: {}", error)))?;
+    if output.status.success() {
+        return Ok(output);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(Error::other(format!(
+        "git failed with {}: {}",
+        output.status,
+        stderr.trim()
+    )))
+}
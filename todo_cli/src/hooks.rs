@@ -0,0 +1,91 @@
+use crate::Task;
+use serde::Deserialize;
+use std::{
+    fmt,
+    io::{Error, ErrorKind, Write},
+    process::{Command, Stdio},
+};
+
+/// Commands run when tasks are added, completed or removed, from `[hooks]` in the config file,
+/// like `on-complete = "notify-send Done"`. They're run by the shell, with the task as JSON on
+/// stdin.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Hooks {
+    pub on_add: Option<String>,
+    pub on_complete: Option<String>,
+    /// Run when a task is moved to the trash.
+    pub on_remove: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Event {
+    Add,
+    Complete,
+    Remove,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Event::Add => "add",
+            Event::Complete => "complete",
+            Event::Remove => "remove",
+        })
+    }
+}
+
+impl Hooks {
+    /// Runs the hook of an event for a task of a list, if there's one. The task was saved
+    /// already, so a hook that fails is only warned about.
+    pub fn run(&self, event: Event, list_name: &str, task: &Task) {
+        let hook = match event {
+            Event::Add => &self.on_add,
+            Event::Complete => &self.on_complete,
+            Event::Remove => &self.on_remove,
+        };
+        let Some(hook) = hook.as_deref().filter(|hook| !hook.trim().is_empty()) else {
+            return;
+        };
+        if let Err(error) = run_hook(hook, event, list_name, task) {
+            eprintln!("Couldn't run the on-{} hook: {}", event, error);
+        }
+    }
+}
+
+/// Runs a hook to completion, failing if it exits with an error.
+fn run_hook(hook: &str, event: Event, list_name: &str, task: &Task) -> Result<(), Error> {
+    let json = serde_json::to_string(task)?;
+    let mut child = shell(hook)
+        .env("TODO_HOOK_EVENT", event.to_string())
+        .env("TODO_LIST", list_name)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook doesn't have to read the task.
+        match stdin.write_all(json.as_bytes()) {
+            Err(error) if error.kind() != ErrorKind::BrokenPipe => return Err(error),
+            _ => {}
+        }
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!("'{}' failed with {}", hook, status)))
+    }
+}
+
+#[cfg(unix)]
+fn shell(hook: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.args(["-c", hook]);
+    command
+}
+
+#[cfg(not(unix))]
+fn shell(hook: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.args(["/C", hook]);
+    command
+}
@@ -0,0 +1,148 @@
+use crate::{due::Due, Priority, Task};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+
+/// Lines longer than this many bytes are folded, as iCalendar requires.
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Formats the tasks with due dates as an iCalendar file that calendar apps can subscribe to.
+/// Every task becomes a `VTODO`, and open tasks also a `VEVENT` on their due date, since many
+/// calendars don't show to-dos. Reminders become alarms of both.
+pub fn format_calendar(list_name: &str, tasks: &[&Task]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//todo_cli//EN".to_string(),
+        format!("X-WR-CALNAME:{}", escape(list_name)),
+    ];
+    let now = format_utc(Utc::now());
+    for task in tasks {
+        let Some(due) = task.due else {
+            continue;
+        };
+        let uid = format!("{}@todo_cli", task.uuid);
+
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:{}", uid));
+        lines.push(format!("DTSTAMP:{}", now));
+        lines.push(format!("DUE{}", format_due(due)));
+        push_details(&mut lines, task);
+        if task.completed {
+            lines.push("STATUS:COMPLETED".to_string());
+            if let Some(completed_at) = task.completed_at {
+                lines.push(format!("COMPLETED:{}", format_utc(completed_at.to_utc())));
+            }
+        } else {
+            lines.push("STATUS:NEEDS-ACTION".to_string());
+        }
+        push_alarm(&mut lines, task);
+        lines.push("END:VTODO".to_string());
+
+        if !task.completed {
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!("UID:event-{}", uid));
+            lines.push(format!("DTSTAMP:{}", now));
+            lines.push(format!("DTSTART{}", format_due(due)));
+            push_details(&mut lines, task);
+            push_alarm(&mut lines, task);
+            lines.push("END:VEVENT".to_string());
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+fn push_details(lines: &mut Vec<String>, task: &Task) {
+    lines.push(format!("SUMMARY:{}", escape(&task.title)));
+    if !task.notes.is_empty() {
+        lines.push(format!("DESCRIPTION:{}", escape(&task.notes)));
+    }
+    if let Some(priority) = task.priority {
+        let priority = match priority {
+            Priority::High => 1,
+            Priority::Medium => 5,
+            Priority::Low => 9,
+        };
+        lines.push(format!("PRIORITY:{}", priority));
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|tag| escape(tag)).collect();
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+    if let Some(repeat) = task.repeat {
+        lines.push(format!("RRULE:{}", repeat.to_rrule()));
+    }
+}
+
+fn push_alarm(lines: &mut Vec<String>, task: &Task) {
+    let Some(remind_at) = task
+        .remind_at
+        .and_then(|remind_at| to_utc(remind_at.start()))
+    else {
+        return;
+    };
+    lines.push("BEGIN:VALARM".to_string());
+    lines.push("ACTION:DISPLAY".to_string());
+    lines.push(format!("DESCRIPTION:{}", escape(&task.title)));
+    lines.push(format!("TRIGGER;VALUE=DATE-TIME:{}", format_utc(remind_at)));
+    lines.push("END:VALARM".to_string());
+}
+
+/// The value of `DUE` or `DTSTART`, with its parameters. Times are floating, so they stay at
+/// the same time of day wherever the calendar is.
+fn format_due(due: Due) -> String {
+    match due {
+        Due::At(date_time) => format!(":{}", date_time.format("%Y%m%dT%H%M%S")),
+        Due::On(date) => format!(";VALUE=DATE:{}", date.format("%Y%m%d")),
+    }
+}
+
+fn to_utc(date_time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    let local = date_time.and_local_timezone(Local).earliest()?;
+    Some(local.to_utc())
+}
+
+fn format_utc(date_time: DateTime<Utc>) -> String {
+    date_time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Splits a line into lines of at most [`MAX_LINE_LENGTH`] bytes, continued with a space,
+/// without splitting characters.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut line_length = 0;
+    for c in line.chars() {
+        if line_length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            line_length = 1;
+        }
+        folded.push(c);
+        line_length += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_and_folds_long_lines() {
+        assert_eq!(escape("a, b; c\\d\ne"), "a\\, b\\; c\\\\d\\ne");
+
+        let line = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = fold(&line);
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::storage::{self, Storage, StoredData};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+const JOURNAL_FILE_NAME: &str = "task_journal.json";
+/// Older operations are forgotten, since every entry holds whole lists.
+const MAX_JOURNAL_ENTRIES: usize = 50;
+
+/// What a command changed of the lists it touched, from before it ran.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    at: DateTime<Local>,
+    /// The command line, like `complete 3 5`.
+    operation: String,
+    /// Called `files` in entries recorded before lists could be kept in a database.
+    #[serde(alias = "files")]
+    stored: Vec<StoredData>,
+}
+
+/// Lists as they were before a command, to be recorded if the command changes them.
+pub struct Snapshot {
+    lists: Vec<String>,
+    stored: Vec<StoredData>,
+}
+
+/// Saves the given lists before a command changes them.
+pub fn snapshot(storage: &dyn Storage, lists: &[&str]) -> Result<Snapshot, Error> {
+    let mut stored = Vec::new();
+    for list in lists {
+        stored.extend(storage.snapshot(list)?);
+    }
+    Ok(Snapshot {
+        lists: lists.iter().map(|list| list.to_string()).collect(),
+        stored,
+    })
+}
+
+/// Records an operation if it changed any of the lists of the snapshot, so it can be undone.
+/// The journal is kept in the data directory.
+pub fn record(
+    data_dir: &Path,
+    storage: &dyn Storage,
+    operation: &str,
+    before: Snapshot,
+) -> Result<(), Error> {
+    let lists: Vec<&str> = before.lists.iter().map(String::as_str).collect();
+    let after = snapshot(storage, &lists)?.stored;
+    let changed: Vec<StoredData> = before
+        .stored
+        .into_iter()
+        .filter(|data| !after.contains(data))
+        .collect();
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    let mut entries = load_entries(data_dir)?;
+    entries.push(Entry {
+        at: Local::now(),
+        operation: operation.to_string(),
+        stored: changed,
+    });
+    let excess = entries.len().saturating_sub(MAX_JOURNAL_ENTRIES);
+    entries.drain(..excess);
+    save_entries(data_dir, &entries)
+}
+
+/// Reverts the last `count` recorded operations, newest first, by putting back the lists as
+/// they were before. Returns the reverted operations.
+pub fn undo(data_dir: &Path, storage: &dyn Storage, count: usize) -> Result<Vec<String>, Error> {
+    let mut entries = load_entries(data_dir)?;
+    let mut undone = Vec::new();
+    for _ in 0..count {
+        let Some(entry) = entries.pop() else {
+            break;
+        };
+        for data in entry.stored {
+            storage.restore(data)?;
+        }
+        undone.push(entry.operation);
+    }
+    save_entries(data_dir, &entries)?;
+    Ok(undone)
+}
+
+/// Forgets all recorded operations, like when the lists they kept were encrypted since.
+pub fn clear(data_dir: &Path) -> Result<(), Error> {
+    save_entries(data_dir, &[])
+}
+
+fn load_entries(data_dir: &Path) -> Result<Vec<Entry>, Error> {
+    match fs::read_to_string(data_dir.join(JOURNAL_FILE_NAME)) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(error),
+    }
+}
+
+fn save_entries(data_dir: &Path, entries: &[Entry]) -> Result<(), Error> {
+    storage::write_atomically(
+        &data_dir.join(JOURNAL_FILE_NAME),
+        &serde_json::to_string(entries)?,
+    )
+}
@@ -0,0 +1,1288 @@
+//! The tasks and lists of the todo app, and everything that keeps, shows and exchanges them,
+//! for the `todo` command and for tests.
+
+pub mod agenda;
+pub mod config;
+pub mod csv;
+pub mod due;
+pub mod editor;
+pub mod error;
+pub mod github;
+pub mod history;
+pub mod hooks;
+pub mod ical;
+pub mod journal;
+pub mod markdown;
+pub mod next;
+pub mod notification;
+pub mod passphrase;
+pub mod pomodoro;
+pub mod recurrence;
+pub mod render;
+pub mod shortener;
+pub mod stats;
+pub mod storage;
+pub mod sync;
+pub mod template;
+pub mod timesheet;
+pub mod todotxt;
+pub mod tui;
+pub mod watch;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use clap::{Args as ClapArgs, ValueEnum};
+use common::dates::format_relative;
+use due::{Due, Snooze};
+use recurrence::Recurrence;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::HashMap,
+    fmt,
+    ops::RangeInclusive,
+};
+use timesheet::Session;
+use uuid::Uuid;
+
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Narrows down the tasks shown by `list`. Every given condition has to match.
+#[derive(ClapArgs, Default)]
+pub struct TaskFilter {
+    /// Only show tasks that aren't completed
+    #[arg(long, conflicts_with = "completed")]
+    pub pending: bool,
+    /// Only show completed tasks
+    #[arg(long)]
+    pub completed: bool,
+    /// Only show tasks with this tag, can be repeated to require several tags
+    #[arg(long = "tag", value_name = "tag", value_parser = parse_tag)]
+    pub tags: Vec<String>,
+    /// Only show tasks with this priority
+    #[arg(long, value_enum)]
+    pub priority: Option<Priority>,
+    /// Only show tasks that are due by this date, like tomorrow or "fri 5pm"
+    #[arg(long, value_name = "date", value_parser = due::parse)]
+    pub due_before: Option<Due>,
+    /// Only show tasks with this context, like home for @home, can be repeated to require
+    /// several contexts
+    #[arg(long = "context", value_name = "context", value_parser = parse_context)]
+    pub contexts: Vec<String>,
+    /// Only show snoozed tasks, which are hidden otherwise
+    #[arg(long)]
+    pub snoozed: bool,
+    /// Only show open tasks that are overdue
+    #[arg(long)]
+    pub overdue: bool,
+    /// Only show open tasks that are due by the end of today, overdue ones included
+    #[arg(long)]
+    pub today: bool,
+    /// Only show open tasks that are due by the end of this week, on Sunday
+    #[arg(long)]
+    pub week: bool,
+}
+
+impl TaskFilter {
+    pub fn is_empty(&self) -> bool {
+        !self.pending
+            && !self.completed
+            && self.tags.is_empty()
+            && self.contexts.is_empty()
+            && self.priority.is_none()
+            && self.due_before.is_none()
+            && !self.snoozed
+            && !self.overdue
+            && !self.today
+            && !self.week
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        let now = Local::now().naive_local();
+        // A task due on a whole day is due by the start of the next day.
+        let due_in_time =
+            |before: Due| task.due.is_some_and(|due| due.deadline() <= before.start());
+        let tomorrow = now.date() + chrono::Duration::days(1);
+        let next_week =
+            now.date().week(chrono::Weekday::Mon).last_day() + chrono::Duration::days(1);
+        let open_and_due_by = |end: NaiveDate| !task.completed && due_in_time(Due::On(end));
+        (!self.pending || !task.completed)
+            && (!self.completed || task.completed)
+            && self.tags.iter().all(|tag| task.has_tag(tag))
+            && self
+                .contexts
+                .iter()
+                .all(|context| task.contexts.contains(context))
+            && self
+                .priority
+                .is_none_or(|priority| task.priority == Some(priority))
+            && self.due_before.is_none_or(due_in_time)
+            && self.snoozed == task.is_snoozed(now)
+            && (!self.overdue || task.is_overdue(now))
+            && (!self.today || open_and_due_by(tomorrow))
+            && (!self.week || open_and_due_by(next_week))
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum SortOrder {
+    /// In the order arranged with `move`, new tasks last
+    Manual,
+    /// By ID, which is the order the tasks were added in
+    Id,
+    /// Alphabetically by title, ignoring case
+    Title,
+    /// Most important open tasks first, then tasks without a priority and completed tasks
+    Priority,
+    /// Earliest due first, tasks without a due date last
+    Due,
+    /// Oldest first, tasks from before creation times were recorded last
+    Created,
+    /// Earliest completed first, open tasks last
+    Completed,
+}
+
+impl SortOrder {
+    /// Sorts stably, so tasks that compare equal stay in the order they were added.
+    pub fn sort(self, tasks: &mut [&Task], reverse: bool) {
+        // The tasks of a list are kept in the manual order.
+        if let SortOrder::Manual = self {
+            if reverse {
+                tasks.reverse();
+            }
+            return;
+        }
+        let directed = |ordering: Ordering| {
+            if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+        tasks.sort_by(|a, b| match self {
+            SortOrder::Manual => Ordering::Equal,
+            SortOrder::Id => directed(a.id.cmp(&b.id)),
+            SortOrder::Title => directed(a.title.to_lowercase().cmp(&b.title.to_lowercase())),
+            SortOrder::Priority => a.completed.cmp(&b.completed).then_with(|| {
+                present_first(a.priority.map(Reverse), b.priority.map(Reverse), reverse)
+            }),
+            SortOrder::Due => present_first(
+                a.due.map(|due| due.deadline()),
+                b.due.map(|due| due.deadline()),
+                reverse,
+            ),
+            SortOrder::Created => present_first(a.created_at, b.created_at, reverse),
+            SortOrder::Completed => present_first(a.completed_at, b.completed_at, reverse),
+        });
+    }
+}
+
+/// Compares optional fields, putting tasks without the field last in either direction.
+fn present_first<T: Ord>(a: Option<T>, b: Option<T>, reverse: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if reverse => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Task {
+    pub id: u32,
+    /// Identifies the task wherever lists are merged, where IDs collide, while the ID is the
+    /// short one to type. Tasks loaded without one, from before UUIDs, get a new one.
+    #[serde(default = "Uuid::new_v4")]
+    pub uuid: Uuid,
+    pub title: String,
+    pub completed: bool,
+    /// Left out of the file when unset, so lists stay readable by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<Due>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remind_at: Option<Due>,
+    /// Until when `snooze` hides the task from `list`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<NaiveDateTime>,
+    /// When `notify` last showed a notification for the task, so it's shown only once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notified_at: Option<NaiveDateTime>,
+    /// Completing a recurring task adds its next occurrence, which takes over the rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<Recurrence>,
+    /// The due date the occurrences of a recurring task are counted from, the one of the first
+    /// occurrence, so monthly tasks don't drift to earlier days after shorter months.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_from: Option<Due>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Where the task can be done, like `home` for `@home` in the title it was added with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contexts: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    /// The URL of the issue the task was imported from, so it's imported only once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Web pages attached with `attach`, with the short URLs `shorten` got for them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<Link>,
+    /// The task this one is a subtask of, in the same list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<u32>,
+    /// Tasks of the same list that have to be completed before this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_by: Vec<u32>,
+    /// `None` for tasks added before timestamps were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Local>>,
+    /// When the task was last changed, recorded as lists are saved, so `sync` can tell which
+    /// side has the newer version. `None` for tasks that weren't changed since it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Local>>,
+    /// Periods of work on the task, the last of which may still be running.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sessions: Vec<Session>,
+    /// How long the task is expected to take, in minutes, of which the time spent in
+    /// `sessions` is done.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate_minutes: Option<u32>,
+    /// Where the task is in the order arranged with `move`, lowest first. Tasks with the same
+    /// position, like those of lists from before it was kept, are in the order they were added.
+    #[serde(default)]
+    pub position: u32,
+}
+
+impl Task {
+    pub fn new(id: u32, title: String) -> Self {
+        Task {
+            id,
+            uuid: Uuid::new_v4(),
+            title,
+            completed: false,
+            due: None,
+            priority: None,
+            remind_at: None,
+            snoozed_until: None,
+            notified_at: None,
+            repeat: None,
+            repeat_from: None,
+            tags: Vec::new(),
+            contexts: Vec::new(),
+            notes: String::new(),
+            source: None,
+            links: Vec::new(),
+            parent_id: None,
+            blocked_by: Vec::new(),
+            created_at: Some(Local::now()),
+            completed_at: None,
+            updated_at: None,
+            sessions: Vec::new(),
+            estimate_minutes: None,
+            position: 0,
+        }
+    }
+
+    pub fn append_note(&mut self, text: &str) {
+        if !self.notes.is_empty() {
+            self.notes.push('\n');
+        }
+        self.notes.push_str(text.trim_end());
+    }
+
+    /// Prints every field on a line of its own, unlike the one-line summary of `list`.
+    pub fn print_details(&self, subtask_ids: &[u32]) {
+        let status = if self.completed { "completed" } else { "open" };
+        println!("Task {}: {}", self.id, self.title);
+        println!("UUID:      {}", self.uuid);
+        println!("Status:    {}", status);
+        if let Some(parent_id) = self.parent_id {
+            println!("Parent:    {}", parent_id);
+        }
+        if !subtask_ids.is_empty() {
+            println!("Subtasks:  {}", join_ids(subtask_ids));
+        }
+        if !self.blocked_by.is_empty() {
+            println!("Blockers:  {}", join_ids(&self.blocked_by));
+        }
+        if let Some(priority) = self.priority {
+            println!("Priority:  {}", priority);
+        }
+        if let Some(repeat) = self.repeat {
+            println!("Repeats:   {}", repeat);
+        }
+        if let Some(due) = self.due {
+            println!("Due:       {}", due);
+        }
+        if let Some(remind_at) = self.remind_at {
+            println!("Reminder:  {}", remind_at);
+        }
+        if let Some(snoozed_until) = self.snoozed_until {
+            println!("Snoozed:   until {}", due::format_timestamp(snoozed_until));
+        }
+        if let Some(estimate) = self.estimate() {
+            print!("Estimate:  {}", timesheet::format_duration(estimate));
+            match self.remaining_estimate(Local::now()) {
+                Some(left) if left < estimate => {
+                    println!(", {} left", timesheet::format_duration(left))
+                }
+                _ => println!(),
+            }
+        }
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|tag| format!("#{}", tag)).collect();
+            println!("Tags:      {}", tags.join(" "));
+        }
+        if !self.contexts.is_empty() {
+            let contexts: Vec<String> = self.contexts.iter().map(|c| format!("@{}", c)).collect();
+            println!("Contexts:  {}", contexts.join(" "));
+        }
+        if let Some(source) = &self.source {
+            println!("Source:    {}", source);
+        }
+        if !self.links.is_empty() {
+            println!("Links:");
+            for link in &self.links {
+                match &link.short_url {
+                    Some(short_url) => println!("  {} ({})", short_url, link.url),
+                    None => println!("  {}", link.url),
+                }
+            }
+        }
+        let now = Local::now().naive_local();
+        if let Some(created_at) = self.created_at {
+            let ago = format_relative(created_at.naive_local(), now);
+            println!("Created:   {} ({})", format_timestamp(created_at), ago);
+        }
+        if let Some(completed_at) = self.completed_at {
+            let ago = format_relative(completed_at.naive_local(), now);
+            println!("Completed: {} ({})", format_timestamp(completed_at), ago);
+        }
+        if let Some(updated_at) = self.updated_at {
+            let ago = format_relative(updated_at.naive_local(), now);
+            println!("Updated:   {} ({})", format_timestamp(updated_at), ago);
+        }
+        if !self.sessions.is_empty() {
+            let spent = timesheet::time_spent(&self.sessions, Local::now());
+            print!("Time:      {}", timesheet::format_duration(spent));
+            match self.running_session() {
+                Some(session) => {
+                    println!(", running since {}", format_timestamp(session.started_at))
+                }
+                None => println!(),
+            }
+        }
+        if !self.notes.is_empty() {
+            println!("Notes:");
+            for line in self.notes.lines() {
+                println!("  {}", line);
+            }
+        }
+    }
+
+    /// Matches the title, notes, tags and contexts against a lowercase query.
+    pub fn matches(&self, query: &str, fuzzy: bool) -> bool {
+        let fields = [self.title.to_lowercase(), self.notes.to_lowercase()];
+        let tags = self.tags.iter().chain(&self.contexts);
+        fields
+            .iter()
+            .chain(tags)
+            .any(|field| field.contains(query) || (fuzzy && contains_in_order(field, query)))
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|existing| existing == tag)
+    }
+
+    /// Returns `false` if the task already had the tag.
+    pub fn add_tag(&mut self, tag: String) -> bool {
+        if self.has_tag(&tag) {
+            return false;
+        }
+        self.tags.push(tag);
+        true
+    }
+
+    /// Returns `false` if the task didn't have the tag.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let tag_count = self.tags.len();
+        self.tags.retain(|existing| existing != tag);
+        self.tags.len() != tag_count
+    }
+
+    /// Returns `false` if the link was already attached.
+    pub fn add_link(&mut self, url: String) -> bool {
+        if self.links.iter().any(|link| link.url == url) {
+            return false;
+        }
+        self.links.push(Link {
+            url,
+            short_url: None,
+        });
+        true
+    }
+
+    /// Removes the link with the URL, or the short URL, given. Returns `false` if the task
+    /// didn't have it.
+    pub fn remove_link(&mut self, url: &str) -> bool {
+        let link_count = self.links.len();
+        self.links
+            .retain(|link| link.url != url && link.short_url.as_deref() != Some(url));
+        self.links.len() != link_count
+    }
+
+    /// Moves the `@context` words of the title into the contexts. A title of nothing but
+    /// contexts is left as it is, since it needs some words.
+    pub fn take_contexts(&mut self) {
+        let (contexts, words): (Vec<&str>, Vec<&str>) = self
+            .title
+            .split_whitespace()
+            .partition(|word| word.len() > 1 && word.starts_with('@'));
+        if contexts.is_empty() || words.is_empty() {
+            return;
+        }
+        for context in contexts
+            .into_iter()
+            .filter_map(|word| parse_context(word).ok())
+        {
+            if !self.contexts.contains(&context) {
+                self.contexts.push(context);
+            }
+        }
+        self.title = words.join(" ");
+    }
+
+    /// Whether the task is hidden from `list` by `snooze`, which ends once it's completed.
+    pub fn is_snoozed(&self, now: NaiveDateTime) -> bool {
+        !self.completed && self.snoozed_until.is_some_and(|until| until > now)
+    }
+
+    /// Hides the task until the snooze ends, and moves its due date, if it has one, to then, or
+    /// as much later as it's snoozed for. Returns when the snooze ends.
+    pub fn snooze(&mut self, snooze: Snooze, now: NaiveDateTime) -> NaiveDateTime {
+        let until = match snooze {
+            Snooze::For(duration) => {
+                self.due = self.due.map(|due| due.shifted(duration));
+                now + duration
+            }
+            Snooze::Until(until) => {
+                self.due = self.due.map(|due| {
+                    if due.start() < until.start() {
+                        until
+                    } else {
+                        due
+                    }
+                });
+                until.start()
+            }
+        };
+        self.snoozed_until = Some(until);
+        until
+    }
+
+    pub fn is_overdue(&self, now: NaiveDateTime) -> bool {
+        !self.completed && self.due.is_some_and(|due| due.deadline() < now)
+    }
+
+    /// The text of a notification about the task, if it became due or its reminder time
+    /// passed since it was last notified.
+    pub fn pending_notification(&self, now: NaiveDateTime) -> Option<String> {
+        if self.completed {
+            return None;
+        }
+        let is_new = |moment: NaiveDateTime| {
+            moment <= now
+                && self
+                    .notified_at
+                    .is_none_or(|notified_at| moment > notified_at)
+        };
+        let due = self.due.filter(|due| is_new(due.start()));
+        let reminder = self.remind_at.filter(|remind_at| is_new(remind_at.start()));
+        match (due, reminder) {
+            (Some(due), _) if self.is_overdue(now) => Some(format!("Overdue, was due {}", due)),
+            (Some(due), _) => Some(format!("Due {}", due)),
+            (None, Some(_)) => Some(match self.due {
+                Some(due) => format!("Reminder, due {}", due),
+                None => "Reminder".to_string(),
+            }),
+            (None, None) => None,
+        }
+    }
+
+    fn mark_completed(&mut self) {
+        if !self.completed {
+            self.completed = true;
+            self.completed_at = Some(Local::now());
+            self.stop_session(Local::now());
+        }
+    }
+
+    pub fn running_session(&self) -> Option<&Session> {
+        self.sessions.last().filter(|session| session.is_running())
+    }
+
+    /// Stops the running session, returning how long it took.
+    pub fn stop_session(&mut self, now: DateTime<Local>) -> Option<chrono::Duration> {
+        let session = self
+            .sessions
+            .last_mut()
+            .filter(|session| session.is_running())?;
+        session.stopped_at = Some(now);
+        Some(session.duration(now))
+    }
+
+    pub fn estimate(&self) -> Option<chrono::Duration> {
+        self.estimate_minutes
+            .map(|minutes| chrono::Duration::minutes(minutes.into()))
+    }
+
+    pub fn set_estimate(&mut self, estimate: chrono::Duration) {
+        self.estimate_minutes = u32::try_from(estimate.num_minutes()).ok();
+    }
+
+    /// What's left of the estimate of an open task after the time spent on it, which is
+    /// nothing once more time was spent than estimated.
+    pub fn remaining_estimate(&self, now: DateTime<Local>) -> Option<chrono::Duration> {
+        let estimate = self.estimate().filter(|_| !self.completed)?;
+        let spent = timesheet::time_spent(&self.sessions, now);
+        Some((estimate - spent).max(chrono::Duration::zero()))
+    }
+
+    pub fn mark_uncompleted(&mut self) {
+        self.completed = false;
+        self.completed_at = None;
+    }
+}
+
+/// A web page attached to a task.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Link {
+    pub url: String,
+    /// The URL that redirects to `url`, from a URL shortener.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub short_url: Option<String>,
+}
+
+/// What [`TaskList::complete_tasks`] did.
+pub struct Completion {
+    pub completed: Vec<u32>,
+    /// Why the tasks that weren't completed weren't.
+    pub refusals: Vec<String>,
+    /// The IDs of the tasks added for recurring tasks.
+    pub next_occurrences: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrashedTask {
+    pub removed_at: DateTime<Local>,
+    #[serde(flatten)]
+    pub task: Task,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskList {
+    /// The version of the file format, which older files are upgraded from when they're loaded.
+    #[serde(default)]
+    pub version: u32,
+    pub tasks: Vec<Task>,
+    pub next_task_id: u32,
+    /// Removed tasks, kept until they're restored or purged.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trash: Vec<TrashedTask>,
+}
+
+impl Default for TaskList {
+    fn default() -> Self {
+        TaskList::new()
+    }
+}
+
+impl TaskList {
+    pub fn new() -> Self {
+        TaskList {
+            version: storage::SCHEMA_VERSION,
+            tasks: Vec::new(),
+            next_task_id: 1,
+            trash: Vec::new(),
+        }
+    }
+
+    pub fn add_task(&mut self, title: String) -> &mut Task {
+        let task = Task::new(self.next_task_id, title);
+        self.insert_task(task);
+        self.tasks.last_mut().unwrap()
+    }
+
+    /// Appends an existing task to the list, assigning it the next free ID and taking the
+    /// contexts out of its title.
+    pub fn insert_task(&mut self, mut task: Task) -> u32 {
+        let id = self.next_task_id;
+        task.id = id;
+        task.take_contexts();
+        task.position = self.tasks.iter().map(|t| t.position + 1).max().unwrap_or(0);
+        self.tasks.push(task);
+        self.next_task_id += 1;
+        id
+    }
+
+    /// Removes a task. Its subtasks stay in the list, moving up to the parent of the removed task,
+    /// and the tasks it blocked aren't blocked by it anymore.
+    pub fn remove_task(&mut self, id: u32) -> Option<Task> {
+        let index = self
+            .tasks
+            .iter()
+            .enumerate()
+            .find(|(_, task)| task.id == id)
+            .map(|(index, _)| index);
+
+        let removed = index.map(|index| self.tasks.remove(index))?;
+        for task in &mut self.tasks {
+            if task.parent_id == Some(id) {
+                task.parent_id = removed.parent_id;
+            }
+            task.blocked_by.retain(|&blocker| blocker != id);
+        }
+        Some(removed)
+    }
+
+    /// Moves a task to the trash, returning `false` if it doesn't exist.
+    pub fn trash_task(&mut self, id: u32) -> bool {
+        let Some(task) = self.remove_task(id) else {
+            return false;
+        };
+        self.trash.push(TrashedTask {
+            removed_at: Local::now(),
+            task,
+        });
+        true
+    }
+
+    /// Records that the tasks that are new or differ from the stored ones were updated now,
+    /// unless their `updated_at` was changed already, like for tasks taken over by `sync`.
+    fn stamp_updates(&mut self, stored: Option<&TaskList>, now: DateTime<Local>) {
+        let stored: HashMap<Uuid, &Task> = stored
+            .iter()
+            .flat_map(|list| &list.tasks)
+            .map(|task| (task.uuid, task))
+            .collect();
+        for task in &mut self.tasks {
+            let changed = match stored.get(&task.uuid) {
+                Some(stored) => task.updated_at == stored.updated_at && task != *stored,
+                None => task.updated_at.is_none(),
+            };
+            if changed {
+                task.updated_at = Some(now);
+            }
+        }
+    }
+
+    /// Gives the tasks the IDs 1 to n in the order of their IDs, followed by the trashed and
+    /// the archived tasks, which can't share IDs with them, and updates the parents and
+    /// blockers. Returns the old and new IDs of the tasks whose ID changed.
+    pub fn renumber(&mut self, archive: &mut TaskList) -> Vec<(u32, u32)> {
+        let sorted = |mut ids: Vec<u32>| {
+            ids.sort();
+            ids
+        };
+        let mut old_ids = sorted(self.tasks.iter().map(|task| task.id).collect());
+        old_ids.extend(sorted(self.trash.iter().map(|t| t.task.id).collect()));
+        old_ids.extend(sorted(archive.tasks.iter().map(|task| task.id).collect()));
+        let new_ids: HashMap<u32, u32> = old_ids.iter().copied().zip(1..).collect();
+
+        let trashed = self.trash.iter_mut().map(|trashed| &mut trashed.task);
+        for task in self
+            .tasks
+            .iter_mut()
+            .chain(trashed)
+            .chain(&mut archive.tasks)
+        {
+            task.id = new_ids[&task.id];
+            // Parents and blockers that were purged from the trash are gone for good.
+            task.parent_id = task.parent_id.and_then(|id| new_ids.get(&id).copied());
+            let blocked_by = task.blocked_by.iter().filter_map(|id| new_ids.get(id));
+            task.blocked_by = blocked_by.copied().collect();
+        }
+        self.next_task_id = old_ids.len() as u32 + 1;
+        archive.next_task_id = self.next_task_id;
+        old_ids
+            .into_iter()
+            .zip(1..)
+            .filter(|(old_id, new_id)| old_id != new_id)
+            .collect()
+    }
+
+    /// Moves a task back from the trash, returning `false` if it isn't there. Its parent and
+    /// blockers are kept as far as they still exist, but removing it unblocked other tasks
+    /// for good.
+    pub fn restore_task(&mut self, id: u32) -> bool {
+        let Some(index) = self.trash.iter().position(|trashed| trashed.task.id == id) else {
+            return false;
+        };
+        let mut task = self.trash.remove(index).task;
+        task.parent_id = task
+            .parent_id
+            .filter(|&parent| self.find_task(parent).is_some());
+        task.blocked_by
+            .retain(|&blocker| self.find_task(blocker).is_some());
+        self.tasks.push(task);
+        self.tasks.sort_by_key(|task| task.position);
+        true
+    }
+
+    /// Moves a task in the manual order, in front of another task or to the top, and numbers
+    /// the positions of all tasks anew. Both tasks have to be in the list.
+    pub fn reorder_task(&mut self, id: u32, before: Option<u32>) {
+        let Some(index) = self.tasks.iter().position(|task| task.id == id) else {
+            return;
+        };
+        let task = self.tasks.remove(index);
+        let target = before.and_then(|before| self.tasks.iter().position(|t| t.id == before));
+        self.tasks.insert(target.unwrap_or(0), task);
+        for (position, task) in self.tasks.iter_mut().enumerate() {
+            task.position = position as u32;
+        }
+    }
+
+    /// Deletes tasks that were removed more than `days` days ago, returning how many.
+    pub fn purge_trash(&mut self, days: u32) -> usize {
+        let cutoff = Local::now() - chrono::Duration::days(days.into());
+        let trash_size = self.trash.len();
+        self.trash.retain(|trashed| trashed.removed_at >= cutoff);
+        trash_size - self.trash.len()
+    }
+
+    pub fn subtasks(&self, id: u32) -> impl Iterator<Item = &Task> {
+        self.tasks
+            .iter()
+            .filter(move |task| task.parent_id == Some(id))
+    }
+
+    pub fn find_task(&self, id: u32) -> Option<&Task> {
+        self.tasks.iter().find(|task| task.id == id)
+    }
+
+    pub fn get_task(&mut self, id: u32) -> Option<&mut Task> {
+        self.tasks.iter_mut().find(|task| task.id == id)
+    }
+
+    pub fn list_tasks(&self) -> &Vec<Task> {
+        &self.tasks
+    }
+
+    /// The blockers of a task that aren't completed yet.
+    pub fn open_blockers(&self, task: &Task) -> Vec<u32> {
+        task.blocked_by
+            .iter()
+            .copied()
+            .filter(|&blocker| self.find_task(blocker).is_some_and(|t| !t.completed))
+            .collect()
+    }
+
+    pub fn overdue_count(&self, now: NaiveDateTime) -> usize {
+        self.tasks
+            .iter()
+            .filter(|task| task.is_overdue(now))
+            .count()
+    }
+
+    /// The tasks completed more than `days` days ago. Tasks completed before completion times
+    /// were recorded never count.
+    pub fn completed_before(&self, days: u32) -> Vec<u32> {
+        let cutoff = Local::now() - chrono::Duration::days(days.into());
+        self.tasks
+            .iter()
+            .filter(|task| {
+                task.completed_at
+                    .is_some_and(|completed_at| completed_at < cutoff)
+            })
+            .map(|task| task.id)
+            .collect()
+    }
+
+    /// Splits the IDs selected by ranges into those of existing tasks and those not found, each
+    /// in the order given and without duplicates.
+    pub fn select_ids(&self, ranges: &[RangeInclusive<u32>]) -> (Vec<u32>, Vec<u32>) {
+        let mut found = Vec::new();
+        let mut missing = Vec::new();
+        for id in ranges.iter().cloned().flatten() {
+            let ids = if self.find_task(id).is_some() {
+                &mut found
+            } else {
+                &mut missing
+            };
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        (found, missing)
+    }
+
+    /// Completes tasks unless they have open subtasks or blockers, where tasks completed
+    /// together count as completed, and adds the next occurrences of recurring tasks.
+    pub fn complete_tasks(&mut self, ids: &[u32], force: bool) -> Completion {
+        let mut completing = ids.to_vec();
+        let mut refusals = Vec::new();
+        // Refusing a task can leave the tasks waiting for it with an open subtask or blocker,
+        // so this repeats until nothing else is refused.
+        let next_refusal = |completing: &[u32]| {
+            let refusal = |&id| Some((id, self.completion_refusal(id, completing)?));
+            completing.iter().find_map(refusal)
+        };
+        if !force {
+            while let Some((id, reason)) = next_refusal(&completing) {
+                completing.retain(|&other| other != id);
+                refusals.push(reason);
+            }
+        }
+
+        let mut next_occurrences = Vec::new();
+        for &id in &completing {
+            if let Some(task) = self.get_task(id) {
+                task.mark_completed();
+                if let Some(id) = self.add_next_occurrence(id) {
+                    next_occurrences.push(id);
+                }
+            }
+        }
+        Completion {
+            completed: completing,
+            refusals,
+            next_occurrences,
+        }
+    }
+
+    /// Adds the next occurrence of a recurring task, which gets its rule, and returns its ID.
+    fn add_next_occurrence(&mut self, id: u32) -> Option<u32> {
+        let task = self.get_task(id)?;
+        let repeat = task.repeat.take()?;
+        let mut next = Task::new(0, task.title.clone());
+        let today = Local::now().date_naive();
+        let anchor = repeat.anchor(task.repeat_from.take(), task.due, today);
+        next.due = Some(repeat.next_due(anchor, task.due, today));
+        next.priority = task.priority;
+        next.repeat = Some(repeat);
+        next.repeat_from = Some(anchor);
+        next.tags = task.tags.clone();
+        next.contexts = task.contexts.clone();
+        next.notes = task.notes.clone();
+        next.estimate_minutes = task.estimate_minutes;
+        next.parent_id = task.parent_id;
+        Some(self.insert_task(next))
+    }
+
+    /// Why a task can't be completed together with the `completing` ones, if it can't.
+    fn completion_refusal(&self, id: u32, completing: &[u32]) -> Option<String> {
+        let is_open = |task: &Task| !task.completed && !completing.contains(&task.id);
+        let open_subtasks = self.subtasks(id).filter(|t| is_open(t)).count();
+        if open_subtasks > 0 {
+            return Some(format!("Task {} has {} open subtask(s)", id, open_subtasks));
+        }
+        let open_blockers: Vec<u32> = self
+            .find_task(id)?
+            .blocked_by
+            .iter()
+            .copied()
+            .filter(|&blocker| self.find_task(blocker).is_some_and(is_open))
+            .collect();
+        if open_blockers.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "Task {} is blocked by {}",
+                id,
+                join_ids(&open_blockers)
+            ))
+        }
+    }
+
+    /// Whether `id` has to wait for `other`, directly or through the tasks blocking it.
+    pub fn depends_on(&self, id: u32, other: u32) -> bool {
+        let mut pending = vec![id];
+        let mut visited = Vec::new();
+        while let Some(current) = pending.pop() {
+            if current == other {
+                return true;
+            }
+            if visited.contains(&current) {
+                continue;
+            }
+            visited.push(current);
+            if let Some(task) = self.find_task(current) {
+                pending.extend(&task.blocked_by);
+            }
+        }
+        false
+    }
+
+    pub fn filtered_tasks(
+        &self,
+        filter: &TaskFilter,
+        sort: SortOrder,
+        reverse: bool,
+    ) -> Vec<&Task> {
+        let mut tasks: Vec<&Task> = self.tasks.iter().filter(|t| filter.matches(t)).collect();
+        sort.sort(&mut tasks, reverse);
+        tasks
+    }
+}
+
+/// Whether all characters of `query`, except whitespace, appear in `text` in the same order.
+fn contains_in_order(text: &str, query: &str) -> bool {
+    let mut text = text.chars();
+    query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|c| text.any(|other| other == c))
+}
+
+pub fn join_ids(ids: &[u32]) -> String {
+    let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+    ids.join(", ")
+}
+
+/// The estimated effort left for the open tasks, or `None` if none of them has an estimate.
+pub fn remaining_estimate(tasks: &[&Task]) -> Option<chrono::Duration> {
+    let now = Local::now();
+    tasks
+        .iter()
+        .filter_map(|task| task.remaining_estimate(now))
+        .reduce(|total, left| total + left)
+}
+
+pub fn format_timestamp(time: DateTime<Local>) -> String {
+    due::format_timestamp(time.naive_local())
+}
+
+impl Task {
+    /// The start of a task's line, like `[✓] 1: Buy milk`.
+    fn heading(&self) -> String {
+        let completion_symbol = if self.completed { "✓" } else { " " };
+        format!("[{}] {}: {}", completion_symbol, self.id, self.title)
+    }
+
+    /// What a task's line shows after its priority: due date, recurrence, contexts and tags.
+    fn annotations(&self) -> String {
+        let mut annotations = String::new();
+        if let Some(due) = self.due {
+            annotations.push_str(&format!(" (due {})", due));
+        }
+        if let Some(repeat) = self.repeat {
+            annotations.push_str(&format!(" (repeats {})", repeat));
+        }
+        if let Some(estimate) = self.estimate() {
+            let estimate = timesheet::format_duration(estimate);
+            annotations.push_str(&format!(" (estimate {})", estimate));
+        }
+        for context in &self.contexts {
+            annotations.push_str(&format!(" @{}", context));
+        }
+        for tag in &self.tags {
+            annotations.push_str(&format!(" #{}", tag));
+        }
+        annotations
+    }
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.heading())?;
+        if let Some(priority) = self.priority {
+            write!(f, " [{}]", priority)?;
+        }
+        write!(f, "{}", self.annotations())
+    }
+}
+
+/// Links are web pages, so only `http` and `https` URLs are taken.
+pub fn parse_link(url: &str) -> Result<String, String> {
+    match reqwest::Url::parse(url.trim()) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(parsed.to_string()),
+        _ => Err(format!("expected an http or https URL, not '{}'", url)),
+    }
+}
+
+/// Tags are matched exactly, so they're kept to single lowercase words.
+pub fn parse_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().trim_start_matches('#').to_lowercase();
+    if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err("tags can't be empty or contain spaces or commas".to_string());
+    }
+    Ok(tag)
+}
+
+/// Contexts are matched like tags, with or without the `@` they're written with in titles.
+pub fn parse_context(context: &str) -> Result<String, String> {
+    let context = context.trim().trim_start_matches('@').to_lowercase();
+    if context.is_empty() || context.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err("contexts can't be empty or contain spaces or commas".to_string());
+    }
+    Ok(context)
+}
+
+/// Parses a line of `add --from-file` or stdin into a task without an ID, or `None` if the line
+/// has no title. Words like `#tag` are tags and `!high`, `!medium` or `!low` the priority, the rest
+/// is the title.
+pub fn parse_task_line(line: &str) -> Option<Task> {
+    let mut task = Task::new(0, String::new());
+    let mut title = Vec::new();
+    for word in line.split_whitespace() {
+        let priority = word
+            .strip_prefix('!')
+            .and_then(|name| Priority::from_str(name, true).ok());
+        let tag = word.strip_prefix('#').and_then(|tag| parse_tag(tag).ok());
+        if let Some(priority) = priority {
+            task.priority = Some(priority);
+        } else if let Some(tag) = tag {
+            task.add_tag(tag);
+        } else {
+            title.push(word);
+        }
+    }
+    if title.is_empty() {
+        return None;
+    }
+    task.title = title.join(" ");
+    Some(task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn allocates_ids_that_are_never_reused() {
+        let mut task_list = TaskList::new();
+        assert_eq!(task_list.add_task("a".to_string()).id, 1);
+        assert_eq!(task_list.add_task("b".to_string()).id, 2);
+        assert!(task_list.trash_task(2));
+        assert_eq!(task_list.add_task("c".to_string()).id, 3);
+
+        let id = task_list.insert_task(Task::new(42, "Call mom @phone".to_string()));
+        assert_eq!(id, 4);
+        assert_eq!(task_list.next_task_id, 5);
+        let task = task_list.find_task(4).unwrap();
+        assert_eq!(task.title, "Call mom");
+        assert_eq!(task.contexts, ["phone"]);
+        // New tasks come last in the manual order.
+        assert_eq!(task.position, 2);
+    }
+
+    #[test]
+    fn removes_and_restores_tasks() {
+        let mut task_list = TaskList::new();
+        task_list.add_task("parent".to_string());
+        task_list.add_task("child".to_string()).parent_id = Some(1);
+        task_list.add_task("blocked".to_string()).blocked_by = vec![1];
+
+        assert!(task_list.trash_task(1));
+        assert!(!task_list.trash_task(1));
+        assert!(task_list.find_task(1).is_none());
+        assert_eq!(task_list.trash[0].task.title, "parent");
+        // The subtask moves up, and the blocked task isn't blocked anymore.
+        assert_eq!(task_list.find_task(2).unwrap().parent_id, None);
+        assert!(task_list.find_task(3).unwrap().blocked_by.is_empty());
+
+        assert!(task_list.restore_task(1));
+        assert!(!task_list.restore_task(1));
+        let ids: Vec<u32> = task_list.tasks.iter().map(|task| task.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+        assert!(task_list.trash.is_empty());
+    }
+
+    #[test]
+    fn completes_tasks_without_open_subtasks_or_blockers() {
+        let mut task_list = TaskList::new();
+        task_list.add_task("parent".to_string());
+        task_list.add_task("child".to_string()).parent_id = Some(1);
+        task_list.add_task("blocked".to_string()).blocked_by = vec![2];
+
+        let completion = task_list.complete_tasks(&[1, 3], false);
+        assert!(completion.completed.is_empty());
+        assert_eq!(
+            completion.refusals,
+            ["Task 1 has 1 open subtask(s)", "Task 3 is blocked by 2"]
+        );
+
+        // Tasks completed together count as completed.
+        let completion = task_list.complete_tasks(&[1, 2, 3], false);
+        assert_eq!(completion.completed, [1, 2, 3]);
+        assert!(task_list.tasks.iter().all(|task| task.completed));
+        assert!(task_list
+            .tasks
+            .iter()
+            .all(|task| task.completed_at.is_some()));
+
+        let mut task_list = TaskList::new();
+        task_list.add_task("parent".to_string());
+        task_list.add_task("child".to_string()).parent_id = Some(1);
+        assert_eq!(task_list.complete_tasks(&[1], true).completed, [1]);
+    }
+
+    #[test]
+    fn completing_a_recurring_task_adds_its_next_occurrence() {
+        let mut task_list = TaskList::new();
+        let task = task_list.add_task("water plants".to_string());
+        task.repeat = Some("weekly".parse().unwrap());
+        task.add_tag("home".to_string());
+
+        let completion = task_list.complete_tasks(&[1], false);
+        assert_eq!(completion.next_occurrences, [2]);
+        let next = task_list.find_task(2).unwrap();
+        assert!(!next.completed);
+        assert!(next.due.is_some());
+        assert_eq!(next.tags, ["home"]);
+        assert!(next.repeat.is_some());
+        assert!(task_list.find_task(1).unwrap().repeat.is_none());
+    }
+
+    #[test]
+    fn finds_tasks_waiting_for_others() {
+        let mut task_list = TaskList::new();
+        for title in ["one", "two", "three", "four", "five"] {
+            task_list.add_task(title.to_string());
+        }
+        // 2 waits for 5, so blocking 5 on 2 would be a cycle.
+        task_list.get_task(2).unwrap().blocked_by = vec![5];
+        assert!(task_list.depends_on(2, 5));
+        assert!(!task_list.depends_on(5, 2));
+
+        // 2 waits for 5 through 3.
+        task_list.get_task(2).unwrap().blocked_by = vec![3];
+        task_list.get_task(3).unwrap().blocked_by = vec![5];
+        assert!(task_list.depends_on(2, 5));
+        assert!(task_list.depends_on(2, 3));
+        assert!(!task_list.depends_on(5, 2));
+        assert!(!task_list.depends_on(2, 4));
+    }
+
+    #[test]
+    fn filters_and_sorts_tasks() {
+        let mut task_list = TaskList::new();
+        let task = task_list.add_task("Buy milk".to_string());
+        task.add_tag("shop".to_string());
+        task.priority = Some(Priority::Low);
+        let task = task_list.add_task("Buy bread".to_string());
+        task.add_tag("shop".to_string());
+        task.priority = Some(Priority::High);
+        task_list.add_task("Call mom".to_string()).completed = true;
+        let overdue = Local::now().naive_local() - Duration::days(1);
+        task_list.add_task("Pay rent".to_string()).due = Some(Due::At(overdue));
+
+        let titles = |filter: &TaskFilter, sort: SortOrder, reverse: bool| -> Vec<String> {
+            let tasks = task_list.filtered_tasks(filter, sort, reverse);
+            tasks.iter().map(|task| task.title.clone()).collect()
+        };
+        let all = TaskFilter::default();
+        assert!(all.is_empty());
+        assert_eq!(titles(&all, SortOrder::Manual, false).len(), 4);
+        assert_eq!(
+            titles(&all, SortOrder::Title, false),
+            ["Buy bread", "Buy milk", "Call mom", "Pay rent"]
+        );
+        assert_eq!(
+            titles(&all, SortOrder::Priority, false),
+            ["Buy bread", "Buy milk", "Pay rent", "Call mom"]
+        );
+        // Tasks without a due date stay last either way.
+        assert_eq!(titles(&all, SortOrder::Due, true)[0], "Pay rent");
+
+        let shopping = TaskFilter {
+            tags: vec!["shop".to_string()],
+            priority: Some(Priority::High),
+            ..Default::default()
+        };
+        assert!(!shopping.is_empty());
+        assert_eq!(titles(&shopping, SortOrder::Manual, false), ["Buy bread"]);
+        let pending = TaskFilter {
+            pending: true,
+            ..Default::default()
+        };
+        assert_eq!(titles(&pending, SortOrder::Id, true).len(), 3);
+        let overdue = TaskFilter {
+            overdue: true,
+            ..Default::default()
+        };
+        assert_eq!(titles(&overdue, SortOrder::Manual, false), ["Pay rent"]);
+    }
+
+    #[test]
+    fn round_trips_lists_through_json() {
+        let mut task_list = TaskList::new();
+        let task = task_list.add_task("Buy milk @shop".to_string());
+        task.take_contexts();
+        task.add_tag("errand".to_string());
+        task.priority = Some(Priority::Medium);
+        task.due = Some(Due::On(NaiveDate::from_ymd_opt(2024, 5, 17).unwrap()));
+        task.append_note("2 liters");
+        task.set_estimate(Duration::minutes(90));
+        task_list.add_task("Call mom".to_string()).blocked_by = vec![1];
+        task_list.trash_task(2);
+
+        let json = serde_json::to_string(&task_list).unwrap();
+        let loaded: TaskList = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.tasks, task_list.tasks);
+        assert_eq!(loaded.trash, task_list.trash);
+        assert_eq!(loaded.next_task_id, task_list.next_task_id);
+        assert_eq!(loaded.version, task_list.version);
+
+        // Unset fields are left out of the file.
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let fields = value["tasks"][0].as_object().unwrap();
+        assert!(!fields.contains_key("remind_at"));
+        assert!(!fields.contains_key("sessions"));
+        assert_eq!(fields["priority"], "medium");
+        assert_eq!(fields["estimate_minutes"], 90);
+    }
+
+    #[test]
+    fn parses_tags_and_task_lines() {
+        assert_eq!(parse_tag(" #Work ").unwrap(), "work");
+        assert!(parse_tag("two words").is_err());
+        assert!(parse_context("@").is_err());
+
+        let task = parse_task_line("Buy milk #shop !high").unwrap();
+        assert_eq!(task.title, "Buy milk");
+        assert_eq!(task.tags, ["shop"]);
+        assert_eq!(task.priority, Some(Priority::High));
+        assert!(parse_task_line("   ").is_none());
+    }
+
+    #[test]
+    fn attaches_and_detaches_links() {
+        assert_eq!(
+            parse_link(" https://example.com/a?b=c ").unwrap(),
+            "https://example.com/a?b=c"
+        );
+        assert!(parse_link("ftp://example.com/file").is_err());
+        assert!(parse_link("example.com").is_err());
+
+        let mut task = Task::new(1, "Read the article".to_string());
+        assert!(task.add_link("https://example.com/a".to_string()));
+        assert!(!task.add_link("https://example.com/a".to_string()));
+        task.links[0].short_url = Some("http://localhost:3000/abc".to_string());
+        // Links can be removed by their short URL too.
+        assert!(task.remove_link("http://localhost:3000/abc"));
+        assert!(!task.remove_link("https://example.com/a"));
+        assert!(task.links.is_empty());
+    }
+}
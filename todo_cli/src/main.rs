@@ -1,10 +1,78 @@
-use clap::{Parser, Subcommand};
-use serde::{Deserialize, Serialize};
-use std::{fs, io::Error};
+mod alias;
+mod completions;
+
+use chrono::{Datelike, Local, NaiveDate};
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+use common::error::with_path;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, Error, ErrorKind, IsTerminal, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+use todo_cli::{
+    agenda,
+    config::{self, Config, StorageBackend},
+    csv,
+    due::{self, Due, Snooze},
+    editor,
+    error::TodoError,
+    format_timestamp, github, history, hooks, ical, join_ids, journal, markdown, next,
+    notification, parse_link, parse_tag, parse_task_line, pomodoro,
+    recurrence::Recurrence,
+    remaining_estimate,
+    render::Renderer,
+    shortener::Shortener,
+    stats,
+    storage::{self, JsonFiles, SqliteDatabase, Storage},
+    sync,
+    timesheet::{self, Session},
+    todotxt, tui, watch, Priority, SortOrder, Task, TaskFilter, TaskList,
+};
+use uuid::Uuid;
 
 #[derive(Parser)]
 #[command()]
 struct Args {
+    /// Name of the task list to operate on, `default_list` of the config file or `default`
+    /// if not given
+    #[arg(long, global = true, value_name = "list-name", env = "TODO_LIST")]
+    list: Option<String>,
+    /// Archive tasks completed more than this many days ago before running the command
+    #[arg(
+        long,
+        global = true,
+        value_name = "days",
+        env = "TODO_AUTO_ARCHIVE_DAYS"
+    )]
+    auto_archive_days: Option<u32>,
+    /// Days after which removed tasks are purged from the trash
+    #[arg(
+        long,
+        global = true,
+        value_name = "days",
+        env = "TODO_TRASH_RETENTION_DAYS",
+        default_value_t = DEFAULT_TRASH_RETENTION_DAYS
+    )]
+    trash_retention_days: u32,
+    /// Print JSON instead of text, like `--format json`, with messages on stderr
+    #[arg(long, global = true)]
+    json: bool,
+    /// Print without colors, whatever the config file says
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Encrypt the list files with a passphrase, like `encrypt = true` in the config file
+    #[arg(long, global = true)]
+    encrypt: bool,
+    /// File of the default list, or the database, with the other files next to it, instead
+    /// of the data directory. `TODO_FILE` if not given
+    #[arg(long = "file", global = true, value_name = "path")]
+    list_file: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -12,132 +80,1935 @@ struct Args {
 #[derive(Subcommand)]
 enum Commands {
     /// Adds a new task
+    #[command(visible_alias = "a")]
     Add {
-        /// Title of the task to be created
-        #[arg(value_name = "task-title")]
-        title: String,
+        /// Title of the task to be created, or - to add a task for every line of stdin
+        #[arg(
+            value_name = "task-title",
+            required_unless_present_any = ["edit", "from_file", "template"]
+        )]
+        title: Option<String>,
+        /// When the task is due, like 2024-05-17, tomorrow or "fri 5pm"
+        #[arg(long, value_name = "date", value_parser = due::parse)]
+        due: Option<Due>,
+        /// How important the task is
+        #[arg(long, value_enum)]
+        priority: Option<Priority>,
+        /// Tag to attach to the task, can be repeated
+        #[arg(long = "tag", value_name = "tag", value_parser = parse_tag)]
+        tags: Vec<String>,
+        /// Longer description of the task
+        #[arg(long, value_name = "text")]
+        note: Option<String>,
+        /// ID of the task this one is a subtask of
+        #[arg(long, value_name = "task-id")]
+        parent: Option<u32>,
+        /// How often the task comes back once completed, like weekly or "every 3 days"
+        #[arg(long, value_name = "rule")]
+        repeat: Option<Recurrence>,
+        /// How long the task is expected to take, like 45m or 1h30m
+        #[arg(long, value_name = "duration", value_parser = timesheet::parse_duration)]
+        estimate: Option<chrono::Duration>,
+        /// Write the task in $EDITOR, starting from the given title and options
+        #[arg(long, conflicts_with = "from_file")]
+        edit: bool,
+        /// Add a task for every line of a file, where words like #tag and !high set tags and
+        /// priorities, in addition to the options
+        #[arg(long, value_name = "file", conflicts_with = "title")]
+        from_file: Option<PathBuf>,
+        /// Add the task, and its subtasks, from a template of the config file, with the given
+        /// title filling in its {title}
+        #[arg(long, value_name = "name", conflicts_with = "from_file")]
+        template: Option<String>,
     },
     /// Lists all tasks
-    List,
-    /// Remove a task by its ID
+    #[command(visible_alias = "ls")]
+    List {
+        /// Order in which the tasks are shown
+        #[arg(long, value_enum, default_value_t = SortOrder::Manual)]
+        sort: SortOrder,
+        /// Reverse the order, still showing tasks without the sorted field last
+        #[arg(long)]
+        reverse: bool,
+        /// Show the archived tasks instead
+        #[arg(long)]
+        archived: bool,
+        #[command(flatten)]
+        filter: TaskFilter,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+        format: OutputFormat,
+        /// Keep showing the list, redrawn whenever it's changed, like by another terminal or
+        /// `sync`
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Lists the open tasks due by the end of today, like `list --today --sort due`
+    Today,
+    /// Lists the open tasks that are overdue, like `list --overdue --sort due`
+    Overdue,
+    /// Lists the open tasks due by the end of this week, like `list --week --sort due`
+    Week,
+    /// Find tasks whose title, notes or tags contain a text, ignoring case
+    Search {
+        /// Text to search for
+        #[arg(value_name = "query")]
+        query: String,
+        /// Also match tasks containing the characters of the query in order, with gaps
+        #[arg(long)]
+        fuzzy: bool,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+        format: OutputFormat,
+    },
+    /// Move tasks to the trash, from where they can be restored
+    #[command(visible_alias = "rm")]
     Remove {
-        /// ID of the task to be removed
+        /// IDs or ranges like 7-9 of the tasks to be removed
+        #[arg(value_name = "task-id", required = true, value_parser = parse_id_range)]
+        ids: Vec<RangeInclusive<u32>>,
+        /// Remove the tasks without asking, which is needed when not running in a terminal
+        #[arg(long, short = 'y')]
+        force: bool,
+    },
+    /// Mark tasks as completed
+    #[command(visible_alias = "done")]
+    Complete {
+        /// IDs or ranges like 7-9 of the tasks to be marked completed
+        #[arg(value_name = "task-id", required = true, value_parser = parse_id_range)]
+        ids: Vec<RangeInclusive<u32>>,
+        /// Complete tasks even if some of their subtasks or blockers are still open
+        #[arg(long)]
+        force: bool,
+    },
+    /// Move all completed tasks to the trash
+    Clear {
+        /// Only show which tasks would be moved
+        #[arg(long)]
+        dry_run: bool,
+        /// Move the tasks without asking, which is needed when not running in a terminal
+        #[arg(long, short = 'y')]
+        force: bool,
+    },
+    /// Move tasks to the archive of the list, where `list --archived` still shows them
+    #[command(group(ArgGroup::new("selection").required(true).args(["ids", "older_than"])))]
+    Archive {
+        /// IDs or ranges like 7-9 of the tasks to be archived
+        #[arg(value_name = "task-id", value_parser = parse_id_range)]
+        ids: Vec<RangeInclusive<u32>>,
+        /// Archive all tasks completed more than this many days ago
+        #[arg(long, value_name = "days")]
+        older_than: Option<u32>,
+    },
+    /// Lists the removed tasks that can still be restored
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands,
+    },
+    /// Give the tasks the IDs 1 to n again, after removing many has left gaps
+    Renumber,
+    /// Move tasks back from the trash, keeping their IDs
+    Restore {
+        /// IDs or ranges like 7-9 of the tasks to be restored
+        #[arg(value_name = "task-id", required = true, value_parser = parse_id_range)]
+        ids: Vec<RangeInclusive<u32>>,
+    },
+    /// Mark completed tasks as open again
+    #[command(visible_alias = "reopen")]
+    Uncomplete {
+        /// IDs or ranges like 7-9 of the tasks to be reopened
+        #[arg(value_name = "task-id", required = true, value_parser = parse_id_range)]
+        ids: Vec<RangeInclusive<u32>>,
+    },
+    /// Mark a task as blocked by other tasks, which have to be completed first
+    Block {
+        /// ID of the task that is blocked
         #[arg(value_name = "task-id")]
         id: u32,
+        /// IDs of the tasks blocking it
+        #[arg(long, value_name = "task-id", num_args = 1.., required = true)]
+        on: Vec<u32>,
     },
-    /// Mark a task as completed
-    Complete {
-        /// ID of the task to be marked completed
+    /// Remove blockers from a task
+    Unblock {
+        /// ID of the task that is blocked
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// IDs of the tasks not blocking it anymore
+        #[arg(long, value_name = "task-id", num_args = 1.., required = true)]
+        on: Vec<u32>,
+    },
+    /// Lists, creates or deletes task lists
+    Lists {
+        #[command(subcommand)]
+        command: Option<ListsCommands>,
+    },
+    /// Change the title, due date, priority or estimate of a task, keeping its ID, or edit the
+    /// task in $EDITOR if nothing but its ID is given
+    Edit {
+        /// ID of the task to be changed
         #[arg(value_name = "task-id")]
         id: u32,
+        /// New title of the task
+        #[arg(value_name = "new-title", conflicts_with = "title")]
+        new_title: Option<String>,
+        /// New title of the task
+        #[arg(long, value_name = "task-title")]
+        title: Option<String>,
+        /// When the task is due, like 2024-05-17, tomorrow or "fri 5pm"
+        #[arg(long, value_name = "date", value_parser = due::parse)]
+        due: Option<Due>,
+        /// How important the task is
+        #[arg(long, value_enum)]
+        priority: Option<Priority>,
+        /// How often the task comes back once completed, like weekly or "every 3 days"
+        #[arg(long, value_name = "rule")]
+        repeat: Option<Recurrence>,
+        /// How long the task is expected to take, like 45m or 1h30m
+        #[arg(long, value_name = "duration", value_parser = timesheet::parse_duration)]
+        estimate: Option<chrono::Duration>,
+    },
+    /// Append a line to the notes of a task
+    Note {
+        /// ID of the task to be changed
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// Text to append
+        #[arg(value_name = "text")]
+        text: String,
+    },
+    /// Show all details of a task
+    Show {
+        /// ID of the task to be shown
+        #[arg(value_name = "task-id")]
+        id: u32,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+        format: OutputFormat,
+    },
+    /// Set when a task is due
+    Due {
+        /// ID of the task to be changed
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// When the task is due, like 2024-05-17, tomorrow or "fri 5pm"
+        #[arg(value_name = "date", value_parser = due::parse)]
+        due: Due,
+    },
+    /// Hide a task from `list` for a while, moving its due date as far
+    Snooze {
+        /// ID of the task to be snoozed
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// How long to snooze it for, like 3d, 2w or 4h, or until when, like tomorrow or "fri 5pm"
+        #[arg(value_name = "duration|date", value_parser = due::parse_snooze)]
+        snooze: Snooze,
+    },
+    /// Set when to be reminded of a task by `notify`
+    Remind {
+        /// ID of the task to be reminded of
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// When to be reminded, like "tomorrow 9am" or 17:00
+        #[arg(long, value_name = "time", value_parser = due::parse)]
+        at: Due,
+    },
+    /// Show desktop notifications for tasks that became due or overdue and for reminders, each
+    /// once, meant to be run regularly by cron or a systemd timer
+    Notify,
+    /// Browse and change the list interactively, with live filtering
+    Tui,
+    /// Show how many tasks there are and how quickly they get completed, archived ones included
+    Stats {
+        /// Number of weeks to show the completed tasks of
+        #[arg(long, value_name = "weeks", default_value_t = 4)]
+        weeks: u32,
+        /// Draw the completed tasks of each week as bars
+        #[arg(long)]
+        sparkline: bool,
+    },
+    /// Show a burndown of the open and completed tasks of the last days, archived ones
+    /// included, and the tasks completed by tag
+    Report {
+        /// Period to report on, like `30d` or `4w`, or the date it starts on
+        #[arg(long, value_name = "period", default_value = "30d", value_parser = stats::parse_since)]
+        since: NaiveDate,
+    },
+    /// Start working on a task, stopping the work on any other task of the list
+    Start {
+        /// ID of the task to be worked on
+        #[arg(value_name = "task-id")]
+        id: u32,
+    },
+    /// Stop working on the task that was started
+    Stop,
+    /// Record time that was spent on a task without starting it
+    Log {
+        /// ID of the task that was worked on
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// How long it was worked on, like 45m or 1h30m
+        #[arg(value_name = "duration", value_parser = timesheet::parse_duration)]
+        duration: chrono::Duration,
+    },
+    /// Work on a task for a timed interval followed by a break, logging the work when it ends
+    Pomodoro {
+        /// ID of the task to be worked on
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// Length of the work interval
+        #[arg(long, value_name = "minutes", default_value_t = 25)]
+        work: u32,
+        /// Length of the break after it, 0 for none
+        #[arg(long = "break", value_name = "minutes", default_value_t = 5)]
+        break_minutes: u32,
+    },
+    /// Show the time spent on each task today, or on every day of this week
+    Timesheet {
+        /// Show this week, from Monday, instead of today
+        #[arg(long)]
+        week: bool,
+    },
+    /// Show the tasks by the day they're due on, for the next seven days, this week or a
+    /// calendar of this month
+    Agenda {
+        /// Show this week, from Monday, instead of the next seven days
+        #[arg(long, conflicts_with = "month")]
+        week: bool,
+        /// Show a calendar of this month, followed by its tasks
+        #[arg(long)]
+        month: bool,
+    },
+    /// Show the contexts of the tasks, like @home, with how many open tasks each has
+    Contexts,
+    /// Suggest which open tasks to work on, by their priority, how soon they're due and how
+    /// many tasks they block
+    Next {
+        /// Number of tasks to suggest
+        #[arg(value_name = "n", default_value_t = 3)]
+        count: usize,
+    },
+    /// Set how important a task is
+    Prioritize {
+        /// ID of the task to be changed
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// How important the task is
+        #[arg(value_enum, value_name = "priority")]
+        priority: Priority,
+    },
+    /// Attach a tag to a task
+    Tag {
+        /// ID of the task to be tagged
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// Tag to attach
+        #[arg(value_name = "tag", value_parser = parse_tag)]
+        tag: String,
+    },
+    /// Remove a tag from a task
+    Untag {
+        /// ID of the task to be untagged
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// Tag to remove
+        #[arg(value_name = "tag", value_parser = parse_tag)]
+        tag: String,
+    },
+    /// Attach a link to a web page to a task
+    Attach {
+        /// ID of the task to be changed
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// URL of the page
+        #[arg(value_name = "url", value_parser = parse_link)]
+        url: String,
+    },
+    /// Remove a link from a task, by its URL or short URL
+    Detach {
+        /// ID of the task to be changed
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// URL to remove
+        #[arg(value_name = "url", value_parser = parse_link)]
+        url: String,
+    },
+    /// Replace the links of a task by short URLs from a running url_shortener, set under
+    /// [shortener] in the config file, keeping the long ones too
+    Shorten {
+        /// ID of the task whose links are shortened
+        #[arg(value_name = "task-id")]
+        id: u32,
+    },
+    /// Print the tasks of the list in another format
+    Export {
+        #[arg(long, value_enum)]
+        format: ExportFormat,
+        /// File to write to instead of printing
+        #[arg(long, short, value_name = "file")]
+        output: Option<PathBuf>,
+    },
+    /// Add the tasks of a file in another format, or the issues assigned to you on a service,
+    /// to the list
+    Import {
+        #[arg(long, value_enum, required_unless_present = "from")]
+        format: Option<ImportFormat>,
+        /// File to import
+        #[arg(
+            value_name = "file",
+            required_unless_present = "from",
+            conflicts_with = "from"
+        )]
+        file: Option<PathBuf>,
+        /// Service to import the open issues assigned to you from, instead of a file. Issues
+        /// that were imported before are skipped
+        #[arg(long, value_enum, requires = "repo", conflicts_with = "format")]
+        from: Option<IssueService>,
+        /// Repository to take the issues of, like `owner/name`
+        #[arg(long, value_name = "owner/name", requires = "from")]
+        repo: Option<String>,
+    },
+    /// Combine a list file from elsewhere, like a copy from another machine, into the list,
+    /// keeping the newest version of every task
+    Merge {
+        /// List file to take the tasks from, which is left as it is
+        #[arg(value_name = "other-file")]
+        other_file: PathBuf,
+    },
+    /// Exchange the changes of the list with the endpoint set under [sync] in the config file,
+    /// to use the list on several machines
+    Sync {
+        /// How changes made on both sides are combined, instead of the one of the config file
+        #[arg(long, value_enum)]
+        strategy: Option<sync::Strategy>,
+    },
+    /// Print a script that completes commands, options and task IDs in a shell, like
+    /// `source <(todo_cli completions bash)`
+    Completions {
+        #[arg(value_enum, value_name = "shell")]
+        shell: completions::Shell,
+    },
+    /// Print the IDs and titles of the open tasks for the completion scripts
+    #[command(name = completions::TASK_IDS_COMMAND, hide = true)]
+    CompleteIds,
+    /// Revert the last changes made by other commands
+    Undo {
+        /// How many commands to revert
+        #[arg(value_name = "count", default_value_t = 1)]
+        count: usize,
+    },
+    /// Show the commits of the data directory, with `git = true` in the config file
+    History {
+        /// How many commits to show, newest first
+        #[arg(value_name = "count", default_value_t = 20)]
+        count: usize,
+    },
+    /// Put all lists back as they were after a commit shown by `history`
+    Revert {
+        #[arg(value_name = "commit")]
+        commit: String,
+    },
+    /// Move a task in the order of the list, which `list` shows it in
+    #[command(group(ArgGroup::new("destination").required(true).args(["before", "to_top"])))]
+    Move {
+        /// ID of the task to be moved
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// ID of the task it's moved in front of
+        #[arg(long, value_name = "task-id")]
+        before: Option<u32>,
+        /// Move it to the top of the list
+        #[arg(long)]
+        to_top: bool,
+    },
+    /// Move a task to another list
+    MoveTask {
+        /// ID of the task to be moved
+        #[arg(value_name = "task-id")]
+        id: u32,
+        /// Name of the list the task is moved to
+        #[arg(long, value_name = "list-name")]
+        to_list: String,
     },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Task {
-    id: u32,
-    title: String,
-    completed: bool,
+#[derive(Subcommand)]
+enum TrashCommands {
+    /// Lists the tasks in the trash, most recently removed first
+    List,
 }
 
-impl Task {
-    fn new(id: u32, title: String) -> Self {
-        return Task {
-            id,
-            title,
-            completed: false,
+#[derive(Subcommand)]
+enum ListsCommands {
+    /// Creates a new empty list
+    Create {
+        /// Name of the list to be created
+        #[arg(value_name = "list-name")]
+        name: String,
+    },
+    /// Deletes a list and all of its tasks
+    Delete {
+        /// Name of the list to be deleted
+        #[arg(value_name = "list-name")]
+        name: String,
+        /// Delete the list without asking, which is needed when not running in a terminal
+        #[arg(long, short = 'y')]
+        force: bool,
+    },
+}
+
+const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+/// Run when no command is given and the config file doesn't set another one.
+const DEFAULT_COMMAND: &str = "list";
+/// Ranges of task IDs are expanded, so they're kept short.
+const MAX_ID_RANGE_LENGTH: u32 = 1000;
+/// How often `list --watch` redraws without changes, for the times relative to now.
+const WATCH_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How `list`, `show` and `search` print tasks.
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    /// Text for people to read
+    Plain,
+    /// JSON for scripts, with the fields of the list file
+    Json,
+    /// Aligned columns that fit the terminal, for `list` and `search`
+    Table,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ExportFormat {
+    /// A line per task in the todo.txt format
+    Todotxt,
+    /// A checklist with a section per tag
+    Markdown,
+    /// An iCalendar file of the tasks with due dates, for calendar apps
+    Ics,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ImportFormat {
+    /// A line per task in the todo.txt format
+    Todotxt,
+    /// Comma-separated values with a header row naming the columns, of which `title` is
+    /// required and `due`, `priority`, `tags`, `notes` and `completed` are optional
+    Csv,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum IssueService {
+    /// GitHub, with a token from `[github]` in the config file or `GITHUB_TOKEN`
+    Github,
+}
+
+fn parse_uuid(arg: &OsStr) -> Option<Uuid> {
+    arg.to_str().and_then(|arg| Uuid::try_parse(arg).ok())
+}
+
+/// Replaces the UUIDs in the arguments by the IDs of their tasks in the list or its archive,
+/// so every command accepts either.
+fn resolve_uuids(
+    args: Vec<OsString>,
+    storage: &dyn Storage,
+    list_name: &str,
+) -> Result<Vec<OsString>, TodoError> {
+    let task_list = storage.open_list(list_name)?;
+    let archive = storage.open_archive(list_name)?;
+    let tasks = task_list.tasks.iter().chain(&archive.tasks);
+    let mut resolved = Vec::new();
+    for arg in args {
+        let Some(uuid) = parse_uuid(&arg) else {
+            resolved.push(arg);
+            continue;
         };
+        let task = tasks.clone().find(|task| task.uuid == uuid);
+        let task = task.ok_or_else(|| TodoError::not_found(uuid, list_name))?;
+        resolved.push(task.id.to_string().into());
+    }
+    Ok(resolved)
+}
+
+/// Parses a task ID like `3` or a range of task IDs like `7-9`.
+fn parse_id_range(input: &str) -> Result<RangeInclusive<u32>, String> {
+    let parse_id = |id: &str| {
+        id.trim()
+            .parse::<u32>()
+            .map_err(|_| format!("'{}' isn't a task ID", id))
+    };
+    let range = match input.split_once('-') {
+        Some((start, end)) => parse_id(start)?..=parse_id(end)?,
+        None => parse_id(input)?..=parse_id(input)?,
+    };
+    if range.is_empty() {
+        return Err(format!("range {} ends before it starts", input));
     }
+    if range.end() - range.start() >= MAX_ID_RANGE_LENGTH {
+        return Err(format!(
+            "range {} is too long, select up to {} tasks at once",
+            input, MAX_ID_RANGE_LENGTH
+        ));
+    }
+    Ok(range)
+}
 
-    fn mark_completed(&mut self) {
-        self.completed = true;
+/// Fails with the IDs that weren't found, once the other tasks were changed.
+fn report_missing(missing: Vec<u32>, list_name: &str) -> Result<(), TodoError> {
+    if missing.is_empty() {
+        return Ok(());
     }
+    Err(TodoError::NotFound {
+        ids: missing.iter().map(u32::to_string).collect(),
+        list_name: list_name.to_string(),
+    })
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct TaskList {
-    tasks: Vec<Task>,
-    next_task_id: u32,
+/// What `show` prints as JSON, a task with the IDs of its subtasks.
+#[derive(Serialize)]
+struct TaskDetails<'a> {
+    #[serde(flatten)]
+    task: &'a Task,
+    subtasks: Vec<u32>,
 }
 
-impl TaskList {
-    fn new() -> Self {
-        return TaskList {
-            tasks: Vec::new(),
-            next_task_id: 1,
-        };
+/// Runs the on-remove hook for tasks that were just moved to the trash.
+fn run_remove_hooks(config: &Config, list_name: &str, task_list: &TaskList, ids: &[u32]) {
+    for &id in ids {
+        // The latest of the trashed tasks with an ID, which could have been reused.
+        let trashed = task_list
+            .trash
+            .iter()
+            .rev()
+            .find(|trashed| trashed.task.id == id);
+        if let Some(trashed) = trashed {
+            config
+                .hooks
+                .run(hooks::Event::Remove, list_name, &trashed.task);
+        }
     }
+}
 
-    fn add_task(&mut self, title: String) {
-        let task = Task::new(self.next_task_id, title);
-        self.tasks.push(task);
-        self.next_task_id += 1;
+/// Prints a message, on stderr when stdout is for JSON.
+fn info(json: bool, message: &str) {
+    if json {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
     }
+}
 
-    fn remove_task(&mut self, id: u32) {
-        let index = self
-            .tasks
-            .iter()
-            .enumerate()
-            .find(|(_, task)| task.id == id)
-            .map(|(index, _)| index);
+fn print_json(value: &impl Serialize) -> Result<(), TodoError> {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).map_err(Error::from)?
+    );
+    Ok(())
+}
 
-        if let Some(index) = index {
-            self.tasks.remove(index);
-        }
+/// Asks whether to go ahead on the terminal. Without one, there's nobody to answer, and it fails
+/// rather than waiting for an answer that never comes.
+fn confirm(prompt: &str) -> Result<bool, Error> {
+    if !io::stdin().is_terminal() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "can't ask \"{}\" when not running in a terminal, use --force to go ahead",
+                prompt
+            ),
+        ));
     }
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
 
-    fn get_task(&mut self, id: u32) -> Option<&mut Task> {
-        self.tasks.iter_mut().find(|task| task.id == id)
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+impl Args {
+    fn list_name(&self) -> &str {
+        self.list.as_deref().unwrap_or(storage::DEFAULT_LIST_NAME)
+    }
+
+    /// The lists whose files the command may change.
+    fn touched_lists(&self) -> Vec<&str> {
+        let mut names = vec![self.list_name()];
+        match &self.command {
+            Commands::MoveTask { to_list, .. } => names.push(to_list),
+            // Run every few minutes, it would push everything else out of the journal.
+            Commands::Notify => return Vec::new(),
+            Commands::CompleteIds => return Vec::new(),
+            Commands::Lists {
+                command: Some(ListsCommands::Create { name } | ListsCommands::Delete { name, .. }),
+            } => names.push(name),
+            _ => {}
+        }
+        names
     }
+}
 
-    fn list_tasks(&self) -> &Vec<Task> {
-        &self.tasks
+fn main() -> ExitCode {
+    match run_command() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            error.exit_code()
+        }
     }
 }
 
-fn load_from_file(file_path: &str) -> TaskList {
-    match fs::read_to_string(file_path) {
-        Ok(file_content) => serde_json::from_str(&file_content).unwrap(),
-        Err(..) => TaskList::new(),
+/// Loads the config, parses the arguments and runs the command in the data directory.
+fn run_command() -> Result<(), TodoError> {
+    let mut config = Config::load()?;
+    let default_command = config.default_command.as_deref().unwrap_or(DEFAULT_COMMAND);
+    let raw_args =
+        alias::with_default(&Args::command(), env::args_os().collect(), default_command)?;
+    // After the default command is added, so it can be an alias.
+    let raw_args = alias::expand(&Args::command(), raw_args, &config.aliases)?;
+    // UUIDs are parsed as a placeholder ID at first, until the list they're looked up in is
+    // known.
+    let has_uuids = raw_args.iter().skip(1).any(|arg| parse_uuid(arg).is_some());
+    let placeholders = raw_args.iter().map(|arg| match parse_uuid(arg) {
+        Some(_) => OsString::from("0"),
+        None => arg.clone(),
+    });
+    let mut args = Args::parse_from(placeholders);
+    if let Commands::Completions { shell } = args.command {
+        print!("{}", completions::generate(shell));
+        return Ok(());
+    }
+    if let Some(date_format) = &config.date_format {
+        due::set_date_format(date_format)?;
+    }
+    args.list = args.list.or(config.default_list.take());
+    // Only list files are looked for, a database is never found by accident.
+    let file = args.list_file.clone().or_else(|| {
+        let from_env = env::var_os("TODO_FILE").filter(|file| !file.is_empty());
+        from_env.map(PathBuf::from).or(match config.storage {
+            StorageBackend::Json if config.local_files => config::local_file_path(),
+            _ => None,
+        })
+    });
+    let data_dir = match &file {
+        Some(file) => storage::parent_dir(file),
+        None => config::data_dir_path().unwrap_or_else(|| PathBuf::from(".")),
+    };
+    fs::create_dir_all(&data_dir)?;
+    // The directory of `--file` is usually under version control of its own already.
+    let git = config.git && file.is_none();
+    // Completing task IDs only reads, and shouldn't hang while another command like `tui` runs.
+    let _lock = match args.command {
+        Commands::CompleteIds => None,
+        // A pomodoro takes the lock once it ends, since other commands are used while it runs.
+        Commands::Pomodoro { .. } => None,
+        // Watching only reads, while other commands change the list.
+        Commands::List { watch: true, .. } => None,
+        _ => Some(storage::lock_dir(&data_dir)?),
+    };
+    let operation = env::args().skip(1).collect::<Vec<_>>().join(" ");
+    match &args.command {
+        Commands::History { count } => {
+            for commit in history::log(&data_dir, *count)? {
+                println!(
+                    "{}  {}  {}",
+                    commit.hash,
+                    format_timestamp(commit.at),
+                    commit.message
+                );
+            }
+            return Ok(());
+        }
+        // Before the storage is opened, since the database is replaced.
+        Commands::Revert { commit } => {
+            history::revert(&data_dir, commit, &operation)?;
+            println!("Reverted the lists to {}", commit);
+            return Ok(());
+        }
+        _ => {}
+    }
+    let commit = |operation: &str| {
+        if git {
+            history::commit(&data_dir, operation)
+        } else {
+            Ok(())
+        }
+    };
+
+    let encrypt = args.encrypt || config.encrypt;
+    let storage = open_storage(&data_dir, file, config.storage, encrypt)?;
+    let storage = storage.as_ref();
+    if has_uuids {
+        let list = args.list.take();
+        let list_name = list.as_deref().unwrap_or(storage::DEFAULT_LIST_NAME);
+        args = Args::parse_from(resolve_uuids(raw_args, storage, list_name)?);
+        args.list = args.list.or(list);
+    }
+
+    if let Commands::Undo { count } = args.command {
+        let undone = journal::undo(&data_dir, storage, count)?;
+        if undone.is_empty() {
+            println!("Nothing to undo");
+        }
+        for operation in undone {
+            println!("Undid `{}`", operation);
+        }
+        commit(&operation)?;
+        return Ok(());
+    }
+
+    if let Commands::Pomodoro {
+        id,
+        work,
+        break_minutes,
+    } = args.command
+    {
+        let pomodoro = pomodoro::Pomodoro {
+            list_name: args.list_name(),
+            id,
+            work_minutes: work,
+            break_minutes,
+            operation: &operation,
+        };
+        let result = pomodoro::run(&data_dir, storage, &pomodoro);
+        commit(&operation)?;
+        return result;
     }
+
+    // Every command is journaled, but only recorded if it changed a list.
+    let lists: Vec<&str> = args
+        .touched_lists()
+        .into_iter()
+        .filter(|name| storage::validate_list_name(name).is_ok())
+        .collect();
+    let before = journal::snapshot(storage, &lists)?;
+    // Commands with IDs that aren't found still change the others, which can be undone.
+    let result = run(args, storage, &config, &data_dir);
+    journal::record(&data_dir, storage, &operation, before)?;
+    commit(&operation)?;
+    result
 }
 
-fn save_to_file(task_list: &TaskList, file_path: &str) -> Result<(), Error> {
-    let serialized = serde_json::to_string(task_list)?;
-    fs::write(file_path, &serialized)
+/// Opens the configured storage in the data directory, where the journal is kept too.
+///
+/// `--file` is the file of the default list, or the database, with everything else next to
+/// it. Otherwise the data directory is used, where lists in the working directory, where they
+/// used to be kept, are moved on first use. Without a home directory everything stays in the
+/// working directory. A new database starts out with the lists of the data directory.
+///
+/// With encryption, the files that aren't encrypted yet are encrypted right away.
+fn open_storage(
+    data_dir: &Path,
+    file: Option<PathBuf>,
+    backend: StorageBackend,
+    encrypt: bool,
+) -> Result<Box<dyn Storage>, Error> {
+    let files = JsonFiles::new(data_dir.to_path_buf());
+    if file.is_none() {
+        let moved = files.migrate_files(Path::new("."))?;
+        if !moved.is_empty() {
+            eprintln!(
+                "Moved {} list file(s) from the working directory to {}",
+                moved.len(),
+                data_dir.display()
+            );
+        }
+    }
+
+    let storage: Box<dyn Storage> = match (backend, file) {
+        (StorageBackend::Json, file) => {
+            let files = match file {
+                Some(file) => JsonFiles::with_default_list_file(file),
+                None => files,
+            };
+            if !encrypt {
+                return Ok(Box::new(files));
+            }
+            let files = files.encrypted();
+            let encrypted = files.encrypt_files()?;
+            if encrypted > 0 {
+                // The journal keeps the lists as they were, which would be readable otherwise.
+                journal::clear(data_dir)?;
+                eprintln!(
+                    "Encrypted {} list file(s), and cleared the history of undo",
+                    encrypted
+                );
+            }
+            Box::new(files)
+        }
+        (StorageBackend::Sqlite, _) if encrypt => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "only lists in JSON files can be encrypted, not a database",
+            ));
+        }
+        (StorageBackend::Sqlite, file) => {
+            let path = file.unwrap_or_else(|| data_dir.join(storage::DATABASE_FILE_NAME));
+            let is_new = !path.exists();
+            let database = SqliteDatabase::open(&path)?;
+            if is_new {
+                let copied = storage::copy_lists(&files, &database)?;
+                if copied > 0 {
+                    eprintln!("Copied {} list(s) into {}", copied, path.display());
+                }
+            }
+            Box::new(database)
+        }
+    };
+    Ok(storage)
 }
 
-fn main() -> Result<(), Error> {
-    let args = Args::parse();
+fn run(
+    mut args: Args,
+    storage: &dyn Storage,
+    config: &Config,
+    data_dir: &Path,
+) -> Result<(), TodoError> {
+    let list_name = args.list_name().to_string();
+    let renderer = Renderer::new(!args.no_color && config.color.enabled());
+    let shortcut = match args.command {
+        Commands::Today => Some(TaskFilter {
+            today: true,
+            ..TaskFilter::default()
+        }),
+        Commands::Overdue => Some(TaskFilter {
+            overdue: true,
+            ..TaskFilter::default()
+        }),
+        Commands::Week => Some(TaskFilter {
+            week: true,
+            ..TaskFilter::default()
+        }),
+        _ => None,
+    };
+    if let Some(filter) = shortcut {
+        args.command = Commands::List {
+            sort: SortOrder::Due,
+            reverse: false,
+            archived: false,
+            filter,
+            format: OutputFormat::Plain,
+            watch: false,
+        };
+    }
+    let json = args.json;
+    let has_json_output = matches!(
+        args.command,
+        Commands::List { .. } | Commands::Search { .. } | Commands::Show { .. }
+    );
+    if json && !has_json_output {
+        return Err(TodoError::Usage(
+            "only list, show and search have JSON output".to_string(),
+        ));
+    }
 
-    let file_path = "task_list.json";
-    let mut task_list = load_from_file(file_path);
+    // Watching doesn't hold the lock these need.
+    let is_watching = matches!(args.command, Commands::List { watch: true, .. });
+    if storage.list_exists(&list_name)? && !is_watching {
+        let mut task_list = storage.open_list(&list_name)?;
+        if task_list.purge_trash(args.trash_retention_days) > 0 {
+            storage.save_list(&list_name, &task_list)?;
+        }
+        if let Some(days) = args.auto_archive_days {
+            let ids = task_list.completed_before(days);
+            if !ids.is_empty() {
+                storage.archive_tasks(&list_name, &mut task_list, &ids)?;
+            }
+        }
+    }
 
     match args.command {
-        Commands::Add { title } => {
-            task_list.add_task(title);
-            save_to_file(&task_list, file_path)
+        Commands::Add {
+            title,
+            due,
+            priority,
+            tags,
+            note,
+            parent,
+            repeat,
+            estimate,
+            edit,
+            from_file,
+            template,
+        } => {
+            let template = match &template {
+                Some(name) => {
+                    let template = config.templates.get(name).ok_or_else(|| {
+                        let message = format!("no template named '{}' in the config file", name);
+                        TodoError::Usage(message)
+                    })?;
+                    Some((name, template))
+                }
+                None => None,
+            };
+            let lines = match (&from_file, title.as_deref()) {
+                (Some(file), _) => {
+                    Some(fs::read_to_string(file).map_err(|error| with_path(error, file))?)
+                }
+                (None, Some("-")) if !edit => Some(io::read_to_string(io::stdin())?),
+                _ => None,
+            };
+            let mut task_list = storage.open_list(&list_name)?;
+            if let Some(parent) = parent {
+                if task_list.find_task(parent).is_none() {
+                    return Err(TodoError::not_found(parent, &list_name));
+                }
+            }
+            let new_tasks: Vec<Task> = match &lines {
+                Some(lines) => lines.lines().filter_map(parse_task_line).collect(),
+                None => match template {
+                    Some((name, template)) => {
+                        let now = Local::now().naive_local();
+                        let task =
+                            template
+                                .instantiate(title.as_deref(), now)
+                                .map_err(|error| {
+                                    let message = format!("template '{}': {}", name, error);
+                                    Error::new(ErrorKind::InvalidData, message)
+                                })?;
+                        vec![task]
+                    }
+                    None => vec![Task::new(0, title.unwrap_or_default())],
+                },
+            };
+            let task_count = new_tasks.len();
+            let mut added = Vec::new();
+            for mut task in new_tasks {
+                task.parent_id = parent;
+                task.repeat = repeat;
+                if let Some(estimate) = estimate {
+                    task.set_estimate(estimate);
+                }
+                if let Some(note) = &note {
+                    task.append_note(note);
+                }
+                task.due = due.or(task.due);
+                task.priority = task.priority.or(priority);
+                for tag in &tags {
+                    task.add_tag(tag.clone());
+                }
+                if edit {
+                    editor::edit_task(&mut task)?;
+                }
+                let id = task_list.insert_task(task);
+                added.push(id);
+                for title in template.iter().flat_map(|(_, template)| &template.subtasks) {
+                    let mut subtask = Task::new(0, title.clone());
+                    subtask.parent_id = Some(id);
+                    added.push(task_list.insert_task(subtask));
+                }
+            }
+            storage.save_list(&list_name, &task_list)?;
+            for task in added.iter().filter_map(|&id| task_list.find_task(id)) {
+                config.hooks.run(hooks::Event::Add, &list_name, task);
+            }
+            if lines.is_some() {
+                let suffix = if task_count == 1 { "" } else { "s" };
+                println!("Added {} task{} to '{}'", task_count, suffix, list_name);
+            }
+            Ok(())
         }
-        Commands::List => {
-            let tasks = task_list.list_tasks();
-            for task in tasks {
-                let completion_symbol = if task.completed { "✓" } else { " " };
-                println!("[{}] {}: {}", completion_symbol, task.id, task.title);
+        Commands::List {
+            sort,
+            reverse,
+            archived,
+            filter,
+            format,
+            watch,
+        } => {
+            let json = json || format == OutputFormat::Json;
+            if watch && json {
+                return Err(TodoError::Usage(
+                    "--watch redraws the list, which doesn't work with JSON".to_string(),
+                ));
+            }
+            let mut watcher = if watch {
+                Some(watch::Watcher::new(data_dir)?)
+            } else {
+                None
+            };
+            loop {
+                if watcher.is_some() {
+                    if io::stdout().is_terminal() {
+                        print!("\x1b[H\x1b[2J");
+                    }
+                    let now = format_timestamp(Local::now());
+                    println!("Watching '{}' as of {}, Ctrl-C to stop", list_name, now);
+                    println!();
+                }
+                let task_list = if archived {
+                    storage.open_archive(&list_name)?
+                } else {
+                    storage.open_list(&list_name)?
+                };
+                if !archived {
+                    let overdue = task_list.overdue_count(Local::now().naive_local());
+                    if overdue > 0 {
+                        let suffix = if overdue == 1 { "" } else { "s" };
+                        info(json, &format!("{} task{} overdue", overdue, suffix));
+                    }
+                    if let Some(capacity) = config.daily_capacity {
+                        let due_today = task_list.filtered_tasks(
+                            &TaskFilter {
+                                today: true,
+                                ..Default::default()
+                            },
+                            SortOrder::Manual,
+                            false,
+                        );
+                        let planned = remaining_estimate(&due_today);
+                        if let Some(planned) = planned.filter(|&planned| planned > capacity) {
+                            let message = format!(
+                                "Tasks due today are estimated at {}, more than the daily capacity of {}",
+                                timesheet::format_duration(planned),
+                                timesheet::format_duration(capacity)
+                            );
+                            info(json, &message);
+                        }
+                    }
+                }
+                let tasks = task_list.filtered_tasks(&filter, sort, reverse);
+                if json {
+                    print_json(&tasks)?;
+                } else if format == OutputFormat::Table {
+                    renderer.print_table(&task_list, &tasks);
+                } else if filter.is_empty() {
+                    renderer.print_tree(&task_list, &tasks);
+                } else {
+                    // Matching subtasks are shown on their own, since their parent might not match.
+                    for task in &tasks {
+                        println!("{}", renderer.summary_line(&task_list, task));
+                    }
+                }
+                if let Some(left) = remaining_estimate(&tasks).filter(|_| !json) {
+                    let left = timesheet::format_duration(left);
+                    println!("Estimated effort left: {}", left);
+                }
+                let Some(watcher) = &mut watcher else {
+                    return Ok(());
+                };
+                io::stdout().flush()?;
+                watcher.wait(WATCH_REFRESH_INTERVAL)?;
             }
+        }
+        Commands::Search {
+            query,
+            fuzzy,
+            format,
+        } => {
+            let task_list = storage.open_list(&list_name)?;
+            let query = query.trim().to_lowercase();
+            let matches: Vec<&Task> = task_list
+                .list_tasks()
+                .iter()
+                .filter(|task| task.matches(&query, fuzzy))
+                .collect();
+            if json || format == OutputFormat::Json {
+                return print_json(&matches);
+            }
+            if format == OutputFormat::Table {
+                renderer.print_table(&task_list, &matches);
+                return Ok(());
+            }
+            for task in matches {
+                println!("{}", renderer.summary_line(&task_list, task));
+            }
+            Ok(())
+        }
+        Commands::Remove { ids, force } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let (ids, missing) = task_list.select_ids(&ids);
+            if !ids.is_empty() && !force {
+                let prompt = match ids.as_slice() {
+                    [id] => format!("Move task {} to the trash?", id),
+                    _ => format!("Move tasks {} to the trash?", join_ids(&ids)),
+                };
+                if !confirm(&prompt)? {
+                    println!("Aborted");
+                    return Ok(());
+                }
+            }
+            for &id in &ids {
+                task_list.trash_task(id);
+            }
+            if !ids.is_empty() {
+                storage.save_list(&list_name, &task_list)?;
+                println!("Moved {} to the trash", join_ids(&ids));
+            }
+            run_remove_hooks(config, &list_name, &task_list, &ids);
+            report_missing(missing, &list_name)
+        }
+        Commands::Complete { ids, force } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let (ids, missing) = task_list.select_ids(&ids);
+            let completion = task_list.complete_tasks(&ids, force);
+            if !completion.completed.is_empty() {
+                storage.save_list(&list_name, &task_list)?;
+                println!("Completed {}", join_ids(&completion.completed));
+            }
+            for &id in &completion.completed {
+                if let Some(task) = task_list.find_task(id) {
+                    config.hooks.run(hooks::Event::Complete, &list_name, task);
+                }
+            }
+            for id in completion.next_occurrences {
+                println!("Next occurrence: {}", task_list.find_task(id).unwrap());
+            }
+            for refusal in &completion.refusals {
+                eprintln!("{}, complete them first or use --force", refusal);
+            }
+            report_missing(missing, &list_name)?;
+            // So scripts can tell that not everything was completed.
+            match completion.refusals.len() {
+                0 => Ok(()),
+                1 => Err(TodoError::Usage("1 task wasn't completed".to_string())),
+                refused => Err(TodoError::Usage(format!(
+                    "{} tasks weren't completed",
+                    refused
+                ))),
+            }
+        }
+        Commands::Clear { dry_run, force } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let completed: Vec<u32> = task_list
+                .list_tasks()
+                .iter()
+                .filter(|task| task.completed)
+                .map(|task| task.id)
+                .collect();
+            let suffix = if completed.len() == 1 { "" } else { "s" };
+            if dry_run {
+                for &id in &completed {
+                    println!("{}", task_list.find_task(id).unwrap());
+                }
+                println!("Would clear {} completed task{}", completed.len(), suffix);
+                return Ok(());
+            }
+            if !completed.is_empty() && !force {
+                let prompt = format!(
+                    "Move {} completed task{} to the trash?",
+                    completed.len(),
+                    suffix
+                );
+                if !confirm(&prompt)? {
+                    println!("Aborted");
+                    return Ok(());
+                }
+            }
+            for &id in &completed {
+                task_list.trash_task(id);
+            }
+            if !completed.is_empty() {
+                storage.save_list(&list_name, &task_list)?;
+            }
+            println!("Cleared {} completed task{}", completed.len(), suffix);
+            run_remove_hooks(config, &list_name, &task_list, &completed);
+            Ok(())
+        }
+        Commands::Trash {
+            command: TrashCommands::List,
+        } => {
+            let task_list = storage.open_list(&list_name)?;
+            for trashed in task_list.trash.iter().rev() {
+                println!(
+                    "{} (removed {})",
+                    trashed.task,
+                    format_timestamp(trashed.removed_at)
+                );
+            }
+            Ok(())
+        }
+        Commands::Restore { ids } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let mut restored = Vec::new();
+            let mut missing = Vec::new();
+            for id in ids.into_iter().flatten() {
+                if task_list.restore_task(id) {
+                    restored.push(id);
+                } else if !restored.contains(&id) && !missing.contains(&id) {
+                    missing.push(id);
+                }
+            }
+            if !restored.is_empty() {
+                storage.save_list(&list_name, &task_list)?;
+                println!("Restored {}", join_ids(&restored));
+            }
+            if !missing.is_empty() {
+                println!(
+                    "Not in the trash of '{}': {}",
+                    list_name,
+                    join_ids(&missing)
+                );
+            }
+            Ok(())
+        }
+        Commands::Archive { ids, older_than } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let (mut ids, missing) = task_list.select_ids(&ids);
+            if let Some(days) = older_than {
+                for id in task_list.completed_before(days) {
+                    if !ids.contains(&id) {
+                        ids.push(id);
+                    }
+                }
+            }
+            if !ids.is_empty() {
+                storage.archive_tasks(&list_name, &mut task_list, &ids)?;
+            }
+            let suffix = if ids.len() == 1 { "" } else { "s" };
+            println!("Archived {} task{}", ids.len(), suffix);
+            report_missing(missing, &list_name)
+        }
+        Commands::Uncomplete { ids } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let (ids, missing) = task_list.select_ids(&ids);
+            let mut reopened = Vec::new();
+            let mut open = 0;
+            for id in ids {
+                match task_list.get_task(id) {
+                    Some(task) if task.completed => {
+                        task.mark_uncompleted();
+                        reopened.push(id);
+                    }
+                    _ => {
+                        eprintln!("Task {} isn't completed", id);
+                        open += 1;
+                    }
+                }
+            }
+            if !reopened.is_empty() {
+                storage.save_list(&list_name, &task_list)?;
+                println!("Reopened {}", join_ids(&reopened));
+            }
+            report_missing(missing, &list_name)?;
+            // So scripts can tell that nothing was changed.
+            match (reopened.is_empty(), open) {
+                (false, _) | (true, 0) => Ok(()),
+                (true, 1) => Err(TodoError::Usage("1 task wasn't reopened".to_string())),
+                (true, open) => Err(TodoError::Usage(format!("{} tasks weren't reopened", open))),
+            }
+        }
+        Commands::Block { id, on } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            if task_list.find_task(id).is_none() {
+                return Err(TodoError::not_found(id, &list_name));
+            }
+            for &blocker in &on {
+                if blocker == id {
+                    return Err(TodoError::Usage(format!("task {} can't block itself", id)));
+                }
+                if task_list.find_task(blocker).is_none() {
+                    return Err(TodoError::not_found(blocker, &list_name));
+                }
+                if task_list.depends_on(blocker, id) {
+                    return Err(TodoError::Usage(format!(
+                        "task {} already waits for task {}, blocking {} on it would be a cycle",
+                        blocker, id, id
+                    )));
+                }
+            }
+            let task = task_list.get_task(id).unwrap();
+            for blocker in on {
+                if !task.blocked_by.contains(&blocker) {
+                    task.blocked_by.push(blocker);
+                }
+            }
+            let blocked_by = join_ids(&task.blocked_by);
+            storage.save_list(&list_name, &task_list)?;
+            println!("Task {} is blocked by {}", id, blocked_by);
             Ok(())
         }
-        Commands::Remove { id } => {
-            task_list.remove_task(id);
-            save_to_file(&task_list, file_path)
+        Commands::Unblock { id, on } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let Some(task) = task_list.get_task(id) else {
+                return Err(TodoError::not_found(id, &list_name));
+            };
+            task.blocked_by.retain(|blocker| !on.contains(blocker));
+            storage.save_list(&list_name, &task_list)?;
+            println!("Unblocked task {}", id);
+            Ok(())
+        }
+        Commands::Edit {
+            id,
+            new_title,
+            title,
+            due,
+            priority,
+            repeat,
+            estimate,
+        } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let in_editor = new_title.is_none()
+                && title.is_none()
+                && due.is_none()
+                && priority.is_none()
+                && repeat.is_none()
+                && estimate.is_none();
+            let Some(task) = task_list.get_task(id) else {
+                return Err(TodoError::not_found(id, &list_name));
+            };
+            if in_editor {
+                editor::edit_task(task)?;
+            }
+            if let Some(title) = new_title.or(title) {
+                task.title = title;
+            }
+            task.take_contexts();
+            task.due = due.or(task.due);
+            task.priority = priority.or(task.priority);
+            task.repeat = repeat.or(task.repeat);
+            if let Some(estimate) = estimate {
+                task.set_estimate(estimate);
+            }
+            let edited = task.to_string();
+            storage.save_list(&list_name, &task_list)?;
+            println!("{}", edited);
+            Ok(())
+        }
+        Commands::Note { id, text } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let Some(task) = task_list.get_task(id) else {
+                return Err(TodoError::not_found(id, &list_name));
+            };
+            task.append_note(&text);
+            storage.save_list(&list_name, &task_list)?;
+            println!("Added a note to task {}", id);
+            Ok(())
+        }
+        Commands::Show { id, format } => {
+            if format == OutputFormat::Table {
+                return Err(TodoError::Usage(
+                    "show has no table format, use `list --format table`".to_string(),
+                ));
+            }
+            let task_list = storage.open_list(&list_name)?;
+            let json = json || format == OutputFormat::Json;
+            let Some(task) = task_list.find_task(id) else {
+                return Err(TodoError::not_found(id, &list_name));
+            };
+            let subtask_ids: Vec<u32> = task_list.subtasks(id).map(|t| t.id).collect();
+            if json {
+                return print_json(&TaskDetails {
+                    task,
+                    subtasks: subtask_ids,
+                });
+            }
+            task.print_details(&subtask_ids);
+            Ok(())
+        }
+        Commands::Due { id, due } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let Some(task) = task_list.get_task(id) else {
+                return Err(TodoError::not_found(id, &list_name));
+            };
+            task.due = Some(due);
+            storage.save_list(&list_name, &task_list)?;
+            println!("Task {} is due {}", id, due);
+            Ok(())
+        }
+        Commands::Snooze { id, snooze } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let Some(task) = task_list.get_task(id) else {
+                return Err(TodoError::not_found(id, &list_name));
+            };
+            let until = task.snooze(snooze, Local::now().naive_local());
+            let due = task.due;
+            storage.save_list(&list_name, &task_list)?;
+            print!("Snoozed task {} until {}", id, due::format_timestamp(until));
+            match due {
+                Some(due) => println!(", it's due {} now", due),
+                None => println!(),
+            }
+            Ok(())
+        }
+        Commands::Remind { id, at } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let Some(task) = task_list.get_task(id) else {
+                return Err(TodoError::not_found(id, &list_name));
+            };
+            task.remind_at = Some(at);
+            storage.save_list(&list_name, &task_list)?;
+            println!("Will remind you of task {} at {}", id, at);
+            Ok(())
+        }
+        Commands::Tui => Ok(tui::run(storage, &list_name, &renderer)?),
+        Commands::Stats { weeks, sparkline } => {
+            let task_list = storage.open_list(&list_name)?;
+            let archive = storage.open_archive(&list_name)?;
+            let tasks: Vec<&Task> = task_list.tasks.iter().chain(&archive.tasks).collect();
+            let now = Local::now().naive_local();
+            print!("{}", stats::format_stats(&tasks, now, weeks, sparkline));
+            Ok(())
+        }
+        Commands::Report { since } => {
+            let task_list = storage.open_list(&list_name)?;
+            let archive = storage.open_archive(&list_name)?;
+            let tasks: Vec<&Task> = task_list.tasks.iter().chain(&archive.tasks).collect();
+            let now = Local::now().naive_local();
+            print!("{}", stats::format_report(&tasks, now, since));
+            Ok(())
         }
-        Commands::Complete { id } => {
+        Commands::Start { id } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let now = Local::now();
+            if task_list.get_task(id).is_none() {
+                return Err(TodoError::not_found(id, &list_name));
+            }
+            if let Some(session) = task_list.get_task(id).and_then(|t| t.running_session()) {
+                let since = format_timestamp(session.started_at);
+                println!("Already working on task {} since {}", id, since);
+                return Ok(());
+            }
+            for task in &mut task_list.tasks {
+                if let Some(duration) = task.stop_session(now) {
+                    let duration = timesheet::format_duration(duration);
+                    println!("Stopped task {} after {}", task.id, duration);
+                }
+            }
             if let Some(task) = task_list.get_task(id) {
-                task.mark_completed();
-                save_to_file(&task_list, file_path)
+                task.sessions.push(Session::start(now));
+            }
+            storage.save_list(&list_name, &task_list)?;
+            println!("Started task {}", id);
+            Ok(())
+        }
+        Commands::Stop => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let now = Local::now();
+            let mut stopped = false;
+            for task in &mut task_list.tasks {
+                if let Some(duration) = task.stop_session(now) {
+                    let total = timesheet::time_spent(&task.sessions, now);
+                    println!(
+                        "Stopped task {} after {}, {} in total",
+                        task.id,
+                        timesheet::format_duration(duration),
+                        timesheet::format_duration(total)
+                    );
+                    stopped = true;
+                }
+            }
+            if stopped {
+                storage.save_list(&list_name, &task_list)?;
+            } else {
+                println!("No task of '{}' was started", list_name);
+            }
+            Ok(())
+        }
+        Commands::Log { id, duration } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let now = Local::now();
+            let Some(task) = task_list.get_task(id) else {
+                return Err(TodoError::not_found(id, &list_name));
+            };
+            task.sessions.push(Session::logged(duration, now));
+            let total = timesheet::time_spent(&task.sessions, now);
+            storage.save_list(&list_name, &task_list)?;
+            println!(
+                "Logged {} on task {}, {} in total",
+                timesheet::format_duration(duration),
+                id,
+                timesheet::format_duration(total)
+            );
+            Ok(())
+        }
+        Commands::Timesheet { week } => {
+            let task_list = storage.open_list(&list_name)?;
+            let archive = storage.open_archive(&list_name)?;
+            let tasks: Vec<&Task> = task_list.tasks.iter().chain(&archive.tasks).collect();
+            let now = Local::now();
+            let today = now.date_naive();
+            let days: Vec<NaiveDate> = if week {
+                let monday = today.week(chrono::Weekday::Mon).first_day();
+                monday.iter_days().take(7).collect()
+            } else {
+                vec![today]
+            };
+            if week {
+                println!("Week of {}", Due::On(days[0]));
+            }
+            print!("{}", timesheet::format_timesheet(&tasks, &days, now));
+            Ok(())
+        }
+        Commands::Agenda { week, month } => {
+            let task_list = storage.open_list(&list_name)?;
+            let today = Local::now().date_naive();
+            let first = if month {
+                today.with_day(1).unwrap_or(today)
+            } else if week {
+                today.week(chrono::Weekday::Mon).first_day()
+            } else {
+                today
+            };
+            let last = if month {
+                first + chrono::Months::new(1) - chrono::Duration::days(1)
+            } else {
+                first + chrono::Duration::days(6)
+            };
+            if month {
+                print!(
+                    "{}",
+                    agenda::format_month(&renderer, &task_list, first, today)
+                );
+                println!();
+            }
+            let days: Vec<NaiveDate> = first.iter_days().take_while(|&day| day <= last).collect();
+            agenda::print_days(&renderer, &task_list, &days, today);
+            Ok(())
+        }
+        Commands::Contexts => {
+            let task_list = storage.open_list(&list_name)?;
+            let mut contexts: BTreeMap<&str, usize> = BTreeMap::new();
+            for task in &task_list.tasks {
+                for context in &task.contexts {
+                    *contexts.entry(context).or_default() += usize::from(!task.completed);
+                }
+            }
+            if contexts.is_empty() {
+                println!("No task of '{}' has a context", list_name);
+            }
+            for (context, open) in contexts {
+                let suffix = if open == 1 { "" } else { "s" };
+                println!("@{}: {} open task{}", context, open, suffix);
+            }
+            Ok(())
+        }
+        Commands::Next { count } => {
+            let task_list = storage.open_list(&list_name)?;
+            let suggestions = next::suggestions(&task_list, Local::now().naive_local(), count);
+            if suggestions.is_empty() {
+                println!("Nothing to work on in '{}'", list_name);
+            }
+            for task in suggestions {
+                println!("{}", renderer.summary_line(&task_list, task));
+            }
+            Ok(())
+        }
+        Commands::Notify => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let now = Local::now().naive_local();
+            let mut notified = 0;
+            for task in &mut task_list.tasks {
+                if let Some(body) = task.pending_notification(now) {
+                    notification::send(&task.title, &body)?;
+                    println!("{}: {}", task, body);
+                    task.notified_at = Some(now);
+                    notified += 1;
+                }
+            }
+            if notified > 0 {
+                storage.save_list(&list_name, &task_list)?;
+            }
+            Ok(())
+        }
+        Commands::Prioritize { id, priority } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let Some(task) = task_list.get_task(id) else {
+                return Err(TodoError::not_found(id, &list_name));
+            };
+            task.priority = Some(priority);
+            storage.save_list(&list_name, &task_list)?;
+            println!("Task {} has {} priority", id, priority);
+            Ok(())
+        }
+        Commands::Tag { id, tag } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            match task_list.get_task(id).map(|task| task.add_tag(tag.clone())) {
+                Some(true) => {
+                    storage.save_list(&list_name, &task_list)?;
+                    println!("Tagged task {} with #{}", id, tag);
+                }
+                Some(false) => println!("Task {} is already tagged with #{}", id, tag),
+                None => return Err(TodoError::not_found(id, &list_name)),
+            }
+            Ok(())
+        }
+        Commands::Untag { id, tag } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            match task_list.get_task(id).map(|task| task.remove_tag(&tag)) {
+                Some(true) => {
+                    storage.save_list(&list_name, &task_list)?;
+                    println!("Removed #{} from task {}", tag, id);
+                }
+                Some(false) => {
+                    return Err(TodoError::Usage(format!(
+                        "task {} isn't tagged with #{}",
+                        id, tag
+                    )))
+                }
+                None => return Err(TodoError::not_found(id, &list_name)),
+            }
+            Ok(())
+        }
+        Commands::Attach { id, url } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            match task_list
+                .get_task(id)
+                .map(|task| task.add_link(url.clone()))
+            {
+                Some(true) => {
+                    storage.save_list(&list_name, &task_list)?;
+                    println!("Attached {} to task {}", url, id);
+                }
+                Some(false) => println!("{} is already attached to task {}", url, id),
+                None => return Err(TodoError::not_found(id, &list_name)),
+            }
+            Ok(())
+        }
+        Commands::Detach { id, url } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            match task_list.get_task(id).map(|task| task.remove_link(&url)) {
+                Some(true) => {
+                    storage.save_list(&list_name, &task_list)?;
+                    println!("Removed {} from task {}", url, id);
+                }
+                Some(false) => println!("{} isn't attached to task {}", url, id),
+                None => return Err(TodoError::not_found(id, &list_name)),
+            }
+            Ok(())
+        }
+        Commands::Shorten { id } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let Some(task) = task_list.get_task(id) else {
+                return Err(TodoError::not_found(id, &list_name));
+            };
+            let long_links: Vec<_> = task
+                .links
+                .iter_mut()
+                .filter(|link| link.short_url.is_none())
+                .collect();
+            if long_links.is_empty() {
+                println!("Task {} has no links to shorten", id);
+                return Ok(());
+            }
+            let shortener = Shortener::new(&config.shortener)?;
+            let mut shortened = 0;
+            let mut result = Ok(());
+            for link in long_links {
+                match shortener.shorten(&link.url) {
+                    Ok(short_url) => {
+                        println!("{} -> {}", short_url, link.url);
+                        link.short_url = Some(short_url);
+                        shortened += 1;
+                    }
+                    // The links shortened before the failure are kept.
+                    Err(error) => {
+                        result = Err(error);
+                        break;
+                    }
+                }
+            }
+            if shortened > 0 {
+                storage.save_list(&list_name, &task_list)?;
+                let suffix = if shortened == 1 { "" } else { "s" };
+                println!("Shortened {} link{} of task {}", shortened, suffix, id);
+            }
+            Ok(result?)
+        }
+        Commands::Lists { command: None } => {
+            for name in storage.list_names()? {
+                let task_list = storage.open_list(&name)?;
+                let task_count = task_list.list_tasks().len();
+                let suffix = if task_count == 1 { "" } else { "s" };
+                let marker = if name == list_name { " (current)" } else { "" };
+                println!("{}: {} task{}{}", name, task_count, suffix, marker);
+            }
+            Ok(())
+        }
+        Commands::Lists {
+            command: Some(ListsCommands::Create { name }),
+        } => {
+            storage.create_list(&name)?;
+            println!("Created list '{}'", name);
+            Ok(())
+        }
+        Commands::Lists {
+            command: Some(ListsCommands::Delete { name, force }),
+        } => {
+            let task_count = storage.open_list(&name)?.list_tasks().len();
+            if task_count > 0 && !force {
+                let prompt = format!(
+                    "List '{}' contains {} task(s). Delete it?",
+                    name, task_count
+                );
+                if !confirm(&prompt)? {
+                    println!("Aborted");
+                    return Ok(());
+                }
+            }
+            storage.delete_list(&name)?;
+            println!("Deleted list '{}'", name);
+            Ok(())
+        }
+        Commands::Renumber => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let mut archive = storage.open_archive(&list_name)?;
+            let changes = task_list.renumber(&mut archive);
+            if changes.is_empty() {
+                println!("The IDs of '{}' have no gaps", list_name);
+                return Ok(());
+            }
+            storage.save_list(&list_name, &task_list)?;
+            storage.save_archive(&list_name, &archive)?;
+            for (old_id, new_id) in changes {
+                println!("{} → {}", old_id, new_id);
+            }
+            Ok(())
+        }
+        Commands::Move { id, before, .. } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            for id in std::iter::once(id).chain(before) {
+                if task_list.find_task(id).is_none() {
+                    return Err(TodoError::not_found(id, &list_name));
+                }
+            }
+            if before == Some(id) {
+                return Err(TodoError::Usage(format!(
+                    "task {} can't be moved in front of itself",
+                    id
+                )));
+            }
+            task_list.reorder_task(id, before);
+            storage.save_list(&list_name, &task_list)?;
+            match before {
+                Some(before) => println!("Moved task {} in front of task {}", id, before),
+                None => println!("Moved task {} to the top", id),
+            }
+            Ok(())
+        }
+        Commands::MoveTask { id, to_list } => {
+            match storage.move_task(&list_name, &to_list, id)? {
+                Some(new_id) => println!("Moved task {} to '{}' as task {}", id, to_list, new_id),
+                None => return Err(TodoError::not_found(id, &list_name)),
+            }
+            Ok(())
+        }
+        Commands::Export { format, output } => {
+            let task_list = storage.open_list(&list_name)?;
+            let tasks: Vec<&Task> = task_list.list_tasks().iter().collect();
+            let exported = match format {
+                ExportFormat::Todotxt => tasks
+                    .iter()
+                    .map(|task| todotxt::format_task(task) + "\n")
+                    .collect(),
+                ExportFormat::Markdown => markdown::format_list(&list_name, &tasks),
+                ExportFormat::Ics => ical::format_calendar(&list_name, &tasks),
+            };
+            match output {
+                Some(file) => {
+                    fs::write(&file, exported)?;
+                    println!("Exported {} to {}", list_name, file.display());
+                }
+                None => print!("{}", exported),
+            }
+            Ok(())
+        }
+        Commands::Import {
+            format,
+            file,
+            from,
+            repo,
+        } => {
+            let mut task_list = storage.open_list(&list_name)?;
+            let tasks: Vec<Task> = match (from, format, file) {
+                (Some(IssueService::Github), _, _) => {
+                    let repo = repo.unwrap_or_default();
+                    github::fetch_assigned_issues(&config.github, &repo)?
+                }
+                (None, Some(format), Some(file)) => {
+                    let contents =
+                        fs::read_to_string(&file).map_err(|error| with_path(error, &file))?;
+                    match format {
+                        ImportFormat::Todotxt => {
+                            contents.lines().filter_map(todotxt::parse_task).collect()
+                        }
+                        ImportFormat::Csv => csv::parse_tasks(&contents).map_err(|error| {
+                            Error::new(
+                                ErrorKind::InvalidData,
+                                format!("{}: {}", file.display(), error),
+                            )
+                        })?,
+                    }
+                }
+                _ => unreachable!("clap requires a file and its format without --from"),
+            };
+            let imported: HashSet<String> = task_list
+                .tasks
+                .iter()
+                .chain(task_list.trash.iter().map(|trashed| &trashed.task))
+                .filter_map(|task| task.source.clone())
+                .collect();
+            let (tasks, known): (Vec<Task>, Vec<Task>) = tasks.into_iter().partition(|task| {
+                task.source
+                    .as_ref()
+                    .is_none_or(|source| !imported.contains(source))
+            });
+            let task_count = tasks.len();
+            for task in tasks {
+                task_list.insert_task(task);
+            }
+            if task_count > 0 {
+                storage.save_list(&list_name, &task_list)?;
+            }
+            let suffix = if task_count == 1 { "" } else { "s" };
+            println!(
+                "Imported {} task{} into '{}'",
+                task_count, suffix, list_name
+            );
+            if !known.is_empty() {
+                let suffix = if known.len() == 1 { "" } else { "s" };
+                println!("Skipped {} issue{} imported before", known.len(), suffix);
+            }
+            Ok(())
+        }
+        Commands::Merge { other_file } => {
+            let task_list = storage.open_list(&list_name)?;
+            let other = JsonFiles::new(data_dir.to_path_buf()).read_file(&other_file)?;
+            let (merged, report) = sync::combine(&task_list, &other);
+            if !report.added.is_empty() || !report.updated.is_empty() {
+                storage.save_list(&list_name, &merged)?;
+            }
+            if !report.added.is_empty() {
+                println!("Added {}", join_ids(&report.added));
+            }
+            if !report.updated.is_empty() {
+                println!("Updated {}", join_ids(&report.updated));
+            }
+            let suffix = if report.skipped == 1 { "" } else { "s" };
+            println!(
+                "Skipped {} task{}, no newer than here",
+                report.skipped, suffix
+            );
+            Ok(())
+        }
+        Commands::Sync { strategy } => {
+            let strategy = strategy.unwrap_or(config.sync.strategy);
+            let outcome = sync::sync(data_dir, storage, &config.sync, &list_name, strategy)?;
+            if outcome.pulled == 0 && outcome.pushed == 0 {
+                println!("'{}' is in sync", list_name);
             } else {
-                Ok(())
+                let suffix = if outcome.pulled == 1 { "" } else { "s" };
+                println!(
+                    "Synced '{}': received {} change{}, sent {}",
+                    list_name, outcome.pulled, suffix, outcome.pushed
+                );
+            }
+            Ok(())
+        }
+        Commands::CompleteIds => {
+            let task_list = storage.open_list(&list_name)?;
+            for task in task_list.list_tasks().iter().filter(|task| !task.completed) {
+                println!("{}\t{}", task.id, task.title);
             }
+            Ok(())
+        }
+        Commands::Completions { .. } => {
+            unreachable!("completions are printed before opening the storage")
+        }
+        Commands::Undo { .. } => unreachable!("undo is handled before running other commands"),
+        Commands::Today | Commands::Overdue | Commands::Week => {
+            unreachable!("shortcuts are run as list")
+        }
+        Commands::History { .. } | Commands::Revert { .. } => {
+            unreachable!("the history is handled before opening the storage")
+        }
+        Commands::Pomodoro { .. } => {
+            unreachable!("pomodoros are run before locking the data directory")
         }
     }
 }
@@ -0,0 +1,60 @@
+use crate::Task;
+
+/// Formats tasks as a Markdown checklist with a section per tag, like:
+///
+/// ```markdown
+/// # work
+///
+/// ## #reports
+///
+/// - [x] Write the report (due Fri 2024-05-17)
+/// ```
+///
+/// Tasks with several tags show up in each of their sections, and untagged tasks come last.
+pub fn format_list(list_name: &str, tasks: &[&Task]) -> String {
+    let mut tags: Vec<&str> = tasks
+        .iter()
+        .flat_map(|task| task.tags.iter().map(String::as_str))
+        .collect();
+    tags.sort();
+    tags.dedup();
+
+    let mut markdown = format!("# {}\n", list_name);
+    for tag in tags {
+        let tagged: Vec<&Task> = tasks.iter().copied().filter(|t| t.has_tag(tag)).collect();
+        write_section(&mut markdown, &format!("#{}", tag), &tagged);
+    }
+    let untagged: Vec<&Task> = tasks
+        .iter()
+        .copied()
+        .filter(|t| t.tags.is_empty())
+        .collect();
+    if !untagged.is_empty() {
+        write_section(&mut markdown, "Untagged", &untagged);
+    }
+    markdown
+}
+
+fn write_section(markdown: &mut String, heading: &str, tasks: &[&Task]) {
+    markdown.push_str(&format!("\n## {}\n\n", heading));
+    for task in tasks {
+        let check = if task.completed { "x" } else { " " };
+        markdown.push_str(&format!("- [{}] {}", check, escape(&task.title)));
+        if let Some(due) = task.due {
+            markdown.push_str(&format!(" (due {})", due));
+        }
+        markdown.push('\n');
+    }
+}
+
+/// Escapes the characters that would turn parts of a title into Markdown formatting.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '#') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
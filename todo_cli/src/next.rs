@@ -0,0 +1,98 @@
+use crate::{Priority, Task, TaskList};
+use chrono::NaiveDateTime;
+
+// Points for what makes a task worth working on next, added up into its score.
+const HIGH_PRIORITY_POINTS: u32 = 30;
+const MEDIUM_PRIORITY_POINTS: u32 = 20;
+const LOW_PRIORITY_POINTS: u32 = 10;
+const OVERDUE_POINTS: u32 = 40;
+/// For a task due right now, less for every day it's due later, down to none two weeks out.
+const DUE_POINTS: u32 = 28;
+/// For every open task that waits for the task.
+const BLOCKING_POINTS: u32 = 5;
+
+/// How much a task is worth working on next, from its priority, how soon it's due, and how
+/// many open tasks wait for it to be completed.
+pub fn score(task: &Task, blocking: usize, now: NaiveDateTime) -> u32 {
+    let priority = match task.priority {
+        Some(Priority::High) => HIGH_PRIORITY_POINTS,
+        Some(Priority::Medium) => MEDIUM_PRIORITY_POINTS,
+        Some(Priority::Low) => LOW_PRIORITY_POINTS,
+        None => 0,
+    };
+    let due = match task.due.map(|due| due.deadline()) {
+        Some(deadline) if deadline < now => OVERDUE_POINTS,
+        Some(deadline) => {
+            let days = u32::try_from((deadline - now).num_days()).unwrap_or(u32::MAX);
+            DUE_POINTS.saturating_sub(days.saturating_mul(2))
+        }
+        None => 0,
+    };
+    let blocking = u32::try_from(blocking).unwrap_or(u32::MAX);
+    priority + due + blocking.saturating_mul(BLOCKING_POINTS)
+}
+
+/// The `count` open tasks with the highest scores, highest first and by ID when they're tied.
+/// Tasks that are blocked by open tasks can't be worked on yet, and are left out like snoozed
+/// ones.
+pub fn suggestions(task_list: &TaskList, now: NaiveDateTime, count: usize) -> Vec<&Task> {
+    let mut scored: Vec<(u32, &Task)> = task_list
+        .tasks
+        .iter()
+        .filter(|task| !task.completed && !task.is_snoozed(now))
+        .filter(|task| task_list.open_blockers(task).is_empty())
+        .map(|task| {
+            let blocking = task_list
+                .tasks
+                .iter()
+                .filter(|other| !other.completed && other.blocked_by.contains(&task.id))
+                .count();
+            (score(task, blocking, now), task)
+        })
+        .collect();
+    scored.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then(a.id.cmp(&b.id)));
+    scored
+        .into_iter()
+        .take(count)
+        .map(|(_, task)| task)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::due::Due;
+    use chrono::{Duration, NaiveDate};
+
+    #[test]
+    fn suggests_important_urgent_and_blocking_tasks_first() {
+        let now = NaiveDate::from_ymd_opt(2024, 5, 17)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        let mut task_list = TaskList::new();
+        task_list.add_task("someday".to_string());
+        task_list.add_task("important".to_string()).priority = Some(Priority::High);
+        task_list.add_task("overdue".to_string()).due = Some(Due::At(now - Duration::hours(1)));
+        task_list.add_task("blocker".to_string());
+        let blocked = task_list.add_task("blocked".to_string());
+        blocked.blocked_by.push(4);
+        blocked.due = Some(Due::At(now));
+        task_list.add_task("done".to_string()).completed = true;
+        task_list.add_task("tomorrow".to_string()).due = Some(Due::At(now + Duration::days(1)));
+
+        let titles: Vec<&str> = suggestions(&task_list, now, 10)
+            .iter()
+            .map(|task| task.title.as_str())
+            .collect();
+        assert_eq!(
+            titles,
+            ["overdue", "important", "tomorrow", "blocker", "someday"]
+        );
+        assert_eq!(suggestions(&task_list, now, 2).len(), 2);
+
+        let task = &task_list.tasks[6];
+        assert_eq!(score(task, 0, now), DUE_POINTS - 2);
+        assert_eq!(score(task, 2, now), DUE_POINTS - 2 + 2 * BLOCKING_POINTS);
+    }
+}
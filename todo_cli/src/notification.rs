@@ -0,0 +1,54 @@
+use std::{io::Error, process::Command};
+
+/// Shows a desktop notification through the notifier of the platform, `notify-send` on Linux
+/// and the BSDs and `osascript` on macOS.
+pub fn send(summary: &str, body: &str) -> Result<(), Error> {
+    let mut command = notifier_command(summary, body)?;
+    let status = command.status().map_err(|error| {
+        Error::new(
+            error.kind(),
+            format!("failed to run {:?}: {}", command.get_program(), error),
+        )
+    })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!(
+            "{:?} failed with {}",
+            command.get_program(),
+            status
+        )))
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn notifier_command(summary: &str, body: &str) -> Result<Command, Error> {
+    let mut command = Command::new("osascript");
+    // The texts are passed as arguments, so they don't need quoting inside the script.
+    command.args([
+        "-e",
+        "on run argv",
+        "-e",
+        "display notification (item 2 of argv) with title (item 1 of argv)",
+        "-e",
+        "end run",
+        summary,
+        body,
+    ]);
+    Ok(command)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn notifier_command(summary: &str, body: &str) -> Result<Command, Error> {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name", "todo", "--", summary, body]);
+    Ok(command)
+}
+
+#[cfg(not(unix))]
+fn notifier_command(_summary: &str, _body: &str) -> Result<Command, Error> {
+    Err(Error::new(
+        std::io::ErrorKind::Unsupported,
+        "desktop notifications aren't supported on this platform",
+    ))
+}
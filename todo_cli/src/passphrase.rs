@@ -0,0 +1,77 @@
+use common::terminal;
+use std::{
+    env,
+    io::{self, Error, ErrorKind, IsTerminal},
+    process::Command,
+};
+
+/// The service the passphrase is stored under in the keyring.
+const KEYRING_SERVICE: &str = "todo_cli";
+
+/// Gets the passphrase of encrypted lists from `TODO_PASSPHRASE`, from the keyring, or by
+/// asking for it. A new passphrase is asked for twice, to catch typos.
+pub fn read(is_new: bool) -> Result<String, Error> {
+    if let Some(passphrase) = env::var("TODO_PASSPHRASE").ok().filter(|p| !p.is_empty()) {
+        return Ok(passphrase);
+    }
+    if let Some(passphrase) = from_keyring() {
+        return Ok(passphrase);
+    }
+    let passphrase = prompt("Passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "the passphrase is empty",
+        ));
+    }
+    if is_new && prompt("Repeat the passphrase: ")? != passphrase {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "the passphrases don't match",
+        ));
+    }
+    Ok(passphrase)
+}
+
+/// Looks the passphrase up in the keyring. Having one is optional, so any failure means the
+/// passphrase isn't there.
+fn from_keyring() -> Option<String> {
+    let output = keyring_command()?.output().ok()?;
+    let passphrase = String::from_utf8(output.stdout).ok()?;
+    let passphrase = passphrase.trim_end_matches(['\r', '\n']);
+    (output.status.success() && !passphrase.is_empty()).then(|| passphrase.to_string())
+}
+
+/// Stored with `security add-generic-password -s todo_cli -a todo_cli -w`.
+#[cfg(target_os = "macos")]
+fn keyring_command() -> Option<Command> {
+    let mut command = Command::new("security");
+    command.args(["find-generic-password", "-s", KEYRING_SERVICE, "-w"]);
+    Some(command)
+}
+
+/// Stored with `secret-tool store --label todo_cli service todo_cli`.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn keyring_command() -> Option<Command> {
+    let mut command = Command::new("secret-tool");
+    command.args(["lookup", "service", KEYRING_SERVICE]);
+    Some(command)
+}
+
+#[cfg(not(unix))]
+fn keyring_command() -> Option<Command> {
+    let _ = KEYRING_SERVICE;
+    None
+}
+
+/// Asks on the terminal, without showing what's typed.
+fn prompt(text: &str) -> Result<String, Error> {
+    if !io::stdin().is_terminal() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "a passphrase is needed, set TODO_PASSPHRASE or store it in the keyring when not \
+            running in a terminal",
+        ));
+    }
+    terminal::read_hidden(text)
+}
@@ -0,0 +1,115 @@
+use crate::{
+    error::TodoError, journal, notification, storage, storage::Storage, timesheet,
+    timesheet::Session,
+};
+use chrono::{Duration, Local};
+use std::{
+    io::{self, Error, IsTerminal, Write},
+    path::Path,
+    thread,
+};
+
+/// What a pomodoro is run on, from the `pomodoro` command.
+pub struct Pomodoro<'a> {
+    pub list_name: &'a str,
+    pub id: u32,
+    pub work_minutes: u32,
+    pub break_minutes: u32,
+    /// The command line, for the journal.
+    pub operation: &'a str,
+}
+
+/// Counts down the work interval and logs it as a session of the task, then counts down the
+/// break, with a notification at the end of each. Stopping with Ctrl-C logs nothing.
+///
+/// The data directory is only locked while the session is saved, rather than for the whole
+/// pomodoro, so other commands can be used meanwhile.
+pub fn run(data_dir: &Path, storage: &dyn Storage, pomodoro: &Pomodoro) -> Result<(), TodoError> {
+    let (list_name, id) = (pomodoro.list_name, pomodoro.id);
+    let task_list = storage.open_list(list_name)?;
+    let Some(task) = task_list.find_task(id) else {
+        return Err(TodoError::not_found(id, list_name));
+    };
+    let title = task.title.clone();
+    let suffix = if pomodoro.work_minutes == 1 { "" } else { "s" };
+    println!(
+        "Working on task {}: {} for {} minute{}",
+        id, title, pomodoro.work_minutes, suffix
+    );
+    let started_at = Local::now();
+    count_down("Work", pomodoro.work_minutes)?;
+
+    let total = {
+        let _lock = storage::lock_dir(data_dir)?;
+        let before = journal::snapshot(storage, &[list_name])?;
+        let mut task_list = storage.open_list(list_name)?;
+        let now = Local::now();
+        let Some(task) = task_list.get_task(id) else {
+            return Err(TodoError::not_found(id, list_name));
+        };
+        task.sessions.push(Session {
+            started_at,
+            stopped_at: Some(now),
+        });
+        let total = timesheet::time_spent(&task.sessions, now);
+        storage.save_list(list_name, &task_list)?;
+        journal::record(data_dir, storage, pomodoro.operation, before)?;
+        total
+    };
+    let work = Duration::minutes(pomodoro.work_minutes.into());
+    println!(
+        "Logged {} on task {}, {} in total",
+        timesheet::format_duration(work),
+        id,
+        timesheet::format_duration(total)
+    );
+
+    if pomodoro.break_minutes == 0 {
+        notify("Pomodoro done", &title);
+        return Ok(());
+    }
+    let break_text = format!("Time for a {} minute break", pomodoro.break_minutes);
+    notify(&format!("Pomodoro done: {}", title), &break_text);
+    println!("{}", break_text);
+    count_down("Break", pomodoro.break_minutes)?;
+    notify("Break is over", &format!("Back to {}", title));
+    println!("Break is over");
+    Ok(())
+}
+
+/// Waits for the interval, showing the time left on a line that's redrawn every second when
+/// printing to a terminal.
+fn count_down(label: &str, minutes: u32) -> Result<(), Error> {
+    let end = Local::now() + Duration::minutes(minutes.into());
+    let terminal = io::stdout().is_terminal();
+    loop {
+        let left = end - Local::now();
+        if left <= Duration::zero() {
+            break;
+        }
+        if terminal {
+            let seconds = left.num_seconds() + 1;
+            print!(
+                "\r\x1b[K{} {:02}:{:02} left",
+                label,
+                seconds / 60,
+                seconds % 60
+            );
+            io::stdout().flush()?;
+        }
+        let step = left.min(Duration::seconds(1));
+        thread::sleep(step.to_std().unwrap_or_default());
+    }
+    if terminal {
+        print!("\r\x1b[K");
+        io::stdout().flush()?;
+    }
+    Ok(())
+}
+
+/// Notifications are a nicety here, so failing to show one doesn't stop the pomodoro.
+fn notify(summary: &str, body: &str) {
+    if let Err(error) = notification::send(summary, body) {
+        eprintln!("Couldn't show a notification: {}", error);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::due::Due;
+use chrono::{Days, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unit {
+    Day,
+    Week,
+    Month,
+}
+
+/// How often a task comes back, like `daily` or `every 3 days`. It's stored in the same form
+/// as it's written.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(into = "String", try_from = "String")]
+pub struct Recurrence {
+    count: u32,
+    unit: Unit,
+}
+
+impl Recurrence {
+    /// The due date the occurrences of a task due at `due` are counted from: the one of the
+    /// first occurrence, as long as `due` is still one of its occurrences, since it may have been
+    /// changed since. Otherwise `due` itself, or today for tasks without a due date.
+    pub fn anchor(&self, first_due: Option<Due>, due: Option<Due>, today: NaiveDate) -> Due {
+        let due = due.unwrap_or(Due::On(today));
+        let is_occurrence = |first: &Due| {
+            (0..)
+                .map_while(|n| self.occurrence(*first, n))
+                .take_while(|occurrence| occurrence.start() <= due.start())
+                .any(|occurrence| occurrence == due)
+        };
+        first_due.filter(is_occurrence).unwrap_or(due)
+    }
+
+    /// The due date of the next occurrence after a task due at `due` was completed on `today`,
+    /// counted from its [`anchor`](Self::anchor). Counting every occurrence from the anchor
+    /// rather than from the one before keeps monthly tasks on their day, so a task due on the
+    /// 31st comes back on the 31st after February. Occurrences that were missed are skipped, so
+    /// the next one is after today.
+    pub fn next_due(&self, anchor: Due, due: Option<Due>, today: NaiveDate) -> Due {
+        let after = due.unwrap_or(anchor).start();
+        (1..)
+            .map_while(|n| self.occurrence(anchor, n))
+            .find(|next| next.start() > after && next.start().date() > today)
+            .unwrap_or(Due::On(NaiveDate::MAX))
+    }
+
+    /// The iCalendar `RRULE` value, like `FREQ=DAILY;INTERVAL=3`.
+    pub fn to_rrule(self) -> String {
+        let frequency = match self.unit {
+            Unit::Day => "DAILY",
+            Unit::Week => "WEEKLY",
+            Unit::Month => "MONTHLY",
+        };
+        format!("FREQ={};INTERVAL={}", frequency, self.count)
+    }
+
+    /// The `rec:` form of todo.txt tools, like `3d`, `1w` or `1m`.
+    pub fn to_todotxt(self) -> String {
+        let unit = match self.unit {
+            Unit::Day => 'd',
+            Unit::Week => 'w',
+            Unit::Month => 'm',
+        };
+        format!("{}{}", self.count, unit)
+    }
+
+    /// Parses the `rec:` form. A leading `+`, which makes some tools count from the due date
+    /// rather than the completion date, is ignored since tasks always count from the due date.
+    pub fn from_todotxt(input: &str) -> Option<Self> {
+        let input = input.strip_prefix('+').unwrap_or(input);
+        let unit = match input.chars().last()? {
+            'd' => Unit::Day,
+            'w' => Unit::Week,
+            'm' => Unit::Month,
+            _ => return None,
+        };
+        let count = input[..input.len() - 1]
+            .parse()
+            .ok()
+            .filter(|&count| count > 0)?;
+        Some(Recurrence { count, unit })
+    }
+
+    /// The `n`th occurrence after the one due at `anchor`, or `None` once it's past the dates
+    /// chrono can represent.
+    fn occurrence(&self, anchor: Due, n: u32) -> Option<Due> {
+        let count = self.count.checked_mul(n)?;
+        let advance_date = |date: NaiveDate| match self.unit {
+            Unit::Day => date.checked_add_days(Days::new(count.into())),
+            Unit::Week => date.checked_add_days(Days::new(u64::from(count) * 7)),
+            // Days missing from shorter months become their last day, like the 31st becomes
+            // the 30th of April.
+            Unit::Month => date.checked_add_months(Months::new(count)),
+        };
+        Some(match anchor {
+            Due::At(date_time) => {
+                Due::At(advance_date(date_time.date())?.and_time(date_time.time()))
+            }
+            Due::On(date) => Due::On(advance_date(date)?),
+        })
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (single, name) = match self.unit {
+            Unit::Day => ("daily", "day"),
+            Unit::Week => ("weekly", "week"),
+            Unit::Month => ("monthly", "month"),
+        };
+        if self.count == 1 {
+            f.write_str(single)
+        } else {
+            write!(f, "every {} {}s", self.count, name)
+        }
+    }
+}
+
+/// Parses `daily`, `weekly`, `monthly`, or `every <n> days`, `weeks` or `months`.
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim().to_lowercase();
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let (count, unit) = match words.as_slice() {
+            ["daily"] => (1, "day"),
+            ["weekly"] => (1, "week"),
+            ["monthly"] => (1, "month"),
+            ["every", unit] => (1, *unit),
+            ["every", count, unit] => (count.parse().map_err(|_| invalid())?, *unit),
+            _ => return Err(invalid()),
+        };
+        let unit = match unit.strip_suffix('s').unwrap_or(unit) {
+            "day" => Unit::Day,
+            "week" => Unit::Week,
+            "month" => Unit::Month,
+            _ => return Err(invalid()),
+        };
+        if count == 0 {
+            return Err(invalid());
+        }
+        Ok(Recurrence { count, unit })
+    }
+}
+
+fn invalid() -> String {
+    "expected daily, weekly, monthly or every <n> days, weeks or months".to_string()
+}
+
+impl From<Recurrence> for String {
+    fn from(recurrence: Recurrence) -> Self {
+        recurrence.to_string()
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parses_and_formats_rules() {
+        for rule in [
+            "daily",
+            "weekly",
+            "monthly",
+            "every 3 days",
+            "every 2 months",
+        ] {
+            assert_eq!(rule.parse::<Recurrence>().unwrap().to_string(), rule);
+        }
+        assert_eq!(
+            "Every 1 Week".parse::<Recurrence>().unwrap().to_string(),
+            "weekly"
+        );
+        assert_eq!(
+            "every day".parse::<Recurrence>().unwrap().to_string(),
+            "daily"
+        );
+        assert!("every 0 days".parse::<Recurrence>().is_err());
+        assert!("every few days".parse::<Recurrence>().is_err());
+        assert!("hourly".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn advances_the_due_date_by_the_interval() {
+        let today = date(2024, 5, 17);
+        let every_3_days: Recurrence = "every 3 days".parse().unwrap();
+        assert_eq!(
+            every_3_days.next_due(Due::On(today), Some(Due::On(today)), today),
+            Due::On(date(2024, 5, 20))
+        );
+
+        let weekly: Recurrence = "weekly".parse().unwrap();
+        let friday_5pm = Due::At(today.and_hms_opt(17, 0, 0).unwrap());
+        assert_eq!(
+            weekly.next_due(friday_5pm, Some(friday_5pm), today),
+            Due::At(date(2024, 5, 24).and_hms_opt(17, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn keeps_monthly_tasks_on_their_day_after_shorter_months() {
+        let monthly: Recurrence = "monthly".parse().unwrap();
+        let first_due = Due::On(date(2024, 1, 31));
+        let mut due = first_due;
+        let mut dues = Vec::new();
+        for _ in 0..3 {
+            let anchor = monthly.anchor(Some(first_due), Some(due), due.start().date());
+            due = monthly.next_due(anchor, Some(due), due.start().date());
+            dues.push(due);
+        }
+        let expected = [date(2024, 2, 29), date(2024, 3, 31), date(2024, 4, 30)];
+        assert_eq!(dues, expected.map(Due::On));
+
+        // A due date that was changed since is counted from instead.
+        let moved = Due::On(date(2024, 3, 15));
+        let anchor = monthly.anchor(Some(first_due), Some(moved), date(2024, 3, 15));
+        assert_eq!(anchor, moved);
+        assert_eq!(
+            monthly.next_due(anchor, Some(moved), date(2024, 3, 15)),
+            Due::On(date(2024, 4, 15))
+        );
+    }
+
+    #[test]
+    fn keeps_monthly_tasks_within_shorter_months() {
+        let monthly: Recurrence = "monthly".parse().unwrap();
+        let today = date(2024, 1, 31);
+        assert_eq!(
+            monthly.next_due(Due::On(today), Some(Due::On(today)), today),
+            Due::On(date(2024, 2, 29))
+        );
+    }
+
+    #[test]
+    fn skips_missed_occurrences() {
+        let may_1 = Due::On(date(2024, 5, 1));
+        let daily: Recurrence = "daily".parse().unwrap();
+        assert_eq!(
+            daily.next_due(may_1, Some(may_1), date(2024, 5, 17)),
+            Due::On(date(2024, 5, 18))
+        );
+
+        let weekly: Recurrence = "weekly".parse().unwrap();
+        assert_eq!(
+            weekly.next_due(may_1, Some(may_1), date(2024, 5, 17)),
+            Due::On(date(2024, 5, 22))
+        );
+    }
+
+    #[test]
+    fn counts_from_today_without_a_due_date() {
+        let weekly: Recurrence = "weekly".parse().unwrap();
+        let today = date(2024, 5, 17);
+        let anchor = weekly.anchor(None, None, today);
+        assert_eq!(
+            weekly.next_due(anchor, None, today),
+            Due::On(date(2024, 5, 24))
+        );
+    }
+}
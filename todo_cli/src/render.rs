@@ -0,0 +1,256 @@
+use crate::{due, join_ids, Priority, Task, TaskList};
+use chrono::{DateTime, Local, NaiveDateTime};
+use common::dates::format_relative;
+use std::env;
+
+/// ANSI styles, as the parameters of a `ESC [ … m` sequence.
+const COMPLETED_STYLE: &str = "2;9";
+pub const OVERDUE_STYLE: &str = "31";
+pub const DUE_TODAY_STYLE: &str = "33";
+pub const HEADER_STYLE: &str = "1";
+
+const COLUMN_COUNT: usize = 7;
+const TABLE_HEADERS: [&str; COLUMN_COUNT] =
+    ["ID", "STATUS", "PRIORITY", "DUE", "ADDED", "TITLE", "TAGS"];
+const PRIORITY_COLUMN: usize = 2;
+const TITLE_COLUMN: usize = 5;
+const TAGS_COLUMN: usize = 6;
+/// Titles are cut no shorter than this to fit the terminal, even if the table gets too wide.
+const MIN_TITLE_WIDTH: usize = 10;
+const COLUMN_GAP: &str = "  ";
+
+/// Renders tasks for the terminal. With colors, completed tasks are dimmed and struck through,
+/// overdue tasks are red, tasks due today yellow, and priorities stand out.
+pub struct Renderer {
+    color: bool,
+    now: NaiveDateTime,
+}
+
+impl Renderer {
+    pub fn new(color: bool) -> Self {
+        Renderer {
+            color,
+            now: Local::now().naive_local(),
+        }
+    }
+
+    /// The line of a task in `list`, which also says whether it's blocked.
+    pub fn summary_line(&self, task_list: &TaskList, task: &Task) -> String {
+        let status = self.status_style(task);
+        let mut line = self.paint(&task.heading(), status);
+        if let Some(priority) = task.priority {
+            let style = priority_cell_style(task, status);
+            line.push_str(&self.paint(&format!(" [{}]", priority), &style));
+        }
+        line.push_str(&self.paint(&task.annotations(), status));
+        if let Some(until) = task.snoozed_until.filter(|_| task.is_snoozed(self.now)) {
+            let snoozed = format!(" (snoozed until {})", due::format_timestamp(until));
+            line.push_str(&self.paint(&snoozed, status));
+        }
+        if let Some(completed_at) = task.completed_at.filter(|_| task.completed) {
+            let done = format!(" (done {})", self.ago(completed_at));
+            line.push_str(&self.paint(&done, status));
+        }
+
+        let open_blockers = task_list.open_blockers(task);
+        if !task.completed && !open_blockers.is_empty() {
+            let blocked = format!(" [blocked by {}]", join_ids(&open_blockers));
+            line.push_str(&self.paint(&blocked, status));
+        }
+        line
+    }
+
+    /// Prints tasks as a tree, with subtasks indented below their parent in the given order.
+    pub fn print_tree(&self, task_list: &TaskList, tasks: &[&Task]) {
+        let is_root = |task: &Task| match task.parent_id {
+            Some(parent_id) => !tasks.iter().any(|t| t.id == parent_id),
+            None => true,
+        };
+        for task in tasks.iter().filter(|task| is_root(task)) {
+            self.print_subtree(task_list, tasks, task, 0);
+        }
+    }
+
+    fn print_subtree(&self, task_list: &TaskList, tasks: &[&Task], task: &Task, depth: usize) {
+        let line = self.summary_line(task_list, task);
+        println!("{}{}", "  ".repeat(depth), line);
+        for subtask in tasks.iter().filter(|t| t.parent_id == Some(task.id)) {
+            self.print_subtree(task_list, tasks, subtask, depth + 1);
+        }
+    }
+
+    /// Prints tasks as a table with a column for each field in the given order. Titles, and
+    /// then tags, are cut short when the table would be wider than the terminal.
+    pub fn print_table(&self, task_list: &TaskList, tasks: &[&Task]) {
+        let rows: Vec<[String; COLUMN_COUNT]> = tasks
+            .iter()
+            .map(|task| {
+                let tags: Vec<String> = task.tags.iter().map(|tag| format!("#{}", tag)).collect();
+                [
+                    task.id.to_string(),
+                    self.status_name(task_list, task).to_string(),
+                    task.priority.map(|p| p.to_string()).unwrap_or_default(),
+                    task.due.map(|due| due.to_string()).unwrap_or_default(),
+                    task.created_at
+                        .map(|created_at| self.ago(created_at))
+                        .unwrap_or_default(),
+                    task.title.clone(),
+                    tags.join(" "),
+                ]
+            })
+            .collect();
+
+        let mut widths = TABLE_HEADERS.map(|header| header.chars().count());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        if let Some(terminal_width) = terminal_width() {
+            fit_columns(&mut widths, terminal_width);
+        }
+
+        let header: Vec<String> = TABLE_HEADERS
+            .iter()
+            .enumerate()
+            .map(|(column, header)| self.paint(&fit_cell(header, &widths, column), HEADER_STYLE))
+            .collect();
+        println!("{}", header.join(COLUMN_GAP).trim_end());
+        for (task, row) in tasks.iter().zip(&rows) {
+            let status = self.status_style(task);
+            let cells: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(column, cell)| {
+                    let style = match column {
+                        PRIORITY_COLUMN => priority_cell_style(task, status),
+                        _ => status.to_string(),
+                    };
+                    self.paint(&fit_cell(cell, &widths, column), &style)
+                })
+                .collect();
+            println!("{}", cells.join(COLUMN_GAP).trim_end());
+        }
+    }
+
+    fn ago(&self, time: DateTime<Local>) -> String {
+        format_relative(time.naive_local(), self.now)
+    }
+
+    /// What the status column of a table says about a task.
+    fn status_name(&self, task_list: &TaskList, task: &Task) -> &'static str {
+        match self.status_style(task) {
+            COMPLETED_STYLE => "done",
+            OVERDUE_STYLE => "overdue",
+            DUE_TODAY_STYLE => "today",
+            _ if !task_list.open_blockers(task).is_empty() => "blocked",
+            _ => "open",
+        }
+    }
+
+    /// The style of a whole line, or an empty string for none.
+    fn status_style(&self, task: &Task) -> &'static str {
+        if task.completed {
+            COMPLETED_STYLE
+        } else if task.is_overdue(self.now) {
+            OVERDUE_STYLE
+        } else if task
+            .due
+            .is_some_and(|due| due.start().date() == self.now.date())
+        {
+            DUE_TODAY_STYLE
+        } else {
+            ""
+        }
+    }
+
+    /// Shows a text in a style, if colors are on.
+    pub fn paint(&self, text: &str, style: &str) -> String {
+        if self.color && !style.is_empty() && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Narrows the title column, and then the tags column, until the table fits into the width.
+fn fit_columns(widths: &mut [usize; COLUMN_COUNT], max_width: usize) {
+    let total_width = widths.iter().sum::<usize>() + COLUMN_GAP.len() * (widths.len() - 1);
+    let mut excess = total_width.saturating_sub(max_width);
+    let title_width = widths[TITLE_COLUMN];
+    let cut = excess.min(title_width.saturating_sub(MIN_TITLE_WIDTH));
+    widths[TITLE_COLUMN] -= cut;
+    excess -= cut;
+    let tags_header_width = TABLE_HEADERS[TAGS_COLUMN].len();
+    widths[TAGS_COLUMN] -= excess.min(widths[TAGS_COLUMN] - tags_header_width);
+}
+
+/// A cell cut to the width of its column, ending in `…` if it had to be cut, and padded to the
+/// width unless it's the last column.
+fn fit_cell(text: &str, widths: &[usize; COLUMN_COUNT], column: usize) -> String {
+    let width = widths[column];
+    let mut cell: String = if text.chars().count() > width {
+        let kept: String = text.chars().take(width.saturating_sub(1)).collect();
+        kept + "…"
+    } else {
+        text.to_string()
+    };
+    if column + 1 < widths.len() {
+        let padding = width - cell.chars().count();
+        cell.push_str(&" ".repeat(padding));
+    }
+    cell
+}
+
+/// The width of the terminal, from `COLUMNS` or else the terminal's own size. `None` when
+/// stdout isn't a terminal, so that output for other programs isn't cut.
+fn terminal_width() -> Option<usize> {
+    let columns = env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok());
+    columns
+        .or_else(|| terminal_size().map(|(columns, _)| columns))
+        .filter(|&columns| columns > 0)
+}
+
+/// The columns and rows of the terminal stdout is, or `None` if it isn't one or doesn't know its
+/// size.
+#[cfg(unix)]
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ only writes the window size into the `winsize` it's given, and fails
+    // when stdout isn't a terminal.
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    let known = result == 0 && size.ws_col > 0 && size.ws_row > 0;
+    known.then_some((usize::from(size.ws_col), usize::from(size.ws_row)))
+}
+
+#[cfg(not(unix))]
+pub fn terminal_size() -> Option<(usize, usize)> {
+    None
+}
+
+/// The style of a task's priority, which stands out from the rest of its line unless the task
+/// is completed.
+fn priority_cell_style(task: &Task, status: &str) -> String {
+    match (task.priority, task.completed) {
+        (Some(priority), false) if status.is_empty() => priority_style(priority).to_string(),
+        (Some(priority), false) => format!("{};{}", status, priority_style(priority)),
+        _ => status.to_string(),
+    }
+}
+
+/// Bold magenta, cyan and blue for high, medium and low priority.
+fn priority_style(priority: Priority) -> &'static str {
+    match priority {
+        Priority::High => "1;35",
+        Priority::Medium => "1;36",
+        Priority::Low => "1;34",
+    }
+}
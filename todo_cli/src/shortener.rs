@@ -0,0 +1,103 @@
+use reqwest::{blocking::Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    io::{Error, ErrorKind},
+};
+
+/// Where url_shortener listens when it runs with its defaults.
+const DEFAULT_URL: &str = "http://localhost:3000";
+
+/// The url_shortener instance `shorten` uses, from `[shortener]` in the config file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShortenerConfig {
+    /// Where the instance is running, `http://localhost:3000` if not set.
+    pub url: Option<String>,
+    /// A key created with url_shortener's `admin api-keys create`. `URL_SHORTENER_API_KEY` is
+    /// used instead when it's set.
+    pub api_key: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ShortenRequest<'a> {
+    original_url: &'a str,
+    /// Links attached to several tasks get the same short URL.
+    reuse_existing: bool,
+}
+
+#[derive(Deserialize)]
+struct ShortenResponse {
+    short_url: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+/// Talks to the JSON API of a running url_shortener.
+pub struct Shortener {
+    client: Client,
+    api_url: String,
+    api_key: String,
+}
+
+impl Shortener {
+    pub fn new(config: &ShortenerConfig) -> Result<Self, Error> {
+        let api_key = env::var("URL_SHORTENER_API_KEY")
+            .ok()
+            .or(config.api_key.clone());
+        let Some(api_key) = api_key.filter(|key| !key.is_empty()) else {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "no API key for the URL shortener, set `api_key` under [shortener] in the config \
+                 file or URL_SHORTENER_API_KEY",
+            ));
+        };
+        let url = config.url.as_deref().unwrap_or(DEFAULT_URL);
+        Ok(Shortener {
+            client: Client::new(),
+            api_url: format!("{}/api/v1", url.trim_end_matches('/')),
+            api_key,
+        })
+    }
+
+    /// Returns the short URL that redirects to `url`.
+    pub fn shorten(&self, url: &str) -> Result<String, Error> {
+        let endpoint = format!("{}/shorten", self.api_url);
+        let response = self
+            .client
+            .post(&endpoint)
+            .header("X-Api-Key", &self.api_key)
+            .json(&ShortenRequest {
+                original_url: url,
+                reuse_existing: true,
+            })
+            .send()
+            .map_err(|error| {
+                Error::other(format!("couldn't reach the URL shortener: {}", error))
+            })?;
+        let status = response.status();
+        if status.is_success() {
+            let response: ShortenResponse = response.json().map_err(|error| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid answer from {}: {}", endpoint, error),
+                )
+            })?;
+            return Ok(response.short_url);
+        }
+        // The messages of the API say what was wrong, like a blocked domain.
+        let message = match response.json::<ErrorResponse>() {
+            Ok(response) => format!("{} answered {}: {}", endpoint, status, response.message),
+            Err(_) => format!("{} answered {}", endpoint, status),
+        };
+        let kind = match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorKind::PermissionDenied,
+            StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => ErrorKind::InvalidInput,
+            _ => ErrorKind::Other,
+        };
+        Err(Error::new(kind, message))
+    }
+}
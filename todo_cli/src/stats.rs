@@ -0,0 +1,390 @@
+use crate::{
+    due::{self, Due},
+    timesheet, Task,
+};
+use chrono::{Datelike, Days, Duration, Local, NaiveDate, NaiveDateTime};
+use std::collections::BTreeMap;
+
+const SPARK_CHARACTERS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Width of the longest bar of the burndown.
+const BAR_WIDTH: usize = 40;
+/// Periods of more days than this get a row per week in the burndown.
+const MAX_DAILY_ROWS: i64 = 31;
+
+/// Counts of a group of tasks.
+#[derive(Default)]
+struct Counts {
+    total: usize,
+    completed: usize,
+}
+
+impl Counts {
+    fn add(&mut self, task: &Task) {
+        self.total += 1;
+        if task.completed {
+            self.completed += 1;
+        }
+    }
+
+    fn rate(&self) -> usize {
+        (self.completed * 100).checked_div(self.total).unwrap_or(0)
+    }
+}
+
+/// Formats statistics about tasks: totals, the completion rate, the tasks completed on every
+/// day of the last weeks, the average time from creating to completing a task, and the same
+/// counts by tag. With `sparkline`, the days of every week are also drawn as bars.
+pub fn format_stats(tasks: &[&Task], now: NaiveDateTime, weeks: u32, sparkline: bool) -> String {
+    let mut counts = Counts::default();
+    let mut by_tag: BTreeMap<String, Counts> = BTreeMap::new();
+    for task in tasks {
+        counts.add(task);
+        let tags = task.tags.iter().map(|tag| format!("#{}", tag));
+        let groups: Vec<String> = if task.tags.is_empty() {
+            vec!["untagged".to_string()]
+        } else {
+            tags.collect()
+        };
+        for group in groups {
+            by_tag.entry(group).or_default().add(task);
+        }
+    }
+    let overdue = tasks.iter().filter(|task| task.is_overdue(now)).count();
+
+    let mut stats = format!(
+        "Tasks:            {}, {} open and {} completed\n",
+        counts.total,
+        counts.total - counts.completed,
+        counts.completed
+    );
+    stats.push_str(&format!("Completion rate:  {}%\n", counts.rate()));
+    stats.push_str(&format!("Overdue:          {}\n", overdue));
+    let now_local = now.and_local_timezone(Local).earliest();
+    let left: Vec<Duration> = tasks
+        .iter()
+        .filter_map(|task| task.remaining_estimate(now_local.unwrap_or_else(Local::now)))
+        .collect();
+    if !left.is_empty() {
+        let suffix = if left.len() == 1 { "" } else { "s" };
+        stats.push_str(&format!(
+            "Estimated left:   {} for {} open task{}\n",
+            timesheet::format_duration(left.iter().sum()),
+            left.len(),
+            suffix
+        ));
+    }
+    let durations: Vec<Duration> = tasks
+        .iter()
+        .filter(|task| task.completed)
+        .filter_map(|task| Some(task.completed_at? - task.created_at?))
+        .collect();
+    if let Some(count) = i32::try_from(durations.len()).ok().filter(|&n| n > 0) {
+        let average = durations.iter().sum::<Duration>() / count;
+        stats.push_str(&format!(
+            "Time to complete: {} on average\n",
+            format_duration(average)
+        ));
+    }
+
+    stats.push_str("\nCompleted per day:\n");
+    let today = now.date();
+    let this_week = today - Duration::days(today.weekday().num_days_from_monday().into());
+    let per_day = |day: NaiveDate| {
+        tasks
+            .iter()
+            .filter(|task| task.completed)
+            .filter(|task| task.completed_at.is_some_and(|at| at.date_naive() == day))
+            .count()
+    };
+    let rows: Vec<(NaiveDate, Vec<usize>)> = (0..weeks.max(1))
+        .rev()
+        .map(|weeks_ago| this_week - Duration::weeks(weeks_ago.into()))
+        .map(|start| {
+            (
+                start,
+                (0..7)
+                    .map(|day| per_day(start + Duration::days(day)))
+                    .collect(),
+            )
+        })
+        .collect();
+    let most = rows.iter().flat_map(|(_, days)| days).copied().max();
+    let count_width = most.unwrap_or(0).to_string().len().max(2);
+    let labels: Vec<String> = rows
+        .iter()
+        .map(|(start, _)| format!("Week of {}", Due::On(*start)))
+        .collect();
+    let label_width = labels.iter().map(|label| label.chars().count()).max();
+    let day_names: Vec<String> = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"]
+        .iter()
+        .map(|name| format!("{:>width$}", name, width = count_width))
+        .collect();
+    stats.push_str(&format!(
+        "  {:width$}  {}  Total\n",
+        "",
+        day_names.join(" "),
+        width = label_width.unwrap_or(0)
+    ));
+    for (label, (_, days)) in labels.iter().zip(&rows) {
+        let cells: Vec<String> = days
+            .iter()
+            .map(|count| format!("{:>width$}", count, width = count_width))
+            .collect();
+        let total: usize = days.iter().sum();
+        let mut row = format!(
+            "  {:width$}  {}  {:>5}",
+            label,
+            cells.join(" "),
+            total,
+            width = label_width.unwrap_or(0)
+        );
+        if sparkline {
+            row.push_str(&format!("  {}", spark(days, most.unwrap_or(0))));
+        }
+        // Weeks without completed tasks have blank bars.
+        stats.push_str(row.trim_end());
+        stats.push('\n');
+    }
+
+    if !by_tag.is_empty() {
+        stats.push_str("\nBy tag:\n");
+        let tag_width = by_tag.keys().map(|tag| tag.chars().count()).max();
+        for (tag, counts) in &by_tag {
+            stats.push_str(&format!(
+                "  {:<width$}  {:>4} of {:>4} completed  {:>3}%\n",
+                tag,
+                counts.completed,
+                counts.total,
+                counts.rate(),
+                width = tag_width.unwrap_or(0)
+            ));
+        }
+    }
+    stats
+}
+
+/// Formats a burndown of the days since `since`: the tasks that were open at the end of every
+/// day and those completed from `since` up to it, drawn as bars, a week to a row for periods
+/// longer than a month. Then come the tasks completed in the period by tag. Tasks without a
+/// creation time are counted as open from the start.
+pub fn format_report(tasks: &[&Task], now: NaiveDateTime, since: NaiveDate) -> String {
+    let today = now.date();
+    let created = |task: &Task| task.created_at.map(|at| at.date_naive());
+    // Completed tasks without a completion time were completed before anything was recorded.
+    let completed = |task: &Task| match (task.completed, task.completed_at) {
+        (true, Some(at)) => Some(at.date_naive()),
+        (true, None) => Some(NaiveDate::MIN),
+        (false, _) => None,
+    };
+    let in_period = |day: Option<NaiveDate>| day.is_some_and(|day| since <= day && day <= today);
+    let added = tasks.iter().filter(|task| in_period(created(task))).count();
+    let done: Vec<&Task> = tasks
+        .iter()
+        .copied()
+        .filter(|task| in_period(completed(task)))
+        .collect();
+    let mut report = format!(
+        "Since {}: {} task{} added and {} completed\n",
+        Due::On(since),
+        added,
+        if added == 1 { "" } else { "s" },
+        done.len()
+    );
+
+    let step = if (today - since).num_days() > MAX_DAILY_ROWS {
+        7
+    } else {
+        1
+    };
+    let mut days: Vec<NaiveDate> = since
+        .iter_days()
+        .step_by(step)
+        .take_while(|&day| day <= today)
+        .collect();
+    if days.last() != Some(&today) {
+        days.push(today);
+    }
+    let rows: Vec<(NaiveDate, usize, usize)> = days
+        .into_iter()
+        .map(|day| {
+            let open = tasks
+                .iter()
+                .filter(|task| created(task).is_none_or(|created| created <= day))
+                .filter(|task| completed(task).is_none_or(|completed| completed > day))
+                .count();
+            let completed = done
+                .iter()
+                .filter(|task| completed(task).is_some_and(|completed| completed <= day))
+                .count();
+            (day, open, completed)
+        })
+        .collect();
+    let most = rows
+        .iter()
+        .map(|&(_, open, completed)| open + completed)
+        .max();
+    let bar = |count: usize| count * BAR_WIDTH / most.unwrap_or(0).max(1);
+    let labels: Vec<String> = rows
+        .iter()
+        .map(|&(day, _, _)| Due::On(day).to_string())
+        .collect();
+    let label_width = labels
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap_or(0);
+    report.push_str(&format!(
+        "\nBurndown:\n  {:width$}  Open  Completed\n",
+        "",
+        width = label_width
+    ));
+    for (label, &(_, open, completed)) in labels.iter().zip(&rows) {
+        let row = format!(
+            "  {:width$}  {:>4}  {:>9}  {}{}",
+            label,
+            open,
+            completed,
+            "#".repeat(bar(open)),
+            "+".repeat(bar(open + completed) - bar(open)),
+            width = label_width
+        );
+        report.push_str(row.trim_end());
+        report.push('\n');
+    }
+    report.push_str("  # open, + completed\n");
+
+    let mut by_tag: BTreeMap<String, usize> = BTreeMap::new();
+    for task in &done {
+        if task.tags.is_empty() {
+            *by_tag.entry("untagged".to_string()).or_default() += 1;
+        }
+        for tag in &task.tags {
+            *by_tag.entry(format!("#{}", tag)).or_default() += 1;
+        }
+    }
+    if !by_tag.is_empty() {
+        let mut by_tag: Vec<(String, usize)> = by_tag.into_iter().collect();
+        by_tag.sort_by(|(a_tag, a), (b_tag, b)| b.cmp(a).then(a_tag.cmp(b_tag)));
+        report.push_str("\nCompleted by tag:\n");
+        let tag_width = by_tag.iter().map(|(tag, _)| tag.chars().count()).max();
+        for (tag, count) in &by_tag {
+            report.push_str(&format!(
+                "  {:<width$}  {:>4}\n",
+                tag,
+                count,
+                width = tag_width.unwrap_or(0)
+            ));
+        }
+    }
+    report
+}
+
+/// Parses how far back `report` goes, like `30d` or `4w`, or the date it starts on, like
+/// `2024-05-01`, for use as a clap value parser.
+pub fn parse_since(input: &str) -> Result<NaiveDate, String> {
+    let today = Local::now().date_naive();
+    let trimmed = input.trim();
+    for (unit, days) in [("d", 1), ("w", 7)] {
+        if let Some(count) = trimmed.strip_suffix(unit) {
+            if let Ok(count) = count.trim().parse::<u32>() {
+                return today
+                    .checked_sub_days(Days::new(u64::from(count) * days))
+                    .ok_or_else(|| format!("invalid period '{}'", input));
+            }
+        }
+    }
+    match due::parse(input) {
+        Ok(due) if due.start().date() <= today => Ok(due.start().date()),
+        Ok(_) => Err("the report can't start in the future".to_string()),
+        Err(_) => Err("expected a period like 30d or 4w, or a date like 2024-05-01".to_string()),
+    }
+}
+
+/// Draws counts as bars, scaled so that `most` is a full bar and only zero is empty.
+fn spark(counts: &[usize], most: usize) -> String {
+    let steps = SPARK_CHARACTERS.len() - 1;
+    counts
+        .iter()
+        .map(|&count| match count {
+            0 => SPARK_CHARACTERS[0],
+            _ => SPARK_CHARACTERS[(count * steps).div_ceil(most).clamp(1, steps)],
+        })
+        .collect()
+}
+
+/// A rough duration in its two largest units, like `2d 4h`, `3h 5m` or `12m`.
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_durations_and_sparklines() {
+        assert_eq!(format_duration(Duration::minutes(12)), "12m");
+        assert_eq!(format_duration(Duration::minutes(185)), "3h 5m");
+        assert_eq!(format_duration(Duration::hours(52)), "2d 4h");
+        assert_eq!(spark(&[0, 1, 4, 8], 8), " ▁▄█");
+    }
+
+    #[test]
+    fn reports_the_burndown_and_completions_by_tag() {
+        let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+        let at = |month, day| {
+            let noon = date(month, day).and_hms_opt(12, 0, 0).unwrap();
+            noon.and_local_timezone(Local).single()
+        };
+        let task = |title: &str, created_at, completed_at: Option<_>, tags: &[&str]| {
+            let mut task = Task::new(0, title.to_string());
+            task.created_at = created_at;
+            task.completed = completed_at.is_some();
+            task.completed_at = completed_at;
+            task.tags = tags.iter().map(|tag| tag.to_string()).collect();
+            task
+        };
+        let tasks = [
+            task("a", at(4, 20), at(5, 2), &["shop"]),
+            task("b", at(5, 3), None, &[]),
+            // Tasks from before creation times were recorded are open from the start.
+            task("c", None, at(5, 4), &["shop", "home"]),
+            task("d", at(4, 1), at(4, 10), &["shop"]),
+        ];
+        let tasks: Vec<&Task> = tasks.iter().collect();
+        let now = date(5, 5).and_hms_opt(18, 0, 0).unwrap();
+
+        let report = format_report(&tasks, now, date(5, 1));
+        let bars = |open, completed| format!("{}{}", "#".repeat(open), "+".repeat(completed));
+        let expected = format!(
+            "\
+Since Wed 2024-05-01: 1 task added and 2 completed
+
+Burndown:
+                  Open  Completed
+  Wed 2024-05-01     2          0  {}
+  Thu 2024-05-02     1          1  {}
+  Fri 2024-05-03     2          1  {}
+  Sat 2024-05-04     1          2  {}
+  Sun 2024-05-05     1          2  {}
+  # open, + completed
+
+Completed by tag:
+  #shop     2
+  #home     1
+",
+            bars(26, 0),
+            bars(13, 13),
+            bars(26, 14),
+            bars(13, 27),
+            bars(13, 27),
+        );
+        assert_eq!(report, expected);
+    }
+}
@@ -0,0 +1,459 @@
+mod encryption;
+mod json_files;
+mod schema;
+mod sqlite;
+
+pub use json_files::{parent_dir, JsonFiles};
+pub use schema::{migrate as migrate_list, CURRENT_VERSION as SCHEMA_VERSION};
+pub use sqlite::{SqliteDatabase, DATABASE_FILE_NAME};
+
+use crate::TaskList;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, TryLockError},
+    io::{Error, ErrorKind, Write},
+    path::Path,
+    process,
+};
+
+pub const DEFAULT_LIST_NAME: &str = "default";
+
+const LOCK_FILE_NAME: &str = "todo.lock";
+
+const MAX_LIST_NAME_LENGTH: usize = 64;
+
+/// Part of what a storage keeps for a list, as the journal saves it to undo a change.
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct StoredData {
+    /// A file name for [`JsonFiles`], a list name for [`SqliteDatabase`].
+    #[serde(alias = "file_name")]
+    pub key: String,
+    /// `None` when there was nothing stored.
+    pub contents: Option<String>,
+}
+
+/// Where lists and their archives are kept. Implementations only load and save, the rules for
+/// lists are the same for all of them. List names are validated before they're passed on.
+pub trait Storage {
+    fn list_exists(&self, name: &str) -> Result<bool, Error>;
+
+    /// Loads a list, or returns `None` if it doesn't exist.
+    fn load_list(&self, name: &str) -> Result<Option<TaskList>, Error>;
+
+    /// Saves a list as it is, see [`Storage::save_list`].
+    fn write_list(&self, name: &str, task_list: &TaskList) -> Result<(), Error>;
+
+    /// Loads the archived tasks of a list, which is empty until tasks are archived.
+    fn load_archive(&self, name: &str) -> Result<TaskList, Error>;
+
+    fn save_archive(&self, name: &str, archive: &TaskList) -> Result<(), Error>;
+
+    /// Returns the names of all existing lists, sorted alphabetically.
+    fn list_names(&self) -> Result<Vec<String>, Error>;
+
+    /// Deletes an existing list along with its archive.
+    fn remove_list(&self, name: &str) -> Result<(), Error>;
+
+    /// Returns everything stored for a list, to be put back by [`Storage::restore`].
+    fn snapshot(&self, name: &str) -> Result<Vec<StoredData>, Error>;
+
+    fn restore(&self, data: StoredData) -> Result<(), Error>;
+
+    /// Saves a list, recording when its tasks were changed. A stored list that can't be read
+    /// is replaced all the same, with every task counted as changed.
+    fn save_list(&self, name: &str, task_list: &TaskList) -> Result<(), Error> {
+        let stored = self.load_list(name).unwrap_or(None);
+        let mut stamped = task_list.clone();
+        stamped.stamp_updates(stored.as_ref(), Local::now());
+        self.write_list(name, &stamped)
+    }
+
+    /// Loads a list for reading or modification. The default list is created on first use,
+    /// other lists have to be created explicitly so that a typo doesn't silently start a new
+    /// list.
+    fn open_list(&self, name: &str) -> Result<TaskList, Error> {
+        validate_list_name(name)?;
+        match self.load_list(name)? {
+            Some(task_list) => Ok(task_list),
+            None if name == DEFAULT_LIST_NAME => Ok(TaskList::new()),
+            None => Err(Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "list '{}' does not exist, create it with `lists create {}`",
+                    name, name
+                ),
+            )),
+        }
+    }
+
+    /// Loads the archived tasks of an existing list.
+    fn open_archive(&self, name: &str) -> Result<TaskList, Error> {
+        self.open_list(name)?;
+        self.load_archive(name)
+    }
+
+    /// Moves tasks from a list to its archive, where they keep their IDs since IDs aren't
+    /// reused. Saves both the archive and the list.
+    fn archive_tasks(
+        &self,
+        name: &str,
+        task_list: &mut TaskList,
+        ids: &[u32],
+    ) -> Result<(), Error> {
+        let mut archive = self.open_archive(name)?;
+        for &id in ids {
+            if let Some(task) = task_list.remove_task(id) {
+                archive.tasks.push(task);
+            }
+        }
+        self.save_archive(name, &archive)?;
+        self.save_list(name, task_list)
+    }
+
+    fn create_list(&self, name: &str) -> Result<(), Error> {
+        validate_list_name(name)?;
+        if self.list_exists(name)? {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("list '{}' already exists", name),
+            ));
+        }
+        self.save_list(name, &TaskList::new())
+    }
+
+    fn delete_list(&self, name: &str) -> Result<(), Error> {
+        validate_list_name(name)?;
+        if !self.list_exists(name)? {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("list '{}' does not exist", name),
+            ));
+        }
+        self.remove_list(name)
+    }
+
+    /// Moves a task to another list, where it gets a fresh ID and no parent or blockers.
+    /// Returns the new ID, or `None` if the task doesn't exist in the source list. Tasks with
+    /// subtasks can't be moved, since the subtasks would be left without their parent.
+    fn move_task(&self, from_list: &str, to_list: &str, id: u32) -> Result<Option<u32>, Error> {
+        if from_list == to_list {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "source and destination lists are the same",
+            ));
+        }
+
+        let mut source = self.open_list(from_list)?;
+        let mut destination = self.open_list(to_list)?;
+
+        if source.subtasks(id).next().is_some() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("task {} has subtasks, move or remove them first", id),
+            ));
+        }
+        let mut task = match source.remove_task(id) {
+            Some(task) => task,
+            None => return Ok(None),
+        };
+        task.parent_id = None;
+        task.blocked_by.clear();
+        let new_id = destination.insert_task(task);
+
+        self.save_list(to_list, &destination)?;
+        self.save_list(from_list, &source)?;
+        Ok(Some(new_id))
+    }
+}
+
+/// Ensures that a list name only contains characters that are safe to use in a file name.
+pub fn validate_list_name(name: &str) -> Result<(), Error> {
+    let is_valid = !name.is_empty()
+        && name.len() <= MAX_LIST_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "invalid list name '{}': use up to {} letters, digits, '-' or '_'",
+                name, MAX_LIST_NAME_LENGTH
+            ),
+        ))
+    }
+}
+
+/// Takes an exclusive lock on the data directory, which is released when the returned file is
+/// dropped. Commands run at the same time, like from scripts, then change the lists one after
+/// another instead of overwriting each other's changes.
+pub fn lock_dir(dir: &Path) -> Result<File, Error> {
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(LOCK_FILE_NAME))?;
+    match file.try_lock() {
+        Ok(()) => return Ok(file),
+        Err(TryLockError::WouldBlock) => {
+            eprintln!("Waiting for another todo_cli command to finish");
+        }
+        Err(TryLockError::Error(error)) => return Err(error),
+    }
+    file.lock()?;
+    Ok(file)
+}
+
+/// Replaces the contents of a file by writing a temporary file next to it and renaming that,
+/// so that a crash or a full disk leaves either the old or the new contents, never a part.
+pub fn write_atomically(path: &Path, contents: &str) -> Result<(), Error> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, process::id()));
+    let written = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    });
+    match written.and_then(|()| fs::rename(&temp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(error) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(error)
+        }
+    }
+}
+
+/// Copies all lists and archives from one storage to another, returning how many lists were
+/// copied.
+pub fn copy_lists(from: &dyn Storage, to: &dyn Storage) -> Result<usize, Error> {
+    let names = from.list_names()?;
+    for name in &names {
+        to.save_list(name, &from.open_list(name)?)?;
+        to.save_archive(name, &from.load_archive(name)?)?;
+    }
+    Ok(names.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf};
+
+    fn temp_dir(test_name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("todo_cli_{}_{}", test_name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Runs a test against every kind of storage, each in a fresh directory.
+    fn with_each_storage(test_name: &str, test: impl Fn(&dyn Storage)) {
+        let dir = temp_dir(&format!("{}_json", test_name));
+        test(&JsonFiles::new(dir.clone()));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let dir = temp_dir(&format!("{}_sqlite", test_name));
+        test(&SqliteDatabase::open(&dir.join(DATABASE_FILE_NAME)).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn titles(task_list: &TaskList) -> Vec<&str> {
+        task_list
+            .list_tasks()
+            .iter()
+            .map(|t| t.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn validates_list_names() {
+        assert!(validate_list_name("work").is_ok());
+        assert!(validate_list_name("side-project_2").is_ok());
+        assert!(validate_list_name("").is_err());
+        assert!(validate_list_name("../etc").is_err());
+        assert!(validate_list_name("my list").is_err());
+    }
+
+    #[test]
+    fn creates_and_enumerates_lists() {
+        with_each_storage("creates_and_enumerates_lists", |storage| {
+            storage.create_list("work").unwrap();
+            storage.create_list("personal").unwrap();
+            assert!(storage.create_list("work").is_err());
+
+            assert_eq!(storage.list_names().unwrap(), vec!["personal", "work"]);
+        });
+    }
+
+    #[test]
+    fn moves_task_between_lists_with_fresh_id() {
+        with_each_storage("moves_task_between_lists", |storage| {
+            storage.create_list("work").unwrap();
+            storage.create_list("personal").unwrap();
+
+            let mut work = storage.open_list("work").unwrap();
+            work.add_task("Write report".to_string());
+            work.add_task("Buy milk".to_string());
+            storage.save_list("work", &work).unwrap();
+
+            let mut personal = storage.open_list("personal").unwrap();
+            personal.add_task("Call mom".to_string());
+            storage.save_list("personal", &personal).unwrap();
+
+            let new_id = storage.move_task("work", "personal", 2).unwrap();
+            assert_eq!(new_id, Some(2));
+            assert_eq!(storage.move_task("work", "personal", 2).unwrap(), None);
+
+            let work = storage.open_list("work").unwrap();
+            assert_eq!(titles(&work), vec!["Write report"]);
+
+            let personal = storage.open_list("personal").unwrap();
+            assert_eq!(titles(&personal), vec!["Call mom", "Buy milk"]);
+        });
+    }
+
+    #[test]
+    fn deleting_a_list_leaves_other_lists_untouched() {
+        with_each_storage("deleting_a_list", |storage| {
+            storage.create_list("work").unwrap();
+            storage.create_list("personal").unwrap();
+
+            let mut personal = storage.open_list("personal").unwrap();
+            personal.add_task("Call mom".to_string());
+            storage.save_list("personal", &personal).unwrap();
+
+            storage.delete_list("work").unwrap();
+
+            assert_eq!(storage.list_names().unwrap(), vec!["personal"]);
+            assert!(storage.open_list("work").is_err());
+            assert_eq!(storage.open_list("personal").unwrap().list_tasks().len(), 1);
+        });
+    }
+
+    #[test]
+    fn archives_tasks_and_restores_snapshots() {
+        with_each_storage("archives_tasks", |storage| {
+            let mut task_list = storage.open_list(DEFAULT_LIST_NAME).unwrap();
+            task_list.add_task("Buy milk".to_string());
+            task_list.add_task("Call mom".to_string());
+            storage.save_list(DEFAULT_LIST_NAME, &task_list).unwrap();
+            let before = storage.snapshot(DEFAULT_LIST_NAME).unwrap();
+
+            storage
+                .archive_tasks(DEFAULT_LIST_NAME, &mut task_list, &[1])
+                .unwrap();
+            let task_list = storage.open_list(DEFAULT_LIST_NAME).unwrap();
+            assert_eq!(titles(&task_list), vec!["Call mom"]);
+            let archive = storage.open_archive(DEFAULT_LIST_NAME).unwrap();
+            assert_eq!(titles(&archive), vec!["Buy milk"]);
+
+            for data in before {
+                storage.restore(data).unwrap();
+            }
+            let task_list = storage.open_list(DEFAULT_LIST_NAME).unwrap();
+            assert_eq!(titles(&task_list), vec!["Buy milk", "Call mom"]);
+            let archive = storage.open_archive(DEFAULT_LIST_NAME).unwrap();
+            assert!(archive.list_tasks().is_empty());
+        });
+    }
+
+    #[test]
+    fn renumbers_tasks_with_their_references_and_archive() {
+        with_each_storage("renumbers_tasks", |storage| {
+            let mut task_list = storage.open_list(DEFAULT_LIST_NAME).unwrap();
+            for title in ["Buy milk", "Call mom", "Pay rent", "Water plants"] {
+                task_list.add_task(title.to_string());
+            }
+            task_list.get_task(4).unwrap().parent_id = Some(3);
+            task_list.get_task(4).unwrap().blocked_by = vec![3];
+            storage.save_list(DEFAULT_LIST_NAME, &task_list).unwrap();
+            storage
+                .archive_tasks(DEFAULT_LIST_NAME, &mut task_list, &[1])
+                .unwrap();
+            task_list.trash_task(2);
+
+            let mut archive = storage.open_archive(DEFAULT_LIST_NAME).unwrap();
+            let changes = task_list.renumber(&mut archive);
+            assert_eq!(changes, vec![(3, 1), (4, 2), (2, 3), (1, 4)]);
+            storage.save_list(DEFAULT_LIST_NAME, &task_list).unwrap();
+            storage.save_archive(DEFAULT_LIST_NAME, &archive).unwrap();
+
+            let task_list = storage.open_list(DEFAULT_LIST_NAME).unwrap();
+            assert_eq!(titles(&task_list), vec!["Pay rent", "Water plants"]);
+            let subtask = task_list.find_task(2).unwrap();
+            assert_eq!(
+                (subtask.parent_id, subtask.blocked_by.clone()),
+                (Some(1), vec![1])
+            );
+            let archive = storage.open_archive(DEFAULT_LIST_NAME).unwrap();
+            assert_eq!(archive.find_task(4).unwrap().title, "Buy milk");
+            assert_eq!(task_list.next_task_id, 5);
+        });
+    }
+
+    #[test]
+    fn keeps_the_order_tasks_were_moved_in() {
+        with_each_storage("keeps_the_order", |storage| {
+            let mut task_list = storage.open_list(DEFAULT_LIST_NAME).unwrap();
+            for title in ["Buy milk", "Call mom", "Pay rent"] {
+                task_list.add_task(title.to_string());
+            }
+            task_list.reorder_task(3, None);
+            task_list.reorder_task(1, Some(2));
+            task_list.add_task("Water plants".to_string());
+            storage.save_list(DEFAULT_LIST_NAME, &task_list).unwrap();
+
+            let task_list = storage.open_list(DEFAULT_LIST_NAME).unwrap();
+            let expected = vec!["Pay rent", "Buy milk", "Call mom", "Water plants"];
+            assert_eq!(titles(&task_list), expected);
+        });
+    }
+
+    #[test]
+    fn reports_malformed_list_files_and_replaces_files_whole() {
+        let dir = temp_dir("malformed_list_files");
+        let files = JsonFiles::new(dir.clone());
+        fs::write(dir.join("task_list.json"), "{\"tasks\": [").unwrap();
+        let error = files.open_list(DEFAULT_LIST_NAME).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("task_list.json"));
+
+        files
+            .save_list(DEFAULT_LIST_NAME, &TaskList::new())
+            .unwrap();
+        assert!(files.open_list(DEFAULT_LIST_NAME).is_ok());
+        // No temporary files are left behind.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn migrates_lists_into_an_empty_data_dir() {
+        let old_dir = temp_dir("migrates_lists_old");
+        let old_files = JsonFiles::new(old_dir.clone());
+        old_files.create_list("work").unwrap();
+        let mut default = old_files.open_list(DEFAULT_LIST_NAME).unwrap();
+        default.add_task("Buy milk".to_string());
+        old_files.save_list(DEFAULT_LIST_NAME, &default).unwrap();
+
+        let new_dir = temp_dir("migrates_lists_new");
+        let files = JsonFiles::with_default_list_file(new_dir.join("tasks.json"));
+        let moved = files.migrate_files(&old_dir).unwrap();
+        assert_eq!(moved.len(), 2);
+        assert!(old_files.list_names().unwrap().is_empty());
+        assert_eq!(files.list_names().unwrap(), vec!["default", "work"]);
+        let default = files.open_list(DEFAULT_LIST_NAME).unwrap();
+        assert_eq!(titles(&default), vec!["Buy milk"]);
+
+        // Lists that are already there aren't overwritten.
+        old_files.create_list("work").unwrap();
+        assert!(files.migrate_files(&old_dir).unwrap().is_empty());
+
+        fs::remove_dir_all(&old_dir).unwrap();
+        fs::remove_dir_all(&new_dir).unwrap();
+    }
+}
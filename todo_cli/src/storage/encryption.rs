@@ -0,0 +1,203 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::{hash_map::Entry, HashMap},
+    io::{Error, ErrorKind},
+    num::NonZeroU32,
+};
+
+const CIPHER: &str = "chacha20-poly1305";
+const KEY_DERIVATION: &str = "pbkdf2-sha256";
+/// As recommended by OWASP for PBKDF2 with SHA-256.
+const ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+/// The contents of an encrypted file, which is JSON too, so the journal can keep it like any
+/// other list file.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptedFile {
+    encrypted: String,
+    key_derivation: String,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl EncryptedFile {
+    /// Reads the contents of a file as encrypted, or returns `None` for a list in plain JSON.
+    pub fn parse(contents: &str) -> Option<Self> {
+        serde_json::from_str(contents).ok()
+    }
+}
+
+/// Encrypts list files with ChaCha20-Poly1305, under a key derived from a passphrase that's
+/// only asked for once a file is encrypted or decrypted.
+pub struct Encryption {
+    read_passphrase: fn(bool) -> Result<String, Error>,
+    passphrase: OnceCell<String>,
+    /// Of the key derivation for files that are written.
+    iterations: u32,
+    /// Derived keys by salt and iterations, since deriving one takes a moment.
+    keys: RefCell<HashMap<(Vec<u8>, u32), LessSafeKey>>,
+    /// The salt files are written with, that of the first file read so the key is derived
+    /// only once.
+    salt: RefCell<Option<Vec<u8>>>,
+    random: SystemRandom,
+}
+
+impl Encryption {
+    /// Takes the function that gets the passphrase, which is told whether it's a new one,
+    /// because nothing was decrypted with it yet.
+    pub fn new(read_passphrase: fn(bool) -> Result<String, Error>) -> Self {
+        Encryption {
+            read_passphrase,
+            passphrase: OnceCell::new(),
+            iterations: ITERATIONS,
+            keys: RefCell::new(HashMap::new()),
+            salt: RefCell::new(None),
+            random: SystemRandom::new(),
+        }
+    }
+
+    /// Whether a file was decrypted or encrypted already, so the passphrase is known.
+    pub fn is_unlocked(&self) -> bool {
+        self.salt.borrow().is_some()
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, Error> {
+        let is_new = !self.is_unlocked();
+        let salt = match self.salt.borrow().clone() {
+            Some(salt) => salt,
+            None => {
+                let mut salt = vec![0; SALT_LEN];
+                self.random.fill(&mut salt).map_err(|_| random_failed())?;
+                salt
+            }
+        };
+        let mut nonce = [0; NONCE_LEN];
+        self.random.fill(&mut nonce).map_err(|_| random_failed())?;
+        let mut ciphertext = plaintext.as_bytes().to_vec();
+        self.with_key(&salt, self.iterations, is_new, |key| {
+            let nonce = Nonce::assume_unique_for_key(nonce);
+            key.seal_in_place_append_tag(nonce, Aad::empty(), &mut ciphertext)
+                .map_err(|_| Error::other("encrypting failed"))
+        })?;
+        *self.salt.borrow_mut() = Some(salt.clone());
+        let file = EncryptedFile {
+            encrypted: CIPHER.to_string(),
+            key_derivation: KEY_DERIVATION.to_string(),
+            iterations: self.iterations,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        Ok(serde_json::to_string(&file)?)
+    }
+
+    /// Decrypts a file, failing if it was encrypted with another passphrase or changed since.
+    pub fn decrypt(&self, file: &EncryptedFile) -> Result<String, Error> {
+        if file.encrypted != CIPHER || file.key_derivation != KEY_DERIVATION {
+            return Err(invalid(format!(
+                "encrypted with {} and {}, which aren't supported",
+                file.encrypted, file.key_derivation
+            )));
+        }
+        let decode = |text: &str| {
+            BASE64
+                .decode(text)
+                .map_err(|error| invalid(error.to_string()))
+        };
+        let salt = decode(&file.salt)?;
+        let nonce = Nonce::try_assume_unique_for_key(&decode(&file.nonce)?)
+            .map_err(|_| invalid("the nonce has the wrong length".to_string()))?;
+        let mut ciphertext = decode(&file.ciphertext)?;
+        let is_new = !self.is_unlocked();
+        let plaintext = self.with_key(&salt, file.iterations, is_new, |key| {
+            let plaintext = key
+                .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+                .map_err(|_| {
+                    Error::new(
+                        ErrorKind::PermissionDenied,
+                        "the passphrase is wrong, or the file was changed",
+                    )
+                })?;
+            Ok(plaintext.to_vec())
+        })?;
+        self.salt.borrow_mut().get_or_insert(salt);
+        String::from_utf8(plaintext).map_err(|error| invalid(error.to_string()))
+    }
+
+    fn with_key<T>(
+        &self,
+        salt: &[u8],
+        iterations: u32,
+        is_new: bool,
+        f: impl FnOnce(&LessSafeKey) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut keys = self.keys.borrow_mut();
+        let key = match keys.entry((salt.to_vec(), iterations)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let passphrase = match self.passphrase.get() {
+                    Some(passphrase) => passphrase,
+                    None => {
+                        let passphrase = (self.read_passphrase)(is_new)?;
+                        self.passphrase.get_or_init(|| passphrase)
+                    }
+                };
+                let iterations = NonZeroU32::new(iterations)
+                    .ok_or_else(|| invalid("the iterations are 0".to_string()))?;
+                let mut key = [0; KEY_LEN];
+                pbkdf2::derive(
+                    pbkdf2::PBKDF2_HMAC_SHA256,
+                    iterations,
+                    salt,
+                    passphrase.as_bytes(),
+                    &mut key,
+                );
+                let key = UnboundKey::new(&CHACHA20_POLY1305, &key)
+                    .map_err(|_| Error::other("the key has the wrong length"))?;
+                entry.insert(LessSafeKey::new(key))
+            }
+        };
+        f(key)
+    }
+}
+
+fn random_failed() -> Error {
+    Error::other("the system's random number generator failed")
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decrypts_what_it_encrypted_with_the_same_passphrase() {
+        let mut encryption = Encryption::new(|_| Ok("correct horse".to_string()));
+        // Few iterations keep the test fast, they're read from the file anyway.
+        encryption.iterations = 10;
+        let encrypted = encryption.encrypt("{\"tasks\": []}").unwrap();
+        assert!(!encrypted.contains("tasks"));
+        let file = EncryptedFile::parse(&encrypted).unwrap();
+        assert_eq!(encryption.decrypt(&file).unwrap(), "{\"tasks\": []}");
+        assert!(EncryptedFile::parse("{\"tasks\": []}").is_none());
+
+        let other = Encryption::new(|_| Ok("battery staple".to_string()));
+        let error = other.decrypt(&file).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,335 @@
+use super::{
+    encryption::{EncryptedFile, Encryption},
+    schema, validate_list_name, write_atomically, Storage, StoredData, DEFAULT_LIST_NAME,
+};
+use crate::{passphrase, TaskList};
+use common::error::with_path;
+use std::{
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+const FILE_PREFIX: &str = "task_list";
+const ARCHIVE_FILE_PREFIX: &str = "task_archive";
+const FILE_EXTENSION: &str = "json";
+
+/// Keeps every list in a JSON file of its own in the data directory, which is rewritten whenever
+/// the list changes. The file of the default list can be somewhere else, like one given by
+/// `--file`, with the other files next to it.
+///
+/// Encrypted files are decrypted as they're loaded, asking for the passphrase then, and files
+/// are saved encrypted once encryption is turned on with [`JsonFiles::encrypted`].
+pub struct JsonFiles {
+    path: PathBuf,
+    default_list_file: PathBuf,
+    encryption: Encryption,
+    encrypt: bool,
+}
+
+impl JsonFiles {
+    pub fn new(path: PathBuf) -> Self {
+        let default_list_file = path.join(format!("{}.{}", FILE_PREFIX, FILE_EXTENSION));
+        JsonFiles {
+            path,
+            default_list_file,
+            encryption: Encryption::new(passphrase::read),
+            encrypt: false,
+        }
+    }
+
+    pub fn with_default_list_file(default_list_file: PathBuf) -> Self {
+        JsonFiles {
+            path: parent_dir(&default_list_file),
+            default_list_file,
+            encryption: Encryption::new(passphrase::read),
+            encrypt: false,
+        }
+    }
+
+    /// Saves files encrypted with a passphrase.
+    pub fn encrypted(mut self) -> Self {
+        self.encrypt = true;
+        self
+    }
+
+    /// Encrypts the files of all lists that aren't yet, returning how many were encrypted.
+    pub fn encrypt_files(&self) -> Result<usize, Error> {
+        let mut plain_files = Vec::new();
+        for name in self.list_names()? {
+            for path in self.list_files(&name) {
+                match fs::read_to_string(&path) {
+                    Ok(contents) if EncryptedFile::parse(&contents).is_none() => {
+                        plain_files.push(path)
+                    }
+                    Err(error) if error.kind() != ErrorKind::NotFound => return Err(error),
+                    _ => {}
+                }
+            }
+        }
+        for path in &plain_files {
+            self.save_to_file(&self.load_from_file(path)?, path)?;
+        }
+        Ok(plain_files.len())
+    }
+
+    /// Decrypts one of the encrypted files, if there are any, so that new files are encrypted
+    /// with the passphrase of the others rather than asking for a new one.
+    fn unlock(&self) -> Result<(), Error> {
+        if self.encryption.is_unlocked() {
+            return Ok(());
+        }
+        for name in self.list_names()? {
+            for path in self.list_files(&name) {
+                let Ok(contents) = fs::read_to_string(&path) else {
+                    continue;
+                };
+                if let Some(file) = EncryptedFile::parse(&contents) {
+                    self.decrypt(&file, &path)?;
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn decrypt(&self, file: &EncryptedFile, file_path: &Path) -> Result<String, Error> {
+        self.encryption.decrypt(file).map_err(|error| {
+            Error::new(
+                error.kind(),
+                format!("can't decrypt {}: {}", file_path.display(), error),
+            )
+        })
+    }
+
+    /// Loads a list from a file, or an empty list if the file doesn't exist. Files of an older
+    /// version are upgraded, after copying them to a backup like `task_list.json.v0.bak`, and
+    /// written in the new format right away, since migrations like adding UUIDs have to give
+    /// the same result every time the file is loaded.
+    fn load_from_file(&self, file_path: &Path) -> Result<TaskList, Error> {
+        let file_content = match fs::read_to_string(file_path) {
+            Ok(file_content) => file_content,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(TaskList::new()),
+            Err(error) => return Err(error),
+        };
+        let (task_list, old_version) = self.parse_file(file_content, file_path)?;
+        if let Some(old_version) = old_version {
+            let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+            let backup = file_path.with_file_name(format!("{}.v{}.bak", file_name, old_version));
+            if !backup.exists() {
+                fs::copy(file_path, backup)?;
+            }
+            self.save_to_file(&task_list, file_path)?;
+        }
+        Ok(task_list)
+    }
+
+    /// Reads a list file from anywhere, like a copy from another machine, which is upgraded
+    /// as it's read but left as it is.
+    pub fn read_file(&self, file_path: &Path) -> Result<TaskList, Error> {
+        let file_content =
+            fs::read_to_string(file_path).map_err(|error| with_path(error, file_path))?;
+        Ok(self.parse_file(file_content, file_path)?.0)
+    }
+
+    /// Decrypts and upgrades the contents of a list file, returning the version it had if it
+    /// was older.
+    fn parse_file(
+        &self,
+        mut file_content: String,
+        file_path: &Path,
+    ) -> Result<(TaskList, Option<u32>), Error> {
+        if let Some(file) = EncryptedFile::parse(&file_content) {
+            file_content = self.decrypt(&file, file_path)?;
+        }
+        let invalid = |error: &dyn std::fmt::Display| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid list file {}: {}", file_path.display(), error),
+            )
+        };
+        let mut list = serde_json::from_str(&file_content).map_err(|error| invalid(&error))?;
+        let old_version = schema::migrate(&mut list).map_err(|error| invalid(&error))?;
+        let task_list = serde_json::from_value(list).map_err(|error| invalid(&error))?;
+        Ok((task_list, old_version))
+    }
+
+    fn save_to_file(&self, task_list: &TaskList, file_path: &Path) -> Result<(), Error> {
+        let mut serialized = serde_json::to_string(task_list)?;
+        if self.encrypt {
+            self.unlock()?;
+            serialized = self.encryption.encrypt(&serialized)?;
+        }
+        write_atomically(file_path, &serialized)
+    }
+
+    /// Returns the data file of a list. The default list keeps using `task_list.json` so that
+    /// existing data is picked up, other lists are stored as `task_list.<name>.json`.
+    fn list_file_path(&self, name: &str) -> PathBuf {
+        if name == DEFAULT_LIST_NAME {
+            self.default_list_file.clone()
+        } else {
+            self.path
+                .join(format!("{}.{}.{}", FILE_PREFIX, name, FILE_EXTENSION))
+        }
+    }
+
+    /// Returns the archive file of a list, named like its data file. The other prefix keeps
+    /// archives out of [`Storage::list_names`].
+    fn archive_file_path(&self, name: &str) -> PathBuf {
+        if name == DEFAULT_LIST_NAME {
+            self.path
+                .join(format!("{}.{}", ARCHIVE_FILE_PREFIX, FILE_EXTENSION))
+        } else {
+            self.path.join(format!(
+                "{}.{}.{}",
+                ARCHIVE_FILE_PREFIX, name, FILE_EXTENSION
+            ))
+        }
+    }
+
+    /// Returns all files kept for a list.
+    fn list_files(&self, name: &str) -> [PathBuf; 2] {
+        [self.list_file_path(name), self.archive_file_path(name)]
+    }
+
+    /// Moves the files of all lists from `old_dir` to the data directory, unless it already has
+    /// lists, and returns the moved files. Lists used to be kept in the working directory.
+    pub fn migrate_files(&self, old_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        if !self.list_names()?.is_empty() || same_dir(old_dir, &self.path) {
+            return Ok(Vec::new());
+        }
+        let old_files = JsonFiles::new(old_dir.to_path_buf());
+        let mut moved = Vec::new();
+        for name in old_files.list_names()? {
+            let files = old_files.list_files(&name);
+            for (from, to) in files.into_iter().zip(self.list_files(&name)) {
+                if from.is_file() {
+                    move_file(&from, &to)?;
+                    moved.push(to);
+                }
+            }
+        }
+        Ok(moved)
+    }
+}
+
+impl Storage for JsonFiles {
+    fn list_exists(&self, name: &str) -> Result<bool, Error> {
+        Ok(self.list_file_path(name).is_file())
+    }
+
+    fn load_list(&self, name: &str) -> Result<Option<TaskList>, Error> {
+        let file_path = self.list_file_path(name);
+        if !file_path.is_file() {
+            return Ok(None);
+        }
+        self.load_from_file(&file_path).map(Some)
+    }
+
+    fn write_list(&self, name: &str, task_list: &TaskList) -> Result<(), Error> {
+        self.save_to_file(task_list, &self.list_file_path(name))
+    }
+
+    fn load_archive(&self, name: &str) -> Result<TaskList, Error> {
+        self.load_from_file(&self.archive_file_path(name))
+    }
+
+    fn save_archive(&self, name: &str, archive: &TaskList) -> Result<(), Error> {
+        self.save_to_file(archive, &self.archive_file_path(name))
+    }
+
+    fn list_names(&self) -> Result<Vec<String>, Error> {
+        let mut names = Vec::new();
+        if self.list_exists(DEFAULT_LIST_NAME)? {
+            names.push(DEFAULT_LIST_NAME.to_string());
+        }
+        for entry in fs::read_dir(&self.path)? {
+            let file_name = entry?.file_name();
+            let Some(file_name) = file_name.to_str() else {
+                continue;
+            };
+            let name = file_name
+                .strip_prefix(FILE_PREFIX)
+                .and_then(|rest| rest.strip_suffix(FILE_EXTENSION))
+                .and_then(|rest| rest.strip_prefix('.')?.strip_suffix('.'));
+            if let Some(name) = name {
+                if name != DEFAULT_LIST_NAME && validate_list_name(name).is_ok() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn remove_list(&self, name: &str) -> Result<(), Error> {
+        fs::remove_file(self.list_file_path(name))?;
+        remove_if_exists(&self.archive_file_path(name))
+    }
+
+    /// The contents of the list and archive files, by file name.
+    fn snapshot(&self, name: &str) -> Result<Vec<StoredData>, Error> {
+        self.list_files(name)
+            .iter()
+            .filter_map(|path| path.file_name()?.to_str())
+            .map(|file_name| {
+                let contents = match fs::read_to_string(self.path.join(file_name)) {
+                    Ok(contents) => Some(contents),
+                    Err(error) if error.kind() == ErrorKind::NotFound => None,
+                    Err(error) => return Err(error),
+                };
+                Ok(StoredData {
+                    key: file_name.to_string(),
+                    contents,
+                })
+            })
+            .collect()
+    }
+
+    fn restore(&self, data: StoredData) -> Result<(), Error> {
+        // Keys are file names, which can't point outside the data directory.
+        if Path::new(&data.key).file_name() != Some(data.key.as_ref()) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("'{}' isn't a file of the data directory", data.key),
+            ));
+        }
+        let path = self.path.join(&data.key);
+        match data.contents {
+            Some(contents) => write_atomically(&path, &contents),
+            None => remove_if_exists(&path),
+        }
+    }
+}
+
+/// The directory a file is in, which is the working directory for bare file names.
+pub fn parent_dir(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Renames a file, copying it where renaming fails, like across file systems.
+fn move_file(from: &Path, to: &Path) -> Result<(), Error> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
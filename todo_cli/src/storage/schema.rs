@@ -0,0 +1,130 @@
+use serde_json::Value;
+use std::io::{Error, ErrorKind};
+use uuid::Uuid;
+
+/// The version of the list file format written by this build.
+pub const CURRENT_VERSION: u32 = 3;
+
+/// Upgrades a list file from the version it's the index of to the next one. New migrations are
+/// appended, and [`CURRENT_VERSION`] raised with them.
+const MIGRATIONS: [fn(&mut Value); CURRENT_VERSION as usize] =
+    [from_unversioned, add_uuids, split_contexts];
+
+/// Upgrades the JSON of a list file to the current version, returning the version it had if it
+/// was older. Files from a newer version are refused rather than losing the fields they have.
+pub fn migrate(list: &mut Value) -> Result<Option<u32>, Error> {
+    let version = match list.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| invalid("the version isn't a number".to_string()))?,
+    };
+    if version > CURRENT_VERSION {
+        return Err(invalid(format!(
+            "version {} was written by a newer todo_cli, which knows fields this one would drop",
+            version
+        )));
+    }
+    if version == CURRENT_VERSION {
+        return Ok(None);
+    }
+    if !list.is_object() {
+        return Err(invalid("expected a JSON object".to_string()));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(list);
+    }
+    list["version"] = CURRENT_VERSION.into();
+    Ok(Some(version))
+}
+
+/// Files from before versions were added. Every field added since then has a default, like
+/// due dates and tags, so they load as they are.
+fn from_unversioned(_list: &mut Value) {}
+
+/// Version 1 had no UUIDs, so every task, in the trash too, gets a new one.
+fn add_uuids(list: &mut Value) {
+    let add_uuid = |task: &mut Value| {
+        if let Some(task) = task.as_object_mut() {
+            task.entry("uuid")
+                .or_insert_with(|| Uuid::new_v4().to_string().into());
+        }
+    };
+    if let Some(tasks) = list.get_mut("tasks").and_then(Value::as_array_mut) {
+        tasks.iter_mut().for_each(add_uuid);
+    }
+    if let Some(trash) = list.get_mut("trash").and_then(Value::as_array_mut) {
+        let tasks = trash
+            .iter_mut()
+            .filter_map(|trashed| trashed.get_mut("task"));
+        tasks.for_each(add_uuid);
+    }
+}
+
+/// Version 2 kept the contexts of tasks imported from todo.txt as tags starting with `@`,
+/// which become contexts of their own.
+fn split_contexts(list: &mut Value) {
+    let split = |task: &mut Value| {
+        let Some(task) = task.as_object_mut() else {
+            return;
+        };
+        let Some(tags) = task.get_mut("tags").and_then(Value::as_array_mut) else {
+            return;
+        };
+        let mut contexts = Vec::new();
+        tags.retain(
+            |tag| match tag.as_str().and_then(|tag| tag.strip_prefix('@')) {
+                Some(context) => {
+                    contexts.push(Value::from(context));
+                    false
+                }
+                None => true,
+            },
+        );
+        if !contexts.is_empty() {
+            task.insert("contexts".to_string(), contexts.into());
+        }
+    };
+    if let Some(tasks) = list.get_mut("tasks").and_then(Value::as_array_mut) {
+        tasks.iter_mut().for_each(split);
+    }
+    if let Some(trash) = list.get_mut("trash").and_then(Value::as_array_mut) {
+        let tasks = trash
+            .iter_mut()
+            .filter_map(|trashed| trashed.get_mut("task"));
+        tasks.for_each(split);
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrates_old_files_and_refuses_newer_ones() {
+        let mut list = json!({"tasks": [{"id": 1, "title": "Buy milk", "completed": false, "tags": ["@shop", "food"]}], "next_task_id": 2});
+        assert_eq!(migrate(&mut list).unwrap(), Some(0));
+        assert_eq!(list["version"], CURRENT_VERSION);
+        assert_eq!(list["tasks"][0]["title"], "Buy milk");
+        assert_eq!(list["tasks"][0]["tags"], json!(["food"]));
+        assert_eq!(list["tasks"][0]["contexts"], json!(["shop"]));
+        assert!(list["tasks"][0]["uuid"]
+            .as_str()
+            .unwrap()
+            .parse::<Uuid>()
+            .is_ok());
+        assert_eq!(migrate(&mut list).unwrap(), None);
+
+        let mut newer = json!({"version": CURRENT_VERSION + 1, "tasks": [], "next_task_id": 1});
+        assert_eq!(
+            migrate(&mut newer).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use super::{validate_list_name, Storage, StoredData};
+use crate::{Task, TaskList};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use std::{collections::HashMap, future::Future, io::Error, path::Path};
+use tokio::runtime::Runtime;
+
+pub const DATABASE_FILE_NAME: &str = "tasks.db";
+
+const SCHEMA: [&str; 2] = [
+    "CREATE TABLE IF NOT EXISTS lists (
+        name TEXT PRIMARY KEY,
+        next_task_id INTEGER NOT NULL,
+        trash TEXT NOT NULL
+    )",
+    // Tasks are kept as JSON, like in the files, so new fields don't need a migration.
+    "CREATE TABLE IF NOT EXISTS tasks (
+        list TEXT NOT NULL REFERENCES lists (name),
+        id INTEGER NOT NULL,
+        archived INTEGER NOT NULL,
+        task TEXT NOT NULL,
+        PRIMARY KEY (list, id)
+    )",
+];
+
+/// What [`SqliteDatabase::snapshot`] saves of a list.
+#[derive(Serialize, Deserialize)]
+struct SavedList {
+    list: TaskList,
+    archive: TaskList,
+}
+
+/// Keeps all lists in a SQLite database, with a row for every task. Saving a list only writes
+/// the tasks that changed, in a transaction, so large lists stay fast and a crash can't leave a
+/// list half written.
+pub struct SqliteDatabase {
+    runtime: Runtime,
+    pool: SqlitePool,
+}
+
+impl SqliteDatabase {
+    /// Opens the database, creating it if it doesn't exist yet.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = runtime.block_on(async {
+            let pool = SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect_with(options)
+                .await?;
+            for statement in SCHEMA {
+                sqlx::query(statement).execute(&pool).await?;
+            }
+            Ok::<_, sqlx::Error>(pool)
+        });
+        let pool = pool.map_err(database_error)?;
+        let database = SqliteDatabase { runtime, pool };
+        database.add_missing_uuids()?;
+        Ok(database)
+    }
+
+    /// Gives the tasks stored before tasks had UUIDs one, so they keep the same one from then
+    /// on. Trashed tasks get theirs when the list is saved next.
+    fn add_missing_uuids(&self) -> Result<(), Error> {
+        let rows: Vec<(String, i64, bool, String)> = self.block_on(
+            sqlx::query_as(
+                "SELECT list, id, archived, task FROM tasks WHERE json_extract(task, '$.uuid') IS NULL",
+            )
+            .fetch_all(&self.pool),
+        )?;
+        for (list, id, archived, task) in rows {
+            // Loading a task without a UUID gives it a new one.
+            let task: Task = serde_json::from_str(&task)?;
+            self.block_on(
+                sqlx::query(
+                    "UPDATE tasks SET task = $1 WHERE list = $2 AND id = $3 AND archived = $4",
+                )
+                .bind(serde_json::to_string(&task)?)
+                .bind(list)
+                .bind(id)
+                .bind(archived)
+                .execute(&self.pool),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Runs a query to completion, since the rest of the CLI is synchronous.
+    fn block_on<T>(&self, query: impl Future<Output = Result<T, sqlx::Error>>) -> Result<T, Error> {
+        self.runtime.block_on(query).map_err(database_error)
+    }
+
+    fn load_tasks(&self, name: &str, archived: bool) -> Result<Vec<Task>, Error> {
+        let rows: Vec<(String,)> = self.block_on(
+            sqlx::query_as("SELECT task FROM tasks WHERE list = $1 AND archived = $2 ORDER BY id")
+                .bind(name)
+                .bind(archived)
+                .fetch_all(&self.pool),
+        )?;
+        let mut tasks = rows
+            .iter()
+            .map(|(task,)| Ok(serde_json::from_str(task)?))
+            .collect::<Result<Vec<Task>, Error>>()?;
+        // The rows are by ID, the lists in the order arranged with `move`.
+        tasks.sort_by_key(|task| task.position);
+        Ok(tasks)
+    }
+
+    /// Writes the tasks that were added or changed, and deletes the ones that aren't there
+    /// anymore, unless they moved between the list and its archive. The row of the list is
+    /// written in the same transaction when saving the list rather than its archive.
+    fn save_tasks(
+        &self,
+        name: &str,
+        tasks: &[Task],
+        archived: bool,
+        list_row: Option<(i64, String)>,
+    ) -> Result<(), Error> {
+        let tasks = tasks
+            .iter()
+            .map(|task| Ok((i64::from(task.id), serde_json::to_string(task)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.block_on(async {
+            let mut transaction = self.pool.begin().await?;
+            if let Some((next_task_id, trash)) = list_row {
+                sqlx::query(
+                    "INSERT INTO lists (name, next_task_id, trash) VALUES ($1, $2, $3)
+                    ON CONFLICT (name) DO UPDATE SET next_task_id = $2, trash = $3",
+                )
+                .bind(name)
+                .bind(next_task_id)
+                .bind(trash)
+                .execute(&mut *transaction)
+                .await?;
+            }
+            let stored: Vec<(i64, String)> =
+                sqlx::query_as("SELECT id, task FROM tasks WHERE list = $1 AND archived = $2")
+                    .bind(name)
+                    .bind(archived)
+                    .fetch_all(&mut *transaction)
+                    .await?;
+            let mut stored: HashMap<i64, String> = stored.into_iter().collect();
+            for (id, task) in &tasks {
+                if stored.remove(id).as_ref() == Some(task) {
+                    continue;
+                }
+                sqlx::query(
+                    "INSERT INTO tasks (list, id, archived, task) VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (list, id) DO UPDATE SET archived = $3, task = $4",
+                )
+                .bind(name)
+                .bind(id)
+                .bind(archived)
+                .bind(task)
+                .execute(&mut *transaction)
+                .await?;
+            }
+            for id in stored.keys() {
+                sqlx::query("DELETE FROM tasks WHERE list = $1 AND id = $2 AND archived = $3")
+                    .bind(name)
+                    .bind(id)
+                    .bind(archived)
+                    .execute(&mut *transaction)
+                    .await?;
+            }
+            transaction.commit().await
+        })
+    }
+}
+
+impl Storage for SqliteDatabase {
+    fn list_exists(&self, name: &str) -> Result<bool, Error> {
+        let row: Option<(i64,)> = self.block_on(
+            sqlx::query_as("SELECT 1 FROM lists WHERE name = $1")
+                .bind(name)
+                .fetch_optional(&self.pool),
+        )?;
+        Ok(row.is_some())
+    }
+
+    fn load_list(&self, name: &str) -> Result<Option<TaskList>, Error> {
+        let row: Option<(i64, String)> = self.block_on(
+            sqlx::query_as("SELECT next_task_id, trash FROM lists WHERE name = $1")
+                .bind(name)
+                .fetch_optional(&self.pool),
+        )?;
+        let Some((next_task_id, trash)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(TaskList {
+            version: super::SCHEMA_VERSION,
+            tasks: self.load_tasks(name, false)?,
+            next_task_id: next_task_id as u32,
+            trash: serde_json::from_str(&trash)?,
+        }))
+    }
+
+    fn write_list(&self, name: &str, task_list: &TaskList) -> Result<(), Error> {
+        let list_row = (
+            i64::from(task_list.next_task_id),
+            serde_json::to_string(&task_list.trash)?,
+        );
+        self.save_tasks(name, &task_list.tasks, false, Some(list_row))
+    }
+
+    fn load_archive(&self, name: &str) -> Result<TaskList, Error> {
+        let mut archive = TaskList::new();
+        archive.tasks = self.load_tasks(name, true)?;
+        Ok(archive)
+    }
+
+    fn save_archive(&self, name: &str, archive: &TaskList) -> Result<(), Error> {
+        self.save_tasks(name, &archive.tasks, true, None)
+    }
+
+    fn list_names(&self) -> Result<Vec<String>, Error> {
+        let rows: Vec<(String,)> = self.block_on(
+            sqlx::query_as("SELECT name FROM lists ORDER BY name").fetch_all(&self.pool),
+        )?;
+        Ok(rows.into_iter().map(|(name,)| name).collect())
+    }
+
+    fn remove_list(&self, name: &str) -> Result<(), Error> {
+        self.block_on(async {
+            let mut transaction = self.pool.begin().await?;
+            sqlx::query("DELETE FROM tasks WHERE list = $1")
+                .bind(name)
+                .execute(&mut *transaction)
+                .await?;
+            sqlx::query("DELETE FROM lists WHERE name = $1")
+                .bind(name)
+                .execute(&mut *transaction)
+                .await?;
+            transaction.commit().await
+        })
+    }
+
+    /// The list and its archive as JSON, by list name.
+    fn snapshot(&self, name: &str) -> Result<Vec<StoredData>, Error> {
+        let contents = match self.load_list(name)? {
+            Some(list) => {
+                let archive = self.load_archive(name)?;
+                Some(serde_json::to_string(&SavedList { list, archive })?)
+            }
+            None => None,
+        };
+        Ok(vec![StoredData {
+            key: name.to_string(),
+            contents,
+        }])
+    }
+
+    fn restore(&self, data: StoredData) -> Result<(), Error> {
+        validate_list_name(&data.key)?;
+        match data.contents {
+            Some(contents) => {
+                let saved: SavedList = serde_json::from_str(&contents)?;
+                self.write_list(&data.key, &saved.list)?;
+                self.save_archive(&data.key, &saved.archive)
+            }
+            None if self.list_exists(&data.key)? => self.remove_list(&data.key),
+            None => Ok(()),
+        }
+    }
+}
+
+fn database_error(error: sqlx::Error) -> Error {
+    Error::other(format!("database error: {}", error))
+}
@@ -0,0 +1,503 @@
+use crate::{storage, storage::Storage, Task, TaskList, TrashedTask};
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use reqwest::{blocking::Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env, fs,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+use uuid::Uuid;
+
+const STATE_FILE_NAME: &str = "sync.json";
+
+/// Where lists are synced, from `[sync]` in the config file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    /// The endpoint, which keeps every list as JSON at `<url>/lists/<name>`.
+    pub url: Option<String>,
+    /// Sent as a bearer token. `TODO_SYNC_TOKEN` is used instead when it's set, so the token
+    /// doesn't have to be in the file.
+    pub token: Option<String>,
+    pub strategy: Strategy,
+}
+
+/// How `sync` combines the local and the remote list.
+#[derive(ValueEnum, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Takes every task from the side where it was changed last, so changes to different
+    /// tasks on both machines are all kept.
+    #[default]
+    Merge,
+    /// Takes the whole list from the side where anything was changed last.
+    LastWriteWins,
+}
+
+/// What `sync` changed.
+pub struct Outcome {
+    /// Tasks that were added, changed or removed in the local list.
+    pub pulled: usize,
+    /// Tasks that were added, changed or removed in the remote list.
+    pub pushed: usize,
+}
+
+/// What `merge` did with the tasks of the other file, by their IDs in the merged list.
+pub struct MergeReport {
+    pub added: Vec<u32>,
+    pub updated: Vec<u32>,
+    /// Tasks that were the same or older in the other file.
+    pub skipped: usize,
+}
+
+/// The UUIDs of the tasks of a list after it was last synced, to tell a task that was removed
+/// on one side from one that's new on the other.
+#[derive(Serialize, Deserialize)]
+struct SyncedList {
+    url: String,
+    uuids: BTreeSet<Uuid>,
+}
+
+/// Fetches the remote list, combines it with the local one and saves the result on both
+/// sides. A list that isn't on the remote yet is uploaded as it is. Nothing is saved locally
+/// unless the upload succeeded.
+pub fn sync(
+    data_dir: &Path,
+    storage: &dyn Storage,
+    config: &SyncConfig,
+    list_name: &str,
+    strategy: Strategy,
+) -> Result<Outcome, Error> {
+    let Some(base_url) = &config.url else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "no endpoint to sync with, set `url` under [sync] in the config file",
+        ));
+    };
+    let url = format!("{}/lists/{}", base_url.trim_end_matches('/'), list_name);
+    let token = env::var("TODO_SYNC_TOKEN").ok().or(config.token.clone());
+    let Some(token) = token else {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "no token to sync with, set `token` under [sync] in the config file or TODO_SYNC_TOKEN",
+        ));
+    };
+
+    let local = storage.open_list(list_name)?;
+    let client = Client::new();
+    let remote = fetch(&client, &url, &token)?;
+
+    let mut states = load_states(data_dir)?;
+    let base = states
+        .get(list_name)
+        .filter(|state| state.url == url)
+        .map(|state| state.uuids.clone())
+        .unwrap_or_default();
+    let merged = match &remote {
+        Some(remote) => merge(&local, remote, &base, strategy),
+        None => local.clone(),
+    };
+    let outcome = Outcome {
+        pulled: differences(&local, &merged),
+        pushed: remote
+            .as_ref()
+            .map_or(merged.tasks.len() + merged.trash.len(), |remote| {
+                differences(remote, &merged)
+            }),
+    };
+
+    if outcome.pushed > 0 {
+        upload(&client, &url, &token, &merged)?;
+    }
+    if outcome.pulled > 0 {
+        storage.save_list(list_name, &merged)?;
+    }
+    states.insert(
+        list_name.to_string(),
+        SyncedList {
+            url,
+            uuids: entries(&merged).into_keys().collect(),
+        },
+    );
+    storage::write_atomically(
+        &data_dir.join(STATE_FILE_NAME),
+        &serde_json::to_string(&states)?,
+    )?;
+    Ok(outcome)
+}
+
+fn fetch(client: &Client, url: &str, token: &str) -> Result<Option<TaskList>, Error> {
+    let response = client
+        .get(url)
+        .bearer_auth(token)
+        .send()
+        .map_err(|error| request_failed(url, error))?;
+    match response.status() {
+        StatusCode::NOT_FOUND => Ok(None),
+        status if status.is_success() => {
+            let mut list = response
+                .json()
+                .map_err(|error| invalid_response(url, &error))?;
+            storage::migrate_list(&mut list).map_err(|error| invalid_response(url, &error))?;
+            let list =
+                serde_json::from_value(list).map_err(|error| invalid_response(url, &error))?;
+            Ok(Some(list))
+        }
+        status => Err(refused(url, status)),
+    }
+}
+
+fn upload(client: &Client, url: &str, token: &str, task_list: &TaskList) -> Result<(), Error> {
+    let response = client
+        .put(url)
+        .bearer_auth(token)
+        .json(task_list)
+        .send()
+        .map_err(|error| request_failed(url, error))?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(refused(url, status)),
+    }
+}
+
+fn request_failed(url: &str, error: reqwest::Error) -> Error {
+    Error::other(format!("couldn't reach {}: {}", url, error))
+}
+
+fn invalid_response(url: &str, error: &dyn std::fmt::Display) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid list from {}: {}", url, error),
+    )
+}
+
+fn refused(url: &str, status: StatusCode) -> Error {
+    let kind = match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorKind::PermissionDenied,
+        _ => ErrorKind::Other,
+    };
+    Error::new(kind, format!("{} answered {}", url, status))
+}
+
+fn load_states(data_dir: &Path) -> Result<BTreeMap<String, SyncedList>, Error> {
+    let path = data_dir.join(STATE_FILE_NAME);
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|error| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid sync state {}: {}", path.display(), error),
+            )
+        }),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(error) => Err(error),
+    }
+}
+
+/// A task of one side, in the list or in its trash.
+#[derive(Clone, Copy)]
+enum Entry<'a> {
+    Active(&'a Task),
+    Trashed(&'a TrashedTask),
+}
+
+impl<'a> Entry<'a> {
+    fn task(self) -> &'a Task {
+        match self {
+            Entry::Active(task) => task,
+            Entry::Trashed(trashed) => &trashed.task,
+        }
+    }
+
+    /// When the task was last changed, where moving it to the trash is a change too.
+    fn changed_at(self) -> Option<DateTime<Local>> {
+        match self {
+            Entry::Active(task) => task.updated_at.or(task.created_at),
+            Entry::Trashed(trashed) => Some(trashed.removed_at),
+        }
+    }
+}
+
+fn entries(task_list: &TaskList) -> HashMap<Uuid, Entry<'_>> {
+    let active = task_list.tasks.iter().map(Entry::Active);
+    let trashed = task_list.trash.iter().map(Entry::Trashed);
+    active
+        .chain(trashed)
+        .map(|entry| (entry.task().uuid, entry))
+        .collect()
+}
+
+/// Counts the tasks that were added, changed or removed from one version of a list to another.
+/// A task that only has another ID isn't changed, since every machine keeps its own IDs.
+fn differences(from: &TaskList, to: &TaskList) -> usize {
+    let (from, to) = (entries(from), entries(to));
+    let uuids: HashSet<&Uuid> = from.keys().chain(to.keys()).collect();
+    uuids
+        .into_iter()
+        .filter(|uuid| match (from.get(uuid), to.get(uuid)) {
+            (Some(&a), Some(&b)) => !is_same(a, b),
+            _ => true,
+        })
+        .count()
+}
+
+/// Combines a copy of a list, like one from another machine, into the list, keeping the newest
+/// version of every task. Tasks of the copy match local ones by UUID, or by title and creation
+/// time for copies of lists from before UUIDs, which got new ones on each machine.
+pub fn combine(local: &TaskList, other: &TaskList) -> (TaskList, MergeReport) {
+    let mut other = other.clone();
+    let local_entries = entries(local);
+    let by_title: HashMap<(&str, DateTime<Local>), Uuid> = local_entries
+        .values()
+        .filter_map(|entry| {
+            let task = entry.task();
+            Some(((task.title.as_str(), task.created_at?), task.uuid))
+        })
+        .collect();
+    let other_tasks = (other.tasks.iter_mut()).chain(other.trash.iter_mut().map(|t| &mut t.task));
+    for task in other_tasks.filter(|task| !local_entries.contains_key(&task.uuid)) {
+        let key = task.created_at.map(|at| (task.title.as_str(), at));
+        if let Some(&uuid) = key.and_then(|key| by_title.get(&key)) {
+            task.uuid = uuid;
+        }
+    }
+
+    let merged = merge(local, &other, &BTreeSet::new(), Strategy::Merge);
+    let (other_entries, merged_entries) = (entries(&other), entries(&merged));
+    let mut report = MergeReport {
+        added: Vec::new(),
+        updated: Vec::new(),
+        skipped: 0,
+    };
+    for (uuid, entry) in &merged_entries {
+        if !other_entries.contains_key(uuid) {
+            continue;
+        }
+        match local_entries.get(uuid) {
+            None => report.added.push(entry.task().id),
+            Some(&local) if !is_same(local, *entry) => report.updated.push(entry.task().id),
+            Some(_) => report.skipped += 1,
+        }
+    }
+    report.added.sort_unstable();
+    report.updated.sort_unstable();
+    (merged, report)
+}
+
+/// Whether two versions of a task are the same, but for their IDs.
+fn is_same(a: Entry, b: Entry) -> bool {
+    let removed_at = |entry: Entry| match entry {
+        Entry::Active(_) => None,
+        Entry::Trashed(trashed) => Some(trashed.removed_at),
+    };
+    let renumbered = Task {
+        id: b.task().id,
+        ..a.task().clone()
+    };
+    removed_at(a) == removed_at(b) && renumbered == *b.task()
+}
+
+/// Combines two versions of a list, task by task.
+///
+/// A task that's only on one side is new there, unless it was synced before, in which case
+/// the other side removed or archived it since. Tasks keep their local IDs, and new remote
+/// ones get the next ones, since the other machine may have used them for other tasks.
+/// Subtasks and blockers are kept as far as their tasks are.
+fn merge(
+    local: &TaskList,
+    remote: &TaskList,
+    base: &BTreeSet<Uuid>,
+    strategy: Strategy,
+) -> TaskList {
+    let (local_entries, remote_entries) = (entries(local), entries(remote));
+    let newest =
+        |entries: &HashMap<Uuid, Entry>| entries.values().filter_map(|e| e.changed_at()).max();
+    let remote_is_newer = newest(&remote_entries) > newest(&local_entries);
+
+    // The local order first, then the tasks that are new on the remote.
+    let mut uuids: Vec<Uuid> = Vec::new();
+    let mut seen = HashSet::new();
+    for list in [local, remote] {
+        let tasks = list.tasks.iter().chain(list.trash.iter().map(|t| &t.task));
+        uuids.extend(
+            tasks
+                .map(|task| task.uuid)
+                .filter(|&uuid| seen.insert(uuid)),
+        );
+    }
+
+    let mut chosen: Vec<(Entry, bool)> = Vec::new();
+    for uuid in &uuids {
+        let local_entry = local_entries.get(uuid).copied();
+        let remote_entry = remote_entries.get(uuid).copied();
+        let from_remote = match strategy {
+            Strategy::LastWriteWins => remote_is_newer,
+            Strategy::Merge => match (local_entry, remote_entry) {
+                (Some(local), Some(remote)) => remote.changed_at() > local.changed_at(),
+                (Some(_), None) | (None, Some(_)) if base.contains(uuid) => continue,
+                (Some(_), None) => false,
+                (None, _) => true,
+            },
+        };
+        let entry = if from_remote {
+            remote_entry
+        } else {
+            local_entry
+        };
+        if let Some(entry) = entry {
+            chosen.push((entry, from_remote));
+        }
+    }
+
+    let mut next_task_id = local.next_task_id;
+    let ids: Vec<u32> = chosen
+        .iter()
+        .map(|(entry, _)| match local_entries.get(&entry.task().uuid) {
+            Some(local) => local.task().id,
+            None => {
+                next_task_id += 1;
+                next_task_id - 1
+            }
+        })
+        .collect();
+    // The merged ID of every remote one, for the references of the tasks taken from it.
+    let remote_ids: HashMap<u32, u32> = chosen
+        .iter()
+        .zip(&ids)
+        .filter_map(|((entry, _), &id)| {
+            let remote = remote_entries.get(&entry.task().uuid)?;
+            Some((remote.task().id, id))
+        })
+        .collect();
+
+    let mut merged = TaskList::new();
+    merged.next_task_id = next_task_id;
+    for ((entry, from_remote), id) in chosen.into_iter().zip(ids) {
+        let mut task = entry.task().clone();
+        task.id = id;
+        if from_remote {
+            task.parent_id = task.parent_id.and_then(|id| remote_ids.get(&id).copied());
+            task.blocked_by = (task.blocked_by.iter())
+                .filter_map(|id| remote_ids.get(id).copied())
+                .collect();
+        }
+        match entry {
+            Entry::Active(_) => merged.tasks.push(task),
+            Entry::Trashed(trashed) => merged.trash.push(TrashedTask {
+                removed_at: trashed.removed_at,
+                task,
+            }),
+        }
+    }
+    let active: HashSet<u32> = merged.tasks.iter().map(|task| task.id).collect();
+    for task in &mut merged.tasks {
+        task.parent_id = task.parent_id.filter(|id| active.contains(id));
+        task.blocked_by.retain(|id| active.contains(id));
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn task(id: u32, title: &str, updated_at: DateTime<Local>) -> Task {
+        let mut task = Task::new(id, title.to_string());
+        task.updated_at = Some(updated_at);
+        task
+    }
+
+    fn list(tasks: Vec<Task>) -> TaskList {
+        let mut list = TaskList::new();
+        list.next_task_id = tasks.iter().map(|task| task.id + 1).max().unwrap_or(1);
+        list.tasks = tasks;
+        list
+    }
+
+    #[test]
+    fn merges_the_newest_version_of_every_task() {
+        let earlier = Local::now() - Duration::hours(1);
+        let later = Local::now();
+        let shared = task(1, "Shared", earlier);
+        let changed_locally = task(2, "Changed here", later);
+        let removed_remotely = task(3, "Removed there", earlier);
+        let new_locally = task(4, "New here", later);
+        let local = list(vec![
+            shared.clone(),
+            changed_locally.clone(),
+            removed_remotely.clone(),
+            new_locally.clone(),
+        ]);
+
+        // The other machine has its own IDs, and one that's taken here for its new task.
+        let mut changed_remotely = shared.clone();
+        changed_remotely.id = 7;
+        changed_remotely.title = "Changed there".to_string();
+        changed_remotely.updated_at = Some(later);
+        let mut outdated = changed_locally.clone();
+        outdated.title = "Outdated".to_string();
+        outdated.updated_at = Some(earlier);
+        let mut new_remotely = task(4, "New there", later + Duration::minutes(1));
+        new_remotely.parent_id = Some(7);
+        new_remotely.blocked_by = vec![3];
+        let remote = list(vec![changed_remotely, outdated, new_remotely]);
+
+        let base = [&shared, &changed_locally, &removed_remotely]
+            .iter()
+            .map(|task| task.uuid)
+            .collect();
+        let merged = merge(&local, &remote, &base, Strategy::Merge);
+        let tasks: Vec<(u32, &str)> = (merged.tasks.iter())
+            .map(|task| (task.id, task.title.as_str()))
+            .collect();
+        assert_eq!(
+            tasks,
+            [
+                (1, "Changed there"),
+                (2, "Changed here"),
+                (4, "New here"),
+                (5, "New there")
+            ]
+        );
+        assert_eq!(merged.tasks[3].parent_id, Some(1));
+        assert!(merged.tasks[3].blocked_by.is_empty());
+        assert_eq!(merged.next_task_id, 6);
+
+        let replaced = merge(&local, &remote, &base, Strategy::LastWriteWins);
+        assert_eq!(differences(&local, &replaced), 5);
+        assert_eq!(replaced.tasks.len(), 3);
+    }
+
+    #[test]
+    fn combines_a_copy_by_uuid_or_title_and_creation_time() {
+        let earlier = Local::now() - Duration::hours(1);
+        let later = Local::now();
+        let same = task(1, "Same", earlier);
+        let older_here = task(2, "Older here", earlier);
+        let without_uuid = task(3, "Copied before UUIDs", earlier);
+        let local = list(vec![same.clone(), older_here.clone(), without_uuid.clone()]);
+
+        let mut newer_there = older_here.clone();
+        newer_there.title = "Newer there".to_string();
+        newer_there.updated_at = Some(later);
+        let mut other_uuid = without_uuid.clone();
+        other_uuid.uuid = Uuid::new_v4();
+        let new_there = task(1, "New there", later);
+        let other = list(vec![same, newer_there, other_uuid, new_there]);
+
+        let (merged, report) = combine(&local, &other);
+        let titles: Vec<&str> = (merged.tasks.iter())
+            .map(|task| task.title.as_str())
+            .collect();
+        assert_eq!(
+            titles,
+            ["Same", "Newer there", "Copied before UUIDs", "New there"]
+        );
+        assert_eq!(report.added, [4]);
+        assert_eq!(report.updated, [2]);
+        assert_eq!(report.skipped, 2);
+    }
+}
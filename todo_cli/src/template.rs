@@ -0,0 +1,54 @@
+use crate::{due, parse_tag, Priority, Task};
+use chrono::{Datelike, NaiveDateTime};
+use serde::Deserialize;
+
+/// A task that's added often, defined under `[templates.<name>]` in the config file and added
+/// with `add --template <name>`.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Template {
+    /// The title, where `{title}` is replaced by the title given to `add`, `{date}` by today's
+    /// date and `{week}` by the number of the week. Without `{title}`, a given title replaces
+    /// this one.
+    pub title: String,
+    pub tags: Vec<String>,
+    pub priority: Option<Priority>,
+    /// When the task is due relative to when it's added, like `fri 5pm` or `in 3 days`.
+    pub due: Option<String>,
+    pub note: Option<String>,
+    /// Titles of subtasks added with the task.
+    pub subtasks: Vec<String>,
+}
+
+impl Template {
+    /// Makes a task without an ID from the template, or explains what's wrong with it.
+    pub fn instantiate(&self, title: Option<&str>, now: NaiveDateTime) -> Result<Task, String> {
+        let title = match title {
+            Some(title) if !self.title.contains("{title}") => title.to_string(),
+            None if self.title.contains("{title}") => {
+                return Err("a title is needed to fill in {title}".to_string());
+            }
+            _ => self
+                .title
+                .replace("{title}", title.unwrap_or_default())
+                .replace("{date}", &due::Due::On(now.date()).to_string())
+                .replace("{week}", &now.iso_week().week().to_string()),
+        };
+        let mut task = Task::new(0, title.trim().to_string());
+        if task.title.is_empty() {
+            return Err("the title is empty, give one to add or to the template".to_string());
+        }
+        for tag in &self.tags {
+            task.add_tag(parse_tag(tag).map_err(|error| format!("tags: {}", error))?);
+        }
+        task.priority = self.priority;
+        if let Some(text) = &self.due {
+            let due = due::parse_relative_to(text, now);
+            task.due = Some(due.map_err(|error| format!("due: {}", error))?);
+        }
+        if let Some(note) = &self.note {
+            task.append_note(note);
+        }
+        Ok(task)
+    }
+}
@@ -0,0 +1,172 @@
+use crate::Task;
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Longest task title shown in a timesheet, so the columns of the days stay on screen.
+const MAX_TITLE_WIDTH: usize = 30;
+
+/// A period of work on a task, from `start` and `stop` or from `log`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Session {
+    pub started_at: DateTime<Local>,
+    /// `None` while the session is running.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stopped_at: Option<DateTime<Local>>,
+}
+
+impl Session {
+    pub fn start(now: DateTime<Local>) -> Self {
+        Session {
+            started_at: now,
+            stopped_at: None,
+        }
+    }
+
+    /// A session that ended `now`, for time that wasn't tracked while it was spent.
+    pub fn logged(duration: Duration, now: DateTime<Local>) -> Self {
+        Session {
+            started_at: now - duration,
+            stopped_at: Some(now),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.stopped_at.is_none()
+    }
+
+    /// How long the session took, or has taken so far if it's running.
+    pub fn duration(&self, now: DateTime<Local>) -> Duration {
+        self.stopped_at.unwrap_or(now) - self.started_at
+    }
+}
+
+/// The time spent on all sessions of a task.
+pub fn time_spent(sessions: &[Session], now: DateTime<Local>) -> Duration {
+    sessions.iter().map(|session| session.duration(now)).sum()
+}
+
+/// Parses durations like `45m`, `2h`, `1h30m` or `1h 30m`, for use as a clap value parser.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "invalid duration '{}', expected one like 45m or 1h30m",
+            input
+        )
+    };
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in input.trim().chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' => {
+                let count: i64 = number.parse().map_err(|_| invalid())?;
+                total += if c == 'h' {
+                    Duration::hours(count)
+                } else {
+                    Duration::minutes(count)
+                };
+                number.clear();
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    if !number.is_empty() || total <= Duration::zero() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// A duration in hours and minutes, like `1h 05m` or `45m`.
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h {:02}m", hours, minutes % 60),
+    }
+}
+
+/// Formats the time spent on every task on each of `days`, with a column for every day, a row
+/// for every task that has time on one of them, and totals. Sessions count for the day they
+/// started on.
+pub fn format_timesheet(tasks: &[&Task], days: &[NaiveDate], now: DateTime<Local>) -> String {
+    let mut rows: Vec<(String, Vec<Duration>)> = Vec::new();
+    for task in tasks {
+        let mut per_day = vec![Duration::zero(); days.len()];
+        for session in &task.sessions {
+            let day = session.started_at.date_naive();
+            if let Some(position) = days.iter().position(|&d| d == day) {
+                per_day[position] += session.duration(now);
+            }
+        }
+        if per_day.iter().any(|duration| !duration.is_zero()) {
+            rows.push((truncate(&format!("{}: {}", task.id, task.title)), per_day));
+        }
+    }
+    if rows.is_empty() {
+        return "No time was tracked\n".to_string();
+    }
+    let totals: Vec<Duration> = (0..days.len())
+        .map(|day| rows.iter().map(|(_, per_day)| per_day[day]).sum())
+        .collect();
+    rows.push(("Total".to_string(), totals));
+
+    let mut header = vec![String::new()];
+    header.extend(days.iter().map(|day| day.format("%a").to_string()));
+    header.push("Total".to_string());
+    let mut lines = vec![header];
+    for (label, per_day) in &rows {
+        let mut line = vec![label.clone()];
+        line.extend(per_day.iter().map(|&duration| {
+            if duration.is_zero() {
+                "-".to_string()
+            } else {
+                format_duration(duration)
+            }
+        }));
+        line.push(format_duration(per_day.iter().sum()));
+        lines.push(line);
+    }
+
+    let widths: Vec<usize> = (0..lines[0].len())
+        .map(|column| {
+            let widths = lines.iter().map(|line| line[column].chars().count());
+            widths.max().unwrap_or(0)
+        })
+        .collect();
+    let mut timesheet = String::new();
+    for line in lines {
+        let mut text = format!("{:<width$}", line[0], width = widths[0]);
+        for (cell, width) in line.iter().zip(&widths).skip(1) {
+            text.push_str(&format!("  {:>width$}", cell, width = width));
+        }
+        timesheet.push_str(text.trim_end());
+        timesheet.push('\n');
+    }
+    timesheet
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_TITLE_WIDTH {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(MAX_TITLE_WIDTH - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_durations() {
+        assert_eq!(parse_duration("45m"), Ok(Duration::minutes(45)));
+        assert_eq!(parse_duration("1h 30m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::hours(2)));
+        assert!(parse_duration("45").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("1.5h").is_err());
+        assert_eq!(format_duration(Duration::minutes(65)), "1h 05m");
+        assert_eq!(format_duration(Duration::minutes(45)), "45m");
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{due::Due, parse_context, parse_tag, recurrence::Recurrence, Priority, Task};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const DATE_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+/// Formats a task as a line of [todo.txt](https://github.com/todotxt/todo.txt), like
+/// `(A) 2024-05-01 Call mom +family @phone due:2024-05-18`. Tags are projects. Due dates and recurrence use the common `due:` and `rec:`
+/// extensions. Notes, subtasks, blockers and reminders have no place in the format and are
+/// left out.
+pub fn format_task(task: &Task) -> String {
+    let mut words = Vec::new();
+    if task.completed {
+        words.push("x".to_string());
+        if let Some(completed_at) = task.completed_at {
+            words.push(completed_at.format(DATE_FORMAT).to_string());
+        }
+    }
+    // Completed tasks keep their priority as an extension, like todo.txt tools do.
+    let priority = task.priority.map(|priority| match priority {
+        Priority::High => 'A',
+        Priority::Medium => 'B',
+        Priority::Low => 'C',
+    });
+    if let (Some(priority), false) = (priority, task.completed) {
+        words.push(format!("({})", priority));
+    }
+    // Completed tasks can only have a creation date after their completion date.
+    if let Some(created_at) = task.created_at {
+        if !task.completed || task.completed_at.is_some() {
+            words.push(created_at.format(DATE_FORMAT).to_string());
+        }
+    }
+    words.push(task.title.clone());
+    for tag in &task.tags {
+        words.push(format!("+{}", tag));
+    }
+    for context in &task.contexts {
+        words.push(format!("@{}", context));
+    }
+    match task.due {
+        Some(Due::At(date_time)) => {
+            words.push(format!("due:{}", date_time.format(DATE_TIME_FORMAT)))
+        }
+        Some(Due::On(date)) => words.push(format!("due:{}", date.format(DATE_FORMAT))),
+        None => {}
+    }
+    if let Some(repeat) = task.repeat {
+        words.push(format!("rec:{}", repeat.to_todotxt()));
+    }
+    if let (Some(priority), true) = (priority, task.completed) {
+        words.push(format!("pri:{}", priority));
+    }
+    words.join(" ")
+}
+
+/// Parses a todo.txt line into a task without an ID, or `None` if the line is blank. Words
+/// that aren't understood stay in the title, so nothing is lost.
+pub fn parse_task(line: &str) -> Option<Task> {
+    let mut words = line.split_whitespace().peekable();
+    words.peek()?;
+    let mut task = Task::new(0, String::new());
+    task.created_at = None;
+
+    if words.next_if_eq(&"x").is_some() {
+        task.completed = true;
+        task.completed_at = words
+            .next_if(|word| parse_date(word).is_some())
+            .and_then(parse_date)
+            .map(start_of);
+    }
+    task.priority = words
+        .next_if(|word| parse_priority(word).is_some())
+        .and_then(parse_priority);
+    task.created_at = words
+        .next_if(|word| parse_date(word).is_some())
+        .and_then(parse_date)
+        .map(start_of);
+
+    let mut title = Vec::new();
+    for word in words {
+        let tag = word
+            .strip_prefix('+')
+            .and_then(|project| parse_tag(project).ok());
+        let context = match word.strip_prefix('@') {
+            Some(context) if !context.is_empty() => parse_context(context).ok(),
+            _ => None,
+        };
+        let (key, value) = word.split_once(':').unwrap_or_default();
+        if let Some(tag) = tag {
+            task.add_tag(tag);
+        } else if let Some(context) = context {
+            if !task.contexts.contains(&context) {
+                task.contexts.push(context);
+            }
+        } else if let ("due", Some(due)) = (key, parse_due(value)) {
+            task.due = Some(due);
+        } else if let ("rec", Some(repeat)) = (key, Recurrence::from_todotxt(value)) {
+            task.repeat = Some(repeat);
+        } else if let ("pri", Some(priority)) = (key, parse_priority(&format!("({})", value))) {
+            task.priority = Some(priority);
+        } else {
+            title.push(word);
+        }
+    }
+    task.title = title.join(" ");
+    Some(task)
+}
+
+/// `(A)` is high priority, `(B)` medium and everything down to `(Z)` low.
+fn parse_priority(word: &str) -> Option<Priority> {
+    let letter = word.strip_prefix('(')?.strip_suffix(')')?;
+    match letter {
+        "A" => Some(Priority::High),
+        "B" => Some(Priority::Medium),
+        _ if letter.len() == 1 && letter.chars().all(|c| c.is_ascii_uppercase()) => {
+            Some(Priority::Low)
+        }
+        _ => None,
+    }
+}
+
+fn parse_date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, DATE_FORMAT).ok()
+}
+
+fn parse_due(value: &str) -> Option<Due> {
+    NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT)
+        .map(Due::At)
+        .ok()
+        .or_else(|| parse_date(value).map(Due::On))
+}
+
+/// Dates in todo.txt have no time, so they're taken as the start of the day.
+fn start_of(date: NaiveDate) -> DateTime<Local> {
+    date.and_time(NaiveTime::MIN)
+        .and_local_timezone(Local)
+        .earliest()
+        .unwrap_or_else(Local::now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_todotxt_lines() {
+        let task =
+            parse_task("(A) 2024-05-01 Call mom +family @phone due:2024-05-18 rec:1w").unwrap();
+        assert_eq!(task.title, "Call mom");
+        assert_eq!(task.priority, Some(Priority::High));
+        assert_eq!(task.tags, vec!["family"]);
+        assert_eq!(task.contexts, vec!["phone"]);
+        assert_eq!(
+            task.due,
+            Some(Due::On(NaiveDate::from_ymd_opt(2024, 5, 18).unwrap()))
+        );
+        assert_eq!(
+            task.repeat.map(|repeat| repeat.to_string()).as_deref(),
+            Some("weekly")
+        );
+        assert!(!task.completed);
+
+        let task = parse_task("x 2024-05-17 2024-05-01 Pay rent pri:B").unwrap();
+        assert!(task.completed);
+        assert_eq!(task.priority, Some(Priority::Medium));
+        assert_eq!(
+            task.completed_at.unwrap().date_naive().to_string(),
+            "2024-05-17"
+        );
+        assert_eq!(
+            task.created_at.unwrap().date_naive().to_string(),
+            "2024-05-01"
+        );
+
+        // Words that look like tags or extensions but aren't stay in the title.
+        let task = parse_task("Email x@example.com about due:soon + stuff").unwrap();
+        assert_eq!(task.title, "Email x@example.com about due:soon + stuff");
+        assert!(parse_task("   ").is_none());
+    }
+
+    #[test]
+    fn formats_tasks_as_todotxt_lines() {
+        for line in [
+            "(A) 2024-05-01 Call mom +family @phone due:2024-05-18 rec:1w",
+            "x 2024-05-17 2024-05-01 Pay rent due:2024-05-17T17:00 pri:B",
+            "Read a book",
+        ] {
+            assert_eq!(format_task(&parse_task(line).unwrap()), line);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{join_ids, render, render::Renderer, storage::Storage, Task, TaskList};
+use std::io::{self, Error, ErrorKind, IsTerminal, Read, Write};
+
+/// Rows of the screen that aren't tasks: the header, a message and the prompt or key help.
+const RESERVED_ROWS: usize = 3;
+const HELP: &str = "↑/↓ move  space complete  a add  e edit  / filter  q quit";
+
+enum Key {
+    Up,
+    Down,
+    Enter,
+    Escape,
+    Backspace,
+    Quit,
+    Char(char),
+}
+
+/// What the bottom line of the screen is used for.
+enum Mode {
+    Browse,
+    Filter,
+    Add,
+    Edit(u32),
+}
+
+struct App<'a> {
+    storage: &'a dyn Storage,
+    list_name: &'a str,
+    task_list: TaskList,
+    filter: String,
+    /// The position of the selected task among the visible ones.
+    selected: usize,
+    /// The position of the first visible task on the screen, when they don't all fit.
+    scroll: usize,
+    mode: Mode,
+    input: String,
+    message: String,
+}
+
+/// Runs an interactive view of a list until `q` is pressed. Every change is saved right away.
+pub fn run(storage: &dyn Storage, list_name: &str, renderer: &Renderer) -> Result<(), Error> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(Error::new(ErrorKind::Unsupported, "tui needs a terminal"));
+    }
+    let mut app = App {
+        storage,
+        list_name,
+        task_list: storage.open_list(list_name)?,
+        filter: String::new(),
+        selected: 0,
+        scroll: 0,
+        mode: Mode::Browse,
+        input: String::new(),
+        message: String::new(),
+    };
+
+    let _raw_mode = RawMode::enable()?;
+    let mut stdin = io::stdin().lock();
+    let mut buffer = [0; 64];
+    loop {
+        app.draw(renderer)?;
+        let read = stdin.read(&mut buffer)?;
+        for key in parse_keys(&buffer[..read]) {
+            if !app.handle(key)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl App<'_> {
+    fn visible_tasks(&self) -> Vec<&Task> {
+        let query = self.filter.trim().to_lowercase();
+        self.task_list
+            .list_tasks()
+            .iter()
+            .filter(|task| task.matches(&query, false))
+            .collect()
+    }
+
+    fn selected_task(&self) -> Option<&Task> {
+        self.visible_tasks().get(self.selected).copied()
+    }
+
+    /// Handles a key press, returning `false` when it's time to quit.
+    fn handle(&mut self, key: Key) -> Result<bool, Error> {
+        match (&self.mode, key) {
+            (_, Key::Quit) | (Mode::Browse, Key::Char('q')) => return Ok(false),
+            (Mode::Browse, Key::Up | Key::Char('k')) => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            (Mode::Browse, Key::Down | Key::Char('j')) => self.selected += 1,
+            (Mode::Browse, Key::Char(' ')) => self.toggle_completion()?,
+            (Mode::Browse, Key::Char('a')) => {
+                self.input.clear();
+                self.mode = Mode::Add;
+            }
+            (Mode::Browse, Key::Char('e') | Key::Enter) => {
+                if let Some(task) = self.selected_task() {
+                    let (id, title) = (task.id, task.title.clone());
+                    self.input = title;
+                    self.mode = Mode::Edit(id);
+                }
+            }
+            (Mode::Browse, Key::Char('/')) => self.mode = Mode::Filter,
+            (Mode::Browse, Key::Escape) => self.filter.clear(),
+            (Mode::Browse, _) => {}
+
+            // The filter applies while it's typed.
+            (Mode::Filter, Key::Char(c)) => {
+                self.filter.push(c);
+                self.selected = 0;
+            }
+            (Mode::Filter, Key::Backspace) => {
+                self.filter.pop();
+            }
+            (Mode::Filter, Key::Escape) => {
+                self.filter.clear();
+                self.mode = Mode::Browse;
+            }
+            (Mode::Filter, _) => self.mode = Mode::Browse,
+
+            (Mode::Add | Mode::Edit(_), Key::Char(c)) => self.input.push(c),
+            (Mode::Add | Mode::Edit(_), Key::Backspace) => {
+                self.input.pop();
+            }
+            (Mode::Add | Mode::Edit(_), Key::Escape) => self.mode = Mode::Browse,
+            (Mode::Add, Key::Enter) => self.add_task()?,
+            (Mode::Edit(id), Key::Enter) => {
+                let id = *id;
+                self.edit_title(id)?;
+            }
+            (Mode::Add | Mode::Edit(_), _) => {}
+        }
+        let visible = self.visible_tasks().len();
+        self.selected = self.selected.min(visible.saturating_sub(1));
+        Ok(true)
+    }
+
+    fn toggle_completion(&mut self) -> Result<(), Error> {
+        let Some(task) = self.selected_task() else {
+            return Ok(());
+        };
+        let (id, completed) = (task.id, task.completed);
+        if completed {
+            if let Some(task) = self.task_list.get_task(id) {
+                task.mark_uncompleted();
+            }
+            self.message = format!("Reopened {}", id);
+        } else {
+            let completion = self.task_list.complete_tasks(&[id], false);
+            if let Some(refusal) = completion.refusals.first() {
+                self.message = format!("{}, complete them first", refusal);
+                return Ok(());
+            }
+            self.message = format!("Completed {}", id);
+            if !completion.next_occurrences.is_empty() {
+                let next = join_ids(&completion.next_occurrences);
+                self.message
+                    .push_str(&format!(", next occurrence is {}", next));
+            }
+        }
+        self.save()
+    }
+
+    fn add_task(&mut self) -> Result<(), Error> {
+        self.mode = Mode::Browse;
+        let title = self.input.trim();
+        if title.is_empty() {
+            return Ok(());
+        }
+        let id = self.task_list.add_task(title.to_string()).id;
+        self.message = format!("Added task {}", id);
+        self.save()?;
+        if let Some(position) = self.visible_tasks().iter().position(|t| t.id == id) {
+            self.selected = position;
+        }
+        Ok(())
+    }
+
+    fn edit_title(&mut self, id: u32) -> Result<(), Error> {
+        self.mode = Mode::Browse;
+        let title = self.input.trim().to_string();
+        match self.task_list.get_task(id) {
+            Some(task) if !title.is_empty() => {
+                task.title = title;
+                task.take_contexts();
+            }
+            _ => return Ok(()),
+        }
+        self.message = format!("Edited task {}", id);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        self.storage.save_list(self.list_name, &self.task_list)
+    }
+
+    fn draw(&mut self, renderer: &Renderer) -> Result<(), Error> {
+        let (_, rows) = render::terminal_size().unwrap_or((80, 24));
+        let task_rows = rows.saturating_sub(RESERVED_ROWS).max(1);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + task_rows {
+            self.scroll = self.selected + 1 - task_rows;
+        }
+
+        let tasks = self.visible_tasks();
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        let shown = if self.filter.is_empty() {
+            format!("{} tasks", tasks.len())
+        } else {
+            format!("{} matching '{}'", tasks.len(), self.filter)
+        };
+        screen.push_str(&format!("\x1b[1m{}\x1b[0m  {}\r\n", self.list_name, shown));
+        for (position, task) in tasks.iter().enumerate().skip(self.scroll).take(task_rows) {
+            let marker = if position == self.selected {
+                "> "
+            } else {
+                "  "
+            };
+            let line = renderer.summary_line(&self.task_list, task);
+            screen.push_str(&format!("{}{}\r\n", marker, line));
+        }
+
+        let prompt = match self.mode {
+            Mode::Browse => HELP.to_string(),
+            Mode::Filter => format!("Filter: {}", self.filter),
+            Mode::Add => format!("New task: {}", self.input),
+            Mode::Edit(id) => format!("Title of task {}: {}", id, self.input),
+        };
+        screen.push_str(&format!(
+            "\x1b[{};1H{}\r\n{}",
+            rows - 1,
+            self.message,
+            prompt
+        ));
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(screen.as_bytes())?;
+        stdout.flush()
+    }
+}
+
+/// Splits what was read from the terminal into keys. Escape sequences of other keys are skipped.
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' if chars.next_if(|&c| c == '[' || c == 'O').is_some() => {
+                // The final byte of a sequence is a letter or `~`.
+                let end = chars.find(|c| c.is_ascii_alphabetic() || *c == '~');
+                match end {
+                    Some('A') => Key::Up,
+                    Some('B') => Key::Down,
+                    _ => continue,
+                }
+            }
+            '\x1b' => Key::Escape,
+            '\r' | '\n' => Key::Enter,
+            '\x7f' | '\x08' => Key::Backspace,
+            '\x03' | '\x04' => Key::Quit,
+            c if c.is_control() => continue,
+            c => Key::Char(c),
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+/// Puts the terminal into raw mode on an alternate screen, so that keys arrive as they're
+/// pressed, and restores it when dropped.
+struct RawMode {
+    #[cfg(unix)]
+    original: libc::termios,
+}
+
+impl RawMode {
+    #[cfg(unix)]
+    fn enable() -> Result<Self, Error> {
+        let mut termios = std::mem::MaybeUninit::uninit();
+        // SAFETY: tcgetattr fills in the termios it's given when it succeeds.
+        let original = unsafe {
+            if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) != 0 {
+                return Err(Error::last_os_error());
+            }
+            termios.assume_init()
+        };
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        // SAFETY: tcsetattr only reads the termios it's given.
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        // Switch to the alternate screen, hide the cursor and cut long lines instead of wrapping.
+        print!("\x1b[?1049h\x1b[?25l\x1b[?7l");
+        io::stdout().flush()?;
+        Ok(RawMode { original })
+    }
+
+    #[cfg(not(unix))]
+    fn enable() -> Result<Self, Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "tui is only supported on Unix",
+        ))
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[?7h\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        // SAFETY: tcsetattr only reads the termios it's given.
+        #[cfg(unix)]
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original);
+        }
+    }
+}
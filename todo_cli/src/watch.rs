@@ -0,0 +1,179 @@
+use std::{
+    io::Error,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// Files that change without the lists changing, like the lock file that every command opens.
+fn is_ignored(file_name: &[u8]) -> bool {
+    file_name == b"todo.lock" || file_name.starts_with(b".")
+}
+
+/// Changes to a command's files come in quick succession, and are waited for together.
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Waits for the files of a directory to change, through inotify on Linux and by comparing
+/// their modification times every second elsewhere.
+pub struct Watcher {
+    #[cfg(target_os = "linux")]
+    inotify: std::os::fd::OwnedFd,
+    #[cfg(not(target_os = "linux"))]
+    dir: std::path::PathBuf,
+    #[cfg(not(target_os = "linux"))]
+    fingerprint: Vec<(std::ffi::OsString, Option<std::time::SystemTime>)>,
+}
+
+impl Watcher {
+    #[cfg(target_os = "linux")]
+    pub fn new(dir: &Path) -> Result<Self, Error> {
+        use std::{
+            ffi::CString,
+            os::{fd::FromRawFd, unix::ffi::OsStrExt},
+        };
+
+        // SAFETY: inotify_init1 takes no pointers, and the descriptor it returns is owned here.
+        let inotify = unsafe {
+            let fd = libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK);
+            if fd < 0 {
+                return Err(Error::last_os_error());
+            }
+            std::os::fd::OwnedFd::from_raw_fd(fd)
+        };
+        let path = CString::new(dir.as_os_str().as_bytes())?;
+        // Lists are replaced by renaming a new file over them, which is a move.
+        let events = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE | libc::IN_DELETE;
+        // SAFETY: the path is a valid C string that outlives the call.
+        let watch = unsafe { libc::inotify_add_watch(raw(&inotify), path.as_ptr(), events) };
+        if watch < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(Watcher { inotify })
+    }
+
+    /// Waits until a file of the directory changed or the timeout passed, returning whether a
+    /// file changed.
+    #[cfg(target_os = "linux")]
+    pub fn wait(&mut self, timeout: Duration) -> Result<bool, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if !self.poll(left)? {
+                return Ok(false);
+            }
+            if self.read_changes()? {
+                // Take in the rest of the changes, so they're shown at once.
+                while self.poll(SETTLE_TIME)? {
+                    self.read_changes()?;
+                }
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Waits for events to read, returning `false` when there were none in time.
+    #[cfg(target_os = "linux")]
+    fn poll(&self, timeout: Duration) -> Result<bool, Error> {
+        let mut pollfd = libc::pollfd {
+            fd: raw(&self.inotify),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+        // SAFETY: poll is given a single pollfd, which lives on the stack for the call.
+        match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+            result if result < 0 => {
+                let error = Error::last_os_error();
+                match error.kind() {
+                    std::io::ErrorKind::Interrupted => Ok(false),
+                    _ => Err(error),
+                }
+            }
+            result => Ok(result > 0),
+        }
+    }
+
+    /// Reads the pending events, returning whether any of them was about a file that matters.
+    #[cfg(target_os = "linux")]
+    fn read_changes(&self) -> Result<bool, Error> {
+        const HEADER_LEN: usize = std::mem::size_of::<libc::inotify_event>();
+        let mut buffer = [0u8; 4096];
+        let mut changed = false;
+        loop {
+            // SAFETY: read writes at most the length of the buffer it's given.
+            let read =
+                unsafe { libc::read(raw(&self.inotify), buffer.as_mut_ptr().cast(), buffer.len()) };
+            if read < 0 {
+                let error = Error::last_os_error();
+                return match error.kind() {
+                    std::io::ErrorKind::WouldBlock => Ok(changed),
+                    _ => Err(error),
+                };
+            }
+            let events = &buffer[..read as usize];
+            let mut offset = 0;
+            while offset + HEADER_LEN <= events.len() {
+                // SAFETY: there's a whole header at the offset, which may not be aligned.
+                let event: libc::inotify_event =
+                    unsafe { std::ptr::read_unaligned(events[offset..].as_ptr().cast()) };
+                // The name follows the header, padded with NUL bytes to `len`.
+                let name_start = offset + HEADER_LEN;
+                let name_end = (name_start + event.len as usize).min(events.len());
+                let name = &events[name_start..name_end];
+                let name = name.split(|&byte| byte == 0).next().unwrap_or_default();
+                changed |= !is_ignored(name);
+                offset = name_end;
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(dir: &Path) -> Result<Self, Error> {
+        let dir = dir.to_path_buf();
+        let fingerprint = modification_times(&dir)?;
+        Ok(Watcher { dir, fingerprint })
+    }
+
+    /// Waits until a file of the directory changed or the timeout passed, returning whether a
+    /// file changed.
+    #[cfg(not(target_os = "linux"))]
+    pub fn wait(&mut self, timeout: Duration) -> Result<bool, Error> {
+        const INTERVAL: Duration = Duration::from_secs(1);
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            std::thread::sleep(INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+            let fingerprint = modification_times(&self.dir)?;
+            if fingerprint != self.fingerprint {
+                std::thread::sleep(SETTLE_TIME);
+                self.fingerprint = modification_times(&self.dir)?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn raw(fd: &std::os::fd::OwnedFd) -> i32 {
+    use std::os::fd::AsRawFd;
+    fd.as_raw_fd()
+}
+
+/// The names and modification times of the files that matter, sorted by name.
+#[cfg(not(target_os = "linux"))]
+fn modification_times(
+    dir: &Path,
+) -> Result<Vec<(std::ffi::OsString, Option<std::time::SystemTime>)>, Error> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !is_ignored(entry.file_name().as_encoded_bytes()) {
+            let modified = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            files.push((entry.file_name(), modified));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,12 @@
+fn main() {
+    // Rebuild when a migration changes, since `sqlx::migrate!` embeds them into the binary.
+    println!("cargo:rerun-if-changed=migrations");
+
+    // Generate the gRPC service with the bundled protoc, so building doesn't require one to be
+    // installed.
+    std::env::set_var(
+        "PROTOC",
+        protoc_bin_vendored::protoc_bin_path().expect("No bundled protoc for this platform"),
+    );
+    tonic_build::compile_protos("proto/url_shortener.proto").expect("Cannot compile the protos");
+}
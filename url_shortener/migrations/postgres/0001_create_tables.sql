@@ -0,0 +1,40 @@
+-- IF NOT EXISTS lets this migration adopt databases that were created before migrations existed.
+
+CREATE TABLE IF NOT EXISTS users (
+    id BIGSERIAL PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS short_urls (
+    id BIGSERIAL PRIMARY KEY,
+    original_url TEXT NOT NULL,
+    short_code TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL,
+    expires_at TEXT,
+    updated_at TEXT,
+    owner_id BIGINT REFERENCES users (id),
+    redirect_type TEXT NOT NULL DEFAULT 'temporary',
+    max_clicks BIGINT,
+    remaining_clicks BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS clicks (
+    id BIGSERIAL PRIMARY KEY,
+    short_url_id BIGINT NOT NULL REFERENCES short_urls (id),
+    clicked_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL,
+    revoked_at TEXT
+);
@@ -0,0 +1,4 @@
+-- short_code is already indexed by its UNIQUE constraint. This index finds it regardless of case.
+CREATE INDEX IF NOT EXISTS short_urls_short_code_lower ON short_urls (LOWER(short_code));
+CREATE INDEX IF NOT EXISTS short_urls_original_url ON short_urls (original_url);
+CREATE INDEX IF NOT EXISTS clicks_short_url_id ON clicks (short_url_id);
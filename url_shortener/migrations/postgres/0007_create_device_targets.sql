@@ -0,0 +1,9 @@
+-- Destinations that replace the default original_url on the given kind of device: ios, android,
+-- mobile or desktop.
+CREATE TABLE device_targets (
+    id BIGSERIAL PRIMARY KEY,
+    short_url_id BIGINT NOT NULL REFERENCES short_urls (id),
+    device TEXT NOT NULL,
+    original_url TEXT NOT NULL,
+    UNIQUE (short_url_id, device)
+);
@@ -0,0 +1,4 @@
+-- Redirects counted as they happen, so listing short URLs doesn't have to count their clicks, and
+-- the count survives clicks that are removed after the click retention.
+ALTER TABLE short_urls ADD COLUMN click_count BIGINT NOT NULL DEFAULT 0;
+UPDATE short_urls SET click_count = (SELECT COUNT(*) FROM clicks WHERE clicks.short_url_id = short_urls.id);
@@ -0,0 +1,2 @@
+-- Query parameters added to the destination on every redirect, as a JSON object, or NULL for none.
+ALTER TABLE short_urls ADD COLUMN tracking_params TEXT;
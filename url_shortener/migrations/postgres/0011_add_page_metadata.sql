@@ -0,0 +1,4 @@
+-- The title and favicon of the destination page, fetched in the background after shortening.
+ALTER TABLE short_urls ADD COLUMN page_title TEXT;
+ALTER TABLE short_urls ADD COLUMN favicon_url TEXT;
+ALTER TABLE short_urls ADD COLUMN metadata_fetched_at TEXT;
@@ -0,0 +1,15 @@
+-- Short URLs created by POST /shorten requests with an Idempotency-Key header, so that retries of
+-- the same request get the same short URL back.
+CREATE TABLE idempotency_keys (
+    -- Who sent the key, since every client picks its own keys.
+    caller TEXT NOT NULL,
+    key TEXT NOT NULL,
+    -- SHA-256 of the request, so the key can't be reused for a different one.
+    request_hash TEXT NOT NULL,
+    -- NULL while the first request with the key is still running.
+    short_code TEXT,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    PRIMARY KEY (caller, key)
+);
+CREATE INDEX idempotency_keys_expires_at ON idempotency_keys (expires_at);
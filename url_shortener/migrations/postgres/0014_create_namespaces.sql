@@ -0,0 +1,16 @@
+-- Prefixes that short codes can be registered under, like docs in /docs/abc1. The short codes
+-- themselves are stored with their prefix, as 'docs/abc1'. A NULL owner_id means the namespace
+-- was created with an API key.
+CREATE TABLE namespaces (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    owner_id BIGINT REFERENCES users (id),
+    created_at TEXT NOT NULL
+);
+
+-- Users besides the owner who may register short codes in a namespace.
+CREATE TABLE namespace_members (
+    namespace_id BIGINT NOT NULL REFERENCES namespaces (id),
+    user_id BIGINT NOT NULL REFERENCES users (id),
+    PRIMARY KEY (namespace_id, user_id)
+);
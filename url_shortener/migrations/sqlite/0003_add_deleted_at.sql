@@ -0,0 +1,3 @@
+-- Deleted short URLs are only deactivated, so they can be reactivated until the cleanup task
+-- purges them.
+ALTER TABLE short_urls ADD COLUMN deleted_at TEXT;
@@ -0,0 +1,4 @@
+-- Where clicks come from, for analytics. Client IPs are only stored as salted hashes.
+ALTER TABLE clicks ADD COLUMN referrer TEXT;
+ALTER TABLE clicks ADD COLUMN user_agent TEXT;
+ALTER TABLE clicks ADD COLUMN ip_hash TEXT;
@@ -0,0 +1,8 @@
+-- Destinations that replace the default original_url for visitors from a given country.
+CREATE TABLE redirect_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    short_url_id BIGINT NOT NULL REFERENCES short_urls (id),
+    country TEXT NOT NULL,
+    original_url TEXT NOT NULL,
+    UNIQUE (short_url_id, country)
+);
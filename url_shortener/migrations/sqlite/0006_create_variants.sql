@@ -0,0 +1,11 @@
+-- Alternative destinations of a split test, each receiving weight percent of the visitors. The
+-- rest go to the short URL's original_url.
+CREATE TABLE variants (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    short_url_id BIGINT NOT NULL REFERENCES short_urls (id),
+    original_url TEXT NOT NULL,
+    weight BIGINT NOT NULL
+);
+
+-- The variant a click was sent to, or NULL for original_url.
+ALTER TABLE clicks ADD COLUMN variant_id BIGINT;
@@ -0,0 +1,2 @@
+-- The extra domain a short URL is served on, or NULL for the host of the base URL.
+ALTER TABLE short_urls ADD COLUMN domain TEXT;
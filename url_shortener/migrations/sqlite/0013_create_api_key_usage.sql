@@ -0,0 +1,7 @@
+-- How many short URLs each API key created per UTC day ('2026-10-14') and month ('2026-10').
+CREATE TABLE api_key_usage (
+    api_key_id BIGINT NOT NULL REFERENCES api_keys (id),
+    period TEXT NOT NULL,
+    links_created BIGINT NOT NULL,
+    PRIMARY KEY (api_key_id, period)
+);
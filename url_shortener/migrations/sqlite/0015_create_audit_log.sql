@@ -0,0 +1,13 @@
+-- Who created, changed or deleted short URLs, API keys and namespaces. details is a JSON object
+-- describing the change.
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    details TEXT NOT NULL
+);
+
+CREATE INDEX audit_log_target ON audit_log (target);
+CREATE INDEX audit_log_actor ON audit_log (actor);
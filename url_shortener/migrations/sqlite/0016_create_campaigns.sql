@@ -0,0 +1,12 @@
+-- Named groups of short URLs, like the links of one marketing campaign, whose clicks are counted
+-- together. A NULL owner_id means the campaign was created with an API key.
+CREATE TABLE campaigns (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    owner_id BIGINT REFERENCES users (id),
+    created_at TEXT NOT NULL
+);
+
+-- A short URL belongs to at most one campaign.
+ALTER TABLE short_urls ADD COLUMN campaign_id BIGINT REFERENCES campaigns (id);
+CREATE INDEX short_urls_campaign_id ON short_urls (campaign_id);
@@ -0,0 +1,11 @@
+-- The last check of the destination by the health checker. health_status is the HTTP status it
+-- answered with, or NULL when it couldn't be reached, in which case health_error says why.
+ALTER TABLE short_urls ADD COLUMN health_checked_at TEXT;
+ALTER TABLE short_urls ADD COLUMN health_status BIGINT;
+ALTER TABLE short_urls ADD COLUMN health_latency_ms BIGINT;
+ALTER TABLE short_urls ADD COLUMN health_error TEXT;
+-- Failed checks in a row. Once there are enough of them, the destination counts as broken from
+-- broken_since on, until a check succeeds again.
+ALTER TABLE short_urls ADD COLUMN health_failures BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE short_urls ADD COLUMN broken_since TEXT;
+CREATE INDEX short_urls_broken_since ON short_urls (broken_since);
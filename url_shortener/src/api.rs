@@ -0,0 +1,512 @@
+//! The REST API: authentication and rate limiting middleware, and the router that ties the handlers
+//! of its modules together. The JSON endpoints are served under [`API_PREFIX`], the redirects at
+//! the root.
+
+mod admin;
+mod auth;
+mod links;
+mod openapi;
+mod redirects;
+mod stats;
+
+use crate::{
+    api_keys::{ApiKeyStore, LinkQuota, QuotaPeriod},
+    audit::AuditLog,
+    clicks::ClickRecorder,
+    config::Config,
+    domains::Domains,
+    monitoring::{self, MonitoringState},
+    rate_limit::RateLimiter,
+    url_shortener::{self, ErrorDetails, LinkScope, UrlShortener},
+    users::UserStore,
+    web_ui,
+};
+use axum::{
+    extract::{ConnectInfo, DefaultBodyLimit, Host, Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER},
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, put},
+    Json, Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+};
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    limit::RequestBodyLimitLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    timeout::TimeoutLayer,
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::{error, Level};
+use utoipa::{OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
+
+const DEFAULT_LINKS_PER_PAGE: u32 = 20;
+const MAX_LINKS_PER_PAGE: u32 = 100;
+pub(crate) const API_KEY_HEADER: &str = "x-api-key";
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+const DEPRECATION_HEADER: &str = "deprecation";
+
+/// Tunable behavior of the HTTP API.
+#[derive(Clone)]
+pub struct ApiOptions {
+    pub trust_forwarded_for: bool,
+    pub reuse_existing_by_default: bool,
+    pub max_batch_size: usize,
+    /// How long redirects of permanent short URLs may be cached by anyone.
+    pub permanent_redirect_max_age: std::time::Duration,
+    /// How long redirects of temporary short URLs may be cached by the visitor's browser.
+    pub temporary_redirect_max_age: std::time::Duration,
+    /// How long retries with the same `Idempotency-Key` return the first short URL.
+    pub idempotency_key_ttl: std::time::Duration,
+    /// Where visitors of unknown short codes are redirected to, with `{short_code}` replaced.
+    pub fallback_redirect_url: Option<String>,
+    /// How many short URLs every API key may create.
+    pub link_quota: LinkQuota,
+}
+
+pub struct AppState {
+    domains: Arc<Domains>,
+    url_shortener: UrlShortener,
+    api_keys: ApiKeyStore,
+    users: UserStore,
+    rate_limiter: RateLimiter,
+    click_recorder: ClickRecorder,
+    options: ApiOptions,
+    audit_log: AuditLog,
+}
+
+impl AppState {
+    pub fn new(
+        domains: Arc<Domains>,
+        url_shortener: UrlShortener,
+        api_keys: ApiKeyStore,
+        users: UserStore,
+        rate_limiter: RateLimiter,
+        click_recorder: ClickRecorder,
+        options: ApiOptions,
+    ) -> Self {
+        AppState {
+            domains,
+            url_shortener,
+            api_keys,
+            users,
+            rate_limiter,
+            click_recorder,
+            options,
+            audit_log: AuditLog::disabled(),
+        }
+    }
+
+    /// Records administrative actions in `audit_log`, which records nothing by default.
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+}
+
+impl Clone for AppState {
+    fn clone(&self) -> Self {
+        Self {
+            domains: self.domains.clone(),
+            url_shortener: self.url_shortener.clone(),
+            api_keys: self.api_keys.clone(),
+            users: self.users.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            click_recorder: self.click_recorder.clone(),
+            options: self.options.clone(),
+            audit_log: self.audit_log.clone(),
+        }
+    }
+}
+
+/// Identifies who is calling a protected endpoint.
+#[derive(Clone, Copy)]
+pub(crate) enum Caller {
+    /// Authenticated with the API key of this ID, which grants access to every short URL.
+    ApiKey(i64),
+    /// Authenticated with a user token, which only grants access to the user's own short URLs.
+    User(i64),
+}
+
+impl Caller {
+    pub(crate) fn link_scope(self) -> LinkScope {
+        match self {
+            Caller::ApiKey(_) => LinkScope::All,
+            Caller::User(user_id) => LinkScope::OwnedBy(user_id),
+        }
+    }
+
+    /// The user new short URLs belong to. Short URLs created with an API key have no owner.
+    pub(crate) fn owner_id(self) -> Option<i64> {
+        match self {
+            Caller::ApiKey(_) => None,
+            Caller::User(user_id) => Some(user_id),
+        }
+    }
+
+    /// Tells callers apart, for state that every caller keeps separately and in the audit log.
+    pub(crate) fn key(self) -> String {
+        match self {
+            Caller::ApiKey(api_key_id) => format!("api_key:{}", api_key_id),
+            Caller::User(user_id) => format!("user:{}", user_id),
+        }
+    }
+}
+
+/// The prefix of the current version of the JSON API.
+pub const API_PREFIX: &str = "/api/v1";
+
+/// The HTTP API, with its own routes and the monitoring endpoints.
+pub fn router(app_state: AppState, monitoring_state: MonitoringState, config: &Config) -> Router {
+    let mut public_routes = Router::new();
+    if config.web_ui_enabled {
+        public_routes = public_routes.merge(web_ui::router());
+    }
+
+    let request_id_header = HeaderName::from_static(monitoring::REQUEST_ID_HEADER);
+    public_routes
+        .merge(redirects::routes())
+        .route_layer(RequestBodyLimitLayer::new(config.max_request_body_size))
+        .nest(
+            API_PREFIX,
+            api_routes(&app_state, config, links::link_routes()),
+        )
+        .merge(legacy_routes(&app_state, config))
+        .merge(SwaggerUi::new("/docs").url("/openapi.json", openapi::ApiDoc::openapi()))
+        .with_state(app_state)
+        // The body limits above replace axum's default limit of 2 MB.
+        .layer(DefaultBodyLimit::disable())
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            config.request_timeout(),
+        ))
+        // A concurrency limit per route, as `ConcurrencyLimitLayer` would give, wouldn't protect
+        // the database. Monitoring endpoints are merged afterwards, so health probes still get
+        // through when the limit is reached.
+        .layer(GlobalConcurrencyLimitLayer::new(
+            config.max_concurrent_requests,
+        ))
+        .merge(
+            Router::new()
+                .route("/metrics", get(monitoring::metrics_handler))
+                .route("/healthz", get(monitoring::healthz_handler))
+                .route("/readyz", get(monitoring::readyz_handler))
+                .with_state(monitoring_state),
+        )
+        .route_layer(middleware::from_fn(monitoring::track_requests))
+        .layer(middleware::from_fn(render_errors))
+        .layer(cors_layer(config))
+        .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(monitoring::make_request_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .layer(SetRequestIdLayer::new(request_id_header, MakeRequestUuid))
+}
+
+/// The JSON endpoints, relative to [`API_PREFIX`]. The routes of single short URLs are passed in,
+/// since they are the ones that moved in `/api/v1`.
+fn api_routes(
+    app_state: &AppState,
+    config: &Config,
+    link_routes: Router<AppState>,
+) -> Router<AppState> {
+    let protected_routes = Router::new()
+        .merge(links::routes(app_state))
+        .merge(link_routes)
+        .merge(stats::routes())
+        .merge(admin::routes())
+        .route_layer(RequestBodyLimitLayer::new(config.max_request_body_size))
+        // Added after the body limit above, which would otherwise apply as well.
+        .merge(links::import_routes())
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            authenticate,
+        ));
+
+    auth::routes()
+        .route_layer(RequestBodyLimitLayer::new(config.max_request_body_size))
+        .merge(protected_routes)
+}
+
+/// The endpoints as they were before the API was versioned, at the root. They answer with a
+/// `Deprecation` header until clients moved to [`API_PREFIX`].
+fn legacy_routes(app_state: &AppState, config: &Config) -> Router<AppState> {
+    let link_routes = Router::new()
+        .route("/api/links/:short_code", get(links::link_handler))
+        .route(
+            "/:short_code",
+            put(links::update_handler).delete(links::delete_handler),
+        );
+
+    api_routes(app_state, config, link_routes).route_layer(middleware::from_fn(mark_deprecated))
+}
+
+async fn mark_deprecated(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(DEPRECATION_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+/// Lets browser frontends on the configured origins call the API. Without any allowed origin, no
+/// CORS headers are sent and browsers keep blocking cross-origin requests.
+fn cors_layer(config: &Config) -> CorsLayer {
+    let allowed_origins = if config
+        .cors_allowed_origins
+        .iter()
+        .any(|origin| origin == "*")
+    {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(config.cors_allowed_origins.iter().map(|origin| {
+            HeaderValue::from_str(origin).expect("origins are validated by Config::load")
+        }))
+    };
+    let allowed_methods = config
+        .cors_allowed_methods
+        .iter()
+        .map(|method| Method::from_str(method).expect("methods are validated by Config::load"))
+        .collect::<Vec<_>>();
+
+    CorsLayer::new()
+        .allow_origin(allowed_origins)
+        .allow_methods(allowed_methods)
+        .allow_headers([
+            CONTENT_TYPE,
+            AUTHORIZATION,
+            HeaderName::from_static(API_KEY_HEADER),
+            HeaderName::from_static(IDEMPOTENCY_KEY_HEADER),
+        ])
+        .expose_headers([
+            RETRY_AFTER,
+            HeaderName::from_static(monitoring::REQUEST_ID_HEADER),
+            HeaderName::from_static(DEPRECATION_HEADER),
+        ])
+}
+
+#[derive(Serialize, ToSchema)]
+struct ErrorResponse {
+    code: &'static str,
+    message: String,
+    request_id: Option<String>,
+}
+
+/// Renders error responses as `{"code": ..., "message": ..., "request_id": ...}`, from the
+/// [`ErrorDetails`] attached to them.
+async fn render_errors(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(monitoring::REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let mut response = next.run(request).await;
+    let details = response
+        .extensions_mut()
+        .remove::<ErrorDetails>()
+        .or_else(|| middleware_error_details(response.status()));
+    match details {
+        Some(details) => {
+            let (mut parts, _) = response.into_parts();
+            // The length of the empty placeholder body no longer applies.
+            parts.headers.remove(CONTENT_LENGTH);
+            let body = ErrorResponse {
+                code: details.code,
+                message: details.message,
+                request_id,
+            };
+            (parts, Json(body)).into_response()
+        }
+        None => response,
+    }
+}
+
+/// Describes the errors the body limit and timeout layers respond with by themselves.
+fn middleware_error_details(status: StatusCode) -> Option<ErrorDetails> {
+    let (code, message) = match status {
+        StatusCode::PAYLOAD_TOO_LARGE => ("payload_too_large", "Request body is too large"),
+        StatusCode::REQUEST_TIMEOUT => ("request_timeout", "Request took too long to handle"),
+        _ => return None,
+    };
+    Some(ErrorDetails {
+        code,
+        message: message.to_string(),
+    })
+}
+
+/// Accepts either an `X-Api-Key` header or an `Authorization: Bearer <token>` header issued by
+/// `/api/v1/auth/login`, and makes the resulting [`Caller`] available to the handlers.
+async fn authenticate(
+    State(AppState {
+        api_keys, users, ..
+    }): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, url_shortener::Error> {
+    let headers = request.headers();
+    let api_key = headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let authorization = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    let caller = identify_caller(&api_keys, &users, api_key, authorization).await?;
+    request.extensions_mut().insert(caller);
+    Ok(next.run(request).await)
+}
+
+/// Checks the API key, or else the bearer token in the `Authorization` value. Shared by the REST
+/// and the gRPC API.
+pub(crate) async fn identify_caller(
+    api_keys: &ApiKeyStore,
+    users: &UserStore,
+    api_key: Option<&str>,
+    authorization: Option<&str>,
+) -> Result<Caller, url_shortener::Error> {
+    let bearer_token = authorization.and_then(|value| value.strip_prefix("Bearer "));
+
+    if let Some(api_key) = api_key {
+        let api_key_id = api_keys
+            .verify(api_key)
+            .await
+            .map_err(url_shortener::Error::Internal)?
+            .ok_or_else(|| url_shortener::Error::Unauthorized("Invalid API key".to_string()))?;
+        Ok(Caller::ApiKey(api_key_id))
+    } else if let Some(token) = bearer_token {
+        let user_id = users.verify_token(token).ok_or_else(|| {
+            url_shortener::Error::Unauthorized("Invalid or expired token".to_string())
+        })?;
+        Ok(Caller::User(user_id))
+    } else {
+        Err(url_shortener::Error::Unauthorized(
+            "Missing API key or bearer token".to_string(),
+        ))
+    }
+}
+
+/// Limits how often a single client may call the wrapped routes. The client is identified by its
+/// IP address, or by the first `X-Forwarded-For` entry when the service runs behind a trusted proxy.
+async fn rate_limit(
+    State(AppState {
+        rate_limiter,
+        options,
+        ..
+    }): State<AppState>,
+    ConnectInfo(peer_address): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, url_shortener::Error> {
+    let client = client_ip(request.headers(), peer_address, options.trust_forwarded_for);
+
+    rate_limiter
+        .check(client)
+        .map_err(url_shortener::Error::RateLimited)?;
+    Ok(next.run(request).await)
+}
+
+/// The IP address of the client, or the first `X-Forwarded-For` entry when the service runs behind
+/// a trusted proxy.
+fn client_ip(headers: &HeaderMap, peer_address: SocketAddr, trust_forwarded_for: bool) -> IpAddr {
+    let forwarded_for = headers
+        .get(FORWARDED_FOR_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .and_then(|value| IpAddr::from_str(value.trim()).ok());
+
+    match forwarded_for {
+        Some(forwarded_for) if trust_forwarded_for => forwarded_for,
+        _ => peer_address.ip(),
+    }
+}
+
+/// The extra domain a request was sent to, or `None` for the base URL's host and any host that
+/// isn't configured.
+fn request_domain(domains: &Domains, host: Option<Host>) -> Option<String> {
+    domains.resolve(host.as_ref().map(|Host(host)| host.as_str()))
+}
+
+/// When a short URL with a time to live of `ttl_seconds` expires.
+pub(crate) fn expires_after(ttl_seconds: i64) -> Result<DateTime<Utc>, url_shortener::Error> {
+    let ttl = Duration::try_seconds(ttl_seconds)
+        .filter(|ttl| *ttl > Duration::zero())
+        .ok_or_else(|| {
+            url_shortener::Error::InvalidExpiration(
+                "ttl_seconds must be a positive number of seconds".to_string(),
+            )
+        })?;
+    Ok(Utc::now() + ttl)
+}
+
+/// Ends a streamed export early. The status has been sent already, so all that's left is to cut
+/// the body short.
+fn export_failed(error: url_shortener::Error) -> std::io::Error {
+    if let url_shortener::Error::Internal(error) = &error {
+        error!("Export failed: {}", error);
+    }
+    std::io::Error::other(error.to_string())
+}
+
+/// Counts `count` new short URLs against the caller's quota before they are created. Only API
+/// keys have quotas.
+pub(crate) async fn reserve_links(
+    api_keys: &ApiKeyStore,
+    quota: LinkQuota,
+    caller: Caller,
+    count: usize,
+) -> Result<(), url_shortener::Error> {
+    let Caller::ApiKey(api_key_id) = caller else {
+        return Ok(());
+    };
+    let reserved = api_keys
+        .reserve_links(api_key_id, count as i64, quota)
+        .await
+        .map_err(url_shortener::Error::Internal)?;
+
+    reserved.map_err(|period| {
+        let now = Utc::now();
+        let period_name = match period {
+            QuotaPeriod::Day => "daily",
+            QuotaPeriod::Month => "monthly",
+        };
+        url_shortener::Error::QuotaExceeded(
+            format!(
+                "This API key has used up its {} quota of short URLs",
+                period_name
+            ),
+            (period.resets_at(now) - now).to_std().unwrap_or_default(),
+        )
+    })
+}
+
+/// Gives back short URLs counted by `reserve_links` that weren't created. Failing to do so only
+/// leaves the caller with a little less of its quota, so the error is logged, not returned.
+pub(crate) async fn release_links(api_keys: &ApiKeyStore, caller: Caller, count: usize) {
+    let Caller::ApiKey(api_key_id) = caller else {
+        return;
+    };
+    if count == 0 {
+        return;
+    }
+    if let Err(error) = api_keys.release_links(api_key_id, count as i64).await {
+        error!(
+            "Failed to release {} short URLs of API key {}: {}",
+            count, api_key_id, error
+        );
+    }
+}
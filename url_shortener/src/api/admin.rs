@@ -0,0 +1,288 @@
+//! Managing API keys, deactivated short URLs, the cache and the audit log.
+
+use super::{
+    links::ShortenResponse, AppState, Caller, ErrorResponse, DEFAULT_LINKS_PER_PAGE,
+    MAX_LINKS_PER_PAGE,
+};
+use crate::{
+    api_keys::PeriodUsage,
+    audit::{AuditAction, AuditFilter, AuditPage},
+    cache::CacheStats,
+    url_shortener,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::{IntoParams, ToSchema};
+
+pub(super) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/api-keys", post(create_api_key_handler))
+        .route("/api-keys/:id", delete(revoke_api_key_handler))
+        .route(
+            "/admin/links/:short_code/reactivate",
+            post(reactivate_handler),
+        )
+        .route("/admin/audit", get(audit_handler))
+        .route("/cache/stats", get(cache_stats_handler))
+        .route("/usage", get(usage_handler))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/links/{short_code}/reactivate",
+    tag = "admin",
+    params(("short_code" = String, Path)),
+    responses(
+        (status = 200, description = "The reactivated short URL", body = ShortenResponse),
+        (status = 403, description = "The caller isn't using an API key", body = ErrorResponse),
+        (status = 404, description = "No such short URL", body = ErrorResponse),
+    ),
+    security(("api_key" = []))
+)]
+pub(super) async fn reactivate_handler(
+    State(AppState {
+        url_shortener,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(short_code): Path<String>,
+) -> Result<Json<ShortenResponse>, url_shortener::Error> {
+    require_api_key_caller(caller)?;
+
+    let short_url = url_shortener
+        .reactivate(&short_code, caller.link_scope())
+        .await?;
+    audit_log
+        .record(
+            &caller.key(),
+            AuditAction::ReactivateLink,
+            &short_code,
+            json!({}),
+        )
+        .await;
+
+    let response = ShortenResponse {
+        short_code,
+        short_url,
+    };
+
+    Ok(Json(response))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct CreateApiKeyRequest {
+    name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct CreateApiKeyResponse {
+    id: i64,
+    name: String,
+    key: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/cache/stats",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Cache hits and misses since startup", body = CacheStats),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn cache_stats_handler(
+    State(AppState { url_shortener, .. }): State<AppState>,
+) -> Json<CacheStats> {
+    Json(url_shortener.cache_stats())
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct UsageResponse {
+    api_key_id: i64,
+    /// The current day and month.
+    periods: Vec<PeriodUsage>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/usage",
+    tag = "admin",
+    responses(
+        (status = 200, description = "How many short URLs the API key created today and this month", body = UsageResponse),
+        (status = 403, description = "Only API keys have quotas", body = ErrorResponse),
+    ),
+    security(("api_key" = []))
+)]
+pub(super) async fn usage_handler(
+    State(AppState {
+        api_keys, options, ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<UsageResponse>, url_shortener::Error> {
+    let Caller::ApiKey(api_key_id) = caller else {
+        return Err(url_shortener::Error::Forbidden(
+            "Only API keys have quotas".to_string(),
+        ));
+    };
+    let periods = api_keys
+        .usage(api_key_id, options.link_quota)
+        .await
+        .map_err(url_shortener::Error::Internal)?;
+    Ok(Json(UsageResponse {
+        api_key_id,
+        periods,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub(super) struct AuditQuery {
+    /// Only actions of this caller, like `api_key:1` or `user:2`.
+    actor: Option<String>,
+    /// Only this kind of action, like `link.update`.
+    action: Option<String>,
+    /// Only actions on this short code, API key ID or namespace.
+    target: Option<String>,
+    /// Only actions at or after this time.
+    since: Option<DateTime<Utc>>,
+    /// Only actions before this time.
+    until: Option<DateTime<Utc>>,
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/audit",
+    tag = "admin",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "A page of recorded actions, the newest first", body = AuditPage),
+        (status = 403, description = "The caller isn't using an API key", body = ErrorResponse),
+    ),
+    security(("api_key" = []))
+)]
+pub(super) async fn audit_handler(
+    State(AppState { audit_log, .. }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditPage>, url_shortener::Error> {
+    require_api_key_caller(caller)?;
+
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_LINKS_PER_PAGE)
+        .clamp(1, MAX_LINKS_PER_PAGE);
+    let filter = AuditFilter {
+        actor: query.actor,
+        action: query.action,
+        target: query.target,
+        since: query.since,
+        until: query.until,
+    };
+    Ok(Json(audit_log.list(&filter, page, per_page).await?))
+}
+
+fn require_api_key_caller(caller: Caller) -> Result<(), url_shortener::Error> {
+    match caller {
+        Caller::ApiKey(_) => Ok(()),
+        Caller::User(_) => Err(url_shortener::Error::Forbidden(
+            "This endpoint requires an API key".to_string(),
+        )),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/api-keys",
+    tag = "admin",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 200, description = "The new API key, which isn't shown again", body = CreateApiKeyResponse),
+        (status = 403, description = "The caller isn't using an API key", body = ErrorResponse),
+    ),
+    security(("api_key" = []))
+)]
+pub(super) async fn create_api_key_handler(
+    State(AppState {
+        api_keys,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<Json<CreateApiKeyResponse>, url_shortener::Error> {
+    require_api_key_caller(caller)?;
+
+    let api_key = api_keys
+        .create(&payload.name)
+        .await
+        .map_err(url_shortener::Error::Internal)?;
+    audit_log
+        .record(
+            &caller.key(),
+            AuditAction::CreateApiKey,
+            &api_key.id.to_string(),
+            json!({ "name": api_key.name }),
+        )
+        .await;
+
+    let response = CreateApiKeyResponse {
+        id: api_key.id,
+        name: api_key.name,
+        key: api_key.key,
+    };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/api-keys/{id}",
+    tag = "admin",
+    params(("id" = i64, Path, description = "The ID of the API key")),
+    responses(
+        (status = 204, description = "The API key was revoked"),
+        (status = 403, description = "The caller isn't using an API key", body = ErrorResponse),
+        (status = 404, description = "No such API key"),
+    ),
+    security(("api_key" = []))
+)]
+pub(super) async fn revoke_api_key_handler(
+    State(AppState {
+        api_keys,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, url_shortener::Error> {
+    require_api_key_caller(caller)?;
+
+    let revoked = api_keys
+        .revoke(id)
+        .await
+        .map_err(url_shortener::Error::Internal)?;
+
+    if revoked {
+        audit_log
+            .record(
+                &caller.key(),
+                AuditAction::RevokeApiKey,
+                &id.to_string(),
+                json!({}),
+            )
+            .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Ok(StatusCode::NOT_FOUND)
+    }
+}
@@ -0,0 +1,73 @@
+//! Registering users and logging them in.
+
+use super::{AppState, ErrorResponse};
+use crate::url_shortener;
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub(super) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/auth/register", post(register_user_handler))
+        .route("/auth/login", post(login_handler))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct CredentialsRequest {
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct RegisterUserResponse {
+    id: i64,
+    username: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/register",
+    tag = "auth",
+    request_body = CredentialsRequest,
+    responses(
+        (status = 201, description = "The user was registered", body = RegisterUserResponse),
+        (status = 400, description = "The username or password is invalid", body = ErrorResponse),
+        (status = 409, description = "The username is taken", body = ErrorResponse),
+    )
+)]
+pub(super) async fn register_user_handler(
+    State(AppState { users, .. }): State<AppState>,
+    Json(payload): Json<CredentialsRequest>,
+) -> Result<(StatusCode, Json<RegisterUserResponse>), url_shortener::Error> {
+    let user = users.register(&payload.username, &payload.password).await?;
+
+    let response = RegisterUserResponse {
+        id: user.id,
+        username: user.username,
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct LoginResponse {
+    token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login",
+    tag = "auth",
+    request_body = CredentialsRequest,
+    responses(
+        (status = 200, description = "A bearer token for the protected endpoints", body = LoginResponse),
+        (status = 401, description = "The username or password is wrong", body = ErrorResponse),
+    )
+)]
+pub(super) async fn login_handler(
+    State(AppState { users, .. }): State<AppState>,
+    Json(payload): Json<CredentialsRequest>,
+) -> Result<Json<LoginResponse>, url_shortener::Error> {
+    let token = users.login(&payload.username, &payload.password).await?;
+    Ok(Json(LoginResponse { token }))
+}
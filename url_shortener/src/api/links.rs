@@ -0,0 +1,978 @@
+//! Creating, changing and listing short URLs, and grouping them in namespaces and campaigns.
+
+use super::{
+    expires_after, export_failed, rate_limit, release_links, request_domain, reserve_links,
+    AppState, Caller, ErrorResponse, DEFAULT_LINKS_PER_PAGE, IDEMPOTENCY_KEY_HEADER,
+    MAX_LINKS_PER_PAGE,
+};
+use crate::{
+    audit::AuditAction,
+    domains::Domains,
+    link_csv,
+    url_shortener::{
+        self, Campaign, DeviceType, IdempotencyKey, ImportConflict, ImportSummary, LinkDetails,
+        LinkPage, LinkSort, Namespace, NewShortUrl, NewVariant, RedirectType, ShortUrlUpdate,
+    },
+};
+use axum::{
+    body::{Body, Bytes},
+    extract::{Host, Path, Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use futures_util::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use tower_http::limit::RequestBodyLimitLayer;
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+/// Largest CSV file `/import` accepts, in bytes.
+const MAX_IMPORT_SIZE: usize = 10 * 1024 * 1024;
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+/// Imports are recorded as one action, not one per short URL.
+const IMPORT_AUDIT_TARGET: &str = "import";
+
+pub(super) fn routes(app_state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/shorten",
+            post(shorten_handler).route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                rate_limit,
+            )),
+        )
+        .route(
+            "/shorten/batch",
+            post(batch_shorten_handler).route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                rate_limit,
+            )),
+        )
+        .route("/links", get(list_handler))
+        .route("/export", get(export_handler))
+        .route(
+            "/namespaces",
+            post(create_namespace_handler).get(namespaces_handler),
+        )
+        .route(
+            "/namespaces/:name/members/:user_id",
+            put(add_namespace_member_handler).delete(remove_namespace_member_handler),
+        )
+        .route(
+            "/campaigns",
+            post(create_campaign_handler).get(campaigns_handler),
+        )
+        .route("/campaigns/:name/links", get(campaign_links_handler))
+        .route(
+            "/campaigns/:name/links/:short_code",
+            put(add_campaign_link_handler).delete(remove_campaign_link_handler),
+        )
+}
+
+/// The routes of single short URLs.
+pub(super) fn link_routes() -> Router<AppState> {
+    Router::new().route(
+        "/links/:short_code",
+        get(link_handler).put(update_handler).delete(delete_handler),
+    )
+}
+
+/// Imports have a body limit of their own, so their route has to be added after the general one.
+pub(super) fn import_routes() -> Router<AppState> {
+    Router::new().route(
+        "/import",
+        post(import_handler).route_layer(RequestBodyLimitLayer::new(MAX_IMPORT_SIZE)),
+    )
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub(super) struct ShortenRequest {
+    original_url: String,
+    /// A short code of your own choosing instead of a generated one.
+    custom_code: Option<String>,
+    /// When the short URL stops working. Can't be combined with `ttl_seconds`.
+    expires_at: Option<DateTime<Utc>>,
+    /// How many seconds from now the short URL stops working.
+    ttl_seconds: Option<i64>,
+    /// Hand out the existing short code when the URL was shortened before.
+    reuse_existing: Option<bool>,
+    redirect_type: Option<RedirectType>,
+    /// How many times the short URL can be followed before it stops working.
+    max_clicks: Option<i64>,
+    /// Destinations for visitors from specific countries, keyed by ISO 3166 country code.
+    #[serde(default)]
+    geo_targets: BTreeMap<String, String>,
+    /// Split test destinations, each receiving `weight` percent of the visitors.
+    #[serde(default)]
+    variants: Vec<NewVariant>,
+    /// Destinations for visitors on specific kinds of devices.
+    #[serde(default)]
+    device_targets: BTreeMap<DeviceType, String>,
+    /// The host of `base_url` or one of `extra_domains` to serve the short URL on. Defaults to
+    /// the domain the request was sent to.
+    domain: Option<String>,
+    /// Query parameters added to the destination on every redirect, unless it already has them.
+    #[serde(default)]
+    tracking_params: BTreeMap<String, String>,
+    /// The length of the generated short code, for example a longer one that is harder to guess.
+    /// Must be between `min_requested_short_code_length` and `max_requested_short_code_length`.
+    short_code_length: Option<usize>,
+    /// A namespace you have access to, to get a short URL like `/<namespace>/<code>`.
+    namespace: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(super) struct ShortenResponse {
+    pub(super) short_code: String,
+    pub(super) short_url: String,
+}
+
+impl ShortenRequest {
+    /// `request_domain` is where the request was sent to, see [`request_domain`].
+    fn into_new_short_url(
+        self,
+        caller: Caller,
+        reuse_existing_by_default: bool,
+        domains: &Domains,
+        request_domain: Option<&str>,
+    ) -> Result<NewShortUrl, url_shortener::Error> {
+        let expires_at = match (self.expires_at, self.ttl_seconds) {
+            (Some(_), Some(_)) => {
+                return Err(url_shortener::Error::InvalidExpiration(
+                    "Only one of expires_at and ttl_seconds may be given".to_string(),
+                ))
+            }
+            (Some(expires_at), None) => Some(expires_at),
+            (None, Some(ttl_seconds)) => Some(expires_after(ttl_seconds)?),
+            (None, None) => None,
+        };
+        let domain = match &self.domain {
+            Some(domain) => domains.select(domain)?,
+            None => request_domain.map(str::to_string),
+        };
+
+        Ok(NewShortUrl {
+            original_url: self.original_url,
+            custom_code: self.custom_code,
+            expires_at,
+            owner_id: caller.owner_id(),
+            redirect_type: self.redirect_type.unwrap_or_default(),
+            max_clicks: self.max_clicks,
+            reuse_existing: self.reuse_existing.unwrap_or(reuse_existing_by_default),
+            geo_targets: self.geo_targets,
+            variants: self.variants,
+            device_targets: self.device_targets,
+            domain,
+            tracking_params: self.tracking_params,
+            short_code_length: self.short_code_length,
+            namespace: self.namespace,
+        })
+    }
+}
+
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<&str>, url_shortener::Error> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    match value.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH => Ok(Some(key)),
+        _ => Err(url_shortener::Error::InvalidRequest(format!(
+            "Idempotency-Key must be between 1 and {} visible ASCII characters",
+            MAX_IDEMPOTENCY_KEY_LENGTH
+        ))),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/shorten",
+    tag = "links",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries of the request return the same short URL instead of creating another one"),
+    ),
+    request_body = ShortenRequest,
+    responses(
+        (status = 200, description = "The short URL", body = ShortenResponse),
+        (status = 400, description = "The request is invalid, or the Idempotency-Key was used for a different request", body = ErrorResponse),
+        (status = 409, description = "The custom code is taken, or a request with the same Idempotency-Key is still running", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn shorten_handler(
+    State(AppState {
+        domains,
+        url_shortener,
+        api_keys,
+        options,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    host: Option<Host>,
+    headers: HeaderMap,
+    Json(payload): Json<ShortenRequest>,
+) -> Result<Json<ShortenResponse>, url_shortener::Error> {
+    let idempotency_key = idempotency_key(&headers)?;
+    // Retries must send the same request, but may be sent to another domain.
+    let request_hash = format!(
+        "{:x}",
+        Sha256::digest(serde_json::to_vec(&payload).expect("requests serialize to JSON"))
+    );
+
+    let new_short_url = payload.into_new_short_url(
+        caller,
+        options.reuse_existing_by_default,
+        &domains,
+        request_domain(&domains, host).as_deref(),
+    )?;
+    reserve_links(&api_keys, options.link_quota, caller, 1).await?;
+    let result = match idempotency_key {
+        Some(key) => {
+            let expires_at = Utc::now()
+                + Duration::from_std(options.idempotency_key_ttl).unwrap_or(Duration::MAX);
+            url_shortener
+                .register_idempotent(
+                    &new_short_url,
+                    &IdempotencyKey {
+                        caller: &caller.key(),
+                        key,
+                        request_hash: &request_hash,
+                        expires_at,
+                    },
+                )
+                .await
+        }
+        None => url_shortener
+            .register(&new_short_url)
+            .await
+            .map(|short_code| (short_code, true)),
+    };
+    // Retries that get the first request's short URL don't create another one.
+    if !matches!(result, Ok((_, true))) {
+        release_links(&api_keys, caller, 1).await;
+    }
+    let (short_code, registered) = result?;
+    if registered {
+        audit_log
+            .record(
+                &caller.key(),
+                AuditAction::CreateLink,
+                &short_code,
+                json!({ "original_url": new_short_url.original_url }),
+            )
+            .await;
+    }
+
+    let short_url = domains.short_url(new_short_url.domain.as_deref(), &short_code);
+    let response = ShortenResponse {
+        short_code,
+        short_url,
+    };
+
+    Ok(Json(response))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub(super) enum BatchShortenResult {
+    Shortened {
+        original_url: String,
+        short_code: String,
+        short_url: String,
+    },
+    Failed {
+        original_url: String,
+        status: u16,
+        code: &'static str,
+        error: String,
+    },
+}
+
+impl BatchShortenResult {
+    fn failed(original_url: String, error: url_shortener::Error) -> Self {
+        if let url_shortener::Error::Internal(error) = &error {
+            error!("Internal error: {}", error);
+        }
+        BatchShortenResult::Failed {
+            original_url,
+            status: error.status_code().as_u16(),
+            code: error.code(),
+            error: error.to_string(),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/shorten/batch",
+    tag = "links",
+    request_body = Vec<ShortenRequest>,
+    responses(
+        (status = 200, description = "One result per requested URL, in the same order", body = Vec<BatchShortenResult>),
+        (status = 400, description = "The batch is too large", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn batch_shorten_handler(
+    State(AppState {
+        domains,
+        url_shortener,
+        api_keys,
+        options,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    host: Option<Host>,
+    Json(payload): Json<Vec<ShortenRequest>>,
+) -> Result<Json<Vec<BatchShortenResult>>, url_shortener::Error> {
+    if payload.len() > options.max_batch_size {
+        return Err(url_shortener::Error::InvalidRequest(format!(
+            "A batch may contain at most {} URLs",
+            options.max_batch_size
+        )));
+    }
+
+    // Requests that can't be turned into a short URL fail right away, the rest are registered
+    // together and their results are put back in the original order afterwards.
+    let request_domain = request_domain(&domains, host);
+    let mut results: Vec<Option<BatchShortenResult>> = Vec::with_capacity(payload.len());
+    let mut new_short_urls = Vec::new();
+    for request in payload {
+        let original_url = request.original_url.clone();
+        match request.into_new_short_url(
+            caller,
+            options.reuse_existing_by_default,
+            &domains,
+            request_domain.as_deref(),
+        ) {
+            Ok(new_short_url) => {
+                new_short_urls.push(new_short_url);
+                results.push(None);
+            }
+            Err(error) => results.push(Some(BatchShortenResult::failed(original_url, error))),
+        }
+    }
+
+    let reserved = new_short_urls.len();
+    reserve_links(&api_keys, options.link_quota, caller, reserved).await?;
+    let registered = url_shortener.register_batch(&new_short_urls).await;
+    let failed = registered.as_ref().map_or(reserved, |registered| {
+        registered.iter().filter(|result| result.is_err()).count()
+    });
+    release_links(&api_keys, caller, failed).await;
+    let registered = registered?;
+    for (registration, new_short_url) in registered.iter().zip(&new_short_urls) {
+        if let Ok(short_code) = registration {
+            audit_log
+                .record(
+                    &caller.key(),
+                    AuditAction::CreateLink,
+                    short_code,
+                    json!({ "original_url": new_short_url.original_url }),
+                )
+                .await;
+        }
+    }
+    let mut registered = registered.into_iter().zip(new_short_urls);
+
+    let results = results
+        .into_iter()
+        .map(|result| {
+            result.unwrap_or_else(|| {
+                let (registration, new_short_url) = registered.next().unwrap();
+                match registration {
+                    Ok(short_code) => BatchShortenResult::Shortened {
+                        short_url: domains.short_url(new_short_url.domain.as_deref(), &short_code),
+                        original_url: new_short_url.original_url,
+                        short_code,
+                    },
+                    Err(error) => BatchShortenResult::failed(new_short_url.original_url, error),
+                }
+            })
+        })
+        .collect();
+
+    Ok(Json(results))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct UpdateRequest {
+    original_url: String,
+    /// Replaces the geo targets when given, and keeps them otherwise.
+    geo_targets: Option<BTreeMap<String, String>>,
+    /// Replaces the split test variants when given, and keeps them otherwise.
+    variants: Option<Vec<NewVariant>>,
+    /// Replaces the device targets when given, and keeps them otherwise.
+    device_targets: Option<BTreeMap<DeviceType, String>>,
+    /// Replaces the tracking parameters when given, and keeps them otherwise.
+    tracking_params: Option<BTreeMap<String, String>>,
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/links/{short_code}",
+    tag = "links",
+    params(("short_code" = String, Path, description = "The short code to update")),
+    request_body = UpdateRequest,
+    responses(
+        (status = 200, description = "The updated short URL", body = ShortenResponse),
+        (status = 400, description = "The request is invalid", body = ErrorResponse),
+        (status = 404, description = "No such short URL", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn update_handler(
+    State(AppState {
+        url_shortener,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(short_code): Path<String>,
+    Json(payload): Json<UpdateRequest>,
+) -> Result<Json<ShortenResponse>, url_shortener::Error> {
+    let previous = url_shortener
+        .details(&short_code, caller.link_scope())
+        .await?;
+    // The destination is recorded with its old value, replaced lists only with their new one.
+    let mut changes = json!({
+        "original_url": { "from": previous.original_url, "to": payload.original_url },
+    });
+    for (field, value) in [
+        ("geo_targets", json!(payload.geo_targets)),
+        ("variants", json!(payload.variants)),
+        ("device_targets", json!(payload.device_targets)),
+        ("tracking_params", json!(payload.tracking_params)),
+    ] {
+        if !value.is_null() {
+            changes[field] = json!({ "to": value });
+        }
+    }
+
+    let short_url = url_shortener
+        .update(
+            &short_code,
+            &ShortUrlUpdate {
+                original_url: payload.original_url,
+                geo_targets: payload.geo_targets,
+                variants: payload.variants,
+                device_targets: payload.device_targets,
+                tracking_params: payload.tracking_params,
+            },
+            caller.link_scope(),
+        )
+        .await?;
+    audit_log
+        .record(&caller.key(), AuditAction::UpdateLink, &short_code, changes)
+        .await;
+
+    let response = ShortenResponse {
+        short_code,
+        short_url,
+    };
+
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/links/{short_code}",
+    tag = "links",
+    params(("short_code" = String, Path, description = "The short code to look up")),
+    responses(
+        (status = 200, description = "The short URL, including expired and deactivated ones", body = LinkDetails),
+        (status = 404, description = "No such short URL", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn link_handler(
+    State(AppState { url_shortener, .. }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(short_code): Path<String>,
+) -> Result<Json<LinkDetails>, url_shortener::Error> {
+    let details = url_shortener
+        .details(&short_code, caller.link_scope())
+        .await?;
+    Ok(Json(details))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/links/{short_code}",
+    tag = "links",
+    params(("short_code" = String, Path)),
+    responses(
+        (status = 204, description = "The short URL was deleted"),
+        (status = 404, description = "No such short URL", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn delete_handler(
+    State(AppState {
+        url_shortener,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(short_code): Path<String>,
+) -> Result<StatusCode, url_shortener::Error> {
+    url_shortener
+        .delete(&short_code, caller.link_scope())
+        .await?;
+    audit_log
+        .record(
+            &caller.key(),
+            AuditAction::DeleteLink,
+            &short_code,
+            json!({}),
+        )
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize, IntoParams)]
+pub(super) struct ListQuery {
+    page: Option<u32>,
+    per_page: Option<u32>,
+    sort: Option<LinkSort>,
+    /// Only list short URLs whose destination counts as broken.
+    broken: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/links",
+    tag = "links",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "A page of short URLs", body = LinkPage),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn list_handler(
+    State(AppState { url_shortener, .. }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<LinkPage>, url_shortener::Error> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_LINKS_PER_PAGE)
+        .clamp(1, MAX_LINKS_PER_PAGE);
+
+    let links = url_shortener
+        .list(
+            page,
+            per_page,
+            query.sort.unwrap_or_default(),
+            caller.link_scope(),
+            query.broken.unwrap_or(false),
+        )
+        .await?;
+    Ok(Json(links))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub(super) struct ImportQuery {
+    /// What to do with short codes that already exist, `skip` by default.
+    on_conflict: Option<ImportConflict>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/import",
+    tag = "links",
+    params(ImportQuery),
+    request_body(
+        content = String,
+        content_type = "text/csv",
+        description = "Rows of `short_code,original_url`, optionally below a header row naming these columns and an optional `domain` column"
+    ),
+    responses(
+        (status = 200, description = "How many short URLs were imported", body = ImportSummary),
+        (status = 400, description = "The file isn't valid CSV", body = ErrorResponse),
+        (status = 409, description = "A short code exists and `on_conflict` is `fail`", body = ErrorResponse),
+        (status = 413, description = "The file is too large"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn import_handler(
+    State(AppState {
+        url_shortener,
+        api_keys,
+        options,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> Result<Json<ImportSummary>, url_shortener::Error> {
+    let (imported_short_urls, invalid_rows) = link_csv::parse(&body)?;
+
+    let reserved = imported_short_urls.len();
+    reserve_links(&api_keys, options.link_quota, caller, reserved).await?;
+    let summary = url_shortener
+        .import(
+            &imported_short_urls,
+            query.on_conflict.unwrap_or_default(),
+            caller.owner_id(),
+            caller.link_scope(),
+        )
+        .await;
+    let imported = summary
+        .as_ref()
+        .map_or(0, |summary| summary.imported as usize);
+    release_links(&api_keys, caller, reserved - imported).await;
+    let mut summary = summary?;
+    audit_log
+        .record(
+            &caller.key(),
+            AuditAction::ImportLinks,
+            IMPORT_AUDIT_TARGET,
+            json!({
+                "imported": summary.imported,
+                "overwritten": summary.overwritten,
+                "skipped": summary.skipped,
+            }),
+        )
+        .await;
+    summary.failed.extend(invalid_rows);
+    summary.failed.sort_by_key(|failure| failure.line);
+
+    Ok(Json(summary))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/export",
+    tag = "links",
+    responses(
+        (status = 200, description = "Every short URL, including deleted ones, as CSV", body = String, content_type = "text/csv"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn export_handler(
+    State(AppState { url_shortener, .. }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Response {
+    let header = futures_util::stream::once(async { Ok(link_csv::write_header()) });
+    let rows = url_shortener
+        .export(caller.link_scope())
+        .map_ok(|links| link_csv::write_links(&links))
+        .map_err(export_failed);
+
+    (
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                CONTENT_DISPOSITION,
+                "attachment; filename=\"short_urls.csv\"",
+            ),
+        ],
+        Body::from_stream(header.chain(rows)),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct CreateNamespaceRequest {
+    /// Letters, digits, `-` and `_`, like a custom code.
+    name: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/namespaces",
+    tag = "links",
+    request_body = CreateNamespaceRequest,
+    responses(
+        (status = 200, description = "The new namespace", body = Namespace),
+        (status = 400, description = "The name is invalid or reserved", body = ErrorResponse),
+        (status = 409, description = "The namespace already exists", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn create_namespace_handler(
+    State(AppState {
+        url_shortener,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<CreateNamespaceRequest>,
+) -> Result<Json<Namespace>, url_shortener::Error> {
+    let namespace = url_shortener
+        .create_namespace(&payload.name, caller.owner_id())
+        .await?;
+    audit_log
+        .record(
+            &caller.key(),
+            AuditAction::CreateNamespace,
+            &namespace.name,
+            json!({}),
+        )
+        .await;
+    Ok(Json(namespace))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/namespaces",
+    tag = "links",
+    responses(
+        (status = 200, description = "The namespaces the caller can register short codes in", body = Vec<Namespace>),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn namespaces_handler(
+    State(AppState { url_shortener, .. }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<Namespace>>, url_shortener::Error> {
+    let namespaces = url_shortener.namespaces(caller.link_scope()).await?;
+    Ok(Json(namespaces))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/namespaces/{name}/members/{user_id}",
+    tag = "links",
+    params(("name" = String, Path), ("user_id" = i64, Path)),
+    responses(
+        (status = 204, description = "The user may register short codes in the namespace"),
+        (status = 403, description = "The caller doesn't own the namespace", body = ErrorResponse),
+        (status = 404, description = "No such namespace", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn add_namespace_member_handler(
+    State(AppState {
+        url_shortener,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, user_id)): Path<(String, i64)>,
+) -> Result<StatusCode, url_shortener::Error> {
+    url_shortener
+        .add_namespace_member(&name, user_id, caller.link_scope())
+        .await?;
+    audit_log
+        .record(
+            &caller.key(),
+            AuditAction::AddNamespaceMember,
+            &name,
+            json!({ "user_id": user_id }),
+        )
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/namespaces/{name}/members/{user_id}",
+    tag = "links",
+    params(("name" = String, Path), ("user_id" = i64, Path)),
+    responses(
+        (status = 204, description = "The user can no longer register short codes in the namespace"),
+        (status = 403, description = "The caller doesn't own the namespace", body = ErrorResponse),
+        (status = 404, description = "No such namespace", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn remove_namespace_member_handler(
+    State(AppState {
+        url_shortener,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, user_id)): Path<(String, i64)>,
+) -> Result<StatusCode, url_shortener::Error> {
+    url_shortener
+        .remove_namespace_member(&name, user_id, caller.link_scope())
+        .await?;
+    audit_log
+        .record(
+            &caller.key(),
+            AuditAction::RemoveNamespaceMember,
+            &name,
+            json!({ "user_id": user_id }),
+        )
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(super) struct CreateCampaignRequest {
+    /// Letters, digits, `-` and `_`, like `spring-sale`.
+    name: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/campaigns",
+    tag = "campaigns",
+    request_body = CreateCampaignRequest,
+    responses(
+        (status = 200, description = "The new campaign", body = Campaign),
+        (status = 400, description = "The name is invalid", body = ErrorResponse),
+        (status = 409, description = "The campaign already exists", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn create_campaign_handler(
+    State(AppState {
+        url_shortener,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Json(payload): Json<CreateCampaignRequest>,
+) -> Result<Json<Campaign>, url_shortener::Error> {
+    let campaign = url_shortener
+        .create_campaign(&payload.name, caller.owner_id())
+        .await?;
+    audit_log
+        .record(
+            &caller.key(),
+            AuditAction::CreateCampaign,
+            &campaign.name,
+            json!({}),
+        )
+        .await;
+    Ok(Json(campaign))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/campaigns",
+    tag = "campaigns",
+    responses(
+        (status = 200, description = "The caller's campaigns", body = Vec<Campaign>),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn campaigns_handler(
+    State(AppState { url_shortener, .. }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<Campaign>>, url_shortener::Error> {
+    let campaigns = url_shortener.campaigns(caller.link_scope()).await?;
+    Ok(Json(campaigns))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub(super) struct CampaignLinksQuery {
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/campaigns/{name}/links",
+    tag = "campaigns",
+    params(("name" = String, Path), CampaignLinksQuery),
+    responses(
+        (status = 200, description = "A page of the campaign's short URLs, the newest first", body = LinkPage),
+        (status = 404, description = "No such campaign", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn campaign_links_handler(
+    State(AppState { url_shortener, .. }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(name): Path<String>,
+    Query(query): Query<CampaignLinksQuery>,
+) -> Result<Json<LinkPage>, url_shortener::Error> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_LINKS_PER_PAGE)
+        .clamp(1, MAX_LINKS_PER_PAGE);
+
+    let links = url_shortener
+        .campaign_links(&name, page, per_page, caller.link_scope())
+        .await?;
+    Ok(Json(links))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/campaigns/{name}/links/{short_code}",
+    tag = "campaigns",
+    params(("name" = String, Path), ("short_code" = String, Path)),
+    responses(
+        (status = 204, description = "The short URL belongs to the campaign, and no other"),
+        (status = 404, description = "No such campaign or short URL", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn add_campaign_link_handler(
+    State(AppState {
+        url_shortener,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, short_code)): Path<(String, String)>,
+) -> Result<StatusCode, url_shortener::Error> {
+    url_shortener
+        .add_campaign_link(&name, &short_code, caller.link_scope())
+        .await?;
+    audit_log
+        .record(
+            &caller.key(),
+            AuditAction::AddCampaignLink,
+            &short_code,
+            json!({ "campaign": name }),
+        )
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/campaigns/{name}/links/{short_code}",
+    tag = "campaigns",
+    params(("name" = String, Path), ("short_code" = String, Path)),
+    responses(
+        (status = 204, description = "The short URL no longer belongs to the campaign"),
+        (status = 404, description = "No such campaign, or the short URL isn't in it", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn remove_campaign_link_handler(
+    State(AppState {
+        url_shortener,
+        audit_log,
+        ..
+    }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path((name, short_code)): Path<(String, String)>,
+) -> Result<StatusCode, url_shortener::Error> {
+    url_shortener
+        .remove_campaign_link(&name, &short_code, caller.link_scope())
+        .await?;
+    audit_log
+        .record(
+            &caller.key(),
+            AuditAction::RemoveCampaignLink,
+            &short_code,
+            json!({ "campaign": name }),
+        )
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,144 @@
+use super::{
+    admin::{CreateApiKeyRequest, CreateApiKeyResponse, UsageResponse},
+    auth::{CredentialsRequest, LoginResponse, RegisterUserResponse},
+    links::{
+        BatchShortenResult, CreateCampaignRequest, CreateNamespaceRequest, ShortenRequest,
+        ShortenResponse, UpdateRequest,
+    },
+    stats::ClickExportFormat,
+    ErrorResponse, API_KEY_HEADER,
+};
+use crate::{
+    api_keys::{PeriodUsage, QuotaPeriod},
+    audit::{AuditEntry, AuditPage},
+    cache::CacheStats,
+    qr::{QrErrorCorrection, QrFormat},
+    url_shortener::{
+        Campaign, CampaignStats, ClickStats, DailyClicks, DeviceType, ExportedClick,
+        ImportConflict, ImportFailure, ImportSummary, LinkClicks, LinkDetails, LinkHealth,
+        LinkPage, LinkSort, LinkStatus, LinkSummary, Namespace, NewVariant, RedirectType,
+        ReferrerClicks, VariantClicks,
+    },
+};
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+/// The OpenAPI description of the HTTP API, served at `/openapi.json` and browsable at `/docs`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "URL Shortener",
+        description = "Shortens URLs, redirects visitors and reports how often short URLs are used."
+    ),
+    paths(
+        super::auth::register_user_handler,
+        super::auth::login_handler,
+        super::links::shorten_handler,
+        super::links::batch_shorten_handler,
+        super::links::link_handler,
+        super::links::list_handler,
+        super::links::import_handler,
+        super::links::export_handler,
+        super::stats::stats_handler,
+        super::stats::export_clicks_handler,
+        super::links::update_handler,
+        super::links::delete_handler,
+        super::redirects::redirect_handler,
+        super::redirects::preview_handler,
+        super::redirects::qr_code_handler,
+        super::admin::reactivate_handler,
+        super::admin::cache_stats_handler,
+        super::admin::create_api_key_handler,
+        super::admin::revoke_api_key_handler,
+        super::admin::usage_handler,
+        super::links::create_namespace_handler,
+        super::links::namespaces_handler,
+        super::links::add_namespace_member_handler,
+        super::links::remove_namespace_member_handler,
+        super::links::create_campaign_handler,
+        super::links::campaigns_handler,
+        super::links::campaign_links_handler,
+        super::links::add_campaign_link_handler,
+        super::links::remove_campaign_link_handler,
+        super::stats::campaign_stats_handler,
+        super::admin::audit_handler,
+    ),
+    components(schemas(
+        ShortenRequest,
+        ShortenResponse,
+        BatchShortenResult,
+        UpdateRequest,
+        NewVariant,
+        RedirectType,
+        DeviceType,
+        ClickStats,
+        DailyClicks,
+        ReferrerClicks,
+        VariantClicks,
+        LinkDetails,
+        LinkHealth,
+        LinkStatus,
+        ExportedClick,
+        ClickExportFormat,
+        LinkPage,
+        LinkSummary,
+        LinkSort,
+        ImportConflict,
+        ImportSummary,
+        ImportFailure,
+        QrFormat,
+        QrErrorCorrection,
+        CacheStats,
+        CredentialsRequest,
+        RegisterUserResponse,
+        LoginResponse,
+        CreateApiKeyRequest,
+        CreateApiKeyResponse,
+        UsageResponse,
+        PeriodUsage,
+        CreateNamespaceRequest,
+        Namespace,
+        CreateCampaignRequest,
+        Campaign,
+        CampaignStats,
+        LinkClicks,
+        AuditPage,
+        AuditEntry,
+        QuotaPeriod,
+        ErrorResponse,
+    )),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "links", description = "Create and manage short URLs"),
+        (name = "redirects", description = "Follow, preview and share short URLs"),
+        (name = "campaigns", description = "Group short URLs and count their clicks together"),
+        (name = "auth", description = "Register users and log in"),
+        (name = "admin", description = "Manage API keys and the service"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Describes the two ways of calling the protected endpoints, see [`super::authenticate`].
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("A token issued by `/api/v1/auth/login`"))
+                    .build(),
+            ),
+        );
+    }
+}
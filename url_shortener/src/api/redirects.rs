@@ -0,0 +1,291 @@
+//! The public routes that visitors of short URLs follow.
+
+use super::{client_ip, request_domain, ApiOptions, AppState, ErrorResponse};
+use crate::{
+    domains::Domains,
+    monitoring, preview,
+    qr::{self, QrErrorCorrection, QrFormat},
+    url_shortener::{self, Destination, RedirectType, UrlShortener, Visitor},
+};
+use axum::{
+    async_trait,
+    extract::{rejection::PathRejection, ConnectInfo, FromRequestParts, Host, Path, Query, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, EXPIRES, LOCATION, REFERER, USER_AGENT},
+        request::Parts,
+        HeaderMap, HeaderName, StatusCode,
+    },
+    response::{AppendHeaders, Html, IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::{Duration, Utc};
+use common::dates::format_http_date;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use utoipa::IntoParams;
+
+pub(super) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/:short_code", get(redirect_handler))
+        .route("/:short_code/qr", get(qr_code_handler))
+        .route("/:short_code/preview", get(preview_handler))
+        // Namespaced short codes. Their first segment has to be named like the one above.
+        .route("/:short_code/:code", get(redirect_handler))
+        .route("/:short_code/:code/qr", get(qr_code_handler))
+        .route("/:short_code/:code/preview", get(preview_handler))
+}
+
+/// The short code of a public route, either `/:short_code` or `/:namespace/:code`. Namespaced
+/// short codes are stored as `namespace/code`.
+pub(super) struct ShortCodePath(String);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ShortCodePath {
+    type Rejection = PathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(segments) = Path::<Vec<String>>::from_request_parts(parts, state).await?;
+        Ok(ShortCodePath(segments.join("/")))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub(super) struct RedirectQuery {
+    /// Shows the preview page instead of redirecting, given as `1` or `true`.
+    preview: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/{short_code}",
+    tag = "redirects",
+    params(("short_code" = String, Path), RedirectQuery),
+    responses(
+        (status = 301, description = "Redirect to a permanent destination", headers(("Location" = String))),
+        (status = 302, description = "Redirect to a temporary destination, or to the fallback URL for unknown short codes when there is one", headers(("Location" = String))),
+        (status = 200, description = "The preview page, when asked for", body = String, content_type = "text/html"),
+        (status = 404, description = "No such short URL, and no fallback URL", body = ErrorResponse),
+        (status = 410, description = "The short URL expired or was deactivated", body = ErrorResponse),
+    )
+)]
+pub(super) async fn redirect_handler(
+    State(AppState {
+        domains,
+        url_shortener,
+        click_recorder,
+        options,
+        ..
+    }): State<AppState>,
+    ConnectInfo(peer_address): ConnectInfo<SocketAddr>,
+    ShortCodePath(short_code): ShortCodePath,
+    Query(query): Query<RedirectQuery>,
+    host: Option<Host>,
+    headers: HeaderMap,
+) -> Result<Response, url_shortener::Error> {
+    let client_ip = client_ip(&headers, peer_address, options.trust_forwarded_for);
+    let user_agent = headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok());
+    let domain = request_domain(&domains, host);
+    let visitor = Visitor {
+        ip: client_ip,
+        user_agent,
+        domain: domain.as_deref(),
+    };
+
+    if matches!(query.preview.as_deref(), Some("1" | "true")) {
+        return preview_page(&url_shortener, &domains, &short_code, &visitor).await;
+    }
+
+    let result = url_shortener.visit(&short_code, &visitor).await;
+    monitoring::record_redirect(match &result {
+        Ok(_) => "hit",
+        Err(url_shortener::Error::NotFound) => "not_found",
+        Err(url_shortener::Error::Expired) => "expired",
+        Err(url_shortener::Error::Deactivated) => "deactivated",
+        Err(_) => "error",
+    });
+
+    let destination = match (result, &options.fallback_redirect_url) {
+        (Err(url_shortener::Error::NotFound), Some(fallback_redirect_url)) => {
+            return Ok(fallback_redirect(fallback_redirect_url, &short_code));
+        }
+        (result, _) => result?,
+    };
+    click_recorder.record(
+        &destination,
+        headers.get(REFERER).and_then(|value| value.to_str().ok()),
+        user_agent,
+        client_ip,
+    );
+
+    let status = match destination.redirect_type {
+        RedirectType::Permanent => StatusCode::MOVED_PERMANENTLY,
+        RedirectType::Temporary => StatusCode::FOUND,
+    };
+    let cache_headers = cache_headers(&destination, &options);
+    Ok((
+        status,
+        [(LOCATION, destination.original_url)],
+        cache_headers,
+    )
+        .into_response())
+}
+
+/// Sends a visitor of an unknown short code to the configured page. The redirect isn't cached,
+/// since the code may be registered later.
+fn fallback_redirect(fallback_redirect_url: &str, short_code: &str) -> Response {
+    let short_code: String = url::form_urlencoded::byte_serialize(short_code.as_bytes()).collect();
+    (
+        StatusCode::FOUND,
+        [
+            (
+                LOCATION,
+                fallback_redirect_url.replace("{short_code}", &short_code),
+            ),
+            (CACHE_CONTROL, "no-store".to_string()),
+        ],
+    )
+        .into_response()
+}
+
+/// Permanent short URLs may be cached by CDNs, for at most as long as they live. Temporary ones
+/// may be cached by browsers only, and short URLs whose destination can change from one visit to
+/// the next not at all.
+fn cache_headers(
+    destination: &Destination,
+    options: &ApiOptions,
+) -> AppendHeaders<Vec<(HeaderName, String)>> {
+    let max_age = match destination.redirect_type {
+        _ if destination.varies => None,
+        RedirectType::Permanent => Some(options.permanent_redirect_max_age),
+        RedirectType::Temporary => Some(options.temporary_redirect_max_age),
+    };
+    let max_age = match (max_age, destination.expires_at) {
+        (Some(max_age), Some(expires_at)) => {
+            Some(max_age.min((expires_at - Utc::now()).to_std().unwrap_or_default()))
+        }
+        (max_age, _) => max_age,
+    };
+
+    let headers = match max_age {
+        None => vec![(CACHE_CONTROL, "no-store".to_string())],
+        Some(max_age) if max_age.is_zero() => vec![(CACHE_CONTROL, "no-cache".to_string())],
+        Some(max_age) => {
+            let visibility = match destination.redirect_type {
+                RedirectType::Permanent => "public",
+                RedirectType::Temporary => "private",
+            };
+            let expires = Utc::now() + Duration::seconds(max_age.as_secs() as i64);
+            vec![
+                (
+                    CACHE_CONTROL,
+                    format!("{}, max-age={}", visibility, max_age.as_secs()),
+                ),
+                (EXPIRES, format_http_date(expires)),
+            ]
+        }
+    };
+    AppendHeaders(headers)
+}
+
+#[utoipa::path(
+    get,
+    path = "/{short_code}/preview",
+    tag = "redirects",
+    params(("short_code" = String, Path)),
+    responses(
+        (status = 200, description = "A page showing where the short URL leads", body = String, content_type = "text/html"),
+        (status = 404, description = "No such short URL", body = ErrorResponse),
+        (status = 410, description = "The short URL expired or was deactivated", body = ErrorResponse),
+    )
+)]
+pub(super) async fn preview_handler(
+    State(AppState {
+        domains,
+        url_shortener,
+        options,
+        ..
+    }): State<AppState>,
+    ConnectInfo(peer_address): ConnectInfo<SocketAddr>,
+    ShortCodePath(short_code): ShortCodePath,
+    host: Option<Host>,
+    headers: HeaderMap,
+) -> Result<Response, url_shortener::Error> {
+    let domain = request_domain(&domains, host);
+    let visitor = Visitor {
+        ip: client_ip(&headers, peer_address, options.trust_forwarded_for),
+        user_agent: headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok()),
+        domain: domain.as_deref(),
+    };
+    preview_page(&url_shortener, &domains, &short_code, &visitor).await
+}
+
+/// Previews aren't counted as clicks, and the page links straight to the destination it shows.
+async fn preview_page(
+    url_shortener: &UrlShortener,
+    domains: &Domains,
+    short_code: &str,
+    visitor: &Visitor<'_>,
+) -> Result<Response, url_shortener::Error> {
+    let preview = url_shortener.preview(short_code, visitor).await?;
+    let short_url = domains.short_url(visitor.domain, short_code);
+    Ok((
+        [(CACHE_CONTROL, "no-store")],
+        Html(preview::render(
+            &short_url,
+            &preview.original_url,
+            preview.page_title.as_deref(),
+        )),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize, IntoParams)]
+pub(super) struct QrCodeQuery {
+    size: Option<u32>,
+    ec: Option<QrErrorCorrection>,
+    format: Option<QrFormat>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/{short_code}/qr",
+    tag = "redirects",
+    params(("short_code" = String, Path), QrCodeQuery),
+    responses(
+        (status = 200, description = "A QR code of the short URL", content(
+            (String = "image/png"),
+            (String = "image/svg+xml"),
+        )),
+        (status = 400, description = "The size is out of range", body = ErrorResponse),
+        (status = 404, description = "No such short URL", body = ErrorResponse),
+    )
+)]
+pub(super) async fn qr_code_handler(
+    State(AppState {
+        domains,
+        url_shortener,
+        ..
+    }): State<AppState>,
+    ShortCodePath(short_code): ShortCodePath,
+    Query(query): Query<QrCodeQuery>,
+    host: Option<Host>,
+) -> Result<Response, url_shortener::Error> {
+    let domain = request_domain(&domains, host);
+    url_shortener
+        .lookup_on(&short_code, domain.as_deref())
+        .await?;
+
+    let format = query.format.unwrap_or_default();
+    let image = qr::render(
+        &domains.short_url(domain.as_deref(), &short_code),
+        query.size.unwrap_or(qr::DEFAULT_QR_SIZE),
+        query.ec.unwrap_or_default(),
+        format,
+    )?;
+    Ok(([(CONTENT_TYPE, format.content_type())], image).into_response())
+}
@@ -0,0 +1,172 @@
+//! How often short URLs and campaigns are visited.
+
+use super::{export_failed, AppState, Caller, ErrorResponse};
+use crate::{
+    link_csv,
+    url_shortener::{self, CampaignStats, ClickStats, ExportedClick},
+};
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderName,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, TryStreamExt};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+pub(super) fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/stats/:short_code", get(stats_handler))
+        .route("/stats/:short_code/export", get(export_clicks_handler))
+        .route("/campaigns/:name/stats", get(campaign_stats_handler))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats/{short_code}",
+    tag = "links",
+    params(("short_code" = String, Path)),
+    responses(
+        (status = 200, description = "Click statistics of the short URL", body = ClickStats),
+        (status = 404, description = "No such short URL", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn stats_handler(
+    State(AppState { url_shortener, .. }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(short_code): Path<String>,
+) -> Result<([(HeaderName, &'static str); 1], Json<ClickStats>), url_shortener::Error> {
+    let stats = url_shortener
+        .stats(&short_code, caller.link_scope())
+        .await?;
+    // Statistics change with every click.
+    Ok(([(CACHE_CONTROL, "no-store")], Json(stats)))
+}
+
+#[derive(Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum ClickExportFormat {
+    #[default]
+    Csv,
+    /// A JSON array of clicks.
+    Json,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub(super) struct ExportClicksQuery {
+    /// `csv` by default.
+    format: Option<ClickExportFormat>,
+    /// Only clicks at or after this time.
+    from: Option<DateTime<Utc>>,
+    /// Only clicks before this time.
+    to: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/stats/{short_code}/export",
+    tag = "links",
+    params(("short_code" = String, Path), ExportClicksQuery),
+    responses(
+        (status = 200, description = "Every click of the short URL in the range, oldest first", content(
+            (String = "text/csv"),
+            (Vec<ExportedClick> = "application/json"),
+        )),
+        (status = 400, description = "The range is empty", body = ErrorResponse),
+        (status = 404, description = "No such short URL", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn export_clicks_handler(
+    State(AppState { url_shortener, .. }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(short_code): Path<String>,
+    Query(query): Query<ExportClicksQuery>,
+) -> Result<Response, url_shortener::Error> {
+    let clicks = url_shortener
+        .export_clicks(&short_code, caller.link_scope(), query.from, query.to)
+        .await?;
+    // Namespaced short codes contain a slash, which can't be part of a file name.
+    let file_name = short_code.replace('/', "_");
+
+    let (content_type, file_name, body) = match query.format.unwrap_or_default() {
+        ClickExportFormat::Csv => {
+            let header = futures_util::stream::once(async { Ok(link_csv::write_click_header()) });
+            let rows = clicks
+                .map_ok(|clicks| link_csv::write_clicks(&clicks))
+                .map_err(export_failed);
+            (
+                "text/csv; charset=utf-8",
+                format!("{}_clicks.csv", file_name),
+                Body::from_stream(header.chain(rows)),
+            )
+        }
+        ClickExportFormat::Json => {
+            // The array is written a page at a time, with commas between the pages.
+            let opening =
+                futures_util::stream::once(async { Ok::<_, std::io::Error>(b"[".to_vec()) });
+            let pages = clicks.enumerate().map(|(index, clicks)| {
+                let clicks = clicks.map_err(export_failed)?;
+                let mut page = Vec::new();
+                for (position, click) in clicks.iter().enumerate() {
+                    if index > 0 || position > 0 {
+                        page.push(b',');
+                    }
+                    serde_json::to_writer(&mut page, click).expect("clicks serialize to JSON");
+                }
+                Ok(page)
+            });
+            let closing = futures_util::stream::once(async { Ok(b"]".to_vec()) });
+            (
+                "application/json",
+                format!("{}_clicks.json", file_name),
+                Body::from_stream(opening.chain(pages).chain(closing)),
+            )
+        }
+    };
+
+    Ok((
+        [
+            (CONTENT_TYPE, content_type.to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+            // Exports change with every click.
+            (CACHE_CONTROL, "no-store".to_string()),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/campaigns/{name}/stats",
+    tag = "campaigns",
+    params(("name" = String, Path)),
+    responses(
+        (status = 200, description = "Click statistics of all of the campaign's short URLs together", body = CampaignStats),
+        (status = 404, description = "No such campaign", body = ErrorResponse),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub(super) async fn campaign_stats_handler(
+    State(AppState { url_shortener, .. }): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    Path(name): Path<String>,
+) -> Result<([(HeaderName, &'static str); 1], Json<CampaignStats>), url_shortener::Error> {
+    let stats = url_shortener
+        .campaign_stats(&name, caller.link_scope())
+        .await?;
+    // Statistics change with every click.
+    Ok(([(CACHE_CONTROL, "no-store")], Json(stats)))
+}
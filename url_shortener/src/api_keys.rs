@@ -0,0 +1,303 @@
+use crate::database::{encode_timestamp, get_timestamp, Database};
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+use utoipa::ToSchema;
+
+const API_KEY_PREFIX: &str = "usk_";
+const API_KEY_RANDOM_LENGTH: usize = 32;
+
+pub struct CreatedApiKey {
+    pub id: i64,
+    pub name: String,
+    pub key: String,
+}
+
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// How many short URLs a single API key may create. `None` means no limit.
+#[derive(Clone, Copy, Default)]
+pub struct LinkQuota {
+    pub per_day: Option<i64>,
+    pub per_month: Option<i64>,
+}
+
+/// The periods quotas are counted in. Both start at midnight UTC.
+#[derive(Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaPeriod {
+    Day,
+    Month,
+}
+
+impl QuotaPeriod {
+    /// How the period containing `now` is stored, like `2026-10-14` or `2026-10`.
+    fn key(self, now: DateTime<Utc>) -> String {
+        match self {
+            QuotaPeriod::Day => now.format("%Y-%m-%d").to_string(),
+            QuotaPeriod::Month => now.format("%Y-%m").to_string(),
+        }
+    }
+
+    /// When the period containing `now` ends and its count starts over.
+    pub fn resets_at(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = now.date_naive();
+        let next = match self {
+            QuotaPeriod::Day => today + Days::new(1),
+            QuotaPeriod::Month => {
+                NaiveDate::from_ymd_opt(today.year(), today.month(), 1)
+                    .expect("the first of a month exists")
+                    + Months::new(1)
+            }
+        };
+        next.and_hms_opt(0, 0, 0)
+            .expect("midnight exists")
+            .and_utc()
+    }
+
+    fn quota(self, quota: LinkQuota) -> Option<i64> {
+        match self {
+            QuotaPeriod::Day => quota.per_day,
+            QuotaPeriod::Month => quota.per_month,
+        }
+    }
+}
+
+/// An API key's short URLs in the current day or month.
+#[derive(Serialize, ToSchema)]
+pub struct PeriodUsage {
+    pub period: QuotaPeriod,
+    pub links_created: i64,
+    /// `None` without a limit.
+    pub quota: Option<i64>,
+    pub resets_at: DateTime<Utc>,
+}
+
+/// Stores API keys as SHA-256 hashes, so the plain key is only known when it is created.
+pub struct ApiKeyStore {
+    database: Database,
+}
+
+impl ApiKeyStore {
+    pub fn new(database: Database) -> Self {
+        ApiKeyStore { database }
+    }
+
+    pub async fn create(&self, name: &str) -> Result<CreatedApiKey, sqlx::Error> {
+        let key = generate_api_key();
+        let row = sqlx::query(
+            "INSERT INTO api_keys (name, key_hash, created_at) VALUES ($1, $2, $3) RETURNING id",
+        )
+        .bind(name)
+        .bind(hash_api_key(&key))
+        .bind(encode_timestamp(Utc::now()))
+        .fetch_one(self.database.pool())
+        .await?;
+
+        Ok(CreatedApiKey {
+            id: row.get::<i64, &str>("id"),
+            name: name.to_string(),
+            key,
+        })
+    }
+
+    /// Revokes a key by its ID. Returns `false` if there is no active key with that ID.
+    pub async fn revoke(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let result =
+            sqlx::query("UPDATE api_keys SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL")
+                .bind(encode_timestamp(Utc::now()))
+                .bind(id)
+                .execute(self.database.pool())
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replaces an active key by a new one with the same name, and revokes the old key. Returns
+    /// `None` if there is no active key with that ID.
+    pub async fn rotate(&self, id: i64) -> Result<Option<CreatedApiKey>, sqlx::Error> {
+        let mut transaction = self.database.pool().begin().await?;
+
+        let now = encode_timestamp(Utc::now());
+        let Some(row) = sqlx::query(
+            "UPDATE api_keys SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL \
+             RETURNING name",
+        )
+        .bind(&now)
+        .bind(id)
+        .fetch_optional(&mut *transaction)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let name = row.get::<String, &str>("name");
+
+        let key = generate_api_key();
+        let row = sqlx::query(
+            "INSERT INTO api_keys (name, key_hash, created_at) VALUES ($1, $2, $3) RETURNING id",
+        )
+        .bind(&name)
+        .bind(hash_api_key(&key))
+        .bind(&now)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(Some(CreatedApiKey {
+            id: row.get::<i64, &str>("id"),
+            name,
+            key,
+        }))
+    }
+
+    /// All keys, including revoked ones, oldest first.
+    pub async fn list(&self) -> Result<Vec<ApiKey>, sqlx::Error> {
+        let rows =
+            sqlx::query("SELECT id, name, created_at, revoked_at FROM api_keys ORDER BY id ASC")
+                .fetch_all(self.database.pool())
+                .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ApiKey {
+                id: row.get::<i64, &str>("id"),
+                name: row.get::<String, &str>("name"),
+                created_at: get_timestamp(row, "created_at").unwrap_or_default(),
+                revoked_at: get_timestamp(row, "revoked_at"),
+            })
+            .collect())
+    }
+
+    /// Returns the ID of the key, or `None` if it doesn't exist or was revoked.
+    pub async fn verify(&self, key: &str) -> Result<Option<i64>, sqlx::Error> {
+        let optional_row =
+            sqlx::query("SELECT id FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL")
+                .bind(hash_api_key(key))
+                .fetch_optional(self.database.pool())
+                .await?;
+
+        Ok(optional_row.map(|row| row.get::<i64, &str>("id")))
+    }
+
+    /// Counts `count` new short URLs against the quota of an API key, before they are created.
+    /// Nothing is counted if that would exceed the quota of any period; the period is returned
+    /// instead.
+    pub async fn reserve_links(
+        &self,
+        api_key_id: i64,
+        count: i64,
+        quota: LinkQuota,
+    ) -> Result<Result<(), QuotaPeriod>, sqlx::Error> {
+        let now = Utc::now();
+        let mut transaction = self.database.pool().begin().await?;
+
+        for period in [QuotaPeriod::Day, QuotaPeriod::Month] {
+            // Counting and reading the new count in one statement keeps concurrent requests from
+            // exceeding the quota together.
+            let links_created = sqlx::query(
+                "INSERT INTO api_key_usage (api_key_id, period, links_created) VALUES ($1, $2, $3)
+                ON CONFLICT (api_key_id, period)
+                DO UPDATE SET links_created = api_key_usage.links_created + excluded.links_created
+                RETURNING links_created",
+            )
+            .bind(api_key_id)
+            .bind(period.key(now))
+            .bind(count)
+            .fetch_one(&mut *transaction)
+            .await?
+            .get::<i64, &str>("links_created");
+
+            if period
+                .quota(quota)
+                .is_some_and(|quota| links_created > quota)
+            {
+                // Dropping the transaction rolls the counts back.
+                return Ok(Err(period));
+            }
+        }
+
+        transaction.commit().await?;
+        Ok(Ok(()))
+    }
+
+    /// Gives back short URLs reserved with `reserve_links` that weren't created after all.
+    pub async fn release_links(&self, api_key_id: i64, count: i64) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        for period in [QuotaPeriod::Day, QuotaPeriod::Month] {
+            sqlx::query(
+                "UPDATE api_key_usage SET links_created = links_created - $1
+                WHERE api_key_id = $2 AND period = $3",
+            )
+            .bind(count)
+            .bind(api_key_id)
+            .bind(period.key(now))
+            .execute(self.database.pool())
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// The usage of an API key in the current day and month.
+    pub async fn usage(
+        &self,
+        api_key_id: i64,
+        quota: LinkQuota,
+    ) -> Result<Vec<PeriodUsage>, sqlx::Error> {
+        let now = Utc::now();
+        let mut usage = Vec::new();
+        for period in [QuotaPeriod::Day, QuotaPeriod::Month] {
+            let links_created = sqlx::query(
+                "SELECT links_created FROM api_key_usage WHERE api_key_id = $1 AND period = $2",
+            )
+            .bind(api_key_id)
+            .bind(period.key(now))
+            .fetch_optional(self.database.pool())
+            .await?
+            .map_or(0, |row| row.get::<i64, &str>("links_created"));
+
+            usage.push(PeriodUsage {
+                period,
+                links_created,
+                quota: period.quota(quota),
+                resets_at: period.resets_at(now),
+            });
+        }
+        Ok(usage)
+    }
+
+    pub async fn count_active(&self) -> Result<i64, sqlx::Error> {
+        let row = sqlx::query("SELECT COUNT(*) AS count FROM api_keys WHERE revoked_at IS NULL")
+            .fetch_one(self.database.pool())
+            .await?;
+
+        Ok(row.get::<i64, &str>("count"))
+    }
+}
+
+impl Clone for ApiKeyStore {
+    fn clone(&self) -> Self {
+        Self {
+            database: self.database.clone(),
+        }
+    }
+}
+
+fn generate_api_key() -> String {
+    let random_part: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_KEY_RANDOM_LENGTH)
+        .map(char::from)
+        .collect();
+    format!("{}{}", API_KEY_PREFIX, random_part)
+}
+
+fn hash_api_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
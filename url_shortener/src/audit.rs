@@ -0,0 +1,215 @@
+use crate::database::{encode_timestamp, get_timestamp, Database};
+use crate::url_shortener::Error;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{
+    any::{AnyArguments, AnyRow},
+    query::Query,
+    Any, Row,
+};
+use tracing::error;
+use utoipa::ToSchema;
+
+/// The actor of changes made with the `admin` command line tool.
+pub const ADMIN_CLI_ACTOR: &str = "admin_cli";
+
+/// Administrative actions that are recorded in the audit log.
+#[derive(Clone, Copy)]
+pub enum AuditAction {
+    CreateLink,
+    ImportLinks,
+    UpdateLink,
+    /// Deactivating a short URL, which is what deleting it does until it's purged.
+    DeleteLink,
+    ReactivateLink,
+    CreateApiKey,
+    RevokeApiKey,
+    CreateNamespace,
+    AddNamespaceMember,
+    RemoveNamespaceMember,
+    CreateCampaign,
+    AddCampaignLink,
+    RemoveCampaignLink,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::CreateLink => "link.create",
+            AuditAction::ImportLinks => "link.import",
+            AuditAction::UpdateLink => "link.update",
+            AuditAction::DeleteLink => "link.delete",
+            AuditAction::ReactivateLink => "link.reactivate",
+            AuditAction::CreateApiKey => "api_key.create",
+            AuditAction::RevokeApiKey => "api_key.revoke",
+            AuditAction::CreateNamespace => "namespace.create",
+            AuditAction::AddNamespaceMember => "namespace.add_member",
+            AuditAction::RemoveNamespaceMember => "namespace.remove_member",
+            AuditAction::CreateCampaign => "campaign.create",
+            AuditAction::AddCampaignLink => "campaign.add_link",
+            AuditAction::RemoveCampaignLink => "campaign.remove_link",
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    /// Who did it, like `api_key:1`, `user:2` or `admin_cli`.
+    pub actor: String,
+    pub action: String,
+    /// The short code, API key ID, namespace or campaign acted on.
+    pub target: String,
+    /// What changed, depending on the action.
+    #[schema(value_type = Object)]
+    pub details: Value,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: i64,
+}
+
+/// Narrows down the entries of [`AuditLog::list`]. Every given field has to match.
+#[derive(Default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Records who changed short URLs, API keys and namespaces, and how.
+pub struct AuditLog {
+    /// `None` when nothing is recorded.
+    database: Option<Database>,
+}
+
+impl AuditLog {
+    pub fn new(database: Database) -> Self {
+        AuditLog {
+            database: Some(database),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        AuditLog { database: None }
+    }
+
+    /// Records an action that already happened. Failing to record it is logged rather than
+    /// returned, since the action can't be undone anymore.
+    pub async fn record(&self, actor: &str, action: AuditAction, target: &str, details: Value) {
+        let Some(database) = &self.database else {
+            return;
+        };
+        let result = sqlx::query(
+            "INSERT INTO audit_log (created_at, actor, action, target, details)
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(encode_timestamp(Utc::now()))
+        .bind(actor)
+        .bind(action.as_str())
+        .bind(target)
+        .bind(details.to_string())
+        .execute(database.pool())
+        .await;
+
+        if let Err(error) = result {
+            error!(
+                "Failed to record {} of {} by {} in the audit log: {}",
+                action.as_str(),
+                target,
+                actor,
+                error
+            );
+        }
+    }
+
+    /// The newest entries first.
+    pub async fn list(
+        &self,
+        filter: &AuditFilter,
+        page: u32,
+        per_page: u32,
+    ) -> Result<AuditPage, Error> {
+        let Some(database) = &self.database else {
+            return Ok(AuditPage {
+                entries: Vec::new(),
+                page,
+                per_page,
+                total: 0,
+            });
+        };
+
+        let total_query = format!(
+            "SELECT COUNT(*) AS total FROM audit_log WHERE {}",
+            FILTER_CONDITIONS
+        );
+        let total = bind_filter(&total_query, filter)
+            .fetch_one(database.pool())
+            .await
+            .map_err(Error::Internal)?
+            .get::<i64, &str>("total");
+
+        let entries_query = format!(
+            "SELECT id, created_at, actor, action, target, details FROM audit_log WHERE {}
+            ORDER BY id DESC LIMIT $6 OFFSET $7",
+            FILTER_CONDITIONS
+        );
+        let entries = bind_filter(&entries_query, filter)
+            .bind(per_page as i64)
+            .bind(page.saturating_sub(1) as i64 * per_page as i64)
+            .fetch_all(database.pool())
+            .await
+            .map_err(Error::Internal)?
+            .iter()
+            .map(audit_entry_from_row)
+            .collect();
+
+        Ok(AuditPage {
+            entries,
+            page,
+            per_page,
+            total,
+        })
+    }
+}
+
+impl Clone for AuditLog {
+    fn clone(&self) -> Self {
+        Self {
+            database: self.database.clone(),
+        }
+    }
+}
+
+/// Matches the parameters bound by [`bind_filter`].
+const FILTER_CONDITIONS: &str = "($1 IS NULL OR actor = $1) AND ($2 IS NULL OR action = $2)
+    AND ($3 IS NULL OR target = $3) AND ($4 IS NULL OR created_at >= $4)
+    AND ($5 IS NULL OR created_at < $5)";
+
+fn bind_filter<'q>(query: &'q str, filter: &'q AuditFilter) -> Query<'q, Any, AnyArguments<'q>> {
+    sqlx::query(query)
+        .bind(filter.actor.as_deref())
+        .bind(filter.action.as_deref())
+        .bind(filter.target.as_deref())
+        .bind(filter.since.map(encode_timestamp))
+        .bind(filter.until.map(encode_timestamp))
+}
+
+fn audit_entry_from_row(row: &AnyRow) -> AuditEntry {
+    AuditEntry {
+        id: row.get("id"),
+        created_at: get_timestamp(row, "created_at").unwrap_or_default(),
+        actor: row.get("actor"),
+        action: row.get("action"),
+        target: row.get("target"),
+        details: serde_json::from_str(&row.get::<String, &str>("details")).unwrap_or_default(),
+    }
+}
@@ -0,0 +1,302 @@
+//! Manages the URL shortener straight through its database, without going through the HTTP API.
+//! Reads the same `config.toml` and environment variables as the server.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use std::{process::ExitCode, sync::Arc};
+use url_shortener::{
+    api_keys::ApiKeyStore,
+    audit::{AuditAction, AuditLog, ADMIN_CLI_ACTOR},
+    cache::{RedisCache, ShortUrlCache},
+    config::Config,
+    database::Database,
+    geoip::NoGeoIp,
+    safety::NoopChecker,
+    url_shortener::{Error, LinkScope, LinkSort, UrlShortener},
+    webhooks::Webhooks,
+};
+
+#[derive(Parser)]
+#[command(about = "Manage short URLs and API keys of the URL shortener")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List short URLs page by page.
+    List {
+        #[arg(long, default_value_t = 1)]
+        page: u32,
+        #[arg(long, default_value_t = 20)]
+        per_page: u32,
+        #[arg(long, value_enum, default_value_t = Sort::Newest)]
+        sort: Sort,
+        /// Only list short URLs whose destination is broken.
+        #[arg(long)]
+        broken: bool,
+    },
+    /// Deactivate a short URL. It can be reactivated later through the API.
+    Delete { short_code: String },
+    /// Print the click statistics of a short URL as JSON.
+    Stats { short_code: String },
+    /// Manage API keys.
+    #[command(subcommand)]
+    ApiKeys(ApiKeysCommand),
+}
+
+#[derive(Subcommand)]
+enum ApiKeysCommand {
+    /// List all API keys, including revoked ones.
+    List,
+    /// Create an API key and print it. It can't be shown again.
+    Create { name: String },
+    /// Revoke an API key.
+    Revoke { id: i64 },
+    /// Replace an API key by a new one with the same name, and revoke the old key.
+    Rotate { id: i64 },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Sort {
+    Newest,
+    Oldest,
+    MostClicked,
+    ShortCode,
+}
+
+impl From<Sort> for LinkSort {
+    fn from(sort: Sort) -> Self {
+        match sort {
+            Sort::Newest => LinkSort::Newest,
+            Sort::Oldest => LinkSort::Oldest,
+            Sort::MostClicked => LinkSort::MostClicked,
+            Sort::ShortCode => LinkSort::ShortCode,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("Invalid configuration: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let database = match Database::connect(&config.db_url, &config.pool_settings()).await {
+        Ok(database) => database,
+        Err(error) => {
+            eprintln!("Cannot connect to the database: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(error) = database.migrate().await {
+        eprintln!("Cannot migrate the database: {}", error);
+        return ExitCode::FAILURE;
+    }
+
+    let result = run(cli.command, &config, database.clone()).await;
+    database.close().await;
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        // Unlike API clients, the operator should see what went wrong in the database.
+        Err(Error::Internal(error)) => {
+            eprintln!("Database error: {}", error);
+            ExitCode::FAILURE
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command, config: &Config, database: Database) -> Result<(), Error> {
+    let audit_log = AuditLog::new(database.clone());
+    match command {
+        Command::List {
+            page,
+            per_page,
+            sort,
+            broken,
+        } => {
+            let links = url_shortener(config, database)
+                .await
+                .list(
+                    page.max(1),
+                    per_page.max(1),
+                    sort.into(),
+                    LinkScope::All,
+                    broken,
+                )
+                .await?;
+
+            println!(
+                "{:<16} {:>8}  {:<20} {:<8} ORIGINAL URL",
+                "SHORT CODE", "CLICKS", "CREATED", "STATUS"
+            );
+            for link in &links.links {
+                let status = if link.deleted_at.is_some() {
+                    "deleted"
+                } else if link
+                    .expires_at
+                    .is_some_and(|expires_at| expires_at <= chrono::Utc::now())
+                {
+                    "expired"
+                } else if link
+                    .health
+                    .as_ref()
+                    .is_some_and(|health| health.broken_since.is_some())
+                {
+                    "broken"
+                } else {
+                    "active"
+                };
+                println!(
+                    "{:<16} {:>8}  {:<20} {:<8} {}",
+                    link.short_code,
+                    link.click_count,
+                    link.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    status,
+                    link.original_url
+                );
+            }
+            println!("Page {}, {} short URLs in total", links.page, links.total);
+        }
+        Command::Delete { short_code } => {
+            url_shortener(config, database)
+                .await
+                .delete(&short_code, LinkScope::All)
+                .await?;
+            audit_log
+                .record(
+                    ADMIN_CLI_ACTOR,
+                    AuditAction::DeleteLink,
+                    &short_code,
+                    json!({}),
+                )
+                .await;
+            println!("Deleted {}", short_code);
+        }
+        Command::Stats { short_code } => {
+            let stats = url_shortener(config, database)
+                .await
+                .stats(&short_code, LinkScope::All)
+                .await?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&stats).expect("stats serialize to JSON")
+            );
+        }
+        Command::ApiKeys(command) => {
+            let api_keys = ApiKeyStore::new(database);
+            match command {
+                ApiKeysCommand::List => {
+                    println!("{:>6}  {:<20} {:<20} REVOKED", "ID", "NAME", "CREATED");
+                    for api_key in api_keys.list().await.map_err(Error::Internal)? {
+                        println!(
+                            "{:>6}  {:<20} {:<20} {}",
+                            api_key.id,
+                            api_key.name,
+                            api_key.created_at.format("%Y-%m-%d %H:%M:%S"),
+                            api_key.revoked_at.map_or("-".to_string(), |revoked_at| {
+                                revoked_at.format("%Y-%m-%d %H:%M:%S").to_string()
+                            })
+                        );
+                    }
+                }
+                ApiKeysCommand::Create { name } => {
+                    let api_key = api_keys.create(&name).await.map_err(Error::Internal)?;
+                    audit_log
+                        .record(
+                            ADMIN_CLI_ACTOR,
+                            AuditAction::CreateApiKey,
+                            &api_key.id.to_string(),
+                            json!({ "name": api_key.name }),
+                        )
+                        .await;
+                    println!(
+                        "Created API key {} ({}): {}",
+                        api_key.id, api_key.name, api_key.key
+                    );
+                }
+                ApiKeysCommand::Revoke { id } => {
+                    if !api_keys.revoke(id).await.map_err(Error::Internal)? {
+                        return Err(no_active_api_key(id));
+                    }
+                    audit_log
+                        .record(
+                            ADMIN_CLI_ACTOR,
+                            AuditAction::RevokeApiKey,
+                            &id.to_string(),
+                            json!({}),
+                        )
+                        .await;
+                    println!("Revoked API key {}", id);
+                }
+                ApiKeysCommand::Rotate { id } => {
+                    let api_key = api_keys
+                        .rotate(id)
+                        .await
+                        .map_err(Error::Internal)?
+                        .ok_or_else(|| no_active_api_key(id))?;
+                    audit_log
+                        .record(
+                            ADMIN_CLI_ACTOR,
+                            AuditAction::RevokeApiKey,
+                            &id.to_string(),
+                            json!({ "replaced_by": api_key.id }),
+                        )
+                        .await;
+                    audit_log
+                        .record(
+                            ADMIN_CLI_ACTOR,
+                            AuditAction::CreateApiKey,
+                            &api_key.id.to_string(),
+                            json!({ "name": api_key.name, "replaces": id }),
+                        )
+                        .await;
+                    println!(
+                        "Revoked API key {} and replaced it by {} ({}): {}",
+                        id, api_key.id, api_key.name, api_key.key
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn no_active_api_key(id: i64) -> Error {
+    Error::InvalidRequest(format!("There is no active API key with ID {}", id))
+}
+
+/// A [`UrlShortener`] for maintenance. It shares the server's Redis cache, so deleted short URLs
+/// stop redirecting right away, but can't reach the in-process cache of a running server.
+async fn url_shortener(config: &Config, database: Database) -> UrlShortener {
+    let cache = match &config.redis_url {
+        Some(redis_url) => Some(ShortUrlCache::redis(
+            RedisCache::connect(redis_url, config.cache_ttl_seconds)
+                .await
+                .expect("Failed to connect to Redis"),
+        )),
+        None => None,
+    };
+
+    UrlShortener::new(
+        database,
+        Arc::new(config.domains()),
+        cache,
+        config.short_code_options(),
+        Arc::new(NoopChecker),
+        Webhooks::disabled(),
+        Arc::new(NoGeoIp),
+    )
+}
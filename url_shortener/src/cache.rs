@@ -0,0 +1,269 @@
+use crate::url_shortener::{DeviceType, RedirectType, Variant};
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tracing::warn;
+use utoipa::ToSchema;
+
+const KEY_PREFIX: &str = "url_shortener:short_url:";
+
+/// The part of a short URL that is needed to serve a redirect.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CachedShortUrl {
+    pub id: i64,
+    pub original_url: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub redirect_type: RedirectType,
+    #[serde(default)]
+    pub max_clicks: Option<i64>,
+    #[serde(default)]
+    pub domain: Option<String>,
+    #[serde(default)]
+    pub tracking_params: BTreeMap<String, String>,
+    /// Destinations by visitor country.
+    #[serde(default)]
+    pub geo_targets: HashMap<String, String>,
+    #[serde(default)]
+    pub variants: Vec<Variant>,
+    #[serde(default)]
+    pub device_targets: BTreeMap<DeviceType, String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CacheStats {
+    pub backend: &'static str,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Caches short URLs in front of the database and counts how often the cache is hit.
+pub struct ShortUrlCache {
+    store: CacheStore,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+enum CacheStore {
+    Redis(Box<RedisCache>),
+    Memory(MemoryCache),
+}
+
+impl ShortUrlCache {
+    pub fn redis(cache: RedisCache) -> Self {
+        Self::with_store(CacheStore::Redis(Box::new(cache)))
+    }
+
+    pub fn memory(cache: MemoryCache) -> Self {
+        Self::with_store(CacheStore::Memory(cache))
+    }
+
+    fn with_store(store: CacheStore) -> Self {
+        ShortUrlCache {
+            store,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub async fn get(&self, short_code: &str) -> Option<CachedShortUrl> {
+        let short_url = match &self.store {
+            CacheStore::Redis(cache) => cache.get(short_code).await,
+            CacheStore::Memory(cache) => cache.get(short_code),
+        };
+
+        let counter = if short_url.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        short_url
+    }
+
+    pub async fn set(&self, short_code: &str, short_url: &CachedShortUrl) {
+        match &self.store {
+            CacheStore::Redis(cache) => cache.set(short_code, short_url).await,
+            CacheStore::Memory(cache) => cache.set(short_code, short_url),
+        }
+    }
+
+    pub async fn invalidate(&self, short_code: &str) {
+        match &self.store {
+            CacheStore::Redis(cache) => cache.invalidate(short_code).await,
+            CacheStore::Memory(cache) => cache.invalidate(short_code),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            backend: match self.store {
+                CacheStore::Redis(_) => "redis",
+                CacheStore::Memory(_) => "memory",
+            },
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Clone for ShortUrlCache {
+    fn clone(&self) -> Self {
+        Self {
+            store: match &self.store {
+                CacheStore::Redis(cache) => CacheStore::Redis(cache.clone()),
+                CacheStore::Memory(cache) => CacheStore::Memory(cache.clone()),
+            },
+            hits: self.hits.clone(),
+            misses: self.misses.clone(),
+        }
+    }
+}
+
+/// Caches short URLs in Redis, shared by every instance of the service. Redis errors are logged
+/// and treated as cache misses, so redirects keep working from the database while Redis is
+/// unavailable.
+pub struct RedisCache {
+    connection: ConnectionManager,
+    ttl_seconds: i64,
+}
+
+impl RedisCache {
+    pub async fn connect(redis_url: &str, ttl_seconds: i64) -> Result<Self, redis::RedisError> {
+        let client = redis::Client::open(redis_url)?;
+        let connection = ConnectionManager::new(client).await?;
+        Ok(RedisCache {
+            connection,
+            ttl_seconds,
+        })
+    }
+
+    pub async fn get(&self, short_code: &str) -> Option<CachedShortUrl> {
+        let mut connection = self.connection.clone();
+        let value: Option<String> = match connection.get(cache_key(short_code)).await {
+            Ok(value) => value,
+            Err(error) => {
+                warn!("Error reading short URL from Redis: {}", error);
+                return None;
+            }
+        };
+        serde_json::from_str(&value?).ok()
+    }
+
+    pub async fn set(&self, short_code: &str, short_url: &CachedShortUrl) {
+        let ttl_milliseconds = ttl_milliseconds(self.ttl_seconds, short_url);
+        if ttl_milliseconds <= 0 {
+            return;
+        }
+
+        let Ok(value) = serde_json::to_string(short_url) else {
+            return;
+        };
+        let mut connection = self.connection.clone();
+        let result: Result<(), _> = connection
+            .pset_ex(cache_key(short_code), value, ttl_milliseconds as u64)
+            .await;
+        if let Err(error) = result {
+            warn!("Error writing short URL to Redis: {}", error);
+        }
+    }
+
+    pub async fn invalidate(&self, short_code: &str) {
+        let mut connection = self.connection.clone();
+        let result: Result<(), _> = connection.del(cache_key(short_code)).await;
+        if let Err(error) = result {
+            warn!("Error invalidating short URL in Redis: {}", error);
+        }
+    }
+}
+
+impl Clone for RedisCache {
+    fn clone(&self) -> Self {
+        Self {
+            connection: self.connection.clone(),
+            ttl_seconds: self.ttl_seconds,
+        }
+    }
+}
+
+/// A bounded in-process cache that evicts the least recently used short URLs, for deployments
+/// without Redis.
+pub struct MemoryCache {
+    entries: Arc<Mutex<LruCache<String, MemoryCacheEntry>>>,
+    ttl_seconds: i64,
+}
+
+struct MemoryCacheEntry {
+    short_url: CachedShortUrl,
+    cached_until: Instant,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: NonZeroUsize, ttl_seconds: i64) -> Self {
+        MemoryCache {
+            entries: Arc::new(Mutex::new(LruCache::new(capacity))),
+            ttl_seconds,
+        }
+    }
+
+    pub fn get(&self, short_code: &str) -> Option<CachedShortUrl> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get(short_code)?;
+        if entry.cached_until <= Instant::now() {
+            entries.pop(short_code);
+            return None;
+        }
+        Some(entry.short_url.clone())
+    }
+
+    pub fn set(&self, short_code: &str, short_url: &CachedShortUrl) {
+        let ttl_milliseconds = ttl_milliseconds(self.ttl_seconds, short_url);
+        if ttl_milliseconds <= 0 {
+            return;
+        }
+
+        self.entries.lock().unwrap().put(
+            short_code.to_string(),
+            MemoryCacheEntry {
+                short_url: short_url.clone(),
+                cached_until: Instant::now() + Duration::from_millis(ttl_milliseconds as u64),
+            },
+        );
+    }
+
+    pub fn invalidate(&self, short_code: &str) {
+        self.entries.lock().unwrap().pop(short_code);
+    }
+}
+
+impl Clone for MemoryCache {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            ttl_seconds: self.ttl_seconds,
+        }
+    }
+}
+
+/// Short URLs are cached for the configured TTL, but never for longer than they stay valid.
+fn ttl_milliseconds(ttl_seconds: i64, short_url: &CachedShortUrl) -> i64 {
+    let ttl_milliseconds = ttl_seconds.saturating_mul(1000);
+    match short_url.expires_at {
+        Some(expires_at) => ttl_milliseconds.min((expires_at - Utc::now()).num_milliseconds()),
+        None => ttl_milliseconds,
+    }
+}
+
+fn cache_key(short_code: &str) -> String {
+    format!("{}{}", KEY_PREFIX, short_code)
+}
@@ -0,0 +1,45 @@
+use crate::monitoring;
+use crate::url_shortener::{Error, UrlShortener};
+use chrono::{Duration, Utc};
+use tracing::{error, info};
+
+pub struct CleanupSettings {
+    pub interval: std::time::Duration,
+    /// How long expired and deleted short URLs are kept, so that they answer `410 Gone` for a
+    /// while before they are gone for good, and deleted ones can still be reactivated.
+    pub expired_link_retention: Duration,
+    /// How long clicks are kept. `None` keeps them as long as their short URL exists.
+    pub click_retention: Option<Duration>,
+}
+
+/// Periodically purges dead short URLs and old clicks in the background.
+pub fn spawn(url_shortener: UrlShortener, settings: CleanupSettings) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(settings.interval);
+        loop {
+            interval.tick().await;
+
+            let now = Utc::now();
+            let result = url_shortener
+                .purge(
+                    now - settings.expired_link_retention,
+                    settings.click_retention.map(|retention| now - retention),
+                )
+                .await;
+            match result {
+                Ok(purged) => {
+                    monitoring::record_purged("links", purged.links);
+                    monitoring::record_purged("clicks", purged.clicks);
+                    if purged.links > 0 || purged.clicks > 0 {
+                        info!(
+                            "Cleanup purged {} short URLs and {} clicks",
+                            purged.links, purged.clicks
+                        );
+                    }
+                }
+                Err(Error::Internal(error)) => error!("Cleanup failed: {}", error),
+                Err(error) => error!("Cleanup failed: {}", error),
+            }
+        }
+    });
+}
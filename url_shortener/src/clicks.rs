@@ -0,0 +1,90 @@
+use crate::url_shortener::{Destination, Error, UrlShortener};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::{net::IpAddr, sync::Arc};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::warn;
+
+/// Clicks are dropped once this many are waiting to be written, so that a slow database can't
+/// slow down redirects.
+const QUEUE_CAPACITY: usize = 10_000;
+/// Longer header values are cut off before they are stored.
+const MAX_HEADER_LENGTH: usize = 1024;
+
+pub struct ClickEvent {
+    pub short_url_id: i64,
+    pub variant_id: Option<i64>,
+    pub clicked_at: DateTime<Utc>,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+    /// Tells visitors apart without storing their IP addresses.
+    pub ip_hash: String,
+}
+
+/// Queues clicks for a background worker that writes them to the database, so that redirects
+/// don't wait for the insert.
+pub struct ClickRecorder {
+    sender: mpsc::Sender<ClickEvent>,
+    ip_hash_salt: Arc<String>,
+}
+
+impl ClickRecorder {
+    /// Starts the worker, which stops once every recorder has been dropped and the queue is empty.
+    pub fn spawn(url_shortener: UrlShortener, ip_hash_salt: String) -> (Self, JoinHandle<()>) {
+        let (sender, mut receiver) = mpsc::channel::<ClickEvent>(QUEUE_CAPACITY);
+        let worker = tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                match url_shortener.record_click(&event).await {
+                    Ok(()) => {}
+                    Err(Error::Internal(error)) => warn!("Error recording click: {}", error),
+                    Err(error) => warn!("Error recording click: {}", error),
+                }
+            }
+        });
+
+        let recorder = ClickRecorder {
+            sender,
+            ip_hash_salt: Arc::new(ip_hash_salt),
+        };
+        (recorder, worker)
+    }
+
+    pub fn record(
+        &self,
+        destination: &Destination,
+        referrer: Option<&str>,
+        user_agent: Option<&str>,
+        client_ip: IpAddr,
+    ) {
+        let event = ClickEvent {
+            short_url_id: destination.short_url_id,
+            variant_id: destination.variant_id,
+            clicked_at: Utc::now(),
+            referrer: referrer.map(truncate_header),
+            user_agent: user_agent.map(truncate_header),
+            ip_hash: format!(
+                "{:x}",
+                Sha256::digest(format!("{}{}", self.ip_hash_salt, client_ip).as_bytes())
+            ),
+        };
+        if let Err(error) = self.sender.try_send(event) {
+            warn!(
+                "Dropping click of short URL {}: {}",
+                destination.short_url_id, error
+            );
+        }
+    }
+}
+
+impl Clone for ClickRecorder {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            ip_hash_salt: self.ip_hash_salt.clone(),
+        }
+    }
+}
+
+fn truncate_header(value: &str) -> String {
+    value.chars().take(MAX_HEADER_LENGTH).collect()
+}
@@ -0,0 +1,720 @@
+use crate::api::ApiOptions;
+use crate::api_keys::LinkQuota;
+use crate::cleanup::CleanupSettings;
+use crate::database::{Backend, PoolSettings};
+use crate::domains::Domains;
+use crate::health_check::HealthCheckSettings;
+use crate::metadata::MetadataSettings;
+use crate::url_shortener::{ShortCodeAlphabet, ShortCodeOptions, ShortCodeStrategy};
+use crate::webhooks::WebhookSettings;
+use axum::http::{HeaderValue, Method};
+use common::config::{
+    load_toml, override_from_env, override_list_from_env, override_optional_from_env, FileError,
+    InvalidEnv,
+};
+use serde::Deserialize;
+use std::{io, path::PathBuf, str::FromStr, time::Duration};
+use url::Url;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const MIN_SHORT_CODE_LENGTH: usize = 2;
+const MAX_SHORT_CODE_LENGTH: usize = 32;
+
+/// Settings of the service. Every setting has a default, can be set in `config.toml` (or the
+/// file named by `CONFIG_FILE`), and can be overridden by the environment variable of the same
+/// name in upper case, e.g. `rate_limit_burst` by `RATE_LIMIT_BURST`. Lists are given as
+/// comma-separated values in the environment.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: u16,
+    /// Defaults to `http://localhost:<port>`, see [`Config::base_url`].
+    pub base_url: Option<String>,
+    /// Further `host` or `host:port` names short URLs can be served on, with the base URL's
+    /// scheme.
+    pub extra_domains: Vec<String>,
+    pub db_url: String,
+    /// A read replica of `db_url` that redirects are looked up on, with its own pool of
+    /// `db_max_connections`.
+    pub db_read_url: Option<String>,
+    pub db_max_connections: u32,
+    pub db_acquire_timeout_seconds: u64,
+    /// Closes connections that have been idle for this long, or never with 0.
+    pub db_idle_timeout_seconds: u64,
+    /// Uses write-ahead logging for SQLite databases, so reads don't wait for writes.
+    pub sqlite_wal: bool,
+    /// How long SQLite waits for a locked database before failing with `database is locked`.
+    pub sqlite_busy_timeout_ms: u64,
+    /// A random secret is generated when none is configured.
+    pub jwt_secret: Option<String>,
+    /// Salts the client IP hashes stored with clicks. A random salt is generated when none is
+    /// configured.
+    pub ip_hash_salt: Option<String>,
+    /// `random` codes, or `counter` for the shortest possible, but guessable, sequential codes.
+    pub short_code_strategy: ShortCodeStrategy,
+    /// Length of random short codes until the code space fills up and they grow longer.
+    pub short_code_length: usize,
+    /// The shortest and longest short codes clients may ask for with `short_code_length`.
+    pub min_requested_short_code_length: usize,
+    pub max_requested_short_code_length: usize,
+    /// `alphanumeric`, or `unambiguous` to leave out look-alike characters such as `0` and `O`.
+    pub short_code_alphabet: ShortCodeAlphabet,
+    /// Generates lower case codes only and matches short codes regardless of case.
+    pub case_insensitive_short_codes: bool,
+    pub rate_limit_burst: u32,
+    pub rate_limit_per_minute: u32,
+    pub rate_limit_trust_forwarded_for: bool,
+    pub reuse_existing_urls: bool,
+    pub max_batch_size: usize,
+    /// How long browsers and CDNs may cache redirects of permanent short URLs.
+    pub permanent_redirect_max_age_seconds: u64,
+    /// How long browsers, but not shared caches, may cache redirects of temporary short URLs.
+    /// With 0 they ask again on every visit.
+    pub temporary_redirect_max_age_seconds: u64,
+    /// Where visitors of unknown short codes are redirected to instead of getting a 404. A
+    /// `{short_code}` in it is replaced by the code they asked for.
+    pub fallback_redirect_url: Option<String>,
+    /// How long `POST /shorten` retries with the same `Idempotency-Key` get the same short URL.
+    pub idempotency_key_ttl_seconds: u64,
+    /// How many short URLs a single API key may create per UTC day, or any number without one.
+    pub api_key_daily_link_quota: Option<i64>,
+    /// How many short URLs a single API key may create per UTC month.
+    pub api_key_monthly_link_quota: Option<i64>,
+    pub redis_url: Option<String>,
+    pub cache_capacity: usize,
+    pub cache_ttl_seconds: i64,
+    pub shutdown_timeout_seconds: u64,
+    /// Largest request body accepted, in bytes. `/import` has its own, larger limit.
+    pub max_request_body_size: usize,
+    /// Requests that take longer, including reading their body, are answered with
+    /// `408 Request Timeout`.
+    pub request_timeout_seconds: u64,
+    /// Further requests wait until one of this many in-flight requests finishes.
+    pub max_concurrent_requests: usize,
+    /// How often dead short URLs and old clicks are purged, or never with 0.
+    pub cleanup_interval_seconds: u64,
+    /// Days expired, used-up and deleted short URLs are kept before they are purged.
+    pub expired_link_retention_days: u32,
+    /// Days clicks are kept, or forever with 0.
+    pub click_retention_days: u32,
+    /// Serves HTTPS instead of HTTP when both a PEM certificate chain and private key are set.
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    /// Additionally listens for plain HTTP on this port and redirects it to HTTPS.
+    pub http_redirect_port: Option<u16>,
+    /// Serves the gRPC API on this port. Without it, only the REST API is available.
+    pub grpc_port: Option<u16>,
+    /// Origins that browsers may call the API from, or `*` for any origin. Empty disables CORS.
+    pub cors_allowed_origins: Vec<String>,
+    pub cors_allowed_methods: Vec<String>,
+    /// File of hosts that must not be shortened, one per line.
+    pub url_blocklist_path: Option<String>,
+    /// MaxMind GeoIP2 or GeoLite2 database (`.mmdb`) that resolves visitor countries for geo
+    /// targets. Without one, every visitor gets the default destination.
+    pub geoip_database_path: Option<String>,
+    /// Short codes that may not be used, on top of the built-in route names and reserved words.
+    pub reserved_short_codes: Vec<String>,
+    /// Receive a signed JSON payload when a short URL is created, clicked or expires.
+    pub webhook_urls: Vec<String>,
+    /// Required when webhooks are configured.
+    pub webhook_secret: Option<String>,
+    pub webhook_max_attempts: u32,
+    pub webhook_timeout_seconds: u64,
+    /// Fetch the title and favicon of destination pages in the background after shortening.
+    pub page_metadata_enabled: bool,
+    pub page_metadata_timeout_seconds: u64,
+    /// Only this many bytes of a destination page are read.
+    pub page_metadata_max_page_size: usize,
+    /// Check the destinations of active short URLs in the background and flag the broken ones.
+    pub health_check_enabled: bool,
+    /// How often a batch of destinations is checked.
+    pub health_check_interval_seconds: u64,
+    pub health_check_batch_size: usize,
+    /// Hours before a destination is checked again.
+    pub health_check_recheck_hours: u32,
+    pub health_check_timeout_seconds: u64,
+    /// Failed checks in a row after which a destination counts as broken.
+    pub health_check_failure_threshold: u32,
+    /// Deactivate short URLs once their destination counts as broken.
+    pub health_check_deactivate_broken: bool,
+    /// Serve a page for shortening URLs from the browser at `/`.
+    pub web_ui_enabled: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            port: 3000,
+            base_url: None,
+            extra_domains: Vec::new(),
+            db_url: "sqlite::memory:".to_string(),
+            db_read_url: None,
+            db_max_connections: 10,
+            db_acquire_timeout_seconds: 30,
+            db_idle_timeout_seconds: 600,
+            sqlite_wal: true,
+            sqlite_busy_timeout_ms: 5000,
+            jwt_secret: None,
+            ip_hash_salt: None,
+            short_code_strategy: ShortCodeStrategy::Random,
+            short_code_length: 4,
+            min_requested_short_code_length: 4,
+            max_requested_short_code_length: MAX_SHORT_CODE_LENGTH,
+            short_code_alphabet: ShortCodeAlphabet::Alphanumeric,
+            case_insensitive_short_codes: false,
+            rate_limit_burst: 10,
+            rate_limit_per_minute: 30,
+            rate_limit_trust_forwarded_for: false,
+            reuse_existing_urls: false,
+            max_batch_size: 100,
+            permanent_redirect_max_age_seconds: 86400,
+            temporary_redirect_max_age_seconds: 0,
+            fallback_redirect_url: None,
+            idempotency_key_ttl_seconds: 86400,
+            api_key_daily_link_quota: None,
+            api_key_monthly_link_quota: None,
+            redis_url: None,
+            cache_capacity: 10_000,
+            cache_ttl_seconds: 3600,
+            shutdown_timeout_seconds: 30,
+            max_request_body_size: 1024 * 1024,
+            request_timeout_seconds: 30,
+            max_concurrent_requests: 1024,
+            cleanup_interval_seconds: 3600,
+            expired_link_retention_days: 7,
+            click_retention_days: 0,
+            tls_cert_path: None,
+            tls_key_path: None,
+            http_redirect_port: None,
+            grpc_port: None,
+            cors_allowed_origins: Vec::new(),
+            cors_allowed_methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            url_blocklist_path: None,
+            geoip_database_path: None,
+            reserved_short_codes: Vec::new(),
+            webhook_urls: Vec::new(),
+            webhook_secret: None,
+            webhook_max_attempts: 5,
+            webhook_timeout_seconds: 10,
+            page_metadata_enabled: true,
+            page_metadata_timeout_seconds: 5,
+            page_metadata_max_page_size: 512 * 1024,
+            health_check_enabled: false,
+            health_check_interval_seconds: 60,
+            health_check_batch_size: 50,
+            health_check_recheck_hours: 24,
+            health_check_timeout_seconds: 10,
+            health_check_failure_threshold: 3,
+            health_check_deactivate_broken: false,
+            web_ui_enabled: true,
+        }
+    }
+}
+
+pub enum ConfigError {
+    File(FileError),
+    InvalidEnv(InvalidEnv),
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::File(error) => write!(f, "{}", error),
+            ConfigError::InvalidEnv(error) => write!(f, "{}", error),
+            ConfigError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<FileError> for ConfigError {
+    fn from(error: FileError) -> Self {
+        ConfigError::File(error)
+    }
+}
+
+impl From<InvalidEnv> for ConfigError {
+    fn from(error: InvalidEnv) -> Self {
+        ConfigError::InvalidEnv(error)
+    }
+}
+
+impl Config {
+    /// Loads the defaults, then the config file if it exists, then the environment variables.
+    pub fn load() -> Result<Config, ConfigError> {
+        let explicit_path = std::env::var("CONFIG_FILE").ok();
+        let path = PathBuf::from(explicit_path.as_deref().unwrap_or(DEFAULT_CONFIG_FILE));
+
+        // The default config file is optional, but one that was asked for explicitly must exist.
+        let mut config = match load_toml(&path)? {
+            Some(config) => config,
+            None if explicit_path.is_some() => {
+                let error = io::Error::new(io::ErrorKind::NotFound, "no such file");
+                return Err(FileError::Read(path, error).into());
+            }
+            None => Config::default(),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        override_from_env(&mut self.port, "PORT")?;
+        override_optional_from_env(&mut self.base_url, "BASE_URL")?;
+        override_list_from_env(&mut self.extra_domains, "EXTRA_DOMAINS");
+        override_from_env(&mut self.db_url, "DB_URL")?;
+        override_optional_from_env(&mut self.db_read_url, "DB_READ_URL")?;
+        override_from_env(&mut self.db_max_connections, "DB_MAX_CONNECTIONS")?;
+        override_from_env(
+            &mut self.db_acquire_timeout_seconds,
+            "DB_ACQUIRE_TIMEOUT_SECONDS",
+        )?;
+        override_from_env(&mut self.db_idle_timeout_seconds, "DB_IDLE_TIMEOUT_SECONDS")?;
+        override_from_env(&mut self.sqlite_wal, "SQLITE_WAL")?;
+        override_from_env(&mut self.sqlite_busy_timeout_ms, "SQLITE_BUSY_TIMEOUT_MS")?;
+        override_optional_from_env(&mut self.jwt_secret, "JWT_SECRET")?;
+        override_optional_from_env(&mut self.ip_hash_salt, "IP_HASH_SALT")?;
+        override_from_env(&mut self.short_code_strategy, "SHORT_CODE_STRATEGY")?;
+        override_from_env(&mut self.short_code_length, "SHORT_CODE_LENGTH")?;
+        override_from_env(
+            &mut self.min_requested_short_code_length,
+            "MIN_REQUESTED_SHORT_CODE_LENGTH",
+        )?;
+        override_from_env(
+            &mut self.max_requested_short_code_length,
+            "MAX_REQUESTED_SHORT_CODE_LENGTH",
+        )?;
+        override_from_env(&mut self.short_code_alphabet, "SHORT_CODE_ALPHABET")?;
+        override_from_env(
+            &mut self.case_insensitive_short_codes,
+            "CASE_INSENSITIVE_SHORT_CODES",
+        )?;
+        override_from_env(&mut self.rate_limit_burst, "RATE_LIMIT_BURST")?;
+        override_from_env(&mut self.rate_limit_per_minute, "RATE_LIMIT_PER_MINUTE")?;
+        override_from_env(
+            &mut self.rate_limit_trust_forwarded_for,
+            "RATE_LIMIT_TRUST_FORWARDED_FOR",
+        )?;
+        override_from_env(&mut self.reuse_existing_urls, "REUSE_EXISTING_URLS")?;
+        override_from_env(&mut self.max_batch_size, "MAX_BATCH_SIZE")?;
+        override_from_env(
+            &mut self.permanent_redirect_max_age_seconds,
+            "PERMANENT_REDIRECT_MAX_AGE_SECONDS",
+        )?;
+        override_from_env(
+            &mut self.temporary_redirect_max_age_seconds,
+            "TEMPORARY_REDIRECT_MAX_AGE_SECONDS",
+        )?;
+        override_optional_from_env(&mut self.fallback_redirect_url, "FALLBACK_REDIRECT_URL")?;
+        override_from_env(
+            &mut self.idempotency_key_ttl_seconds,
+            "IDEMPOTENCY_KEY_TTL_SECONDS",
+        )?;
+        override_optional_from_env(
+            &mut self.api_key_daily_link_quota,
+            "API_KEY_DAILY_LINK_QUOTA",
+        )?;
+        override_optional_from_env(
+            &mut self.api_key_monthly_link_quota,
+            "API_KEY_MONTHLY_LINK_QUOTA",
+        )?;
+        override_optional_from_env(&mut self.redis_url, "REDIS_URL")?;
+        override_from_env(&mut self.cache_capacity, "CACHE_CAPACITY")?;
+        override_from_env(&mut self.cache_ttl_seconds, "CACHE_TTL_SECONDS")?;
+        override_from_env(
+            &mut self.shutdown_timeout_seconds,
+            "SHUTDOWN_TIMEOUT_SECONDS",
+        )?;
+        override_from_env(&mut self.max_request_body_size, "MAX_REQUEST_BODY_SIZE")?;
+        override_from_env(&mut self.request_timeout_seconds, "REQUEST_TIMEOUT_SECONDS")?;
+        override_from_env(&mut self.max_concurrent_requests, "MAX_CONCURRENT_REQUESTS")?;
+        override_from_env(
+            &mut self.cleanup_interval_seconds,
+            "CLEANUP_INTERVAL_SECONDS",
+        )?;
+        override_from_env(
+            &mut self.expired_link_retention_days,
+            "EXPIRED_LINK_RETENTION_DAYS",
+        )?;
+        override_from_env(&mut self.click_retention_days, "CLICK_RETENTION_DAYS")?;
+        override_optional_from_env(&mut self.tls_cert_path, "TLS_CERT_PATH")?;
+        override_optional_from_env(&mut self.tls_key_path, "TLS_KEY_PATH")?;
+        override_optional_from_env(&mut self.http_redirect_port, "HTTP_REDIRECT_PORT")?;
+        override_optional_from_env(&mut self.grpc_port, "GRPC_PORT")?;
+        override_list_from_env(&mut self.cors_allowed_origins, "CORS_ALLOWED_ORIGINS");
+        override_list_from_env(&mut self.cors_allowed_methods, "CORS_ALLOWED_METHODS");
+        override_optional_from_env(&mut self.url_blocklist_path, "URL_BLOCKLIST_PATH")?;
+        override_optional_from_env(&mut self.geoip_database_path, "GEOIP_DATABASE_PATH")?;
+        override_list_from_env(&mut self.reserved_short_codes, "RESERVED_SHORT_CODES");
+        override_list_from_env(&mut self.webhook_urls, "WEBHOOK_URLS");
+        override_optional_from_env(&mut self.webhook_secret, "WEBHOOK_SECRET")?;
+        override_from_env(&mut self.webhook_max_attempts, "WEBHOOK_MAX_ATTEMPTS")?;
+        override_from_env(&mut self.webhook_timeout_seconds, "WEBHOOK_TIMEOUT_SECONDS")?;
+        override_from_env(&mut self.page_metadata_enabled, "PAGE_METADATA_ENABLED")?;
+        override_from_env(
+            &mut self.page_metadata_timeout_seconds,
+            "PAGE_METADATA_TIMEOUT_SECONDS",
+        )?;
+        override_from_env(
+            &mut self.page_metadata_max_page_size,
+            "PAGE_METADATA_MAX_PAGE_SIZE",
+        )?;
+        override_from_env(&mut self.health_check_enabled, "HEALTH_CHECK_ENABLED")?;
+        override_from_env(
+            &mut self.health_check_interval_seconds,
+            "HEALTH_CHECK_INTERVAL_SECONDS",
+        )?;
+        override_from_env(&mut self.health_check_batch_size, "HEALTH_CHECK_BATCH_SIZE")?;
+        override_from_env(
+            &mut self.health_check_recheck_hours,
+            "HEALTH_CHECK_RECHECK_HOURS",
+        )?;
+        override_from_env(
+            &mut self.health_check_timeout_seconds,
+            "HEALTH_CHECK_TIMEOUT_SECONDS",
+        )?;
+        override_from_env(
+            &mut self.health_check_failure_threshold,
+            "HEALTH_CHECK_FAILURE_THRESHOLD",
+        )?;
+        override_from_env(
+            &mut self.health_check_deactivate_broken,
+            "HEALTH_CHECK_DEACTIVATE_BROKEN",
+        )?;
+        override_from_env(&mut self.web_ui_enabled, "WEB_UI_ENABLED")?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.port == 0 {
+            return Err(ConfigError::Invalid("port must not be 0".to_string()));
+        }
+
+        let base_url = Url::parse(&self.base_url()).map_err(|error| {
+            ConfigError::Invalid(format!("base_url is not a valid URL: {}", error))
+        })?;
+        if base_url.scheme() != "http" && base_url.scheme() != "https" {
+            return Err(ConfigError::Invalid(
+                "base_url must be an http or https URL".to_string(),
+            ));
+        }
+        for domain in &self.extra_domains {
+            // Anything but a plain host and port, like a path, changes when it's parsed.
+            let is_domain = Url::parse(&format!("http://{}", domain))
+                .is_ok_and(|url| url.as_str() == format!("http://{}/", domain.to_lowercase()));
+            if !is_domain {
+                return Err(ConfigError::Invalid(format!(
+                    "extra_domains contains an invalid domain '{}'",
+                    domain
+                )));
+            }
+            if self
+                .domains()
+                .select(domain)
+                .is_ok_and(|domain| domain.is_none())
+            {
+                return Err(ConfigError::Invalid(format!(
+                    "extra_domains contains '{}', the host of base_url",
+                    domain
+                )));
+            }
+        }
+
+        let link_quotas = [
+            self.api_key_daily_link_quota,
+            self.api_key_monthly_link_quota,
+        ];
+        if link_quotas.into_iter().flatten().any(|quota| quota < 0) {
+            return Err(ConfigError::Invalid(
+                "api_key_daily_link_quota and api_key_monthly_link_quota must not be negative"
+                    .to_string(),
+            ));
+        }
+
+        if Backend::from_url(&self.db_url).is_none() {
+            return Err(ConfigError::Invalid(
+                "db_url must start with sqlite: or postgres:".to_string(),
+            ));
+        }
+
+        if let Some(db_read_url) = &self.db_read_url {
+            if Backend::from_url(db_read_url) != Backend::from_url(&self.db_url) {
+                return Err(ConfigError::Invalid(
+                    "db_read_url must use the same database as db_url, sqlite: or postgres:"
+                        .to_string(),
+                ));
+            }
+        }
+
+        if self.db_max_connections == 0 || self.db_acquire_timeout_seconds == 0 {
+            return Err(ConfigError::Invalid(
+                "db_max_connections and db_acquire_timeout_seconds must be at least 1".to_string(),
+            ));
+        }
+
+        if !(MIN_SHORT_CODE_LENGTH..=MAX_SHORT_CODE_LENGTH).contains(&self.short_code_length) {
+            return Err(ConfigError::Invalid(format!(
+                "short_code_length must be between {} and {}",
+                MIN_SHORT_CODE_LENGTH, MAX_SHORT_CODE_LENGTH
+            )));
+        }
+        if self.min_requested_short_code_length < MIN_SHORT_CODE_LENGTH
+            || self.max_requested_short_code_length > MAX_SHORT_CODE_LENGTH
+            || self.min_requested_short_code_length > self.max_requested_short_code_length
+        {
+            return Err(ConfigError::Invalid(format!(
+                "min_requested_short_code_length and max_requested_short_code_length must be \
+                between {} and {}, and the minimum must not exceed the maximum",
+                MIN_SHORT_CODE_LENGTH, MAX_SHORT_CODE_LENGTH
+            )));
+        }
+
+        if self.max_request_body_size == 0
+            || self.request_timeout_seconds == 0
+            || self.max_concurrent_requests == 0
+        {
+            return Err(ConfigError::Invalid(
+                "max_request_body_size, request_timeout_seconds and max_concurrent_requests must \
+                be at least 1"
+                    .to_string(),
+            ));
+        }
+
+        if self.rate_limit_burst == 0 || self.rate_limit_per_minute == 0 {
+            return Err(ConfigError::Invalid(
+                "rate_limit_burst and rate_limit_per_minute must be at least 1".to_string(),
+            ));
+        }
+
+        if self.max_batch_size == 0 {
+            return Err(ConfigError::Invalid(
+                "max_batch_size must be at least 1".to_string(),
+            ));
+        }
+
+        if self.cache_ttl_seconds <= 0 {
+            return Err(ConfigError::Invalid(
+                "cache_ttl_seconds must be at least 1".to_string(),
+            ));
+        }
+
+        if self.tls_cert_path.is_some() != self.tls_key_path.is_some() {
+            return Err(ConfigError::Invalid(
+                "tls_cert_path and tls_key_path must be set together".to_string(),
+            ));
+        }
+
+        if let Some(http_redirect_port) = self.http_redirect_port {
+            if self.tls().is_none() {
+                return Err(ConfigError::Invalid(
+                    "http_redirect_port requires tls_cert_path and tls_key_path".to_string(),
+                ));
+            }
+            if http_redirect_port == 0 || http_redirect_port == self.port {
+                return Err(ConfigError::Invalid(
+                    "http_redirect_port must be a non-zero port other than port".to_string(),
+                ));
+            }
+        }
+
+        if let Some(grpc_port) = self.grpc_port {
+            if grpc_port == 0
+                || grpc_port == self.port
+                || Some(grpc_port) == self.http_redirect_port
+            {
+                return Err(ConfigError::Invalid(
+                    "grpc_port must be a non-zero port other than port and http_redirect_port"
+                        .to_string(),
+                ));
+            }
+        }
+
+        for origin in &self.cors_allowed_origins {
+            if origin != "*" && HeaderValue::from_str(origin).is_err() {
+                return Err(ConfigError::Invalid(format!(
+                    "cors_allowed_origins contains an invalid origin '{}'",
+                    origin
+                )));
+            }
+        }
+        for method in &self.cors_allowed_methods {
+            if Method::from_str(method).is_err() {
+                return Err(ConfigError::Invalid(format!(
+                    "cors_allowed_methods contains an invalid method '{}'",
+                    method
+                )));
+            }
+        }
+
+        if let Some(fallback_redirect_url) = &self.fallback_redirect_url {
+            let is_http_url = Url::parse(fallback_redirect_url)
+                .is_ok_and(|url| url.scheme() == "http" || url.scheme() == "https");
+            if !is_http_url {
+                return Err(ConfigError::Invalid(
+                    "fallback_redirect_url must be an http or https URL".to_string(),
+                ));
+            }
+        }
+
+        for webhook_url in &self.webhook_urls {
+            let is_http_url = Url::parse(webhook_url)
+                .is_ok_and(|url| url.scheme() == "http" || url.scheme() == "https");
+            if !is_http_url {
+                return Err(ConfigError::Invalid(format!(
+                    "webhook_urls contains an invalid URL '{}'",
+                    webhook_url
+                )));
+            }
+        }
+        if !self.webhook_urls.is_empty() && self.webhook_secret.is_none() {
+            return Err(ConfigError::Invalid(
+                "webhook_urls requires webhook_secret".to_string(),
+            ));
+        }
+        if self.webhook_max_attempts == 0 || self.webhook_timeout_seconds == 0 {
+            return Err(ConfigError::Invalid(
+                "webhook_max_attempts and webhook_timeout_seconds must be at least 1".to_string(),
+            ));
+        }
+        if self.page_metadata_timeout_seconds == 0 || self.page_metadata_max_page_size == 0 {
+            return Err(ConfigError::Invalid(
+                "page_metadata_timeout_seconds and page_metadata_max_page_size must be at least 1"
+                    .to_string(),
+            ));
+        }
+        if self.health_check_interval_seconds == 0
+            || self.health_check_batch_size == 0
+            || self.health_check_timeout_seconds == 0
+            || self.health_check_failure_threshold == 0
+        {
+            return Err(ConfigError::Invalid(
+                "health_check_interval_seconds, health_check_batch_size, \
+                health_check_timeout_seconds and health_check_failure_threshold must be at least 1"
+                    .to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Defaults to `http://localhost:<port>`, or `https://` when TLS is enabled.
+    pub fn base_url(&self) -> String {
+        let scheme = if self.tls().is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        self.base_url
+            .clone()
+            .unwrap_or_else(|| format!("{}://localhost:{}", scheme, self.port))
+    }
+
+    pub fn api_options(&self) -> ApiOptions {
+        ApiOptions {
+            trust_forwarded_for: self.rate_limit_trust_forwarded_for,
+            reuse_existing_by_default: self.reuse_existing_urls,
+            max_batch_size: self.max_batch_size,
+            permanent_redirect_max_age: Duration::from_secs(
+                self.permanent_redirect_max_age_seconds,
+            ),
+            temporary_redirect_max_age: Duration::from_secs(
+                self.temporary_redirect_max_age_seconds,
+            ),
+            idempotency_key_ttl: Duration::from_secs(self.idempotency_key_ttl_seconds),
+            fallback_redirect_url: self.fallback_redirect_url.clone(),
+            link_quota: LinkQuota {
+                per_day: self.api_key_daily_link_quota,
+                per_month: self.api_key_monthly_link_quota,
+            },
+        }
+    }
+
+    pub fn domains(&self) -> Domains {
+        Domains::new(
+            Url::parse(&self.base_url()).expect("base_url is validated by Config::load"),
+            self.extra_domains.clone(),
+        )
+    }
+
+    /// Returns `None` if the cleanup task is disabled.
+    pub fn cleanup_settings(&self) -> Option<CleanupSettings> {
+        (self.cleanup_interval_seconds > 0).then(|| CleanupSettings {
+            interval: Duration::from_secs(self.cleanup_interval_seconds),
+            expired_link_retention: chrono::Duration::days(self.expired_link_retention_days.into()),
+            click_retention: (self.click_retention_days > 0)
+                .then(|| chrono::Duration::days(self.click_retention_days.into())),
+        })
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_seconds)
+    }
+
+    pub fn pool_settings(&self) -> PoolSettings {
+        PoolSettings {
+            max_connections: self.db_max_connections,
+            acquire_timeout: Duration::from_secs(self.db_acquire_timeout_seconds),
+            idle_timeout: (self.db_idle_timeout_seconds > 0)
+                .then(|| Duration::from_secs(self.db_idle_timeout_seconds)),
+            sqlite_wal: self.sqlite_wal,
+            sqlite_busy_timeout: Duration::from_millis(self.sqlite_busy_timeout_ms),
+        }
+    }
+
+    pub fn short_code_options(&self) -> ShortCodeOptions {
+        ShortCodeOptions {
+            strategy: self.short_code_strategy,
+            initial_length: self.short_code_length,
+            alphabet: self.short_code_alphabet,
+            case_insensitive: self.case_insensitive_short_codes,
+            reserved: self.reserved_short_codes.clone(),
+            requestable_lengths: self.min_requested_short_code_length
+                ..=self.max_requested_short_code_length,
+        }
+    }
+
+    /// Returns `None` if no webhook is configured.
+    pub fn webhook_settings(&self) -> Option<WebhookSettings> {
+        let secret = self.webhook_secret.clone()?;
+        (!self.webhook_urls.is_empty()).then(|| WebhookSettings {
+            urls: self
+                .webhook_urls
+                .iter()
+                .map(|url| Url::parse(url).expect("webhook_urls are validated by Config::load"))
+                .collect(),
+            secret,
+            max_attempts: self.webhook_max_attempts,
+            timeout: Duration::from_secs(self.webhook_timeout_seconds),
+        })
+    }
+
+    /// Returns `None` if fetching page metadata is turned off.
+    pub fn metadata_settings(&self) -> Option<MetadataSettings> {
+        self.page_metadata_enabled.then(|| MetadataSettings {
+            timeout: Duration::from_secs(self.page_metadata_timeout_seconds),
+            max_page_size: self.page_metadata_max_page_size,
+        })
+    }
+
+    /// Returns `None` if checking destinations is turned off.
+    pub fn health_check_settings(&self) -> Option<HealthCheckSettings> {
+        self.health_check_enabled.then(|| HealthCheckSettings {
+            interval: Duration::from_secs(self.health_check_interval_seconds),
+            batch_size: self.health_check_batch_size,
+            recheck_after: chrono::Duration::hours(self.health_check_recheck_hours.into()),
+            timeout: Duration::from_secs(self.health_check_timeout_seconds),
+            failure_threshold: self.health_check_failure_threshold.into(),
+            deactivate_broken: self.health_check_deactivate_broken,
+        })
+    }
+
+    /// Returns the certificate and key paths if HTTPS is enabled.
+    pub fn tls(&self) -> Option<(&str, &str)> {
+        match (&self.tls_cert_path, &self.tls_key_path) {
+            (Some(cert_path), Some(key_path)) => Some((cert_path, key_path)),
+            _ => None,
+        }
+    }
+}
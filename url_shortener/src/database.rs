@@ -0,0 +1,279 @@
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use sqlx::{
+    any::{install_default_drivers, AnyPoolOptions, AnyRow},
+    migrate::{MigrateDatabase, MigrateError, Migrator},
+    Any, AnyPool, Executor, Row,
+};
+use std::time::Duration;
+
+static SQLITE_MIGRATIONS: Migrator = sqlx::migrate!("./migrations/sqlite");
+static POSTGRES_MIGRATIONS: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Columns that were added to existing tables before the schema was managed by migrations. The
+/// first migration only creates missing tables, so databases from that time may lack them.
+const LEGACY_COLUMNS: &[(&str, &str, &str)] = &[
+    ("short_urls", "expires_at", "TEXT"),
+    ("short_urls", "updated_at", "TEXT"),
+    ("short_urls", "owner_id", "BIGINT REFERENCES users (id)"),
+    (
+        "short_urls",
+        "redirect_type",
+        "TEXT NOT NULL DEFAULT 'temporary'",
+    ),
+    ("short_urls", "max_clicks", "BIGINT"),
+    ("short_urls", "remaining_clicks", "BIGINT"),
+];
+
+/// The database engines the service can run against, selected by the scheme of `DB_URL`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+impl Backend {
+    pub fn from_url(db_url: &str) -> Option<Backend> {
+        let (scheme, _) = db_url.split_once(':')?;
+        match scheme {
+            "sqlite" => Some(Backend::Sqlite),
+            "postgres" | "postgresql" => Some(Backend::Postgres),
+            _ => None,
+        }
+    }
+}
+
+/// How many connections the pool keeps and how SQLite connections are set up.
+pub struct PoolSettings {
+    pub max_connections: u32,
+    /// How long a query waits for a free connection before failing.
+    pub acquire_timeout: Duration,
+    /// Closes connections that have been idle for this long. `None` keeps them open.
+    pub idle_timeout: Option<Duration>,
+    /// Uses SQLite's write-ahead log, which lets readers continue while a write is in progress.
+    pub sqlite_wal: bool,
+    /// How long SQLite waits for a lock held by another connection before reporting that the
+    /// database is locked.
+    pub sqlite_busy_timeout: Duration,
+}
+
+/// A connection pool together with the backend it talks to, for the few statements that differ
+/// between SQLite and Postgres.
+pub struct Database {
+    pool: AnyPool,
+    /// Connections to a read replica, or to the same database as `pool` when there is none.
+    read_pool: AnyPool,
+    has_read_replica: bool,
+    backend: Backend,
+}
+
+impl Database {
+    /// Connects to the database, creating it first if it doesn't exist yet.
+    pub async fn connect(db_url: &str, settings: &PoolSettings) -> Result<Database, sqlx::Error> {
+        let backend = Backend::from_url(db_url).ok_or_else(|| {
+            sqlx::Error::Configuration(
+                format!(
+                    "Unsupported database URL '{}', expected sqlite: or postgres:",
+                    db_url
+                )
+                .into(),
+            )
+        })?;
+
+        install_default_drivers();
+        if !Any::database_exists(db_url).await.unwrap_or(false) {
+            Any::create_database(db_url).await?;
+        }
+        let pool = connect_pool(db_url, backend, settings).await?;
+
+        Ok(Database {
+            read_pool: pool.clone(),
+            pool,
+            has_read_replica: false,
+            backend,
+        })
+    }
+
+    /// Sends the queries that serve redirects to a read replica at `read_db_url`, which has to
+    /// run the same backend. Everything else, including migrations, keeps using the primary.
+    pub async fn with_read_replica(
+        mut self,
+        read_db_url: &str,
+        settings: &PoolSettings,
+    ) -> Result<Database, sqlx::Error> {
+        if Backend::from_url(read_db_url) != Some(self.backend) {
+            return Err(sqlx::Error::Configuration(
+                format!(
+                    "The read replica '{}' must use the same backend as the primary database",
+                    read_db_url
+                )
+                .into(),
+            ));
+        }
+
+        self.read_pool = connect_pool(read_db_url, self.backend, settings).await?;
+        self.has_read_replica = true;
+        Ok(self)
+    }
+
+    /// The primary database, for writes and for reads that must see them right away.
+    pub fn pool(&self) -> &AnyPool {
+        &self.pool
+    }
+
+    /// The read replica, which may lag behind the primary, or the primary if there is none.
+    pub fn read_pool(&self) -> &AnyPool {
+        &self.read_pool
+    }
+
+    pub fn has_read_replica(&self) -> bool {
+        self.has_read_replica
+    }
+
+    /// Checks that the database, and the read replica if there is one, can still be reached.
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        self.pool.execute("SELECT 1").await?;
+        if self.has_read_replica {
+            self.read_pool.execute("SELECT 1").await?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the connections in use to be returned, then closes every connection.
+    pub async fn close(&self) {
+        self.pool.close().await;
+        self.read_pool.close().await;
+    }
+
+    /// Applies the migrations in `migrations/<backend>` that haven't been applied yet.
+    pub async fn migrate(&self) -> Result<(), MigrateError> {
+        let is_legacy_database = self.table_exists("short_urls").await?
+            && !self.table_exists("_sqlx_migrations").await?;
+        if is_legacy_database {
+            for (table, column, definition) in LEGACY_COLUMNS {
+                self.add_column_if_missing(table, column, definition)
+                    .await?;
+            }
+        }
+
+        let migrator = match self.backend {
+            Backend::Sqlite => &SQLITE_MIGRATIONS,
+            Backend::Postgres => &POSTGRES_MIGRATIONS,
+        };
+        migrator.run(&self.pool).await
+    }
+
+    async fn table_exists(&self, table: &str) -> Result<bool, sqlx::Error> {
+        let query = match self.backend {
+            Backend::Sqlite => "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = $1",
+            Backend::Postgres => {
+                "SELECT 1 FROM information_schema.tables
+                WHERE table_schema = current_schema() AND table_name = $1"
+            }
+        };
+        let row = sqlx::query(query)
+            .bind(table)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn add_column_if_missing(
+        &self,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<(), sqlx::Error> {
+        match self.backend {
+            Backend::Sqlite => {
+                let existing_column =
+                    sqlx::query("SELECT 1 FROM pragma_table_info($1) WHERE name = $2")
+                        .bind(table)
+                        .bind(column)
+                        .fetch_optional(&self.pool)
+                        .await?;
+
+                if existing_column.is_none() {
+                    let statement =
+                        format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition);
+                    self.pool.execute(statement.as_str()).await?;
+                }
+            }
+            Backend::Postgres => {
+                let statement = format!(
+                    "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {}",
+                    table, column, definition
+                );
+                self.pool.execute(statement.as_str()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Opens a pool of connections to an existing database.
+async fn connect_pool(
+    db_url: &str,
+    backend: Backend,
+    settings: &PoolSettings,
+) -> Result<AnyPool, sqlx::Error> {
+    let mut pool_options = AnyPoolOptions::new()
+        .max_connections(settings.max_connections)
+        .acquire_timeout(settings.acquire_timeout)
+        .idle_timeout(settings.idle_timeout);
+    if backend == Backend::Sqlite {
+        // The Any driver can't set SQLite pragmas itself, so every new connection runs them.
+        let pragmas = format!(
+            "PRAGMA journal_mode = {}; PRAGMA busy_timeout = {};",
+            if settings.sqlite_wal { "WAL" } else { "DELETE" },
+            settings.sqlite_busy_timeout.as_millis()
+        );
+        pool_options = pool_options.after_connect(move |connection, _| {
+            let pragmas = pragmas.clone();
+            Box::pin(async move {
+                connection.execute(pragmas.as_str()).await?;
+                Ok(())
+            })
+        });
+    }
+    if backend == Backend::Sqlite && db_url.contains(":memory:") {
+        // Every connection to an in-memory SQLite database gets a database of its own, so all
+        // queries have to share a single connection that is never closed.
+        pool_options = pool_options
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None);
+    }
+    pool_options.connect(db_url).await
+}
+
+impl Clone for Database {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            read_pool: self.read_pool.clone(),
+            has_read_replica: self.has_read_replica,
+            backend: self.backend,
+        }
+    }
+}
+
+/// Timestamps are stored as RFC 3339 text, which both backends can store, compare and sort.
+pub fn encode_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// Reads a timestamp column. Besides RFC 3339, this also accepts SQLite's `CURRENT_TIMESTAMP`
+/// format used by databases created by older versions of the service.
+pub fn get_timestamp(row: &AnyRow, column: &str) -> Option<DateTime<Utc>> {
+    let value = row.get::<Option<String>, &str>(column)?;
+
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(&value) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+    if let Ok(timestamp) = DateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S%.f%:z") {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|timestamp| timestamp.and_utc())
+}
@@ -0,0 +1,82 @@
+use crate::url_shortener::Error;
+use url::Url;
+
+/// The domains short URLs are served on: the host of the base URL, and the configured extra
+/// domains. Every short URL belongs to one of them and only resolves when it is requested there.
+/// Short URLs on the base URL's host don't store a domain, so they are also found on hosts that
+/// aren't configured, like a bare IP address.
+pub struct Domains {
+    base_url: Url,
+    /// `host` or `host:port` names in lower case, the way they appear in a `Host` header.
+    extra: Vec<String>,
+}
+
+impl Domains {
+    pub fn new(base_url: Url, extra: Vec<String>) -> Self {
+        Domains {
+            base_url,
+            extra: extra.iter().map(|domain| domain.to_lowercase()).collect(),
+        }
+    }
+
+    /// The extra domain named by a request's `Host` header, or `None` for any other host.
+    pub fn resolve(&self, host: Option<&str>) -> Option<String> {
+        let host = host?.to_lowercase();
+        self.extra.contains(&host).then_some(host)
+    }
+
+    /// Checks a domain a client asked for explicitly. Returns `None` for the base URL's host.
+    pub fn select(&self, domain: &str) -> Result<Option<String>, Error> {
+        let domain = domain.to_lowercase();
+        if domain == self.base_host() {
+            Ok(None)
+        } else if self.extra.contains(&domain) {
+            Ok(Some(domain))
+        } else {
+            Err(Error::InvalidRequest(format!(
+                "'{}' is not a domain of this service",
+                domain
+            )))
+        }
+    }
+
+    pub fn is_extra(&self, domain: &str) -> bool {
+        self.extra.iter().any(|extra| extra == domain)
+    }
+
+    /// Extra domains use the scheme of the base URL, but not its path.
+    pub fn short_url(&self, domain: Option<&str>, short_code: &str) -> String {
+        match domain {
+            Some(domain) => format!("{}://{}/{}", self.base_url.scheme(), domain, short_code),
+            None => format!(
+                "{}/{}",
+                self.base_url.as_str().trim_end_matches('/'),
+                short_code
+            ),
+        }
+    }
+
+    /// Whether `url` points at one of the domains, so a short URL for it would redirect to the
+    /// shortener itself.
+    pub fn serves(&self, url: &Url) -> bool {
+        let is_base_url = url.host_str() == self.base_url.host_str()
+            && url.port_or_known_default() == self.base_url.port_or_known_default();
+        is_base_url
+            || self.extra.iter().any(|domain| {
+                Url::parse(&format!("{}://{}", url.scheme(), domain)).is_ok_and(|extra| {
+                    extra.host_str() == url.host_str()
+                        && extra.port_or_known_default() == url.port_or_known_default()
+                })
+            })
+    }
+
+    /// The base URL's host as it appears in a `Host` header, with the port only when it isn't the
+    /// scheme's default.
+    fn base_host(&self) -> String {
+        let host = self.base_url.host_str().unwrap_or_default();
+        match self.base_url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use maxminddb::{geoip2, MaxMindDbError, Reader};
+use std::{net::IpAddr, path::Path};
+
+/// Resolves the country of a client IP address for geo-targeted redirects.
+pub trait GeoIpLookup: Send + Sync {
+    /// Returns the ISO 3166-1 alpha-2 code of the country, e.g. `DE`.
+    fn country(&self, ip: IpAddr) -> Option<String>;
+}
+
+/// Knows no country, so every visitor gets the default destination.
+pub struct NoGeoIp;
+
+impl GeoIpLookup for NoGeoIp {
+    fn country(&self, _ip: IpAddr) -> Option<String> {
+        None
+    }
+}
+
+/// Looks countries up in a MaxMind GeoIP2 or GeoLite2 Country or City database.
+pub struct MaxMindGeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl MaxMindGeoIp {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MaxMindDbError> {
+        Ok(MaxMindGeoIp {
+            reader: Reader::open_readfile(path)?,
+        })
+    }
+
+    pub fn database_type(&self) -> &str {
+        &self.reader.metadata().database_type
+    }
+}
+
+impl GeoIpLookup for MaxMindGeoIp {
+    fn country(&self, ip: IpAddr) -> Option<String> {
+        let country = self
+            .reader
+            .lookup(ip)
+            .ok()?
+            .decode::<geoip2::Country>()
+            .ok()??;
+        country.country.iso_code.map(str::to_string)
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{
+    api::{expires_after, identify_caller, release_links, reserve_links, Caller, API_KEY_HEADER},
+    api_keys::{ApiKeyStore, LinkQuota},
+    audit::{AuditAction, AuditLog},
+    domains::Domains,
+    url_shortener::{Error, NewShortUrl, RedirectType, UrlShortener},
+    users::UserStore,
+};
+use serde_json::json;
+use std::{future::Future, net::SocketAddr, sync::Arc};
+use tonic::{metadata::MetadataMap, transport::Server, Code, Request, Response, Status};
+use tracing::error;
+
+mod proto {
+    tonic::include_proto!("url_shortener");
+}
+
+use proto::url_shortener_server::UrlShortenerServer;
+use proto::{
+    DeleteRequest, DeleteResponse, LookupRequest, LookupResponse, ShortenRequest, ShortenResponse,
+};
+
+/// The gRPC counterpart of the REST API's `/api/v1/shorten` and `/api/v1/links/:short_code`,
+/// backed by the same [`UrlShortener`].
+pub struct GrpcService {
+    domains: Arc<Domains>,
+    url_shortener: UrlShortener,
+    api_keys: ApiKeyStore,
+    users: UserStore,
+    reuse_existing_by_default: bool,
+    link_quota: LinkQuota,
+    audit_log: AuditLog,
+}
+
+impl GrpcService {
+    pub fn new(
+        domains: Arc<Domains>,
+        url_shortener: UrlShortener,
+        api_keys: ApiKeyStore,
+        users: UserStore,
+        reuse_existing_by_default: bool,
+        link_quota: LinkQuota,
+        audit_log: AuditLog,
+    ) -> Self {
+        GrpcService {
+            domains,
+            url_shortener,
+            api_keys,
+            users,
+            reuse_existing_by_default,
+            link_quota,
+            audit_log,
+        }
+    }
+
+    /// Accepts the same credentials as the REST API, sent as `x-api-key` or `authorization`
+    /// metadata.
+    async fn authenticate(&self, metadata: &MetadataMap) -> Result<Caller, Status> {
+        let api_key = metadata
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok());
+        let authorization = metadata
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+
+        identify_caller(&self.api_keys, &self.users, api_key, authorization)
+            .await
+            .map_err(to_status)
+    }
+}
+
+#[tonic::async_trait]
+impl proto::url_shortener_server::UrlShortener for GrpcService {
+    async fn shorten(
+        &self,
+        request: Request<ShortenRequest>,
+    ) -> Result<Response<ShortenResponse>, Status> {
+        let caller = self.authenticate(request.metadata()).await?;
+        let request = request.into_inner();
+
+        let new_short_url = NewShortUrl {
+            original_url: request.original_url,
+            custom_code: request.custom_code,
+            expires_at: request
+                .ttl_seconds
+                .map(expires_after)
+                .transpose()
+                .map_err(to_status)?,
+            owner_id: caller.owner_id(),
+            redirect_type: if request.permanent {
+                RedirectType::Permanent
+            } else {
+                RedirectType::Temporary
+            },
+            max_clicks: request.max_clicks,
+            reuse_existing: request
+                .reuse_existing
+                .unwrap_or(self.reuse_existing_by_default),
+            geo_targets: Default::default(),
+            variants: Vec::new(),
+            device_targets: Default::default(),
+            tracking_params: Default::default(),
+            short_code_length: None,
+            namespace: None,
+            domain: request
+                .domain
+                .map(|domain| self.domains.select(&domain))
+                .transpose()
+                .map_err(to_status)?
+                .flatten(),
+        };
+        reserve_links(&self.api_keys, self.link_quota, caller, 1)
+            .await
+            .map_err(to_status)?;
+        let short_code = self.url_shortener.register(&new_short_url).await;
+        if short_code.is_err() {
+            release_links(&self.api_keys, caller, 1).await;
+        }
+        let short_code = short_code.map_err(to_status)?;
+        self.audit_log
+            .record(
+                &caller.key(),
+                AuditAction::CreateLink,
+                &short_code,
+                json!({ "original_url": new_short_url.original_url }),
+            )
+            .await;
+
+        Ok(Response::new(ShortenResponse {
+            short_url: self
+                .domains
+                .short_url(new_short_url.domain.as_deref(), &short_code),
+            short_code,
+        }))
+    }
+
+    async fn lookup(
+        &self,
+        request: Request<LookupRequest>,
+    ) -> Result<Response<LookupResponse>, Status> {
+        self.authenticate(request.metadata()).await?;
+
+        let original_url = self
+            .url_shortener
+            .lookup(&request.get_ref().short_code)
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(LookupResponse { original_url }))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        let caller = self.authenticate(request.metadata()).await?;
+
+        let short_code = &request.get_ref().short_code;
+        self.url_shortener
+            .delete(short_code, caller.link_scope())
+            .await
+            .map_err(to_status)?;
+        self.audit_log
+            .record(
+                &caller.key(),
+                AuditAction::DeleteLink,
+                short_code,
+                json!({}),
+            )
+            .await;
+        Ok(Response::new(DeleteResponse {}))
+    }
+}
+
+/// Serves the gRPC API on its own port until `shutdown` resolves.
+pub async fn serve(
+    service: GrpcService,
+    address: SocketAddr,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .add_service(UrlShortenerServer::new(service))
+        .serve_with_shutdown(address, shutdown)
+        .await
+}
+
+/// The gRPC equivalent of [`Error::status_code`].
+fn to_status(error: Error) -> Status {
+    let code = match &error {
+        Error::NotFound => Code::NotFound,
+        Error::InvalidRequest(_)
+        | Error::InvalidUrl(_)
+        | Error::UnsafeUrl(_)
+        | Error::InvalidShortCode(_)
+        | Error::InvalidExpiration(_)
+        | Error::InvalidUser(_) => Code::InvalidArgument,
+        Error::Unauthorized(_) => Code::Unauthenticated,
+        Error::Forbidden(_) => Code::PermissionDenied,
+        Error::Conflict(_) => Code::AlreadyExists,
+        Error::RateLimited(_) | Error::QuotaExceeded(..) => Code::ResourceExhausted,
+        Error::Expired | Error::Deactivated => Code::FailedPrecondition,
+        Error::Internal(_) => Code::Internal,
+    };
+    if let Error::Internal(error) = &error {
+        error!("Internal error: {}", error);
+    }
+    Status::new(code, error.to_string())
+}
@@ -0,0 +1,270 @@
+use crate::audit::{AuditAction, AuditLog};
+use crate::database::{encode_timestamp, get_timestamp, Database};
+use crate::metadata::{is_public, USER_AGENT};
+use crate::monitoring;
+use crate::url_shortener::{LinkScope, UrlShortener};
+use chrono::{Duration, Utc};
+use futures_util::StreamExt;
+use reqwest::{redirect, StatusCode};
+use serde_json::json;
+use sqlx::{any::AnyRow, Row};
+use std::time::Instant;
+use tracing::{error, info, warn};
+use url::Url;
+
+/// The actor of short URLs that are deactivated because their destination is broken.
+pub const HEALTH_CHECK_ACTOR: &str = "health_check";
+const MAX_CONCURRENT_CHECKS: usize = 8;
+const MAX_REDIRECTS: usize = 5;
+/// Longer error messages are cut off before they are stored.
+const MAX_ERROR_LENGTH: usize = 200;
+
+pub struct HealthCheckSettings {
+    /// How often a batch of destinations is checked.
+    pub interval: std::time::Duration,
+    pub batch_size: usize,
+    /// How long a destination isn't checked again after it was.
+    pub recheck_after: Duration,
+    pub timeout: std::time::Duration,
+    /// Failed checks in a row after which a destination counts as broken.
+    pub failure_threshold: i64,
+    /// Deactivate short URLs once their destination counts as broken.
+    pub deactivate_broken: bool,
+}
+
+/// How a destination answered a check.
+struct CheckResult {
+    /// `None` when it couldn't be reached at all.
+    status: Option<u16>,
+    latency_ms: Option<i64>,
+    error: Option<String>,
+}
+
+impl CheckResult {
+    /// Missing pages and server errors fail like unreachable hosts do. Any other status, even
+    /// `401`, `403` or `429`, means that something still answers there.
+    fn failed(&self) -> bool {
+        match self.status {
+            Some(status) => status == 404 || status == 410 || status >= 500,
+            None => true,
+        }
+    }
+}
+
+/// Requests the destinations of active short URLs, records how they answered, and flags the ones
+/// that keep failing as broken. Only the main destination of a short URL is checked, not its
+/// targets or variants.
+pub struct HealthChecker {
+    database: Database,
+    url_shortener: UrlShortener,
+    audit_log: AuditLog,
+    settings: HealthCheckSettings,
+    client: reqwest::Client,
+}
+
+impl HealthChecker {
+    pub fn new(
+        database: Database,
+        url_shortener: UrlShortener,
+        audit_log: AuditLog,
+        settings: HealthCheckSettings,
+    ) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(settings.timeout)
+            .user_agent(USER_AGENT)
+            // Every hop has to be public, not just the first one.
+            .redirect(redirect::Policy::custom(|attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if !is_public(attempt.url()) {
+                    attempt.stop()
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()
+            .expect("Failed to build the health check HTTP client");
+
+        HealthChecker {
+            database,
+            url_shortener,
+            audit_log,
+            settings,
+            client,
+        }
+    }
+
+    /// Checks the destinations that are due, those never checked first, and returns how many
+    /// were checked.
+    pub async fn check_due(&self) -> Result<usize, sqlx::Error> {
+        let now = Utc::now();
+        let rows = sqlx::query(
+            "SELECT id, short_code, original_url, health_failures, broken_since FROM short_urls
+            WHERE deleted_at IS NULL AND (expires_at IS NULL OR expires_at > $1)
+                AND (remaining_clicks IS NULL OR remaining_clicks > 0)
+                AND (health_checked_at IS NULL OR health_checked_at < $2)
+            ORDER BY COALESCE(health_checked_at, ''), id
+            LIMIT $3",
+        )
+        .bind(encode_timestamp(now))
+        .bind(encode_timestamp(now - self.settings.recheck_after))
+        .bind(self.settings.batch_size as i64)
+        .fetch_all(self.database.pool())
+        .await?;
+
+        let checked = rows.len();
+        futures_util::stream::iter(rows)
+            .for_each_concurrent(MAX_CONCURRENT_CHECKS, |row| async move {
+                if let Err(error) = self.check_link(&row).await {
+                    error!(
+                        "Error saving the health check of {}: {}",
+                        row.get::<String, &str>("short_code"),
+                        error
+                    );
+                }
+            })
+            .await;
+        Ok(checked)
+    }
+
+    async fn check_link(&self, row: &AnyRow) -> Result<(), sqlx::Error> {
+        let short_url_id = row.get::<i64, &str>("id");
+        let short_code = row.get::<String, &str>("short_code");
+        let now = encode_timestamp(Utc::now());
+
+        let url = Url::parse(&row.get::<String, &str>("original_url"))
+            .ok()
+            .filter(is_public);
+        let Some(url) = url else {
+            // Destinations on private networks may well work, they just can't be checked from
+            // here, so they don't count as failures.
+            monitoring::record_health_check("skipped");
+            sqlx::query(
+                "UPDATE short_urls SET health_checked_at = $1, health_status = NULL,
+                health_latency_ms = NULL, health_error = $2 WHERE id = $3",
+            )
+            .bind(&now)
+            .bind("Not checked, the destination isn't a public http(s) URL")
+            .bind(short_url_id)
+            .execute(self.database.pool())
+            .await?;
+            return Ok(());
+        };
+
+        let result = self.check(url).await;
+        let was_broken = get_timestamp(row, "broken_since").is_some();
+        let (failures, broken_since) = if result.failed() {
+            let failures = row.get::<i64, &str>("health_failures") + 1;
+            let broken_since = (failures >= self.settings.failure_threshold).then(|| {
+                row.get::<Option<String>, &str>("broken_since")
+                    .unwrap_or_else(|| now.clone())
+            });
+            (failures, broken_since)
+        } else {
+            (0, None)
+        };
+        monitoring::record_health_check(if result.failed() { "failed" } else { "ok" });
+
+        sqlx::query(
+            "UPDATE short_urls SET health_checked_at = $1, health_status = $2,
+            health_latency_ms = $3, health_error = $4, health_failures = $5, broken_since = $6
+            WHERE id = $7",
+        )
+        .bind(&now)
+        .bind(result.status.map(i64::from))
+        .bind(result.latency_ms)
+        .bind(result.error.as_deref())
+        .bind(failures)
+        .bind(broken_since.as_deref())
+        .bind(short_url_id)
+        .execute(self.database.pool())
+        .await?;
+
+        // A short URL that was reactivated while broken stays active.
+        if broken_since.is_some() && !was_broken {
+            warn!(
+                "The destination of {} is broken after {} failed checks",
+                short_code, failures
+            );
+            if self.settings.deactivate_broken {
+                self.deactivate(&short_code, &result).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn check(&self, url: Url) -> CheckResult {
+        let started_at = Instant::now();
+        let mut response = self.client.head(url.clone()).send().await;
+        // Some servers don't implement HEAD. They get a GET instead, whose body isn't read.
+        let head_unsupported = response.as_ref().is_ok_and(|response| {
+            matches!(
+                response.status(),
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+            )
+        });
+        if head_unsupported {
+            response = self.client.get(url).send().await;
+        }
+        let latency_ms = started_at.elapsed().as_millis() as i64;
+
+        match response {
+            Ok(response) => CheckResult {
+                status: Some(response.status().as_u16()),
+                latency_ms: Some(latency_ms),
+                error: None,
+            },
+            Err(error) => CheckResult {
+                status: None,
+                latency_ms: None,
+                error: Some(describe_error(&error)),
+            },
+        }
+    }
+
+    async fn deactivate(&self, short_code: &str, result: &CheckResult) {
+        match self.url_shortener.delete(short_code, LinkScope::All).await {
+            Ok(()) => {
+                info!("Deactivated {}, whose destination is broken", short_code);
+                self.audit_log
+                    .record(
+                        HEALTH_CHECK_ACTOR,
+                        AuditAction::DeleteLink,
+                        short_code,
+                        json!({
+                            "reason": "broken_destination",
+                            "status_code": result.status,
+                            "error": result.error,
+                        }),
+                    )
+                    .await;
+            }
+            Err(error) => error!("Failed to deactivate {}: {}", short_code, error),
+        }
+    }
+}
+
+/// Checks a batch of due destinations every `interval` in the background.
+pub fn spawn(health_checker: HealthChecker) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(health_checker.settings.interval);
+        loop {
+            interval.tick().await;
+            if let Err(error) = health_checker.check_due().await {
+                error!("Health check failed: {}", error);
+            }
+        }
+    });
+}
+
+/// reqwest's own message only says that the request failed, its sources say why.
+fn describe_error(error: &reqwest::Error) -> String {
+    let mut description = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        description.push_str(": ");
+        description.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    description.chars().take(MAX_ERROR_LENGTH).collect()
+}
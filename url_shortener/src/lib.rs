@@ -0,0 +1,27 @@
+//! The URL shortener's business logic and APIs, shared by the server in `main.rs`, the admin CLI
+//! in `bin/admin.rs` and the integration tests.
+
+pub mod api;
+pub mod api_keys;
+pub mod audit;
+pub mod cache;
+pub mod cleanup;
+pub mod clicks;
+pub mod config;
+pub mod database;
+pub mod domains;
+pub mod geoip;
+pub mod grpc;
+pub mod health_check;
+pub mod link_csv;
+pub mod metadata;
+pub mod monitoring;
+pub mod preview;
+pub mod qr;
+pub mod rate_limit;
+pub mod safety;
+pub mod tls;
+pub mod url_shortener;
+pub mod users;
+pub mod web_ui;
+pub mod webhooks;
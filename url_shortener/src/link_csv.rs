@@ -0,0 +1,149 @@
+use crate::url_shortener::{Error, ExportedClick, ImportFailure, ImportedShortUrl, LinkSummary};
+
+const SHORT_CODE_COLUMN: &str = "short_code";
+const ORIGINAL_URL_COLUMN: &str = "original_url";
+const DOMAIN_COLUMN: &str = "domain";
+const EXPORT_COLUMNS: [&str; 9] = [
+    SHORT_CODE_COLUMN,
+    ORIGINAL_URL_COLUMN,
+    "created_at",
+    "expires_at",
+    "redirect_type",
+    "max_clicks",
+    "deleted_at",
+    "click_count",
+    DOMAIN_COLUMN,
+];
+const CLICK_EXPORT_COLUMNS: [&str; 5] = [
+    "clicked_at",
+    "variant_id",
+    "referrer",
+    "user_agent",
+    "ip_hash",
+];
+
+/// Reads `short_code,original_url` rows. A header row naming both columns is optional, and lets
+/// them appear in any order among other columns, like in the files [`write_links`] produces.
+/// Rows that lack one of the columns are returned as failures. With a header, a `domain` column
+/// puts short URLs on one of the service's extra domains.
+pub fn parse(data: &[u8]) -> Result<(Vec<ImportedShortUrl>, Vec<ImportFailure>), Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    let mut imported_short_urls = Vec::new();
+    let mut failures = Vec::new();
+    let mut columns = (0, 1);
+    let mut domain_column = None;
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|error| {
+            Error::InvalidRequest(format!("The file isn't valid CSV: {}", error))
+        })?;
+        let line = record.position().map_or(0, |position| position.line());
+
+        if index == 0 {
+            let position = |name: &str| {
+                record
+                    .iter()
+                    .position(|field| field.eq_ignore_ascii_case(name))
+            };
+            if let (Some(short_code), Some(original_url)) =
+                (position(SHORT_CODE_COLUMN), position(ORIGINAL_URL_COLUMN))
+            {
+                columns = (short_code, original_url);
+                domain_column = position(DOMAIN_COLUMN);
+                continue;
+            }
+        }
+
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        match (record.get(columns.0), record.get(columns.1)) {
+            (Some(short_code), Some(original_url)) => imported_short_urls.push(ImportedShortUrl {
+                line,
+                short_code: short_code.to_string(),
+                original_url: original_url.to_string(),
+                domain: domain_column
+                    .and_then(|column| record.get(column))
+                    .map(str::to_string),
+            }),
+            (short_code, _) => {
+                let error = Error::InvalidRequest(
+                    "Every row needs a short code and an original URL".to_string(),
+                );
+                failures.push(ImportFailure {
+                    line,
+                    short_code: short_code.unwrap_or_default().to_string(),
+                    code: error.code(),
+                    error: error.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok((imported_short_urls, failures))
+}
+
+pub fn write_header() -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(EXPORT_COLUMNS)
+        .expect("writing to a Vec can't fail");
+    writer.into_inner().expect("writing to a Vec can't fail")
+}
+
+pub fn write_links(links: &[LinkSummary]) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for link in links {
+        writer
+            .write_record([
+                link.short_code.clone(),
+                link.original_url.clone(),
+                link.created_at.to_rfc3339(),
+                link.expires_at
+                    .map(|expires_at| expires_at.to_rfc3339())
+                    .unwrap_or_default(),
+                link.redirect_type.as_str().to_string(),
+                link.max_clicks
+                    .map(|max_clicks| max_clicks.to_string())
+                    .unwrap_or_default(),
+                link.deleted_at
+                    .map(|deleted_at| deleted_at.to_rfc3339())
+                    .unwrap_or_default(),
+                link.click_count.to_string(),
+                link.domain.clone().unwrap_or_default(),
+            ])
+            .expect("writing to a Vec can't fail");
+    }
+    writer.into_inner().expect("writing to a Vec can't fail")
+}
+
+pub fn write_click_header() -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(CLICK_EXPORT_COLUMNS)
+        .expect("writing to a Vec can't fail");
+    writer.into_inner().expect("writing to a Vec can't fail")
+}
+
+pub fn write_clicks(clicks: &[ExportedClick]) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for click in clicks {
+        writer
+            .write_record([
+                click.clicked_at.to_rfc3339(),
+                click
+                    .variant_id
+                    .map(|variant_id| variant_id.to_string())
+                    .unwrap_or_default(),
+                click.referrer.clone().unwrap_or_default(),
+                click.user_agent.clone().unwrap_or_default(),
+                click.ip_hash.clone().unwrap_or_default(),
+            ])
+            .expect("writing to a Vec can't fail");
+    }
+    writer.into_inner().expect("writing to a Vec can't fail")
+}
@@ -1,125 +1,278 @@
-mod url_shortener;
-
-use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::{IntoResponse, Redirect},
-    routing::{get, post},
-    Json, Router,
+use rand::{distributions::Alphanumeric, Rng};
+use std::{
+    future::{Future, IntoFuture},
+    net::SocketAddr,
+    num::NonZeroUsize,
+    pin::Pin,
+    sync::Arc,
+};
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+use url_shortener::{
+    api::{self, AppState},
+    api_keys::ApiKeyStore,
+    audit::AuditLog,
+    cache::{MemoryCache, RedisCache, ShortUrlCache},
+    cleanup,
+    clicks::ClickRecorder,
+    config::Config,
+    database::Database,
+    geoip::{GeoIpLookup, MaxMindGeoIp, NoGeoIp},
+    grpc::{self, GrpcService},
+    health_check::{self, HealthChecker},
+    metadata::MetadataFetcher,
+    monitoring::{self, MonitoringState},
+    rate_limit::RateLimiter,
+    safety::{BlocklistChecker, NoopChecker, UrlSafetyChecker},
+    tls,
+    url_shortener::UrlShortener,
+    users::UserStore,
+    webhooks::Webhooks,
 };
-use serde::{Deserialize, Serialize};
-use sqlx::{migrate::MigrateDatabase, sqlite::SqlitePoolOptions, Pool, Sqlite};
-use url_shortener::UrlShortener;
-
-struct AppState {
-    base_url: String,
-    url_shortener: UrlShortener,
-}
-
-impl AppState {
-    fn new(base_url: String, url_shortener: UrlShortener) -> Self {
-        AppState {
-            base_url,
-            url_shortener,
-        }
-    }
-}
 
-impl Clone for AppState {
-    fn clone(&self) -> Self {
-        Self {
-            base_url: self.base_url.clone(),
-            url_shortener: self.url_shortener.clone(),
-        }
-    }
-}
+/// How long to wait on shutdown for queued clicks to be written.
+const CLICK_FLUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() {
-    let db_url = std::env::var("DB_URL").unwrap_or("sqlite://:memory:".to_string());
-    let port = std::env::var("PORT").unwrap_or("3000".to_string());
-    let base_url = std::env::var("BASE_URL").unwrap_or(format!("http://localhost:{}", port));
+    monitoring::init_tracing();
 
-    let pool = setup_database(&db_url).await.unwrap();
+    let config = Config::load().unwrap_or_else(|error| {
+        error!("Invalid configuration: {}", error);
+        std::process::exit(1);
+    });
 
-    let url_shortener = UrlShortener::new(pool);
-    url_shortener.auto_migrate().await.unwrap();
+    let jwt_secret = config.jwt_secret.clone().unwrap_or_else(|| {
+        warn!("JWT_SECRET is not set, user tokens will be invalidated on restart");
+        generate_secret()
+    });
+    let ip_hash_salt = config.ip_hash_salt.clone().unwrap_or_else(|| {
+        warn!("IP_HASH_SALT is not set, returning visitors won't be recognized after a restart");
+        generate_secret()
+    });
+    let options = config.api_options();
+    let shutdown_timeout = std::time::Duration::from_secs(config.shutdown_timeout_seconds);
+    let domains = Arc::new(config.domains());
 
-    let app_state = AppState::new(base_url, url_shortener);
+    let metrics_handle = monitoring::install_recorder();
+    let database = Database::connect(&config.db_url, &config.pool_settings())
+        .await
+        .unwrap();
+    database.migrate().await.unwrap_or_else(|error| {
+        error!("Cannot migrate the database: {}", error);
+        std::process::exit(1);
+    });
+    let database = match &config.db_read_url {
+        Some(db_read_url) => database
+            .with_read_replica(db_read_url, &config.pool_settings())
+            .await
+            .unwrap_or_else(|error| {
+                error!("Cannot connect to the read replica: {}", error);
+                std::process::exit(1);
+            }),
+        None => database,
+    };
 
-    let app = Router::new()
-        .route("/shorten", post(shorten_handler))
-        .route("/lookup", get(lookup_handler))
-        .route("/:short_code", get(redirect_handler))
-        .with_state(app_state);
+    let users = UserStore::new(database.clone(), jwt_secret);
 
-    let bind_address = format!("0.0.0.0:{}", port);
-    let listener = tokio::net::TcpListener::bind(&bind_address).await.unwrap();
-    println!("URL Shortener is bound to {}", bind_address);
-    axum::serve(listener, app).await.unwrap();
-}
+    let cache_ttl_seconds = config.cache_ttl_seconds;
+    let cache = match &config.redis_url {
+        Some(redis_url) => Some(ShortUrlCache::redis(
+            RedisCache::connect(redis_url, cache_ttl_seconds)
+                .await
+                .expect("Failed to connect to Redis"),
+        )),
+        None => NonZeroUsize::new(config.cache_capacity)
+            .map(|capacity| ShortUrlCache::memory(MemoryCache::new(capacity, cache_ttl_seconds))),
+    };
 
-async fn setup_database(db_url: &str) -> Result<Pool<Sqlite>, sqlx::Error> {
-    if !Sqlite::database_exists(db_url).await.unwrap_or(false) {
-        Sqlite::create_database(db_url).await?;
-    }
-    SqlitePoolOptions::new().connect(db_url).await
-}
+    let safety_checker: Arc<dyn UrlSafetyChecker> = match &config.url_blocklist_path {
+        Some(path) => {
+            let blocklist = BlocklistChecker::load(path).unwrap_or_else(|error| {
+                error!("Cannot read URL blocklist {}: {}", path, error);
+                std::process::exit(1);
+            });
+            info!(
+                "Loaded {} blocked hosts from {}",
+                blocklist.host_count(),
+                path
+            );
+            Arc::new(blocklist)
+        }
+        None => Arc::new(NoopChecker),
+    };
 
-impl IntoResponse for url_shortener::Error {
-    fn into_response(self) -> axum::response::Response {
-        match self {
-            url_shortener::Error::NotFound => {
-                (StatusCode::NOT_FOUND, "Short URL not found").into_response()
-            }
-            url_shortener::Error::Internal(error) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response()
-            }
+    let geoip: Arc<dyn GeoIpLookup> = match &config.geoip_database_path {
+        Some(path) => {
+            let geoip = MaxMindGeoIp::open(path).unwrap_or_else(|error| {
+                error!("Cannot read GeoIP database {}: {}", path, error);
+                std::process::exit(1);
+            });
+            info!("Loaded {} database from {}", geoip.database_type(), path);
+            Arc::new(geoip)
+        }
+        None => Arc::new(NoGeoIp),
+    };
+
+    let webhooks = match config.webhook_settings() {
+        Some(webhook_settings) => {
+            info!("Sending webhooks to {} URLs", webhook_settings.urls.len());
+            Webhooks::spawn(database.clone(), webhook_settings)
         }
+        None => Webhooks::disabled(),
+    };
+
+    let url_shortener = UrlShortener::new(
+        database.clone(),
+        domains.clone(),
+        cache,
+        config.short_code_options(),
+        safety_checker,
+        webhooks,
+        geoip,
+    );
+    let url_shortener = match config.metadata_settings() {
+        Some(metadata_settings) => url_shortener
+            .with_metadata_fetcher(MetadataFetcher::spawn(database.clone(), metadata_settings)),
+        None => url_shortener,
+    };
+
+    if let Some(cleanup_settings) = config.cleanup_settings() {
+        cleanup::spawn(url_shortener.clone(), cleanup_settings);
     }
-}
 
-#[derive(Deserialize)]
-struct ShortenRequest {
-    original_url: String,
-}
+    let (click_recorder, click_worker) = ClickRecorder::spawn(url_shortener.clone(), ip_hash_salt);
 
-#[derive(Serialize)]
-struct ShortenResponse {
-    short_code: String,
-    short_url: String,
-}
+    let monitoring_state =
+        MonitoringState::new(metrics_handle, database.clone(), url_shortener.clone());
+
+    let api_keys = ApiKeyStore::new(database.clone());
+    let audit_log = AuditLog::new(database.clone());
+    if let Some(health_check_settings) = config.health_check_settings() {
+        health_check::spawn(HealthChecker::new(
+            database.clone(),
+            url_shortener.clone(),
+            audit_log.clone(),
+            health_check_settings,
+        ));
+    }
+    if api_keys.count_active().await.unwrap() == 0 {
+        let api_key = api_keys.create("initial").await.unwrap();
+        info!(
+            "No API key found, created an initial API key: {}",
+            api_key.key
+        );
+    }
+
+    let grpc_service = GrpcService::new(
+        domains.clone(),
+        url_shortener.clone(),
+        api_keys.clone(),
+        users.clone(),
+        options.reuse_existing_by_default,
+        options.link_quota,
+        audit_log.clone(),
+    );
 
-async fn shorten_handler(
-    State(AppState {
-        base_url: host,
+    let rate_limiter = RateLimiter::new(config.rate_limit_burst, config.rate_limit_per_minute);
+    let app_state = AppState::new(
+        domains,
         url_shortener,
-    }): State<AppState>,
-    Json(payload): Json<ShortenRequest>,
-) -> Result<Json<ShortenResponse>, url_shortener::Error> {
-    let short_code = url_shortener.register(&payload.original_url).await?;
-
-    let short_url = format!("{}/{}", host, short_code);
-    let response = ShortenResponse {
-        short_code,
-        short_url,
+        api_keys,
+        users,
+        rate_limiter,
+        click_recorder,
+        options,
+    )
+    .with_audit_log(audit_log);
+
+    let app = api::router(app_state, monitoring_state, &config);
+
+    let bind_address = SocketAddr::from(([0, 0, 0, 0], config.port));
+    let shutdown_requested = Arc::new(Notify::new());
+    let shutdown = {
+        let shutdown_requested = shutdown_requested.clone();
+        async move {
+            common::shutdown::signal().await;
+            info!("Shutting down, waiting for in-flight requests to finish");
+            shutdown_requested.notify_one();
+        }
     };
 
-    Ok(Json(response))
-}
+    let server: Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>> = match config.tls() {
+        Some((cert_path, key_path)) => {
+            if let Some(http_redirect_port) = config.http_redirect_port {
+                tokio::spawn(tls::serve_https_redirect(http_redirect_port, config.port));
+            }
+            info!("URL Shortener is serving HTTPS on {}", bind_address);
+            Box::pin(tls::serve_https(
+                app,
+                bind_address,
+                cert_path,
+                key_path,
+                shutdown,
+            ))
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(bind_address).await.unwrap();
+            info!("URL Shortener is bound to {}", bind_address);
+            Box::pin(
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(shutdown)
+                .into_future(),
+            )
+        }
+    };
 
-async fn lookup_handler(
-    State(AppState { url_shortener, .. }): State<AppState>,
-    Query(short_code): Query<String>,
-) -> axum::response::Result<String, url_shortener::Error> {
-    let original_url = url_shortener.lookup(&short_code).await?;
-    Ok(original_url)
+    // The gRPC server listens for the shutdown signal on its own, and is given as long as the HTTP
+    // server to finish its in-flight calls.
+    let grpc_server: Pin<Box<dyn Future<Output = Result<(), tonic::transport::Error>> + Send>> =
+        match config.grpc_port {
+            Some(grpc_port) => {
+                let grpc_address = SocketAddr::from(([0, 0, 0, 0], grpc_port));
+                info!("gRPC API is bound to {}", grpc_address);
+                Box::pin(grpc::serve(
+                    grpc_service,
+                    grpc_address,
+                    common::shutdown::signal(),
+                ))
+            }
+            None => Box::pin(std::future::ready(Ok(()))),
+        };
+
+    tokio::select! {
+        (result, grpc_result) = async { tokio::join!(server, grpc_server) } => {
+            result.unwrap();
+            grpc_result.unwrap();
+        }
+        _ = async {
+            shutdown_requested.notified().await;
+            tokio::time::sleep(shutdown_timeout).await;
+        } => warn!("In-flight requests didn't finish within {:?}, shutting down anyway", shutdown_timeout),
+    }
+
+    // The server has dropped every click recorder by now, so the worker stops once it has written
+    // the queued clicks.
+    if tokio::time::timeout(CLICK_FLUSH_TIMEOUT, click_worker)
+        .await
+        .is_err()
+    {
+        warn!(
+            "Queued clicks weren't written within {:?}",
+            CLICK_FLUSH_TIMEOUT
+        );
+    }
+    database.close().await;
+    info!("URL Shortener has shut down");
 }
 
-async fn redirect_handler(
-    State(AppState { url_shortener, .. }): State<AppState>,
-    Path(short_code): Path<String>,
-) -> axum::response::Result<Redirect, url_shortener::Error> {
-    let original_url = url_shortener.lookup(&short_code).await?;
-    Ok(Redirect::temporary(&original_url))
+fn generate_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(64)
+        .map(char::from)
+        .collect()
 }
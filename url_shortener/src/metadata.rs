@@ -0,0 +1,376 @@
+use crate::database::{encode_timestamp, Database};
+use chrono::Utc;
+use reqwest::{header::CONTENT_TYPE, redirect, Response};
+use std::{net::IpAddr, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{info, warn};
+use url::{Host, Url};
+
+/// Pages are dropped once this many are waiting to be fetched.
+const QUEUE_CAPACITY: usize = 10_000;
+const MAX_CONCURRENT_FETCHES: usize = 8;
+const MAX_REDIRECTS: usize = 5;
+/// Longer titles are cut off before they are stored.
+const MAX_TITLE_LENGTH: usize = 200;
+pub(crate) const USER_AGENT: &str = concat!("url_shortener/", env!("CARGO_PKG_VERSION"));
+
+pub struct MetadataSettings {
+    pub timeout: Duration,
+    /// Only the beginning of larger pages is read, which is where the title and icons are.
+    pub max_page_size: usize,
+}
+
+/// What a destination page says about itself.
+struct PageMetadata {
+    title: Option<String>,
+    favicon_url: Option<String>,
+}
+
+struct PendingFetch {
+    short_code: String,
+    original_url: String,
+}
+
+/// Queues destination pages for a background worker that fetches their title and favicon and
+/// stores them with the short URL, so that shortening doesn't wait for the destination.
+pub struct MetadataFetcher {
+    /// `None` when fetching metadata is turned off.
+    sender: Option<mpsc::Sender<PendingFetch>>,
+}
+
+impl MetadataFetcher {
+    pub fn disabled() -> Self {
+        MetadataFetcher { sender: None }
+    }
+
+    pub fn spawn(database: Database, settings: MetadataSettings) -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(fetch_all(receiver, database, settings));
+        MetadataFetcher {
+            sender: Some(sender),
+        }
+    }
+
+    pub fn fetch(&self, short_code: &str, original_url: &str) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let pending = PendingFetch {
+            short_code: short_code.to_string(),
+            original_url: original_url.to_string(),
+        };
+        if let Err(error) = sender.try_send(pending) {
+            warn!("Dropping page metadata fetch of {}: {}", short_code, error);
+        }
+    }
+}
+
+impl Clone for MetadataFetcher {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+async fn fetch_all(
+    mut receiver: mpsc::Receiver<PendingFetch>,
+    database: Database,
+    settings: MetadataSettings,
+) {
+    let client = reqwest::Client::builder()
+        .timeout(settings.timeout)
+        .user_agent(USER_AGENT)
+        // Every hop has to be public, not just the first one.
+        .redirect(redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if !is_public(attempt.url()) {
+                attempt.stop()
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+        .expect("Failed to build the page metadata HTTP client");
+    let max_page_size = settings.max_page_size;
+    let fetches = Arc::new(Semaphore::new(MAX_CONCURRENT_FETCHES));
+
+    while let Some(pending) = receiver.recv().await {
+        let permit = fetches
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("The fetch semaphore is never closed");
+        let client = client.clone();
+        let database = database.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            // Failures still clear what was fetched for a previous destination.
+            let metadata = match fetch_page(&client, &pending.original_url, max_page_size).await {
+                Ok(metadata) => metadata,
+                Err(error) => {
+                    info!(
+                        "Cannot fetch page metadata of {}: {}",
+                        pending.original_url, error
+                    );
+                    PageMetadata {
+                        title: None,
+                        favicon_url: None,
+                    }
+                }
+            };
+            if let Err(error) = save(&database, &pending.short_code, &metadata).await {
+                warn!(
+                    "Error saving page metadata of {}: {}",
+                    pending.short_code, error
+                );
+            }
+        });
+    }
+}
+
+async fn fetch_page(
+    client: &reqwest::Client,
+    original_url: &str,
+    max_page_size: usize,
+) -> Result<PageMetadata, String> {
+    let url = Url::parse(original_url).map_err(|error| error.to_string())?;
+    if !is_public(&url) {
+        return Err("not a public http(s) URL".to_string());
+    }
+
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(Response::error_for_status)
+        .map_err(|error| error.to_string())?;
+    let page_url = response.url().clone();
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if !is_html {
+        return Ok(PageMetadata {
+            title: None,
+            favicon_url: default_favicon_url(&page_url),
+        });
+    }
+
+    let mut body = Vec::new();
+    while body.len() < max_page_size {
+        match response.chunk().await.map_err(|error| error.to_string())? {
+            Some(chunk) => body.extend_from_slice(&chunk),
+            None => break,
+        }
+    }
+    body.truncate(max_page_size);
+
+    Ok(parse_page(&String::from_utf8_lossy(&body), &page_url))
+}
+
+async fn save(
+    database: &Database,
+    short_code: &str,
+    metadata: &PageMetadata,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE short_urls SET page_title = $1, favicon_url = $2, metadata_fetched_at = $3
+        WHERE short_code = $4",
+    )
+    .bind(metadata.title.as_deref())
+    .bind(metadata.favicon_url.as_deref())
+    .bind(encode_timestamp(Utc::now()))
+    .bind(short_code)
+    .execute(database.pool())
+    .await?;
+    Ok(())
+}
+
+/// Keeps the fetcher from being pointed at the shortener's own network. Host names that resolve
+/// to private addresses aren't caught, so the fetcher should still run without access to
+/// anything sensitive.
+pub(crate) fn is_public(url: &Url) -> bool {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.to_ascii_lowercase();
+            domain != "localhost" && !domain.ends_with(".localhost")
+        }
+        Some(Host::Ipv4(ip)) => is_public_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => is_public_ip(IpAddr::V6(ip)),
+        None => false,
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation())
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first_segment = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local addresses, fc00::/7.
+                    || (first_segment & 0xfe00) == 0xfc00
+                    // Link-local addresses, fe80::/10.
+                    || (first_segment & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Reads the `<title>` and the first `<link rel="icon">` of a page, falling back to
+/// `/favicon.ico` like browsers do. This is no HTML parser, but it's enough for the head of
+/// ordinary pages.
+fn parse_page(html: &str, page_url: &Url) -> PageMetadata {
+    // ASCII lower-casing keeps byte offsets, so positions found in `lowercase` apply to `html`.
+    let lowercase = html.to_ascii_lowercase();
+
+    let title = lowercase.find("<title").and_then(|start| {
+        let content_start = start + lowercase[start..].find('>')? + 1;
+        let content_end = content_start + lowercase[content_start..].find("</title")?;
+        let title = decode_entities(&html[content_start..content_end])
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        (!title.is_empty()).then(|| title.chars().take(MAX_TITLE_LENGTH).collect())
+    });
+
+    let favicon_url = lowercase
+        .match_indices("<link")
+        .filter_map(|(start, _)| {
+            let end = start + lowercase[start..].find('>')?;
+            let tag = &html[start..end];
+            let is_icon = attribute(tag, "rel")?
+                .split_ascii_whitespace()
+                .any(|rel| rel.eq_ignore_ascii_case("icon"));
+            let href = attribute(tag, "href")?;
+            is_icon.then(|| page_url.join(&decode_entities(href)).ok())?
+        })
+        .find(|url| url.scheme() == "http" || url.scheme() == "https")
+        .map(String::from)
+        .or_else(|| default_favicon_url(page_url));
+
+    PageMetadata { title, favicon_url }
+}
+
+fn default_favicon_url(page_url: &Url) -> Option<String> {
+    page_url.join("/favicon.ico").ok().map(String::from)
+}
+
+/// The value of attribute `name` in the opening tag `tag`, quoted or not.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let lowercase = tag.to_ascii_lowercase();
+    let pattern = format!("{}=", name);
+    let (position, _) = lowercase.match_indices(&pattern).find(|(position, _)| {
+        *position > 0 && lowercase.as_bytes()[position - 1].is_ascii_whitespace()
+    })?;
+    let value = &tag[position + pattern.len()..];
+    match value.chars().next()? {
+        quote @ ('"' | '\'') => value[1..].split(quote).next(),
+        _ => value.split(|c: char| c.is_ascii_whitespace()).next(),
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(html: &str, page_url: &str) -> PageMetadata {
+        parse_page(html, &Url::parse(page_url).unwrap())
+    }
+
+    #[test]
+    fn reads_the_title() {
+        let page = parse(
+            "<html><head><TITLE lang=\"en\">\n  Rust &amp;\n Cargo  </title></head></html>",
+            "https://example.com/",
+        );
+        assert_eq!(page.title.as_deref(), Some("Rust & Cargo"));
+
+        let page = parse(
+            &format!("<title>{}</title>", "é".repeat(300)),
+            "https://example.com/",
+        );
+        assert_eq!(page.title.unwrap().chars().count(), MAX_TITLE_LENGTH);
+
+        assert!(parse("<title> </title>", "https://example.com/")
+            .title
+            .is_none());
+        assert!(parse("<title>Open", "https://example.com/").title.is_none());
+    }
+
+    #[test]
+    fn finds_the_favicon() {
+        let page = parse(
+            "<link rel=\"stylesheet\" href=\"/style.css\">\
+            <link REL=\"shortcut icon\" href=\"/static/icon.png\">",
+            "https://example.com/docs/page",
+        );
+        assert_eq!(
+            page.favicon_url.as_deref(),
+            Some("https://example.com/static/icon.png")
+        );
+
+        // Relative links are resolved against the page the redirects ended at.
+        let page = parse(
+            "<link rel=icon href=icon.svg>",
+            "https://www.example.com/docs/page",
+        );
+        assert_eq!(
+            page.favicon_url.as_deref(),
+            Some("https://www.example.com/docs/icon.svg")
+        );
+
+        let page = parse(
+            "<link rel='icon' href='data:image/png;base64,AAAA'>",
+            "https://example.com/docs/page",
+        );
+        assert_eq!(
+            page.favicon_url.as_deref(),
+            Some("https://example.com/favicon.ico")
+        );
+        let page = parse("<title>No icon</title>", "http://example.com/a/b");
+        assert_eq!(
+            page.favicon_url.as_deref(),
+            Some("http://example.com/favicon.ico")
+        );
+    }
+
+    #[test]
+    fn reads_attributes() {
+        let tag = "<link data-href=\"/no\" href='/yes' rel=icon";
+        assert_eq!(attribute(tag, "href"), Some("/yes"));
+        assert_eq!(attribute(tag, "rel"), Some("icon"));
+        assert_eq!(attribute(tag, "type"), None);
+    }
+
+    #[test]
+    fn decodes_entities_once() {
+        assert_eq!(decode_entities("a &lt;b&gt; &quot;c&#39;"), "a <b> \"c'");
+        assert_eq!(decode_entities("&amp;lt;"), "&lt;");
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{database::Database, url_shortener::UrlShortener};
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tracing::{info_span, Span};
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_LOG_FILTER: &str = "url_shortener=info,tower_http=info";
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Kept well below the usual probe timeouts, so an unreachable database is reported as such
+/// instead of the probe itself timing out.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+const REQUEST_DURATION_METRIC: &str = "http_request_duration_seconds";
+const REQUEST_DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+pub struct MonitoringState {
+    handle: PrometheusHandle,
+    database: Database,
+    url_shortener: UrlShortener,
+}
+
+impl MonitoringState {
+    pub fn new(handle: PrometheusHandle, database: Database, url_shortener: UrlShortener) -> Self {
+        MonitoringState {
+            handle,
+            database,
+            url_shortener,
+        }
+    }
+}
+
+impl Clone for MonitoringState {
+    fn clone(&self) -> Self {
+        Self {
+            handle: self.handle.clone(),
+            database: self.database.clone(),
+            url_shortener: self.url_shortener.clone(),
+        }
+    }
+}
+
+/// Sets up logging, filtered by `RUST_LOG` and printed either human readable or, with
+/// `LOG_FORMAT=json`, as one JSON object per line.
+pub fn init_tracing() {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => subscriber.json().init(),
+        _ => subscriber.init(),
+    }
+}
+
+/// Puts every log line written while handling a request into a span carrying its request ID.
+pub fn make_request_span(request: &Request) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    info_span!(
+        "request",
+        request_id,
+        method = %request.method(),
+        uri = %request.uri(),
+    )
+}
+
+/// Installs the global recorder that every `metrics` macro reports to.
+pub fn install_recorder() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(REQUEST_DURATION_METRIC.to_string()),
+            REQUEST_DURATION_BUCKETS,
+        )
+        .expect("Histogram buckets must not be empty")
+        .install_recorder()
+        .expect("Failed to install the metrics recorder")
+}
+
+/// Counts requests and measures their latency, labelled by route instead of the raw path so
+/// short codes don't each get their own time series.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(matched_path) => matched_path.as_str().to_string(),
+        None => "unmatched".to_string(),
+    };
+    let method = request.method().to_string();
+
+    let started_at = Instant::now();
+    let response = next.run(request).await;
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+
+    counter!("http_requests_total", &labels).increment(1);
+    histogram!(REQUEST_DURATION_METRIC, &labels).record(started_at.elapsed().as_secs_f64());
+    response
+}
+
+/// Records whether a redirect found an active short URL, e.g. `hit`, `not_found` or `expired`.
+pub fn record_redirect(result: &'static str) {
+    counter!("redirects_total", "result" => result).increment(1);
+}
+
+/// Counts webhook delivery attempts by `result`: `delivered`, `retried` or `failed`.
+pub fn record_webhook_delivery(result: &'static str) {
+    counter!("webhook_deliveries_total", "result" => result).increment(1);
+}
+
+/// Counts destination checks of the health checker by `result`: `ok`, `failed` or `skipped`.
+pub fn record_health_check(result: &'static str) {
+    counter!("health_checks_total", "result" => result).increment(1);
+}
+
+/// Counts rows removed by the cleanup task, by `kind` such as `links` or `clicks`.
+pub fn record_purged(kind: &'static str, count: u64) {
+    counter!("cleanup_purged_rows_total", "kind" => kind).increment(count);
+}
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Liveness probe: answers as long as the process is able to serve requests.
+pub async fn healthz_handler() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok",
+        error: None,
+    })
+}
+
+/// Readiness probe: only reports ready while the database is reachable.
+pub async fn readyz_handler(
+    State(state): State<MonitoringState>,
+) -> (StatusCode, Json<HealthResponse>) {
+    let result = match tokio::time::timeout(READINESS_TIMEOUT, state.database.ping()).await {
+        Ok(result) => result.map_err(|error| error.to_string()),
+        Err(_) => Err("Timed out while connecting to the database".to_string()),
+    };
+
+    match result {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(HealthResponse {
+                status: "ready",
+                error: None,
+            }),
+        ),
+        Err(error) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(HealthResponse {
+                status: "unavailable",
+                error: Some(error),
+            }),
+        ),
+    }
+}
+
+pub async fn metrics_handler(State(state): State<MonitoringState>) -> String {
+    let pool = state.database.pool();
+    gauge!("db_pool_connections").set(pool.size() as f64);
+    gauge!("db_pool_idle_connections").set(pool.num_idle() as f64);
+
+    let cache_stats = state.url_shortener.cache_stats();
+    counter!("cache_hits_total").absolute(cache_stats.hits);
+    counter!("cache_misses_total").absolute(cache_stats.misses);
+
+    state.handle.render()
+}
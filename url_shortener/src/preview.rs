@@ -0,0 +1,63 @@
+use url::Url;
+
+/// Renders the page that shows where a short URL leads, so that visitors can check the
+/// destination before following it. `page_title` is the destination page's own title, if known.
+pub fn render(short_url: &str, destination: &str, page_title: Option<&str>) -> String {
+    let summary = match Url::parse(destination) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => format!(
+            "This link leads to <strong>{}</strong>:",
+            escape_html(url.host_str().unwrap_or_default())
+        ),
+        Ok(url) => format!(
+            "This link opens an app with a <strong>{}:</strong> link:",
+            escape_html(url.scheme())
+        ),
+        Err(_) => "This link leads to:".to_string(),
+    };
+    let page_title = page_title
+        .map(|page_title| format!("<p><em>{}</em></p>\n", escape_html(page_title)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>Preview of {short_url}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 40rem; margin: 4rem auto; padding: 0 1rem; color: #222; }}
+code {{ display: block; padding: 0.75rem; background: #f4f4f4; border-radius: 4px; word-break: break-all; }}
+a.button {{ display: inline-block; margin-top: 1.5rem; padding: 0.6rem 1.2rem; background: #2563eb; color: #fff; border-radius: 4px; text-decoration: none; }}
+</style>
+</head>
+<body>
+<h1>{short_url}</h1>
+<p>{summary}</p>
+{page_title}<code>{destination}</code>
+<a class="button" href="{destination}" rel="noreferrer nofollow">Continue</a>
+</body>
+</html>
+"#,
+        short_url = escape_html(short_url),
+        summary = summary,
+        page_title = page_title,
+        destination = escape_html(destination),
+    )
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,85 @@
+use crate::url_shortener::Error;
+use image::{ImageFormat, Luma};
+use qrcode::{render::svg, EcLevel, QrCode};
+use serde::Deserialize;
+use std::io::Cursor;
+use utoipa::ToSchema;
+
+pub const DEFAULT_QR_SIZE: u32 = 256;
+pub const MIN_QR_SIZE: u32 = 64;
+pub const MAX_QR_SIZE: u32 = 2048;
+
+#[derive(Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QrFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+impl QrFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            QrFormat::Png => "image/png",
+            QrFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+/// How much of the code can be damaged while staying readable, from about 7% (`l`) to 30% (`h`).
+#[derive(Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QrErrorCorrection {
+    L,
+    #[default]
+    M,
+    Q,
+    H,
+}
+
+impl QrErrorCorrection {
+    fn ec_level(self) -> EcLevel {
+        match self {
+            QrErrorCorrection::L => EcLevel::L,
+            QrErrorCorrection::M => EcLevel::M,
+            QrErrorCorrection::Q => EcLevel::Q,
+            QrErrorCorrection::H => EcLevel::H,
+        }
+    }
+}
+
+/// Renders `data` as a square QR code of at least `size` pixels.
+pub fn render(
+    data: &str,
+    size: u32,
+    error_correction: QrErrorCorrection,
+    format: QrFormat,
+) -> Result<Vec<u8>, Error> {
+    if !(MIN_QR_SIZE..=MAX_QR_SIZE).contains(&size) {
+        return Err(Error::InvalidRequest(format!(
+            "QR code size must be between {} and {} pixels",
+            MIN_QR_SIZE, MAX_QR_SIZE
+        )));
+    }
+
+    let code = QrCode::with_error_correction_level(data, error_correction.ec_level())
+        .map_err(|error| Error::InvalidRequest(format!("Cannot create QR code: {}", error)))?;
+
+    match format {
+        QrFormat::Png => {
+            let image = code.render::<Luma<u8>>().min_dimensions(size, size).build();
+            let mut bytes = Vec::new();
+            image
+                .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+                .expect("Encoding a PNG into memory cannot fail");
+            Ok(bytes)
+        }
+        QrFormat::Svg => {
+            let image = code
+                .render::<svg::Color>()
+                .min_dimensions(size, size)
+                .build();
+            Ok(image.into_bytes())
+        }
+    }
+}
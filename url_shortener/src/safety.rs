@@ -0,0 +1,78 @@
+use std::{collections::HashSet, path::Path};
+use url::{Host, Url};
+
+pub enum UrlVerdict {
+    Safe,
+    /// The URL must not be shortened, for the given reason.
+    Unsafe(String),
+}
+
+/// Decides whether a destination URL may be shortened, e.g. to keep malware and phishing sites
+/// out. It is asked for every URL that is registered or updated.
+pub trait UrlSafetyChecker: Send + Sync {
+    fn check(&self, url: &Url) -> UrlVerdict;
+}
+
+/// Accepts every URL.
+pub struct NoopChecker;
+
+impl UrlSafetyChecker for NoopChecker {
+    fn check(&self, _url: &Url) -> UrlVerdict {
+        UrlVerdict::Safe
+    }
+}
+
+/// Rejects URLs whose host, or any domain it belongs to, is listed in a blocklist file.
+pub struct BlocklistChecker {
+    hosts: HashSet<String>,
+}
+
+impl BlocklistChecker {
+    /// Reads a file with one host per line, e.g. `evil.example`, which also blocks all of its
+    /// subdomains. Internationalized domain names may be listed in Unicode or as punycode. Empty
+    /// lines and lines starting with `#` are ignored.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let contents = std::fs::read_to_string(path)?;
+        let hosts = contents
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| normalize_host(line.trim_start_matches("*.")))
+            .collect();
+        Ok(BlocklistChecker { hosts })
+    }
+
+    pub fn host_count(&self) -> usize {
+        self.hosts.len()
+    }
+}
+
+impl UrlSafetyChecker for BlocklistChecker {
+    fn check(&self, url: &Url) -> UrlVerdict {
+        let Some(host) = url.host_str() else {
+            return UrlVerdict::Safe;
+        };
+        let host = host.trim_end_matches('.').to_lowercase();
+
+        // Try the host itself, then every parent domain: a.b.example, b.example, example.
+        let mut domain = host.as_str();
+        loop {
+            if self.hosts.contains(domain) {
+                return UrlVerdict::Unsafe(format!("{} is on the blocklist", host));
+            }
+            match domain.split_once('.') {
+                Some((_, parent)) => domain = parent,
+                None => return UrlVerdict::Safe,
+            }
+        }
+    }
+}
+
+/// Encodes internationalized domain names as punycode, which is how the hosts of parsed URLs are
+/// checked.
+fn normalize_host(host: &str) -> String {
+    match Host::parse(host) {
+        Ok(Host::Domain(domain)) => domain,
+        _ => host.to_lowercase(),
+    }
+}
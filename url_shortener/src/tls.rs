@@ -0,0 +1,68 @@
+use axum::{
+    http::{header::HOST, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use std::{future::Future, io, net::SocketAddr};
+use tracing::info;
+
+const DEFAULT_HTTPS_PORT: u16 = 443;
+
+/// Serves the app over HTTPS with rustls until `shutdown` resolves, then stops accepting
+/// connections and waits for the in-flight requests.
+pub async fn serve_https(
+    app: Router,
+    address: SocketAddr,
+    cert_path: &str,
+    key_path: &str,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    // Another dependency may already have installed a provider, which is just as good.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let tls_config = RustlsConfig::from_pem_file(cert_path, key_path).await?;
+
+    let handle = Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.await;
+            handle.graceful_shutdown(None);
+        }
+    });
+
+    axum_server::bind_rustls(address, tls_config)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+}
+
+/// Answers every plain HTTP request on `port` with a permanent redirect to the same URL on HTTPS.
+pub async fn serve_https_redirect(port: u16, https_port: u16) -> io::Result<()> {
+    let app = Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+        redirect_to_https(&headers, &uri, https_port)
+    });
+
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await?;
+    info!("Redirecting HTTP on port {} to HTTPS", port);
+    axum::serve(listener, app).await
+}
+
+fn redirect_to_https(headers: &HeaderMap, uri: &Uri, https_port: u16) -> Response {
+    let Some(host) = headers.get(HOST).and_then(|host| host.to_str().ok()) else {
+        return (StatusCode::BAD_REQUEST, "Missing Host header").into_response();
+    };
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+
+    let authority = if https_port == DEFAULT_HTTPS_PORT {
+        host.to_string()
+    } else {
+        format!("{}:{}", host, https_port)
+    };
+    let path_and_query = uri.path_and_query().map_or("/", |path| path.as_str());
+
+    Redirect::permanent(&format!("https://{}{}", authority, path_and_query)).into_response()
+}
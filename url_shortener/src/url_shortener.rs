@@ -1,118 +1,2999 @@
-use rand::{distributions::Alphanumeric, Rng};
-use sqlx::{Executor, Pool, Row, Sqlite};
+use crate::cache::{CacheStats, CachedShortUrl, ShortUrlCache};
+use crate::clicks::ClickEvent;
+use crate::database::{encode_timestamp, get_timestamp, Database};
+use crate::domains::Domains;
+use crate::geoip::GeoIpLookup;
+use crate::metadata::MetadataFetcher;
+use crate::safety::{UrlSafetyChecker, UrlVerdict};
+use crate::webhooks::{LinkEvent, Webhooks};
+use axum::{
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use common::short_code;
+use futures_util::Stream;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::{any::AnyRow, Acquire, AnyConnection, AnyPool, Row};
+use std::{
+    collections::{BTreeMap, HashSet},
+    net::IpAddr,
+    ops::RangeInclusive,
+    str::FromStr,
+    sync::Arc,
+};
+use tracing::{error, info, warn};
+use url::Url;
+use utoipa::ToSchema;
 
-const DEFAULT_SHORT_CODE_LENGTH: usize = 4;
+const MIN_CUSTOM_CODE_LENGTH: usize = 3;
+const MAX_CUSTOM_CODE_LENGTH: usize = 32;
+const MAX_ORIGINAL_URL_LENGTH: usize = 2048;
+const TOP_REFERRERS_LIMIT: i64 = 10;
+const TOP_CAMPAIGN_LINKS_LIMIT: i64 = 10;
+const MAX_CAMPAIGN_NAME_LENGTH: usize = 64;
+/// Schemes that would run code or read local files in the browser instead of opening an app.
+const UNSAFE_DEEP_LINK_SCHEMES: &[&str] = &["javascript", "data", "file", "vbscript", "blob"];
+const MAX_TRACKING_PARAMS: usize = 20;
+/// Random codes of a requested length are given up on after this many collisions.
+const REQUESTED_LENGTH_ATTEMPTS: usize = 10;
+
+/// The columns of `short_urls` that are read back. `created_at` is left out because databases
+/// created by the first versions of the service declare it as `TIMESTAMP`, which the `Any` driver
+/// can't decode; where it's needed, it is cast to `TEXT`.
+const SHORT_URL_COLUMNS: &str =
+    "id, original_url, short_code, expires_at, owner_id, redirect_type, max_clicks, deleted_at, domain, tracking_params,
+    page_title, favicon_url, health_checked_at, health_status, health_latency_ms, health_error, health_failures,
+    broken_since";
+
+/// Codes that are never handed out, compared case-insensitively: paths of the service's own
+/// routes, words that look official, and words nobody wants to see in a link.
+const RESERVED_SHORT_CODES: &[&str] = &[
+    "shorten",
+    "lookup",
+    "links",
+    "stats",
+    "cache",
+    "api-keys",
+    "auth",
+    "metrics",
+    "healthz",
+    "readyz",
+    "admin",
+    "api",
+    "docs",
+    "import",
+    "export",
+    "login",
+    "logout",
+    "static",
+    "favicon",
+    "robots",
+    "usage",
+    "namespaces",
+    "campaigns",
+    "qr",
+    "preview",
+    "fuck",
+    "shit",
+    "cunt",
+    "dick",
+    "cock",
+    "piss",
+    "tits",
+    "porn",
+    "nazi",
+    "slut",
+    "whore",
+];
 
 pub enum Error {
     NotFound,
+    InvalidRequest(String),
+    InvalidUrl(String),
+    /// The URL was rejected by the [`UrlSafetyChecker`].
+    UnsafeUrl(String),
+    InvalidShortCode(String),
+    InvalidExpiration(String),
+    InvalidUser(String),
+    Unauthorized(String),
+    Forbidden(String),
+    Conflict(String),
+    /// The client has to wait this long before it may try again.
+    RateLimited(std::time::Duration),
+    /// The API key created as many short URLs as its quota allows, until the quota resets after
+    /// the given time.
+    QuotaExceeded(String, std::time::Duration),
+    Expired,
+    Deactivated,
     Internal(sqlx::Error),
 }
 
+impl Error {
+    /// A stable, machine readable name of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NotFound => "not_found",
+            Error::InvalidRequest(_) => "invalid_request",
+            Error::InvalidUrl(_) => "invalid_url",
+            Error::UnsafeUrl(_) => "unsafe_url",
+            Error::InvalidShortCode(_) => "invalid_short_code",
+            Error::InvalidExpiration(_) => "invalid_expiration",
+            Error::InvalidUser(_) => "invalid_user",
+            Error::Unauthorized(_) => "unauthorized",
+            Error::Forbidden(_) => "forbidden",
+            Error::Conflict(_) => "conflict",
+            Error::RateLimited(_) => "rate_limited",
+            Error::QuotaExceeded(..) => "quota_exceeded",
+            Error::Expired => "expired",
+            Error::Deactivated => "deactivated",
+            Error::Internal(_) => "internal",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::InvalidRequest(_)
+            | Error::InvalidUrl(_)
+            | Error::UnsafeUrl(_)
+            | Error::InvalidShortCode(_)
+            | Error::InvalidExpiration(_)
+            | Error::InvalidUser(_) => StatusCode::BAD_REQUEST,
+            Error::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Error::Forbidden(_) => StatusCode::FORBIDDEN,
+            Error::Conflict(_) => StatusCode::CONFLICT,
+            Error::RateLimited(_) | Error::QuotaExceeded(..) => StatusCode::TOO_MANY_REQUESTS,
+            Error::Expired | Error::Deactivated => StatusCode::GONE,
+            Error::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// What an error response tells the client. It is attached to the response by `into_response`
+/// and turned into the JSON body by the server's error rendering middleware, which knows the
+/// request ID.
+#[derive(Clone)]
+pub struct ErrorDetails {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        if let Error::Internal(error) = &self {
+            error!("Internal error: {}", error);
+        }
+
+        let mut response = self.status_code().into_response();
+        if let Error::RateLimited(retry_after) | Error::QuotaExceeded(_, retry_after) = &self {
+            let retry_after_seconds = retry_after.as_secs_f64().ceil() as u64;
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after_seconds));
+        }
+        response.extensions_mut().insert(ErrorDetails {
+            code: self.code(),
+            message: self.to_string(),
+        });
+        response
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound => write!(f, "Short URL not found"),
+            Error::InvalidRequest(message)
+            | Error::InvalidUrl(message)
+            | Error::UnsafeUrl(message)
+            | Error::InvalidShortCode(message)
+            | Error::InvalidExpiration(message)
+            | Error::InvalidUser(message)
+            | Error::Unauthorized(message)
+            | Error::Forbidden(message)
+            | Error::Conflict(message)
+            | Error::QuotaExceeded(message, _) => write!(f, "{}", message),
+            Error::RateLimited(retry_after) => write!(
+                f,
+                "Too many requests, retry in {} seconds",
+                retry_after.as_secs_f64().ceil()
+            ),
+            Error::Expired => write!(f, "Short URL has expired"),
+            Error::Deactivated => write!(f, "Short URL has been deactivated"),
+            // Database errors can reveal the schema, so clients only get a generic message.
+            Error::Internal(_) => write!(f, "Internal server error"),
+        }
+    }
+}
+
+pub struct NewShortUrl {
+    pub original_url: String,
+    pub custom_code: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub owner_id: Option<i64>,
+    pub redirect_type: RedirectType,
+    /// Makes the short URL expire once it has been visited this many times.
+    pub max_clicks: Option<i64>,
+    /// Returns the code of an existing short URL for the same destination instead of creating a
+    /// new one. Only applies when neither a custom code nor an expiration or click limit is
+    /// requested.
+    pub reuse_existing: bool,
+    /// Destinations for visitors from the given countries, keyed by ISO 3166-1 alpha-2 code.
+    pub geo_targets: BTreeMap<String, String>,
+    /// Split test destinations that take their share of visitors away from `original_url`.
+    pub variants: Vec<NewVariant>,
+    /// Destinations for visitors on the given kinds of devices.
+    pub device_targets: BTreeMap<DeviceType, String>,
+    /// The extra domain the short URL is served on, or `None` for the base URL's host.
+    pub domain: Option<String>,
+    /// Query parameters added to every destination on redirect, like `utm_source`.
+    pub tracking_params: BTreeMap<String, String>,
+    /// The length of the random short code, instead of the current default length.
+    pub short_code_length: Option<usize>,
+    /// Registers the short code under this namespace, to be served at `/<namespace>/<code>`.
+    /// Users need access to the namespace, API keys may use any.
+    pub namespace: Option<String>,
+}
+
+impl NewShortUrl {
+    /// A copy with every destination passed through [`normalize_url`].
+    fn normalized(&self) -> NewShortUrl {
+        NewShortUrl {
+            original_url: normalize_url(&self.original_url),
+            custom_code: self.custom_code.clone(),
+            expires_at: self.expires_at,
+            owner_id: self.owner_id,
+            redirect_type: self.redirect_type,
+            max_clicks: self.max_clicks,
+            reuse_existing: self.reuse_existing,
+            geo_targets: normalize_targets(&self.geo_targets),
+            variants: self.variants.iter().map(NewVariant::normalized).collect(),
+            device_targets: normalize_targets(&self.device_targets),
+            domain: self.domain.clone(),
+            tracking_params: self.tracking_params.clone(),
+            short_code_length: self.short_code_length,
+            namespace: self.namespace.clone(),
+        }
+    }
+}
+
+/// A key a client sends with a registration, so that retries of it return the short code of the
+/// first attempt instead of registering the URL again.
+pub struct IdempotencyKey<'a> {
+    /// Who sent the key. Keys of different callers never match.
+    pub caller: &'a str,
+    pub key: &'a str,
+    /// Identifies the request, which retries with the same key must repeat.
+    pub request_hash: &'a str,
+    /// When the key may be used for a new request again.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A named group of short URLs, whose clicks are counted together.
+#[derive(Serialize, ToSchema)]
+pub struct Campaign {
+    pub name: String,
+    /// The user who created the campaign, or `None` when it was created with an API key.
+    pub owner_id: Option<i64>,
+    pub link_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A prefix that short codes can be registered under, so that teams sharing one deployment don't
+/// compete for the same codes.
+#[derive(Serialize, ToSchema)]
+pub struct Namespace {
+    pub name: String,
+    /// The user who created the namespace and manages its members, or `None` when it was created
+    /// with an API key.
+    pub owner_id: Option<i64>,
+    /// Users besides the owner who may register short codes in the namespace.
+    pub member_ids: Vec<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct NewVariant {
+    pub original_url: String,
+    /// Percentage of visitors sent to this variant.
+    pub weight: i64,
+}
+
+impl NewVariant {
+    fn normalized(&self) -> NewVariant {
+        NewVariant {
+            original_url: normalize_url(&self.original_url),
+            weight: self.weight,
+        }
+    }
+}
+
+/// Changes to an existing short URL. Targets, variants and tracking parameters are only replaced
+/// when given.
+pub struct ShortUrlUpdate {
+    pub original_url: String,
+    pub geo_targets: Option<BTreeMap<String, String>>,
+    pub variants: Option<Vec<NewVariant>>,
+    pub device_targets: Option<BTreeMap<DeviceType, String>>,
+    pub tracking_params: Option<BTreeMap<String, String>>,
+}
+
+impl ShortUrlUpdate {
+    /// A copy with every destination passed through [`normalize_url`].
+    fn normalized(&self) -> ShortUrlUpdate {
+        ShortUrlUpdate {
+            original_url: normalize_url(&self.original_url),
+            geo_targets: self.geo_targets.as_ref().map(normalize_targets),
+            variants: self
+                .variants
+                .as_ref()
+                .map(|variants| variants.iter().map(NewVariant::normalized).collect()),
+            device_targets: self.device_targets.as_ref().map(normalize_targets),
+            tracking_params: self.tracking_params.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Variant {
+    pub id: i64,
+    pub original_url: String,
+    pub weight: i64,
+}
+
+/// The characters random short codes are made of.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortCodeAlphabet {
+    /// Digits and upper and lower case letters.
+    #[default]
+    Alphanumeric,
+    /// Like `Alphanumeric`, but without characters that are easily mistaken for each other when
+    /// read aloud or typed from print: `0`, `O`, `o`, `1`, `I` and `l`.
+    Unambiguous,
+}
+
+impl ShortCodeAlphabet {
+    fn characters(self, lowercase: bool) -> &'static [u8] {
+        match (self, lowercase) {
+            (ShortCodeAlphabet::Alphanumeric, false) => short_code::ALPHANUMERIC,
+            (ShortCodeAlphabet::Alphanumeric, true) => short_code::ALPHANUMERIC_LOWERCASE,
+            (ShortCodeAlphabet::Unambiguous, false) => short_code::UNAMBIGUOUS,
+            (ShortCodeAlphabet::Unambiguous, true) => short_code::UNAMBIGUOUS_LOWERCASE,
+        }
+    }
+}
+
+impl FromStr for ShortCodeAlphabet {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "alphanumeric" => Ok(ShortCodeAlphabet::Alphanumeric),
+            "unambiguous" => Ok(ShortCodeAlphabet::Unambiguous),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How new short codes are picked when no custom code is requested.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortCodeStrategy {
+    /// Random codes, which grow longer once collisions become frequent.
+    #[default]
+    Random,
+    /// A counter stored in the settings table, written in the digits of the alphabet. Codes are
+    /// as short as possible and never collide with each other, but they are easy to guess.
+    Counter,
+}
+
+impl FromStr for ShortCodeStrategy {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "random" => Ok(ShortCodeStrategy::Random),
+            "counter" => Ok(ShortCodeStrategy::Counter),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How short codes are generated and matched.
+pub struct ShortCodeOptions {
+    pub strategy: ShortCodeStrategy,
+    /// Length of random short codes until the code space fills up and they grow longer.
+    pub initial_length: usize,
+    pub alphabet: ShortCodeAlphabet,
+    /// Generates lower case codes only, stores custom codes in lower case and finds short URLs
+    /// regardless of the case of the requested code.
+    pub case_insensitive: bool,
+    /// Codes that can't be registered, in addition to the built-in reserved codes.
+    pub reserved: Vec<String>,
+    /// Lengths clients may ask for with [`NewShortUrl::short_code_length`].
+    pub requestable_lengths: RangeInclusive<usize>,
+}
+
+/// Whether a redirect tells clients and search engines that the short URL has moved for good
+/// (`301 Moved Permanently`) or may change and should be requested again (`302 Found`).
+#[derive(Serialize, Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectType {
+    Permanent,
+    #[default]
+    Temporary,
+}
+
+impl RedirectType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RedirectType::Permanent => "permanent",
+            RedirectType::Temporary => "temporary",
+        }
+    }
+
+    fn from_row(row: &AnyRow) -> Self {
+        match row.get::<Option<String>, &str>("redirect_type").as_deref() {
+            Some("permanent") => RedirectType::Permanent,
+            _ => RedirectType::Temporary,
+        }
+    }
+}
+
+/// The kinds of devices a short URL can have separate destinations for, which are told apart by
+/// their `User-Agent`.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceType {
+    /// iPhones, iPads and iPods. Their destination may be an app deep link like `myapp://`.
+    Ios,
+    /// Android phones and tablets. Their destination may be an app deep link like `myapp://`.
+    Android,
+    /// Any mobile device, unless its platform has a destination of its own.
+    Mobile,
+    Desktop,
+}
+
+impl DeviceType {
+    fn as_str(self) -> &'static str {
+        match self {
+            DeviceType::Ios => "ios",
+            DeviceType::Android => "android",
+            DeviceType::Mobile => "mobile",
+            DeviceType::Desktop => "desktop",
+        }
+    }
+
+    /// The device types whose destinations apply to a user agent, most specific first.
+    fn matching(user_agent: &str) -> &'static [DeviceType] {
+        if ["iPhone", "iPad", "iPod"]
+            .iter()
+            .any(|device| user_agent.contains(device))
+        {
+            &[DeviceType::Ios, DeviceType::Mobile]
+        } else if user_agent.contains("Android") {
+            &[DeviceType::Android, DeviceType::Mobile]
+        } else if user_agent.contains("Mobi") || user_agent.contains("Windows Phone") {
+            &[DeviceType::Mobile]
+        } else {
+            &[DeviceType::Desktop]
+        }
+    }
+}
+
+impl FromStr for DeviceType {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "ios" => Ok(DeviceType::Ios),
+            "android" => Ok(DeviceType::Android),
+            "mobile" => Ok(DeviceType::Mobile),
+            "desktop" => Ok(DeviceType::Desktop),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A short URL read from an import file.
+pub struct ImportedShortUrl {
+    /// Where the short URL was found in the file, for reporting errors.
+    pub line: u64,
+    pub short_code: String,
+    pub original_url: String,
+    /// The domain the short URL is served on. Empty or absent for the base URL's host.
+    pub domain: Option<String>,
+}
+
+impl ImportedShortUrl {
+    fn normalized(&self) -> ImportedShortUrl {
+        ImportedShortUrl {
+            line: self.line,
+            short_code: self.short_code.clone(),
+            original_url: normalize_url(&self.original_url),
+            domain: self.domain.clone(),
+        }
+    }
+}
+
+/// What to do when an imported short code already exists.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportConflict {
+    /// Keep the existing short URL.
+    #[default]
+    Skip,
+    /// Point the existing short URL at the imported destination.
+    Overwrite,
+    /// Reject the whole import.
+    Fail,
+}
+
+#[derive(Serialize, Default, ToSchema)]
+pub struct ImportSummary {
+    pub imported: u64,
+    pub overwritten: u64,
+    pub skipped: u64,
+    /// Short URLs that couldn't be imported. The others are imported nevertheless.
+    pub failed: Vec<ImportFailure>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportFailure {
+    pub line: u64,
+    pub short_code: String,
+    pub code: &'static str,
+    pub error: String,
+}
+
+enum ImportOutcome {
+    Imported(String),
+    Overwritten(String),
+    Skipped,
+}
+
+/// How many short URLs [`UrlShortener::export`] reads from the database at a time.
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+/// Restricts which short URLs an operation may see or modify.
+#[derive(Clone, Copy)]
+pub enum LinkScope {
+    All,
+    OwnedBy(i64),
+}
+
+impl LinkScope {
+    fn owner_id(self) -> Option<i64> {
+        match self {
+            LinkScope::All => None,
+            LinkScope::OwnedBy(owner_id) => Some(owner_id),
+        }
+    }
+
+    fn allows(self, owner_id: Option<i64>) -> bool {
+        match self {
+            LinkScope::All => true,
+            LinkScope::OwnedBy(user_id) => owner_id == Some(user_id),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ClickStats {
+    /// Every redirect so far, including clicks that were never logged or were removed after the
+    /// click retention.
+    pub click_count: i64,
+    /// Logged clicks.
+    pub total_clicks: i64,
+    /// Distinct hashed client IPs. Clicks logged before referrers and IPs were recorded aren't
+    /// counted.
+    pub unique_visitors: i64,
+    pub first_clicked_at: Option<DateTime<Utc>>,
+    pub last_clicked_at: Option<DateTime<Utc>>,
+    pub clicks_per_day: Vec<DailyClicks>,
+    pub top_referrers: Vec<ReferrerClicks>,
+    /// Clicks per split test destination, starting with the original URL. Empty without a split
+    /// test.
+    pub variants: Vec<VariantClicks>,
+    /// The title of the destination page, once it has been fetched.
+    pub page_title: Option<String>,
+    pub favicon_url: Option<String>,
+    /// The last check of the destination, once it has been checked.
+    pub health: Option<LinkHealth>,
+}
+
+/// How the destination of a short URL answered the last check of the health checker.
+#[derive(Serialize, ToSchema)]
+pub struct LinkHealth {
+    pub checked_at: DateTime<Utc>,
+    /// The HTTP status of the answer, or `None` when the destination couldn't be reached.
+    pub status_code: Option<i64>,
+    pub latency_ms: Option<i64>,
+    /// Why the destination couldn't be reached or wasn't checked.
+    pub error: Option<String>,
+    /// Failed checks in a row.
+    pub consecutive_failures: i64,
+    /// Since when the destination counts as broken, after failing too many checks in a row.
+    pub broken_since: Option<DateTime<Utc>>,
+}
+
+/// Clicks of every short URL in a campaign, counted together.
+#[derive(Serialize, ToSchema)]
+pub struct CampaignStats {
+    pub name: String,
+    pub link_count: i64,
+    /// Every redirect so far, like [`ClickStats::click_count`].
+    pub click_count: i64,
+    /// Logged clicks.
+    pub total_clicks: i64,
+    /// Distinct hashed client IPs across all short URLs. A visitor of two of them counts once.
+    pub unique_visitors: i64,
+    pub first_clicked_at: Option<DateTime<Utc>>,
+    pub last_clicked_at: Option<DateTime<Utc>>,
+    pub clicks_per_day: Vec<DailyClicks>,
+    /// The short URLs with the most logged clicks.
+    pub top_links: Vec<LinkClicks>,
+    pub top_referrers: Vec<ReferrerClicks>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LinkClicks {
+    pub short_code: String,
+    pub clicks: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DailyClicks {
+    pub date: NaiveDate,
+    pub clicks: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReferrerClicks {
+    pub referrer: String,
+    pub clicks: i64,
+}
+
+/// The client following a short URL.
+pub struct Visitor<'a> {
+    pub ip: IpAddr,
+    pub user_agent: Option<&'a str>,
+    /// The extra domain the short URL was requested on, see [`Domains::resolve`].
+    pub domain: Option<&'a str>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VariantClicks {
+    /// `None` for the original URL.
+    pub variant_id: Option<i64>,
+    pub original_url: String,
+    pub weight: i64,
+    pub clicks: i64,
+}
+
+/// Where a short URL would redirect a visitor to, for the preview page.
+pub struct Preview {
+    pub original_url: String,
+    /// The title of the destination page, once it has been fetched. Targets and variants may
+    /// have other titles.
+    pub page_title: Option<String>,
+}
+
+/// Where a visited short URL redirects to.
+pub struct Destination {
+    pub short_url_id: i64,
+    /// The split test variant that was picked, if any.
+    pub variant_id: Option<i64>,
+    pub original_url: String,
+    pub redirect_type: RedirectType,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Whether the next visit may end up somewhere else, because of a split test, device or geo
+    /// targets, or a click limit. Such redirects must not be cached.
+    pub varies: bool,
+}
+
+/// A single recorded click, as exported by [`UrlShortener::export_clicks`].
+#[derive(Serialize, ToSchema)]
+pub struct ExportedClick {
+    pub clicked_at: DateTime<Utc>,
+    /// The split test variant the visitor was sent to, if any.
+    pub variant_id: Option<i64>,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+    /// The salted hash of the visitor's IP address.
+    pub ip_hash: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LinkSummary {
+    pub short_code: String,
+    pub original_url: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub redirect_type: RedirectType,
+    pub max_clicks: Option<i64>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub click_count: i64,
+    /// The extra domain the short URL is served on, or `None` for the base URL's host.
+    pub domain: Option<String>,
+    /// The title of the destination page, once it has been fetched.
+    pub page_title: Option<String>,
+    pub favicon_url: Option<String>,
+    pub health: Option<LinkHealth>,
+}
+
+/// Everything about a single short URL, whether it still redirects or not.
+#[derive(Serialize, ToSchema)]
+pub struct LinkDetails {
+    pub short_code: String,
+    pub short_url: String,
+    pub original_url: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_clicks: Option<i64>,
+    pub click_count: i64,
+    pub status: LinkStatus,
+    pub tracking_params: BTreeMap<String, String>,
+    /// The title of the destination page, once it has been fetched.
+    pub page_title: Option<String>,
+    pub favicon_url: Option<String>,
+    pub health: Option<LinkHealth>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkStatus {
+    Active,
+    /// Past its expiration time, or out of clicks.
+    Expired,
+    /// Deleted, but not purged yet.
+    Deactivated,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LinkPage {
+    pub links: Vec<LinkSummary>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: i64,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkSort {
+    #[default]
+    Newest,
+    Oldest,
+    MostClicked,
+    ShortCode,
+}
+
+impl LinkSort {
+    fn order_by(self) -> &'static str {
+        match self {
+            LinkSort::Newest => "short_urls.id DESC",
+            LinkSort::Oldest => "short_urls.id ASC",
+            LinkSort::MostClicked => "click_count DESC, short_urls.id DESC",
+            LinkSort::ShortCode => "short_urls.short_code ASC",
+        }
+    }
+}
+
+/// The number of rows removed by [`UrlShortener::purge`].
+pub struct PurgedRows {
+    pub links: u64,
+    pub clicks: u64,
+}
+
 pub struct UrlShortener {
-    pool: Pool<Sqlite>,
+    database: Database,
+    domains: Arc<Domains>,
+    cache: Option<ShortUrlCache>,
+    short_code_strategy: ShortCodeStrategy,
+    initial_short_code_length: usize,
+    requestable_short_code_lengths: RangeInclusive<usize>,
+    short_code_characters: &'static [u8],
+    case_insensitive_short_codes: bool,
+    safety_checker: Arc<dyn UrlSafetyChecker>,
+    reserved_short_codes: Arc<HashSet<String>>,
+    webhooks: Webhooks,
+    geoip: Arc<dyn GeoIpLookup>,
+    metadata_fetcher: MetadataFetcher,
 }
 
 impl UrlShortener {
-    pub fn new(pool: Pool<Sqlite>) -> Self {
-        UrlShortener { pool }
-    }
-
-    pub async fn auto_migrate(&self) -> Result<(), sqlx::Error> {
-        self.pool
-            .execute(
-                "CREATE TABLE IF NOT EXISTS short_urls (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                original_url TEXT NOT NULL,
-                short_code TEXT NOT NULL UNIQUE,
-                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
-            )",
+    pub fn new(
+        database: Database,
+        domains: Arc<Domains>,
+        cache: Option<ShortUrlCache>,
+        short_code_options: ShortCodeOptions,
+        safety_checker: Arc<dyn UrlSafetyChecker>,
+        webhooks: Webhooks,
+        geoip: Arc<dyn GeoIpLookup>,
+    ) -> Self {
+        let reserved_short_codes = RESERVED_SHORT_CODES
+            .iter()
+            .copied()
+            .chain(short_code_options.reserved.iter().map(String::as_str))
+            .map(str::to_lowercase)
+            .collect();
+
+        UrlShortener {
+            database,
+            domains,
+            cache,
+            short_code_strategy: short_code_options.strategy,
+            initial_short_code_length: short_code_options.initial_length,
+            requestable_short_code_lengths: short_code_options.requestable_lengths,
+            short_code_characters: short_code_options
+                .alphabet
+                .characters(short_code_options.case_insensitive),
+            case_insensitive_short_codes: short_code_options.case_insensitive,
+            safety_checker,
+            reserved_short_codes: Arc::new(reserved_short_codes),
+            webhooks,
+            geoip,
+            metadata_fetcher: MetadataFetcher::disabled(),
+        }
+    }
+
+    /// Fetches the title and favicon of destinations after they are shortened or updated.
+    pub fn with_metadata_fetcher(mut self, metadata_fetcher: MetadataFetcher) -> Self {
+        self.metadata_fetcher = metadata_fetcher;
+        self
+    }
+
+    pub async fn register(&self, new_short_url: &NewShortUrl) -> Result<String, Error> {
+        let new_short_url = &new_short_url.normalized();
+        let mut connection = self
+            .database
+            .pool()
+            .acquire()
+            .await
+            .map_err(Error::Internal)?;
+        let (short_code, created) = self.register_with(&mut connection, new_short_url).await?;
+        if created {
+            self.webhooks
+                .notify(LinkEvent::Created, &short_code, &new_short_url.original_url);
+            self.metadata_fetcher
+                .fetch(&short_code, &new_short_url.original_url);
+        }
+        Ok(short_code)
+    }
+
+    /// Like `register`, but only registers the URL the first time `idempotency_key` is seen, and
+    /// returns the same short code for retries until the key expires. Failed registrations don't
+    /// use up the key. Also returns whether this call registered the URL.
+    pub async fn register_idempotent(
+        &self,
+        new_short_url: &NewShortUrl,
+        idempotency_key: &IdempotencyKey<'_>,
+    ) -> Result<(String, bool), Error> {
+        let now = encode_timestamp(Utc::now());
+        sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= $1")
+            .bind(&now)
+            .execute(self.database.pool())
+            .await
+            .map_err(Error::Internal)?;
+
+        // Inserting the key first makes concurrent retries wait for the first attempt instead of
+        // registering the URL as well.
+        let claimed = sqlx::query(
+            "INSERT INTO idempotency_keys (caller, key, request_hash, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5) ON CONFLICT (caller, key) DO NOTHING",
+        )
+        .bind(idempotency_key.caller)
+        .bind(idempotency_key.key)
+        .bind(idempotency_key.request_hash)
+        .bind(&now)
+        .bind(encode_timestamp(idempotency_key.expires_at))
+        .execute(self.database.pool())
+        .await
+        .map_err(Error::Internal)?
+        .rows_affected()
+            == 1;
+
+        if !claimed {
+            let row = sqlx::query(
+                "SELECT request_hash, short_code FROM idempotency_keys WHERE caller = $1 AND key = $2",
             )
-            .await?;
+            .bind(idempotency_key.caller)
+            .bind(idempotency_key.key)
+            .fetch_optional(self.database.pool())
+            .await
+            .map_err(Error::Internal)?
+            // The first attempt failed and released the key in the meantime.
+            .ok_or_else(|| {
+                Error::Conflict("Retry the request with this Idempotency-Key".to_string())
+            })?;
+            if row.get::<String, &str>("request_hash") != idempotency_key.request_hash {
+                return Err(Error::InvalidRequest(
+                    "This Idempotency-Key was already used for a different request".to_string(),
+                ));
+            }
+            let short_code = row
+                .get::<Option<String>, &str>("short_code")
+                .ok_or_else(|| {
+                    Error::Conflict(
+                        "A request with this Idempotency-Key is still in progress".to_string(),
+                    )
+                })?;
+            return Ok((short_code, false));
+        }
 
-        self.pool
-            .execute(
-                "CREATE TABLE IF NOT EXISTS settings (
-                key TEXT PRIMARY KEY,
-                value TEXT NOT NULL,
-                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
-            )",
+        let result = self.register(new_short_url).await;
+        let query = match &result {
+            Ok(short_code) => sqlx::query(
+                "UPDATE idempotency_keys SET short_code = $1 WHERE caller = $2 AND key = $3",
             )
-            .await?;
+            .bind(short_code),
+            Err(_) => sqlx::query("DELETE FROM idempotency_keys WHERE caller = $1 AND key = $2"),
+        };
+        query
+            .bind(idempotency_key.caller)
+            .bind(idempotency_key.key)
+            .execute(self.database.pool())
+            .await
+            .map_err(Error::Internal)?;
+        result.map(|short_code| (short_code, true))
+    }
+
+    /// Registers several short URLs inside a single transaction. Every URL gets its own result, so
+    /// one invalid URL doesn't prevent the others from being registered. Each URL is registered in
+    /// a savepoint of its own, since PostgreSQL aborts the whole transaction on the first failed
+    /// statement otherwise.
+    pub async fn register_batch(
+        &self,
+        new_short_urls: &[NewShortUrl],
+    ) -> Result<Vec<Result<String, Error>>, Error> {
+        let new_short_urls: Vec<_> = new_short_urls.iter().map(NewShortUrl::normalized).collect();
+        let mut transaction = self
+            .database
+            .pool()
+            .begin()
+            .await
+            .map_err(Error::Internal)?;
+
+        let mut results = Vec::with_capacity(new_short_urls.len());
+        for new_short_url in &new_short_urls {
+            let mut savepoint = transaction.begin().await.map_err(Error::Internal)?;
+            let result = self.register_with(&mut savepoint, new_short_url).await;
+            match result {
+                Ok(_) => savepoint.commit().await.map_err(Error::Internal)?,
+                Err(_) => savepoint.rollback().await.map_err(Error::Internal)?,
+            }
+            results.push(result);
+        }
+
+        transaction.commit().await.map_err(Error::Internal)?;
+
+        let results = results
+            .into_iter()
+            .zip(&new_short_urls)
+            .map(|(result, new_short_url)| {
+                let (short_code, created) = result?;
+                if created {
+                    self.webhooks.notify(
+                        LinkEvent::Created,
+                        &short_code,
+                        &new_short_url.original_url,
+                    );
+                    self.metadata_fetcher
+                        .fetch(&short_code, &new_short_url.original_url);
+                }
+                Ok(short_code)
+            })
+            .collect();
+        Ok(results)
+    }
+
+    /// Creates a namespace. Like custom codes, its name must not be reserved, which also keeps it
+    /// from hiding routes like `/stats/:short_code`.
+    pub async fn create_namespace(
+        &self,
+        name: &str,
+        owner_id: Option<i64>,
+    ) -> Result<Namespace, Error> {
+        validate_namespace_name(name)?;
+        if self.is_reserved(name) {
+            return Err(Error::InvalidRequest(format!(
+                "Namespace '{}' is reserved",
+                name
+            )));
+        }
+
+        let name = self.normalize_short_code(name);
+        let created_at = Utc::now();
+        let result =
+            sqlx::query("INSERT INTO namespaces (name, owner_id, created_at) VALUES ($1, $2, $3)")
+                .bind(&name)
+                .bind(owner_id)
+                .bind(encode_timestamp(created_at))
+                .execute(self.database.pool())
+                .await;
+
+        match result {
+            Ok(_) => Ok(Namespace {
+                name,
+                owner_id,
+                member_ids: Vec::new(),
+                created_at,
+            }),
+            Err(sqlx::Error::Database(ref database_error))
+                if database_error.is_unique_violation() =>
+            {
+                Err(Error::Conflict(format!(
+                    "Namespace '{}' already exists",
+                    name
+                )))
+            }
+            Err(error) => Err(Error::Internal(error)),
+        }
+    }
+
+    /// The namespaces the scope may register short codes in: every namespace for
+    /// [`LinkScope::All`], otherwise the ones a user owns or is a member of.
+    pub async fn namespaces(&self, scope: LinkScope) -> Result<Vec<Namespace>, Error> {
+        let rows = sqlx::query(
+            "SELECT id, name, owner_id, created_at FROM namespaces
+            WHERE $1 IS NULL OR owner_id = $1
+                OR id IN (SELECT namespace_id FROM namespace_members WHERE user_id = $1)
+            ORDER BY name",
+        )
+        .bind(scope.owner_id())
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?;
+
+        let mut namespaces = Vec::with_capacity(rows.len());
+        for row in rows {
+            let member_ids = sqlx::query(
+                "SELECT user_id FROM namespace_members WHERE namespace_id = $1 ORDER BY user_id",
+            )
+            .bind(row.get::<i64, &str>("id"))
+            .fetch_all(self.database.pool())
+            .await
+            .map_err(Error::Internal)?
+            .iter()
+            .map(|member_row| member_row.get::<i64, &str>("user_id"))
+            .collect();
 
+            namespaces.push(Namespace {
+                name: row.get("name"),
+                owner_id: row.get("owner_id"),
+                member_ids,
+                created_at: get_timestamp(&row, "created_at").unwrap_or_default(),
+            });
+        }
+        Ok(namespaces)
+    }
+
+    /// Lets a user register short codes in a namespace. Only the namespace's owner and API keys
+    /// may change its members.
+    pub async fn add_namespace_member(
+        &self,
+        name: &str,
+        user_id: i64,
+        scope: LinkScope,
+    ) -> Result<(), Error> {
+        let namespace_id = self.find_managed_namespace(name, scope).await?;
+        let user_row = sqlx::query("SELECT 1 FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(self.database.pool())
+            .await
+            .map_err(Error::Internal)?;
+        if user_row.is_none() {
+            return Err(Error::InvalidRequest(format!(
+                "There is no user with ID {}",
+                user_id
+            )));
+        }
+
+        sqlx::query(
+            "INSERT INTO namespace_members (namespace_id, user_id) VALUES ($1, $2)
+            ON CONFLICT (namespace_id, user_id) DO NOTHING",
+        )
+        .bind(namespace_id)
+        .bind(user_id)
+        .execute(self.database.pool())
+        .await
+        .map_err(Error::Internal)?;
         Ok(())
     }
 
-    pub async fn register(&self, original_url: &str) -> Result<String, Error> {
+    /// Short codes the user already registered in the namespace stay theirs.
+    pub async fn remove_namespace_member(
+        &self,
+        name: &str,
+        user_id: i64,
+        scope: LinkScope,
+    ) -> Result<(), Error> {
+        let namespace_id = self.find_managed_namespace(name, scope).await?;
+        sqlx::query("DELETE FROM namespace_members WHERE namespace_id = $1 AND user_id = $2")
+            .bind(namespace_id)
+            .bind(user_id)
+            .execute(self.database.pool())
+            .await
+            .map_err(Error::Internal)?;
+        Ok(())
+    }
+
+    /// Returns the ID of a namespace whose members the scope may change.
+    async fn find_managed_namespace(&self, name: &str, scope: LinkScope) -> Result<i64, Error> {
+        let row = sqlx::query("SELECT id, owner_id FROM namespaces WHERE name = $1")
+            .bind(self.normalize_short_code(name))
+            .fetch_optional(self.database.pool())
+            .await
+            .map_err(Error::Internal)?
+            .ok_or(Error::NotFound)?;
+
+        if scope.allows(row.get::<Option<i64>, &str>("owner_id")) {
+            Ok(row.get::<i64, &str>("id"))
+        } else {
+            Err(Error::Forbidden(format!(
+                "Only the owner of namespace '{}' can change its members",
+                name
+            )))
+        }
+    }
+
+    pub async fn create_campaign(
+        &self,
+        name: &str,
+        owner_id: Option<i64>,
+    ) -> Result<Campaign, Error> {
+        validate_campaign_name(name)?;
+
+        let created_at = Utc::now();
+        let result =
+            sqlx::query("INSERT INTO campaigns (name, owner_id, created_at) VALUES ($1, $2, $3)")
+                .bind(name)
+                .bind(owner_id)
+                .bind(encode_timestamp(created_at))
+                .execute(self.database.pool())
+                .await;
+
+        match result {
+            Ok(_) => Ok(Campaign {
+                name: name.to_string(),
+                owner_id,
+                link_count: 0,
+                created_at,
+            }),
+            Err(sqlx::Error::Database(ref database_error))
+                if database_error.is_unique_violation() =>
+            {
+                Err(Error::Conflict(format!(
+                    "Campaign '{}' already exists",
+                    name
+                )))
+            }
+            Err(error) => Err(Error::Internal(error)),
+        }
+    }
+
+    /// Every campaign for [`LinkScope::All`], otherwise the ones a user created.
+    pub async fn campaigns(&self, scope: LinkScope) -> Result<Vec<Campaign>, Error> {
+        let campaigns = sqlx::query(
+            "SELECT name, owner_id, created_at,
+                (SELECT COUNT(*) FROM short_urls WHERE campaign_id = campaigns.id) AS link_count
+            FROM campaigns WHERE $1 IS NULL OR owner_id = $1 ORDER BY name",
+        )
+        .bind(scope.owner_id())
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?
+        .iter()
+        .map(|row| Campaign {
+            name: row.get("name"),
+            owner_id: row.get("owner_id"),
+            link_count: row.get("link_count"),
+            created_at: get_timestamp(row, "created_at").unwrap_or_default(),
+        })
+        .collect();
+        Ok(campaigns)
+    }
+
+    /// Moves a short URL into a campaign, out of the one it was in before. Both have to be in the
+    /// scope.
+    pub async fn add_campaign_link(
+        &self,
+        name: &str,
+        short_code: &str,
+        scope: LinkScope,
+    ) -> Result<(), Error> {
+        let campaign_id = self.find_campaign(name, scope).await?;
+        let short_url_id = self
+            .find_short_url_in_scope(short_code, scope)
+            .await?
+            .get::<i64, &str>("id");
+
+        sqlx::query("UPDATE short_urls SET campaign_id = $1 WHERE id = $2")
+            .bind(campaign_id)
+            .bind(short_url_id)
+            .execute(self.database.pool())
+            .await
+            .map_err(Error::Internal)?;
+        Ok(())
+    }
+
+    /// Fails with [`Error::NotFound`] if the short URL isn't in the campaign.
+    pub async fn remove_campaign_link(
+        &self,
+        name: &str,
+        short_code: &str,
+        scope: LinkScope,
+    ) -> Result<(), Error> {
+        let campaign_id = self.find_campaign(name, scope).await?;
+        let short_url_id = self
+            .find_short_url_in_scope(short_code, scope)
+            .await?
+            .get::<i64, &str>("id");
+
+        let result = sqlx::query(
+            "UPDATE short_urls SET campaign_id = NULL WHERE id = $1 AND campaign_id = $2",
+        )
+        .bind(short_url_id)
+        .bind(campaign_id)
+        .execute(self.database.pool())
+        .await
+        .map_err(Error::Internal)?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    /// The short URLs in a campaign, newest first.
+    pub async fn campaign_links(
+        &self,
+        name: &str,
+        page: u32,
+        per_page: u32,
+        scope: LinkScope,
+    ) -> Result<LinkPage, Error> {
+        let campaign_id = self.find_campaign(name, scope).await?;
+
+        let total = sqlx::query("SELECT COUNT(*) AS total FROM short_urls WHERE campaign_id = $1")
+            .bind(campaign_id)
+            .fetch_one(self.database.pool())
+            .await
+            .map_err(Error::Internal)?
+            .get::<i64, &str>("total");
+
+        let links = sqlx::query(
+            "SELECT short_urls.short_code, short_urls.original_url,
+                CAST(short_urls.created_at AS TEXT) AS created_at, short_urls.expires_at,
+                short_urls.redirect_type, short_urls.max_clicks, short_urls.deleted_at, short_urls.domain,
+                short_urls.click_count, short_urls.page_title, short_urls.favicon_url,
+                short_urls.health_checked_at, short_urls.health_status, short_urls.health_latency_ms,
+                short_urls.health_error, short_urls.health_failures, short_urls.broken_since
+            FROM short_urls
+            WHERE short_urls.campaign_id = $1
+            ORDER BY short_urls.id DESC
+            LIMIT $2 OFFSET $3",
+        )
+        .bind(campaign_id)
+        .bind(per_page as i64)
+        .bind(page.saturating_sub(1) as i64 * per_page as i64)
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?
+        .iter()
+        .map(link_summary_from_row)
+        .collect();
+
+        Ok(LinkPage {
+            links,
+            page,
+            per_page,
+            total,
+        })
+    }
+
+    pub async fn campaign_stats(
+        &self,
+        name: &str,
+        scope: LinkScope,
+    ) -> Result<CampaignStats, Error> {
+        let campaign_id = self.find_campaign(name, scope).await?;
+
+        let links = sqlx::query(
+            "SELECT COUNT(*) AS link_count, CAST(COALESCE(SUM(click_count), 0) AS BIGINT) AS click_count
+            FROM short_urls WHERE campaign_id = $1",
+        )
+        .bind(campaign_id)
+        .fetch_one(self.database.pool())
+        .await
+        .map_err(Error::Internal)?;
+
+        let summary = sqlx::query(
+            "SELECT COUNT(*) AS total_clicks, COUNT(DISTINCT clicks.ip_hash) AS unique_visitors,
+            MIN(clicks.clicked_at) AS first_clicked_at, MAX(clicks.clicked_at) AS last_clicked_at
+            FROM clicks JOIN short_urls ON short_urls.id = clicks.short_url_id
+            WHERE short_urls.campaign_id = $1",
+        )
+        .bind(campaign_id)
+        .fetch_one(self.database.pool())
+        .await
+        .map_err(Error::Internal)?;
+
+        let clicks_per_day = sqlx::query(
+            "SELECT substr(clicks.clicked_at, 1, 10) AS date, COUNT(*) AS clicks
+            FROM clicks JOIN short_urls ON short_urls.id = clicks.short_url_id
+            WHERE short_urls.campaign_id = $1
+            GROUP BY substr(clicks.clicked_at, 1, 10) ORDER BY date",
+        )
+        .bind(campaign_id)
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?
+        .iter()
+        .filter_map(|row| {
+            let date = NaiveDate::parse_from_str(&row.get::<String, &str>("date"), "%Y-%m-%d");
+            Some(DailyClicks {
+                date: date.ok()?,
+                clicks: row.get::<i64, &str>("clicks"),
+            })
+        })
+        .collect();
+
+        let top_links = sqlx::query(
+            "SELECT short_urls.short_code, COUNT(*) AS clicks
+            FROM clicks JOIN short_urls ON short_urls.id = clicks.short_url_id
+            WHERE short_urls.campaign_id = $1
+            GROUP BY short_urls.short_code ORDER BY clicks DESC, short_urls.short_code LIMIT $2",
+        )
+        .bind(campaign_id)
+        .bind(TOP_CAMPAIGN_LINKS_LIMIT)
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?
+        .iter()
+        .map(|row| LinkClicks {
+            short_code: row.get::<String, &str>("short_code"),
+            clicks: row.get::<i64, &str>("clicks"),
+        })
+        .collect();
+
+        let top_referrers = sqlx::query(
+            "SELECT clicks.referrer, COUNT(*) AS clicks
+            FROM clicks JOIN short_urls ON short_urls.id = clicks.short_url_id
+            WHERE short_urls.campaign_id = $1 AND clicks.referrer IS NOT NULL
+            GROUP BY clicks.referrer ORDER BY clicks DESC, clicks.referrer LIMIT $2",
+        )
+        .bind(campaign_id)
+        .bind(TOP_REFERRERS_LIMIT)
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?
+        .iter()
+        .map(|row| ReferrerClicks {
+            referrer: row.get::<String, &str>("referrer"),
+            clicks: row.get::<i64, &str>("clicks"),
+        })
+        .collect();
+
+        Ok(CampaignStats {
+            name: name.to_string(),
+            link_count: links.get::<i64, &str>("link_count"),
+            click_count: links.get::<i64, &str>("click_count"),
+            total_clicks: summary.get::<i64, &str>("total_clicks"),
+            unique_visitors: summary.get::<i64, &str>("unique_visitors"),
+            first_clicked_at: get_timestamp(&summary, "first_clicked_at"),
+            last_clicked_at: get_timestamp(&summary, "last_clicked_at"),
+            clicks_per_day,
+            top_links,
+            top_referrers,
+        })
+    }
+
+    /// Returns the ID of a campaign, treating campaigns outside of the scope as non-existent.
+    async fn find_campaign(&self, name: &str, scope: LinkScope) -> Result<i64, Error> {
+        let row = sqlx::query("SELECT id, owner_id FROM campaigns WHERE name = $1")
+            .bind(name)
+            .fetch_optional(self.database.pool())
+            .await
+            .map_err(Error::Internal)?
+            .ok_or(Error::NotFound)?;
+
+        if scope.allows(row.get::<Option<i64>, &str>("owner_id")) {
+            Ok(row.get::<i64, &str>("id"))
+        } else {
+            Err(Error::NotFound)
+        }
+    }
+
+    /// `owner_id` is the user registering a short code, or `None` for API keys, which may use
+    /// every namespace.
+    async fn check_namespace_access(
+        &self,
+        connection: &mut AnyConnection,
+        namespace: &str,
+        owner_id: Option<i64>,
+    ) -> Result<(), Error> {
+        let row = sqlx::query("SELECT id, owner_id FROM namespaces WHERE name = $1")
+            .bind(namespace)
+            .fetch_optional(&mut *connection)
+            .await
+            .map_err(Error::Internal)?
+            .ok_or_else(|| {
+                Error::InvalidRequest(format!("Namespace '{}' doesn't exist", namespace))
+            })?;
+        let Some(user_id) = owner_id else {
+            return Ok(());
+        };
+        if row.get::<Option<i64>, &str>("owner_id") == Some(user_id) {
+            return Ok(());
+        }
+
+        let member_row =
+            sqlx::query("SELECT 1 FROM namespace_members WHERE namespace_id = $1 AND user_id = $2")
+                .bind(row.get::<i64, &str>("id"))
+                .bind(user_id)
+                .fetch_optional(&mut *connection)
+                .await
+                .map_err(Error::Internal)?;
+        if member_row.is_some() {
+            Ok(())
+        } else {
+            Err(Error::Forbidden(format!(
+                "You have no access to namespace '{}'",
+                namespace
+            )))
+        }
+    }
+
+    /// Returns the short code, and whether a new short URL was created for it rather than an
+    /// existing one reused.
+    async fn register_with(
+        &self,
+        connection: &mut AnyConnection,
+        new_short_url: &NewShortUrl,
+    ) -> Result<(String, bool), Error> {
+        self.validate_original_url(&new_short_url.original_url)?;
+        let geo_targets = self.validate_geo_targets(&new_short_url.geo_targets)?;
+        self.validate_variants(&new_short_url.variants)?;
+        self.validate_device_targets(&new_short_url.device_targets)?;
+        validate_tracking_params(&new_short_url.tracking_params)?;
+        if let Some(domain) = &new_short_url.domain {
+            if !self.domains.is_extra(domain) {
+                return Err(Error::InvalidRequest(format!(
+                    "'{}' is not a domain of this service",
+                    domain
+                )));
+            }
+        }
+
+        if let Some(expires_at) = new_short_url.expires_at {
+            if expires_at <= Utc::now() {
+                return Err(Error::InvalidExpiration(
+                    "Expiration time must be in the future".to_string(),
+                ));
+            }
+        }
+
+        if new_short_url
+            .max_clicks
+            .is_some_and(|max_clicks| max_clicks < 1)
+        {
+            return Err(Error::InvalidRequest(
+                "max_clicks must be at least 1".to_string(),
+            ));
+        }
+
+        if let Some(short_code_length) = new_short_url.short_code_length {
+            self.validate_short_code_length(new_short_url, short_code_length)?;
+        }
+
+        if let Some(namespace) = &new_short_url.namespace {
+            self.check_namespace_access(connection, namespace, new_short_url.owner_id)
+                .await?;
+        }
+
+        let can_reuse = new_short_url.reuse_existing
+            && new_short_url.custom_code.is_none()
+            && new_short_url.expires_at.is_none()
+            && new_short_url.max_clicks.is_none()
+            && geo_targets.is_empty()
+            && new_short_url.variants.is_empty()
+            && new_short_url.device_targets.is_empty()
+            && new_short_url.tracking_params.is_empty()
+            && new_short_url.short_code_length.is_none()
+            && new_short_url.namespace.is_none();
+        if can_reuse {
+            if let Some(short_code) = self
+                .find_reusable_short_code(connection, new_short_url)
+                .await?
+            {
+                return Ok((short_code, false));
+            }
+        }
+
+        let short_code = match &new_short_url.custom_code {
+            Some(custom_code) => {
+                self.register_custom_code(connection, new_short_url, custom_code)
+                    .await
+            }
+            None => match self.short_code_strategy {
+                ShortCodeStrategy::Random => {
+                    self.register_random_code(connection, new_short_url).await
+                }
+                ShortCodeStrategy::Counter => {
+                    self.register_counter_code(connection, new_short_url).await
+                }
+            },
+        }?;
+
+        if !geo_targets.is_empty()
+            || !new_short_url.variants.is_empty()
+            || !new_short_url.device_targets.is_empty()
+        {
+            let short_url_id = sqlx::query("SELECT id FROM short_urls WHERE short_code = $1")
+                .bind(&short_code)
+                .fetch_one(&mut *connection)
+                .await
+                .map_err(Error::Internal)?
+                .get::<i64, &str>("id");
+            insert_geo_targets(connection, short_url_id, &geo_targets).await?;
+            insert_variants(connection, short_url_id, &new_short_url.variants).await?;
+            insert_device_targets(connection, short_url_id, &new_short_url.device_targets).await?;
+        }
+
+        Ok((short_code, true))
+    }
+
+    fn validate_short_code_length(
+        &self,
+        new_short_url: &NewShortUrl,
+        short_code_length: usize,
+    ) -> Result<(), Error> {
+        if new_short_url.custom_code.is_some() {
+            return Err(Error::InvalidRequest(
+                "short_code_length can't be combined with custom_code".to_string(),
+            ));
+        }
+        if matches!(self.short_code_strategy, ShortCodeStrategy::Counter) {
+            return Err(Error::InvalidRequest(
+                "short_code_length only applies to random short codes".to_string(),
+            ));
+        }
+        if !self
+            .requestable_short_code_lengths
+            .contains(&short_code_length)
+        {
+            return Err(Error::InvalidRequest(format!(
+                "short_code_length must be between {} and {}",
+                self.requestable_short_code_lengths.start(),
+                self.requestable_short_code_lengths.end()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Only accepts absolute http(s) URLs that don't point back at the shortener itself and pass the
+    /// safety check.
+    fn validate_original_url(&self, original_url: &str) -> Result<(), Error> {
+        if original_url.len() > MAX_ORIGINAL_URL_LENGTH {
+            return Err(Error::InvalidUrl(format!(
+                "URL must not be longer than {} characters",
+                MAX_ORIGINAL_URL_LENGTH
+            )));
+        }
+
+        let url = Url::parse(original_url)
+            .map_err(|error| Error::InvalidUrl(format!("Invalid URL: {}", error)))?;
+
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(Error::InvalidUrl(
+                "Only http and https URLs can be shortened".to_string(),
+            ));
+        }
+
+        if url.host_str().is_none() {
+            return Err(Error::InvalidUrl("URL must have a host".to_string()));
+        }
+
+        if self.domains.serves(&url) {
+            return Err(Error::InvalidUrl(
+                "URL must not point to the URL shortener itself".to_string(),
+            ));
+        }
+
+        if let UrlVerdict::Unsafe(reason) = self.safety_checker.check(&url) {
+            warn!("Rejected unsafe URL {}: {}", original_url, reason);
+            return Err(Error::UnsafeUrl(format!(
+                "URL cannot be shortened: {}",
+                reason
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Validates every destination and returns the targets keyed by upper case country codes.
+    fn validate_geo_targets(
+        &self,
+        geo_targets: &BTreeMap<String, String>,
+    ) -> Result<BTreeMap<String, String>, Error> {
+        geo_targets
+            .iter()
+            .map(|(country, original_url)| {
+                if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+                    return Err(Error::InvalidRequest(format!(
+                        "'{}' is not a two-letter ISO 3166-1 country code",
+                        country
+                    )));
+                }
+                self.validate_original_url(original_url)?;
+                Ok((country.to_uppercase(), original_url.clone()))
+            })
+            .collect()
+    }
+
+    /// Besides http(s) URLs, iOS and Android destinations may be app deep links.
+    fn validate_device_targets(
+        &self,
+        device_targets: &BTreeMap<DeviceType, String>,
+    ) -> Result<(), Error> {
+        for (device, original_url) in device_targets {
+            let is_app = matches!(device, DeviceType::Ios | DeviceType::Android);
+            match Url::parse(original_url) {
+                Ok(url) if is_app && url.scheme() != "http" && url.scheme() != "https" => {
+                    if UNSAFE_DEEP_LINK_SCHEMES.contains(&url.scheme()) {
+                        return Err(Error::InvalidUrl(format!(
+                            "{} URLs can't be used as deep links",
+                            url.scheme()
+                        )));
+                    }
+                    if original_url.len() > MAX_ORIGINAL_URL_LENGTH {
+                        return Err(Error::InvalidUrl(format!(
+                            "URL must not be longer than {} characters",
+                            MAX_ORIGINAL_URL_LENGTH
+                        )));
+                    }
+                }
+                _ => self.validate_original_url(original_url)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_variants(&self, variants: &[NewVariant]) -> Result<(), Error> {
+        for variant in variants {
+            self.validate_original_url(&variant.original_url)?;
+            if !(1..=100).contains(&variant.weight) {
+                return Err(Error::InvalidRequest(
+                    "Variant weights must be between 1 and 100".to_string(),
+                ));
+            }
+        }
+        if variants.iter().map(|variant| variant.weight).sum::<i64>() > 100 {
+            return Err(Error::InvalidRequest(
+                "Variant weights must not add up to more than 100".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn find_reusable_short_code(
+        &self,
+        connection: &mut AnyConnection,
+        new_short_url: &NewShortUrl,
+    ) -> Result<Option<String>, Error> {
+        let optional_row = sqlx::query(
+            "SELECT short_code FROM short_urls
+            WHERE original_url = $1 AND owner_id IS NOT DISTINCT FROM $2 AND expires_at IS NULL
+                AND redirect_type = $3 AND deleted_at IS NULL AND domain IS NOT DISTINCT FROM $4
+            ORDER BY id LIMIT 1",
+        )
+        .bind(&new_short_url.original_url)
+        .bind(new_short_url.owner_id)
+        .bind(new_short_url.redirect_type.as_str())
+        .bind(new_short_url.domain.as_deref())
+        .fetch_optional(&mut *connection)
+        .await
+        .map_err(Error::Internal)?;
+
+        Ok(optional_row.map(|row| row.get::<String, &str>("short_code")))
+    }
+
+    async fn register_custom_code(
+        &self,
+        connection: &mut AnyConnection,
+        new_short_url: &NewShortUrl,
+        custom_code: &str,
+    ) -> Result<String, Error> {
+        validate_custom_code(custom_code)?;
+        if self.is_reserved(custom_code) {
+            return Err(Error::InvalidShortCode(format!(
+                "Short code '{}' is reserved",
+                custom_code
+            )));
+        }
+
+        let custom_code = namespaced(new_short_url, self.normalize_short_code(custom_code));
+        let taken_error =
+            || Error::Conflict(format!("Short code '{}' is already taken", custom_code));
+
+        // The unique constraint is case-sensitive, so it doesn't catch codes registered in a
+        // different case before case-insensitive short codes were turned on.
+        if self.case_insensitive_short_codes {
+            let existing_row = sqlx::query("SELECT 1 FROM short_urls WHERE LOWER(short_code) = $1")
+                .bind(&custom_code)
+                .fetch_optional(&mut *connection)
+                .await
+                .map_err(Error::Internal)?;
+            if existing_row.is_some() {
+                return Err(taken_error());
+            }
+        }
+
+        if self
+            .insert_short_url(connection, new_short_url, &custom_code)
+            .await
+            .map_err(Error::Internal)?
+        {
+            Ok(custom_code)
+        } else {
+            Err(taken_error())
+        }
+    }
+
+    async fn register_random_code(
+        &self,
+        connection: &mut AnyConnection,
+        new_short_url: &NewShortUrl,
+    ) -> Result<String, Error> {
+        if let Some(short_code_length) = new_short_url.short_code_length {
+            return self
+                .register_random_code_of_length(connection, new_short_url, short_code_length)
+                .await;
+        }
+
+        loop {
+            let short_code_length = self.get_short_code_length(connection).await?;
+
+            for _ in 0..3 {
+                let short_code = namespaced(
+                    new_short_url,
+                    self.generate_unreserved_short_code(short_code_length),
+                );
+                if self
+                    .insert_short_url(connection, new_short_url, &short_code)
+                    .await
+                    .map_err(Error::Internal)?
+                {
+                    return Ok(short_code);
+                }
+            }
+
+            self.save_short_code_length(connection, short_code_length + 1)
+                .await?;
+        }
+    }
+
+    /// Unlike the default length, a requested length doesn't grow when codes collide, since the
+    /// client asked for exactly that length.
+    async fn register_random_code_of_length(
+        &self,
+        connection: &mut AnyConnection,
+        new_short_url: &NewShortUrl,
+        short_code_length: usize,
+    ) -> Result<String, Error> {
+        for _ in 0..REQUESTED_LENGTH_ATTEMPTS {
+            let short_code = namespaced(
+                new_short_url,
+                self.generate_unreserved_short_code(short_code_length),
+            );
+            if self
+                .insert_short_url(connection, new_short_url, &short_code)
+                .await
+                .map_err(Error::Internal)?
+            {
+                return Ok(short_code);
+            }
+        }
+
+        Err(Error::Conflict(format!(
+            "No free short code of length {} was found, try a longer one",
+            short_code_length
+        )))
+    }
+
+    async fn register_counter_code(
+        &self,
+        connection: &mut AnyConnection,
+        new_short_url: &NewShortUrl,
+    ) -> Result<String, Error> {
         loop {
-            let short_code_length = self.get_short_code_length().await?;
+            let counter = self.next_short_code_counter(connection).await?;
+            let short_code = encode_counter(self.short_code_characters, counter);
+            if self.is_reserved(&short_code) {
+                continue;
+            }
+            let short_code = namespaced(new_short_url, short_code);
 
-            for _ in 0..3 {
-                let short_code = generate_short_code(short_code_length);
-                let result =
-                    sqlx::query("INSERT INTO short_urls (original_url, short_code) VALUES (?, ?)")
-                        .bind(&original_url)
-                        .bind(&short_code)
-                        .execute(&self.pool)
-                        .await;
-
-                match result {
-                    Ok(_) => {
-                        return Ok(short_code);
-                    }
-                    Err(error) => {
-                        if let sqlx::Error::Database(ref database_error) = error {
-                            if database_error.is_unique_violation() {
-                                continue;
-                            }
-                        }
-                        return Err(Error::Internal(error));
-                    }
-                }
+            // Otherwise the code was already registered as a custom code.
+            if self
+                .insert_short_url(connection, new_short_url, &short_code)
+                .await
+                .map_err(Error::Internal)?
+            {
+                return Ok(short_code);
             }
+        }
+    }
+
+    /// Increments the counter and returns its new value in a single statement, so concurrent
+    /// registrations never get the same value.
+    async fn next_short_code_counter(&self, connection: &mut AnyConnection) -> Result<u64, Error> {
+        let value = sqlx::query(
+            "INSERT INTO settings (key, value) VALUES ('short_code_counter', '1')
+            ON CONFLICT (key) DO UPDATE SET value = CAST(CAST(settings.value AS BIGINT) + 1 AS TEXT)
+            RETURNING value",
+        )
+        .fetch_one(&mut *connection)
+        .await
+        .map_err(Error::Internal)?
+        .get::<String, &str>("value");
 
-            self.save_short_code_length(short_code_length + 1).await?;
+        value.parse::<u64>().map_err(|error| {
+            Error::Internal(sqlx::Error::Decode(
+                format!("Invalid short_code_counter value '{}': {}", value, error).into(),
+            ))
+        })
+    }
+
+    fn generate_unreserved_short_code(&self, length: usize) -> String {
+        loop {
+            let short_code = short_code::generate(self.short_code_characters, length);
+            if !self.is_reserved(&short_code) {
+                return short_code;
+            }
         }
     }
 
-    async fn get_short_code_length(&self) -> Result<usize, Error> {
-        let optional_row = sqlx::query("SELECT * FROM settings WHERE key = 'short_code_length'")
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|error| Error::Internal(error))?;
+    fn is_reserved(&self, short_code: &str) -> bool {
+        self.reserved_short_codes
+            .contains(&short_code.to_lowercase())
+    }
+
+    /// Returns whether the short URL was inserted, or `false` when the code is taken. A taken code
+    /// isn't an error, which would abort the transaction the insert is part of on PostgreSQL,
+    /// so the callers can go on to try another code.
+    async fn insert_short_url(
+        &self,
+        connection: &mut AnyConnection,
+        new_short_url: &NewShortUrl,
+        short_code: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO short_urls (original_url, short_code, created_at, expires_at, owner_id, redirect_type, max_clicks, remaining_clicks, domain, tracking_params)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $7, $8, $9)
+            ON CONFLICT (short_code) DO NOTHING",
+        )
+        .bind(&new_short_url.original_url)
+        .bind(short_code)
+        .bind(encode_timestamp(Utc::now()))
+        .bind(new_short_url.expires_at.map(encode_timestamp))
+        .bind(new_short_url.owner_id)
+        .bind(new_short_url.redirect_type.as_str())
+        .bind(new_short_url.max_clicks)
+        .bind(new_short_url.domain.as_deref())
+        .bind(encode_tracking_params(&new_short_url.tracking_params))
+        .execute(&mut *connection)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn get_short_code_length(&self, connection: &mut AnyConnection) -> Result<usize, Error> {
+        let optional_row =
+            sqlx::query("SELECT value FROM settings WHERE key = 'short_code_length'")
+                .fetch_optional(&mut *connection)
+                .await
+                .map_err(Error::Internal)?;
 
         match optional_row {
             Some(row) => {
                 let value_string = row.get::<String, &str>("value");
                 let value = value_string.parse::<usize>().unwrap_or_else(|error| {
-                    println!("Error parsing short_code_length value: {}", error);
-                    DEFAULT_SHORT_CODE_LENGTH
+                    warn!("Error parsing short_code_length value: {}", error);
+                    self.initial_short_code_length
                 });
-                Ok(value)
+                // Raising the configured length also applies once codes have grown on their own.
+                Ok(value.max(self.initial_short_code_length))
             }
-            None => Ok(DEFAULT_SHORT_CODE_LENGTH),
+            None => Ok(self.initial_short_code_length),
         }
     }
 
-    async fn save_short_code_length(&self, length: usize) -> Result<(), Error> {
-        let _ = sqlx::query("INSERT INTO settings (key, value) VALUES ('short_code_length', ?) ON CONFLICT DO UPDATE SET value = excluded.value")
+    async fn save_short_code_length(
+        &self,
+        connection: &mut AnyConnection,
+        length: usize,
+    ) -> Result<(), Error> {
+        sqlx::query("INSERT INTO settings (key, value) VALUES ('short_code_length', $1) ON CONFLICT (key) DO UPDATE SET value = excluded.value")
             .bind(length.to_string())
-            .execute(&self.pool)
-            .await.map_err(|error| Error::Internal(error));
+            .execute(&mut *connection)
+            .await
+            .map_err(Error::Internal)?;
 
-        println!("short_code_length has been changed to {}", length);
+        info!("short_code_length has been changed to {}", length);
         Ok(())
     }
 
     pub async fn lookup(&self, short_code: &str) -> Result<String, Error> {
-        let optional_row = sqlx::query("SELECT * FROM short_urls WHERE short_code = ?")
-            .bind(short_code)
-            .fetch_optional(&self.pool)
+        Ok(self
+            .find_visitable_short_url(short_code)
+            .await?
+            .original_url)
+    }
+
+    /// Like `lookup`, but only finds short URLs served on `domain`.
+    pub async fn lookup_on(&self, short_code: &str, domain: Option<&str>) -> Result<String, Error> {
+        let short_url = self.find_visitable_short_url(short_code).await?;
+        check_domain(&short_url, domain)?;
+        Ok(short_url.original_url)
+    }
+
+    /// Resolves where a visitor would be redirected to without counting a click, for the preview
+    /// page. Split tests aren't run, so their visitors see the original URL.
+    pub async fn preview(&self, short_code: &str, visitor: &Visitor<'_>) -> Result<Preview, Error> {
+        let mut short_url = self.find_visitable_short_url(short_code).await?;
+        check_domain(&short_url, visitor.domain)?;
+        let original_url = self
+            .targeted_url(&mut short_url, visitor)
+            .unwrap_or_else(|| short_url.original_url.clone());
+
+        // Page titles aren't cached, since they arrive after the short URL was first visited.
+        let page_title = sqlx::query("SELECT page_title FROM short_urls WHERE id = $1")
+            .bind(short_url.id)
+            .fetch_optional(self.database.pool())
             .await
-            .map_err(|error| Error::Internal(error))?;
+            .map_err(Error::Internal)?
+            .and_then(|row| row.get::<Option<String>, &str>("page_title"));
 
-        match optional_row {
-            Some(row) => Ok(row.get::<String, &str>("original_url")),
-            None => Err(Error::NotFound),
+        Ok(Preview {
+            original_url: append_tracking_params(&original_url, &short_url.tracking_params),
+            page_title,
+        })
+    }
+
+    /// Increments the hit counter of a short URL without making the redirect wait for it. A single
+    /// `UPDATE` keeps concurrent redirects from losing hits.
+    fn count_hit(&self, short_url_id: i64) {
+        let database = self.database.clone();
+        tokio::spawn(async move {
+            let result =
+                sqlx::query("UPDATE short_urls SET click_count = click_count + 1 WHERE id = $1")
+                    .bind(short_url_id)
+                    .execute(database.pool())
+                    .await;
+            if let Err(error) = result {
+                error!(
+                    "Failed to count a hit of short URL {}: {}",
+                    short_url_id, error
+                );
+            }
+        });
+    }
+
+    /// Like `find_active_short_url`, but also fails for short URLs that used up their clicks.
+    async fn find_visitable_short_url(&self, short_code: &str) -> Result<CachedShortUrl, Error> {
+        let short_url = self.find_active_short_url(short_code).await?;
+
+        if short_url.max_clicks.is_some() {
+            // Clicks are counted down on the primary, where a replica may not have caught up.
+            let remaining_clicks =
+                sqlx::query("SELECT remaining_clicks FROM short_urls WHERE id = $1")
+                    .bind(short_url.id)
+                    .fetch_optional(self.database.pool())
+                    .await
+                    .map_err(Error::Internal)?
+                    .ok_or(Error::NotFound)?
+                    .get::<Option<i64>, &str>("remaining_clicks");
+            if remaining_clicks.is_some_and(|remaining_clicks| remaining_clicks <= 0) {
+                return Err(Error::Expired);
+            }
+        }
+
+        Ok(short_url)
+    }
+
+    /// Resolves a short code for a redirect, counting down the remaining clicks of a click-limited
+    /// short URL and counting the hit in the background. The click itself is logged separately by
+    /// a `ClickRecorder`.
+    pub async fn visit(
+        &self,
+        short_code: &str,
+        visitor: &Visitor<'_>,
+    ) -> Result<Destination, Error> {
+        let mut short_url = self.find_active_short_url(short_code).await?;
+        check_domain(&short_url, visitor.domain)?;
+
+        let mut used_up = false;
+        if short_url.max_clicks.is_some() {
+            // Checking and counting down in one statement keeps concurrent redirects from
+            // exceeding the limit.
+            let remaining_clicks: i64 = sqlx::query(
+                "UPDATE short_urls SET remaining_clicks = remaining_clicks - 1
+                WHERE id = $1 AND remaining_clicks > 0 RETURNING remaining_clicks",
+            )
+            .bind(short_url.id)
+            .fetch_optional(self.database.pool())
+            .await
+            .map_err(Error::Internal)?
+            .ok_or(Error::Expired)?
+            .get("remaining_clicks");
+            used_up = remaining_clicks == 0;
+        }
+
+        self.count_hit(short_url.id);
+        self.webhooks
+            .notify(LinkEvent::Clicked, short_code, &short_url.original_url);
+        if used_up {
+            self.webhooks
+                .notify(LinkEvent::Expired, short_code, &short_url.original_url);
+        }
+
+        let varies = short_url.max_clicks.is_some()
+            || !short_url.variants.is_empty()
+            || !short_url.geo_targets.is_empty()
+            || !short_url.device_targets.is_empty();
+
+        // Split tests only run among visitors without a device or geo target.
+        let targeted_url = self.targeted_url(&mut short_url, visitor);
+        let variant = if targeted_url.is_some() {
+            None
+        } else {
+            pick_variant(short_url.variants)
+        };
+
+        let original_url = match variant {
+            Some(ref variant) => &variant.original_url,
+            None => targeted_url.as_ref().unwrap_or(&short_url.original_url),
+        };
+        Ok(Destination {
+            short_url_id: short_url.id,
+            variant_id: variant.as_ref().map(|variant| variant.id),
+            original_url: append_tracking_params(original_url, &short_url.tracking_params),
+            redirect_type: short_url.redirect_type,
+            expires_at: short_url.expires_at,
+            varies,
+        })
+    }
+
+    /// Device targets win over geo targets, because a deep link only works on its own platform.
+    fn targeted_url(
+        &self,
+        short_url: &mut CachedShortUrl,
+        visitor: &Visitor<'_>,
+    ) -> Option<String> {
+        visitor
+            .user_agent
+            .and_then(|user_agent| {
+                DeviceType::matching(user_agent)
+                    .iter()
+                    .find_map(|device| short_url.device_targets.remove(device))
+            })
+            .or_else(|| {
+                // Only short URLs with geo targets pay for the GeoIP lookup.
+                if short_url.geo_targets.is_empty() {
+                    return None;
+                }
+                self.geoip
+                    .country(visitor.ip)
+                    .and_then(|country| short_url.geo_targets.remove(&country))
+            })
+    }
+
+    pub async fn record_click(&self, click: &ClickEvent) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO clicks (short_url_id, variant_id, clicked_at, referrer, user_agent, ip_hash)
+            VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(click.short_url_id)
+        .bind(click.variant_id)
+        .bind(encode_timestamp(click.clicked_at))
+        .bind(click.referrer.as_deref())
+        .bind(click.user_agent.as_deref())
+        .bind(&click.ip_hash)
+        .execute(self.database.pool())
+        .await
+        .map_err(Error::Internal)?;
+        Ok(())
+    }
+
+    pub async fn stats(&self, short_code: &str, scope: LinkScope) -> Result<ClickStats, Error> {
+        let short_url = self.find_short_url_in_scope(short_code, scope).await?;
+        let short_url_id = short_url.get::<i64, &str>("id");
+
+        let summary = sqlx::query(
+            "SELECT COUNT(*) AS total_clicks, COUNT(DISTINCT ip_hash) AS unique_visitors,
+            MIN(clicked_at) AS first_clicked_at, MAX(clicked_at) AS last_clicked_at,
+            (SELECT click_count FROM short_urls WHERE id = $1) AS click_count
+            FROM clicks WHERE short_url_id = $1",
+        )
+        .bind(short_url_id)
+        .fetch_one(self.database.pool())
+        .await
+        .map_err(Error::Internal)?;
+
+        let clicks_per_day = sqlx::query(
+            "SELECT substr(clicked_at, 1, 10) AS date, COUNT(*) AS clicks
+            FROM clicks WHERE short_url_id = $1 GROUP BY substr(clicked_at, 1, 10) ORDER BY date",
+        )
+        .bind(short_url_id)
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?
+        .iter()
+        .filter_map(|row| {
+            let date = NaiveDate::parse_from_str(&row.get::<String, &str>("date"), "%Y-%m-%d");
+            Some(DailyClicks {
+                date: date.ok()?,
+                clicks: row.get::<i64, &str>("clicks"),
+            })
+        })
+        .collect();
+
+        let top_referrers = sqlx::query(
+            "SELECT referrer, COUNT(*) AS clicks FROM clicks
+            WHERE short_url_id = $1 AND referrer IS NOT NULL
+            GROUP BY referrer ORDER BY clicks DESC, referrer LIMIT $2",
+        )
+        .bind(short_url_id)
+        .bind(TOP_REFERRERS_LIMIT)
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?
+        .iter()
+        .map(|row| ReferrerClicks {
+            referrer: row.get::<String, &str>("referrer"),
+            clicks: row.get::<i64, &str>("clicks"),
+        })
+        .collect();
+
+        let mut variants: Vec<VariantClicks> = sqlx::query(
+            "SELECT variants.id, variants.original_url, variants.weight, COUNT(clicks.id) AS clicks
+            FROM variants LEFT JOIN clicks ON clicks.variant_id = variants.id
+            WHERE variants.short_url_id = $1
+            GROUP BY variants.id, variants.original_url, variants.weight ORDER BY variants.id",
+        )
+        .bind(short_url_id)
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?
+        .iter()
+        .map(|row| VariantClicks {
+            variant_id: Some(row.get::<i64, &str>("id")),
+            original_url: row.get::<String, &str>("original_url"),
+            weight: row.get::<i64, &str>("weight"),
+            clicks: row.get::<i64, &str>("clicks"),
+        })
+        .collect();
+        if !variants.is_empty() {
+            let original_url_clicks = sqlx::query(
+                "SELECT COUNT(*) AS clicks FROM clicks WHERE short_url_id = $1 AND variant_id IS NULL",
+            )
+            .bind(short_url_id)
+            .fetch_one(self.database.pool())
+            .await
+            .map_err(Error::Internal)?
+            .get::<i64, &str>("clicks");
+            let original_url_weight =
+                100 - variants.iter().map(|variant| variant.weight).sum::<i64>();
+            variants.insert(
+                0,
+                VariantClicks {
+                    variant_id: None,
+                    original_url: short_url.get::<String, &str>("original_url"),
+                    weight: original_url_weight,
+                    clicks: original_url_clicks,
+                },
+            );
+        }
+
+        Ok(ClickStats {
+            click_count: summary.get::<i64, &str>("click_count"),
+            total_clicks: summary.get::<i64, &str>("total_clicks"),
+            unique_visitors: summary.get::<i64, &str>("unique_visitors"),
+            first_clicked_at: get_timestamp(&summary, "first_clicked_at"),
+            last_clicked_at: get_timestamp(&summary, "last_clicked_at"),
+            clicks_per_day,
+            top_referrers,
+            variants,
+            page_title: short_url.get::<Option<String>, &str>("page_title"),
+            favicon_url: short_url.get::<Option<String>, &str>("favicon_url"),
+            health: link_health_from_row(&short_url),
+        })
+    }
+
+    /// Returns the short URL, on the domain it is served on.
+    pub async fn update(
+        &self,
+        short_code: &str,
+        update: &ShortUrlUpdate,
+        scope: LinkScope,
+    ) -> Result<String, Error> {
+        let update = &update.normalized();
+        self.validate_original_url(&update.original_url)?;
+        let geo_targets = update
+            .geo_targets
+            .as_ref()
+            .map(|geo_targets| self.validate_geo_targets(geo_targets))
+            .transpose()?;
+        if let Some(variants) = &update.variants {
+            self.validate_variants(variants)?;
+        }
+        if let Some(device_targets) = &update.device_targets {
+            self.validate_device_targets(device_targets)?;
+        }
+        if let Some(tracking_params) = &update.tracking_params {
+            validate_tracking_params(tracking_params)?;
+        }
+
+        let row = self.find_short_url_in_scope(short_code, scope).await?;
+        let short_url_id = row.get::<i64, &str>("id");
+
+        let mut transaction = self
+            .database
+            .pool()
+            .begin()
+            .await
+            .map_err(Error::Internal)?;
+
+        sqlx::query("UPDATE short_urls SET original_url = $1, updated_at = $2 WHERE id = $3")
+            .bind(&update.original_url)
+            .bind(encode_timestamp(Utc::now()))
+            .bind(short_url_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(Error::Internal)?;
+
+        if let Some(tracking_params) = &update.tracking_params {
+            sqlx::query("UPDATE short_urls SET tracking_params = $1 WHERE id = $2")
+                .bind(encode_tracking_params(tracking_params))
+                .bind(short_url_id)
+                .execute(&mut *transaction)
+                .await
+                .map_err(Error::Internal)?;
+        }
+
+        if let Some(geo_targets) = geo_targets {
+            sqlx::query("DELETE FROM redirect_rules WHERE short_url_id = $1")
+                .bind(short_url_id)
+                .execute(&mut *transaction)
+                .await
+                .map_err(Error::Internal)?;
+            insert_geo_targets(&mut transaction, short_url_id, &geo_targets).await?;
+        }
+
+        // Clicks keep pointing at the variants they were sent to, so replacing the variants
+        // starts a new split test.
+        if let Some(variants) = &update.variants {
+            sqlx::query("DELETE FROM variants WHERE short_url_id = $1")
+                .bind(short_url_id)
+                .execute(&mut *transaction)
+                .await
+                .map_err(Error::Internal)?;
+            insert_variants(&mut transaction, short_url_id, variants).await?;
+        }
+
+        if let Some(device_targets) = &update.device_targets {
+            sqlx::query("DELETE FROM device_targets WHERE short_url_id = $1")
+                .bind(short_url_id)
+                .execute(&mut *transaction)
+                .await
+                .map_err(Error::Internal)?;
+            insert_device_targets(&mut transaction, short_url_id, device_targets).await?;
+        }
+
+        transaction.commit().await.map_err(Error::Internal)?;
+
+        self.invalidate_cache(short_code).await;
+        if update.original_url != row.get::<String, &str>("original_url") {
+            self.metadata_fetcher
+                .fetch(&row.get::<String, &str>("short_code"), &update.original_url);
+        }
+        Ok(self.short_url_of(&row))
+    }
+
+    pub async fn delete(&self, short_code: &str, scope: LinkScope) -> Result<(), Error> {
+        self.set_deleted_at(short_code, scope, Some(Utc::now()))
+            .await?;
+        Ok(())
+    }
+
+    /// Makes a deleted short URL redirect again, and returns it like `update`.
+    pub async fn reactivate(&self, short_code: &str, scope: LinkScope) -> Result<String, Error> {
+        let row = self.set_deleted_at(short_code, scope, None).await?;
+        Ok(self.short_url_of(&row))
+    }
+
+    /// Deleting only deactivates a short URL, which keeps its row and clicks until the cleanup
+    /// task purges it. Returns the row as it was before.
+    async fn set_deleted_at(
+        &self,
+        short_code: &str,
+        scope: LinkScope,
+        deleted_at: Option<DateTime<Utc>>,
+    ) -> Result<AnyRow, Error> {
+        let row = self.find_short_url_in_scope(short_code, scope).await?;
+        let short_url_id = row.get::<i64, &str>("id");
+
+        sqlx::query("UPDATE short_urls SET deleted_at = $1 WHERE id = $2")
+            .bind(deleted_at.map(encode_timestamp))
+            .bind(short_url_id)
+            .execute(self.database.pool())
+            .await
+            .map_err(Error::Internal)?;
+
+        self.invalidate_cache(short_code).await;
+        Ok(row)
+    }
+
+    /// The full short URL of a row read with [`SHORT_URL_COLUMNS`].
+    fn short_url_of(&self, row: &AnyRow) -> String {
+        self.domains.short_url(
+            row.get::<Option<String>, &str>("domain").as_deref(),
+            &row.get::<String, &str>("short_code"),
+        )
+    }
+
+    /// Unlike `lookup`, also describes short URLs that have expired or were deleted.
+    pub async fn details(&self, short_code: &str, scope: LinkScope) -> Result<LinkDetails, Error> {
+        let row = self.find_short_url_in_scope(short_code, scope).await?;
+        let short_url_id = row.get::<i64, &str>("id");
+
+        let details = sqlx::query(
+            "SELECT CAST(created_at AS TEXT) AS created_at, remaining_clicks, click_count
+            FROM short_urls WHERE id = $1",
+        )
+        .bind(short_url_id)
+        .fetch_one(self.database.pool())
+        .await
+        .map_err(Error::Internal)?;
+
+        let expires_at = get_timestamp(&row, "expires_at");
+        let max_clicks = row.get::<Option<i64>, &str>("max_clicks");
+        let used_up = max_clicks.is_some()
+            && details
+                .get::<Option<i64>, &str>("remaining_clicks")
+                .is_some_and(|remaining_clicks| remaining_clicks <= 0);
+        let status = if get_timestamp(&row, "deleted_at").is_some() {
+            LinkStatus::Deactivated
+        } else if used_up || expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            LinkStatus::Expired
+        } else {
+            LinkStatus::Active
+        };
+
+        Ok(LinkDetails {
+            short_code: row.get::<String, &str>("short_code"),
+            short_url: self.short_url_of(&row),
+            original_url: row.get::<String, &str>("original_url"),
+            created_at: get_timestamp(&details, "created_at").unwrap_or_default(),
+            expires_at,
+            max_clicks,
+            click_count: details.get::<i64, &str>("click_count"),
+            status,
+            tracking_params: decode_tracking_params(&row),
+            page_title: row.get::<Option<String>, &str>("page_title"),
+            favicon_url: row.get::<Option<String>, &str>("favicon_url"),
+            health: link_health_from_row(&row),
+        })
+    }
+
+    /// Returns one page of short URLs. Pages are numbered from 1. With `broken_only`, only short
+    /// URLs whose destination counts as broken are listed.
+    pub async fn list(
+        &self,
+        page: u32,
+        per_page: u32,
+        sort: LinkSort,
+        scope: LinkScope,
+        broken_only: bool,
+    ) -> Result<LinkPage, Error> {
+        let owner_id = scope.owner_id();
+        let broken_condition = if broken_only {
+            " AND short_urls.broken_since IS NOT NULL"
+        } else {
+            ""
+        };
+
+        let total = sqlx::query(&format!(
+            "SELECT COUNT(*) AS total FROM short_urls
+            WHERE ($1 IS NULL OR short_urls.owner_id = $1){}",
+            broken_condition
+        ))
+        .bind(owner_id)
+        .fetch_one(self.database.pool())
+        .await
+        .map_err(Error::Internal)?
+        .get::<i64, &str>("total");
+
+        let query = format!(
+            "SELECT short_urls.short_code, short_urls.original_url,
+                CAST(short_urls.created_at AS TEXT) AS created_at, short_urls.expires_at,
+                short_urls.redirect_type, short_urls.max_clicks, short_urls.deleted_at, short_urls.domain,
+                short_urls.click_count, short_urls.page_title, short_urls.favicon_url,
+                short_urls.health_checked_at, short_urls.health_status, short_urls.health_latency_ms,
+                short_urls.health_error, short_urls.health_failures, short_urls.broken_since
+            FROM short_urls
+            WHERE ($1 IS NULL OR short_urls.owner_id = $1){}
+            ORDER BY {}
+            LIMIT $2 OFFSET $3",
+            broken_condition,
+            sort.order_by()
+        );
+        let links = sqlx::query(&query)
+            .bind(owner_id)
+            .bind(per_page as i64)
+            .bind(page.saturating_sub(1) as i64 * per_page as i64)
+            .fetch_all(self.database.pool())
+            .await
+            .map_err(Error::Internal)?
+            .iter()
+            .map(link_summary_from_row)
+            .collect();
+
+        Ok(LinkPage {
+            links,
+            page,
+            per_page,
+            total,
+        })
+    }
+
+    async fn find_short_url(&self, short_code: &str) -> Result<AnyRow, Error> {
+        self.find_short_url_in(self.database.pool(), short_code)
+            .await
+    }
+
+    /// Looks a short URL up for a redirect on the read replica. One that was shortened a moment
+    /// ago may not have been replicated yet, so misses are looked up on the primary again. Also
+    /// returns the pool it was found in, for the rest of its rows.
+    async fn find_short_url_to_visit(&self, short_code: &str) -> Result<(AnyRow, &AnyPool), Error> {
+        let read_pool = self.database.read_pool();
+        match self.find_short_url_in(read_pool, short_code).await {
+            Err(Error::NotFound) if self.database.has_read_replica() => {
+                let pool = self.database.pool();
+                Ok((self.find_short_url_in(pool, short_code).await?, pool))
+            }
+            result => Ok((result?, read_pool)),
+        }
+    }
+
+    async fn find_short_url_in(&self, pool: &AnyPool, short_code: &str) -> Result<AnyRow, Error> {
+        let query = format!(
+            "SELECT {} FROM short_urls WHERE {} ORDER BY id LIMIT 1",
+            SHORT_URL_COLUMNS,
+            if self.case_insensitive_short_codes {
+                "LOWER(short_code) = $1"
+            } else {
+                "short_code = $1"
+            }
+        );
+        let optional_row = sqlx::query(&query)
+            .bind(self.normalize_short_code(short_code))
+            .fetch_optional(pool)
+            .await
+            .map_err(Error::Internal)?;
+
+        optional_row.ok_or(Error::NotFound)
+    }
+
+    /// Imports short URLs with the given codes in a single transaction. Imported codes only need
+    /// to be valid characters, so links shortened elsewhere keep working even if they are shorter
+    /// than a custom code may be.
+    pub async fn import(
+        &self,
+        imported_short_urls: &[ImportedShortUrl],
+        on_conflict: ImportConflict,
+        owner_id: Option<i64>,
+        scope: LinkScope,
+    ) -> Result<ImportSummary, Error> {
+        let imported_short_urls: Vec<_> = imported_short_urls
+            .iter()
+            .map(ImportedShortUrl::normalized)
+            .collect();
+        let mut transaction = self
+            .database
+            .pool()
+            .begin()
+            .await
+            .map_err(Error::Internal)?;
+
+        let mut summary = ImportSummary::default();
+        let mut created = Vec::new();
+        let mut overwritten = Vec::new();
+        for imported_short_url in &imported_short_urls {
+            let outcome = self
+                .import_one(
+                    &mut transaction,
+                    imported_short_url,
+                    on_conflict,
+                    owner_id,
+                    scope,
+                )
+                .await;
+            match outcome {
+                Ok(ImportOutcome::Imported(short_code)) => {
+                    summary.imported += 1;
+                    created.push((short_code, &imported_short_url.original_url));
+                }
+                Ok(ImportOutcome::Overwritten(short_code)) => {
+                    summary.overwritten += 1;
+                    overwritten.push((short_code, &imported_short_url.original_url));
+                }
+                Ok(ImportOutcome::Skipped) => summary.skipped += 1,
+                // Dropping the transaction rolls back what has been imported so far.
+                Err(error @ Error::Internal(_)) => return Err(error),
+                Err(error @ Error::Conflict(_)) if on_conflict == ImportConflict::Fail => {
+                    return Err(error)
+                }
+                Err(error) => summary.failed.push(ImportFailure {
+                    line: imported_short_url.line,
+                    short_code: imported_short_url.short_code.clone(),
+                    code: error.code(),
+                    error: error.to_string(),
+                }),
+            }
+        }
+
+        transaction.commit().await.map_err(Error::Internal)?;
+
+        for (short_code, original_url) in overwritten {
+            self.invalidate_cache(&short_code).await;
+            self.metadata_fetcher.fetch(&short_code, original_url);
+        }
+        for (short_code, original_url) in created {
+            self.webhooks
+                .notify(LinkEvent::Created, &short_code, original_url);
+            self.metadata_fetcher.fetch(&short_code, original_url);
+        }
+        info!(
+            "Imported {} short URLs, overwrote {}, skipped {} and rejected {}",
+            summary.imported,
+            summary.overwritten,
+            summary.skipped,
+            summary.failed.len()
+        );
+        Ok(summary)
+    }
+
+    async fn import_one(
+        &self,
+        connection: &mut AnyConnection,
+        imported_short_url: &ImportedShortUrl,
+        on_conflict: ImportConflict,
+        owner_id: Option<i64>,
+        scope: LinkScope,
+    ) -> Result<ImportOutcome, Error> {
+        let short_code = imported_short_url.short_code.as_str();
+        if short_code.is_empty() || short_code.chars().count() > MAX_CUSTOM_CODE_LENGTH {
+            return Err(Error::InvalidShortCode(format!(
+                "Short code must be between 1 and {} characters long",
+                MAX_CUSTOM_CODE_LENGTH
+            )));
+        }
+        validate_short_code_charset(short_code)?;
+        if self.is_reserved(short_code) {
+            return Err(Error::InvalidShortCode(format!(
+                "Short code '{}' is reserved",
+                short_code
+            )));
+        }
+        self.validate_original_url(&imported_short_url.original_url)?;
+        let domain = match imported_short_url.domain.as_deref() {
+            Some(domain) if !domain.is_empty() => self.domains.select(domain)?,
+            _ => None,
+        };
+
+        // Looking the code up first rather than relying on the unique constraint keeps the
+        // transaction usable on PostgreSQL, which aborts it on the first failed statement.
+        let short_code = self.normalize_short_code(short_code);
+        let query = if self.case_insensitive_short_codes {
+            "SELECT id, owner_id FROM short_urls WHERE LOWER(short_code) = $1"
+        } else {
+            "SELECT id, owner_id FROM short_urls WHERE short_code = $1"
+        };
+        let existing_row = sqlx::query(query)
+            .bind(&short_code)
+            .fetch_optional(&mut *connection)
+            .await
+            .map_err(Error::Internal)?;
+
+        let taken_error =
+            || Error::Conflict(format!("Short code '{}' is already taken", short_code));
+        let Some(existing_row) = existing_row else {
+            let new_short_url = NewShortUrl {
+                original_url: imported_short_url.original_url.clone(),
+                custom_code: None,
+                expires_at: None,
+                owner_id,
+                redirect_type: RedirectType::default(),
+                max_clicks: None,
+                reuse_existing: false,
+                geo_targets: BTreeMap::new(),
+                variants: Vec::new(),
+                device_targets: BTreeMap::new(),
+                domain,
+                tracking_params: BTreeMap::new(),
+                short_code_length: None,
+                namespace: None,
+            };
+            // Registered by a concurrent request since it was looked up.
+            if !self
+                .insert_short_url(connection, &new_short_url, &short_code)
+                .await
+                .map_err(Error::Internal)?
+            {
+                return Err(taken_error());
+            }
+            return Ok(ImportOutcome::Imported(short_code));
+        };
+
+        match on_conflict {
+            ImportConflict::Skip => Ok(ImportOutcome::Skipped),
+            ImportConflict::Fail => Err(taken_error()),
+            ImportConflict::Overwrite => {
+                if !scope.allows(existing_row.get::<Option<i64>, &str>("owner_id")) {
+                    return Err(taken_error());
+                }
+                sqlx::query("UPDATE short_urls SET original_url = $1 WHERE id = $2")
+                    .bind(&imported_short_url.original_url)
+                    .bind(existing_row.get::<i64, &str>("id"))
+                    .execute(&mut *connection)
+                    .await
+                    .map_err(Error::Internal)?;
+                Ok(ImportOutcome::Overwritten(short_code))
+            }
+        }
+    }
+
+    /// Every short URL in the scope, including deleted and expired ones, oldest first. The short
+    /// URLs are read page by page, so exports don't hold all of them in memory or keep a
+    /// connection busy while the client reads.
+    pub fn export(
+        &self,
+        scope: LinkScope,
+    ) -> impl Stream<Item = Result<Vec<LinkSummary>, Error>> + Send + 'static {
+        let url_shortener = self.clone();
+        futures_util::stream::try_unfold(Some(0), move |after_id| {
+            let url_shortener = url_shortener.clone();
+            async move {
+                let Some(after_id) = after_id else {
+                    return Ok(None);
+                };
+                let (links, last_id) = url_shortener.export_page(after_id, scope).await?;
+                let next = (links.len() as i64 == EXPORT_PAGE_SIZE).then_some(last_id);
+                Ok((!links.is_empty()).then_some((links, next)))
+            }
+        })
+    }
+
+    /// Streams the clicks of a short URL that happened in `[from, to)`, oldest first, in pages,
+    /// so large exports don't have to fit in memory. Fails right away if there is no such short
+    /// URL in the scope.
+    pub async fn export_clicks(
+        &self,
+        short_code: &str,
+        scope: LinkScope,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<impl Stream<Item = Result<Vec<ExportedClick>, Error>> + Send + 'static, Error> {
+        if from.zip(to).is_some_and(|(from, to)| from >= to) {
+            return Err(Error::InvalidRequest("from must be before to".to_string()));
+        }
+        let short_url_id = self
+            .find_short_url_in_scope(short_code, scope)
+            .await?
+            .get::<i64, &str>("id");
+
+        let url_shortener = self.clone();
+        Ok(futures_util::stream::try_unfold(Some(0), move |after_id| {
+            let url_shortener = url_shortener.clone();
+            async move {
+                let Some(after_id) = after_id else {
+                    return Ok(None);
+                };
+                let (clicks, last_id) = url_shortener
+                    .export_clicks_page(short_url_id, after_id, from, to)
+                    .await?;
+                let next = (clicks.len() as i64 == EXPORT_PAGE_SIZE).then_some(last_id);
+                Ok((!clicks.is_empty()).then_some((clicks, next)))
+            }
+        }))
+    }
+
+    /// Returns the next page of clicks after the one with ID `after_id`, and the ID of the last
+    /// one.
+    async fn export_clicks_page(
+        &self,
+        short_url_id: i64,
+        after_id: i64,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<(Vec<ExportedClick>, i64), Error> {
+        let rows = sqlx::query(
+            "SELECT id, clicked_at, variant_id, referrer, user_agent, ip_hash FROM clicks
+            WHERE short_url_id = $1 AND id > $2
+                AND ($3 IS NULL OR clicked_at >= $3) AND ($4 IS NULL OR clicked_at < $4)
+            ORDER BY id ASC
+            LIMIT $5",
+        )
+        .bind(short_url_id)
+        .bind(after_id)
+        .bind(from.map(encode_timestamp))
+        .bind(to.map(encode_timestamp))
+        .bind(EXPORT_PAGE_SIZE)
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?;
+
+        let last_id = rows
+            .last()
+            .map_or(after_id, |row| row.get::<i64, &str>("id"));
+        let clicks = rows
+            .iter()
+            .map(|row| ExportedClick {
+                clicked_at: get_timestamp(row, "clicked_at").unwrap_or_default(),
+                variant_id: row.get::<Option<i64>, &str>("variant_id"),
+                referrer: row.get::<Option<String>, &str>("referrer"),
+                user_agent: row.get::<Option<String>, &str>("user_agent"),
+                ip_hash: row.get::<Option<String>, &str>("ip_hash"),
+            })
+            .collect();
+        Ok((clicks, last_id))
+    }
+
+    /// Returns the next page of short URLs after the one with ID `after_id`, and the ID of the last
+    /// one.
+    async fn export_page(
+        &self,
+        after_id: i64,
+        scope: LinkScope,
+    ) -> Result<(Vec<LinkSummary>, i64), Error> {
+        let rows = sqlx::query(
+            "SELECT short_urls.id, short_urls.short_code, short_urls.original_url,
+                CAST(short_urls.created_at AS TEXT) AS created_at, short_urls.expires_at,
+                short_urls.redirect_type, short_urls.max_clicks, short_urls.deleted_at, short_urls.domain,
+                short_urls.click_count, short_urls.page_title, short_urls.favicon_url,
+                short_urls.health_checked_at, short_urls.health_status, short_urls.health_latency_ms,
+                short_urls.health_error, short_urls.health_failures, short_urls.broken_since
+            FROM short_urls
+            WHERE short_urls.id > $1 AND ($2 IS NULL OR short_urls.owner_id = $2)
+            ORDER BY short_urls.id ASC
+            LIMIT $3",
+        )
+        .bind(after_id)
+        .bind(scope.owner_id())
+        .bind(EXPORT_PAGE_SIZE)
+        .fetch_all(self.database.pool())
+        .await
+        .map_err(Error::Internal)?;
+
+        let last_id = rows
+            .last()
+            .map_or(after_id, |row| row.get::<i64, &str>("id"));
+        let links = rows.iter().map(link_summary_from_row).collect();
+        Ok((links, last_id))
+    }
+
+    fn normalize_short_code(&self, short_code: &str) -> String {
+        if self.case_insensitive_short_codes {
+            short_code.to_lowercase()
+        } else {
+            short_code.to_string()
+        }
+    }
+
+    /// Finds a short URL, treating short URLs outside of the scope as non-existent.
+    async fn find_short_url_in_scope(
+        &self,
+        short_code: &str,
+        scope: LinkScope,
+    ) -> Result<AnyRow, Error> {
+        let row = self.find_short_url(short_code).await?;
+
+        if scope.allows(row.get::<Option<i64>, &str>("owner_id")) {
+            Ok(row)
+        } else {
+            Err(Error::NotFound)
+        }
+    }
+
+    /// Finds a short URL that hasn't expired, reading through the cache if there is one and from
+    /// the read replica if there is one.
+    async fn find_active_short_url(&self, short_code: &str) -> Result<CachedShortUrl, Error> {
+        let short_code = &self.normalize_short_code(short_code);
+        let cached_short_url = match &self.cache {
+            Some(cache) => cache.get(short_code).await,
+            None => None,
+        };
+
+        let short_url = match cached_short_url {
+            Some(short_url) => short_url,
+            None => {
+                let (row, pool) = self.find_short_url_to_visit(short_code).await?;
+                // Deactivated short URLs aren't cached, so deleting one only has to invalidate it.
+                if get_timestamp(&row, "deleted_at").is_some() {
+                    return Err(Error::Deactivated);
+                }
+                let id = row.get::<i64, &str>("id");
+                let geo_targets = sqlx::query(
+                    "SELECT country, original_url FROM redirect_rules WHERE short_url_id = $1",
+                )
+                .bind(id)
+                .fetch_all(pool)
+                .await
+                .map_err(Error::Internal)?
+                .iter()
+                .map(|row| {
+                    (
+                        row.get::<String, &str>("country"),
+                        row.get::<String, &str>("original_url"),
+                    )
+                })
+                .collect();
+                let variants = sqlx::query(
+                    "SELECT id, original_url, weight FROM variants WHERE short_url_id = $1 ORDER BY id",
+                )
+                .bind(id)
+                .fetch_all(pool)
+                .await
+                .map_err(Error::Internal)?
+                .iter()
+                .map(|row| Variant {
+                    id: row.get::<i64, &str>("id"),
+                    original_url: row.get::<String, &str>("original_url"),
+                    weight: row.get::<i64, &str>("weight"),
+                })
+                .collect();
+                let device_targets = sqlx::query(
+                    "SELECT device, original_url FROM device_targets WHERE short_url_id = $1",
+                )
+                .bind(id)
+                .fetch_all(pool)
+                .await
+                .map_err(Error::Internal)?
+                .iter()
+                .filter_map(|row| {
+                    Some((
+                        DeviceType::from_str(&row.get::<String, &str>("device")).ok()?,
+                        row.get::<String, &str>("original_url"),
+                    ))
+                })
+                .collect();
+                let short_url = CachedShortUrl {
+                    id,
+                    original_url: row.get::<String, &str>("original_url"),
+                    expires_at: get_timestamp(&row, "expires_at"),
+                    redirect_type: RedirectType::from_row(&row),
+                    max_clicks: row.get::<Option<i64>, &str>("max_clicks"),
+                    domain: row.get::<Option<String>, &str>("domain"),
+                    tracking_params: decode_tracking_params(&row),
+                    geo_targets,
+                    variants,
+                    device_targets,
+                };
+                if let Some(cache) = &self.cache {
+                    cache.set(short_code, &short_url).await;
+                }
+                short_url
+            }
+        };
+
+        if short_url
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return Err(Error::Expired);
+        }
+
+        Ok(short_url)
+    }
+
+    /// Removes short URLs that expired, used up their clicks or were deleted before `links_before`,
+    /// together with their clicks, and clicks of all short URLs recorded before `clicks_before`.
+    pub async fn purge(
+        &self,
+        links_before: DateTime<Utc>,
+        clicks_before: Option<DateTime<Utc>>,
+    ) -> Result<PurgedRows, Error> {
+        let links_before = encode_timestamp(links_before);
+        let dead_links = "(expires_at IS NOT NULL AND expires_at < $1)
+            OR (deleted_at IS NOT NULL AND deleted_at < $1)
+            OR (remaining_clicks <= 0 AND NOT EXISTS (
+                SELECT 1 FROM clicks WHERE short_url_id = short_urls.id AND clicked_at >= $1
+            ))";
+
+        let mut transaction = self
+            .database
+            .pool()
+            .begin()
+            .await
+            .map_err(Error::Internal)?;
+
+        let short_codes: Vec<String> = sqlx::query(&format!(
+            "SELECT short_code FROM short_urls WHERE {}",
+            dead_links
+        ))
+        .bind(&links_before)
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(Error::Internal)?
+        .iter()
+        .map(|row| row.get::<String, &str>("short_code"))
+        .collect();
+
+        let mut clicks = sqlx::query(&format!(
+            "DELETE FROM clicks WHERE short_url_id IN (SELECT id FROM short_urls WHERE {})",
+            dead_links
+        ))
+        .bind(&links_before)
+        .execute(&mut *transaction)
+        .await
+        .map_err(Error::Internal)?
+        .rows_affected();
+
+        for table in ["redirect_rules", "variants", "device_targets"] {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE short_url_id IN (SELECT id FROM short_urls WHERE {})",
+                table, dead_links
+            ))
+            .bind(&links_before)
+            .execute(&mut *transaction)
+            .await
+            .map_err(Error::Internal)?;
+        }
+
+        let links = sqlx::query(&format!("DELETE FROM short_urls WHERE {}", dead_links))
+            .bind(&links_before)
+            .execute(&mut *transaction)
+            .await
+            .map_err(Error::Internal)?
+            .rows_affected();
+
+        if let Some(clicks_before) = clicks_before {
+            clicks += sqlx::query("DELETE FROM clicks WHERE clicked_at < $1")
+                .bind(encode_timestamp(clicks_before))
+                .execute(&mut *transaction)
+                .await
+                .map_err(Error::Internal)?
+                .rows_affected();
+        }
+
+        transaction.commit().await.map_err(Error::Internal)?;
+
+        for short_code in &short_codes {
+            self.invalidate_cache(short_code).await;
+        }
+        Ok(PurgedRows { links, clicks })
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        match &self.cache {
+            Some(cache) => cache.stats(),
+            None => CacheStats {
+                backend: "disabled",
+                hits: 0,
+                misses: 0,
+            },
+        }
+    }
+
+    async fn invalidate_cache(&self, short_code: &str) {
+        if let Some(cache) = &self.cache {
+            cache
+                .invalidate(&self.normalize_short_code(short_code))
+                .await;
         }
     }
 }
@@ -120,15 +3001,310 @@ impl UrlShortener {
 impl Clone for UrlShortener {
     fn clone(&self) -> Self {
         Self {
-            pool: self.pool.clone(),
+            database: self.database.clone(),
+            domains: self.domains.clone(),
+            cache: self.cache.clone(),
+            short_code_strategy: self.short_code_strategy,
+            initial_short_code_length: self.initial_short_code_length,
+            requestable_short_code_lengths: self.requestable_short_code_lengths.clone(),
+            short_code_characters: self.short_code_characters,
+            case_insensitive_short_codes: self.case_insensitive_short_codes,
+            safety_checker: self.safety_checker.clone(),
+            reserved_short_codes: self.reserved_short_codes.clone(),
+            webhooks: self.webhooks.clone(),
+            geoip: self.geoip.clone(),
+            metadata_fetcher: self.metadata_fetcher.clone(),
         }
     }
 }
 
-fn generate_short_code(length: usize) -> String {
-    rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(length)
-        .map(char::from)
+/// Short URLs requested on another domain than their own are treated as non-existent.
+fn check_domain(short_url: &CachedShortUrl, domain: Option<&str>) -> Result<(), Error> {
+    if short_url.domain.as_deref() == domain {
+        Ok(())
+    } else {
+        Err(Error::NotFound)
+    }
+}
+
+fn validate_tracking_params(tracking_params: &BTreeMap<String, String>) -> Result<(), Error> {
+    if tracking_params.len() > MAX_TRACKING_PARAMS {
+        return Err(Error::InvalidRequest(format!(
+            "At most {} tracking parameters are allowed",
+            MAX_TRACKING_PARAMS
+        )));
+    }
+    if tracking_params.keys().any(|name| name.is_empty()) {
+        return Err(Error::InvalidRequest(
+            "Tracking parameter names must not be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// `None` for no tracking parameters, so that short URLs without them keep a `NULL` column.
+fn encode_tracking_params(tracking_params: &BTreeMap<String, String>) -> Option<String> {
+    (!tracking_params.is_empty())
+        .then(|| serde_json::to_string(tracking_params).expect("strings serialize to JSON"))
+}
+
+fn decode_tracking_params(row: &AnyRow) -> BTreeMap<String, String> {
+    row.get::<Option<String>, &str>("tracking_params")
+        .and_then(|json| {
+            serde_json::from_str(&json)
+                .inspect_err(|error| warn!("Ignoring invalid tracking parameters: {}", error))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Adds the tracking parameters that `original_url` doesn't already have to its query, after the
+/// existing ones. The rest of the URL, including the encoding of its query and its fragment, is
+/// kept as it is. App deep links are left alone, since their query may not be a form.
+fn append_tracking_params(
+    original_url: &str,
+    tracking_params: &BTreeMap<String, String>,
+) -> String {
+    let Ok(mut url) = Url::parse(original_url) else {
+        return original_url.to_string();
+    };
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return original_url.to_string();
+    }
+
+    let existing: HashSet<String> = url
+        .query_pairs()
+        .map(|(name, _)| name.into_owned())
+        .collect();
+    let missing: Vec<_> = tracking_params
+        .iter()
+        .filter(|(name, _)| !existing.contains(*name))
+        .collect();
+    if missing.is_empty() {
+        return original_url.to_string();
+    }
+
+    url.query_pairs_mut().extend_pairs(missing);
+    url.into()
+}
+
+/// Normalizes the host of http(s) URLs like browsers do, so that the same destination is always
+/// stored the same way: internationalized domain names are mapped to Unicode NFC and lower case
+/// and encoded as punycode, and default ports are dropped. The path, query and fragment are kept
+/// as they are. Other URLs, like app deep links, and invalid ones are returned unchanged.
+fn normalize_url(original_url: &str) -> String {
+    let Ok(url) = Url::parse(original_url) else {
+        return original_url.to_string();
+    };
+    let Some(host) = url.host_str() else {
+        return original_url.to_string();
+    };
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return original_url.to_string();
+    }
+
+    let prefix = format!("{}://", url.scheme());
+    let rest = original_url
+        .get(..prefix.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(&prefix))
+        .and_then(|_| original_url.get(prefix.len()..));
+    let Some(rest) = rest else {
+        // Unusual spellings like `https:example.com` are only accepted by the parser, so its
+        // serialization is used instead.
+        return url.into();
+    };
+
+    let (authority, path) = rest.split_at(rest.find(['/', '?', '#', '\\']).unwrap_or(rest.len()));
+    let user_info = authority.rfind('@').map_or("", |at| &authority[..=at]);
+    let port = url
+        .port()
+        .map(|port| format!(":{}", port))
+        .unwrap_or_default();
+    format!("{}{}{}{}{}", prefix, user_info, host, port, path)
+}
+
+fn normalize_targets<K: Clone + Ord>(targets: &BTreeMap<K, String>) -> BTreeMap<K, String> {
+    targets
+        .iter()
+        .map(|(key, original_url)| (key.clone(), normalize_url(original_url)))
         .collect()
 }
+
+/// How a short code registered under the namespace of `new_short_url` is stored and requested.
+fn namespaced(new_short_url: &NewShortUrl, short_code: String) -> String {
+    match &new_short_url.namespace {
+        Some(namespace) => format!("{}/{}", namespace, short_code),
+        None => short_code,
+    }
+}
+
+fn validate_custom_code(custom_code: &str) -> Result<(), Error> {
+    let length = custom_code.chars().count();
+    if !(MIN_CUSTOM_CODE_LENGTH..=MAX_CUSTOM_CODE_LENGTH).contains(&length) {
+        return Err(Error::InvalidShortCode(format!(
+            "Custom code must be between {} and {} characters long",
+            MIN_CUSTOM_CODE_LENGTH, MAX_CUSTOM_CODE_LENGTH
+        )));
+    }
+
+    validate_short_code_charset(custom_code)
+}
+
+fn validate_namespace_name(name: &str) -> Result<(), Error> {
+    let length = name.chars().count();
+    let has_valid_charset = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !(MIN_CUSTOM_CODE_LENGTH..=MAX_CUSTOM_CODE_LENGTH).contains(&length) || !has_valid_charset {
+        return Err(Error::InvalidRequest(format!(
+            "Namespace names must be between {} and {} letters, digits, '-' or '_'",
+            MIN_CUSTOM_CODE_LENGTH, MAX_CUSTOM_CODE_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+fn validate_campaign_name(name: &str) -> Result<(), Error> {
+    let length = name.chars().count();
+    let has_valid_charset = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !(1..=MAX_CAMPAIGN_NAME_LENGTH).contains(&length) || !has_valid_charset {
+        return Err(Error::InvalidRequest(format!(
+            "Campaign names must be between 1 and {} letters, digits, '-' or '_'",
+            MAX_CAMPAIGN_NAME_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+fn validate_short_code_charset(short_code: &str) -> Result<(), Error> {
+    let has_valid_charset = short_code
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !has_valid_charset {
+        return Err(Error::InvalidShortCode(
+            "Custom code may only contain letters, digits, '-' and '_'".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reads a row selected with the columns of [`UrlShortener::list`].
+fn link_summary_from_row(row: &AnyRow) -> LinkSummary {
+    LinkSummary {
+        short_code: row.get::<String, &str>("short_code"),
+        original_url: row.get::<String, &str>("original_url"),
+        created_at: get_timestamp(row, "created_at").unwrap_or_default(),
+        expires_at: get_timestamp(row, "expires_at"),
+        redirect_type: RedirectType::from_row(row),
+        max_clicks: row.get::<Option<i64>, &str>("max_clicks"),
+        deleted_at: get_timestamp(row, "deleted_at"),
+        click_count: row.get::<i64, &str>("click_count"),
+        domain: row.get::<Option<String>, &str>("domain"),
+        page_title: row.get::<Option<String>, &str>("page_title"),
+        favicon_url: row.get::<Option<String>, &str>("favicon_url"),
+        health: link_health_from_row(row),
+    }
+}
+
+/// Reads the health columns of a row, `None` until the destination has been checked.
+fn link_health_from_row(row: &AnyRow) -> Option<LinkHealth> {
+    Some(LinkHealth {
+        checked_at: get_timestamp(row, "health_checked_at")?,
+        status_code: row.get::<Option<i64>, &str>("health_status"),
+        latency_ms: row.get::<Option<i64>, &str>("health_latency_ms"),
+        error: row.get::<Option<String>, &str>("health_error"),
+        consecutive_failures: row.get::<i64, &str>("health_failures"),
+        broken_since: get_timestamp(row, "broken_since"),
+    })
+}
+
+async fn insert_geo_targets(
+    connection: &mut AnyConnection,
+    short_url_id: i64,
+    geo_targets: &BTreeMap<String, String>,
+) -> Result<(), Error> {
+    for (country, original_url) in geo_targets {
+        sqlx::query(
+            "INSERT INTO redirect_rules (short_url_id, country, original_url) VALUES ($1, $2, $3)",
+        )
+        .bind(short_url_id)
+        .bind(country)
+        .bind(original_url)
+        .execute(&mut *connection)
+        .await
+        .map_err(Error::Internal)?;
+    }
+    Ok(())
+}
+
+async fn insert_variants(
+    connection: &mut AnyConnection,
+    short_url_id: i64,
+    variants: &[NewVariant],
+) -> Result<(), Error> {
+    for variant in variants {
+        sqlx::query(
+            "INSERT INTO variants (short_url_id, original_url, weight) VALUES ($1, $2, $3)",
+        )
+        .bind(short_url_id)
+        .bind(&variant.original_url)
+        .bind(variant.weight)
+        .execute(&mut *connection)
+        .await
+        .map_err(Error::Internal)?;
+    }
+    Ok(())
+}
+
+async fn insert_device_targets(
+    connection: &mut AnyConnection,
+    short_url_id: i64,
+    device_targets: &BTreeMap<DeviceType, String>,
+) -> Result<(), Error> {
+    for (device, original_url) in device_targets {
+        sqlx::query(
+            "INSERT INTO device_targets (short_url_id, device, original_url) VALUES ($1, $2, $3)",
+        )
+        .bind(short_url_id)
+        .bind(device.as_str())
+        .bind(original_url)
+        .execute(&mut *connection)
+        .await
+        .map_err(Error::Internal)?;
+    }
+    Ok(())
+}
+
+/// Picks a variant for `weight` percent of the calls each, or `None` for the remaining calls that
+/// go to the original URL.
+fn pick_variant(variants: Vec<Variant>) -> Option<Variant> {
+    if variants.is_empty() {
+        return None;
+    }
+    let mut roll = rand::thread_rng().gen_range(0..100);
+    for variant in variants {
+        if roll < variant.weight {
+            return Some(variant);
+        }
+        roll -= variant.weight;
+    }
+    None
+}
+
+/// Writes `counter` as a number whose digits are the given characters, e.g. in base 62.
+fn encode_counter(characters: &[u8], mut counter: u64) -> String {
+    let base = characters.len() as u64;
+    let mut digits = Vec::new();
+    loop {
+        digits.push(characters[(counter % base) as usize]);
+        counter /= base;
+        if counter == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    String::from_utf8(digits).expect("Short code characters are ASCII")
+}
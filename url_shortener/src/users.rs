@@ -0,0 +1,149 @@
+use crate::database::{encode_timestamp, Database};
+use crate::url_shortener::Error;
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+const MIN_USERNAME_LENGTH: usize = 3;
+const MAX_USERNAME_LENGTH: usize = 32;
+const MIN_PASSWORD_LENGTH: usize = 8;
+const TOKEN_LIFETIME_HOURS: i64 = 24;
+
+pub struct User {
+    pub id: i64,
+    pub username: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    sub: i64,
+    username: String,
+    exp: i64,
+}
+
+pub struct UserStore {
+    database: Database,
+    jwt_secret: String,
+}
+
+impl UserStore {
+    pub fn new(database: Database, jwt_secret: String) -> Self {
+        UserStore {
+            database,
+            jwt_secret,
+        }
+    }
+
+    pub async fn register(&self, username: &str, password: &str) -> Result<User, Error> {
+        validate_credentials(username, password)?;
+
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|error| Error::InvalidUser(error.to_string()))?
+            .to_string();
+
+        let result = sqlx::query(
+            "INSERT INTO users (username, password_hash, created_at) VALUES ($1, $2, $3) RETURNING id",
+        )
+        .bind(username)
+        .bind(password_hash)
+        .bind(encode_timestamp(Utc::now()))
+        .fetch_one(self.database.pool())
+        .await;
+
+        match result {
+            Ok(row) => Ok(User {
+                id: row.get::<i64, &str>("id"),
+                username: username.to_string(),
+            }),
+            Err(error) => {
+                if let sqlx::Error::Database(ref database_error) = error {
+                    if database_error.is_unique_violation() {
+                        return Err(Error::Conflict(format!(
+                            "Username '{}' is already taken",
+                            username
+                        )));
+                    }
+                }
+                Err(Error::Internal(error))
+            }
+        }
+    }
+
+    /// Checks the credentials and issues a signed token for the user.
+    pub async fn login(&self, username: &str, password: &str) -> Result<String, Error> {
+        let invalid_credentials =
+            || Error::Unauthorized("Invalid username or password".to_string());
+
+        let row = sqlx::query("SELECT id, password_hash FROM users WHERE username = $1")
+            .bind(username)
+            .fetch_optional(self.database.pool())
+            .await
+            .map_err(Error::Internal)?
+            .ok_or_else(invalid_credentials)?;
+
+        let password_hash = row.get::<String, &str>("password_hash");
+        let parsed_hash = PasswordHash::new(&password_hash).map_err(|_| invalid_credentials())?;
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .map_err(|_| invalid_credentials())?;
+
+        let claims = Claims {
+            sub: row.get::<i64, &str>("id"),
+            username: username.to_string(),
+            exp: (Utc::now() + Duration::hours(TOKEN_LIFETIME_HOURS)).timestamp(),
+        };
+        jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        )
+        .map_err(|error| Error::Unauthorized(error.to_string()))
+    }
+
+    /// Returns the ID of the user a token was issued to, if the token is valid and not expired.
+    pub fn verify_token(&self, token: &str) -> Option<i64> {
+        jsonwebtoken::decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(self.jwt_secret.as_bytes()),
+            &Validation::default(),
+        )
+        .ok()
+        .map(|token_data| token_data.claims.sub)
+    }
+}
+
+impl Clone for UserStore {
+    fn clone(&self) -> Self {
+        Self {
+            database: self.database.clone(),
+            jwt_secret: self.jwt_secret.clone(),
+        }
+    }
+}
+
+fn validate_credentials(username: &str, password: &str) -> Result<(), Error> {
+    let username_length = username.chars().count();
+    if !(MIN_USERNAME_LENGTH..=MAX_USERNAME_LENGTH).contains(&username_length) {
+        return Err(Error::InvalidUser(format!(
+            "Username must be between {} and {} characters long",
+            MIN_USERNAME_LENGTH, MAX_USERNAME_LENGTH
+        )));
+    }
+
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(Error::InvalidUser(format!(
+            "Password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+
+    Ok(())
+}
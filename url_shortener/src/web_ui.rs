@@ -0,0 +1,47 @@
+use axum::{
+    http::header::{CACHE_CONTROL, CONTENT_TYPE},
+    response::{Html, IntoResponse},
+    routing::get,
+    Router,
+};
+
+// The page is compiled into the binary, so it's deployed together with the API it calls.
+const INDEX_HTML: &str = include_str!("../web_ui/index.html");
+const APP_JS: &str = include_str!("../web_ui/app.js");
+const STYLE_CSS: &str = include_str!("../web_ui/style.css");
+
+/// Browsers check for a newer page on every visit, so it never gets out of step with the API.
+const ASSET_CACHE_CONTROL: &str = "no-cache";
+
+/// A small page at `/` for shortening URLs from the browser. It calls `/api/v1/shorten` with an
+/// API key that the visitor enters, and shows the short URL and its QR code.
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route("/", get(index_handler))
+        .route("/static/app.js", get(app_js_handler))
+        .route("/static/style.css", get(style_css_handler))
+}
+
+async fn index_handler() -> impl IntoResponse {
+    ([(CACHE_CONTROL, ASSET_CACHE_CONTROL)], Html(INDEX_HTML))
+}
+
+async fn app_js_handler() -> impl IntoResponse {
+    (
+        [
+            (CONTENT_TYPE, "text/javascript; charset=utf-8"),
+            (CACHE_CONTROL, ASSET_CACHE_CONTROL),
+        ],
+        APP_JS,
+    )
+}
+
+async fn style_css_handler() -> impl IntoResponse {
+    (
+        [
+            (CONTENT_TYPE, "text/css; charset=utf-8"),
+            (CACHE_CONTROL, ASSET_CACHE_CONTROL),
+        ],
+        STYLE_CSS,
+    )
+}
@@ -0,0 +1,241 @@
+use crate::database::{encode_timestamp, get_timestamp, Database};
+use crate::monitoring;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::Row;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{error, warn};
+use url::Url;
+
+/// Events are dropped once this many are waiting to be delivered.
+const QUEUE_CAPACITY: usize = 10_000;
+/// Deliveries to slow endpoints hold back the queue beyond this many.
+const MAX_CONCURRENT_DELIVERIES: usize = 32;
+/// Waiting time before the first retry, which doubles with every further retry.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// How often short URLs are checked for having expired.
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+const EVENT_HEADER: &str = "X-Webhook-Event";
+
+pub struct WebhookSettings {
+    pub urls: Vec<Url>,
+    /// Key of the HMAC-SHA256 signature that lets receivers verify payloads.
+    pub secret: String,
+    /// Deliveries are given up after this many failed attempts.
+    pub max_attempts: u32,
+    pub timeout: Duration,
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub enum LinkEvent {
+    #[serde(rename = "link.created")]
+    Created,
+    #[serde(rename = "link.clicked")]
+    Clicked,
+    /// The short URL reached its expiration time or used up its clicks.
+    #[serde(rename = "link.expired")]
+    Expired,
+}
+
+impl LinkEvent {
+    fn as_str(self) -> &'static str {
+        match self {
+            LinkEvent::Created => "link.created",
+            LinkEvent::Clicked => "link.clicked",
+            LinkEvent::Expired => "link.expired",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookPayload {
+    event: LinkEvent,
+    short_code: String,
+    original_url: String,
+    occurred_at: DateTime<Utc>,
+}
+
+/// Queues link events for a background worker that posts them to the configured webhook URLs.
+pub struct Webhooks {
+    /// `None` when no webhook is configured.
+    sender: Option<mpsc::Sender<WebhookPayload>>,
+}
+
+impl Webhooks {
+    pub fn disabled() -> Self {
+        Webhooks { sender: None }
+    }
+
+    /// Starts the delivery worker, and a task that watches the database for short URLs reaching
+    /// their expiration time.
+    pub fn spawn(database: Database, settings: WebhookSettings) -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(deliver_all(receiver, settings));
+        tokio::spawn(watch_expirations(database, sender.clone()));
+        Webhooks {
+            sender: Some(sender),
+        }
+    }
+
+    pub fn notify(&self, event: LinkEvent, short_code: &str, original_url: &str) {
+        if let Some(sender) = &self.sender {
+            send(sender, event, short_code, original_url, Utc::now());
+        }
+    }
+}
+
+impl Clone for Webhooks {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+fn send(
+    sender: &mpsc::Sender<WebhookPayload>,
+    event: LinkEvent,
+    short_code: &str,
+    original_url: &str,
+    occurred_at: DateTime<Utc>,
+) {
+    let payload = WebhookPayload {
+        event,
+        short_code: short_code.to_string(),
+        original_url: original_url.to_string(),
+        occurred_at,
+    };
+    if let Err(error) = sender.try_send(payload) {
+        warn!(
+            "Dropping {} webhook of {}: {}",
+            event.as_str(),
+            short_code,
+            error
+        );
+    }
+}
+
+async fn deliver_all(mut receiver: mpsc::Receiver<WebhookPayload>, settings: WebhookSettings) {
+    let client = reqwest::Client::builder()
+        .timeout(settings.timeout)
+        .build()
+        .expect("Failed to build the webhook HTTP client");
+    let secret = Arc::new(settings.secret);
+    let deliveries = Arc::new(Semaphore::new(MAX_CONCURRENT_DELIVERIES));
+
+    while let Some(payload) = receiver.recv().await {
+        let body = serde_json::to_vec(&payload).expect("Webhook payloads serialize to JSON");
+        let signature = sign(&secret, &body);
+
+        for url in &settings.urls {
+            let permit = deliveries
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("The delivery semaphore is never closed");
+            let client = client.clone();
+            let url = url.clone();
+            let body = body.clone();
+            let signature = signature.clone();
+            let max_attempts = settings.max_attempts;
+            tokio::spawn(async move {
+                deliver(&client, url, payload.event, body, &signature, max_attempts).await;
+                drop(permit);
+            });
+        }
+    }
+}
+
+/// Posts a payload until the endpoint answers with a 2xx status, waiting exponentially longer
+/// between attempts.
+async fn deliver(
+    client: &reqwest::Client,
+    url: Url,
+    event: LinkEvent,
+    body: Vec<u8>,
+    signature: &str,
+    max_attempts: u32,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+    for attempt in 1..=max_attempts {
+        let result = client
+            .post(url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event.as_str())
+            .header(SIGNATURE_HEADER, signature)
+            .body(body.clone())
+            .send()
+            .await;
+        let failure = match result {
+            Ok(response) if response.status().is_success() => {
+                monitoring::record_webhook_delivery("delivered");
+                return;
+            }
+            Ok(response) => format!("responded with {}", response.status()),
+            Err(error) => error.to_string(),
+        };
+
+        if attempt == max_attempts {
+            warn!(
+                "Giving up on {} webhook to {} after {} attempts: {}",
+                event.as_str(),
+                url,
+                max_attempts,
+                failure
+            );
+        } else {
+            monitoring::record_webhook_delivery("retried");
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+    monitoring::record_webhook_delivery("failed");
+}
+
+/// Signs the payload like `sha256=<hex encoded HMAC-SHA256 of the body>`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Sends `link.expired` for short URLs whose expiration time has passed since the last check.
+/// Short URLs that expire while the service isn't running are not reported.
+async fn watch_expirations(database: Database, sender: mpsc::Sender<WebhookPayload>) {
+    let mut interval = tokio::time::interval(EXPIRY_CHECK_INTERVAL);
+    let mut checked_until = Utc::now();
+    loop {
+        interval.tick().await;
+
+        let now = Utc::now();
+        let result = sqlx::query(
+            "SELECT short_code, original_url, expires_at FROM short_urls
+            WHERE expires_at > $1 AND expires_at <= $2 AND deleted_at IS NULL",
+        )
+        .bind(encode_timestamp(checked_until))
+        .bind(encode_timestamp(now))
+        .fetch_all(database.pool())
+        .await;
+        match result {
+            Ok(rows) => {
+                for row in rows {
+                    send(
+                        &sender,
+                        LinkEvent::Expired,
+                        &row.get::<String, &str>("short_code"),
+                        &row.get::<String, &str>("original_url"),
+                        get_timestamp(&row, "expires_at").unwrap_or(now),
+                    );
+                }
+                checked_until = now;
+            }
+            Err(error) => error!("Cannot check for expired short URLs: {}", error),
+        }
+    }
+}
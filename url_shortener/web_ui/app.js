@@ -0,0 +1,61 @@
+"use strict";
+
+const API_KEY_STORAGE_KEY = "url_shortener.api_key";
+
+const form = document.getElementById("shorten-form");
+const apiKeyInput = document.getElementById("api-key");
+const error = document.getElementById("error");
+const result = document.getElementById("result");
+const shortUrl = document.getElementById("short-url");
+const qrCode = document.getElementById("qr-code");
+const copyButton = document.getElementById("copy");
+
+apiKeyInput.value = localStorage.getItem(API_KEY_STORAGE_KEY) || "";
+
+form.addEventListener("submit", async (event) => {
+  event.preventDefault();
+  const submitButton = form.querySelector("button[type=submit]");
+  submitButton.disabled = true;
+  error.hidden = true;
+  result.hidden = true;
+
+  const request = { original_url: form.original_url.value.trim() };
+  const customCode = form.custom_code.value.trim();
+  if (customCode) {
+    request.custom_code = customCode;
+  }
+  const apiKey = apiKeyInput.value.trim();
+  localStorage.setItem(API_KEY_STORAGE_KEY, apiKey);
+
+  try {
+    const response = await fetch("/api/v1/shorten", {
+      method: "POST",
+      headers: { "content-type": "application/json", "x-api-key": apiKey },
+      body: JSON.stringify(request),
+    });
+    const body = await response.json().catch(() => null);
+    if (!response.ok) {
+      throw new Error(body?.message || `The request failed with status ${response.status}`);
+    }
+    showResult(body.short_url);
+  } catch (failure) {
+    error.textContent = failure.message;
+    error.hidden = false;
+  } finally {
+    submitButton.disabled = false;
+  }
+});
+
+copyButton.addEventListener("click", async () => {
+  await navigator.clipboard.writeText(shortUrl.href);
+  copyButton.textContent = "Copied";
+  setTimeout(() => (copyButton.textContent = "Copy"), 2000);
+});
+
+function showResult(url) {
+  shortUrl.href = url;
+  shortUrl.textContent = url;
+  qrCode.src = `${url}/qr?format=svg`;
+  copyButton.textContent = "Copy";
+  result.hidden = false;
+}
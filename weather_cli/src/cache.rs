@@ -0,0 +1,105 @@
+//! The places found for queries, so every run after the first only asks for the forecast.
+//! Cities don't move, so they're kept until `--refresh` looks them up again.
+
+use crate::geocoding::Location;
+use common::{config::xdg_dir, error::with_path};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+const APP_DIR_NAME: &str = "weather";
+const FILE_NAME: &str = "locations.json";
+
+pub struct LocationCache {
+    path: PathBuf,
+    /// By [`Query::cache_key`](crate::geocoding::Query::cache_key).
+    locations: BTreeMap<String, Location>,
+    changed: bool,
+}
+
+impl LocationCache {
+    /// The file of the cache, `$XDG_CACHE_HOME/weather/locations.json` or
+    /// `~/.cache/weather/locations.json`.
+    pub fn default_path() -> Option<PathBuf> {
+        xdg_dir("XDG_CACHE_HOME", ".cache").map(|dir| dir.join(APP_DIR_NAME).join(FILE_NAME))
+    }
+
+    /// Reads the cache at `path`. A file that doesn't exist yet or can't be read as a cache
+    /// gives an empty one, which replaces it when saved.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let locations = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
+            Err(error) if error.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => return Err(with_path(error, path)),
+        };
+        Ok(LocationCache {
+            path: path.to_path_buf(),
+            locations,
+            changed: false,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Location> {
+        self.locations.get(key)
+    }
+
+    pub fn insert(&mut self, key: String, location: Location) {
+        self.locations.insert(key, location);
+        self.changed = true;
+    }
+
+    /// Writes the cache if anything was added, to a file next to it first, so runs at the same
+    /// time don't read half of it.
+    pub fn save(&mut self) -> Result<(), Error> {
+        if !self.changed {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|error| with_path(error, dir))?;
+        }
+        let contents = serde_json::to_string_pretty(&self.locations).map_err(Error::other)?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, contents).map_err(|error| with_path(error, &tmp_path))?;
+        fs::rename(&tmp_path, &self.path).map_err(|error| with_path(error, &self.path))?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn keeps_locations_between_runs() {
+        let dir = env::temp_dir().join(format!("weather-cache-test-{}", std::process::id()));
+        let path = dir.join("nested").join(FILE_NAME);
+        let location = Location {
+            name: "Lisbon".to_string(),
+            admin1: Some("Lisbon".to_string()),
+            country: Some("Portugal".to_string()),
+            country_code: Some("PT".to_string()),
+            latitude: 38.71667,
+            longitude: -9.13333,
+            timezone: Some("Europe/Lisbon".to_string()),
+        };
+
+        let mut cache = LocationCache::load(&path).unwrap();
+        assert!(cache.get("lisbon").is_none());
+        cache.insert("lisbon".to_string(), location.clone());
+        cache.save().unwrap();
+        assert_eq!(
+            LocationCache::load(&path).unwrap().get("lisbon"),
+            Some(&location)
+        );
+
+        // A broken cache is started over rather than getting in the way.
+        fs::write(&path, "{").unwrap();
+        assert!(LocationCache::load(&path).unwrap().get("lisbon").is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,267 @@
+//! The current weather and the forecast of the next days at a place, converted from the
+//! columns of the forecast API to a day by day list.
+
+use crate::{geocoding::Location, get_json};
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::ValueEnum;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::io::Error;
+
+pub const DEFAULT_URL: &str = "https://api.open-meteo.com";
+
+/// Today and the two days after it.
+pub const FORECAST_DAYS: usize = 3;
+
+const CURRENT_FIELDS: &str = "temperature_2m,apparent_temperature,relative_humidity_2m,\
+                              weather_code,wind_speed_10m,wind_direction_10m";
+const DAILY_FIELDS: &str = "weather_code,temperature_2m_max,temperature_2m_min,\
+                            precipitation_sum,precipitation_probability_max";
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// °C, km/h and mm
+    Metric,
+    /// °F, mph and inches
+    Imperial,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Forecast {
+    pub units: UnitLabels,
+    pub current: Current,
+    pub days: Vec<Day>,
+}
+
+/// The units of the values, as the API writes them, like `°C` and `km/h`.
+#[derive(Debug, Serialize)]
+pub struct UnitLabels {
+    pub temperature: String,
+    pub wind_speed: String,
+    pub precipitation: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Current {
+    /// In the time zone of the place.
+    pub time: NaiveDateTime,
+    pub temperature: f64,
+    pub feels_like: f64,
+    /// In percent.
+    pub humidity: f64,
+    pub weather_code: u8,
+    pub conditions: &'static str,
+    pub wind_speed: f64,
+    /// In degrees, clockwise from north, where the wind comes from.
+    pub wind_direction: f64,
+}
+
+/// A day of the forecast, with the values the API doesn't have left out, which happens at the
+/// end of its range.
+#[derive(Debug, Serialize)]
+pub struct Day {
+    pub date: NaiveDate,
+    pub weather_code: Option<u8>,
+    pub conditions: Option<&'static str>,
+    pub temperature_min: Option<f64>,
+    pub temperature_max: Option<f64>,
+    pub precipitation: Option<f64>,
+    /// In percent.
+    pub precipitation_probability: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ForecastResponse {
+    current_units: CurrentUnits,
+    current: ApiCurrent,
+    daily_units: DailyUnits,
+    daily: ApiDaily,
+}
+
+#[derive(Deserialize)]
+struct CurrentUnits {
+    temperature_2m: String,
+    wind_speed_10m: String,
+}
+
+#[derive(Deserialize)]
+struct DailyUnits {
+    precipitation_sum: String,
+}
+
+#[derive(Deserialize)]
+struct ApiCurrent {
+    /// Like `2024-10-14T12:15`, without seconds.
+    #[serde(deserialize_with = "deserialize_minutes")]
+    time: NaiveDateTime,
+    temperature_2m: f64,
+    apparent_temperature: f64,
+    relative_humidity_2m: f64,
+    weather_code: u8,
+    wind_speed_10m: f64,
+    wind_direction_10m: f64,
+}
+
+/// A column for every field, with a row for every day.
+#[derive(Deserialize)]
+struct ApiDaily {
+    time: Vec<NaiveDate>,
+    weather_code: Vec<Option<u8>>,
+    temperature_2m_max: Vec<Option<f64>>,
+    temperature_2m_min: Vec<Option<f64>>,
+    precipitation_sum: Vec<Option<f64>>,
+    precipitation_probability_max: Vec<Option<f64>>,
+}
+
+fn deserialize_minutes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<NaiveDateTime, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    NaiveDateTime::parse_from_str(&time, "%Y-%m-%dT%H:%M").map_err(serde::de::Error::custom)
+}
+
+/// Asks for the weather at the location, with times in its own time zone.
+pub fn fetch(
+    client: &Client,
+    base_url: &str,
+    location: &Location,
+    units: Units,
+) -> Result<Forecast, Error> {
+    let url = format!("{}/v1/forecast", base_url.trim_end_matches('/'));
+    let mut parameters = vec![
+        ("latitude", location.latitude.to_string()),
+        ("longitude", location.longitude.to_string()),
+        ("current", CURRENT_FIELDS.to_string()),
+        ("daily", DAILY_FIELDS.to_string()),
+        ("timezone", "auto".to_string()),
+        ("forecast_days", FORECAST_DAYS.to_string()),
+    ];
+    if units == Units::Imperial {
+        parameters.push(("temperature_unit", "fahrenheit".to_string()));
+        parameters.push(("wind_speed_unit", "mph".to_string()));
+        parameters.push(("precipitation_unit", "inch".to_string()));
+    }
+    let response: ForecastResponse = get_json(client, &url, &parameters)?;
+    Ok(response.into())
+}
+
+impl From<ForecastResponse> for Forecast {
+    fn from(response: ForecastResponse) -> Self {
+        let current = response.current;
+        let daily = response.daily;
+        // Columns shorter than the dates leave the days at their end without the value.
+        let value = |column: &[Option<f64>], index: usize| column.get(index).copied().flatten();
+        let days = daily
+            .time
+            .iter()
+            .enumerate()
+            .map(|(index, &date)| {
+                let weather_code = daily.weather_code.get(index).copied().flatten();
+                Day {
+                    date,
+                    weather_code,
+                    conditions: weather_code.map(describe),
+                    temperature_min: value(&daily.temperature_2m_min, index),
+                    temperature_max: value(&daily.temperature_2m_max, index),
+                    precipitation: value(&daily.precipitation_sum, index),
+                    precipitation_probability: value(&daily.precipitation_probability_max, index),
+                }
+            })
+            .collect();
+        Forecast {
+            units: UnitLabels {
+                temperature: response.current_units.temperature_2m,
+                wind_speed: response.current_units.wind_speed_10m,
+                precipitation: response.daily_units.precipitation_sum,
+            },
+            current: Current {
+                time: current.time,
+                temperature: current.temperature_2m,
+                feels_like: current.apparent_temperature,
+                humidity: current.relative_humidity_2m,
+                weather_code: current.weather_code,
+                conditions: describe(current.weather_code),
+                wind_speed: current.wind_speed_10m,
+                wind_direction: current.wind_direction_10m,
+            },
+            days,
+        }
+    }
+}
+
+/// What a WMO weather code, which the API gives the conditions as, stands for.
+pub fn describe(code: u8) -> &'static str {
+    match code {
+        0 => "Clear sky",
+        1 => "Mainly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 => "Fog",
+        48 => "Rime fog",
+        51 => "Light drizzle",
+        53 => "Drizzle",
+        55 => "Dense drizzle",
+        56 | 57 => "Freezing drizzle",
+        61 => "Slight rain",
+        63 => "Rain",
+        65 => "Heavy rain",
+        66 | 67 => "Freezing rain",
+        71 => "Slight snow",
+        73 => "Snow",
+        75 => "Heavy snow",
+        77 => "Snow grains",
+        80 => "Slight showers",
+        81 => "Showers",
+        82 => "Violent showers",
+        85 | 86 => "Snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// An answer of the API for Berlin, cut down to the fields that are asked for.
+    pub(crate) const RESPONSE: &str = r#"{"latitude":52.52,"longitude":13.419998,
+        "generationtime_ms":0.1,"utc_offset_seconds":7200,"timezone":"Europe/Berlin",
+        "timezone_abbreviation":"CEST","elevation":38.0,
+        "current_units":{"time":"iso8601","interval":"seconds","temperature_2m":"°C",
+            "relative_humidity_2m":"%","apparent_temperature":"°C","weather_code":"wmo code",
+            "wind_speed_10m":"km/h","wind_direction_10m":"°"},
+        "current":{"time":"2024-10-14T12:15","interval":900,"temperature_2m":14.2,
+            "relative_humidity_2m":71,"apparent_temperature":12.9,"weather_code":3,
+            "wind_speed_10m":11.5,"wind_direction_10m":236},
+        "daily_units":{"time":"iso8601","weather_code":"wmo code","temperature_2m_max":"°C",
+            "temperature_2m_min":"°C","precipitation_sum":"mm",
+            "precipitation_probability_max":"%"},
+        "daily":{"time":["2024-10-14","2024-10-15","2024-10-16"],"weather_code":[3,61,2],
+            "temperature_2m_max":[15.1,13.0,12.4],"temperature_2m_min":[8.3,9.1,6.0],
+            "precipitation_sum":[0.0,4.2,0.1],"precipitation_probability_max":[10,80,null]}}"#;
+
+    pub(crate) fn forecast() -> Forecast {
+        serde_json::from_str::<ForecastResponse>(RESPONSE)
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn converts_columns_to_days() {
+        let forecast = forecast();
+        assert_eq!(forecast.units.temperature, "°C");
+        assert_eq!(forecast.current.conditions, "Overcast");
+        assert_eq!(forecast.current.time.to_string(), "2024-10-14 12:15:00");
+        assert_eq!(forecast.days.len(), 3);
+        let day = &forecast.days[1];
+        assert_eq!(day.date.to_string(), "2024-10-15");
+        assert_eq!(day.conditions, Some("Slight rain"));
+        assert_eq!(
+            (day.temperature_min, day.temperature_max),
+            (Some(9.1), Some(13.0))
+        );
+        assert_eq!(forecast.days[2].precipitation_probability, None);
+    }
+}
@@ -0,0 +1,140 @@
+//! Finding the coordinates of cities by name, which the forecast API needs.
+
+use crate::get_json;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::{fmt, io::Error};
+
+pub const DEFAULT_URL: &str = "https://geocoding-api.open-meteo.com";
+
+/// A place found by the geocoding API, as it's kept in the cache and printed with `--json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub name: String,
+    /// The state or region, like `California`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country_code: Option<String>,
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+/// Names the place like `Springfield, Illinois, United States`, without a region named like the
+/// city, as in `Berlin, Germany`.
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        let admin1 = self
+            .admin1
+            .as_ref()
+            .filter(|admin1| !admin1.contains(&self.name));
+        for part in [admin1, self.country.as_ref()].into_iter().flatten() {
+            write!(f, ", {}", part)?;
+        }
+        Ok(())
+    }
+}
+
+/// A city to look for, like `Paris` or `Paris, US`, with the two letters of a country to choose
+/// between cities of the same name.
+#[derive(Debug, PartialEq)]
+pub struct Query {
+    pub name: String,
+    pub country_code: Option<String>,
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Query, String> {
+        let (name, country_code) = match text.rsplit_once(',') {
+            Some((name, code)) => {
+                let code = code.trim();
+                if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+                    return Err(format!(
+                        "invalid country code '{}', expected two letters like US",
+                        code
+                    ));
+                }
+                (name, Some(code.to_ascii_uppercase()))
+            }
+            None => (text, None),
+        };
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            return Err("the name of the city is empty".to_string());
+        }
+        Ok(Query { name, country_code })
+    }
+
+    /// The same for queries that only differ in case or spaces, like `new  york` and
+    /// `New York`, so they share a place in the cache.
+    pub fn cache_key(&self) -> String {
+        let name = self.name.to_lowercase();
+        match &self.country_code {
+            Some(code) => format!("{}, {}", name, code.to_lowercase()),
+            None => name,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    /// Left out, rather than empty, when nothing was found.
+    #[serde(default)]
+    results: Vec<Location>,
+}
+
+/// The place the API finds for the query, the one with the most people of those with the name.
+pub fn search(client: &Client, base_url: &str, query: &Query) -> Result<Option<Location>, Error> {
+    let url = format!("{}/v1/search", base_url.trim_end_matches('/'));
+    let mut parameters = vec![
+        ("name", query.name.clone()),
+        ("count", "1".to_string()),
+        ("language", "en".to_string()),
+        ("format", "json".to_string()),
+    ];
+    if let Some(code) = &query.country_code {
+        parameters.push(("countryCode", code.clone()));
+    }
+    let response: SearchResponse = get_json(client, &url, &parameters)?;
+    Ok(response.results.into_iter().next())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_queries() {
+        let query = Query::parse("  new   York , us").unwrap();
+        assert_eq!(query.name, "new York");
+        assert_eq!(query.country_code.as_deref(), Some("US"));
+        assert_eq!(query.cache_key(), "new york, us");
+        assert_eq!(Query::parse("Berlin").unwrap().cache_key(), "berlin");
+        assert!(Query::parse("Paris, France").is_err());
+        assert!(Query::parse(" , FR").is_err());
+    }
+
+    #[test]
+    fn reads_search_results() {
+        let response: SearchResponse = serde_json::from_str(
+            r#"{"results":[{"id":2950159,"name":"Berlin","latitude":52.52437,
+                "longitude":13.41053,"elevation":74.0,"feature_code":"PPLC","country_code":"DE",
+                "timezone":"Europe/Berlin","population":3426354,"country":"Germany",
+                "admin1":"Land Berlin"}],"generationtime_ms":0.9}"#,
+        )
+        .unwrap();
+        assert_eq!(response.results[0].to_string(), "Berlin, Germany");
+        assert_eq!(
+            response.results[0].timezone.as_deref(),
+            Some("Europe/Berlin")
+        );
+        let response: SearchResponse =
+            serde_json::from_str(r#"{"generationtime_ms":0.4}"#).unwrap();
+        assert!(response.results.is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+//! Looks up the weather of cities with the free APIs of [Open-Meteo](https://open-meteo.com/),
+//! which need no key.
+
+pub mod cache;
+pub mod forecast;
+pub mod geocoding;
+pub mod report;
+
+use reqwest::blocking::Client;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    io::{Error, ErrorKind},
+    time::Duration,
+};
+
+/// Long enough for slow connections, short enough not to hang on a dead one.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The body of the answers to requests the APIs refuse, like `{"error":true,"reason":"..."}`.
+#[derive(Deserialize)]
+struct ApiError {
+    reason: String,
+}
+
+/// A client for the APIs, which asks them to name this program in their logs.
+pub fn client() -> Result<Client, Error> {
+    Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(concat!("weather_cli/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(Error::other)
+}
+
+/// Gets `url` with the parameters of `query` and reads the JSON it answers.
+fn get_json<T: DeserializeOwned>(
+    client: &Client,
+    url: &str,
+    query: &[(&str, String)],
+) -> Result<T, Error> {
+    let response = client
+        .get(url)
+        .query(query)
+        .send()
+        .map_err(|error| Error::other(format!("couldn't reach Open-Meteo: {}", error)))?;
+    let status = response.status();
+    if !status.is_success() {
+        // The reasons say what was wrong, like coordinates out of range.
+        let message = match response.json::<ApiError>() {
+            Ok(error) => format!("{} answered {}: {}", url, status, error.reason),
+            Err(_) => format!("{} answered {}", url, status),
+        };
+        return Err(Error::other(message));
+    }
+    response.json().map_err(|error| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid answer from {}: {}", url, error),
+        )
+    })
+}
@@ -0,0 +1,105 @@
+use clap::Parser;
+use std::{
+    io::{Error, ErrorKind},
+    path::PathBuf,
+    process::ExitCode,
+};
+use weather_cli::{
+    cache::LocationCache,
+    forecast::{self, Units},
+    geocoding::{self, Location, Query},
+    report::{self, Report},
+};
+
+/// Prints the current weather and the forecast of the next days for a city.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The city, like Lisbon or "Portland, US" with the code of its country
+    #[arg(value_name = "city", required = true, num_args = 1..)]
+    city: Vec<String>,
+    /// Print JSON instead of text
+    #[arg(long)]
+    json: bool,
+    #[arg(long, value_enum, env = "WEATHER_UNITS", default_value_t = Units::Metric)]
+    units: Units,
+    /// Look the city up again instead of using the place found the last time
+    #[arg(long)]
+    refresh: bool,
+    /// The file the places of cities are kept in, `~/.cache/weather/locations.json` if not
+    /// given
+    #[arg(long, value_name = "path", env = "WEATHER_CACHE_FILE")]
+    cache_file: Option<PathBuf>,
+    /// The geocoding API to use, like a self-hosted one
+    #[arg(long, value_name = "url", env = "WEATHER_GEOCODING_URL", default_value = geocoding::DEFAULT_URL)]
+    geocoding_url: String,
+    /// The forecast API to use, like a self-hosted one
+    #[arg(long, value_name = "url", env = "WEATHER_FORECAST_URL", default_value = forecast::DEFAULT_URL)]
+    forecast_url: String,
+}
+
+fn main() -> ExitCode {
+    match run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    // Without quotes, `weather New York` is the same as `weather "New York"`.
+    let query = Query::parse(&args.city.join(" "))
+        .map_err(|message| Error::new(ErrorKind::InvalidInput, message))?;
+    let client = weather_cli::client()?;
+    let location = find_location(&args, &client, &query)?;
+    let forecast = forecast::fetch(&client, &args.forecast_url, &location, args.units)?;
+
+    if args.json {
+        let report = Report {
+            location: &location,
+            forecast: &forecast,
+        };
+        let json = serde_json::to_string_pretty(&report).map_err(Error::other)?;
+        println!("{}", json);
+    } else {
+        print!("{}", report::render(&location, &forecast));
+    }
+    Ok(())
+}
+
+/// The place of the query from the cache, or from the geocoding API, which is then cached.
+fn find_location(
+    args: &Args,
+    client: &reqwest::blocking::Client,
+    query: &Query,
+) -> Result<Location, Error> {
+    let cache_path = args.cache_file.clone().or_else(LocationCache::default_path);
+    let mut cache = match &cache_path {
+        Some(path) => Some(LocationCache::load(path)?),
+        None => None,
+    };
+    let key = query.cache_key();
+    if !args.refresh {
+        if let Some(location) = cache.as_ref().and_then(|cache| cache.get(&key)) {
+            return Ok(location.clone());
+        }
+    }
+
+    let Some(location) = geocoding::search(client, &args.geocoding_url, query)? else {
+        let message = match &query.country_code {
+            Some(code) => format!("no city named '{}' in {}", query.name, code),
+            None => format!("no city named '{}'", query.name),
+        };
+        return Err(Error::new(ErrorKind::NotFound, message));
+    };
+    if let Some(cache) = &mut cache {
+        cache.insert(key, location.clone());
+        // The forecast is still worth printing without the cache.
+        if let Err(error) = cache.save() {
+            eprintln!("warning: couldn't save the location cache: {}", error);
+        }
+    }
+    Ok(location)
+}
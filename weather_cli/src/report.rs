@@ -0,0 +1,139 @@
+//! Printing the weather of a place, for people or, with `--json`, for scripts.
+
+use crate::{
+    forecast::{Day, Forecast},
+    geocoding::Location,
+};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// What `--json` prints.
+#[derive(Serialize)]
+pub struct Report<'a> {
+    pub location: &'a Location,
+    #[serde(flatten)]
+    pub forecast: &'a Forecast,
+}
+
+const COMPASS_POINTS: [&str; 16] = [
+    "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW",
+    "NNW",
+];
+
+/// The weather now, then a line for every day, like:
+///
+/// ```text
+/// Berlin, Germany
+/// Overcast, 14 °C, feels like 13 °C (at 12:15)
+/// Humidity 71%, wind 12 km/h from the SW
+///
+/// Today       Overcast         8 to 15 °C  0.0 mm (10%)
+/// ```
+pub fn render(location: &Location, forecast: &Forecast) -> String {
+    let units = &forecast.units;
+    let current = &forecast.current;
+    let mut text = String::new();
+    // Writing to a `String` can't fail.
+    let _ = writeln!(text, "{}", location);
+    let _ = writeln!(
+        text,
+        "{}, {:.0} {temperature}, feels like {:.0} {temperature} (at {})",
+        current.conditions,
+        current.temperature,
+        current.feels_like,
+        current.time.format("%H:%M"),
+        temperature = units.temperature
+    );
+    let _ = writeln!(
+        text,
+        "Humidity {:.0}%, wind {:.0} {} from the {}",
+        current.humidity,
+        current.wind_speed,
+        units.wind_speed,
+        compass_point(current.wind_direction)
+    );
+    text.push('\n');
+
+    let today = current.time.date();
+    let conditions_width = forecast
+        .days
+        .iter()
+        .map(|day| conditions(day).len())
+        .max()
+        .unwrap_or_default();
+    for day in &forecast.days {
+        let label = match (day.date - today).num_days() {
+            0 => "Today".to_string(),
+            1 => "Tomorrow".to_string(),
+            _ => day.date.format("%a %-d %b").to_string(),
+        };
+        let temperatures = format!(
+            "{} to {} {}",
+            rounded(day.temperature_min),
+            rounded(day.temperature_max),
+            units.temperature
+        );
+        let mut precipitation = match day.precipitation {
+            Some(amount) => format!("{:.1} {}", amount, units.precipitation),
+            None => format!("? {}", units.precipitation),
+        };
+        if let Some(probability) = day.precipitation_probability {
+            let _ = write!(precipitation, " ({:.0}%)", probability);
+        }
+        let line = format!(
+            "{:<11} {:<conditions_width$}  {:>12}  {}",
+            label,
+            conditions(day),
+            temperatures,
+            precipitation
+        );
+        let _ = writeln!(text, "{}", line.trim_end());
+    }
+    text
+}
+
+fn conditions(day: &Day) -> &'static str {
+    day.conditions.unwrap_or("?")
+}
+
+fn rounded(value: Option<f64>) -> String {
+    value.map_or("?".to_string(), |value| format!("{:.0}", value))
+}
+
+/// The nearest of the 16 points of the compass, like `SW` for 236°.
+fn compass_point(degrees: f64) -> &'static str {
+    let index = (degrees.rem_euclid(360.0) / 22.5).round() as usize % COMPASS_POINTS.len();
+    COMPASS_POINTS[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forecast;
+
+    #[test]
+    fn renders_the_forecast() {
+        let location = Location {
+            name: "Berlin".to_string(),
+            admin1: Some("Land Berlin".to_string()),
+            country: Some("Germany".to_string()),
+            country_code: Some("DE".to_string()),
+            latitude: 52.52437,
+            longitude: 13.41053,
+            timezone: Some("Europe/Berlin".to_string()),
+        };
+        let text = render(&location, &forecast::tests::forecast());
+        let expected = "\
+Berlin, Germany
+Overcast, 14 °C, feels like 13 °C (at 12:15)
+Humidity 71%, wind 12 km/h from the SW
+
+Today       Overcast         8 to 15 °C  0.0 mm (10%)
+Tomorrow    Slight rain      9 to 13 °C  4.2 mm (80%)
+Wed 16 Oct  Partly cloudy    6 to 12 °C  0.1 mm
+";
+        assert_eq!(text, expected);
+        assert_eq!(compass_point(359.0), "N");
+        assert_eq!(compass_point(-90.0), "W");
+    }
+}
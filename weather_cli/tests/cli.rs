@@ -0,0 +1,123 @@
+//! Runs the `weather` binary against a fake of the Open-Meteo APIs, which answers canned JSON
+//! and keeps the paths it was asked for.
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::Path,
+    process::{Command, Output},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+const SEARCH_RESPONSE: &str = r#"{"results":[{"id":2267057,"name":"Lisbon","latitude":38.71667,
+    "longitude":-9.13333,"country_code":"PT","timezone":"Europe/Lisbon","country":"Portugal",
+    "admin1":"Lisbon"}]}"#;
+const FORECAST_RESPONSE: &str = r#"{"current_units":{"temperature_2m":"°C","wind_speed_10m":"km/h"},
+    "current":{"time":"2024-10-14T18:00","interval":900,"temperature_2m":21.4,
+        "relative_humidity_2m":64,"apparent_temperature":21.0,"weather_code":0,
+        "wind_speed_10m":17.3,"wind_direction_10m":315},
+    "daily_units":{"precipitation_sum":"mm"},
+    "daily":{"time":["2024-10-14","2024-10-15","2024-10-16"],"weather_code":[0,80,95],
+        "temperature_2m_max":[24.0,21.5,19.2],"temperature_2m_min":[15.2,16.0,14.8],
+        "precipitation_sum":[0.0,2.5,12.8],"precipitation_probability_max":[0,55,90]}}"#;
+
+/// Serves the fake APIs on a free port, returning its URL and the requests it got.
+fn start_api() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&requests);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            // The rest of the headers, up to the blank line.
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let target = request_line
+                .split(' ')
+                .nth(1)
+                .unwrap_or_default()
+                .to_string();
+            let (status, body) = if target.contains("name=Atlantis") {
+                ("200 OK", r#"{"generationtime_ms":0.3}"#)
+            } else if target.starts_with("/v1/search") {
+                ("200 OK", SEARCH_RESPONSE)
+            } else if target.starts_with("/v1/forecast") {
+                ("200 OK", FORECAST_RESPONSE)
+            } else {
+                ("404 Not Found", r#"{"error":true,"reason":"Not Found"}"#)
+            };
+            seen.lock().unwrap().push(target);
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    (url, requests)
+}
+
+fn weather(api_url: &str, cache_file: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_weather"))
+        .args(args)
+        .env("WEATHER_GEOCODING_URL", api_url)
+        .env("WEATHER_FORECAST_URL", api_url)
+        .env("WEATHER_CACHE_FILE", cache_file)
+        .env_remove("WEATHER_UNITS")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn prints_the_forecast_and_caches_the_location() {
+    let (url, requests) = start_api();
+    let dir = env::temp_dir().join(format!("weather-cli-test-{}", std::process::id()));
+    let cache_file = dir.join("locations.json");
+
+    let output = weather(&url, &cache_file, &["lisbon"]);
+    assert!(output.status.success(), "{:?}", output);
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.starts_with("Lisbon, Portugal\nClear sky, 21 °C"));
+    assert!(text.contains("wind 17 km/h from the NW"));
+    assert!(text.contains("Thunderstorm"));
+    {
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].starts_with("/v1/search?name=lisbon"));
+        assert!(requests[1].contains("latitude=38.71667&longitude=-9.13333"));
+    }
+
+    // The same city, written differently, is found in the cache.
+    let output = weather(
+        &url,
+        &cache_file,
+        &["LISBON", "--json", "--units", "imperial"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["location"]["country"], "Portugal");
+    assert_eq!(json["current"]["conditions"], "Clear sky");
+    assert_eq!(json["days"][2]["precipitation"], 12.8);
+    {
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].contains("temperature_unit=fahrenheit"));
+    }
+
+    let output = weather(&url, &cache_file, &["Atlantis"]);
+    assert!(!output.status.success());
+    let error = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(error, "error: no city named 'Atlantis'\n");
+    fs::remove_dir_all(&dir).unwrap();
+}